@@ -0,0 +1,187 @@
+mod validate;
+
+use phantom::{
+    app::{AssetGraph, Pak},
+    dependencies::{
+        anyhow::{bail, Context, Result},
+        env_logger,
+        image::RgbaImage,
+    },
+    render::render_thumbnail,
+    world::{asset_id, load_gltf, LightmapSettings, NavMesh, NavMeshSettings, World},
+};
+use std::path::Path;
+
+const USAGE: &str = "Usage: phantom-cli <command> [arguments]
+
+Commands:
+    import <model.gltf> <scene.phantom>       Converts a glTF model into a scene
+    export <model or scene> <output.glb>      Writes a scene or processed model out as glTF
+    validate <scene.phantom>...               Checks scenes for broken references
+    pack <directory> <output.pak>             Packs a directory of assets into a pak
+    thumbnail <model or scene> <output.png> [size]
+                                              Renders a thumbnail, 256 pixels square by default
+    bake-lightmaps <model or scene> <directory> [resolution]
+                                              Bakes the direct light on each mesh into a png,
+                                              128 pixels square by default
+    bake-navmesh <model or scene> <output.navmesh> [max slope in degrees]
+                                              Bakes the walkable surfaces, up to 45 degrees by default";
+
+const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let arguments = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match arguments[..] {
+        ["import", source, destination] => import(source, destination),
+        ["export", source, destination] => export(source, destination),
+        ["validate", ref scenes @ ..] if !scenes.is_empty() => validate_scenes(scenes),
+        ["pack", directory, destination] => pack(directory, destination),
+        ["thumbnail", source, destination] => {
+            thumbnail(source, destination, DEFAULT_THUMBNAIL_SIZE)
+        }
+        ["thumbnail", source, destination, size] => {
+            thumbnail(source, destination, size.parse().context("Invalid size")?)
+        }
+        ["bake-lightmaps", source, directory] => {
+            bake_lightmaps(source, directory, LightmapSettings::default())
+        }
+        ["bake-lightmaps", source, directory, resolution] => {
+            let settings = LightmapSettings {
+                resolution: resolution.parse().context("Invalid resolution")?,
+                ..Default::default()
+            };
+            bake_lightmaps(source, directory, settings)
+        }
+        ["bake-navmesh", source, destination] => {
+            bake_navmesh(source, destination, NavMeshSettings::default())
+        }
+        ["bake-navmesh", source, destination, max_slope] => {
+            let settings = NavMeshSettings {
+                max_slope: max_slope
+                    .parse::<f32>()
+                    .context("Invalid max slope")?
+                    .to_radians(),
+                ..Default::default()
+            };
+            bake_navmesh(source, destination, settings)
+        }
+        _ => bail!("{}", USAGE),
+    }
+}
+
+fn import(source: &str, destination: &str) -> Result<()> {
+    warn_about_dependencies(source)?;
+    let world = load_world(source)?;
+    world.save(destination)?;
+    // Both get sidecars, so references to them survive the files being moved
+    asset_id(source)?;
+    asset_id(destination)?;
+    println!("Imported {} to {}", source, destination);
+    Ok(())
+}
+
+/// Reports every missing or cyclic dependency, since the import stops at the first missing file
+fn warn_about_dependencies(source: &str) -> Result<()> {
+    let problems = AssetGraph::default().scan(source, |path| Ok(std::fs::read(path)?))?;
+    for problem in problems.iter() {
+        println!("Warning: {}", problem);
+    }
+    Ok(())
+}
+
+fn export(source: &str, destination: &str) -> Result<()> {
+    let world = load_world(source)?;
+    world.export_gltf(destination)?;
+    println!("Exported {} to {}", source, destination);
+    Ok(())
+}
+
+fn validate_scenes(scenes: &[&str]) -> Result<()> {
+    let mut number_of_problems = 0;
+    for scene in scenes {
+        let world = World::load(scene).context(format!("Failed to load {}", scene))?;
+        let problems = validate::validate(&world);
+        for problem in problems.iter() {
+            println!("{}: {}", scene, problem);
+        }
+        number_of_problems += problems.len();
+    }
+    if number_of_problems > 0 {
+        bail!("Found {} problems", number_of_problems);
+    }
+    println!("All scenes are valid");
+    Ok(())
+}
+
+fn pack(directory: &str, destination: &str) -> Result<()> {
+    let pak = Pak::from_directory(directory)?;
+    pak.save(destination)?;
+    println!("Packed {} files into {}", pak.paths().count(), destination);
+    Ok(())
+}
+
+fn thumbnail(source: &str, destination: &str, size: u32) -> Result<()> {
+    let mut world = load_world(source)?;
+    if is_model(source) {
+        world.frame_camera()?;
+    }
+    let frame = render_thumbnail(&world, size, size)?;
+    RgbaImage::from_raw(frame.width, frame.height, frame.pixels)
+        .context("The thumbnail has the wrong number of pixels!")?
+        .save(destination)?;
+    println!("Rendered {} to {}", source, destination);
+    Ok(())
+}
+
+fn bake_lightmaps(source: &str, directory: &str, settings: LightmapSettings) -> Result<()> {
+    let world = load_world(source)?;
+    if world.lights()?.is_empty() {
+        println!(
+            "Warning: {} has no lights, so its lightmaps will be black",
+            source
+        );
+    }
+    std::fs::create_dir_all(directory)?;
+    let lightmaps = world.bake_lightmaps(&settings)?;
+    // Names aren't unique, so each file is numbered too
+    for (index, lightmap) in lightmaps.iter().enumerate() {
+        let texture = &lightmap.texture;
+        let path = Path::new(directory).join(format!("{}_{}.png", index, lightmap.name));
+        RgbaImage::from_raw(texture.width, texture.height, texture.pixels.clone())
+            .context("The lightmap has the wrong number of pixels!")?
+            .save(&path)?;
+    }
+    println!("Baked {} lightmaps into {}", lightmaps.len(), directory);
+    Ok(())
+}
+
+fn bake_navmesh(source: &str, destination: &str, settings: NavMeshSettings) -> Result<()> {
+    let world = load_world(source)?;
+    let nav_mesh = NavMesh::bake(&world, &settings)?;
+    nav_mesh.save(destination)?;
+    println!(
+        "Baked a navmesh with {} triangles to {}",
+        nav_mesh.triangles.len(),
+        destination
+    );
+    Ok(())
+}
+
+fn is_model(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .map_or(false, |extension| extension == "gltf" || extension == "glb")
+}
+
+/// Models are loaded into a new world, everything else is expected to be a saved scene
+fn load_world(path: &str) -> Result<World> {
+    if !is_model(path) {
+        return World::load(path).context(format!("Failed to load {}", path));
+    }
+    let mut world = World::new()?;
+    load_gltf(path, &mut world).context(format!("Failed to import {}", path))?;
+    Ok(world)
+}
@@ -0,0 +1,94 @@
+use phantom::{
+    dependencies::legion::{EntityStore, IntoQuery},
+    world::{Entity, MeshRender, World},
+};
+
+/// Describes every broken reference in a world, such as meshes that were never loaded
+pub fn validate(world: &World) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if world.active_camera().is_err() {
+        problems.push("There is no enabled camera to render with".to_string());
+    }
+
+    for (graph_index, graph) in world.scene.graphs.iter().enumerate() {
+        graph
+            .walk(|node_index| {
+                let entity = graph[node_index];
+                if world.ecs.entry_ref(entity).is_err() {
+                    problems.push(format!(
+                        "Scenegraph {} references a missing entity {:?}",
+                        graph_index, entity
+                    ));
+                }
+                Ok(())
+            })
+            .ok();
+    }
+
+    let mut query = <(Entity, &MeshRender)>::query();
+    for (entity, mesh_render) in query.iter(&world.ecs) {
+        if !world.geometry.meshes.contains_key(&mesh_render.name) {
+            problems.push(format!(
+                "Entity {:?} renders a missing mesh '{}'",
+                entity, mesh_render.name
+            ));
+        }
+    }
+
+    let number_of_vertices = world.geometry.vertices.len();
+    let number_of_indices = world.geometry.indices.len();
+    for mesh in world.geometry.meshes.values() {
+        for (index, primitive) in mesh.primitives.iter().enumerate() {
+            if primitive.first_index + primitive.number_of_indices > number_of_indices
+                || primitive.first_vertex + primitive.number_of_vertices > number_of_vertices
+            {
+                problems.push(format!(
+                    "Primitive {} of mesh '{}' is outside of the world's geometry",
+                    index, mesh.name
+                ));
+            }
+            if let Some(material_index) = primitive.material_index {
+                if material_index >= world.materials.len() {
+                    problems.push(format!(
+                        "Primitive {} of mesh '{}' uses a missing material {}",
+                        index, mesh.name, material_index
+                    ));
+                }
+            }
+        }
+    }
+
+    for material in world.materials.iter() {
+        let textures = [
+            material.color_texture_index,
+            material.metallic_roughness_texture_index,
+            material.normal_texture_index,
+            material.occlusion_texture_index,
+            material.emissive_texture_index,
+            material.transmission_texture_index,
+        ];
+        for texture_index in textures {
+            if texture_index >= world.textures.len() as i32 {
+                problems.push(format!(
+                    "Material '{}' uses a missing texture {}",
+                    material.name, texture_index
+                ));
+            }
+        }
+    }
+
+    for (index, texture) in world.textures.iter().enumerate() {
+        let expected = (texture.bytes_per_row() * texture.height) as usize;
+        if texture.pixels.len() != expected {
+            problems.push(format!(
+                "Texture {} has {} bytes of pixels but its size needs {}",
+                index,
+                texture.pixels.len(),
+                expected
+            ));
+        }
+    }
+
+    problems
+}
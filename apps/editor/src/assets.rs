@@ -0,0 +1,326 @@
+use phantom::{
+    app::Resources,
+    dependencies::{
+        anyhow::{Context, Result},
+        egui::{
+            vec2, Align2, Button, Color32, Image, Rect, Response, ScrollArea, Sense, TextEdit,
+            TextStyle, TextureId, Ui,
+        },
+        image, log,
+    },
+    world::{load_gltf, World},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AssetKind {
+    Folder,
+    Model,
+    Texture,
+    Other,
+}
+
+impl AssetKind {
+    fn of(path: &Path) -> Self {
+        if path.is_dir() {
+            return Self::Folder;
+        }
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "gltf" | "glb" => Self::Model,
+            "png" | "jpg" | "jpeg" | "bmp" | "tga" => Self::Texture,
+            _ => Self::Other,
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        match self {
+            Self::Folder => "📁",
+            Self::Model => "📦",
+            Self::Texture => "🖼",
+            Self::Other => "📄",
+        }
+    }
+}
+
+struct Asset {
+    path: PathBuf,
+    name: String,
+    kind: AssetKind,
+}
+
+enum Thumbnail {
+    Ready(TextureId),
+    Failed,
+}
+
+/// What the browser did with the pointer this frame, for the placement tool to follow
+pub enum AssetDrag {
+    Started(PathBuf),
+    Released,
+}
+
+/// Browses the assets directory with thumbnails for models and textures.
+/// Models can be dragged into the viewport to place them.
+pub struct AssetBrowser {
+    root: PathBuf,
+    directory: PathBuf,
+    search: String,
+    assets: Vec<Asset>,
+    loaded: bool,
+    thumbnails: HashMap<PathBuf, Thumbnail>,
+
+    /// Thumbnails to free from the renderer on the next update
+    released: Vec<TextureId>,
+
+    dragging: Option<PathBuf>,
+}
+
+impl Default for AssetBrowser {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("assets"),
+            directory: PathBuf::from("assets"),
+            search: String::new(),
+            assets: Vec::new(),
+            loaded: false,
+            thumbnails: HashMap::new(),
+            released: Vec::new(),
+            dragging: None,
+        }
+    }
+}
+
+impl AssetBrowser {
+    const TILE_SIZE: f32 = 72.0;
+    const THUMBNAIL_SIZE: u32 = 128;
+
+    /// Renders at most this many thumbnails each frame so browsing stays responsive
+    const THUMBNAILS_PER_FRAME: usize = 1;
+
+    /// Rescans the directory when needed and renders thumbnails for visible assets
+    pub fn update(&mut self, resources: &mut Resources) {
+        for texture in self.released.drain(..) {
+            resources.renderer.free_gui_image(texture);
+        }
+        if !self.loaded {
+            if let Err(error) = self.scan() {
+                log::error!("Failed to scan assets: {}", error);
+            }
+            self.loaded = true;
+        }
+
+        let pending = self
+            .assets
+            .iter()
+            .filter(|asset| matches!(asset.kind, AssetKind::Model | AssetKind::Texture))
+            .filter(|asset| !self.thumbnails.contains_key(&asset.path))
+            .take(Self::THUMBNAILS_PER_FRAME)
+            .map(|asset| (asset.path.to_path_buf(), asset.kind))
+            .collect::<Vec<_>>();
+        for (path, kind) in pending {
+            let thumbnail = match Self::render_thumbnail(resources, &path, kind) {
+                Ok(Some(texture)) => Thumbnail::Ready(texture),
+                Ok(None) => Thumbnail::Failed,
+                Err(error) => {
+                    log::warn!(
+                        "Failed to render a thumbnail of {}: {}",
+                        path.display(),
+                        error
+                    );
+                    Thumbnail::Failed
+                }
+            };
+            self.thumbnails.insert(path, thumbnail);
+        }
+    }
+
+    fn render_thumbnail(
+        resources: &mut Resources,
+        path: &Path,
+        kind: AssetKind,
+    ) -> Result<Option<TextureId>> {
+        let image = match kind {
+            AssetKind::Texture => image::open(path)?
+                .thumbnail(Self::THUMBNAIL_SIZE, Self::THUMBNAIL_SIZE)
+                .into_rgba8(),
+            AssetKind::Model => {
+                let mut world = World::new()?;
+                load_gltf(path, &mut world)?;
+                world.frame_camera()?;
+                let frame = resources.renderer.render_thumbnail(
+                    &world,
+                    Self::THUMBNAIL_SIZE,
+                    Self::THUMBNAIL_SIZE,
+                )?;
+                image::RgbaImage::from_raw(frame.width, frame.height, frame.pixels)
+                    .context("The thumbnail has the wrong number of pixels!")?
+            }
+            AssetKind::Folder | AssetKind::Other => return Ok(None),
+        };
+        let (width, height) = image.dimensions();
+        Ok(resources
+            .renderer
+            .create_gui_image(width, height, image.as_raw()))
+    }
+
+    /// Lists the current directory, or every asset beneath the root that matches the search
+    fn scan(&mut self) -> Result<()> {
+        self.assets.clear();
+        if !self.directory.exists() {
+            return Ok(());
+        }
+        let search = self.search.to_lowercase();
+        let mut paths = Vec::new();
+        if search.is_empty() {
+            for entry in fs::read_dir(&self.directory)? {
+                paths.push(entry?.path());
+            }
+        } else {
+            collect_files(&self.root, &mut paths)?;
+        }
+
+        self.assets = paths
+            .into_iter()
+            .map(|path| Asset {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                kind: AssetKind::of(&path),
+                path,
+            })
+            .filter(|asset| search.is_empty() || asset.name.to_lowercase().contains(&search))
+            .collect();
+
+        // Folders first, then alphabetically
+        self.assets.sort_by(|a, b| {
+            (a.kind != AssetKind::Folder, a.name.to_lowercase())
+                .cmp(&(b.kind != AssetKind::Folder, b.name.to_lowercase()))
+        });
+        Ok(())
+    }
+
+    fn open_directory(&mut self, directory: PathBuf) {
+        self.directory = directory;
+        self.search.clear();
+        self.loaded = false;
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<AssetDrag> {
+        ui.horizontal(|ui| {
+            ui.heading("Assets");
+            let at_root = self.directory == self.root;
+            if ui.add_enabled(!at_root, Button::new("⬆")).clicked() {
+                if let Some(parent) = self.directory.parent().map(Path::to_path_buf) {
+                    self.open_directory(parent);
+                }
+            }
+            ui.label(self.directory.display().to_string());
+            ui.separator();
+            let search = ui.add(TextEdit::singleline(&mut self.search).hint_text("Search"));
+            if search.changed() {
+                self.loaded = false;
+            }
+            if ui.button("Refresh").clicked() {
+                self.loaded = false;
+                self.released
+                    .extend(
+                        self.thumbnails
+                            .drain()
+                            .filter_map(|(_, thumbnail)| match thumbnail {
+                                Thumbnail::Ready(texture) => Some(texture),
+                                Thumbnail::Failed => None,
+                            }),
+                    );
+            }
+        });
+        ui.separator();
+
+        let mut drag = None;
+        let mut opened = None;
+        ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for asset in self.assets.iter() {
+                    let response = self.tile_ui(ui, asset);
+                    if asset.kind == AssetKind::Folder && response.double_clicked() {
+                        opened = Some(asset.path.to_path_buf());
+                    }
+                    if asset.kind == AssetKind::Model {
+                        if response.drag_started() {
+                            self.dragging = Some(asset.path.to_path_buf());
+                            drag = Some(AssetDrag::Started(asset.path.to_path_buf()));
+                        }
+                        if response.drag_released() && self.dragging.take().is_some() {
+                            drag = Some(AssetDrag::Released);
+                        }
+                    }
+                    response.on_hover_text(asset.path.display().to_string());
+                }
+            });
+        });
+        if let Some(directory) = opened {
+            self.open_directory(directory);
+        }
+        drag
+    }
+
+    fn tile_ui(&self, ui: &mut Ui, asset: &Asset) -> Response {
+        let size = vec2(Self::TILE_SIZE, Self::TILE_SIZE + 16.0);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        if response.hovered() {
+            ui.painter()
+                .rect_filled(rect, 4.0, ui.visuals().widgets.hovered.bg_fill);
+        }
+
+        let image_rect =
+            Rect::from_min_size(rect.min, vec2(Self::TILE_SIZE, Self::TILE_SIZE)).shrink(4.0);
+        match self.thumbnails.get(&asset.path) {
+            Some(Thumbnail::Ready(texture)) => {
+                Image::new(*texture, image_rect.size()).paint_at(ui, image_rect)
+            }
+            _ => {
+                ui.painter().text(
+                    image_rect.center(),
+                    Align2::CENTER_CENTER,
+                    asset.kind.icon(),
+                    TextStyle::Heading,
+                    ui.visuals().text_color(),
+                );
+            }
+        }
+
+        // Long names are cut to fit the tile
+        let mut name = asset.name.to_string();
+        if name.chars().count() > 12 {
+            name = format!("{}…", name.chars().take(11).collect::<String>());
+        }
+        ui.painter().text(
+            rect.center_bottom() - vec2(0.0, 8.0),
+            Align2::CENTER_CENTER,
+            name,
+            TextStyle::Small,
+            Color32::from_gray(200),
+        );
+        response
+    }
+}
+
+fn collect_files(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
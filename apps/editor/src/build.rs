@@ -0,0 +1,316 @@
+use crate::measure::Annotation;
+use phantom::{
+    app::Pak,
+    dependencies::{
+        anyhow::{anyhow, bail, Context, Error, Result},
+        egui::{ComboBox, CtxRef, Window},
+        legion::IntoQuery,
+        log,
+    },
+    world::{Entity, World},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildPlatform {
+    Windows,
+    Linux,
+    MacOs,
+}
+
+impl BuildPlatform {
+    pub const ALL: [BuildPlatform; 3] = [Self::Windows, Self::Linux, Self::MacOs];
+
+    pub fn host() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Windows => "windows",
+            Self::Linux => "linux",
+            Self::MacOs => "macos",
+        }
+    }
+
+    fn target_triple(&self) -> &'static str {
+        match self {
+            Self::Windows => "x86_64-pc-windows-msvc",
+            Self::Linux => "x86_64-unknown-linux-gnu",
+            Self::MacOs => "x86_64-apple-darwin",
+        }
+    }
+
+    fn executable_name(&self, game: &str) -> String {
+        match self {
+            Self::Windows => format!("{}.exe", game),
+            _ => game.to_string(),
+        }
+    }
+
+    /// The directory the executable and its pak are placed in, inside the platform folder
+    fn executable_directory(&self, game: &str) -> PathBuf {
+        match self {
+            Self::MacOs => Path::new(&format!("{}.app", game))
+                .join("Contents")
+                .join("MacOS"),
+            _ => PathBuf::new(),
+        }
+    }
+}
+
+impl std::str::FromStr for BuildPlatform {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|platform| platform.name() == name)
+            .copied()
+            .context(format!("Unknown build platform: {}", name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildSettings {
+    /// The cargo package of the game
+    pub game: String,
+    pub platform: BuildPlatform,
+
+    /// A prebuilt runtime to ship instead of compiling the game
+    pub runtime: Option<PathBuf>,
+    pub assets: PathBuf,
+    pub output: PathBuf,
+}
+
+impl Default for BuildSettings {
+    fn default() -> Self {
+        Self {
+            game: String::new(),
+            platform: BuildPlatform::host(),
+            runtime: None,
+            assets: PathBuf::from("assets"),
+            output: PathBuf::from("build"),
+        }
+    }
+}
+
+impl BuildSettings {
+    /// Parses `--game <package> --platform <name> --runtime <path> --assets <dir> --output <dir>`
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut settings = Self::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .context(format!("Missing a value for {}", flag))?;
+            match flag.as_str() {
+                "--game" => settings.game = value.to_string(),
+                "--platform" => settings.platform = value.parse()?,
+                "--runtime" => settings.runtime = Some(PathBuf::from(value)),
+                "--assets" => settings.assets = PathBuf::from(value),
+                "--output" => settings.output = PathBuf::from(value),
+                _ => bail!("Unknown build option: {}", flag),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Packages a release of the game into `<output>/<platform>`, returning that directory
+pub fn build(settings: &BuildSettings) -> Result<PathBuf> {
+    if settings.game.is_empty() && settings.runtime.is_none() {
+        bail!("A game package or a prebuilt runtime is required to make a build!");
+    }
+
+    let platform_directory = settings.output.join(settings.platform.name());
+    if platform_directory.exists() {
+        fs::remove_dir_all(&platform_directory)?;
+    }
+    let game = if settings.game.is_empty() {
+        "game"
+    } else {
+        settings.game.as_str()
+    };
+    let executable_directory =
+        platform_directory.join(settings.platform.executable_directory(game));
+    fs::create_dir_all(&executable_directory)?;
+
+    log::info!("Building {} for {}", game, settings.platform.name());
+    let executable = match settings.runtime.as_ref() {
+        Some(runtime) => runtime.to_path_buf(),
+        None => compile(game, settings.platform)?,
+    };
+    fs::copy(
+        &executable,
+        executable_directory.join(settings.platform.executable_name(game)),
+    )
+    .context(format!("Failed to copy {}", executable.display()))?;
+
+    log::info!("Packing {}", settings.assets.display());
+    let pak = Pak::from_directory_with(&settings.assets, |path, bytes| {
+        // Scenes are stripped of editor-only data when packed
+        if path
+            .extension()
+            .map_or(false, |extension| extension == World::SCENE_EXTENSION)
+        {
+            let mut world = World::from_scene_bytes(&bytes)?;
+            strip_editor_entities(&mut world)?;
+            return Ok(Some(world.as_scene_bytes()?));
+        }
+        Ok(Some(bytes))
+    })?;
+    pak.save(executable_directory.join(Pak::DEFAULT_FILE_NAME))?;
+
+    if settings.platform == BuildPlatform::MacOs {
+        write_info_plist(&platform_directory.join(format!("{}.app", game)), game)?;
+    }
+
+    log::info!("Build finished: {}", platform_directory.display());
+    Ok(platform_directory)
+}
+
+fn compile(game: &str, platform: BuildPlatform) -> Result<PathBuf> {
+    let mut command = Command::new("cargo");
+    command.args(["build", "--release", "--package", game]);
+    let mut target_directory = PathBuf::from("target");
+    if platform != BuildPlatform::host() {
+        command.args(["--target", platform.target_triple()]);
+        target_directory = target_directory.join(platform.target_triple());
+    }
+    if !command.status()?.success() {
+        bail!("Failed to compile {}!", game);
+    }
+    Ok(target_directory
+        .join("release")
+        .join(platform.executable_name(game)))
+}
+
+/// Removes entities that only exist to help while editing, such as annotations
+fn strip_editor_entities(world: &mut World) -> Result<()> {
+    let mut query = <(Entity, &Annotation)>::query();
+    let entities = query
+        .iter(&world.ecs)
+        .map(|(entity, _)| *entity)
+        .collect::<Vec<_>>();
+    for entity in entities {
+        world.remove_entity(entity)?;
+    }
+    Ok(())
+}
+
+fn write_info_plist(bundle: &Path, game: &str) -> Result<()> {
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleName</key>
+    <string>{0}</string>
+    <key>CFBundleExecutable</key>
+    <string>{0}</string>
+    <key>CFBundleIdentifier</key>
+    <string>com.phantom.{0}</string>
+    <key>CFBundlePackageType</key>
+    <string>APPL</string>
+</dict>
+</plist>
+"#,
+        game
+    );
+    Ok(fs::write(
+        bundle.join("Contents").join("Info.plist"),
+        plist,
+    )?)
+}
+
+/// Runs builds in the background so the editor stays responsive while the game compiles
+#[derive(Default)]
+pub struct BuildWindow {
+    pub open: bool,
+    settings: BuildSettings,
+    runtime: String,
+    running: Option<Receiver<Result<PathBuf>>>,
+    status: String,
+}
+
+impl BuildWindow {
+    pub fn ui(&mut self, ctx: &CtxRef) {
+        self.poll();
+
+        let mut start = false;
+        let mut open = self.open;
+        Window::new("Build").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Game Package");
+                ui.text_edit_singleline(&mut self.settings.game);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Runtime");
+                ui.text_edit_singleline(&mut self.runtime)
+                    .on_hover_text("A prebuilt executable to ship instead of compiling the game");
+            });
+            ComboBox::from_label("Platform")
+                .selected_text(self.settings.platform.name())
+                .show_ui(ui, |ui| {
+                    for platform in BuildPlatform::ALL {
+                        ui.selectable_value(&mut self.settings.platform, platform, platform.name());
+                    }
+                });
+            ui.separator();
+            ui.add_enabled_ui(self.running.is_none(), |ui| {
+                start = ui.button("Build").clicked();
+            });
+            ui.label(&self.status);
+        });
+        self.open = open;
+
+        if start {
+            self.start();
+        }
+    }
+
+    fn start(&mut self) {
+        let mut settings = self.settings.clone();
+        settings.runtime = match self.runtime.trim() {
+            "" => None,
+            runtime => Some(PathBuf::from(runtime)),
+        };
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let _ = sender.send(build(&settings));
+        });
+        self.running = Some(receiver);
+        self.status = "Building...".to_string();
+    }
+
+    fn poll(&mut self) {
+        let result = match self.running.as_ref().map(|receiver| receiver.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(TryRecvError::Disconnected)) => {
+                Err(anyhow!("The build thread stopped unexpectedly!"))
+            }
+            _ => return,
+        };
+        self.running = None;
+        self.status = match result {
+            Ok(directory) => format!("Built to {}", directory.display()),
+            Err(error) => {
+                log::error!("Build failed: {}", error);
+                format!("Build failed: {}", error)
+            }
+        };
+    }
+}
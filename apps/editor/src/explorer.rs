@@ -0,0 +1,306 @@
+use crate::{entity_name, history::History};
+use phantom::{
+    dependencies::{
+        anyhow::{Context, Result},
+        egui::{
+            vec2, Align2, Button, Color32, Id, Key, Label, LayerId, Order, Rect, Sense, TextEdit,
+            TextStyle, Ui,
+        },
+        log,
+        petgraph::graph::NodeIndex,
+    },
+    world::{Entity, Name, SceneGraph, World},
+};
+use std::collections::HashSet;
+
+/// Changes picked in the tree, applied once it's done borrowing the world
+enum ExplorerAction {
+    Select(Entity),
+    ToggleEnabled(Entity),
+    StartRename(Entity),
+    Rename(Entity, String),
+    Reparent(Entity, Option<Entity>),
+    Delete(Entity),
+}
+
+/// A tree of the world's scenegraphs for selecting, renaming, reparenting and deleting entities
+#[derive(Default)]
+pub struct SceneExplorer {
+    expanded: HashSet<Entity>,
+    renaming: Option<(Entity, String)>,
+    dragging: Option<Entity>,
+}
+
+impl SceneExplorer {
+    const ARROW_WIDTH: f32 = 16.0;
+
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        world: &mut World,
+        selected: &mut Option<Entity>,
+        history: &mut History,
+    ) {
+        let mut actions = Vec::new();
+
+        ui.horizontal(|ui| {
+            let entity = *selected;
+            ui.add_enabled_ui(entity.is_some(), |ui| {
+                if ui.button("Rename").clicked() {
+                    actions.extend(entity.map(ExplorerAction::StartRename));
+                }
+                if ui.button("Delete").clicked() {
+                    actions.extend(entity.map(ExplorerAction::Delete));
+                }
+            });
+        });
+        ui.separator();
+
+        let mut drop_targets = Vec::new();
+        for graph in world.scene.graphs.iter() {
+            for root_index in graph.root_nodes() {
+                self.node_ui(
+                    ui,
+                    world,
+                    graph,
+                    root_index,
+                    *selected,
+                    &mut actions,
+                    &mut drop_targets,
+                );
+            }
+        }
+
+        // Dropping an entity here moves it to the root of its scenegraph
+        let root_response = ui.add(
+            Label::new("Drop here to unparent")
+                .text_style(TextStyle::Small)
+                .weak()
+                .sense(Sense::hover()),
+        );
+
+        if let Some(entity) = self.dragging {
+            self.drag_ui(
+                ui,
+                world,
+                entity,
+                root_response.rect,
+                &drop_targets,
+                &mut actions,
+            );
+        }
+
+        let explorer_focused = ui.ui_contains_pointer() && !ui.ctx().wants_keyboard_input();
+        if let (Some(entity), true) = (*selected, explorer_focused) {
+            if ui.input().key_pressed(Key::Delete) {
+                actions.push(ExplorerAction::Delete(entity));
+            }
+        }
+
+        for action in actions {
+            self.apply(world, selected, history, action);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn node_ui(
+        &mut self,
+        ui: &mut Ui,
+        world: &World,
+        graph: &SceneGraph,
+        node_index: NodeIndex,
+        selected: Option<Entity>,
+        actions: &mut Vec<ExplorerAction>,
+        drop_targets: &mut Vec<(Entity, Rect)>,
+    ) {
+        let entity = graph[node_index];
+        let children = graph.children(node_index);
+        let expanded = self.expanded.contains(&entity);
+        let row = ui.horizontal(|ui| {
+            if children.is_empty() {
+                ui.add_space(Self::ARROW_WIDTH);
+            } else if ui
+                .add_sized(
+                    [Self::ARROW_WIDTH, 0.0],
+                    Button::new(if expanded { "▼" } else { "▶" }).frame(false),
+                )
+                .clicked()
+            {
+                if expanded {
+                    self.expanded.remove(&entity);
+                } else {
+                    self.expanded.insert(entity);
+                }
+            }
+
+            if ui
+                .selectable_label(world.is_enabled(entity), "👁")
+                .on_hover_text("Toggle visibility")
+                .clicked()
+            {
+                actions.push(ExplorerAction::ToggleEnabled(entity));
+            }
+
+            match self.renaming.as_mut() {
+                Some((renaming, name)) if *renaming == entity => {
+                    let response = ui.add(TextEdit::singleline(name).desired_width(120.0));
+                    response.request_focus();
+                    if response.lost_focus() {
+                        if ui.input().key_pressed(Key::Escape) {
+                            self.renaming = None;
+                        } else {
+                            actions.push(ExplorerAction::Rename(entity, name.to_string()));
+                        }
+                    }
+                }
+                _ => self.entity_label(ui, world, entity, selected, actions),
+            }
+        });
+        drop_targets.push((entity, row.response.rect));
+
+        if expanded && !children.is_empty() {
+            ui.indent(entity, |ui| {
+                for child_index in children {
+                    self.node_ui(
+                        ui,
+                        world,
+                        graph,
+                        child_index,
+                        selected,
+                        actions,
+                        drop_targets,
+                    );
+                }
+            });
+        }
+    }
+
+    fn entity_label(
+        &mut self,
+        ui: &mut Ui,
+        world: &World,
+        entity: Entity,
+        selected: Option<Entity>,
+        actions: &mut Vec<ExplorerAction>,
+    ) {
+        let response = ui
+            .selectable_label(selected == Some(entity), entity_name(world, entity))
+            .interact(Sense::click_and_drag());
+        if response.double_clicked() {
+            actions.push(ExplorerAction::StartRename(entity));
+        } else if response.clicked() {
+            actions.push(ExplorerAction::Select(entity));
+        }
+        if response.drag_started() {
+            self.dragging = Some(entity);
+        }
+    }
+
+    /// Follows the pointer with the dragged entity's name and reparents it where it's dropped
+    fn drag_ui(
+        &mut self,
+        ui: &mut Ui,
+        world: &World,
+        entity: Entity,
+        root_rect: Rect,
+        drop_targets: &[(Entity, Rect)],
+        actions: &mut Vec<ExplorerAction>,
+    ) {
+        let (pointer, released) = {
+            let input = ui.input();
+            (input.pointer.hover_pos(), input.pointer.any_released())
+        };
+        let pointer = match pointer {
+            Some(pointer) => pointer,
+            None => {
+                self.dragging = None;
+                return;
+            }
+        };
+        let target = drop_targets
+            .iter()
+            .find(|(target, rect)| *target != entity && rect.contains(pointer))
+            .map(|(target, _)| *target);
+
+        if let Some((_, rect)) = drop_targets
+            .iter()
+            .find(|(candidate, _)| Some(*candidate) == target)
+        {
+            ui.painter()
+                .rect_stroke(*rect, 2.0, (1.0, Color32::LIGHT_BLUE));
+        }
+        ui.ctx()
+            .layer_painter(LayerId::new(Order::Tooltip, Id::new("scene_explorer_drag")))
+            .text(
+                pointer + vec2(12.0, 0.0),
+                Align2::LEFT_CENTER,
+                entity_name(world, entity),
+                TextStyle::Body,
+                Color32::WHITE,
+            );
+
+        if !released {
+            return;
+        }
+        self.dragging = None;
+        if let Some(target) = target {
+            actions.push(ExplorerAction::Reparent(entity, Some(target)));
+        } else if root_rect.contains(pointer) {
+            actions.push(ExplorerAction::Reparent(entity, None));
+        }
+    }
+
+    fn apply(
+        &mut self,
+        world: &mut World,
+        selected: &mut Option<Entity>,
+        history: &mut History,
+        action: ExplorerAction,
+    ) {
+        let result = match action {
+            ExplorerAction::Select(entity) => {
+                *selected = Some(entity);
+                Ok(())
+            }
+            ExplorerAction::ToggleEnabled(entity) => {
+                let enabled = world.is_enabled(entity);
+                history.edit_entities("Toggle Visibility", world, |world| {
+                    world.set_enabled(entity, !enabled)
+                })
+            }
+            ExplorerAction::StartRename(entity) => {
+                self.renaming = Some((entity, entity_name(world, entity)));
+                Ok(())
+            }
+            ExplorerAction::Rename(entity, name) => {
+                self.renaming = None;
+                history.edit_entities("Rename", world, |world| rename(world, entity, name))
+            }
+            ExplorerAction::Reparent(entity, parent) => {
+                history.edit_entities("Reparent", world, |world| world.reparent(entity, parent))
+            }
+            ExplorerAction::Delete(entity) => {
+                if self.renaming.as_ref().map(|(renaming, _)| *renaming) == Some(entity) {
+                    self.renaming = None;
+                }
+                let removed = history.edit_entities("Delete", world, |world| world.despawn(entity));
+                if selected.map_or(false, |selected| !world.ecs.contains(selected)) {
+                    *selected = None;
+                }
+                removed
+            }
+        };
+        if let Err(error) = result {
+            log::error!("Scene explorer action failed: {}", error);
+        }
+    }
+}
+
+fn rename(world: &mut World, entity: Entity, name: String) -> Result<()> {
+    let mut entry = world.ecs.entry(entity).context("Failed to find entity!")?;
+    match entry.get_component_mut::<Name>() {
+        Ok(existing) => existing.0 = name,
+        Err(_) => entry.add_component(Name(name)),
+    }
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use phantom::{
+    app::Resources,
+    dependencies::{
+        anyhow::Result,
+        egui::{
+            pos2, Color32, CtxRef, Grid, Image, Rect, ScrollArea, Sense, Slider, Stroke, TextureId,
+            Ui, Window,
+        },
+        image::{self, RgbaImage},
+        log,
+    },
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A saved capture and the renderer settings it was taken with
+struct Screenshot {
+    name: String,
+    image: RgbaImage,
+    settings: Vec<(String, String)>,
+    texture: Option<TextureId>,
+}
+
+/// Lists captured screenshots and compares two of them with a slider
+pub struct ScreenshotGallery {
+    pub open: bool,
+    directory: PathBuf,
+    screenshots: Vec<Screenshot>,
+    loaded: bool,
+    selected: Option<usize>,
+    compared: Option<usize>,
+    split: f32,
+}
+
+impl Default for ScreenshotGallery {
+    fn default() -> Self {
+        Self {
+            open: false,
+            directory: PathBuf::from("screenshots"),
+            screenshots: Vec::new(),
+            loaded: false,
+            selected: None,
+            compared: None,
+            split: 0.5,
+        }
+    }
+}
+
+impl ScreenshotGallery {
+    pub fn capture(&mut self, resources: &mut Resources) {
+        resources.recorder.request_screenshot();
+    }
+
+    /// Saves new captures and uploads screenshot textures for the gui
+    pub fn update(&mut self, resources: &mut Resources) -> Result<()> {
+        if self.open && !self.loaded {
+            self.load()?;
+            self.loaded = true;
+        }
+
+        if let Some(image) = resources.recorder.take_screenshot() {
+            let settings = resources.renderer.settings();
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            let name = format!("screenshot_{}", timestamp);
+            self.save(&name, &image, &settings)?;
+            self.screenshots.push(Screenshot {
+                name,
+                image,
+                settings,
+                texture: None,
+            });
+            self.selected = Some(self.screenshots.len() - 1);
+        }
+
+        if self.open {
+            for screenshot in self.screenshots.iter_mut() {
+                if screenshot.texture.is_none() {
+                    let (width, height) = screenshot.image.dimensions();
+                    screenshot.texture = resources.renderer.create_gui_image(
+                        width,
+                        height,
+                        screenshot.image.as_raw(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ui(&mut self, ctx: &CtxRef) {
+        let mut open = self.open;
+        Window::new("Screenshots")
+            .open(&mut open)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_max_width(200.0);
+                        self.list_ui(ui);
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        self.comparison_ui(ui);
+                    });
+                });
+            });
+        self.open = open;
+    }
+
+    fn list_ui(&mut self, ui: &mut Ui) {
+        ui.label("Click to view, right click to compare");
+        ScrollArea::vertical().show(ui, |ui| {
+            for (index, screenshot) in self.screenshots.iter().enumerate() {
+                let label = match (self.selected == Some(index), self.compared == Some(index)) {
+                    (_, true) => format!("{} (B)", screenshot.name),
+                    (true, _) => format!("{} (A)", screenshot.name),
+                    _ => screenshot.name.to_string(),
+                };
+                let response = ui.selectable_label(
+                    self.selected == Some(index) || self.compared == Some(index),
+                    label,
+                );
+                if response.clicked() {
+                    self.selected = Some(index);
+                }
+                if response.secondary_clicked() {
+                    self.compared = if self.compared == Some(index) {
+                        None
+                    } else {
+                        Some(index)
+                    };
+                }
+            }
+        });
+    }
+
+    fn comparison_ui(&mut self, ui: &mut Ui) {
+        let selected = match self.selected.and_then(|index| self.screenshots.get(index)) {
+            Some(screenshot) => screenshot,
+            None => {
+                ui.label("No screenshot selected");
+                return;
+            }
+        };
+        let compared = self.compared.and_then(|index| self.screenshots.get(index));
+
+        let (width, height) = selected.image.dimensions();
+        let display_width = ui.available_width().min(width as f32).max(1.0);
+        let size = [display_width, display_width * height as f32 / width as f32];
+        let (rect, _response) = ui.allocate_exact_size(size.into(), Sense::hover());
+
+        if let Some(texture) = selected.texture {
+            Image::new(texture, size).paint_at(ui, rect);
+        }
+
+        if let Some(texture) = compared.and_then(|compared| compared.texture) {
+            let split = rect.min.x + rect.width() * self.split;
+            Image::new(texture, size)
+                .uv(Rect::from_min_max(pos2(self.split, 0.0), pos2(1.0, 1.0)))
+                .paint_at(ui, Rect::from_min_max(pos2(split, rect.min.y), rect.max));
+            ui.painter().line_segment(
+                [pos2(split, rect.min.y), pos2(split, rect.max.y)],
+                Stroke::new(2.0, Color32::WHITE),
+            );
+            ui.add(Slider::new(&mut self.split, 0.0..=1.0).text("A / B"));
+        }
+
+        ui.separator();
+        Grid::new("screenshot_settings")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Setting");
+                ui.label("A");
+                if compared.is_some() {
+                    ui.label("B");
+                }
+                ui.end_row();
+                for (key, value) in selected.settings.iter() {
+                    ui.label(key);
+                    ui.label(value);
+                    if let Some(compared) = compared {
+                        let other = compared
+                            .settings
+                            .iter()
+                            .find(|(other_key, _)| other_key == key)
+                            .map(|(_, value)| value.to_string())
+                            .unwrap_or_default();
+                        if other == *value {
+                            ui.label(other);
+                        } else {
+                            ui.colored_label(Color32::YELLOW, other);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn save(&self, name: &str, image: &RgbaImage, settings: &[(String, String)]) -> Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        image.save(self.directory.join(format!("{}.png", name)))?;
+        let metadata = settings
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(self.directory.join(format!("{}.txt", name)), metadata)?;
+        Ok(())
+    }
+
+    /// Loads screenshots captured in previous sessions
+    fn load(&mut self) -> Result<()> {
+        if !self.directory.exists() {
+            return Ok(());
+        }
+        let mut paths = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "png")
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if self
+                .screenshots
+                .iter()
+                .any(|screenshot| screenshot.name == name)
+            {
+                continue;
+            }
+            let image = match image::open(&path) {
+                Ok(image) => image.into_rgba8(),
+                Err(error) => {
+                    log::warn!("Failed to load screenshot {}: {}", path.display(), error);
+                    continue;
+                }
+            };
+            self.screenshots.push(Screenshot {
+                settings: load_settings(&path.with_extension("txt")),
+                name,
+                image,
+                texture: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn load_settings(path: &Path) -> Vec<(String, String)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            line.split_once(": ")
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,512 @@
+use phantom::{
+    dependencies::{
+        anyhow::Result,
+        egui::{
+            pos2, Align2, Color32, CtxRef, CursorIcon, LayerId, Painter, Pos2, Rect, Stroke,
+            TextStyle,
+        },
+        legion::IntoQuery,
+        nalgebra_glm as glm,
+        rapier3d::geometry::Ray,
+    },
+    world::{
+        AudioSource, Camera, Entity, Light, LightKind, PickPrecision, Projection, Transform, World,
+    },
+};
+use std::{cmp::Ordering, f32::consts::TAU};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GizmoKind {
+    Light,
+    Camera,
+    AudioSource,
+}
+
+impl GizmoKind {
+    fn icon(&self) -> &'static str {
+        match self {
+            GizmoKind::Light => "💡",
+            GizmoKind::Camera => "🎥",
+            GizmoKind::AudioSource => "🔊",
+        }
+    }
+
+    fn color(&self) -> Color32 {
+        match self {
+            GizmoKind::Light => Color32::YELLOW,
+            GizmoKind::Camera => Color32::WHITE,
+            GizmoKind::AudioSource => Color32::from_rgb(140, 180, 255),
+        }
+    }
+}
+
+struct Gizmo {
+    entity: Entity,
+    kind: GizmoKind,
+    position: glm::Vec3,
+
+    /// Radius of the light range or audio attenuation sphere
+    range: Option<f32>,
+
+    /// Camera frustum edges and light directions
+    lines: Vec<(glm::Vec3, glm::Vec3)>,
+}
+
+/// The part of the window the scene is rendered in, in points,
+/// along with the ray from the active camera through the cursor
+#[derive(Copy, Clone)]
+pub struct SceneViewport {
+    pub rect: Rect,
+    pub cursor_ray: Option<Ray>,
+}
+
+impl SceneViewport {
+    pub fn aspect_ratio(&self) -> f32 {
+        self.rect.width() / self.rect.height().max(1.0)
+    }
+}
+
+/// Maps world space points onto the screen using the active camera
+pub struct ScreenProjection {
+    view_projection: glm::Mat4,
+    screen: Rect,
+    camera_right: glm::Vec3,
+    cursor_ray: Option<Ray>,
+}
+
+impl ScreenProjection {
+    /// Nothing is rendered without a camera, so there is no projection either
+    pub fn new(world: &World, viewport: &SceneViewport) -> Option<Self> {
+        let (projection, view) = world.active_camera_matrices(viewport.aspect_ratio()).ok()?;
+        Some(Self {
+            view_projection: projection * view,
+            screen: viewport.rect,
+            camera_right: glm::vec3(view[(0, 0)], view[(0, 1)], view[(0, 2)]),
+            cursor_ray: viewport.cursor_ray,
+        })
+    }
+
+    /// The ray from the camera through the cursor, for picking and dragging in the scene
+    pub fn cursor_ray(&self) -> Option<Ray> {
+        self.cursor_ray
+    }
+
+    pub fn project(&self, point: &glm::Vec3) -> Option<Pos2> {
+        let clip = self.view_projection * glm::vec4(point.x, point.y, point.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        Some(pos2(
+            self.screen.left() + (ndc.x + 1.0) * 0.5 * self.screen.width(),
+            self.screen.top() + (1.0 - ndc.y) * 0.5 * self.screen.height(),
+        ))
+    }
+
+    /// How many pixels a world space unit at the point covers on the screen
+    pub fn pixels_per_unit(&self, point: &glm::Vec3) -> Option<f32> {
+        let center = self.project(point)?;
+        let offset = self.project(&(point + self.camera_right))?;
+        let pixels = center.distance(offset);
+        (pixels > f32::EPSILON).then(|| pixels)
+    }
+
+    pub fn line(&self, painter: &Painter, start: &glm::Vec3, end: &glm::Vec3, stroke: Stroke) {
+        if let (Some(start), Some(end)) = (self.project(start), self.project(end)) {
+            painter.line_segment([start, end], stroke);
+        }
+    }
+}
+
+/// Editor-only handles for lights, cameras and audio sources,
+/// which have no mesh to click on in the viewport
+pub struct Gizmos {
+    pub visible: bool,
+    dragging: Option<(Entity, GizmoKind)>,
+}
+
+impl Default for Gizmos {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            dragging: None,
+        }
+    }
+}
+
+impl Gizmos {
+    const ICON_RADIUS: f32 = 12.0;
+    const HANDLE_RADIUS: f32 = 6.0;
+    const CIRCLE_SEGMENTS: usize = 32;
+    const MINIMUM_RANGE: f32 = 0.1;
+
+    /// Frustums of cameras without a far plane are drawn out to this distance
+    const FRUSTUM_LENGTH: f32 = 2.0;
+
+    pub fn ui(
+        &mut self,
+        ctx: &CtxRef,
+        world: &mut World,
+        viewport: &SceneViewport,
+        selected: &mut Option<Entity>,
+        pointer_captured: bool,
+    ) -> Result<()> {
+        if !self.visible {
+            self.dragging = None;
+            return Ok(());
+        }
+
+        let screen_projection = match ScreenProjection::new(world, viewport) {
+            Some(screen_projection) => screen_projection,
+            None => return Ok(()),
+        };
+        let gizmos = collect_gizmos(world, viewport.aspect_ratio())?;
+
+        let painter = ctx.layer_painter(LayerId::background());
+        for gizmo in gizmos.iter() {
+            paint_gizmo(
+                &painter,
+                &screen_projection,
+                gizmo,
+                *selected == Some(gizmo.entity),
+            );
+        }
+
+        if pointer_captured {
+            self.dragging = None;
+            return Ok(());
+        }
+        self.handle_pointer(ctx, world, &screen_projection, &gizmos, selected)
+    }
+
+    fn handle_pointer(
+        &mut self,
+        ctx: &CtxRef,
+        world: &mut World,
+        screen_projection: &ScreenProjection,
+        gizmos: &[Gizmo],
+        selected: &mut Option<Entity>,
+    ) -> Result<()> {
+        let (pointer, pressed, down) = {
+            let input = ctx.input();
+            (
+                input.pointer.hover_pos(),
+                input.pointer.any_pressed(),
+                input.pointer.primary_down(),
+            )
+        };
+
+        if let Some((entity, kind)) = self.dragging {
+            ctx.output().cursor_icon = CursorIcon::Grabbing;
+            match (down, pointer) {
+                (true, Some(pointer)) => {
+                    let gizmo = gizmos
+                        .iter()
+                        .find(|gizmo| gizmo.entity == entity && gizmo.kind == kind);
+                    if let Some(gizmo) = gizmo {
+                        drag_range(world, screen_projection, gizmo, pointer)?;
+                    }
+                }
+                _ => self.dragging = None,
+            }
+            return Ok(());
+        }
+
+        let pointer = match pointer {
+            Some(pointer) if !ctx.is_pointer_over_area() => pointer,
+            _ => return Ok(()),
+        };
+        let handle = hovered_handle(screen_projection, gizmos, *selected, pointer);
+        let icon = hovered_icon(screen_projection, gizmos, pointer);
+        if handle.is_some() {
+            ctx.output().cursor_icon = CursorIcon::Grab;
+        } else if icon.is_some() {
+            ctx.output().cursor_icon = CursorIcon::PointingHand;
+        }
+        if !(pressed && down) {
+            return Ok(());
+        }
+
+        if let Some(gizmo) = handle {
+            self.dragging = Some((gizmo.entity, gizmo.kind));
+            return Ok(());
+        }
+        if let Some(gizmo) = icon {
+            *selected = Some(gizmo.entity);
+            return Ok(());
+        }
+
+        let hit = screen_projection
+            .cursor_ray()
+            .and_then(|ray| world.pick_entity(&ray, PickPrecision::Triangle));
+        if let Some(hit) = hit {
+            *selected = Some(hit.entity);
+        }
+        Ok(())
+    }
+}
+
+/// The selected entity's range handle under the pointer. These are checked before icons
+/// so they can be grabbed even when another icon is drawn on top of them.
+fn hovered_handle<'a>(
+    screen_projection: &ScreenProjection,
+    gizmos: &'a [Gizmo],
+    selected: Option<Entity>,
+    pointer: Pos2,
+) -> Option<&'a Gizmo> {
+    gizmos
+        .iter()
+        .filter(|gizmo| selected == Some(gizmo.entity))
+        .find(|gizmo| {
+            range_handle(screen_projection, gizmo).map_or(false, |handle| {
+                handle.distance(pointer) <= Gizmos::HANDLE_RADIUS
+            })
+        })
+}
+
+/// The gizmo whose icon is closest to the pointer, if the pointer is over one
+fn hovered_icon<'a>(
+    screen_projection: &ScreenProjection,
+    gizmos: &'a [Gizmo],
+    pointer: Pos2,
+) -> Option<&'a Gizmo> {
+    gizmos
+        .iter()
+        .filter_map(|gizmo| {
+            let position = screen_projection.project(&gizmo.position)?;
+            Some((gizmo, position.distance(pointer)))
+        })
+        .filter(|(_, distance)| *distance <= Gizmos::ICON_RADIUS)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        .map(|(gizmo, _)| gizmo)
+}
+
+fn collect_gizmos(world: &World, aspect_ratio: f32) -> Result<Vec<Gizmo>> {
+    // The camera being looked through would cover the whole screen
+    let active_camera = world.active_camera().ok();
+
+    let mut gizmos = Vec::new();
+    let mut query = <(
+        Entity,
+        Option<&Light>,
+        Option<&Camera>,
+        Option<&AudioSource>,
+    )>::query();
+    for (entity, light, camera, audio_source) in query.iter(&world.ecs) {
+        let has_gizmo = light.is_some() || camera.is_some() || audio_source.is_some();
+        if !has_gizmo || !world.is_enabled_in_hierarchy(*entity) {
+            continue;
+        }
+        let matrix = world.entity_global_transform_matrix(*entity)?;
+        let transform = Transform::from(matrix);
+        let position = transform.translation;
+
+        if let Some(light) = light {
+            let (range, lines) = match light.kind {
+                LightKind::Point => (Some(light.range), Vec::new()),
+                LightKind::Spot { .. } => (
+                    Some(light.range),
+                    vec![(position, position + transform.forward())],
+                ),
+                LightKind::Directional => (None, vec![(position, position + transform.forward())]),
+            };
+            gizmos.push(Gizmo {
+                entity: *entity,
+                kind: GizmoKind::Light,
+                position,
+                // A light without a positive range reaches infinitely far
+                range: range.filter(|range| *range > 0.0),
+                lines,
+            });
+        }
+
+        if let Some(camera) = camera {
+            if Some(*entity) != active_camera {
+                gizmos.push(Gizmo {
+                    entity: *entity,
+                    kind: GizmoKind::Camera,
+                    position,
+                    range: None,
+                    lines: frustum_lines(camera, &matrix, aspect_ratio),
+                });
+            }
+        }
+
+        if let Some(audio_source) = audio_source {
+            gizmos.push(Gizmo {
+                entity: *entity,
+                kind: GizmoKind::AudioSource,
+                position,
+                range: Some(audio_source.range),
+                lines: Vec::new(),
+            });
+        }
+    }
+    Ok(gizmos)
+}
+
+fn frustum_lines(
+    camera: &Camera,
+    transform: &glm::Mat4,
+    aspect_ratio: f32,
+) -> Vec<(glm::Vec3, glm::Vec3)> {
+    let (near, far) = match &camera.projection {
+        Projection::Perspective(perspective) => {
+            let aspect_ratio = perspective.aspect_ratio.unwrap_or(aspect_ratio);
+            let far = perspective
+                .z_far
+                .unwrap_or(f32::MAX)
+                .min(perspective.z_near + Gizmos::FRUSTUM_LENGTH);
+            let half_height = |distance: f32| distance * (perspective.y_fov_rad / 2.0).tan();
+            (
+                frustum_rectangle(
+                    half_height(perspective.z_near) * aspect_ratio,
+                    half_height(perspective.z_near),
+                    perspective.z_near,
+                ),
+                frustum_rectangle(half_height(far) * aspect_ratio, half_height(far), far),
+            )
+        }
+        Projection::Orthographic(orthographic) => {
+            let far = orthographic
+                .z_far
+                .min(orthographic.z_near + Gizmos::FRUSTUM_LENGTH);
+            (
+                frustum_rectangle(orthographic.x_mag, orthographic.y_mag, orthographic.z_near),
+                frustum_rectangle(orthographic.x_mag, orthographic.y_mag, far),
+            )
+        }
+    };
+
+    let to_world =
+        |point: &glm::Vec3| (transform * glm::vec4(point.x, point.y, point.z, 1.0)).xyz();
+    let near = near.iter().map(to_world).collect::<Vec<_>>();
+    let far = far.iter().map(to_world).collect::<Vec<_>>();
+    let mut lines = Vec::new();
+    for corner in 0..4 {
+        let next = (corner + 1) % 4;
+        lines.push((near[corner], near[next]));
+        lines.push((far[corner], far[next]));
+        lines.push((near[corner], far[corner]));
+    }
+    lines
+}
+
+/// The corners of a slice through a camera's frustum, in the camera's local space
+fn frustum_rectangle(half_width: f32, half_height: f32, distance: f32) -> [glm::Vec3; 4] {
+    [
+        glm::vec3(-half_width, -half_height, -distance),
+        glm::vec3(half_width, -half_height, -distance),
+        glm::vec3(half_width, half_height, -distance),
+        glm::vec3(-half_width, half_height, -distance),
+    ]
+}
+
+fn paint_gizmo(
+    painter: &Painter,
+    screen_projection: &ScreenProjection,
+    gizmo: &Gizmo,
+    selected: bool,
+) {
+    let color = if selected {
+        Color32::from_rgb(255, 140, 0)
+    } else {
+        gizmo.kind.color()
+    };
+    let stroke = Stroke::new(1.0, color);
+
+    for (start, end) in gizmo.lines.iter() {
+        screen_projection.line(painter, start, end, stroke);
+    }
+
+    if let Some(range) = gizmo.range {
+        paint_sphere(painter, screen_projection, &gizmo.position, range, stroke);
+    }
+
+    if selected {
+        if let Some(handle) = range_handle(screen_projection, gizmo) {
+            painter.circle_filled(handle, Gizmos::HANDLE_RADIUS, color);
+        }
+    }
+
+    if let Some(position) = screen_projection.project(&gizmo.position) {
+        painter.circle_filled(
+            position,
+            Gizmos::ICON_RADIUS,
+            Color32::from_black_alpha(160),
+        );
+        if selected {
+            painter.circle_stroke(position, Gizmos::ICON_RADIUS, Stroke::new(2.0, color));
+        }
+        painter.text(
+            position,
+            Align2::CENTER_CENTER,
+            gizmo.kind.icon(),
+            TextStyle::Body,
+            color,
+        );
+    }
+}
+
+/// Draws a circle around each axis so the sphere reads from any angle
+fn paint_sphere(
+    painter: &Painter,
+    screen_projection: &ScreenProjection,
+    center: &glm::Vec3,
+    radius: f32,
+    stroke: Stroke,
+) {
+    let planes = [
+        (glm::Vec3::x(), glm::Vec3::y()),
+        (glm::Vec3::x(), glm::Vec3::z()),
+        (glm::Vec3::y(), glm::Vec3::z()),
+    ];
+    for (first, second) in planes.iter() {
+        let points = (0..=Gizmos::CIRCLE_SEGMENTS)
+            .map(|segment| {
+                let angle = segment as f32 / Gizmos::CIRCLE_SEGMENTS as f32 * TAU;
+                center + (first * angle.cos() + second * angle.sin()) * radius
+            })
+            .collect::<Vec<_>>();
+        for segment in points.windows(2) {
+            screen_projection.line(painter, &segment[0], &segment[1], stroke);
+        }
+    }
+}
+
+/// The point on the edge of the range sphere that can be dragged to resize it
+fn range_handle(screen_projection: &ScreenProjection, gizmo: &Gizmo) -> Option<Pos2> {
+    let range = gizmo.range?;
+    screen_projection.project(&(gizmo.position + screen_projection.camera_right * range))
+}
+
+fn drag_range(
+    world: &mut World,
+    screen_projection: &ScreenProjection,
+    gizmo: &Gizmo,
+    pointer: Pos2,
+) -> Result<()> {
+    let (range, center, handle) = match (
+        gizmo.range,
+        screen_projection.project(&gizmo.position),
+        range_handle(screen_projection, gizmo),
+    ) {
+        (Some(range), Some(center), Some(handle)) => (range, center, handle),
+        _ => return Ok(()),
+    };
+    let pixels = center.distance(handle);
+    if pixels <= f32::EPSILON {
+        return Ok(());
+    }
+    let range = (range * center.distance(pointer) / pixels).max(Gizmos::MINIMUM_RANGE);
+    match gizmo.kind {
+        GizmoKind::Light => {
+            world.modify_component::<Light>(gizmo.entity, |light| light.range = range)
+        }
+        GizmoKind::AudioSource => world
+            .modify_component::<AudioSource>(gizmo.entity, |audio_source| {
+                audio_source.range = range
+            }),
+        GizmoKind::Camera => Ok(()),
+    }
+}
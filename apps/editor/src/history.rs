@@ -0,0 +1,299 @@
+use phantom::{
+    dependencies::{
+        anyhow::{Context, Result},
+        egui::{Button, CtxRef, Key, ScrollArea, Window},
+        log,
+    },
+    world::{
+        entities_as_bytes, restore_entities_from_bytes, ComponentSnapshot, Entity, Material,
+        Transform, World,
+    },
+};
+
+/// A reversible edit to the world
+pub enum Command {
+    Transform {
+        entity: Entity,
+        before: Transform,
+        after: Transform,
+    },
+    Component {
+        entity: Entity,
+        before: ComponentSnapshot,
+        after: ComponentSnapshot,
+    },
+    Material {
+        index: usize,
+        before: Material,
+        after: Material,
+    },
+
+    /// Adding, removing or reparenting entities, stored as the entities before and after
+    Entities {
+        name: String,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+}
+
+impl Command {
+    /// Snapshots the world's entities around an edit that adds, removes or reparents them
+    pub fn entities(
+        name: &str,
+        world: &mut World,
+        edit: impl FnOnce(&mut World) -> Result<()>,
+    ) -> Result<Self> {
+        let before = entities_as_bytes(world)?;
+        edit(world)?;
+        Ok(Self::Entities {
+            name: name.to_string(),
+            before,
+            after: entities_as_bytes(world)?,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Transform { .. } => "Transform".to_string(),
+            Self::Component { before, .. } => format!("Edit {}", before.name()),
+            Self::Material { after, .. } => format!("Edit Material {}", after.name),
+            Self::Entities { name, .. } => name.to_string(),
+        }
+    }
+
+    fn apply(&self, world: &mut World) -> Result<()> {
+        match self {
+            Self::Transform { entity, after, .. } => {
+                world.modify_component::<Transform>(*entity, |transform| *transform = *after)
+            }
+            Self::Component { entity, after, .. } => world.restore_component(*entity, after),
+            Self::Material { index, after, .. } => set_material(world, *index, after),
+            Self::Entities { after, .. } => restore_entities_from_bytes(world, after),
+        }
+    }
+
+    fn revert(&self, world: &mut World) -> Result<()> {
+        match self {
+            Self::Transform { entity, before, .. } => {
+                world.modify_component::<Transform>(*entity, |transform| *transform = *before)
+            }
+            Self::Component { entity, before, .. } => world.restore_component(*entity, before),
+            Self::Material { index, before, .. } => set_material(world, *index, before),
+            Self::Entities { before, .. } => restore_entities_from_bytes(world, before),
+        }
+    }
+
+    /// Folds a later edit of the same thing into this one, so a drag is undone in one step
+    fn merge(&mut self, next: Command) -> Option<Command> {
+        match (self, next) {
+            (
+                Self::Transform { entity, after, .. },
+                Self::Transform {
+                    entity: next_entity,
+                    after: next_after,
+                    ..
+                },
+            ) if *entity == next_entity => {
+                *after = next_after;
+                None
+            }
+            (
+                Self::Component { entity, after, .. },
+                Self::Component {
+                    entity: next_entity,
+                    after: next_after,
+                    ..
+                },
+            ) if *entity == next_entity && after.name() == next_after.name() => {
+                *after = next_after;
+                None
+            }
+            (
+                Self::Material { index, after, .. },
+                Self::Material {
+                    index: next_index,
+                    after: next_after,
+                    ..
+                },
+            ) if *index == next_index => {
+                *after = next_after;
+                None
+            }
+            (_, next) => Some(next),
+        }
+    }
+}
+
+fn set_material(world: &mut World, index: usize, material: &Material) -> Result<()> {
+    *world
+        .materials
+        .get_mut(index)
+        .context(format!("Failed to find material {}!", index))? = material.clone();
+    world.mark_materials_changed();
+    Ok(())
+}
+
+/// Edits that can be undone with ctrl+z and redone with ctrl+shift+z or ctrl+y
+#[derive(Default)]
+pub struct History {
+    pub open: bool,
+    commands: Vec<Command>,
+
+    /// Commands before this index are applied, the rest have been undone
+    position: usize,
+
+    /// Edits recorded while the pointer stays down are merged into one command
+    merging: bool,
+}
+
+impl History {
+    /// The oldest commands are forgotten beyond this
+    const MAX_COMMANDS: usize = 200;
+
+    /// Records an edit that has already been made, discarding anything that was undone
+    pub fn record(&mut self, command: Command) {
+        self.commands.truncate(self.position);
+        let command = match (self.merging, self.commands.last_mut()) {
+            (true, Some(last)) => match last.merge(command) {
+                Some(command) => command,
+                None => return,
+            },
+            _ => command,
+        };
+        self.commands.push(command);
+        if self.commands.len() > Self::MAX_COMMANDS {
+            self.commands.remove(0);
+        }
+        self.position = self.commands.len();
+        self.merging = true;
+    }
+
+    /// Makes an edit that adds, removes or reparents entities and records it
+    pub fn edit_entities(
+        &mut self,
+        name: &str,
+        world: &mut World,
+        edit: impl FnOnce(&mut World) -> Result<()>,
+    ) -> Result<()> {
+        let command = Command::entities(name, world, edit)?;
+        self.merging = false;
+        self.record(command);
+        self.merging = false;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.position = 0;
+        self.merging = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.commands.len()
+    }
+
+    pub fn undo(&mut self, world: &mut World) -> Result<()> {
+        if self.can_undo() {
+            self.merging = false;
+            self.position -= 1;
+            self.commands[self.position].revert(world)?;
+        }
+        Ok(())
+    }
+
+    pub fn redo(&mut self, world: &mut World) -> Result<()> {
+        if self.can_redo() {
+            self.merging = false;
+            self.commands[self.position].apply(world)?;
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Undoes or redoes until the first `position` commands are applied
+    fn jump_to(&mut self, world: &mut World, position: usize) -> Result<()> {
+        while self.position > position {
+            self.undo(world)?;
+        }
+        while self.position < position {
+            self.redo(world)?;
+        }
+        Ok(())
+    }
+
+    /// Handles the shortcuts and shows the history window.
+    /// Call after the frame's edits, so edits made while releasing the pointer are merged.
+    pub fn ui(&mut self, ctx: &CtxRef, world: &mut World) -> Result<()> {
+        if !ctx.wants_keyboard_input() {
+            let (undo, redo) = {
+                let input = ctx.input();
+                let command = input.modifiers.command;
+                let shift = input.modifiers.shift;
+                (
+                    command && !shift && input.key_pressed(Key::Z),
+                    command && (shift && input.key_pressed(Key::Z) || input.key_pressed(Key::Y)),
+                )
+            };
+            if undo {
+                self.undo(world)?;
+            } else if redo {
+                self.redo(world)?;
+            }
+        }
+
+        let mut target = None;
+        let mut open = self.open;
+        Window::new("History").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.can_undo(), Button::new("Undo"))
+                    .clicked()
+                {
+                    target = Some(self.position - 1);
+                }
+                if ui
+                    .add_enabled(self.can_redo(), Button::new("Redo"))
+                    .clicked()
+                {
+                    target = Some(self.position + 1);
+                }
+            });
+            ui.separator();
+            ScrollArea::vertical().show(ui, |ui| {
+                if ui.selectable_label(self.position == 0, "Start").clicked() {
+                    target = Some(0);
+                }
+                for (index, command) in self.commands.iter().enumerate() {
+                    let applied = index < self.position;
+                    let label = if applied {
+                        command.name()
+                    } else {
+                        format!("({})", command.name())
+                    };
+                    if ui
+                        .selectable_label(self.position == index + 1, label)
+                        .clicked()
+                    {
+                        target = Some(index + 1);
+                    }
+                }
+            });
+        });
+        self.open = open;
+
+        if let Some(target) = target {
+            if let Err(error) = self.jump_to(world, target) {
+                log::error!("Failed to move through the history: {}", error);
+            }
+        }
+
+        if !ctx.input().pointer.any_down() {
+            self.merging = false;
+        }
+        Ok(())
+    }
+}
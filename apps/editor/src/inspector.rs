@@ -0,0 +1,204 @@
+use crate::history::{Command, History};
+use phantom::{
+    dependencies::{
+        egui::{CollapsingHeader, ComboBox, DragValue, Grid, Id, Slider, TextEdit, Ui},
+        log,
+        nalgebra::UnitQuaternion,
+        nalgebra_glm as glm,
+    },
+    world::{reflect_value, Entity, FieldMut, Inspector, MeshRender, World},
+};
+use std::collections::BTreeSet;
+
+/// Shows a widget for each field of a reflected value, one grid row per field
+struct WidgetInspector<'a> {
+    ui: &'a mut Ui,
+    id: Id,
+}
+
+impl<'a> WidgetInspector<'a> {
+    fn vec3(ui: &mut Ui, value: &mut glm::Vec3, speed: f32) -> bool {
+        ui.horizontal(|ui| {
+            let x = ui.add(DragValue::new(&mut value.x).speed(speed)).changed();
+            let y = ui.add(DragValue::new(&mut value.y).speed(speed)).changed();
+            let z = ui.add(DragValue::new(&mut value.z).speed(speed)).changed();
+            x || y || z
+        })
+        .inner
+    }
+}
+
+impl<'a> Inspector for WidgetInspector<'a> {
+    fn field(&mut self, name: &str, value: FieldMut) -> bool {
+        let ui = &mut *self.ui;
+        ui.label(name);
+        let changed = match value {
+            FieldMut::Bool(value) => ui.checkbox(value, "").changed(),
+            FieldMut::Float(value) => ui.add(DragValue::new(value).speed(0.1)).changed(),
+            FieldMut::Ranged(value, min, max) => ui.add(Slider::new(value, min..=max)).changed(),
+            FieldMut::Angle(value) => {
+                let mut degrees = value.to_degrees();
+                let changed = ui
+                    .add(DragValue::new(&mut degrees).speed(1.0).suffix("°"))
+                    .changed();
+                if changed {
+                    *value = degrees.to_radians();
+                }
+                changed
+            }
+            FieldMut::Text(value) => ui.add(TextEdit::singleline(value)).changed(),
+            FieldMut::Vec3(value) => Self::vec3(ui, value, 0.1),
+            FieldMut::Rotation(value) => {
+                let (roll, pitch, yaw) = UnitQuaternion::from_quaternion(*value).euler_angles();
+                let mut degrees = glm::vec3(roll, pitch, yaw).map(f32::to_degrees);
+                let changed = Self::vec3(ui, &mut degrees, 1.0);
+                if changed {
+                    let radians = degrees.map(f32::to_radians);
+                    *value = UnitQuaternion::from_euler_angles(radians.x, radians.y, radians.z)
+                        .into_inner();
+                }
+                changed
+            }
+            FieldMut::Color(value) => {
+                let mut color = [value.x, value.y, value.z];
+                let changed = ui.color_edit_button_rgb(&mut color).changed();
+                if changed {
+                    *value = glm::vec3(color[0], color[1], color[2]);
+                }
+                changed
+            }
+            FieldMut::ColorAlpha(value) => {
+                let mut color = [value.x, value.y, value.z, value.w];
+                let changed = ui.color_edit_button_rgba_unmultiplied(&mut color).changed();
+                if changed {
+                    *value = glm::vec4(color[0], color[1], color[2], color[3]);
+                }
+                changed
+            }
+            FieldMut::Choice(choice) => {
+                let options = choice.options();
+                let mut selected = choice.selected();
+                ComboBox::from_id_source(self.id.with(name))
+                    .selected_text(options.get(selected).copied().unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for (index, option) in options.iter().enumerate() {
+                            ui.selectable_value(&mut selected, index, *option);
+                        }
+                    });
+                let changed = selected != choice.selected();
+                if changed {
+                    choice.select(selected);
+                }
+                changed
+            }
+            FieldMut::Listed(value, options) => {
+                let mut selected = value.clone();
+                ComboBox::from_id_source(self.id.with(name))
+                    .selected_text(selected.as_str())
+                    .show_ui(ui, |ui| {
+                        for option in options.iter() {
+                            ui.selectable_value(&mut selected, option.clone(), option.as_str());
+                        }
+                    });
+                let changed = selected != *value;
+                if changed {
+                    *value = selected;
+                }
+                changed
+            }
+        };
+        ui.end_row();
+        changed
+    }
+}
+
+/// Edits the selected entity's reflected components and the materials of its mesh
+#[derive(Default)]
+pub struct InspectorPanel;
+
+impl InspectorPanel {
+    pub fn ui(&mut self, ui: &mut Ui, world: &mut World, entity: Entity, history: &mut History) {
+        for name in world.reflected_components(entity) {
+            CollapsingHeader::new(&name)
+                .default_open(true)
+                .show(ui, |ui| {
+                    let before = world.snapshot_component(entity, &name);
+                    let id = ui.make_persistent_id(&name);
+                    let changed = Grid::new(id)
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            let mut inspector = WidgetInspector { ui, id };
+                            world.reflect_component(entity, &name, &mut inspector)
+                        })
+                        .inner;
+                    match (changed, before, world.snapshot_component(entity, &name)) {
+                        (Ok(true), Some(before), Some(after)) => {
+                            history.record(Command::Component {
+                                entity,
+                                before,
+                                after,
+                            });
+                        }
+                        (Err(error), ..) => {
+                            log::error!("Failed to inspect component '{}': {}", name, error);
+                        }
+                        _ => {}
+                    }
+                });
+        }
+        self.materials_ui(ui, world, entity, history);
+    }
+
+    fn materials_ui(
+        &mut self,
+        ui: &mut Ui,
+        world: &mut World,
+        entity: Entity,
+        history: &mut History,
+    ) {
+        let mesh_name = match world.ecs.entry_ref(entity) {
+            Ok(entry) => match entry.get_component::<MeshRender>() {
+                Ok(mesh) => mesh.name.to_string(),
+                Err(_) => return,
+            },
+            Err(_) => return,
+        };
+        let material_indices = match world.geometry.meshes.get(&mesh_name) {
+            Some(mesh) => mesh
+                .primitives
+                .iter()
+                .filter_map(|primitive| primitive.material_index)
+                .collect::<BTreeSet<_>>(),
+            None => return,
+        };
+
+        for index in material_indices {
+            let material = match world.materials.get_mut(index) {
+                Some(material) => material,
+                None => continue,
+            };
+            let before = material.clone();
+            let changed = CollapsingHeader::new(format!("Material: {}", material.name))
+                .id_source(("material", index))
+                .show(ui, |ui| {
+                    let id = ui.make_persistent_id(("material", index));
+                    Grid::new(id)
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            reflect_value(material, &mut WidgetInspector { ui, id })
+                        })
+                        .inner
+                })
+                .body_returned
+                .unwrap_or_default();
+            if changed {
+                history.record(Command::Material {
+                    index,
+                    before,
+                    after: material.clone(),
+                });
+                world.mark_materials_changed();
+            }
+        }
+    }
+}
@@ -0,0 +1,772 @@
+mod assets;
+mod build;
+mod explorer;
+mod gallery;
+mod gizmos;
+mod history;
+mod inspector;
+mod manipulator;
+mod measure;
+mod placement;
+mod plugin;
+mod statistics;
+mod tables;
+mod watch;
+
+use assets::{AssetBrowser, AssetDrag};
+use build::{BuildSettings, BuildWindow};
+use explorer::SceneExplorer;
+use gallery::ScreenshotGallery;
+use gizmos::{Gizmos, SceneViewport};
+use history::History;
+use inspector::InspectorPanel;
+use manipulator::TransformGizmo;
+use measure::MeasureTool;
+use phantom::{
+    app::{
+        run, AppConfig, CVars, DisplaySettings, Resources, SimulationStep, State, Transition,
+        WindowMode,
+    },
+    dependencies::{
+        anyhow::{Context, Result},
+        egui::{
+            global_dark_light_mode_switch, menu, ComboBox, DragValue, Grid, ScrollArea, SidePanel,
+            TopBottomPanel, Ui, Window,
+        },
+        env_logger,
+        gilrs::Event as GilrsEvent,
+        legion::EntityStore,
+        log,
+        winit::event::{ElementState, Event, KeyboardInput, MouseButton},
+    },
+    world::{CollisionMatrix, Entity, FlyCamera, Name, OrbitCamera, Viewport, World},
+};
+use placement::PlacementTool;
+pub use plugin::{ComponentInspector, EditorPlugin};
+use statistics::StatisticsPanel;
+use tables::DataTablesPanel;
+use watch::WatchPanel;
+
+/// The controllers the scene's active camera had before the editor attached its own.
+/// They're swapped back in while the scene is saved, so the editor's navigation isn't saved with it.
+#[derive(Default)]
+struct SceneCameraControllers {
+    camera: Option<Entity>,
+    orbit: Option<OrbitCamera>,
+    fly: Option<FlyCamera>,
+}
+
+#[derive(Default)]
+struct Editor {
+    world: World,
+    scene_path: String,
+    show_project_settings: bool,
+    show_mods: bool,
+    show_cvars: bool,
+    display: DisplaySettings,
+    build: BuildWindow,
+    gallery: ScreenshotGallery,
+    watch: WatchPanel,
+    gizmos: Gizmos,
+    transform_gizmo: TransformGizmo,
+    placement: PlacementTool,
+    measure: MeasureTool,
+    statistics: StatisticsPanel,
+    explorer: SceneExplorer,
+    inspector: InspectorPanel,
+    history: History,
+    assets: AssetBrowser,
+    data_tables: DataTablesPanel,
+    selected: Option<Entity>,
+    fly_camera: bool,
+    scene_camera_controllers: SceneCameraControllers,
+    mouse_look: bool,
+    plugins: Vec<Box<dyn EditorPlugin>>,
+}
+
+impl State for Editor {
+    fn on_start(&mut self, resources: &mut Resources) -> Result<()> {
+        log::info!("Starting the Phantom editor");
+        DataTablesPanel::load_tables(resources.data_tables);
+        resources
+            .console
+            .add_command("save_scene", "Saves the scene, optionally to a new path");
+        resources
+            .console
+            .add_command("open_scene", "Opens the scene, optionally from a new path");
+        self.world = World::new()?;
+        resources.cvars.apply_to_world(&mut self.world);
+        self.attach_camera_controller()?;
+        self.scene_path = format!("assets/scenes/main.{}", World::SCENE_EXTENSION);
+        Ok(())
+    }
+
+    fn on_stop(&mut self, resources: &mut Resources) -> Result<()> {
+        log::info!("Stopping the Phantom editor");
+        resources.system.viewport = None;
+        Ok(())
+    }
+
+    fn world(&mut self) -> Option<&mut World> {
+        Some(&mut self.world)
+    }
+
+    fn on_pause(&mut self, _resources: &mut Resources) -> Result<()> {
+        log::info!("Editor paused");
+        Ok(())
+    }
+
+    fn on_resume(&mut self, _resources: &mut Resources) -> Result<()> {
+        log::info!("Editor unpaused");
+        Ok(())
+    }
+
+    fn fixed_update(&mut self, _resources: &mut Resources) -> Result<Transition> {
+        self.world.tick()?;
+        Ok(Transition::None)
+    }
+
+    fn update(&mut self, resources: &mut Resources) -> Result<Transition> {
+        // The app's fixed updates step the world, so they follow the world's physics settings
+        resources.system.fixed_timestep = self.world.physics.settings.fixed_timestep() as f64;
+        resources.system.max_fixed_updates = self.world.physics.settings.max_substeps;
+        let delta_time = resources.system.simulation_delta_time as f32;
+        if delta_time > 0.0 {
+            self.world
+                .interpolate_rigid_bodies(resources.system.interpolation_alpha as f32);
+            self.watch.record(&self.world, delta_time);
+        }
+        self.navigate_viewport(resources)?;
+        self.run_console_commands(resources);
+        resources.bug_reporter.scene_path = Some(self.scene_path.clone());
+        resources.renderer.update_world(&self.world)?;
+        resources.audio.update(&self.world)?;
+        self.gallery.update(resources)?;
+        self.assets.update(resources);
+        Ok(Transition::None)
+    }
+
+    fn update_gui(&mut self, resources: &mut Resources) -> Result<Transition> {
+        let ctx = &resources.gui.context();
+
+        TopBottomPanel::top("top_panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                menu::bar(ui, |ui| {
+                    global_dark_light_mode_switch(ui);
+                    menu::menu(ui, "Scene", |ui| {
+                        ui.text_edit_singleline(&mut self.scene_path);
+                        if ui.button("Save").clicked() {
+                            self.save_scene();
+                        }
+                        if ui.button("Open").clicked() {
+                            self.open_scene(resources.cvars);
+                        }
+                        ui.separator();
+                        if ui.button("History").clicked() {
+                            self.history.open = true;
+                        }
+                    });
+                    menu::menu(ui, "Project", |ui| {
+                        if ui.button("Settings").clicked() {
+                            self.show_project_settings = true;
+                        }
+                        if ui.button("Build").clicked() {
+                            self.build.open = true;
+                        }
+                        if ui.button("Data Tables").clicked() {
+                            self.data_tables.open = true;
+                        }
+                        if ui.button("Mods").clicked() {
+                            self.show_mods = true;
+                        }
+                    });
+                    menu::menu(ui, "Screenshots", |ui| {
+                        if ui.button("Capture").clicked() {
+                            self.gallery.capture(resources);
+                        }
+                        if ui.button("Gallery").clicked() {
+                            self.gallery.open = true;
+                        }
+                    });
+                    menu::menu(ui, "Tools", |ui| {
+                        if ui.button("Transform Gizmo").clicked() {
+                            self.transform_gizmo.open = true;
+                        }
+                        if ui.button("Placement").clicked() {
+                            self.placement.open = true;
+                        }
+                        if ui.button("Measure").clicked() {
+                            self.measure.open = true;
+                        }
+                    });
+                    menu::menu(ui, "Camera", |ui| {
+                        let mut fly_camera = self.fly_camera;
+                        ui.radio_value(&mut fly_camera, false, "Orbit");
+                        ui.radio_value(&mut fly_camera, true, "Fly");
+                        if fly_camera != self.fly_camera {
+                            if let Err(error) = self.set_fly_camera(fly_camera) {
+                                log::error!("Failed to switch cameras: {}", error);
+                            }
+                        }
+                    });
+                    for plugin in self.plugins.iter_mut().filter(|plugin| plugin.has_menu()) {
+                        let name = plugin.name().to_string();
+                        menu::menu(ui, &name, |ui| plugin.menu_ui(ui, &mut self.world));
+                    }
+                    menu::menu(ui, "Debug", |ui| {
+                        if ui.button("Watch").clicked() {
+                            self.watch.open = true;
+                        }
+                        if ui.button("Scene Statistics").clicked() {
+                            self.statistics.open = true;
+                        }
+                        if ui.button("CVars").clicked() {
+                            self.show_cvars = true;
+                        }
+                        ui.checkbox(&mut self.gizmos.visible, "Gizmos");
+                    });
+                    ui.separator();
+                    simulation_controls_ui(ui, resources);
+                });
+            });
+
+        let mut show_project_settings = self.show_project_settings;
+        Window::new("Project Settings")
+            .open(&mut show_project_settings)
+            .show(ctx, |ui| {
+                self.project_settings_ui(ui, resources.cvars);
+                ui.separator();
+                display_settings_ui(ui, &mut self.display, resources);
+            });
+        self.show_project_settings = show_project_settings;
+
+        Window::new("Mods")
+            .open(&mut self.show_mods)
+            .show(ctx, |ui| resources.mods.ui(ui));
+
+        Window::new("CVars")
+            .open(&mut self.show_cvars)
+            .show(ctx, |ui| resources.cvars.ui(ui));
+
+        self.build.ui(ctx);
+        self.gallery.ui(ctx);
+        self.watch.ui(ctx, &self.world);
+        self.data_tables.ui(ctx, resources.data_tables);
+
+        SidePanel::left("scene_explorer")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading("Scene Explorer");
+                ScrollArea::vertical().show(ui, |ui| {
+                    self.explorer
+                        .ui(ui, &mut self.world, &mut self.selected, &mut self.history);
+                });
+                ui.allocate_space(ui.available_size());
+            });
+
+        SidePanel::right("inspector")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading("Inspector");
+                if let Some(entity) = self.selected {
+                    ui.label(entity_name(&self.world, entity));
+                    ScrollArea::vertical().show(ui, |ui| {
+                        self.inspector
+                            .ui(ui, &mut self.world, entity, &mut self.history);
+                        for plugin in self.plugins.iter_mut() {
+                            plugin.inspector_ui(ui, &mut self.world, entity);
+                        }
+                    });
+                }
+                ui.allocate_space(ui.available_size());
+            });
+
+        TopBottomPanel::bottom("console")
+            .resizable(true)
+            .show(ctx, |ui| {
+                match self.assets.ui(ui) {
+                    Some(AssetDrag::Started(path)) => self.placement.begin_drag(&path),
+                    Some(AssetDrag::Released) => self.placement.end_drag(),
+                    None => {}
+                }
+                ui.allocate_space(ui.available_size());
+            });
+
+        // The scene is rendered in the space the panels leave,
+        // which egui measures in points and the renderer in pixels
+        let rect = ctx.available_rect();
+        let pixels_per_point = ctx.pixels_per_point();
+        resources.system.viewport = Some(Viewport {
+            x: rect.left() * pixels_per_point,
+            y: rect.top() * pixels_per_point,
+            width: rect.width() * pixels_per_point,
+            height: rect.height() * pixels_per_point,
+        });
+        let viewport = SceneViewport {
+            rect,
+            cursor_ray: resources.cursor_ray(&self.world).ok(),
+        };
+
+        self.placement
+            .ui(ctx, &mut self.world, &viewport, &mut self.history)?;
+        self.measure.ui(ctx, &mut self.world, &viewport)?;
+        self.statistics.ui(
+            ctx,
+            &self.world,
+            &**resources.renderer,
+            resources.cvars,
+            viewport.aspect_ratio(),
+        );
+        let pointer_captured = self.gizmos.visible
+            && self.transform_gizmo.ui(
+                ctx,
+                &mut self.world,
+                &viewport,
+                self.selected,
+                !self.mouse_look,
+                &mut self.history,
+            )?;
+        self.gizmos.ui(
+            ctx,
+            &mut self.world,
+            &viewport,
+            &mut self.selected,
+            pointer_captured,
+        )?;
+        for plugin in self.plugins.iter_mut() {
+            plugin.ui(ctx, &mut self.world)?;
+        }
+
+        self.history.ui(ctx, &mut self.world)?;
+        if self
+            .selected
+            .map_or(false, |entity| !self.world.ecs.contains(entity))
+        {
+            self.selected = None;
+        }
+
+        Ok(Transition::None)
+    }
+
+    fn on_file_dropped(
+        &mut self,
+        resources: &mut Resources,
+        path: &std::path::PathBuf,
+    ) -> Result<Transition> {
+        let is_gltf = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .map_or(false, |extension| extension == "gltf" || extension == "glb");
+        if !is_gltf {
+            log::info!("Ignoring dropped file: {}", path.display());
+            return Ok(Transition::None);
+        }
+
+        let mut root = None;
+        let imported = self
+            .history
+            .edit_entities("Import", &mut self.world, |world| {
+                root = Some(world.import_gltf(path)?);
+                Ok(())
+            });
+        match imported {
+            Ok(()) => {
+                self.selected = root;
+                resources.renderer.update_world(&self.world)?;
+                log::info!("Imported {}", path.display());
+            }
+            Err(error) => log::error!("Failed to import {}: {}", path.display(), error),
+        }
+        Ok(Transition::None)
+    }
+
+    fn on_mouse(
+        &mut self,
+        _resources: &mut Resources,
+        button: &MouseButton,
+        button_state: &ElementState,
+    ) -> Result<Transition> {
+        log::info!("Mouse event: {:#?} {:#?}", button, button_state,);
+        Ok(Transition::None)
+    }
+
+    fn on_key(&mut self, _resources: &mut Resources, input: KeyboardInput) -> Result<Transition> {
+        log::info!("Key event received: {:#?}", input);
+        Ok(Transition::None)
+    }
+
+    fn on_gamepad_event(
+        &mut self,
+        _resources: &mut Resources,
+        event: GilrsEvent,
+    ) -> Result<Transition> {
+        let GilrsEvent { id, time, event } = event;
+        log::info!("{:?} New event from {}: {:?}", time, id, event);
+        Ok(Transition::None)
+    }
+
+    fn on_event(&mut self, _resources: &mut Resources, _event: &Event<()>) -> Result<Transition> {
+        Ok(Transition::None)
+    }
+}
+
+impl Editor {
+    fn attach_camera_controller(&mut self) -> Result<()> {
+        let camera = self.world.active_camera()?;
+        let mut entry = self
+            .world
+            .ecs
+            .entry(camera)
+            .context("Failed to find the active camera!")?;
+        self.scene_camera_controllers = SceneCameraControllers {
+            camera: Some(camera),
+            orbit: entry.get_component::<OrbitCamera>().ok().cloned(),
+            fly: entry.get_component::<FlyCamera>().ok().cloned(),
+        };
+        self.fly_camera = entry.get_component::<FlyCamera>().is_ok();
+        if !self.fly_camera && entry.get_component::<OrbitCamera>().is_err() {
+            entry.add_component(OrbitCamera::default());
+        }
+        Ok(())
+    }
+
+    fn navigate_viewport(&mut self, resources: &mut Resources) -> Result<()> {
+        let pointer_free = !resources.gui.context().wants_pointer_input();
+
+        // Fly cameras look around while the right mouse button is held
+        let mouse_look = self.fly_camera
+            && resources.input.mouse.is_right_clicked
+            && (self.mouse_look || pointer_free);
+        if mouse_look != self.mouse_look {
+            resources.set_mouse_look(mouse_look)?;
+            self.mouse_look = mouse_look;
+        }
+
+        let camera = self.world.active_camera()?;
+        let mut entry = self
+            .world
+            .ecs
+            .entry(camera)
+            .context("Failed to find the active camera!")?;
+        if let Ok(orbit) = entry.get_component_mut::<OrbitCamera>() {
+            if pointer_free {
+                resources.input.drive_orbit_camera(orbit);
+            }
+        }
+        if let Ok(fly) = entry.get_component_mut::<FlyCamera>() {
+            if mouse_look {
+                let delta_time = resources.system.delta_time as f32;
+                resources.input.drive_fly_camera(fly, delta_time);
+            }
+        }
+        self.world.update_orbit_cameras()?;
+        self.world.update_fly_cameras()
+    }
+
+    /// Swaps the active camera's controller, keeping its current view
+    fn set_fly_camera(&mut self, fly_camera: bool) -> Result<()> {
+        let camera = self.world.active_camera()?;
+        let transform = self.world.entity_global_transform(camera)?;
+        let mut entry = self
+            .world
+            .ecs
+            .entry(camera)
+            .context("Failed to find the active camera!")?;
+        if fly_camera {
+            entry.remove_component::<OrbitCamera>();
+            entry.add_component(FlyCamera::from_transform(&transform));
+        } else {
+            let radius = OrbitCamera::default().radius;
+            entry.remove_component::<FlyCamera>();
+            entry.add_component(OrbitCamera::from_transform(&transform, radius));
+        }
+        self.fly_camera = fly_camera;
+        Ok(())
+    }
+
+    /// Exchanges the camera controllers the editor navigates with for the ones the scene had.
+    /// Swapping twice puts the editor's controllers back.
+    fn swap_camera_controllers(&mut self) {
+        let controllers = &mut self.scene_camera_controllers;
+        let mut entry = match controllers
+            .camera
+            .and_then(|camera| self.world.ecs.entry(camera))
+        {
+            Some(entry) => entry,
+            None => return,
+        };
+        let orbit = entry.get_component::<OrbitCamera>().ok().cloned();
+        let fly = entry.get_component::<FlyCamera>().ok().cloned();
+        entry.remove_component::<OrbitCamera>();
+        entry.remove_component::<FlyCamera>();
+        if let Some(orbit) = controllers.orbit.take() {
+            entry.add_component(orbit);
+        }
+        if let Some(fly) = controllers.fly.take() {
+            entry.add_component(fly);
+        }
+        controllers.orbit = orbit;
+        controllers.fly = fly;
+    }
+
+    fn save_scene(&mut self) {
+        self.swap_camera_controllers();
+        let result = self.world.save(&self.scene_path);
+        self.swap_camera_controllers();
+        match result {
+            Ok(()) => log::info!("Saved scene to {}", self.scene_path),
+            Err(error) => log::error!("Failed to save scene: {}", error),
+        }
+    }
+
+    fn run_console_commands(&mut self, resources: &mut Resources) {
+        for command in resources.console.take_commands() {
+            let path = command.arguments.first();
+            match command.name.as_str() {
+                "save_scene" => {
+                    if let Some(path) = path {
+                        self.scene_path = path.to_string();
+                    }
+                    self.save_scene();
+                }
+                "open_scene" => {
+                    if let Some(path) = path {
+                        self.scene_path = path.to_string();
+                    }
+                    self.open_scene(resources.cvars);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn open_scene(&mut self, cvars: &mut CVars) {
+        match World::load(&self.scene_path) {
+            Ok(world) => {
+                self.world = world;
+                cvars.read_from_world(&self.world);
+                self.selected = None;
+                self.history.clear();
+                if let Err(error) = self.attach_camera_controller() {
+                    log::error!("Failed to attach a camera controller: {}", error);
+                }
+                log::info!("Opened scene {}", self.scene_path);
+            }
+            Err(error) => log::error!("Failed to open scene: {}", error),
+        }
+    }
+
+    fn project_settings_ui(&mut self, ui: &mut Ui, cvars: &mut CVars) {
+        ui.heading("Physics");
+        ui.horizontal(|ui| {
+            ui.label("Gravity");
+            let gravity = &mut self.world.physics.gravity;
+            ui.add(DragValue::new(&mut gravity.x).speed(0.1));
+            // Vertical gravity is the p.gravity cvar, which is applied to the world when it changes
+            let mut vertical = gravity.y;
+            if ui.add(DragValue::new(&mut vertical).speed(0.1)).changed() {
+                if let Err(error) = cvars.set(CVars::GRAVITY, vertical) {
+                    log::error!("{}", error);
+                }
+            }
+            ui.add(DragValue::new(&mut gravity.z).speed(0.1));
+        });
+
+        ui.separator();
+        ui.heading("Collision Layers");
+        let matrix = &mut self.world.physics.collision_matrix;
+        Grid::new("collision_layer_names").show(ui, |ui| {
+            for layer in 0..CollisionMatrix::MAX_LAYERS {
+                ui.label(format!("Layer {}", layer));
+                ui.text_edit_singleline(&mut matrix.layer_names[layer]);
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        ui.heading("Collision Matrix");
+        let layers = matrix.named_layers();
+        let mut changed = false;
+        Grid::new("collision_matrix").striped(true).show(ui, |ui| {
+            ui.label("");
+            for layer in layers.iter().rev() {
+                ui.label(&matrix.layer_names[*layer as usize]);
+            }
+            ui.end_row();
+            for (row, first) in layers.iter().enumerate() {
+                ui.label(&matrix.layer_names[*first as usize]);
+                for second in layers[row..].iter().rev() {
+                    let mut collides = matrix.collides(*first, *second);
+                    if ui.checkbox(&mut collides, "").changed() {
+                        matrix.set_collides(*first, *second, collides);
+                        changed = true;
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        if changed {
+            if let Err(error) = self.world.apply_collision_matrix() {
+                log::error!("Failed to apply collision matrix: {}", error);
+            }
+        }
+    }
+}
+
+fn simulation_controls_ui(ui: &mut Ui, resources: &mut Resources) {
+    let system = &mut resources.system;
+    let label = if system.paused { "▶" } else { "⏸" };
+    if ui
+        .button(label)
+        .on_hover_text("Pause or resume the simulation")
+        .clicked()
+    {
+        system.toggle_pause();
+    }
+    if ui.button("⏭").on_hover_text("Step one frame").clicked() {
+        system.step(SimulationStep::Frame);
+    }
+    if ui
+        .button("⏩")
+        .on_hover_text("Step one fixed update")
+        .clicked()
+    {
+        system.step(SimulationStep::FixedUpdate);
+    }
+    ui.label("Time Scale");
+    let mut time_scale = system.time_scale;
+    if ui
+        .add(
+            DragValue::new(&mut time_scale)
+                .speed(0.01)
+                .clamp_range(0.0..=10.0),
+        )
+        .changed()
+    {
+        if let Err(error) = resources.cvars.set(CVars::TIME_SCALE, time_scale) {
+            log::error!("{}", error);
+        }
+    }
+}
+
+fn display_settings_ui(ui: &mut Ui, display: &mut DisplaySettings, resources: &mut Resources) {
+    ui.heading("Display");
+    let monitors = resources.monitors();
+    Grid::new("display_settings").show(ui, |ui| {
+        ui.label("Mode");
+        ComboBox::from_id_source("window_mode")
+            .selected_text(format!("{:?}", display.mode))
+            .show_ui(ui, |ui| {
+                for mode in [
+                    WindowMode::Windowed,
+                    WindowMode::BorderlessFullscreen,
+                    WindowMode::ExclusiveFullscreen,
+                ] {
+                    ui.selectable_value(&mut display.mode, mode, format!("{:?}", mode));
+                }
+            });
+        ui.end_row();
+
+        ui.label("Monitor");
+        let selected_monitor = monitors
+            .iter()
+            .find(|monitor| Some(&monitor.id) == display.monitor.as_ref());
+        ComboBox::from_id_source("monitor")
+            .selected_text(selected_monitor.map_or("Current", |monitor| monitor.name.as_str()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut display.monitor, None, "Current");
+                for monitor in monitors.iter() {
+                    let label = if monitor.primary {
+                        format!("{} (Primary)", monitor.name)
+                    } else {
+                        monitor.name.to_string()
+                    };
+                    ui.selectable_value(&mut display.monitor, Some(monitor.id.clone()), label);
+                }
+            });
+        ui.end_row();
+
+        ui.label("Video Mode");
+        let video_modes = selected_monitor
+            .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+            .map(|monitor| monitor.video_modes.clone())
+            .unwrap_or_default();
+        ui.add_enabled_ui(display.mode == WindowMode::ExclusiveFullscreen, |ui| {
+            ComboBox::from_id_source("video_mode")
+                .selected_text(
+                    display
+                        .video_mode
+                        .map_or("Best".to_string(), |video_mode| video_mode.to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut display.video_mode, None, "Best");
+                    for video_mode in video_modes {
+                        ui.selectable_value(
+                            &mut display.video_mode,
+                            Some(video_mode),
+                            video_mode.to_string(),
+                        );
+                    }
+                });
+        });
+        ui.end_row();
+    });
+    if ui.button("Apply").clicked() {
+        if let Err(error) = display.apply(resources) {
+            log::error!("Failed to apply display settings: {}", error);
+        }
+        if let Err(error) = display.save(DisplaySettings::DEFAULT_PATH) {
+            log::error!("Failed to save display settings: {}", error);
+        }
+    }
+}
+
+fn entity_name(world: &World, entity: Entity) -> String {
+    match world.ecs.entry_ref(entity) {
+        Ok(entry) => match entry.get_component::<Name>() {
+            Ok(name) => name.0.to_string(),
+            Err(_) => format!("{:?}", entity),
+        },
+        Err(_) => format!("{:?}", entity),
+    }
+}
+
+/// Runs the editor with project-specific plugins, such as a game crate's custom panels.
+/// `editor build --game <package> --platform <name>` packages a release without opening a window.
+pub fn run_editor(mut plugins: Vec<Box<dyn EditorPlugin>>) -> Result<()> {
+    measure::register_components()?;
+    for plugin in plugins.iter_mut() {
+        plugin.on_start()?;
+    }
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("build") {
+        env_logger::init();
+        build::build(&BuildSettings::from_args(&args[1..])?)?;
+        return Ok(());
+    }
+
+    let display = if std::path::Path::new(DisplaySettings::DEFAULT_PATH).exists() {
+        DisplaySettings::load(DisplaySettings::DEFAULT_PATH).unwrap_or_else(|error| {
+            log::error!("Failed to load display settings: {}", error);
+            DisplaySettings::default()
+        })
+    } else {
+        DisplaySettings::default()
+    };
+
+    run(
+        Editor {
+            plugins,
+            display: display.clone(),
+            ..Default::default()
+        },
+        AppConfig {
+            icon: Some("assets/icon/phantom.png".to_string()),
+            display,
+            ..Default::default()
+        },
+    )
+}
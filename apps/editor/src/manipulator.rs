@@ -0,0 +1,619 @@
+use crate::{
+    gizmos::{SceneViewport, ScreenProjection},
+    history::{Command, History},
+};
+use phantom::{
+    dependencies::{
+        anyhow::Result,
+        egui::{
+            vec2, Color32, CtxRef, CursorIcon, DragValue, Key, LayerId, Painter, Pos2, Rect, Shape,
+            Stroke, Window,
+        },
+        nalgebra_glm as glm,
+        rapier3d::geometry::Ray,
+    },
+    world::{Entity, Transform, World},
+};
+use std::{cmp::Ordering, f32::consts::TAU};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Whether the handles follow the world axes or the selected entity's rotation.
+/// Scaling always happens along the entity's own axes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GizmoSpace {
+    Local,
+    World,
+}
+
+/// A part of the gizmo that can be dragged
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Handle {
+    Axis(usize),
+
+    /// Translates within the plane perpendicular to the axis
+    Plane(usize),
+
+    /// Scales every axis at once
+    Center,
+}
+
+/// Where the gizmo is drawn, in world space
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    origin: glm::Vec3,
+    axes: [glm::Vec3; 3],
+
+    /// Length of the axis handles, which keeps the gizmo the same size on screen
+    size: f32,
+}
+
+/// What the pointer grabbed when the drag started
+#[derive(Debug, Copy, Clone)]
+enum Anchor {
+    /// Distance along the handle's axis
+    Axis(f32),
+    Plane(glm::Vec3),
+    Pointer(Pos2),
+}
+
+struct Drag {
+    entity: Entity,
+    handle: Handle,
+    frame: Frame,
+    anchor: Anchor,
+
+    /// The entity's local transform and its parent's global transform when the drag started
+    start: Transform,
+    parent: glm::Mat4,
+}
+
+/// Translate, rotate and scale handles for the selected entity
+pub struct TransformGizmo {
+    pub open: bool,
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+
+    /// Snaps to the increments below while dragging. Holding ctrl snaps as well.
+    pub snap: bool,
+    pub translation_increment: f32,
+
+    /// In degrees
+    pub rotation_increment: f32,
+    pub scale_increment: f32,
+    dragging: Option<Drag>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            open: false,
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snap: false,
+            translation_increment: 0.5,
+            rotation_increment: 15.0,
+            scale_increment: 0.1,
+            dragging: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    const SIZE_IN_PIXELS: f32 = 80.0;
+    const PICK_RADIUS: f32 = 6.0;
+    const CIRCLE_SEGMENTS: usize = 48;
+    const MINIMUM_SCALE: f32 = 0.001;
+
+    /// Plane handles are squares spanning this range of the axes' length
+    const PLANE_START: f32 = 0.2;
+    const PLANE_END: f32 = 0.4;
+
+    /// Draws the gizmo over the selected entity and drags its handles.
+    /// Returns true while the pointer is over a handle or dragging one,
+    /// so clicks on the gizmo don't select whatever is behind it.
+    pub fn ui(
+        &mut self,
+        ctx: &CtxRef,
+        world: &mut World,
+        viewport: &SceneViewport,
+        selected: Option<Entity>,
+        shortcuts: bool,
+        history: &mut History,
+    ) -> Result<bool> {
+        self.settings_ui(ctx, shortcuts);
+
+        let entity = match selected {
+            Some(entity)
+                if world
+                    .ecs
+                    .entry_ref(entity)
+                    .map_or(false, |entry| entry.get_component::<Transform>().is_ok()) =>
+            {
+                entity
+            }
+            _ => {
+                self.dragging = None;
+                return Ok(false);
+            }
+        };
+        if self
+            .dragging
+            .as_ref()
+            .map_or(false, |drag| drag.entity != entity)
+        {
+            self.dragging = None;
+        }
+
+        let screen_projection = match ScreenProjection::new(world, viewport) {
+            Some(screen_projection) => screen_projection,
+            None => return Ok(false),
+        };
+        let frame = match self.dragging.as_ref() {
+            Some(drag) => drag.frame,
+            None => match self.frame(world, &screen_projection, entity)? {
+                Some(frame) => frame,
+                None => return Ok(false),
+            },
+        };
+
+        let (pointer, pressed, down, ctrl) = {
+            let input = ctx.input();
+            (
+                input.pointer.hover_pos(),
+                input.pointer.any_pressed(),
+                input.pointer.primary_down(),
+                input.modifiers.ctrl,
+            )
+        };
+        let pointer = pointer.filter(|_| self.dragging.is_some() || !ctx.is_pointer_over_area());
+        let hovered = match (self.dragging.as_ref(), pointer) {
+            (Some(drag), _) => Some(drag.handle),
+            (None, Some(pointer)) => self.hovered_handle(&screen_projection, &frame, pointer),
+            (None, None) => None,
+        };
+
+        let painter = ctx.layer_painter(LayerId::background());
+        self.paint(&painter, &screen_projection, &frame, hovered);
+
+        if let Some(drag) = self.dragging.as_ref() {
+            ctx.output().cursor_icon = CursorIcon::Grabbing;
+            match (down, pointer) {
+                (true, Some(pointer)) => {
+                    let transform = screen_projection.cursor_ray().and_then(|ray| {
+                        self.dragged_transform(drag, &screen_projection, &ray, pointer, ctrl)
+                    });
+                    if let Some(transform) = transform {
+                        world.modify_component::<Transform>(entity, |current| {
+                            *current = transform
+                        })?;
+                    }
+                }
+                _ => {
+                    // The whole drag is undone at once
+                    let after = *world.ecs.entry_ref(entity)?.get_component::<Transform>()?;
+                    history.record(Command::Transform {
+                        entity,
+                        before: drag.start,
+                        after,
+                    });
+                    self.dragging = None;
+                }
+            }
+            return Ok(true);
+        }
+
+        let (handle, pointer) = match (hovered, pointer) {
+            (Some(handle), Some(pointer)) => (handle, pointer),
+            _ => return Ok(false),
+        };
+        ctx.output().cursor_icon = CursorIcon::Grab;
+        if pressed && down {
+            let anchor = screen_projection
+                .cursor_ray()
+                .and_then(|ray| self.anchor(&frame, handle, &ray, pointer));
+            if let Some(anchor) = anchor {
+                let start = *world.ecs.entry_ref(entity)?.get_component::<Transform>()?;
+                self.dragging = Some(Drag {
+                    entity,
+                    handle,
+                    frame,
+                    anchor,
+                    start,
+                    parent: world.parent_global_transform(entity)?,
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    fn settings_ui(&mut self, ctx: &CtxRef, shortcuts: bool) {
+        if shortcuts && !ctx.wants_keyboard_input() {
+            let input = ctx.input();
+            if input.key_pressed(Key::W) {
+                self.mode = GizmoMode::Translate;
+            } else if input.key_pressed(Key::E) {
+                self.mode = GizmoMode::Rotate;
+            } else if input.key_pressed(Key::R) {
+                self.mode = GizmoMode::Scale;
+            }
+        }
+
+        let mut open = self.open;
+        Window::new("Transform Gizmo")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.mode, GizmoMode::Translate, "Translate (W)");
+                    ui.radio_value(&mut self.mode, GizmoMode::Rotate, "Rotate (E)");
+                    ui.radio_value(&mut self.mode, GizmoMode::Scale, "Scale (R)");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Space");
+                    ui.radio_value(&mut self.space, GizmoSpace::World, "World");
+                    ui.radio_value(&mut self.space, GizmoSpace::Local, "Local");
+                });
+                ui.checkbox(&mut self.snap, "Snap (or hold Ctrl)");
+                ui.horizontal(|ui| {
+                    ui.label("Translation");
+                    ui.add(
+                        DragValue::new(&mut self.translation_increment)
+                            .speed(0.05)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                    ui.label("Rotation");
+                    ui.add(
+                        DragValue::new(&mut self.rotation_increment)
+                            .speed(1.0)
+                            .clamp_range(0.0..=180.0)
+                            .suffix("°"),
+                    );
+                    ui.label("Scale");
+                    ui.add(
+                        DragValue::new(&mut self.scale_increment)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                });
+            });
+        self.open = open;
+    }
+
+    fn frame(
+        &self,
+        world: &World,
+        screen_projection: &ScreenProjection,
+        entity: Entity,
+    ) -> Result<Option<Frame>> {
+        let global = world.entity_global_transform(entity)?;
+        let axes = match (self.mode, self.space) {
+            (GizmoMode::Translate, GizmoSpace::World) | (GizmoMode::Rotate, GizmoSpace::World) => {
+                [glm::Vec3::x(), glm::Vec3::y(), glm::Vec3::z()]
+            }
+            _ => [glm::Vec3::x(), glm::Vec3::y(), glm::Vec3::z()]
+                .map(|axis| glm::quat_rotate_vec3(&global.rotation, &axis)),
+        };
+        Ok(screen_projection
+            .pixels_per_unit(&global.translation)
+            .map(|pixels| Frame {
+                origin: global.translation,
+                axes,
+                size: Self::SIZE_IN_PIXELS / pixels,
+            }))
+    }
+
+    fn hovered_handle(
+        &self,
+        screen_projection: &ScreenProjection,
+        frame: &Frame,
+        pointer: Pos2,
+    ) -> Option<Handle> {
+        let project = |point: &glm::Vec3| screen_projection.project(point);
+        let origin = project(&frame.origin)?;
+        match self.mode {
+            GizmoMode::Translate => {
+                let plane = (0..3).find(|axis| {
+                    let corners = plane_corners(frame, *axis)
+                        .iter()
+                        .map(project)
+                        .collect::<Option<Vec<_>>>();
+                    corners.map_or(false, |corners| inside_quad(&corners, pointer))
+                });
+                if let Some(axis) = plane {
+                    return Some(Handle::Plane(axis));
+                }
+            }
+            GizmoMode::Scale => {
+                if origin.distance(pointer) <= Self::PICK_RADIUS * 1.5 {
+                    return Some(Handle::Center);
+                }
+            }
+            GizmoMode::Rotate => {}
+        }
+
+        (0..3)
+            .filter_map(|axis| {
+                let points = match self.mode {
+                    GizmoMode::Rotate => circle_points(frame, axis)
+                        .iter()
+                        .map(project)
+                        .collect::<Option<Vec<_>>>()?,
+                    _ => vec![
+                        origin,
+                        project(&(frame.origin + frame.axes[axis] * frame.size))?,
+                    ],
+                };
+                let distance = points
+                    .windows(2)
+                    .map(|segment| distance_to_segment(pointer, segment[0], segment[1]))
+                    .fold(f32::MAX, f32::min);
+                Some((axis, distance))
+            })
+            .filter(|(_, distance)| *distance <= Self::PICK_RADIUS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .map(|(axis, _)| Handle::Axis(axis))
+    }
+
+    fn anchor(&self, frame: &Frame, handle: Handle, ray: &Ray, pointer: Pos2) -> Option<Anchor> {
+        match (self.mode, handle) {
+            (GizmoMode::Rotate, Handle::Axis(axis)) | (_, Handle::Plane(axis)) => {
+                ray_plane(ray, &frame.origin, &frame.axes[axis]).map(Anchor::Plane)
+            }
+            (_, Handle::Axis(axis)) => {
+                ray_axis(ray, &frame.origin, &frame.axes[axis]).map(Anchor::Axis)
+            }
+            (_, Handle::Center) => Some(Anchor::Pointer(pointer)),
+        }
+    }
+
+    /// The entity's local transform with the drag applied
+    fn dragged_transform(
+        &self,
+        drag: &Drag,
+        screen_projection: &ScreenProjection,
+        ray: &Ray,
+        pointer: Pos2,
+        ctrl: bool,
+    ) -> Option<Transform> {
+        let snap = self.snap || ctrl;
+        let frame = &drag.frame;
+        let mut transform = drag.start;
+        match (self.mode, drag.handle, drag.anchor) {
+            (GizmoMode::Translate, Handle::Axis(axis), Anchor::Axis(start)) => {
+                let distance = ray_axis(ray, &frame.origin, &frame.axes[axis])? - start;
+                let distance = snapped(distance, self.translation_increment, snap);
+                transform.translation += self.local_offset(drag, &(frame.axes[axis] * distance));
+            }
+            (GizmoMode::Translate, Handle::Plane(axis), Anchor::Plane(start)) => {
+                let offset = ray_plane(ray, &frame.origin, &frame.axes[axis])? - start;
+                let offset = [(axis + 1) % 3, (axis + 2) % 3]
+                    .iter()
+                    .map(|other| {
+                        let distance = glm::dot(&offset, &frame.axes[*other]);
+                        frame.axes[*other] * snapped(distance, self.translation_increment, snap)
+                    })
+                    .fold(glm::Vec3::zeros(), |sum, offset| sum + offset);
+                transform.translation += self.local_offset(drag, &offset);
+            }
+            (GizmoMode::Rotate, Handle::Axis(axis), Anchor::Plane(start)) => {
+                let normal = frame.axes[axis];
+                let from = start - frame.origin;
+                let to = ray_plane(ray, &frame.origin, &normal)? - frame.origin;
+                let angle = glm::dot(&glm::cross(&from, &to), &normal).atan2(glm::dot(&from, &to));
+                let angle = snapped(angle.to_degrees(), self.rotation_increment, snap).to_radians();
+                transform.rotation = match self.space {
+                    GizmoSpace::Local => {
+                        let local_axis = [glm::Vec3::x(), glm::Vec3::y(), glm::Vec3::z()][axis];
+                        drag.start.rotation * glm::quat_angle_axis(angle, &local_axis)
+                    }
+                    GizmoSpace::World => {
+                        let parent_rotation = Transform::from(drag.parent).rotation;
+                        glm::quat_inverse(&parent_rotation)
+                            * glm::quat_angle_axis(angle, &normal)
+                            * parent_rotation
+                            * drag.start.rotation
+                    }
+                };
+                transform.rotation = glm::quat_normalize(&transform.rotation);
+            }
+            (GizmoMode::Scale, Handle::Axis(axis), Anchor::Axis(start)) => {
+                if start.abs() <= f32::EPSILON {
+                    return None;
+                }
+                let factor = ray_axis(ray, &frame.origin, &frame.axes[axis])? / start;
+                transform.scale[axis] = self.scaled(drag.start.scale[axis], factor, snap);
+            }
+            (GizmoMode::Scale, Handle::Center, Anchor::Pointer(start)) => {
+                let origin = screen_projection.project(&frame.origin)?;
+                let start_distance = origin.distance(start);
+                if start_distance <= f32::EPSILON {
+                    return None;
+                }
+                let factor = origin.distance(pointer) / start_distance;
+                for axis in 0..3 {
+                    transform.scale[axis] = self.scaled(drag.start.scale[axis], factor, snap);
+                }
+            }
+            _ => return None,
+        }
+        Some(transform)
+    }
+
+    /// Brings a world space offset into the space of the entity's parent
+    fn local_offset(&self, drag: &Drag, offset: &glm::Vec3) -> glm::Vec3 {
+        (glm::inverse(&drag.parent) * glm::vec4(offset.x, offset.y, offset.z, 0.0)).xyz()
+    }
+
+    fn scaled(&self, scale: f32, factor: f32, snap: bool) -> f32 {
+        let scale = snapped(scale * factor, self.scale_increment, snap);
+        if scale.abs() < Self::MINIMUM_SCALE {
+            Self::MINIMUM_SCALE.copysign(scale)
+        } else {
+            scale
+        }
+    }
+
+    fn paint(
+        &self,
+        painter: &Painter,
+        screen_projection: &ScreenProjection,
+        frame: &Frame,
+        hovered: Option<Handle>,
+    ) {
+        let color = |handle: Handle, axis: usize| {
+            if hovered == Some(handle) {
+                Color32::YELLOW
+            } else {
+                [
+                    Color32::RED,
+                    Color32::GREEN,
+                    Color32::from_rgb(80, 120, 255),
+                ][axis]
+            }
+        };
+        let project = |point: &glm::Vec3| screen_projection.project(point);
+
+        for axis in 0..3 {
+            let stroke = Stroke::new(2.5, color(Handle::Axis(axis), axis));
+            if self.mode == GizmoMode::Rotate {
+                if let Some(points) = circle_points(frame, axis)
+                    .iter()
+                    .map(project)
+                    .collect::<Option<Vec<_>>>()
+                {
+                    painter.add(Shape::line(points, stroke));
+                }
+                continue;
+            }
+
+            let tip = frame.origin + frame.axes[axis] * frame.size;
+            screen_projection.line(painter, &frame.origin, &tip, stroke);
+            if let Some(tip) = project(&tip) {
+                match self.mode {
+                    GizmoMode::Scale => painter.rect_filled(
+                        Rect::from_center_size(tip, vec2(8.0, 8.0)),
+                        0.0,
+                        stroke.color,
+                    ),
+                    _ => painter.circle_filled(tip, 5.0, stroke.color),
+                }
+            }
+
+            if self.mode == GizmoMode::Translate {
+                if let Some(corners) = plane_corners(frame, axis)
+                    .iter()
+                    .map(project)
+                    .collect::<Option<Vec<_>>>()
+                {
+                    let plane_color = color(Handle::Plane(axis), axis);
+                    painter.add(Shape::convex_polygon(
+                        corners,
+                        plane_color.linear_multiply(0.3),
+                        Stroke::new(1.0, plane_color),
+                    ));
+                }
+            }
+        }
+
+        if self.mode == GizmoMode::Scale {
+            if let Some(origin) = project(&frame.origin) {
+                let center_color = if hovered == Some(Handle::Center) {
+                    Color32::YELLOW
+                } else {
+                    Color32::WHITE
+                };
+                painter.rect_filled(
+                    Rect::from_center_size(origin, vec2(10.0, 10.0)),
+                    0.0,
+                    center_color,
+                );
+            }
+        }
+    }
+}
+
+fn snapped(value: f32, increment: f32, snap: bool) -> f32 {
+    if snap && increment > 0.0 {
+        (value / increment).round() * increment
+    } else {
+        value
+    }
+}
+
+/// The corners of the square handle for translating in the plane perpendicular to an axis
+fn plane_corners(frame: &Frame, axis: usize) -> [glm::Vec3; 4] {
+    let first = frame.axes[(axis + 1) % 3] * frame.size;
+    let second = frame.axes[(axis + 2) % 3] * frame.size;
+    let corner = |a: f32, b: f32| frame.origin + first * a + second * b;
+    let (start, end) = (TransformGizmo::PLANE_START, TransformGizmo::PLANE_END);
+    [
+        corner(start, start),
+        corner(end, start),
+        corner(end, end),
+        corner(start, end),
+    ]
+}
+
+/// The ring for rotating about an axis, closed by repeating the first point
+fn circle_points(frame: &Frame, axis: usize) -> Vec<glm::Vec3> {
+    let first = frame.axes[(axis + 1) % 3] * frame.size;
+    let second = frame.axes[(axis + 2) % 3] * frame.size;
+    (0..=TransformGizmo::CIRCLE_SEGMENTS)
+        .map(|segment| {
+            let angle = segment as f32 / TransformGizmo::CIRCLE_SEGMENTS as f32 * TAU;
+            frame.origin + first * angle.cos() + second * angle.sin()
+        })
+        .collect()
+}
+
+/// Distance along an axis through `origin` to the point closest to the ray
+fn ray_axis(ray: &Ray, origin: &glm::Vec3, axis: &glm::Vec3) -> Option<f32> {
+    let offset = origin - ray.origin.coords;
+    let alignment = glm::dot(axis, &ray.dir);
+    let denominator = 1.0 - alignment * alignment;
+    // The axis points almost straight at the camera
+    if denominator.abs() <= 1e-4 {
+        return None;
+    }
+    Some((alignment * glm::dot(&ray.dir, &offset) - glm::dot(axis, &offset)) / denominator)
+}
+
+fn ray_plane(ray: &Ray, origin: &glm::Vec3, normal: &glm::Vec3) -> Option<glm::Vec3> {
+    let denominator = glm::dot(normal, &ray.dir);
+    if denominator.abs() <= 1e-4 {
+        return None;
+    }
+    let distance = glm::dot(&(origin - ray.origin.coords), normal) / denominator;
+    (distance >= 0.0).then(|| ray.origin.coords + ray.dir * distance)
+}
+
+fn distance_to_segment(point: Pos2, start: Pos2, end: Pos2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_sq();
+    if length_squared <= f32::EPSILON {
+        return point.distance(start);
+    }
+    let to_point = point - start;
+    let along =
+        ((to_point.x * segment.x + to_point.y * segment.y) / length_squared).clamp(0.0, 1.0);
+    point.distance(start + segment * along)
+}
+
+/// Whether a point is inside a convex quad, wound either way
+fn inside_quad(corners: &[Pos2], point: Pos2) -> bool {
+    let sides = (0..corners.len())
+        .map(|index| {
+            let start = corners[index];
+            let end = corners[(index + 1) % corners.len()];
+            let edge = end - start;
+            let to_point = point - start;
+            edge.x * to_point.y - edge.y * to_point.x
+        })
+        .collect::<Vec<_>>();
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
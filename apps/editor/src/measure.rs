@@ -0,0 +1,246 @@
+use crate::gizmos::{SceneViewport, ScreenProjection};
+use phantom::{
+    dependencies::{
+        anyhow::Result,
+        egui::{Align2, Color32, CtxRef, LayerId, Painter, Stroke, TextStyle, Ui, Window},
+        legion::IntoQuery,
+        nalgebra_glm as glm,
+        serde::{Deserialize, Serialize},
+    },
+    world::{register_component, Entity, Name, Transform, World},
+};
+
+/// An editor-only note left at a point in the scene for the rest of the team
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom::dependencies::serde")]
+pub struct Annotation {
+    pub text: String,
+}
+
+pub fn register_components() -> Result<()> {
+    register_component::<Annotation>("annotation")
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MeasureMode {
+    Distance,
+    Angle,
+    Annotate,
+}
+
+impl MeasureMode {
+    fn number_of_points(&self) -> usize {
+        match self {
+            MeasureMode::Distance => 2,
+            MeasureMode::Angle => 3,
+            MeasureMode::Annotate => 1,
+        }
+    }
+}
+
+/// Measures distances and angles between points picked on surfaces in the viewport
+pub struct MeasureTool {
+    pub open: bool,
+    mode: MeasureMode,
+    points: Vec<glm::Vec3>,
+    note: String,
+}
+
+impl Default for MeasureTool {
+    fn default() -> Self {
+        Self {
+            open: false,
+            mode: MeasureMode::Distance,
+            points: Vec::new(),
+            note: String::new(),
+        }
+    }
+}
+
+impl MeasureTool {
+    const MAX_DISTANCE: f32 = 1000.0;
+
+    pub fn ui(&mut self, ctx: &CtxRef, world: &mut World, viewport: &SceneViewport) -> Result<()> {
+        let mut removed = Vec::new();
+        let mut open = self.open;
+        Window::new("Measure").open(&mut open).show(ctx, |ui| {
+            self.controls_ui(ui);
+            ui.separator();
+            annotations_ui(ui, world, &mut removed);
+        });
+        self.open = open;
+        for entity in removed {
+            world.remove_entity(entity)?;
+        }
+
+        let screen_projection = match ScreenProjection::new(world, viewport) {
+            Some(screen_projection) => screen_projection,
+            None => return Ok(()),
+        };
+
+        let painter = ctx.layer_painter(LayerId::background());
+        paint_annotations(&painter, &screen_projection, world)?;
+        if !self.open {
+            self.points.clear();
+            return Ok(());
+        }
+        self.paint_measurement(&painter, &screen_projection);
+
+        let (pressed, down) = {
+            let input = ctx.input();
+            (input.pointer.any_pressed(), input.pointer.primary_down())
+        };
+        let ray = match screen_projection.cursor_ray() {
+            Some(ray) if pressed && down && !ctx.is_pointer_over_area() => ray,
+            _ => return Ok(()),
+        };
+        if let Some(hit) = world.surface_hit(&ray, Self::MAX_DISTANCE) {
+            self.add_point(world, hit.point)?;
+        }
+        Ok(())
+    }
+
+    fn controls_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mode = self.mode;
+            ui.radio_value(&mut self.mode, MeasureMode::Distance, "Distance");
+            ui.radio_value(&mut self.mode, MeasureMode::Angle, "Angle");
+            ui.radio_value(&mut self.mode, MeasureMode::Annotate, "Annotate");
+            if self.mode != mode {
+                self.points.clear();
+            }
+        });
+
+        match self.mode {
+            MeasureMode::Distance => {
+                ui.label("Click two points in the viewport");
+                if let [start, end] = self.points[..] {
+                    ui.label(format!("Distance: {:.3}", glm::distance(&start, &end)));
+                }
+            }
+            MeasureMode::Angle => {
+                ui.label("Click three points in the viewport, the second is the vertex");
+                if let [first, vertex, last] = self.points[..] {
+                    ui.label(format!(
+                        "Angle: {:.2}°",
+                        angle(&first, &vertex, &last).to_degrees()
+                    ));
+                }
+            }
+            MeasureMode::Annotate => {
+                ui.label("Click in the viewport to leave a note");
+                ui.text_edit_multiline(&mut self.note);
+            }
+        }
+
+        if ui.button("Clear").clicked() {
+            self.points.clear();
+        }
+    }
+
+    fn add_point(&mut self, world: &mut World, point: glm::Vec3) -> Result<()> {
+        if self.mode == MeasureMode::Annotate {
+            if self.note.trim().is_empty() {
+                return Ok(());
+            }
+            let entity = world.ecs.push((
+                Name("Annotation".to_string()),
+                Transform {
+                    translation: point,
+                    ..Default::default()
+                },
+                Annotation {
+                    text: self.note.to_string(),
+                },
+            ));
+            world.scene.default_scenegraph_mut()?.add_node(entity);
+            self.note.clear();
+            return Ok(());
+        }
+
+        // Clicking again after a finished measurement starts a new one
+        if self.points.len() >= self.mode.number_of_points() {
+            self.points.clear();
+        }
+        self.points.push(point);
+        Ok(())
+    }
+
+    fn paint_measurement(&self, painter: &Painter, screen_projection: &ScreenProjection) {
+        let stroke = Stroke::new(2.0, Color32::LIGHT_GREEN);
+        for point in self.points.iter() {
+            if let Some(position) = screen_projection.project(point) {
+                painter.circle_filled(position, 4.0, stroke.color);
+            }
+        }
+        for segment in self.points.windows(2) {
+            screen_projection.line(painter, &segment[0], &segment[1], stroke);
+        }
+
+        let label = match (self.mode, &self.points[..]) {
+            (MeasureMode::Distance, [start, end]) => Some((
+                (start + end) / 2.0,
+                format!("{:.3}", glm::distance(start, end)),
+            )),
+            (MeasureMode::Angle, [first, vertex, last]) => Some((
+                *vertex,
+                format!("{:.2}°", angle(first, vertex, last).to_degrees()),
+            )),
+            _ => None,
+        };
+        if let Some((point, text)) = label {
+            if let Some(position) = screen_projection.project(&point) {
+                painter.text(
+                    position,
+                    Align2::LEFT_BOTTOM,
+                    text,
+                    TextStyle::Body,
+                    stroke.color,
+                );
+            }
+        }
+    }
+}
+
+/// The angle at the vertex between the lines to the other two points, in radians
+fn angle(first: &glm::Vec3, vertex: &glm::Vec3, last: &glm::Vec3) -> f32 {
+    glm::angle(&(first - vertex), &(last - vertex))
+}
+
+fn annotations_ui(ui: &mut Ui, world: &mut World, removed: &mut Vec<Entity>) {
+    ui.heading("Annotations");
+    let mut query = <(Entity, &mut Annotation)>::query();
+    for (entity, annotation) in query.iter_mut(&mut world.ecs) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut annotation.text);
+            if ui.small_button("✖").clicked() {
+                removed.push(*entity);
+            }
+        });
+    }
+}
+
+fn paint_annotations(
+    painter: &Painter,
+    screen_projection: &ScreenProjection,
+    world: &World,
+) -> Result<()> {
+    let mut query = <(Entity, &Annotation)>::query();
+    for (entity, annotation) in query.iter(&world.ecs) {
+        if !world.is_enabled_in_hierarchy(*entity) {
+            continue;
+        }
+        let transform = world.entity_global_transform_matrix(*entity)?;
+        let point = glm::vec3(transform[(0, 3)], transform[(1, 3)], transform[(2, 3)]);
+        if let Some(position) = screen_projection.project(&point) {
+            painter.text(
+                position,
+                Align2::LEFT_BOTTOM,
+                format!("📌 {}", annotation.text),
+                TextStyle::Body,
+                Color32::GOLD,
+            );
+        }
+    }
+    Ok(())
+}
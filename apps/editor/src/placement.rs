@@ -0,0 +1,210 @@
+use crate::{
+    gizmos::{SceneViewport, ScreenProjection},
+    history::History,
+};
+use phantom::{
+    dependencies::{
+        anyhow::Result,
+        egui::{Color32, CtxRef, Label, LayerId, ScrollArea, Sense, Stroke, Window},
+        log, nalgebra_glm as glm,
+    },
+    world::{load_gltf, SurfaceHit, Transform, World},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Snapping {
+    None,
+    Vertex,
+    Edge,
+}
+
+/// Places assets dragged from the asset list onto the surface under the cursor
+pub struct PlacementTool {
+    pub open: bool,
+    directory: PathBuf,
+    assets: Vec<PathBuf>,
+    loaded: bool,
+    dragging: Option<PathBuf>,
+    dropped: Option<PathBuf>,
+    snapping: Snapping,
+    align_to_normal: bool,
+}
+
+impl Default for PlacementTool {
+    fn default() -> Self {
+        Self {
+            open: false,
+            directory: PathBuf::from("assets/models"),
+            assets: Vec::new(),
+            loaded: false,
+            dragging: None,
+            dropped: None,
+            snapping: Snapping::None,
+            align_to_normal: false,
+        }
+    }
+}
+
+impl PlacementTool {
+    const MAX_DISTANCE: f32 = 1000.0;
+
+    /// Starts placing an asset dragged from elsewhere, such as the asset browser
+    pub fn begin_drag(&mut self, path: &Path) {
+        self.dragging = Some(path.to_path_buf());
+    }
+
+    /// Places the dragged asset under the pointer, if it's over the viewport
+    pub fn end_drag(&mut self) {
+        self.dropped = self.dragging.take();
+    }
+
+    pub fn ui(
+        &mut self,
+        ctx: &CtxRef,
+        world: &mut World,
+        viewport: &SceneViewport,
+        history: &mut History,
+    ) -> Result<()> {
+        if self.open && !self.loaded {
+            self.load()?;
+            self.loaded = true;
+        }
+
+        let mut dropped = self.dropped.take();
+        let mut open = self.open;
+        Window::new("Placement").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Snapping");
+                ui.radio_value(&mut self.snapping, Snapping::None, "None");
+                ui.radio_value(&mut self.snapping, Snapping::Vertex, "Vertex");
+                ui.radio_value(&mut self.snapping, Snapping::Edge, "Edge");
+            });
+            ui.checkbox(&mut self.align_to_normal, "Align to surface normal");
+            if ui.button("Refresh").clicked() {
+                self.loaded = false;
+            }
+            ui.separator();
+            ui.label("Drag an asset into the viewport to place it");
+            ScrollArea::vertical().show(ui, |ui| {
+                for path in self.assets.iter() {
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let response = ui.add(Label::new(name).sense(Sense::drag()));
+                    if response.drag_started() {
+                        self.dragging = Some(path.to_path_buf());
+                    }
+                    if response.drag_released() {
+                        dropped = self.dragging.take();
+                    }
+                }
+            });
+        });
+        // Closing the window mid drag cancels the placement
+        if self.open && !open {
+            self.dragging = None;
+        }
+        self.open = open;
+
+        if self.dragging.is_none() && dropped.is_none() {
+            return Ok(());
+        }
+
+        if ctx.is_pointer_over_area() {
+            return Ok(());
+        }
+        let screen_projection = match ScreenProjection::new(world, viewport) {
+            Some(screen_projection) => screen_projection,
+            None => return Ok(()),
+        };
+        let hit = match screen_projection
+            .cursor_ray()
+            .and_then(|ray| world.surface_hit(&ray, Self::MAX_DISTANCE))
+        {
+            Some(hit) => hit,
+            None => return Ok(()),
+        };
+        let position = self.snap(&hit);
+
+        match dropped {
+            Some(path) => history.edit_entities("Place", world, |world| {
+                self.place(world, &path, &position, &hit.normal)
+            })?,
+            None => {
+                let painter = ctx.layer_painter(LayerId::debug());
+                let stroke = Stroke::new(2.0, Color32::LIGHT_GREEN);
+                screen_projection.line(&painter, &position, &(position + hit.normal), stroke);
+                if let Some(position) = screen_projection.project(&position) {
+                    painter.circle_stroke(position, 6.0, stroke);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn snap(&self, hit: &SurfaceHit) -> glm::Vec3 {
+        let snapped = match self.snapping {
+            Snapping::None => None,
+            Snapping::Vertex => hit.nearest_vertex(),
+            Snapping::Edge => hit.nearest_edge_point(),
+        };
+        snapped.unwrap_or(hit.point)
+    }
+
+    /// Loads the asset and moves its root nodes to the placement point
+    fn place(
+        &self,
+        world: &mut World,
+        path: &Path,
+        position: &glm::Vec3,
+        normal: &glm::Vec3,
+    ) -> Result<()> {
+        let first_graph = world.scene.graphs.len();
+        load_gltf(path, world)?;
+
+        let rotation = if self.align_to_normal {
+            glm::quat_rotation(&glm::Vec3::y(), normal)
+        } else {
+            glm::Quat::identity()
+        };
+        let roots = world.scene.graphs[first_graph..]
+            .iter()
+            .flat_map(|graph| {
+                graph
+                    .root_nodes()
+                    .into_iter()
+                    .map(move |index| graph[index])
+            })
+            .collect::<Vec<_>>();
+        for entity in roots {
+            world.modify_component::<Transform>(entity, |transform| {
+                transform.translation =
+                    position + glm::quat_rotate_vec3(&rotation, &transform.translation);
+                transform.rotation = rotation * transform.rotation;
+            })?;
+        }
+        log::info!("Placed {} at {:?}", path.display(), position);
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<()> {
+        self.assets.clear();
+        if !self.directory.exists() {
+            return Ok(());
+        }
+        self.assets = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "gltf" || extension == "glb")
+            })
+            .collect();
+        self.assets.sort();
+        Ok(())
+    }
+}
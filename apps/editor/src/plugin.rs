@@ -0,0 +1,78 @@
+use phantom::{
+    dependencies::{
+        anyhow::Result,
+        egui::{CollapsingHeader, CtxRef, Ui},
+        legion::storage::Component,
+    },
+    world::{Entity, World},
+};
+
+/// Extends the editor with project-specific tooling.
+/// Every method has a default, so plugins only implement what they need.
+pub trait EditorPlugin {
+    /// Shown as the plugin's menu in the menu bar
+    fn name(&self) -> &str;
+
+    /// Called once before the editor starts or makes a build.
+    /// Custom components should be registered here so scenes using them can be opened.
+    fn on_start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the plugin adds its own menu to the menu bar
+    fn has_menu(&self) -> bool {
+        false
+    }
+
+    fn menu_ui(&mut self, _ui: &mut Ui, _world: &mut World) {}
+
+    /// Draws the plugin's own windows and panels
+    fn ui(&mut self, _ctx: &CtxRef, _world: &mut World) -> Result<()> {
+        Ok(())
+    }
+
+    /// Draws widgets in the inspector for the selected entity
+    fn inspector_ui(&mut self, _ui: &mut Ui, _world: &mut World, _entity: Entity) {}
+}
+
+/// Shows a widget in the inspector for entities with a `T` component.
+/// The widget returns whether it changed the component, so the change can be tracked.
+pub struct ComponentInspector<T> {
+    name: String,
+    widget: fn(&mut Ui, &mut T) -> bool,
+}
+
+impl<T> ComponentInspector<T> {
+    pub fn new(name: &str, widget: fn(&mut Ui, &mut T) -> bool) -> Self {
+        Self {
+            name: name.to_string(),
+            widget,
+        }
+    }
+}
+
+impl<T: Component> EditorPlugin for ComponentInspector<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn inspector_ui(&mut self, ui: &mut Ui, world: &mut World, entity: Entity) {
+        let mut entry = match world.ecs.entry(entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let component = match entry.get_component_mut::<T>() {
+            Ok(component) => component,
+            Err(_) => return,
+        };
+        let widget = self.widget;
+        let changed = CollapsingHeader::new(&self.name)
+            .default_open(true)
+            .show(ui, |ui| widget(ui, component))
+            .body_returned
+            .unwrap_or_default();
+        if changed {
+            world.changes.mark_changed::<T>(entity);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::entity_name;
+use phantom::{
+    app::CVars,
+    dependencies::{
+        egui::{
+            CollapsingHeader, Color32, ComboBox, CtxRef, DragValue, Grid, ScrollArea, Ui, Window,
+        },
+        legion::IntoQuery,
+        log, nalgebra_glm as glm,
+    },
+    render::{Renderer, Tonemapping},
+    world::{BoundingBox, Entity, Light, LightKind, MeshRender, World},
+};
+use std::collections::HashMap;
+
+struct MeshStatistics {
+    name: String,
+    vertices: usize,
+    triangles: usize,
+    instances: usize,
+}
+
+struct TextureStatistics {
+    index: usize,
+    width: u32,
+    height: u32,
+    bytes: usize,
+}
+
+/// A snapshot of the costs in a scene, gathered when the report is refreshed
+#[derive(Default)]
+struct SceneReport {
+    meshes: Vec<MeshStatistics>,
+    textures: Vec<TextureStatistics>,
+
+    /// Estimated number of times each pixel is drawn,
+    /// from the screen area covered by every visible mesh's bounding box
+    overdraw: f32,
+
+    /// Visible entities whose mesh is over the triangle budget, which would benefit from LODs
+    dense_entities: Vec<(String, usize)>,
+
+    /// Point and spot lights without a range, which light every mesh in the scene
+    unbounded_lights: Vec<String>,
+}
+
+/// Lists the heaviest assets in the scene to help optimize it before shipping
+pub struct StatisticsPanel {
+    pub open: bool,
+    report: Option<SceneReport>,
+    max_texture_size: u32,
+    triangle_budget: usize,
+}
+
+impl Default for StatisticsPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            report: None,
+            max_texture_size: 2048,
+            triangle_budget: 10_000,
+        }
+    }
+}
+
+impl StatisticsPanel {
+    /// Only the heaviest entries in each list are shown
+    const MAX_ROWS: usize = 20;
+
+    pub fn ui(
+        &mut self,
+        ctx: &CtxRef,
+        world: &World,
+        renderer: &dyn Renderer,
+        cvars: &mut CVars,
+        aspect_ratio: f32,
+    ) {
+        if self.open && self.report.is_none() {
+            self.report = Some(self.generate(world, aspect_ratio));
+        }
+
+        let mut refresh = false;
+        let mut open = self.open;
+        Window::new("Scene Statistics")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    refresh = ui.button("Refresh").clicked();
+                    ui.label("Max Texture Size");
+                    ui.add(DragValue::new(&mut self.max_texture_size).speed(16.0));
+                    ui.label("Triangle Budget");
+                    ui.add(DragValue::new(&mut self.triangle_budget).speed(100.0));
+                });
+                ui.horizontal(|ui| {
+                    let statistics = renderer.statistics();
+                    ui.label(format!(
+                        "Draw Calls: {} visible, {} culled",
+                        statistics.visible_draw_calls, statistics.culled_draw_calls
+                    ));
+                    let mut frustum_culling = renderer.render_settings().frustum_culling;
+                    if ui
+                        .checkbox(&mut frustum_culling, "Frustum Culling")
+                        .changed()
+                    {
+                        if let Err(error) = cvars.set(CVars::FRUSTUM_CULLING, frustum_culling) {
+                            log::error!("{}", error);
+                        }
+                    }
+                    let sample_count = renderer.render_settings().sample_count;
+                    ComboBox::from_label("MSAA")
+                        .selected_text(format!("{}x", sample_count))
+                        .show_ui(ui, |ui| {
+                            for count in renderer.supported_sample_counts() {
+                                if ui
+                                    .selectable_label(count == sample_count, format!("{}x", count))
+                                    .clicked()
+                                {
+                                    if let Err(error) = cvars.set(CVars::MSAA, count) {
+                                        log::error!("{}", error);
+                                    }
+                                }
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let settings = renderer.render_settings();
+                    ComboBox::from_label("Tonemapping")
+                        .selected_text(settings.tonemapping.name())
+                        .show_ui(ui, |ui| {
+                            for tonemapping in Tonemapping::ALL {
+                                if ui
+                                    .selectable_label(
+                                        tonemapping == settings.tonemapping,
+                                        tonemapping.name(),
+                                    )
+                                    .clicked()
+                                {
+                                    if let Err(error) =
+                                        cvars.set(CVars::TONEMAPPING, tonemapping.name())
+                                    {
+                                        log::error!("{}", error);
+                                    }
+                                }
+                            }
+                        });
+                    let mut bloom = settings.bloom;
+                    if ui.checkbox(&mut bloom, "Bloom").changed() {
+                        if let Err(error) = cvars.set(CVars::BLOOM, bloom) {
+                            log::error!("{}", error);
+                        }
+                    }
+                    let mut fxaa = settings.fxaa;
+                    if ui.checkbox(&mut fxaa, "FXAA").changed() {
+                        if let Err(error) = cvars.set(CVars::FXAA, fxaa) {
+                            log::error!("{}", error);
+                        }
+                    }
+                });
+                ui.separator();
+                if let Some(report) = self.report.as_ref() {
+                    ScrollArea::vertical().show(ui, |ui| {
+                        report_ui(ui, report, self.max_texture_size);
+                    });
+                }
+            });
+        self.open = open;
+
+        if refresh {
+            self.report = Some(self.generate(world, aspect_ratio));
+        }
+        if !self.open {
+            self.report = None;
+        }
+    }
+
+    fn generate(&self, world: &World, aspect_ratio: f32) -> SceneReport {
+        let mut instances = HashMap::new();
+        let mut query = <&MeshRender>::query();
+        for mesh_render in query.iter(&world.ecs) {
+            *instances.entry(mesh_render.name.to_string()).or_insert(0) += 1;
+        }
+
+        let mut meshes = world
+            .geometry
+            .meshes
+            .values()
+            .map(|mesh| MeshStatistics {
+                name: mesh.name.to_string(),
+                vertices: mesh
+                    .primitives
+                    .iter()
+                    .map(|primitive| primitive.number_of_vertices)
+                    .sum(),
+                triangles: mesh
+                    .primitives
+                    .iter()
+                    .map(|primitive| primitive.number_of_indices / 3)
+                    .sum(),
+                instances: instances.get(&mesh.name).copied().unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        meshes.sort_by(|a, b| {
+            (b.triangles * b.instances.max(1)).cmp(&(a.triangles * a.instances.max(1)))
+        });
+
+        let mut textures = world
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(index, texture)| TextureStatistics {
+                index,
+                width: texture.width,
+                height: texture.height,
+                bytes: texture.pixels.len(),
+            })
+            .collect::<Vec<_>>();
+        textures.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+        let mut dense_entities = Vec::new();
+        let mut overdraw = 0.0;
+        let camera = world.active_camera_matrices(aspect_ratio).ok();
+        let mut query = <(Entity, &MeshRender)>::query();
+        for (entity, mesh_render) in query.iter(&world.ecs) {
+            if !world.is_enabled_in_hierarchy(*entity) {
+                continue;
+            }
+            let mesh = match world.geometry.meshes.get(&mesh_render.name) {
+                Some(mesh) if !mesh.primitives.is_empty() => mesh,
+                _ => continue,
+            };
+            let triangles = mesh
+                .primitives
+                .iter()
+                .map(|primitive| primitive.number_of_indices / 3)
+                .sum::<usize>();
+            if triangles > self.triangle_budget {
+                dense_entities.push((entity_name(world, *entity), triangles));
+            }
+            if let (Some((projection, view)), Ok(model)) =
+                (camera, world.entity_global_transform_matrix(*entity))
+            {
+                overdraw += screen_coverage(&(projection * view * model), &mesh.bounding_box());
+            }
+        }
+        dense_entities.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let mut unbounded_lights = Vec::new();
+        let mut query = <(Entity, &Light)>::query();
+        for (entity, light) in query.iter(&world.ecs) {
+            if !matches!(light.kind, LightKind::Directional) && light.range <= 0.0 {
+                unbounded_lights.push(entity_name(world, *entity));
+            }
+        }
+
+        SceneReport {
+            meshes,
+            textures,
+            overdraw,
+            dense_entities,
+            unbounded_lights,
+        }
+    }
+}
+
+/// The fraction of the screen covered by a bounding box,
+/// treating boxes that cross the camera plane as covering the whole screen
+fn screen_coverage(model_view_projection: &glm::Mat4, bounding_box: &BoundingBox) -> f32 {
+    let (min, max) = (bounding_box.min, bounding_box.max);
+    let mut screen_min = glm::vec2(f32::MAX, f32::MAX);
+    let mut screen_max = glm::vec2(f32::MIN, f32::MIN);
+    for corner in 0..8 {
+        let point = glm::vec3(
+            if corner & 1 == 0 { min.x } else { max.x },
+            if corner & 2 == 0 { min.y } else { max.y },
+            if corner & 4 == 0 { min.z } else { max.z },
+        );
+        let clip = model_view_projection * glm::vec4(point.x, point.y, point.z, 1.0);
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+        let ndc = clip.xy() / clip.w;
+        screen_min = glm::min2(&screen_min, &ndc);
+        screen_max = glm::max2(&screen_max, &ndc);
+    }
+    let screen_min = glm::clamp(&screen_min, -1.0, 1.0);
+    let screen_max = glm::clamp(&screen_max, -1.0, 1.0);
+    let size = screen_max - screen_min;
+    size.x * size.y / 4.0
+}
+
+fn report_ui(ui: &mut Ui, report: &SceneReport, max_texture_size: u32) {
+    ui.label(format!("Estimated Overdraw: {:.2}x", report.overdraw));
+
+    CollapsingHeader::new(format!("Heaviest Meshes ({})", report.meshes.len()))
+        .default_open(true)
+        .show(ui, |ui| {
+            Grid::new("statistics_meshes").striped(true).show(ui, |ui| {
+                ui.label("Mesh");
+                ui.label("Vertices");
+                ui.label("Triangles");
+                ui.label("Instances");
+                ui.end_row();
+                for mesh in report.meshes.iter().take(StatisticsPanel::MAX_ROWS) {
+                    ui.label(&mesh.name);
+                    ui.label(mesh.vertices.to_string());
+                    ui.label(mesh.triangles.to_string());
+                    ui.label(mesh.instances.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+
+    CollapsingHeader::new(format!("Heaviest Textures ({})", report.textures.len()))
+        .default_open(true)
+        .show(ui, |ui| {
+            Grid::new("statistics_textures")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Texture");
+                    ui.label("Size");
+                    ui.label("Memory");
+                    ui.end_row();
+                    for texture in report.textures.iter().take(StatisticsPanel::MAX_ROWS) {
+                        ui.label(format!("Texture {}", texture.index));
+                        let size = format!("{}x{}", texture.width, texture.height);
+                        if texture.width.max(texture.height) > max_texture_size {
+                            ui.colored_label(Color32::YELLOW, size)
+                                .on_hover_text("Larger than the max texture size");
+                        } else {
+                            ui.label(size);
+                        }
+                        ui.label(format!("{:.2} MB", texture.bytes as f32 / 1_048_576.0));
+                        ui.end_row();
+                    }
+                });
+        });
+
+    CollapsingHeader::new(format!(
+        "Entities Over The Triangle Budget ({})",
+        report.dense_entities.len()
+    ))
+    .show(ui, |ui| {
+        ui.label("These meshes have no LODs and are drawn at full detail at any distance");
+        for (name, triangles) in report.dense_entities.iter().take(StatisticsPanel::MAX_ROWS) {
+            ui.label(format!("{} - {} triangles", name, triangles));
+        }
+    });
+
+    CollapsingHeader::new(format!(
+        "Lights Without A Range ({})",
+        report.unbounded_lights.len()
+    ))
+    .show(ui, |ui| {
+        ui.label("These lights can't be culled and affect every mesh in the scene");
+        for name in report.unbounded_lights.iter() {
+            ui.label(name);
+        }
+    });
+}
@@ -0,0 +1,117 @@
+use phantom::{
+    app::DataTables,
+    dependencies::{
+        egui::{ComboBox, CtxRef, Grid, ScrollArea, Window},
+        log,
+        serde_json::Value,
+    },
+};
+use std::path::Path;
+
+/// Shows the game's data tables, which reload as designers edit their files
+#[derive(Default)]
+pub struct DataTablesPanel {
+    pub open: bool,
+    selected: Option<String>,
+    filter: String,
+}
+
+impl DataTablesPanel {
+    pub const DIRECTORY: &'static str = "assets/tables";
+
+    /// Loads every table in the project's tables directory, if it has one
+    pub fn load_tables(data_tables: &mut DataTables) {
+        if !Path::new(Self::DIRECTORY).exists() {
+            return;
+        }
+        if let Err(error) = data_tables.load_directory(Self::DIRECTORY) {
+            log::error!("Failed to load data tables: {}", error);
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &CtxRef, data_tables: &mut DataTables) {
+        let mut open = self.open;
+        Window::new("Data Tables")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let selected = self.selected.clone().unwrap_or_default();
+                    ComboBox::from_id_source("data_table")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for table in data_tables.tables() {
+                                ui.selectable_value(
+                                    &mut self.selected,
+                                    Some(table.name.clone()),
+                                    &table.name,
+                                );
+                            }
+                        });
+                    if ui.button("Reload All").clicked() {
+                        Self::load_tables(data_tables);
+                    }
+                    ui.checkbox(&mut data_tables.hot_reload, "Hot Reload");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.filter);
+                });
+                ui.separator();
+
+                let table = match self
+                    .selected
+                    .as_ref()
+                    .and_then(|name| data_tables.get(name))
+                {
+                    Some(table) => table,
+                    None => {
+                        ui.label("Select a table to view its rows");
+                        return;
+                    }
+                };
+                ui.label(format!(
+                    "{} ({} rows)",
+                    table.path.display(),
+                    table.rows().len()
+                ));
+
+                let filter = self.filter.to_lowercase();
+                ScrollArea::both().show(ui, |ui| {
+                    Grid::new("data_table_rows").striped(true).show(ui, |ui| {
+                        for column in table.columns.iter() {
+                            ui.strong(column);
+                        }
+                        ui.end_row();
+                        for row in table.rows() {
+                            let cells = table
+                                .columns
+                                .iter()
+                                .map(|column| row.get(column).map_or_else(String::new, cell_text))
+                                .collect::<Vec<_>>();
+                            if !filter.is_empty()
+                                && !cells
+                                    .iter()
+                                    .any(|cell| cell.to_lowercase().contains(&filter))
+                            {
+                                continue;
+                            }
+                            for cell in cells {
+                                ui.label(cell);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        self.open = open;
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,249 @@
+use crate::entity_name;
+use phantom::{
+    dependencies::{
+        egui::{
+            plot::{Legend, Line, Plot, Value, Values},
+            ComboBox, CtxRef, DragValue, Ui, Window,
+        },
+        legion::{EntityStore, IntoQuery},
+    },
+    world::{Entity, MeshRender, RigidBody, World},
+};
+use std::collections::VecDeque;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchField {
+    Position,
+    Velocity,
+    AngularVelocity,
+    MorphWeights,
+}
+
+impl WatchField {
+    const ALL: [WatchField; 4] = [
+        WatchField::Position,
+        WatchField::Velocity,
+        WatchField::AngularVelocity,
+        WatchField::MorphWeights,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            WatchField::Position => "Position",
+            WatchField::Velocity => "Velocity",
+            WatchField::AngularVelocity => "Angular Velocity",
+            WatchField::MorphWeights => "Morph Weights",
+        }
+    }
+
+    fn sample(&self, world: &World, entity: Entity) -> Option<Vec<f32>> {
+        match self {
+            WatchField::Position => {
+                let transform = world.entity_global_transform_matrix(entity).ok()?;
+                Some(vec![
+                    transform[(0, 3)],
+                    transform[(1, 3)],
+                    transform[(2, 3)],
+                ])
+            }
+            WatchField::Velocity | WatchField::AngularVelocity => {
+                let entry = world.ecs.entry_ref(entity).ok()?;
+                let handle = entry.get_component::<RigidBody>().ok()?.handle;
+                let body = world.physics.bodies.get(handle)?;
+                let velocity = if *self == WatchField::Velocity {
+                    *body.linvel()
+                } else {
+                    *body.angvel()
+                };
+                Some(vec![velocity.x, velocity.y, velocity.z])
+            }
+            WatchField::MorphWeights => {
+                let entry = world.ecs.entry_ref(entity).ok()?;
+                let mesh_name = entry.get_component::<MeshRender>().ok()?.name.to_string();
+                Some(world.geometry.meshes.get(&mesh_name)?.weights.to_vec())
+            }
+        }
+    }
+
+    fn series_name(&self, index: usize) -> String {
+        match self {
+            WatchField::MorphWeights => format!("Weight {}", index),
+            _ => ["X", "Y", "Z"]
+                .get(index)
+                .map_or_else(|| index.to_string(), |axis| axis.to_string()),
+        }
+    }
+}
+
+struct Watch {
+    entity: Entity,
+    field: WatchField,
+    samples: VecDeque<(f64, Vec<f32>)>,
+}
+
+/// Plots component values of watched entities over time
+pub struct WatchPanel {
+    pub open: bool,
+    paused: bool,
+    time: f64,
+
+    /// Seconds of samples shown in each plot
+    history: f64,
+    watches: Vec<Watch>,
+    new_entity: Option<Entity>,
+    new_field: WatchField,
+}
+
+impl Default for WatchPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            paused: false,
+            time: 0.0,
+            history: 10.0,
+            watches: Vec::new(),
+            new_entity: None,
+            new_field: WatchField::Position,
+        }
+    }
+}
+
+impl WatchPanel {
+    /// The longest history that can be shown, older samples are discarded
+    const MAX_HISTORY: f64 = 60.0;
+
+    pub fn record(&mut self, world: &World, delta_time: f32) {
+        if self.paused || self.watches.is_empty() {
+            return;
+        }
+        self.time += delta_time as f64;
+        for watch in self.watches.iter_mut() {
+            if let Some(values) = watch.field.sample(world, watch.entity) {
+                watch.samples.push_back((self.time, values));
+            }
+            while watch
+                .samples
+                .front()
+                .map_or(false, |(time, _)| self.time - time > Self::MAX_HISTORY)
+            {
+                watch.samples.pop_front();
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &CtxRef, world: &World) {
+        let mut open = self.open;
+        Window::new("Watch")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                self.controls_ui(ui, world);
+                ui.separator();
+                self.plots_ui(ui, world);
+            });
+        self.open = open;
+    }
+
+    fn controls_ui(&mut self, ui: &mut Ui, world: &World) {
+        ui.horizontal(|ui| {
+            let label = if self.paused {
+                "▶ Resume"
+            } else {
+                "⏸ Pause"
+            };
+            if ui.button(label).clicked() {
+                self.paused = !self.paused;
+            }
+            if ui.button("Clear").clicked() {
+                self.watches
+                    .iter_mut()
+                    .for_each(|watch| watch.samples.clear());
+            }
+            ui.label("History");
+            ui.add(
+                DragValue::new(&mut self.history)
+                    .speed(0.1)
+                    .clamp_range(1.0..=Self::MAX_HISTORY)
+                    .suffix("s"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            let selected_text = self
+                .new_entity
+                .map(|entity| entity_name(world, entity))
+                .unwrap_or_else(|| "Select an entity".to_string());
+            ComboBox::from_id_source("watch_entity")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    let mut query = <Entity>::query();
+                    for entity in query.iter(&world.ecs) {
+                        ui.selectable_value(
+                            &mut self.new_entity,
+                            Some(*entity),
+                            entity_name(world, *entity),
+                        );
+                    }
+                });
+            ComboBox::from_id_source("watch_field")
+                .selected_text(self.new_field.label())
+                .show_ui(ui, |ui| {
+                    for field in WatchField::ALL {
+                        ui.selectable_value(&mut self.new_field, field, field.label());
+                    }
+                });
+            if let Some(entity) = self.new_entity {
+                if ui.button("Watch").clicked() {
+                    self.watches.push(Watch {
+                        entity,
+                        field: self.new_field,
+                        samples: VecDeque::new(),
+                    });
+                }
+            }
+        });
+    }
+
+    fn plots_ui(&mut self, ui: &mut Ui, world: &World) {
+        let mut removed = None;
+        let start = self.time - self.history;
+        for (index, watch) in self.watches.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} - {}",
+                    entity_name(world, watch.entity),
+                    watch.field.label()
+                ));
+                if ui.small_button("✖").clicked() {
+                    removed = Some(index);
+                }
+            });
+
+            let series_count = watch.samples.back().map_or(0, |(_, values)| values.len());
+            let mut plot = Plot::new(("watch_plot", index))
+                .height(120.0)
+                .allow_zoom(self.paused)
+                .allow_drag(self.paused)
+                .legend(Legend::default());
+            for series in 0..series_count {
+                let values = watch
+                    .samples
+                    .iter()
+                    .filter(|(time, _)| *time >= start)
+                    .filter_map(|(time, values)| {
+                        values
+                            .get(series)
+                            .map(|value| Value::new(*time, *value as f64))
+                    });
+                plot = plot.line(
+                    Line::new(Values::from_values_iter(values))
+                        .name(watch.field.series_name(series)),
+                );
+            }
+            ui.add(plot);
+        }
+        if let Some(index) = removed {
+            self.watches.remove(index);
+        }
+    }
+}
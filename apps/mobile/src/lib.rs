@@ -0,0 +1,43 @@
+use phantom::{
+    app::{run, AppConfig, Resources, State, Transition},
+    dependencies::{
+        anyhow::Result,
+        log,
+        winit::event::{Touch, TouchPhase},
+    },
+};
+
+#[derive(Default)]
+struct Mobile;
+
+impl State for Mobile {
+    fn on_start(&mut self, _resources: &mut Resources) -> Result<()> {
+        log::info!("Starting the Phantom mobile example");
+        Ok(())
+    }
+
+    fn on_touch(&mut self, resources: &mut Resources, touch: &Touch) -> Result<Transition> {
+        if touch.phase == TouchPhase::Started {
+            log::info!(
+                "Touch {} started at ({}, {}), {} active",
+                touch.id,
+                touch.location.x,
+                touch.location.y,
+                resources.input.touches.count()
+            );
+        }
+        Ok(Transition::None)
+    }
+}
+
+#[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
+pub fn main() {
+    let config = AppConfig {
+        title: "Phantom Mobile".to_string(),
+        icon: Some("assets/icon/phantom.png".to_string()),
+        ..Default::default()
+    };
+    if let Err(error) = run(Mobile::default(), config) {
+        log::error!("Application error: {}", error);
+    }
+}
@@ -0,0 +1,50 @@
+// Extracts the bright parts of the frame and blurs them, at half resolution
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// The threshold for extraction, or the offset between blur taps in uv space
+struct Parameters {
+    values: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> parameters: Parameters;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var output: VertexOutput;
+    output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+
+[[stage(fragment)]]
+fn fs_extract(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, input.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - parameters.values.x, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// A separable gaussian blur along one direction
+[[stage(fragment)]]
+fn fs_blur(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let offset = parameters.values.xy;
+    var color = textureSample(source_texture, source_sampler, input.uv).rgb * 0.227027;
+    color = color + textureSample(source_texture, source_sampler, input.uv + offset).rgb * 0.1945946;
+    color = color + textureSample(source_texture, source_sampler, input.uv - offset).rgb * 0.1945946;
+    color = color + textureSample(source_texture, source_sampler, input.uv + offset * 2.0).rgb * 0.1216216;
+    color = color + textureSample(source_texture, source_sampler, input.uv - offset * 2.0).rgb * 0.1216216;
+    color = color + textureSample(source_texture, source_sampler, input.uv + offset * 3.0).rgb * 0.054054;
+    color = color + textureSample(source_texture, source_sampler, input.uv - offset * 3.0).rgb * 0.054054;
+    color = color + textureSample(source_texture, source_sampler, input.uv + offset * 4.0).rgb * 0.016216;
+    color = color + textureSample(source_texture, source_sampler, input.uv - offset * 4.0).rgb * 0.016216;
+    return vec4<f32>(color, 1.0);
+}
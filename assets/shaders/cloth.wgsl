@@ -0,0 +1,234 @@
+struct Particle {
+    // xyz is the position, w is the inverse mass
+    position: vec4<f32>;
+    previous: vec4<f32>;
+};
+
+struct Neighbor {
+    index: i32;
+    rest_length: f32;
+    compliance: f32;
+    padding: f32;
+};
+
+struct Collider {
+    // x is the kind of shape, 0 for spheres, 1 for capsules and 2 for planes.
+    // y is the radius, or the plane's distance from the origin.
+    shape: vec4<f32>;
+    // The sphere's center, the capsule's start or the plane's normal
+    first: vec4<f32>;
+    // The capsule's end
+    second: vec4<f32>;
+};
+
+struct Params {
+    // xyz is gravity, w is the substep duration
+    gravity: vec4<f32>;
+    // xyz is the wind, w is the damping
+    wind: vec4<f32>;
+    // particle count, columns, rows, collider count
+    counts: vec4<u32>;
+    // x is the thickness kept between the cloth and colliders
+    settings: vec4<f32>;
+};
+
+struct Particles {
+    particles: [[stride(32)]] array<Particle>;
+};
+
+struct Neighbors {
+    neighbors: [[stride(16)]] array<Neighbor>;
+};
+
+struct Colliders {
+    colliders: [[stride(48)]] array<Collider>;
+};
+
+// Vertices are written as floats, since their fields aren't aligned the way wgsl structs are
+struct Vertices {
+    values: [[stride(4)]] array<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> params: Params;
+[[group(0), binding(1)]] var<storage, read> source: Particles;
+[[group(0), binding(2)]] var<storage, read_write> destination: Particles;
+[[group(0), binding(3)]] var<storage, read> neighbors: Neighbors;
+[[group(0), binding(4)]] var<storage, read> colliders: Colliders;
+[[group(0), binding(5)]] var<storage, read_write> vertices: Vertices;
+
+let MAX_NEIGHBORS: u32 = 12u;
+
+// Position, normal, two texture coordinates, joints, weights and color
+let VERTEX_FLOATS: u32 = 21u;
+
+fn particle_normal(index: u32) -> vec3<f32> {
+    let columns = params.counts.y;
+    let rows = params.counts.z;
+    let column = index % columns;
+    let row = index / columns;
+    let right = row * columns + min(column + 1u, columns - 1u);
+    let left = row * columns + max(column, 1u) - 1u;
+    let down = min(row + 1u, rows - 1u) * columns + column;
+    let up = (max(row, 1u) - 1u) * columns + column;
+    let horizontal = source.particles[right].position.xyz - source.particles[left].position.xyz;
+    let vertical = source.particles[down].position.xyz - source.particles[up].position.xyz;
+    let normal = cross(horizontal, vertical);
+    let length_squared = dot(normal, normal);
+    if (length_squared < 0.000001) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    return normal / sqrt(length_squared);
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn integrate([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.counts.x) {
+        return;
+    }
+
+    let particle = source.particles[index];
+    if (particle.position.w == 0.0) {
+        destination.particles[index] = particle;
+        return;
+    }
+
+    let substep = params.gravity.w;
+    let normal = particle_normal(index);
+    let wind = normal * dot(normal, params.wind.xyz);
+    let velocity = (particle.position.xyz - particle.previous.xyz) * (1.0 - params.wind.w);
+    let acceleration = params.gravity.xyz + wind;
+    let position = particle.position.xyz + velocity + acceleration * substep * substep;
+    destination.particles[index].previous = vec4<f32>(particle.position.xyz, 0.0);
+    destination.particles[index].position = vec4<f32>(position, particle.position.w);
+}
+
+// Solves the distance constraints with Jacobi iteration,
+// averaging the corrections from every neighbor
+[[stage(compute), workgroup_size(64)]]
+fn solve([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.counts.x) {
+        return;
+    }
+
+    let particle = source.particles[index];
+    let weight = particle.position.w;
+    if (weight == 0.0) {
+        destination.particles[index] = particle;
+        return;
+    }
+
+    let substep = params.gravity.w;
+    var correction = vec3<f32>(0.0, 0.0, 0.0);
+    var count = 0.0;
+    for (var slot = 0u; slot < MAX_NEIGHBORS; slot = slot + 1u) {
+        let neighbor = neighbors.neighbors[index * MAX_NEIGHBORS + slot];
+        if (neighbor.index < 0) {
+            break;
+        }
+        let other = source.particles[u32(neighbor.index)];
+        let offset = particle.position.xyz - other.position.xyz;
+        let distance = length(offset);
+        if (distance < 0.000001) {
+            continue;
+        }
+        let alpha = neighbor.compliance / (substep * substep);
+        let lambda = -(distance - neighbor.rest_length) / (weight + other.position.w + alpha);
+        correction = correction + offset * (lambda * weight / distance);
+        count = count + 1.0;
+    }
+
+    if (count > 0.0) {
+        correction = correction / count;
+    }
+
+    destination.particles[index].previous = particle.previous;
+    destination.particles[index].position = vec4<f32>(particle.position.xyz + correction, weight);
+}
+
+fn push_out_of_sphere(position: vec3<f32>, center: vec3<f32>, radius: f32) -> vec3<f32> {
+    let offset = position - center;
+    let distance = length(offset);
+    if (distance < radius && distance > 0.000001) {
+        return center + offset * (radius / distance);
+    }
+    return position;
+}
+
+// Pushes particles out of the colliders, after the constraints of every substep are solved
+[[stage(compute), workgroup_size(64)]]
+fn collide([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.counts.x) {
+        return;
+    }
+
+    let particle = source.particles[index];
+    destination.particles[index] = particle;
+    if (particle.position.w == 0.0) {
+        return;
+    }
+
+    let thickness = params.settings.x;
+    var position = particle.position.xyz;
+    for (var slot = 0u; slot < params.counts.w; slot = slot + 1u) {
+        let collider = colliders.colliders[slot];
+        let kind = u32(collider.shape.x);
+        if (kind == 0u) {
+            position = push_out_of_sphere(position, collider.first.xyz, collider.shape.y + thickness);
+        } else {
+            if (kind == 1u) {
+                let start = collider.first.xyz;
+                let segment = collider.second.xyz - start;
+                let length_squared = dot(segment, segment);
+                var t = 0.0;
+                if (length_squared > 0.000001) {
+                    t = clamp(dot(position - start, segment) / length_squared, 0.0, 1.0);
+                }
+                position = push_out_of_sphere(position, start + segment * t, collider.shape.y + thickness);
+            } else {
+                let normal = collider.first.xyz;
+                let depth = dot(position, normal) - collider.shape.y - thickness;
+                if (depth < 0.0) {
+                    position = position - normal * depth;
+                }
+            }
+        }
+    }
+    destination.particles[index].position = vec4<f32>(position, particle.position.w);
+}
+
+// Writes the particles into the vertex buffer the cloth is drawn from
+[[stage(compute), workgroup_size(64)]]
+fn write_vertices([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.counts.x) {
+        return;
+    }
+
+    let columns = params.counts.y;
+    let rows = params.counts.z;
+    let position = source.particles[index].position.xyz;
+    let normal = particle_normal(index);
+    let uv = vec2<f32>(
+        f32(index % columns) / f32(max(columns, 2u) - 1u),
+        f32(index / columns) / f32(max(rows, 2u) - 1u)
+    );
+
+    let base = index * VERTEX_FLOATS;
+    vertices.values[base] = position.x;
+    vertices.values[base + 1u] = position.y;
+    vertices.values[base + 2u] = position.z;
+    vertices.values[base + 3u] = normal.x;
+    vertices.values[base + 4u] = normal.y;
+    vertices.values[base + 5u] = normal.z;
+    vertices.values[base + 6u] = uv.x;
+    vertices.values[base + 7u] = uv.y;
+    for (var offset = 8u; offset < 18u; offset = offset + 1u) {
+        vertices.values[base + offset] = 0.0;
+    }
+    vertices.values[base + 18u] = 1.0;
+    vertices.values[base + 19u] = 1.0;
+    vertices.values[base + 20u] = 1.0;
+}
@@ -0,0 +1,31 @@
+// Accessibility color correction, applied to the finished frame before the gui is drawn
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+struct Parameters {
+    color_filter: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> parameters: Parameters;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var output: VertexOutput;
+    output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, input.uv);
+    return vec4<f32>((parameters.color_filter * vec4<f32>(color.rgb, 0.0)).xyz, color.a);
+}
@@ -0,0 +1,69 @@
+// Fast approximate antialiasing, blurring along the edges found from luminance
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// The size of a texel in uv space
+struct Parameters {
+    values: vec4<f32>;
+};
+
+let REDUCE_MIN: f32 = 0.0078125;
+let REDUCE_MULTIPLIER: f32 = 0.125;
+let SPAN_MAX: f32 = 8.0;
+
+[[group(0), binding(0)]]
+var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> parameters: Parameters;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var output: VertexOutput;
+    output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = parameters.values.xy;
+    let center = textureSample(source_texture, source_sampler, input.uv);
+    let luma_north_west = luma(textureSample(source_texture, source_sampler, input.uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_north_east = luma(textureSample(source_texture, source_sampler, input.uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_south_west = luma(textureSample(source_texture, source_sampler, input.uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_south_east = luma(textureSample(source_texture, source_sampler, input.uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_center = luma(center.rgb);
+
+    let luma_min = min(luma_center, min(min(luma_north_west, luma_north_east), min(luma_south_west, luma_south_east)));
+    let luma_max = max(luma_center, max(max(luma_north_west, luma_north_east), max(luma_south_west, luma_south_east)));
+
+    var direction = vec2<f32>(
+        (luma_south_west + luma_south_east) - (luma_north_west + luma_north_east),
+        (luma_north_west + luma_south_west) - (luma_north_east + luma_south_east)
+    );
+    let direction_reduce = max((luma_north_west + luma_north_east + luma_south_west + luma_south_east) * 0.25 * REDUCE_MULTIPLIER, REDUCE_MIN);
+    let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    // Everything is sampled before choosing between the results
+    let near = 0.5 * (
+        textureSample(source_texture, source_sampler, input.uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(source_texture, source_sampler, input.uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let far = near * 0.5 + 0.25 * (
+        textureSample(source_texture, source_sampler, input.uv - direction * 0.5).rgb +
+        textureSample(source_texture, source_sampler, input.uv + direction * 0.5).rgb
+    );
+    let luma_far = luma(far);
+    let outside = luma_far < luma_min || luma_far > luma_max;
+    return vec4<f32>(select(far, near, outside), center.a);
+}
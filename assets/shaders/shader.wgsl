@@ -1,23 +1,380 @@
+struct Uniform {
+    view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    camera_position: vec4<f32>;
+    light_view_projection: mat4x4<f32>;
+    // Points towards the directional light
+    light_direction: vec4<f32>;
+    // The shadow bias, one if shadows are enabled, and the size of a shadow map texel
+    shadow_parameters: vec4<f32>;
+};
+
+struct DynamicUniform {
+    model: mat4x4<f32>;
+    joint_offset: u32;
+};
+
+struct Joints {
+    matrices: array<mat4x4<f32>>;
+};
+
+// Texture sets are -1 for textures the material doesn't have
+struct Material {
+    base_color_factor: vec4<f32>;
+    emissive_factor: vec4<f32>;
+    metallic_factor: f32;
+    roughness_factor: f32;
+    normal_scale: f32;
+    occlusion_strength: f32;
+    alpha_cutoff: f32;
+    is_unlit: i32;
+    color_texture_set: i32;
+    metallic_roughness_texture_set: i32;
+    normal_texture_set: i32;
+    occlusion_texture_set: i32;
+    emissive_texture_set: i32;
+    transmission_factor: f32;
+    // The top two rows of the matrix transforming texture coordinates
+    texture_transform_x: vec4<f32>;
+    texture_transform_y: vec4<f32>;
+};
+
+let DIRECTIONAL_LIGHT: u32 = 0u;
+let POINT_LIGHT: u32 = 1u;
+let SPOT_LIGHT: u32 = 2u;
+
+// Lights without a range have a range of zero or less
+struct Light {
+    position: vec4<f32>;
+    direction: vec4<f32>;
+    // The color in rgb and the intensity in alpha
+    color: vec4<f32>;
+    range: f32;
+    kind: u32;
+    inner_cone_cos: f32;
+    outer_cone_cos: f32;
+};
+
+// The shadow light is the index of the light casting shadows, or -1
+struct Lights {
+    lights: array<Light, 64>;
+    count: u32;
+    shadow_light: i32;
+    padding: vec2<u32>;
+};
+
+[[group(0), binding(0)]] var<uniform> ubo: Uniform;
+[[group(0), binding(1)]] var shadow_texture: texture_depth_2d;
+[[group(0), binding(2)]] var shadow_sampler: sampler_comparison;
+[[group(0), binding(3)]] var<uniform> lights_ubo: Lights;
+[[group(1), binding(0)]] var<uniform> mesh_ubo: DynamicUniform;
+[[group(2), binding(0)]] var color_texture: texture_2d<f32>;
+[[group(2), binding(1)]] var color_sampler: sampler;
+[[group(2), binding(2)]] var metallic_roughness_texture: texture_2d<f32>;
+[[group(2), binding(3)]] var metallic_roughness_sampler: sampler;
+[[group(2), binding(4)]] var normal_texture: texture_2d<f32>;
+[[group(2), binding(5)]] var normal_sampler: sampler;
+[[group(2), binding(6)]] var occlusion_texture: texture_2d<f32>;
+[[group(2), binding(7)]] var occlusion_sampler: sampler;
+[[group(2), binding(8)]] var emissive_texture: texture_2d<f32>;
+[[group(2), binding(9)]] var emissive_sampler: sampler;
+[[group(2), binding(10)]] var<uniform> material_ubo: Material;
+[[group(3), binding(0)]] var<storage, read> joints: Joints;
+// The joint matrices as four texels each, bound instead of the storage buffer
+// on adapters that can't read storage buffers in vertex shaders
+[[group(3), binding(1)]] var joint_texture: texture_2d<f32>;
+
 // Vertex shader
 
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv_0: vec2<f32>;
+    [[location(3)]] uv_1: vec2<f32>;
+    [[location(4)]] joint_0: vec4<f32>;
+    [[location(5)]] weight_0: vec4<f32>;
+    [[location(6)]] color_0: vec3<f32>;
+};
+
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv_0: vec2<f32>;
+    [[location(3)]] uv_1: vec2<f32>;
+    [[location(4)]] color: vec4<f32>;
 };
 
-[[stage(vertex)]]
-fn vs_main(
-    [[builtin(vertex_index)]] in_vertex_index: u32,
-) -> VertexOutput {
+fn vertex_output(vertex: VertexInput, local_position: vec4<f32>, local_normal: vec4<f32>) -> VertexOutput {
     var out: VertexOutput;
-    let x = f32(1 - i32(in_vertex_index)) * 0.5;
-    let y = f32(i32(in_vertex_index & 1u) * 2 - 1) * 0.5;
-    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    let model = mesh_ubo.model;
+    let position = model * local_position;
+    out.clip_position = ubo.projection * ubo.view * position;
+    out.position = position.xyz;
+    out.normal = (model * local_normal).xyz;
+    out.uv_0 = vertex.uv_0;
+    out.uv_1 = vertex.uv_1;
+    out.color = vec4<f32>(vertex.color_0, 1.0);
     return out;
 }
 
+[[stage(vertex)]]
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    return vertex_output(vertex, vec4<f32>(vertex.position, 1.0), vec4<f32>(vertex.normal, 0.0));
+}
+
+fn joint_indices(vertex: VertexInput) -> vec4<u32> {
+    return vec4<u32>(vertex.joint_0) + vec4<u32>(mesh_ubo.joint_offset, mesh_ubo.joint_offset, mesh_ubo.joint_offset, mesh_ubo.joint_offset);
+}
+
+// Blends the vector by up to four joints, weighted by the vertex's joint weights
+fn blend_joints(value: vec4<f32>, weight: vec4<f32>, x: mat4x4<f32>, y: mat4x4<f32>, z: mat4x4<f32>, w: mat4x4<f32>) -> vec4<f32> {
+    return weight.x * (x * value) + weight.y * (y * value) + weight.z * (z * value) + weight.w * (w * value);
+}
+
+fn skin(value: vec4<f32>, vertex: VertexInput) -> vec4<f32> {
+    let joint = joint_indices(vertex);
+    return blend_joints(value, vertex.weight_0, joints.matrices[joint.x], joints.matrices[joint.y], joints.matrices[joint.z], joints.matrices[joint.w]);
+}
+
+// Reads a joint matrix column by column from the joint texture, which holds 256 matrices per row
+fn joint_texture_matrix(index: u32) -> mat4x4<f32> {
+    let texel = vec2<i32>(i32(index % 256u) * 4, i32(index / 256u));
+    return mat4x4<f32>(
+        textureLoad(joint_texture, texel, 0),
+        textureLoad(joint_texture, texel + vec2<i32>(1, 0), 0),
+        textureLoad(joint_texture, texel + vec2<i32>(2, 0), 0),
+        textureLoad(joint_texture, texel + vec2<i32>(3, 0), 0)
+    );
+}
+
+fn skin_texture(value: vec4<f32>, vertex: VertexInput) -> vec4<f32> {
+    let joint = joint_indices(vertex);
+    return blend_joints(value, vertex.weight_0, joint_texture_matrix(joint.x), joint_texture_matrix(joint.y), joint_texture_matrix(joint.z), joint_texture_matrix(joint.w));
+}
+
+[[stage(vertex)]]
+fn vs_skinned(vertex: VertexInput) -> VertexOutput {
+    let position = skin(vec4<f32>(vertex.position, 1.0), vertex);
+    let normal = skin(vec4<f32>(vertex.normal, 0.0), vertex);
+    return vertex_output(vertex, position, normal);
+}
+
+[[stage(vertex)]]
+fn vs_skinned_texture(vertex: VertexInput) -> VertexOutput {
+    let position = skin_texture(vec4<f32>(vertex.position, 1.0), vertex);
+    let normal = skin_texture(vec4<f32>(vertex.normal, 0.0), vertex);
+    return vertex_output(vertex, position, normal);
+}
+
+// Shadow pass, rendering depth from the directional light
+
+[[stage(vertex)]]
+fn vs_shadow(vertex: VertexInput) -> [[builtin(position)]] vec4<f32> {
+    return ubo.light_view_projection * mesh_ubo.model * vec4<f32>(vertex.position, 1.0);
+}
+
+[[stage(vertex)]]
+fn vs_shadow_skinned(vertex: VertexInput) -> [[builtin(position)]] vec4<f32> {
+    let position = skin(vec4<f32>(vertex.position, 1.0), vertex);
+    return ubo.light_view_projection * mesh_ubo.model * position;
+}
+
+[[stage(vertex)]]
+fn vs_shadow_skinned_texture(vertex: VertexInput) -> [[builtin(position)]] vec4<f32> {
+    let position = skin_texture(vec4<f32>(vertex.position, 1.0), vertex);
+    return ubo.light_view_projection * mesh_ubo.model * position;
+}
+
 // Fragment shader
 
+let PI: f32 = 3.14159265359;
+let AMBIENT: f32 = 0.1;
+let LIGHT_INTENSITY: f32 = 3.0;
+
+fn texture_coordinates(set: i32, in: VertexOutput) -> vec2<f32> {
+    let uv = vec3<f32>(select(in.uv_0, in.uv_1, set == 1), 1.0);
+    return vec2<f32>(dot(material_ubo.texture_transform_x.xyz, uv), dot(material_ubo.texture_transform_y.xyz, uv));
+}
+
+// Normal mapping without precomputed tangents, using the screen space derivatives
+// of the position and texture coordinates to build the tangent frame
+fn perturb_normal(normal: vec3<f32>, position: vec3<f32>, uv: vec2<f32>, sampled: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(position);
+    let dp2 = dpdy(position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+    let dp2_perpendicular = cross(dp2, normal);
+    let dp1_perpendicular = cross(normal, dp1);
+    let tangent = dp2_perpendicular * duv1.x + dp1_perpendicular * duv2.x;
+    let bitangent = dp2_perpendicular * duv1.y + dp1_perpendicular * duv2.y;
+    let scale = inverseSqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+    let tbn = mat3x3<f32>(tangent * scale, bitangent * scale, normal);
+    return normalize(tbn * sampled);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let alpha = roughness * roughness;
+    let alpha_squared = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * denominator * denominator);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let view = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let light = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return view * light;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0, 1.0, 1.0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Smoothly fades a light to nothing at its range, falling off with the inverse square of the distance
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let inverse_square = 1.0 / max(distance * distance, 0.0001);
+    if (range <= 0.0) {
+        return inverse_square;
+    }
+    let ratio = distance / range;
+    return clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0) * inverse_square;
+}
+
+struct Surface {
+    position: vec3<f32>;
+    normal: vec3<f32>;
+    view_direction: vec3<f32>;
+    base_color: vec3<f32>;
+    metallic: f32;
+    roughness: f32;
+};
+
+// The light reflected towards the viewer by a light arriving from the light direction
+fn shade(surface: Surface, light_direction: vec3<f32>, radiance: vec3<f32>) -> vec3<f32> {
+    let normal = surface.normal;
+    let view_direction = surface.view_direction;
+    let halfway = normalize(view_direction + light_direction);
+    let n_dot_v = max(dot(normal, view_direction), 0.0001);
+    let n_dot_l = max(dot(normal, light_direction), 0.0);
+    let n_dot_h = max(dot(normal, halfway), 0.0);
+    let h_dot_v = max(dot(halfway, view_direction), 0.0);
+
+    let f0 = mix(vec3<f32>(0.04, 0.04, 0.04), surface.base_color, vec3<f32>(surface.metallic, surface.metallic, surface.metallic));
+    let fresnel = fresnel_schlick(h_dot_v, f0);
+    let specular = fresnel * (distribution_ggx(n_dot_h, surface.roughness) * geometry_smith(n_dot_v, n_dot_l, surface.roughness)
+        / (4.0 * n_dot_v * max(n_dot_l, 0.0001)));
+    let diffuse = (vec3<f32>(1.0, 1.0, 1.0) - fresnel) * surface.base_color * ((1.0 - surface.metallic) / PI);
+    return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// How much of the directional light reaches the position, from 0 in full shadow to 1 when lit.
+// Percentage closer filtering averages a 3x3 block of shadow map comparisons to soften the edges.
+fn shadow_factor(position: vec3<f32>, n_dot_l: f32) -> f32 {
+    if (ubo.shadow_parameters.y < 0.5) {
+        return 1.0;
+    }
+    let light_position = ubo.light_view_projection * vec4<f32>(position, 1.0);
+    let ndc = light_position.xyz / light_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    // Surfaces at a grazing angle to the light need more bias to avoid acne
+    let bias = ubo.shadow_parameters.x * (1.0 + 4.0 * (1.0 - n_dot_l));
+    let depth = ndc.z - bias;
+    let texel = ubo.shadow_parameters.z;
+    var lit: f32 = 0.0;
+    for (var x: i32 = -1; x <= 1; x = x + 1) {
+        for (var y: i32 = -1; y <= 1; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(shadow_texture, shadow_sampler, uv + offset, depth);
+        }
+    }
+    return lit / 9.0;
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(0.3, 0.2, 0.1, 1.0);
-}
\ No newline at end of file
+    let material = material_ubo;
+
+    // Everything using derivatives is sampled before any branching
+    let color_sample = textureSample(color_texture, color_sampler, texture_coordinates(material.color_texture_set, in));
+    let metallic_roughness_sample = textureSample(metallic_roughness_texture, metallic_roughness_sampler, texture_coordinates(material.metallic_roughness_texture_set, in));
+    let normal_uv = texture_coordinates(material.normal_texture_set, in);
+    let normal_sample = textureSample(normal_texture, normal_sampler, normal_uv);
+    let occlusion_sample = textureSample(occlusion_texture, occlusion_sampler, texture_coordinates(material.occlusion_texture_set, in));
+    let emissive_sample = textureSample(emissive_texture, emissive_sampler, texture_coordinates(material.emissive_texture_set, in));
+
+    // Meshes without normals are shaded with the normal of each face
+    let face_normal = normalize(cross(dpdy(in.position), dpdx(in.position)));
+    var normal: vec3<f32> = face_normal;
+    if (dot(in.normal, in.normal) > 0.0) {
+        normal = normalize(in.normal);
+    }
+    let tangent_normal = (normal_sample.xyz * 2.0 - vec3<f32>(1.0, 1.0, 1.0)) * vec3<f32>(material.normal_scale, material.normal_scale, 1.0);
+    let mapped_normal = perturb_normal(normal, in.position, normal_uv, tangent_normal);
+    if (material.normal_texture_set >= 0) {
+        normal = mapped_normal;
+    }
+
+    let base_color = in.color * material.base_color_factor * color_sample;
+    if (base_color.a < material.alpha_cutoff) {
+        discard;
+    }
+    if (material.is_unlit == 1) {
+        return base_color;
+    }
+
+    // Metalness is stored in the blue channel and roughness in the green channel
+    let metallic = clamp(material.metallic_factor * metallic_roughness_sample.b, 0.0, 1.0);
+    let roughness = clamp(material.roughness_factor * metallic_roughness_sample.g, 0.04, 1.0);
+    let occlusion = mix(1.0, occlusion_sample.r, material.occlusion_strength);
+    let emissive = material.emissive_factor.rgb * emissive_sample.rgb;
+
+    var surface: Surface;
+    surface.position = in.position;
+    surface.normal = normal;
+    surface.view_direction = normalize(ubo.camera_position.xyz - in.position);
+    surface.base_color = base_color.rgb;
+    surface.metallic = metallic;
+    surface.roughness = roughness;
+
+    var lit: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+
+    // Scenes without any lights are lit by a fallback directional light
+    if (lights_ubo.count == 0u) {
+        let light_direction = normalize(ubo.light_direction.xyz);
+        let shadow = shadow_factor(in.position, max(dot(normal, light_direction), 0.0));
+        let radiance = vec3<f32>(LIGHT_INTENSITY, LIGHT_INTENSITY, LIGHT_INTENSITY) * shadow;
+        lit = shade(surface, light_direction, radiance);
+    }
+
+    for (var index: u32 = 0u; index < lights_ubo.count; index = index + 1u) {
+        let light = lights_ubo.lights[index];
+        var light_direction: vec3<f32> = -normalize(light.direction.xyz);
+        var radiance: vec3<f32> = light.color.rgb * light.color.a;
+        if (light.kind != DIRECTIONAL_LIGHT) {
+            let offset = light.position.xyz - in.position;
+            let distance = length(offset);
+            light_direction = offset / max(distance, 0.0001);
+            radiance = radiance * attenuation(distance, light.range);
+        }
+        if (light.kind == SPOT_LIGHT) {
+            let cone_cos = dot(-light_direction, normalize(light.direction.xyz));
+            radiance = radiance * smoothStep(light.outer_cone_cos, light.inner_cone_cos, cone_cos);
+        }
+        if (i32(index) == lights_ubo.shadow_light) {
+            radiance = radiance * shadow_factor(in.position, max(dot(normal, light_direction), 0.0));
+        }
+        lit = lit + shade(surface, light_direction, radiance);
+    }
+
+    let ambient = AMBIENT * base_color.rgb * occlusion;
+    let color = ambient + lit + emissive;
+
+    // Nothing behind the surface is available to refract, so transmission is approximated with transparency
+    let alpha = base_color.a * (1.0 - clamp(material.transmission_factor, 0.0, 1.0));
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,55 @@
+// Combines the world with its bloom and maps it to the range the display can show
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// The exposure, bloom intensity and tonemapping operator
+struct Parameters {
+    values: vec4<f32>;
+};
+
+let TONEMAPPING_REINHARD: f32 = 1.0;
+let TONEMAPPING_ACES: f32 = 2.0;
+
+[[group(0), binding(0)]]
+var hdr_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var bloom_texture: texture_2d<f32>;
+[[group(0), binding(2)]]
+var source_sampler: sampler;
+[[group(0), binding(3)]]
+var<uniform> parameters: Parameters;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var output: VertexOutput;
+    output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (color + vec3<f32>(1.0));
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let hdr = textureSample(hdr_texture, source_sampler, input.uv);
+    let bloom = textureSample(bloom_texture, source_sampler, input.uv).rgb;
+    let color = (hdr.rgb + bloom * parameters.values.y) * parameters.values.x;
+
+    var mapped = color;
+    if (parameters.values.z == TONEMAPPING_ACES) {
+        mapped = aces(color);
+    } else if (parameters.values.z == TONEMAPPING_REINHARD) {
+        mapped = reinhard(color);
+    }
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), hdr.a);
+}
@@ -0,0 +1,58 @@
+use crate::Resources;
+use phantom_dependencies::{
+    anyhow::Result,
+    bincode,
+    serde::{Deserialize, Serialize},
+    winit::event::VirtualKeyCode,
+};
+use phantom_gui::SubtitleStyle;
+use phantom_render::ColorFilter;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AccessibilitySettings {
+    /// Multiplies the size of the gui's text and spacing
+    pub ui_scale: f32,
+    pub color_filter: ColorFilter,
+    pub subtitles: SubtitleStyle,
+
+    /// Keys that stay pressed after being pressed once, for players who can't hold keys down
+    pub hold_to_toggle: Vec<VirtualKeyCode>,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            color_filter: ColorFilter::None,
+            subtitles: SubtitleStyle::default(),
+            hold_to_toggle: Vec::new(),
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    pub const MIN_UI_SCALE: f32 = 0.5;
+    pub const MAX_UI_SCALE: f32 = 3.0;
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, bincode::serialize(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(bincode::deserialize(&std::fs::read(path)?)?)
+    }
+
+    /// Applies the settings to the gui, renderer and input.
+    /// Call this again after changing any of them.
+    pub fn apply(&self, resources: &mut Resources) {
+        let ui_scale = self.ui_scale.clamp(Self::MIN_UI_SCALE, Self::MAX_UI_SCALE);
+        if (resources.gui.ui_scale() - ui_scale).abs() > f32::EPSILON {
+            resources.gui.set_ui_scale(ui_scale);
+        }
+        resources.gui.subtitle_style = self.subtitles;
+        resources.renderer.set_color_filter(self.color_filter);
+        resources.input.hold_to_toggle = self.hold_to_toggle.iter().copied().collect();
+    }
+}
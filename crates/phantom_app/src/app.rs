@@ -1,9 +1,9 @@
+use phantom_audio::Audio;
 use phantom_dependencies::{
-    anyhow::{anyhow, Result},
-    env_logger,
+    anyhow::{anyhow, Context, Result},
     gilrs::Gilrs,
-    image::io::Reader,
-    log,
+    image, log,
+    raw_window_handle::HasRawWindowHandle,
     winit::{
         dpi::PhysicalSize,
         event::*,
@@ -12,17 +12,57 @@ use phantom_dependencies::{
     },
 };
 use phantom_gui::{Gui, ScreenDescriptor};
-use phantom_render::{create_render_backend, Backend};
+use phantom_platform::{Platform, PlatformConfig};
+use phantom_render::{
+    create_render_backend_with_fallbacks, Backend, RenderSettings, ThreadedRenderer,
+};
+use phantom_world::World;
 
-use crate::{Input, Resources, State, StateMachine, System};
+use crate::{
+    init_logging, mount_default_pak, read_asset, scan_mounted_asset_ids, AccessibilitySettings,
+    ActionContext, AppEvent, AssetServer, BugReporter, CVars, Console, Cursor, DataTables,
+    DisplaySettings, EventProxy, Input, ModLoader, Recorder, Resources, State, StateMachine,
+    StateScopes, System, Telemetry, WindowMode,
+};
 
 pub struct AppConfig {
     pub width: u32,
     pub height: u32,
+
+    /// Starts in borderless fullscreen when the display settings are windowed
     pub is_fullscreen: bool,
+
+    pub display: DisplaySettings,
     pub title: String,
     pub icon: Option<String>,
+
+    /// Lets the desktop show through wherever nothing is drawn, for overlay utilities.
+    /// Transparency can only be chosen when the window is created.
+    pub transparent: bool,
+
+    pub always_on_top: bool,
+
+    /// Borderless tool windows have no title bar or frame
+    pub decorations: bool,
+
     pub render_backend: Backend,
+
+    /// Tried in order when the render backend can't be created, such as without a Vulkan driver
+    pub render_fallbacks: Vec<Backend>,
+
+    /// Samples per pixel for multisample antialiasing, one of 1, 2, 4 or 8
+    pub sample_count: u32,
+
+    /// Submits frames from a dedicated render thread, so the next frame is simulated
+    /// while the last one is drawn
+    pub render_thread: bool,
+
+    pub platform: PlatformConfig,
+
+    /// Fixed updates per second
+    pub tick_rate: u32,
+
+    pub accessibility: AccessibilitySettings,
 }
 
 impl Default for AppConfig {
@@ -31,35 +71,107 @@ impl Default for AppConfig {
             width: 1024,
             height: 768,
             is_fullscreen: false,
+            display: DisplaySettings::default(),
             title: "Phantom Editor".to_string(),
             icon: None,
+            transparent: false,
+            always_on_top: false,
+            decorations: true,
             render_backend: Backend::Wgpu,
+            render_fallbacks: Backend::FALLBACKS.to_vec(),
+            sample_count: 4,
+            render_thread: true,
+            platform: PlatformConfig::default(),
+            tick_rate: 60,
+            accessibility: AccessibilitySettings::default(),
         }
     }
 }
 
 pub fn run(initial_state: impl State + 'static, config: AppConfig) -> Result<()> {
-    env_logger::init();
+    init_logging();
 
     log::info!("Phantom app started");
 
-    let event_loop = EventLoop::new();
+    mount_default_pak()?;
+
+    let mut mods = ModLoader::default();
+    if let Err(error) = mods.discover().and_then(|_| mods.apply()) {
+        log::error!("Failed to load mods: {}", error);
+    }
+
+    let event_loop = EventLoop::with_user_event();
+    let mut event_proxy = EventProxy::new(event_loop.create_proxy());
     let mut window_builder = WindowBuilder::new()
         .with_title(config.title.to_string())
-        .with_inner_size(PhysicalSize::new(config.width, config.height));
+        .with_inner_size(PhysicalSize::new(config.width, config.height))
+        .with_transparent(config.transparent)
+        .with_always_on_top(config.always_on_top)
+        .with_decorations(config.decorations);
 
     if let Some(icon_path) = config.icon.as_ref() {
-        let image = Reader::open(icon_path)?.decode()?.into_rgba8();
+        let image = image::load_from_memory(&read_asset(icon_path)?)?.into_rgba8();
         let (width, height) = image.dimensions();
         let icon = Icon::from_rgba(image.into_raw(), width, height)?;
         window_builder = window_builder.with_window_icon(Some(icon));
     }
 
-    let mut window = window_builder.build(&event_loop)?;
+    // Window managers without transparency or an always on top hint can refuse the window,
+    // so a plain window is tried before giving up
+    let mut window = match window_builder.build(&event_loop) {
+        Ok(window) => window,
+        Err(error) => {
+            log::warn!(
+                "Failed to create the window, retrying without extra attributes: {}",
+                error
+            );
+            WindowBuilder::new()
+                .with_title(config.title.to_string())
+                .with_inner_size(PhysicalSize::new(config.width, config.height))
+                .build(&event_loop)
+                .context("Failed to create a window!")?
+        }
+    };
+
+    let mut display = config.display.clone();
+    if config.is_fullscreen && display.mode == WindowMode::Windowed {
+        display.mode = WindowMode::BorderlessFullscreen;
+    }
+    if let Err(error) = display.apply_to_window(&window) {
+        log::error!("Failed to apply display settings: {}", error);
+    }
+
+    // The native window is created asynchronously on android
+    #[cfg(target_os = "android")]
+    while phantom_dependencies::ndk_glue::native_window().is_none() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
 
     let physical_size = window.inner_size();
     let window_dimensions = [physical_size.width, physical_size.height];
-    let mut renderer = create_render_backend(&config.render_backend, &window, &window_dimensions)?;
+    let backends = std::iter::once(config.render_backend)
+        .chain(
+            config
+                .render_fallbacks
+                .iter()
+                .copied()
+                .filter(|backend| *backend != config.render_backend),
+        )
+        .collect::<Vec<_>>();
+    let mut renderer =
+        create_render_backend_with_fallbacks(&backends, &window, &window_dimensions)?;
+    if config.render_thread {
+        renderer = Box::new(ThreadedRenderer::new(renderer)?);
+    }
+    log::info!("Rendering with the {:?} backend", renderer.backend());
+    if let Err(error) = renderer.attach_gui() {
+        log::warn!("The gui won't be drawn: {}", error);
+    }
+    renderer.set_transparent(config.transparent);
+    renderer.set_render_settings(RenderSettings {
+        sample_count: config.sample_count,
+        ..renderer.render_settings()
+    });
 
     let mut state_machine = StateMachine::new(initial_state);
 
@@ -71,18 +183,105 @@ pub fn run(initial_state: impl State + 'static, config: AppConfig) -> Result<()>
     });
 
     let mut input = Input::default();
-    let mut system = System::new(window_dimensions);
+    input.gamepads.add_connected(&gilrs);
+    let mut system = System::new(window_dimensions, config.tick_rate);
+    let mut platform = Platform::new(&config.platform);
+    let mut recorder = Recorder::default();
+    let mut telemetry = Telemetry::default();
+    let mut bug_reporter = BugReporter::default();
+    let mut console = Console::default();
+    let mut cvars = CVars::default();
+    let mut cursor = Cursor::default();
+    let mut scopes = StateScopes::default();
+    cvars.register_engine_cvars(&renderer.render_settings());
+    if std::path::Path::new(CVars::DEFAULT_PATH).exists() {
+        if let Err(error) = cvars.load_overrides(CVars::DEFAULT_PATH) {
+            log::error!("Failed to load cvars: {}", error);
+        }
+    }
+    cvars.apply_to_engine(&mut *renderer, &mut system);
+    cvars.take_changes();
+    let mut audio = Audio::default();
+    let mut data_tables = DataTables::default();
+    let mut asset_server = AssetServer::default();
+    // Assets referred to by id, such as the clips audio sources play, are found through their sidecars
+    if std::path::Path::new("assets").exists() {
+        if let Err(error) = asset_server.ids_mut().scan("assets") {
+            log::error!("Failed to scan asset ids: {}", error);
+        }
+    }
+    scan_mounted_asset_ids(asset_server.ids_mut());
 
+    config.accessibility.apply(&mut Resources {
+        window: &mut window,
+        gilrs: &mut gilrs,
+        renderer: &mut renderer,
+        gui: &mut gui,
+        audio: &mut audio,
+        input: &mut input,
+        system: &mut system,
+        platform: &mut platform,
+        recorder: &mut recorder,
+        telemetry: &mut telemetry,
+        bug_reporter: &mut bug_reporter,
+        console: &mut console,
+        cvars: &mut cvars,
+        cursor: &mut cursor,
+        data_tables: &mut data_tables,
+        asset_server: &mut asset_server,
+        mods: &mut mods,
+        scopes: &mut scopes,
+        event_proxy: &mut event_proxy,
+    });
+
+    // App events are delivered in the order they arrived before the next update
+    let mut app_events = Vec::new();
     event_loop.run(move |event, _, control_flow| {
+        let event = match event.map_nonuser_event() {
+            Ok(event) => event,
+            Err(Event::UserEvent(app_event)) => {
+                app_events.push(app_event);
+                return;
+            }
+            Err(_) => return,
+        };
+
+        if Input::is_queued_event(&event) {
+            // Input events never hold references, so they can always be made static
+            if let Some(event) = event.to_static() {
+                input.queue_event(event);
+            }
+            return;
+        }
+
         let mut resources = Resources {
             window: &mut window,
             gilrs: &mut gilrs,
             renderer: &mut renderer,
             gui: &mut gui,
+            audio: &mut audio,
             input: &mut input,
             system: &mut system,
+            platform: &mut platform,
+            recorder: &mut recorder,
+            telemetry: &mut telemetry,
+            bug_reporter: &mut bug_reporter,
+            console: &mut console,
+            cvars: &mut cvars,
+            cursor: &mut cursor,
+            data_tables: &mut data_tables,
+            asset_server: &mut asset_server,
+            mods: &mut mods,
+            scopes: &mut scopes,
+            event_proxy: &mut event_proxy,
         };
-        if let Err(error) = run_loop(&mut state_machine, &event, &mut resources, control_flow) {
+        if let Err(error) = run_loop(
+            &mut state_machine,
+            &event,
+            &mut app_events,
+            &mut resources,
+            control_flow,
+        ) {
             log::error!("Application error: {}", error);
         }
     });
@@ -91,6 +290,7 @@ pub fn run(initial_state: impl State + 'static, config: AppConfig) -> Result<()>
 fn run_loop(
     state_machine: &mut StateMachine,
     event: &Event<()>,
+    app_events: &mut Vec<AppEvent>,
     resources: &mut Resources,
     control_flow: &mut ControlFlow,
 ) -> Result<()> {
@@ -98,33 +298,151 @@ fn run_loop(
         state_machine.start(resources)?;
     }
 
+    // Input that arrived since the last frame is handled in order before updating
+    if let Event::MainEventsCleared = event {
+        for queued in resources.input.take_queued() {
+            resources.input.event_time = Some(queued.time);
+            handle_event(state_machine, &queued.event, resources, control_flow)?;
+        }
+        resources.input.event_time = None;
+
+        for app_event in app_events.drain(..) {
+            state_machine.on_user_event(resources, &app_event)?;
+        }
+
+        while let Some(event) = resources.gilrs.next_event() {
+            resources.gui.handle_gamepad_event(&event.event);
+            resources.input.handle_gamepad_event(&event);
+            state_machine.on_gamepad_event(resources, event)?;
+        }
+    }
+
+    handle_event(state_machine, event, resources, control_flow)
+}
+
+fn handle_event(
+    state_machine: &mut StateMachine,
+    event: &Event<()>,
+    resources: &mut Resources,
+    control_flow: &mut ControlFlow,
+) -> Result<()> {
+    resources.system.handle_event(&event);
+    let window_center = resources.system.window_center();
+    resources.input.handle_event(&event, window_center);
     resources.gui.handle_event(&event);
 
     state_machine
         .handle_event(resources, &event)
         .expect("Failed to handle event!");
 
-    if let Some(event) = resources.gilrs.next_event() {
-        state_machine
-            .current_state()?
-            .on_gamepad_event(resources, event)?;
-    }
-
     match event {
+        Event::Suspended => {
+            resources.system.suspended = true;
+            resources.renderer.suspend();
+            resources.audio.set_suspended(true);
+        }
+
+        Event::Resumed => {
+            if resources.system.suspended {
+                let size = resources.window.inner_size();
+                resources.renderer.resume(
+                    resources.window.raw_window_handle(),
+                    [size.width, size.height],
+                )?;
+                resources.system.suspended = false;
+                resources.audio.set_suspended(false);
+            }
+        }
+
+        Event::MainEventsCleared if resources.system.suspended => {}
+
+        Event::LoopDestroyed => {
+            resources.telemetry.shutdown();
+            if let Err(error) = resources.cvars.save_overrides(CVars::DEFAULT_PATH) {
+                log::error!("Failed to save cvars: {}", error);
+            }
+        }
+
         Event::MainEventsCleared => {
+            resources.platform.update();
+            let delta_time = resources.system.delta_time as f32;
+            resources.telemetry.record_frame_time(delta_time);
+            resources
+                .bug_reporter
+                .update(resources.recorder, &**resources.renderer)?;
+            resources.data_tables.reload_changed();
+            resources.asset_server.update();
+            for world in state_machine.running_worlds() {
+                load_clip_assets(resources.audio, resources.asset_server, world);
+            }
+            let changes = resources.cvars.take_changes();
+            if !changes.is_empty() {
+                resources
+                    .cvars
+                    .apply_to_engine(&mut **resources.renderer, resources.system);
+            }
+            // Worlds only follow the physics cvars when they change,
+            // so loaded scenes keep their own settings until then
+            if changes.iter().any(|name| name.starts_with("p.")) {
+                for world in state_machine.running_worlds() {
+                    resources.cvars.apply_to_world(world);
+                }
+            }
+
+            let typed = resources.gui.virtual_keyboard.take_text();
+            resources.input.text.push_str(&typed);
+
+            let gpu_cloth = resources.renderer.simulates_cloth();
+            for world in state_machine.running_worlds() {
+                world.gpu_cloth = gpu_cloth;
+            }
+
+            for _ in 0..resources.system.fixed_updates() {
+                state_machine.fixed_update(resources)?;
+            }
             state_machine.update(resources)?;
+            for clip in resources.audio.take_started_clips() {
+                resources.gui.subtitles.audio_started(&clip);
+            }
 
             let _frame_data = resources
                 .gui
                 .start_frame(resources.window.scale_factor() as _);
 
             state_machine.update_gui(resources)?;
+            resources
+                .console
+                .ui(&resources.gui.context(), resources.cvars);
+
+            // The console takes every input while it's open
+            let actions = &mut resources.input.actions;
+            actions.context_mut(ActionContext::CONSOLE).exclusive = true;
+            actions.set_context_active(ActionContext::CONSOLE, resources.console.open);
+
+            resources.cursor.paint(&resources.gui.context());
 
             let paint_jobs = resources.gui.end_frame(&resources.window);
+            resources
+                .cursor
+                .apply(resources.window, resources.gui.cursor_icon());
 
             resources
                 .renderer
-                .render(&resources.gui.context(), paint_jobs)?;
+                .set_frame_capture(resources.recorder.capturing());
+            if resources
+                .recorder
+                .wants_frame(resources.system.delta_time as f32)
+            {
+                resources.renderer.capture_next_frame();
+            }
+            if let Some(gui_renderer) = resources.renderer.gui_renderer() {
+                gui_renderer.update(&resources.gui.context(), paint_jobs);
+            }
+            resources.renderer.set_viewport(resources.system.viewport);
+            resources.renderer.render()?;
+            if let Some(frame) = resources.renderer.take_captured_frame() {
+                resources.recorder.push(frame);
+            }
         }
 
         Event::WindowEvent {
@@ -139,20 +457,38 @@ fn run_loop(
                 {
                     *control_flow = ControlFlow::Exit;
                 }
-                state_machine.current_state()?.on_key(resources, *input)?;
+                if input.state == ElementState::Pressed
+                    && resources.recorder.enabled
+                    && input.virtual_keycode.is_some()
+                    && input.virtual_keycode == resources.recorder.hotkey
+                {
+                    resources.recorder.save()?;
+                }
+                if input.state == ElementState::Pressed
+                    && input.virtual_keycode.is_some()
+                    && input.virtual_keycode == resources.bug_reporter.hotkey
+                {
+                    resources.bug_reporter.request(resources.recorder);
+                }
+                if input.state == ElementState::Pressed
+                    && input.virtual_keycode.is_some()
+                    && input.virtual_keycode == resources.console.hotkey
+                {
+                    resources.console.toggle();
+                }
+                state_machine.on_key(resources, *input)?;
             }
 
             WindowEvent::MouseInput { button, state, .. } => {
-                state_machine
-                    .current_state()?
-                    .on_mouse(resources, button, state)?;
+                state_machine.on_mouse(resources, button, state)?;
+            }
+
+            WindowEvent::Touch(ref touch) => {
+                state_machine.on_touch(resources, touch)?;
             }
 
             WindowEvent::DroppedFile(ref path) => {
-                // TODO: Transition if a state transition is returned
-                state_machine
-                    .current_state()?
-                    .on_file_dropped(resources, path)?;
+                state_machine.on_file_dropped(resources, path)?;
             }
 
             WindowEvent::Resized(physical_size) => {
@@ -174,3 +510,22 @@ fn run_loop(
     }
     Ok(())
 }
+
+/// Loads the clips audio sources refer to, requesting them from the asset server
+/// and handing them to the audio once they have been read
+fn load_clip_assets(audio: &mut Audio, asset_server: &mut AssetServer, world: &World) {
+    for id in audio.missing_clip_assets(world) {
+        let path = match asset_server.load_id(id) {
+            Some(path) => path,
+            None => continue,
+        };
+        let bytes = match asset_server.get(&path) {
+            Some(bytes) => bytes.to_vec(),
+            None => continue,
+        };
+        let name = path.display().to_string();
+        if let Err(error) = audio.load_clip_asset(id, &name, bytes) {
+            log::error!("Failed to load audio clip {}: {}", name, error);
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{files_in, read_asset, Pak};
+use phantom_dependencies::{anyhow::Result, log, serde_json};
+use phantom_world::{gltf_dependencies, AssetId, AssetMeta};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+/// Something wrong with an asset's dependencies, found when they are scanned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyProblem {
+    Missing {
+        asset: PathBuf,
+        dependency: PathBuf,
+    },
+
+    /// The assets around the cycle, starting and ending with the same asset
+    Cycle(Vec<PathBuf>),
+}
+
+impl fmt::Display for DependencyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing { asset, dependency } => write!(
+                f,
+                "{} depends on {}, which is missing",
+                asset.display(),
+                dependency.display()
+            ),
+            Self::Cycle(assets) => write!(
+                f,
+                "Cyclic dependency: {}",
+                assets
+                    .iter()
+                    .map(|asset| asset.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
+        }
+    }
+}
+
+/// The assets found in an asset's contents that it needs to be loaded,
+/// such as the buffers and textures of a glTF scene
+pub fn discover_dependencies(path: &Path, bytes: &[u8]) -> Result<Vec<PathBuf>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "gltf" | "glb" => gltf_dependencies(path, bytes),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Which assets each asset needs, found in their contents or added by hand
+#[derive(Default, Debug, Clone)]
+pub struct AssetGraph {
+    dependencies: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl AssetGraph {
+    /// Returns the cycle the dependency closes, if any. It is still added,
+    /// since everything walking the graph visits each asset once.
+    pub fn add_dependency(
+        &mut self,
+        asset: impl AsRef<Path>,
+        dependency: impl AsRef<Path>,
+    ) -> Option<Vec<PathBuf>> {
+        let (asset, dependency) = (asset.as_ref(), dependency.as_ref());
+        let dependencies = self.dependencies.entry(asset.to_path_buf()).or_default();
+        if dependencies.iter().any(|existing| existing == dependency) {
+            return None;
+        }
+        dependencies.push(dependency.to_path_buf());
+        self.path_between(dependency, asset).map(|mut cycle| {
+            cycle.insert(0, asset.to_path_buf());
+            cycle
+        })
+    }
+
+    /// The assets an asset needs directly
+    pub fn dependencies(&self, asset: impl AsRef<Path>) -> &[PathBuf] {
+        self.dependencies
+            .get(asset.as_ref())
+            .map_or(&[], |dependencies| dependencies.as_slice())
+    }
+
+    /// The assets that need an asset directly
+    pub fn dependents(&self, asset: impl AsRef<Path>) -> Vec<&Path> {
+        let asset = asset.as_ref();
+        self.dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.iter().any(|dependency| dependency == asset))
+            .map(|(dependent, _)| dependent.as_path())
+            .collect()
+    }
+
+    /// The assets followed by everything they need directly or indirectly, each listed once
+    pub fn closure(&self, assets: &[PathBuf]) -> Vec<PathBuf> {
+        let mut visited = HashSet::new();
+        let mut closure = Vec::new();
+        let mut pending = assets.iter().rev().collect::<Vec<_>>();
+        while let Some(asset) = pending.pop() {
+            if !visited.insert(asset) {
+                continue;
+            }
+            closure.push(asset.to_path_buf());
+            pending.extend(self.dependencies(asset).iter().rev());
+        }
+        closure
+    }
+
+    /// Reads an asset and everything it needs, adding the dependencies found in their contents.
+    /// Fails only if the asset itself can't be read.
+    pub fn scan(
+        &mut self,
+        asset: impl AsRef<Path>,
+        mut read: impl FnMut(&Path) -> Result<Vec<u8>>,
+    ) -> Result<Vec<DependencyProblem>> {
+        let asset = asset.as_ref().to_path_buf();
+        let mut problems = Vec::new();
+        let mut scanned = HashSet::new();
+        let mut pending = vec![(asset.clone(), None)];
+        while let Some((path, dependent)) = pending.pop() {
+            if !scanned.insert(path.clone()) {
+                continue;
+            }
+            let bytes = match (read(&path), dependent) {
+                (Ok(bytes), _) => bytes,
+                (Err(error), None) => return Err(error),
+                (Err(_), Some(dependent)) => {
+                    problems.push(DependencyProblem::Missing {
+                        asset: dependent,
+                        dependency: path,
+                    });
+                    continue;
+                }
+            };
+            for dependency in discover_dependencies(&path, &bytes)? {
+                if let Some(cycle) = self.add_dependency(&path, &dependency) {
+                    problems.push(DependencyProblem::Cycle(cycle));
+                }
+                pending.push((dependency, Some(path.clone())));
+            }
+        }
+        Ok(problems)
+    }
+
+    /// The assets leading from one asset to another through their dependencies, both included
+    fn path_between(&self, from: &Path, to: &Path) -> Option<Vec<PathBuf>> {
+        let mut previous: HashMap<&Path, &Path> = HashMap::new();
+        let mut pending = vec![from];
+        while let Some(asset) = pending.pop() {
+            if asset == to {
+                let mut path = vec![asset.to_path_buf()];
+                let mut current = asset;
+                while let Some(parent) = previous.get(current) {
+                    path.push(parent.to_path_buf());
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for dependency in self.dependencies(asset) {
+                let dependency = dependency.as_path();
+                if dependency != from && !previous.contains_key(dependency) {
+                    previous.insert(dependency, asset);
+                    pending.push(dependency);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Where each asset with a `.meta` sidecar currently is, so assets referred to
+/// by id are still found after being renamed or moved along with their sidecar
+#[derive(Default, Debug, Clone)]
+pub struct AssetIds {
+    paths: HashMap<AssetId, PathBuf>,
+}
+
+impl AssetIds {
+    /// Finds the sidecars beneath a directory, replacing the paths of ids found before
+    pub fn scan(&mut self, directory: impl AsRef<Path>) -> Result<()> {
+        for path in files_in(directory.as_ref())? {
+            if path
+                .extension()
+                .map_or(false, |extension| extension == "meta")
+            {
+                let bytes = std::fs::read(&path)?;
+                self.insert_meta(path, &bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the sidecars packed into a pak
+    pub fn scan_pak(&mut self, pak: &Pak) {
+        for path in pak.paths().filter(|path| path.ends_with(".meta")) {
+            if let Some(bytes) = pak.read(path) {
+                self.insert_meta(PathBuf::from(path), bytes);
+            }
+        }
+    }
+
+    pub fn insert(&mut self, id: AssetId, path: impl AsRef<Path>) {
+        self.paths.insert(id, path.as_ref().to_path_buf());
+    }
+
+    pub fn path(&self, id: AssetId) -> Option<&Path> {
+        self.paths.get(&id).map(PathBuf::as_path)
+    }
+
+    pub fn id(&self, path: impl AsRef<Path>) -> Option<AssetId> {
+        let path = path.as_ref();
+        self.paths
+            .iter()
+            .find(|(_, asset_path)| asset_path.as_path() == path)
+            .map(|(id, _)| *id)
+    }
+
+    fn insert_meta(&mut self, meta_path: PathBuf, bytes: &[u8]) {
+        match serde_json::from_slice::<AssetMeta>(bytes) {
+            Ok(meta) => {
+                let asset = meta_path.with_extension("");
+                if let Some(existing) = self.paths.get(&AssetId(meta.uuid)) {
+                    if existing != &asset {
+                        log::warn!(
+                            "{} and {} have the same asset id, probably from a copied sidecar",
+                            existing.display(),
+                            asset.display()
+                        );
+                    }
+                }
+                self.paths.insert(AssetId(meta.uuid), asset);
+            }
+            Err(error) => log::warn!("Failed to read {}: {}", meta_path.display(), error),
+        }
+    }
+}
+
+/// How much of a preload set has finished loading. The total grows
+/// as the dependencies of the set's assets are found while they load.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PreloadProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl PreloadProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.loaded + self.failed) as f32 / self.total as f32
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+}
+
+enum AssetState {
+    Loading,
+    Loaded(Vec<u8>),
+    Failed(String),
+}
+
+/// Reads assets on a background thread, loading everything each asset depends on with it.
+/// Preload sets name groups of assets, such as everything a level needs,
+/// so their progress can be shown on a loading screen.
+pub struct AssetServer {
+    graph: AssetGraph,
+    ids: AssetIds,
+    assets: HashMap<PathBuf, AssetState>,
+    preload_sets: HashMap<String, Vec<PathBuf>>,
+    requests: Sender<PathBuf>,
+    loaded: Receiver<(PathBuf, Result<Vec<u8>>)>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        let (requests, request_receiver) = channel::<PathBuf>();
+        let (loaded_sender, loaded) = channel();
+        thread::Builder::new()
+            .name("assets".to_string())
+            .spawn(move || {
+                for path in request_receiver.iter() {
+                    let result = read_asset(&path);
+                    if loaded_sender.send((path, result)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn the asset loading thread!");
+        Self {
+            graph: AssetGraph::default(),
+            ids: AssetIds::default(),
+            assets: HashMap::new(),
+            preload_sets: HashMap::new(),
+            requests,
+            loaded,
+        }
+    }
+}
+
+impl AssetServer {
+    /// Starts loading an asset and everything it depends on, unless it has been already
+    pub fn load(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        if self.assets.contains_key(path) {
+            return;
+        }
+        self.assets.insert(path.to_path_buf(), AssetState::Loading);
+        if self.requests.send(path.to_path_buf()).is_err() {
+            self.assets.insert(
+                path.to_path_buf(),
+                AssetState::Failed("The asset loading thread has stopped!".to_string()),
+            );
+        }
+    }
+
+    /// Starts loading the asset with an id, returning where it was found
+    pub fn load_id(&mut self, id: AssetId) -> Option<PathBuf> {
+        let path = self.ids.path(id)?.to_path_buf();
+        self.load(&path);
+        Some(path)
+    }
+
+    /// The contents of a loaded asset
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&[u8]> {
+        match self.assets.get(path.as_ref()) {
+            Some(AssetState::Loaded(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn is_loaded(&self, path: impl AsRef<Path>) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Why an asset failed to load, if it did
+    pub fn error(&self, path: impl AsRef<Path>) -> Option<&str> {
+        match self.assets.get(path.as_ref()) {
+            Some(AssetState::Failed(error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Frees an asset's contents. Its dependencies stay loaded, since other assets may need them.
+    pub fn unload(&mut self, path: impl AsRef<Path>) {
+        if self.is_loaded(&path) {
+            self.assets.remove(path.as_ref());
+        }
+    }
+
+    pub fn graph(&self) -> &AssetGraph {
+        &self.graph
+    }
+
+    pub fn ids(&self) -> &AssetIds {
+        &self.ids
+    }
+
+    /// Scan asset directories and paks with this so assets can be loaded by id
+    pub fn ids_mut(&mut self) -> &mut AssetIds {
+        &mut self.ids
+    }
+
+    /// Declares a dependency that can't be found in the asset's contents,
+    /// such as the audio clips a saved scene plays
+    pub fn add_dependency(&mut self, asset: impl AsRef<Path>, dependency: impl AsRef<Path>) {
+        let (asset, dependency) = (asset.as_ref(), dependency.as_ref());
+        self.track_dependency(asset, dependency);
+        if self.assets.contains_key(asset) {
+            self.load(dependency);
+        }
+    }
+
+    /// Names a set of assets and starts loading them with everything they depend on,
+    /// replacing any set with the same name
+    pub fn preload(&mut self, name: &str, assets: &[impl AsRef<Path>]) {
+        let assets = assets
+            .iter()
+            .map(|asset| asset.as_ref().to_path_buf())
+            .collect::<Vec<_>>();
+        for asset in assets.iter() {
+            self.load(asset);
+        }
+        self.preload_sets.insert(name.to_string(), assets);
+    }
+
+    pub fn remove_preload_set(&mut self, name: &str) {
+        self.preload_sets.remove(name);
+    }
+
+    /// The progress of every asset in a preload set and everything they depend on
+    pub fn preload_progress(&self, name: &str) -> Option<PreloadProgress> {
+        let assets = self.preload_sets.get(name)?;
+        let mut progress = PreloadProgress::default();
+        for asset in self.graph.closure(assets) {
+            progress.total += 1;
+            match self.assets.get(&asset) {
+                Some(AssetState::Loaded(_)) => progress.loaded += 1,
+                Some(AssetState::Failed(_)) => progress.failed += 1,
+                _ => {}
+            }
+        }
+        Some(progress)
+    }
+
+    /// Stores the assets read since the last update and starts loading their dependencies
+    pub fn update(&mut self) {
+        while let Ok((path, result)) = self.loaded.try_recv() {
+            match result {
+                Ok(bytes) => {
+                    match discover_dependencies(&path, &bytes) {
+                        Ok(dependencies) => {
+                            for dependency in dependencies {
+                                self.track_dependency(&path, &dependency);
+                                self.load(&dependency);
+                            }
+                        }
+                        Err(error) => log::warn!(
+                            "Failed to find the dependencies of {}: {}",
+                            path.display(),
+                            error
+                        ),
+                    }
+                    self.assets.insert(path, AssetState::Loaded(bytes));
+                }
+                Err(error) => {
+                    for dependent in self.graph.dependents(&path) {
+                        let problem = DependencyProblem::Missing {
+                            asset: dependent.to_path_buf(),
+                            dependency: path.to_path_buf(),
+                        };
+                        log::warn!("{}", problem);
+                    }
+                    log::error!("Failed to load {}: {}", path.display(), error);
+                    self.assets
+                        .insert(path, AssetState::Failed(error.to_string()));
+                }
+            }
+        }
+    }
+
+    fn track_dependency(&mut self, asset: &Path, dependency: &Path) {
+        if let Some(cycle) = self.graph.add_dependency(asset, dependency) {
+            log::warn!("{}", DependencyProblem::Cycle(cycle));
+        }
+    }
+}
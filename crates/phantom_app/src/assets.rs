@@ -0,0 +1,48 @@
+use crate::read_mounted_asset;
+use phantom_dependencies::anyhow::Result;
+use std::path::Path;
+
+/// Reads a file bundled with the app.
+/// On android this reads from the apk's assets, on iOS from the app bundle,
+/// and everywhere else from the working directory.
+/// Assets in mounted paks take priority over all of these.
+pub fn read_asset(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    if let Some(bytes) = read_mounted_asset(path.as_ref()) {
+        return Ok(bytes);
+    }
+    read_platform_asset(path.as_ref())
+}
+
+#[cfg(target_os = "android")]
+fn read_platform_asset(path: &Path) -> Result<Vec<u8>> {
+    use phantom_dependencies::{anyhow::Context, ndk_glue};
+    use std::{ffi::CString, io::Read};
+
+    let path = path
+        .to_str()
+        .context("Asset paths must be valid unicode!")?
+        .trim_start_matches("assets/");
+    let mut asset = ndk_glue::native_activity()
+        .asset_manager()
+        .open(&CString::new(path)?)
+        .context(format!("Failed to find asset: {}", path))?;
+    let mut bytes = Vec::new();
+    asset.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(target_os = "ios")]
+fn read_platform_asset(path: &Path) -> Result<Vec<u8>> {
+    use phantom_dependencies::anyhow::Context;
+
+    let bundle = std::env::current_exe()?;
+    let bundle = bundle
+        .parent()
+        .context("Failed to find the app bundle directory!")?;
+    Ok(std::fs::read(bundle.join(path))?)
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn read_platform_asset(path: &Path) -> Result<Vec<u8>> {
+    Ok(std::fs::read(path)?)
+}
@@ -0,0 +1,295 @@
+use crate::read_asset;
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    csv, log, ron,
+    serde::de::DeserializeOwned,
+    serde_json::{self, Map, Value},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub type Row = Map<String, Value>;
+
+/// Rows of designer-tuned values such as weapons, enemies or loot,
+/// loaded from a csv, json or ron file
+#[derive(Debug, Clone)]
+pub struct DataTable {
+    pub name: String,
+    pub path: PathBuf,
+    pub columns: Vec<String>,
+    rows: Vec<Row>,
+}
+
+impl DataTable {
+    /// The column that rows are looked up by
+    pub const KEY_COLUMN: &'static str = "id";
+
+    pub const EXTENSIONS: [&'static str; 3] = ["csv", "json", "ron"];
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::parse(path, &read_asset(path)?)
+    }
+
+    /// Parses a table, picking the format from the path's extension.
+    /// Json and ron tables are a list of rows, or a map of rows keyed by their id.
+    pub fn parse(path: impl AsRef<Path>, bytes: &[u8]) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let (columns, rows) = match extension.as_str() {
+            "csv" => parse_csv(bytes)?,
+            "json" => value_rows(serde_json::from_slice(bytes)?)?,
+            "ron" => value_rows(ron::de::from_bytes(bytes)?)?,
+            _ => bail!("Unsupported data table format: {}", path.display()),
+        };
+
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .context(format!("Invalid data table path: {}", path.display()))?
+            .to_string();
+
+        Ok(Self {
+            name,
+            path: path.to_path_buf(),
+            columns,
+            rows,
+        })
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Row> {
+        self.rows
+            .iter()
+            .find(|row| match row.get(Self::KEY_COLUMN) {
+                Some(Value::String(key)) => key == id,
+                Some(key) => key.to_string() == id,
+                None => false,
+            })
+    }
+
+    /// Deserializes the row with the given id
+    pub fn row<T: DeserializeOwned>(&self, id: &str) -> Result<T> {
+        let row = self
+            .find(id)
+            .context(format!("Table '{}' has no row '{}'", self.name, id))?;
+        deserialize_row(row)
+    }
+
+    /// Deserializes every row
+    pub fn typed_rows<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.rows.iter().map(deserialize_row).collect()
+    }
+
+    pub fn value(&self, row: usize, column: &str) -> Option<&Value> {
+        self.rows.get(row).and_then(|row| row.get(column))
+    }
+}
+
+fn deserialize_row<T: DeserializeOwned>(row: &Row) -> Result<T> {
+    Ok(serde_json::from_value(Value::Object(row.clone()))?)
+}
+
+/// Cells holding numbers or booleans are parsed as such and empty cells are null
+fn parse_csv(bytes: &[u8]) -> Result<(Vec<String>, Vec<Row>)> {
+    let mut reader = csv::Reader::from_reader(bytes);
+    let columns = reader
+        .headers()?
+        .iter()
+        .map(|header| header.trim().to_string())
+        .collect::<Vec<_>>();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let row = columns
+            .iter()
+            .zip(record?.iter())
+            .map(|(column, cell)| (column.clone(), parse_cell(cell.trim())))
+            .collect();
+        rows.push(row);
+    }
+    Ok((columns, rows))
+}
+
+fn parse_cell(cell: &str) -> Value {
+    if cell.is_empty() {
+        Value::Null
+    } else if let Ok(value) = cell.parse::<bool>() {
+        Value::Bool(value)
+    } else if let Ok(value) = cell.parse::<i64>() {
+        Value::from(value)
+    } else if let Ok(value) = cell.parse::<f64>() {
+        Value::from(value)
+    } else {
+        Value::String(cell.to_string())
+    }
+}
+
+/// Columns are listed with the id first, followed by every other field in order of appearance
+fn value_rows(value: Value) -> Result<(Vec<String>, Vec<Row>)> {
+    let rows = match value {
+        Value::Array(rows) => rows
+            .into_iter()
+            .map(|row| match row {
+                Value::Object(row) => Ok(row),
+                _ => bail!("Data table rows must be objects"),
+            })
+            .collect::<Result<Vec<Row>>>()?,
+        Value::Object(rows) => rows
+            .into_iter()
+            .map(|(id, row)| match row {
+                Value::Object(mut row) => {
+                    row.insert(DataTable::KEY_COLUMN.to_string(), Value::String(id));
+                    Ok(row)
+                }
+                _ => bail!("Data table rows must be objects"),
+            })
+            .collect::<Result<Vec<Row>>>()?,
+        _ => bail!("Data tables must be a list or map of rows"),
+    };
+
+    let mut columns = vec![DataTable::KEY_COLUMN.to_string()];
+    for column in rows.iter().flat_map(|row| row.keys()) {
+        if !columns.contains(column) {
+            columns.push(column.to_string());
+        }
+    }
+    if !rows
+        .iter()
+        .any(|row| row.contains_key(DataTable::KEY_COLUMN))
+    {
+        columns.remove(0);
+    }
+    Ok((columns, rows))
+}
+
+/// Every loaded data table, reloaded when its file changes on disk
+/// so designers can tune numbers while the game is running
+pub struct DataTables {
+    pub hot_reload: bool,
+    tables: BTreeMap<String, DataTable>,
+    modified: HashMap<String, SystemTime>,
+    reloaded: Vec<String>,
+    last_check: Instant,
+}
+
+impl Default for DataTables {
+    fn default() -> Self {
+        Self {
+            hot_reload: cfg!(debug_assertions),
+            tables: BTreeMap::new(),
+            modified: HashMap::new(),
+            reloaded: Vec::new(),
+            last_check: Instant::now(),
+        }
+    }
+}
+
+impl DataTables {
+    const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Loads a table, replacing any table with the same name
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<&DataTable> {
+        let table = DataTable::load(path)?;
+        if let Some(modified) = modified_time(&table.path) {
+            self.modified.insert(table.name.clone(), modified);
+        }
+        let name = table.name.clone();
+        self.tables.insert(name.clone(), table);
+        Ok(&self.tables[&name])
+    }
+
+    /// Loads every table in a directory on disk
+    pub fn load_directory(&mut self, directory: impl AsRef<Path>) -> Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let supported = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| {
+                    DataTable::EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                });
+            if supported {
+                self.load(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataTable> {
+        self.tables.get(name)
+    }
+
+    pub fn table(&self, name: &str) -> Result<&DataTable> {
+        self.get(name)
+            .context(format!("Data table '{}' has not been loaded", name))
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &DataTable> {
+        self.tables.values()
+    }
+
+    /// Deserializes a row from a table by its id
+    pub fn row<T: DeserializeOwned>(&self, table: &str, id: &str) -> Result<T> {
+        self.table(table)?.row(id)
+    }
+
+    pub fn unload(&mut self, name: &str) {
+        self.tables.remove(name);
+        self.modified.remove(name);
+    }
+
+    /// Tables reloaded by the last call to `reload_changed`,
+    /// so cached values read from them can be refreshed
+    pub fn reloaded(&self) -> &[String] {
+        &self.reloaded
+    }
+
+    /// Reloads tables whose files changed since they were loaded.
+    /// A table that fails to parse keeps its previous rows.
+    pub fn reload_changed(&mut self) {
+        self.reloaded.clear();
+        if !self.hot_reload || self.last_check.elapsed() < Self::CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let changed = self
+            .tables
+            .values()
+            .filter_map(|table| {
+                let modified = modified_time(&table.path)?;
+                (self.modified.get(&table.name) != Some(&modified))
+                    .then(|| (table.name.clone(), table.path.clone(), modified))
+            })
+            .collect::<Vec<_>>();
+
+        for (name, path, modified) in changed {
+            self.modified.insert(name.clone(), modified);
+            match DataTable::load(&path) {
+                Ok(table) => {
+                    log::info!("Reloaded data table '{}'", name);
+                    self.tables.insert(name.clone(), table);
+                    self.reloaded.push(name);
+                }
+                Err(error) => log::error!("Failed to reload data table '{}': {}", name, error),
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
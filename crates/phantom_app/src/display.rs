@@ -0,0 +1,249 @@
+use crate::Resources;
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    bincode, log,
+    serde::{Deserialize, Serialize},
+    winit::{
+        monitor::{MonitorHandle, VideoMode},
+        window::{Fullscreen, Window},
+    },
+};
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum WindowMode {
+    Windowed,
+
+    /// Covers the monitor with a borderless window at the desktop's resolution
+    BorderlessFullscreen,
+
+    /// Takes over the monitor and switches it to the chosen video mode
+    ExclusiveFullscreen,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct VideoModeInfo {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u16,
+    pub bit_depth: u16,
+}
+
+impl VideoModeInfo {
+    fn new(video_mode: &VideoMode) -> Self {
+        let size = video_mode.size();
+        Self {
+            width: size.width,
+            height: size.height,
+            refresh_rate: video_mode.refresh_rate(),
+            bit_depth: video_mode.bit_depth(),
+        }
+    }
+}
+
+impl std::fmt::Display for VideoModeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} @ {}Hz",
+            self.width, self.height, self.refresh_rate
+        )
+    }
+}
+
+/// Finds a monitor again by its name and where it sits on the desktop.
+/// Monitors without a name, or that share one, are told apart by their position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct MonitorId {
+    pub name: Option<String>,
+    pub position: [i32; 2],
+}
+
+impl MonitorId {
+    fn new(monitor: &MonitorHandle) -> Self {
+        let position = monitor.position();
+        Self {
+            name: monitor.name(),
+            position: [position.x, position.y],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub id: MonitorId,
+
+    /// The monitor's name, or its number for monitors that don't report one
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub position: [i32; 2],
+    pub scale_factor: f64,
+    pub primary: bool,
+
+    /// Sorted from the largest resolution and fastest refresh rate down
+    pub video_modes: Vec<VideoModeInfo>,
+}
+
+/// Lists the monitors connected to the system and the video modes each supports
+pub fn monitors(window: &Window) -> Vec<MonitorInfo> {
+    let primary = window.primary_monitor();
+    window
+        .available_monitors()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let size = monitor.size();
+            let position = monitor.position();
+            let mut video_modes = monitor
+                .video_modes()
+                .map(|video_mode| VideoModeInfo::new(&video_mode))
+                .collect::<Vec<_>>();
+            video_modes.sort_by(|a, b| {
+                (b.width * b.height, b.refresh_rate, b.bit_depth).cmp(&(
+                    a.width * a.height,
+                    a.refresh_rate,
+                    a.bit_depth,
+                ))
+            });
+            video_modes.dedup();
+            MonitorInfo {
+                id: MonitorId::new(&monitor),
+                name: monitor
+                    .name()
+                    .unwrap_or_else(|| format!("Monitor {}", index + 1)),
+                width: size.width,
+                height: size.height,
+                position: [position.x, position.y],
+                scale_factor: monitor.scale_factor(),
+                primary: primary.as_ref() == Some(&monitor),
+                video_modes,
+            }
+        })
+        .collect()
+}
+
+/// How the window is shown, which games usually expose in their graphics menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct DisplaySettings {
+    pub mode: WindowMode,
+
+    /// The monitor to go fullscreen on, or the window's current monitor when `None`
+    pub monitor: Option<MonitorId>,
+
+    /// Video mode used in exclusive fullscreen, or the monitor's best mode when `None`
+    pub video_mode: Option<VideoModeInfo>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: WindowMode::Windowed,
+            monitor: None,
+            video_mode: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub const DEFAULT_PATH: &'static str = "display.bin";
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, bincode::serialize(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(bincode::deserialize(&std::fs::read(path)?)?)
+    }
+
+    /// Switches the window to the chosen mode and monitor.
+    /// Call this again after changing any of the settings.
+    pub fn apply(&self, resources: &mut Resources) -> Result<()> {
+        self.apply_to_window(resources.window)
+    }
+
+    pub(crate) fn apply_to_window(&self, window: &Window) -> Result<()> {
+        let fullscreen = match self.mode {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen => {
+                Some(Fullscreen::Borderless(self.find_monitor(window)))
+            }
+            WindowMode::ExclusiveFullscreen => {
+                let monitor = self
+                    .find_monitor(window)
+                    .context("No monitor is available for exclusive fullscreen!")?;
+                Some(Fullscreen::Exclusive(self.find_video_mode(&monitor)?))
+            }
+        };
+        window.set_fullscreen(fullscreen);
+        Ok(())
+    }
+
+    /// The chosen monitor, matched by both name and position, then by name alone so the choice
+    /// survives the desktop being rearranged, then by position alone for unnamed monitors
+    fn find_monitor(&self, window: &Window) -> Option<MonitorHandle> {
+        let chosen = self.monitor.as_ref().and_then(|id| {
+            let monitors = window
+                .available_monitors()
+                .map(|monitor| (MonitorId::new(&monitor), monitor))
+                .collect::<Vec<_>>();
+            let find = |matches: &dyn Fn(&MonitorId) -> bool| {
+                monitors
+                    .iter()
+                    .find(|(monitor_id, _)| matches(monitor_id))
+                    .map(|(_, monitor)| monitor.clone())
+            };
+            let monitor = find(&|monitor_id| monitor_id == id)
+                .or_else(|| find(&|monitor_id| id.name.is_some() && monitor_id.name == id.name))
+                .or_else(|| find(&|monitor_id| monitor_id.position == id.position));
+            if monitor.is_none() {
+                log::warn!("Monitor {:?} wasn't found, using the current monitor", id);
+            }
+            monitor
+        });
+        chosen
+            .or_else(|| window.current_monitor())
+            .or_else(|| window.primary_monitor())
+    }
+
+    /// The requested video mode, or the closest the monitor supports if it isn't available
+    fn find_video_mode(&self, monitor: &MonitorHandle) -> Result<VideoMode> {
+        let mut video_modes = monitor.video_modes().collect::<Vec<_>>();
+        let requested = match self.video_mode {
+            Some(requested) => requested,
+            None => {
+                let size = monitor.size();
+                VideoModeInfo {
+                    width: size.width,
+                    height: size.height,
+                    refresh_rate: u16::MAX,
+                    bit_depth: u16::MAX,
+                }
+            }
+        };
+        video_modes.sort_by_key(|video_mode| {
+            let info = VideoModeInfo::new(video_mode);
+            (
+                (info.width as i64 - requested.width as i64).abs()
+                    + (info.height as i64 - requested.height as i64).abs(),
+                (info.refresh_rate as i32 - requested.refresh_rate as i32).abs(),
+                (info.bit_depth as i32 - requested.bit_depth as i32).abs(),
+            )
+        });
+        let video_mode = video_modes
+            .into_iter()
+            .next()
+            .context("The monitor reports no video modes!")?;
+        if self.video_mode.is_some() && Some(VideoModeInfo::new(&video_mode)) != self.video_mode {
+            log::warn!(
+                "Video mode {} isn't supported, using {}",
+                requested,
+                VideoModeInfo::new(&video_mode)
+            );
+        }
+        Ok(video_mode)
+    }
+}
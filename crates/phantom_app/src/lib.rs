@@ -1,5 +1,16 @@
+mod accessibility;
 mod app;
+mod asset_server;
+mod assets;
+mod data_tables;
+mod display;
+mod mods;
+mod pak;
 mod resources;
+mod saves;
 mod state;
 
-pub use self::{app::*, resources::*, state::*};
+pub use self::{
+    accessibility::*, app::*, asset_server::*, assets::*, data_tables::*, display::*, mods::*,
+    pak::*, resources::*, saves::*, state::*,
+};
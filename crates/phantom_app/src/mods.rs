@@ -0,0 +1,324 @@
+use crate::{mount, unmount, MountSource, Pak};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    egui::{Button, Grid, Ui},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Describes a mod, read from the `mod.json` at the root of its directory or pak.
+/// Pak mods are packed from the mod's `assets` directory with the manifest inserted beside it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ModManifest {
+    pub name: String,
+
+    #[serde(default)]
+    pub version: String,
+
+    #[serde(default)]
+    pub author: String,
+
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ModManifest {
+    pub const FILE_NAME: &'static str = "mod.json";
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    /// Mods without a manifest are named after their directory or pak
+    fn unnamed(path: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            name,
+            version: String::new(),
+            author: String::new(),
+            description: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Mod {
+    pub manifest: ModManifest,
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+impl Mod {
+    /// Reads the mod's manifest, returning the pak too for pak mods so it isn't read again
+    /// when mounted. Files that aren't mods are skipped.
+    fn read(path: PathBuf) -> Result<Option<(Self, Option<Pak>)>> {
+        let (manifest, pak) = if path.is_dir() {
+            let manifest_path = path.join(ModManifest::FILE_NAME);
+            let manifest = match fs::read(&manifest_path) {
+                Ok(bytes) => ModManifest::parse(&bytes)
+                    .context(format!("Invalid mod manifest: {}", manifest_path.display()))?,
+                Err(_) => ModManifest::unnamed(&path),
+            };
+            (manifest, None)
+        } else if path
+            .extension()
+            .map_or(false, |extension| extension == "pak")
+        {
+            let pak = Pak::load(&path)?;
+            let manifest = match pak.read(ModManifest::FILE_NAME) {
+                Some(bytes) => ModManifest::parse(bytes)
+                    .context(format!("Invalid mod manifest in: {}", path.display()))?,
+                None => ModManifest::unnamed(&path),
+            };
+            (manifest, Some(pak))
+        } else {
+            return Ok(None);
+        };
+        let installed = Self {
+            manifest,
+            path,
+            enabled: true,
+        };
+        Ok(Some((installed, pak)))
+    }
+
+    fn source(&self, pak: Option<Pak>) -> Result<MountSource> {
+        if self.path.is_dir() {
+            return Ok(MountSource::Directory(self.path.clone()));
+        }
+        match pak {
+            Some(pak) => Ok(MountSource::Pak(pak)),
+            None => Ok(MountSource::Pak(Pak::load(&self.path)?)),
+        }
+    }
+}
+
+/// The player's choice of enabled mods and their load order, saved beside the mods
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct ModSettings {
+    load_order: Vec<String>,
+    disabled: Vec<String>,
+}
+
+/// Finds mods in the mods directory and mounts the enabled ones over the base assets.
+/// Each mod is a directory or pak mirroring the game's asset paths, so any asset
+/// read through `read_asset` can be replaced. Mods later in the load order win.
+pub struct ModLoader {
+    pub directory: PathBuf,
+    mods: Vec<Mod>,
+    mounted: Vec<String>,
+
+    /// Paks read while discovering mods, kept until they are mounted
+    paks: HashMap<PathBuf, Pak>,
+}
+
+impl Default for ModLoader {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_DIRECTORY)
+    }
+}
+
+impl ModLoader {
+    pub const DEFAULT_DIRECTORY: &'static str = "mods";
+    pub const SETTINGS_FILE_NAME: &'static str = "load_order.json";
+
+    /// Mods are mounted above this priority, so they override the shipped paks
+    pub const BASE_PRIORITY: i32 = 100;
+
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            mods: Vec::new(),
+            mounted: Vec::new(),
+            paks: HashMap::new(),
+        }
+    }
+
+    /// The mods in load order
+    pub fn mods(&self) -> &[Mod] {
+        &self.mods
+    }
+
+    /// Finds every mod directory and pak, ordering them by the saved load order.
+    /// Newly installed mods are enabled and loaded last. Broken mods are logged and skipped.
+    pub fn discover(&mut self) -> Result<()> {
+        self.mods.clear();
+        self.paks.clear();
+        if !self.directory.exists() {
+            return Ok(());
+        }
+
+        let mut mods = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(error) => {
+                    log::error!("Failed to read the mods directory: {}", error);
+                    continue;
+                }
+            };
+            match Mod::read(path.clone()) {
+                Ok(Some((installed, pak))) => {
+                    if let Some(pak) = pak {
+                        self.paks.insert(path, pak);
+                    }
+                    mods.push(installed);
+                }
+                Ok(None) => {}
+                Err(error) => log::error!("Skipped mod {}: {:?}", path.display(), error),
+            }
+        }
+        mods.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+
+        let settings = self.load_settings();
+        for name in settings.load_order.iter() {
+            if let Some(index) = mods
+                .iter()
+                .position(|installed| &installed.manifest.name == name)
+            {
+                self.mods.push(mods.remove(index));
+            }
+        }
+        self.mods.extend(mods);
+        for installed in self.mods.iter_mut() {
+            installed.enabled = !settings.disabled.contains(&installed.manifest.name);
+        }
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(installed) = self
+            .mods
+            .iter_mut()
+            .find(|installed| installed.manifest.name == name)
+        {
+            installed.enabled = enabled;
+        }
+    }
+
+    /// Moves a mod to a new position in the load order
+    pub fn move_mod(&mut self, from: usize, to: usize) {
+        if from < self.mods.len() && to < self.mods.len() {
+            let installed = self.mods.remove(from);
+            self.mods.insert(to, installed);
+        }
+    }
+
+    /// Remounts the enabled mods in load order, skipping any that fail to load.
+    /// Assets already loaded aren't reloaded, so this is best applied before loading a scene.
+    pub fn apply(&mut self) -> Result<()> {
+        for name in self.mounted.drain(..) {
+            unmount(&name);
+        }
+        for (index, installed) in self
+            .mods
+            .iter()
+            .enumerate()
+            .filter(|(_, installed)| installed.enabled)
+        {
+            let name = format!("mod:{}", installed.manifest.name);
+            let source = match installed.source(self.paks.remove(&installed.path)) {
+                Ok(source) => source,
+                Err(error) => {
+                    log::error!(
+                        "Failed to mount mod '{}': {}",
+                        installed.manifest.name,
+                        error
+                    );
+                    continue;
+                }
+            };
+            mount(&name, source, Self::BASE_PRIORITY + index as i32);
+            log::info!("Mounted mod '{}'", installed.manifest.name);
+            self.mounted.push(name);
+        }
+        Ok(())
+    }
+
+    pub fn save_settings(&self) -> Result<()> {
+        let settings = ModSettings {
+            load_order: self
+                .mods
+                .iter()
+                .map(|installed| installed.manifest.name.clone())
+                .collect(),
+            disabled: self
+                .mods
+                .iter()
+                .filter(|installed| !installed.enabled)
+                .map(|installed| installed.manifest.name.clone())
+                .collect(),
+        };
+        fs::create_dir_all(&self.directory)?;
+        fs::write(
+            self.directory.join(Self::SETTINGS_FILE_NAME),
+            serde_json::to_string_pretty(&settings)?,
+        )?;
+        Ok(())
+    }
+
+    fn load_settings(&self) -> ModSettings {
+        fs::read(self.directory.join(Self::SETTINGS_FILE_NAME))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Lists the mods with checkboxes to enable them and buttons to change the load order.
+    /// Changes are saved and mounted when applied.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if self.mods.is_empty() {
+            ui.label(format!("No mods found in '{}'", self.directory.display()));
+        }
+
+        let mut moved = None;
+        let count = self.mods.len();
+        Grid::new("mods").striped(true).show(ui, |ui| {
+            for (index, installed) in self.mods.iter_mut().enumerate() {
+                ui.checkbox(&mut installed.enabled, &installed.manifest.name)
+                    .on_hover_text(&installed.manifest.description);
+                ui.label(&installed.manifest.version);
+                ui.label(&installed.manifest.author);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(index > 0, Button::new("Up")).clicked() {
+                        moved = Some((index, index - 1));
+                    }
+                    if ui
+                        .add_enabled(index + 1 < count, Button::new("Down"))
+                        .clicked()
+                    {
+                        moved = Some((index, index + 1));
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some((from, to)) = moved {
+            self.move_mod(from, to);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Rescan").clicked() {
+                if let Err(error) = self.discover() {
+                    log::error!("Failed to find mods: {}", error);
+                }
+            }
+            if ui.button("Apply").clicked() {
+                if let Err(error) = self.save_settings().and_then(|_| self.apply()) {
+                    log::error!("Failed to apply mods: {}", error);
+                }
+            }
+        });
+    }
+}
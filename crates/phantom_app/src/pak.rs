@@ -0,0 +1,196 @@
+use crate::AssetIds;
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    bincode,
+    lazy_static::lazy_static,
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+
+lazy_static! {
+    static ref MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+}
+
+/// Where a mount's assets are read from
+pub enum MountSource {
+    Pak(Pak),
+
+    /// A directory laid out like the working directory,
+    /// so `<directory>/assets/models/a.glb` overrides `assets/models/a.glb`
+    Directory(PathBuf),
+}
+
+impl MountSource {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        match self {
+            Self::Pak(pak) => pak.read(path).map(|bytes| bytes.to_vec()),
+            Self::Directory(directory) => fs::read(directory.join(entry_key(path))).ok(),
+        }
+    }
+}
+
+struct Mount {
+    name: String,
+    priority: i32,
+    source: MountSource,
+}
+
+/// A single file archive of assets, keyed by their path relative to the packed directory
+#[derive(Default, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Pak {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl Pak {
+    /// The pak loaded automatically from beside the executable when the app starts
+    pub const DEFAULT_FILE_NAME: &'static str = "assets.pak";
+
+    /// Packs every file in a directory. Entries are keyed by the directory's name
+    /// joined with their relative path, so `assets/models/a.glb` can be read as before.
+    pub fn from_directory(directory: impl AsRef<Path>) -> Result<Self> {
+        Self::from_directory_with(directory, |_path, bytes| Ok(Some(bytes)))
+    }
+
+    /// Packs every file in a directory, passing each through `transform` first.
+    /// Returning `None` from `transform` leaves the file out of the pak.
+    pub fn from_directory_with(
+        directory: impl AsRef<Path>,
+        mut transform: impl FnMut(&Path, Vec<u8>) -> Result<Option<Vec<u8>>>,
+    ) -> Result<Self> {
+        let directory = directory.as_ref();
+        let root = directory.parent().unwrap_or_else(|| Path::new(""));
+        let mut pak = Self::default();
+        for path in files_in(directory)? {
+            let bytes = fs::read(&path)?;
+            if let Some(bytes) = transform(&path, bytes)? {
+                pak.insert(path.strip_prefix(root)?, bytes);
+            }
+        }
+        Ok(pak)
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>, bytes: Vec<u8>) {
+        self.entries.insert(entry_key(path.as_ref()), bytes);
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> Option<&[u8]> {
+        self.entries
+            .get(&entry_key(path.as_ref()))
+            .map(|bytes| bytes.as_slice())
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(fs::write(path, bincode::serialize(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).context(format!("Failed to read pak: {}", path.display()))?;
+        Ok(bincode::deserialize(&bytes)?)
+    }
+}
+
+/// Makes the assets in a pak readable through `read_asset`.
+/// Paks mounted later take priority over earlier ones.
+pub fn mount_pak(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let pak = Pak::load(path)?;
+    mount(&path.display().to_string(), MountSource::Pak(pak), 0);
+    Ok(())
+}
+
+/// Makes the assets in a pak or directory readable through `read_asset`, replacing
+/// any mount with the same name. Mounts with a higher priority override lower ones,
+/// and among equal priorities the latest mount wins.
+pub fn mount(name: &str, source: MountSource, priority: i32) {
+    let mut mounts = MOUNTS.write().expect("Failed to access mounts!");
+    mounts.retain(|mount| mount.name != name);
+    let index = mounts.partition_point(|mount| mount.priority <= priority);
+    mounts.insert(
+        index,
+        Mount {
+            name: name.to_string(),
+            priority,
+            source,
+        },
+    );
+}
+
+pub fn unmount(name: &str) {
+    MOUNTS
+        .write()
+        .expect("Failed to access mounts!")
+        .retain(|mount| mount.name != name);
+}
+
+/// The names of every mount, from the lowest priority to the highest
+pub fn mounted() -> Vec<String> {
+    MOUNTS
+        .read()
+        .expect("Failed to access mounts!")
+        .iter()
+        .map(|mount| mount.name.clone())
+        .collect()
+}
+
+pub(crate) fn read_mounted_asset(path: &Path) -> Option<Vec<u8>> {
+    MOUNTS
+        .read()
+        .expect("Failed to access mounts!")
+        .iter()
+        .rev()
+        .find_map(|mount| mount.source.read(path))
+}
+
+/// Finds the sidecars in every mounted pak, with higher priority mounts replacing lower ones
+pub(crate) fn scan_mounted_asset_ids(ids: &mut AssetIds) {
+    for mount in MOUNTS.read().expect("Failed to access mounts!").iter() {
+        if let MountSource::Pak(pak) = &mount.source {
+            ids.scan_pak(pak);
+        }
+    }
+}
+
+/// Mounts the default pak if the game was shipped with one
+pub(crate) fn mount_default_pak() -> Result<()> {
+    let executable = std::env::current_exe()?;
+    let path = match executable.parent() {
+        Some(directory) => directory.join(Pak::DEFAULT_FILE_NAME),
+        None => return Ok(()),
+    };
+    if path.exists() {
+        mount_pak(path)?;
+    }
+    Ok(())
+}
+
+fn entry_key(path: &Path) -> String {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn files_in(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(files_in(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
@@ -1,19 +1,68 @@
+mod actions;
+mod bug_report;
+mod console;
+mod cursor;
+mod cvars;
+mod events;
+mod gamepad;
 mod input;
+mod input_tuning;
+mod recorder;
+mod scope;
 mod system;
+mod telemetry;
 
-pub use self::{input::Input, system::System};
+pub use self::{
+    actions::*,
+    bug_report::*,
+    console::*,
+    cursor::Cursor,
+    cvars::*,
+    events::{AppEvent, EventProxy},
+    gamepad::*,
+    input::{Input, TimedEvent, TouchPoint, Touches},
+    input_tuning::*,
+    recorder::{Recorder, RecordingFormat},
+    scope::{Scope, StateScopes},
+    system::{SimulationStep, System},
+    telemetry::*,
+};
 
-use phantom_dependencies::{anyhow::Result, gilrs::Gilrs, winit::window::Window};
+use crate::{monitors, AssetServer, DataTables, ModLoader, MonitorInfo};
+use phantom_audio::Audio;
+use phantom_dependencies::{
+    anyhow::Result,
+    gilrs::Gilrs,
+    rapier3d::geometry::Ray,
+    winit::window::{CursorIcon, Window},
+};
 use phantom_gui::Gui;
+use phantom_platform::Platform;
 use phantom_render::Renderer;
+use phantom_world::World;
 
 pub struct Resources<'a> {
     pub window: &'a mut Window,
     pub gilrs: &'a mut Gilrs,
     pub renderer: &'a mut Box<dyn Renderer>,
     pub gui: &'a mut Gui,
+    pub audio: &'a mut Audio,
     pub input: &'a mut Input,
     pub system: &'a mut System,
+    pub platform: &'a mut Platform,
+    pub recorder: &'a mut Recorder,
+    pub telemetry: &'a mut Telemetry,
+    pub bug_reporter: &'a mut BugReporter,
+    pub console: &'a mut Console,
+    pub cvars: &'a mut CVars,
+    pub cursor: &'a mut Cursor,
+    pub data_tables: &'a mut DataTables,
+    pub asset_server: &'a mut AssetServer,
+    pub mods: &'a mut ModLoader,
+    pub scopes: &'a mut StateScopes,
+
+    /// Clone this to send events to the app from other threads
+    pub event_proxy: &'a mut EventProxy,
 }
 
 impl<'a> Resources<'a> {
@@ -24,4 +73,59 @@ impl<'a> Resources<'a> {
     pub fn set_cursor_visible(&mut self, visible: bool) {
         self.window.set_cursor_visible(visible)
     }
+
+    /// Shows a standard cursor icon, replacing any custom cursor
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor.icon = icon;
+        self.cursor.set_custom(None).ok();
+    }
+
+    /// Loads an image from the assets as a custom cursor, which `set_custom_cursor` shows by name.
+    /// The hotspot is the pixel in the image that points at the cursor's position.
+    pub fn load_cursor(&mut self, name: &str, path: &str, hotspot: [f32; 2]) -> Result<()> {
+        self.cursor
+            .load_custom(&mut **self.renderer, name, path, hotspot)
+    }
+
+    pub fn set_custom_cursor(&mut self, name: Option<&str>) -> Result<()> {
+        self.cursor.set_custom(name)
+    }
+
+    /// The connected monitors and the video modes each supports
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        monitors(self.window)
+    }
+
+    pub fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.window.set_always_on_top(always_on_top)
+    }
+
+    /// Removes the title bar and frame for borderless tool windows, or restores them
+    pub fn set_decorations(&mut self, decorations: bool) {
+        self.window.set_decorations(decorations)
+    }
+
+    /// A ray from the world's active camera through the cursor, for picking, placing and aiming.
+    /// The cursor is measured within the system's viewport, so this works when the
+    /// scene is rendered inside an editor panel.
+    pub fn cursor_ray(&self, world: &World) -> Result<Ray> {
+        world.viewport_ray(&self.input.mouse.position, &self.system.viewport())
+    }
+
+    /// Whether the cursor is over the part of the window the scene is rendered in
+    pub fn cursor_in_viewport(&self) -> bool {
+        self.system.viewport().contains(&self.input.mouse.position)
+    }
+
+    /// Values, entities and cleanup hooks owned by the current state, released when it stops
+    pub fn scope(&mut self) -> &mut Scope {
+        self.scopes.current()
+    }
+
+    /// Grabs and hides the cursor so the mouse can turn a camera freely, or releases it
+    pub fn set_mouse_look(&mut self, enabled: bool) -> Result<()> {
+        self.set_cursor_grab(enabled)?;
+        self.set_cursor_visible(!enabled);
+        Ok(())
+    }
 }
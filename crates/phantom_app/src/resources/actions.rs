@@ -0,0 +1,385 @@
+use crate::{Input, InputTuning};
+use phantom_dependencies::{
+    anyhow::Result,
+    gilrs::{Axis, Button},
+    serde::{Deserialize, Serialize},
+    serde_json,
+    winit::event::{MouseButton, VirtualKeyCode},
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+/// A button-like input that can trigger an action
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum InputBinding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    GamepadButton(Button),
+}
+
+impl InputBinding {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(self, Self::GamepadButton(_))
+    }
+}
+
+/// An input that produces a value from -1 to 1
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum AxisBinding {
+    Keys {
+        positive: VirtualKeyCode,
+        negative: VirtualKeyCode,
+    },
+    GamepadButtons {
+        positive: Button,
+        negative: Button,
+    },
+    GamepadAxis(Axis),
+
+    /// Lines scrolled this frame
+    MouseWheel,
+}
+
+impl AxisBinding {
+    /// The buttons that drive the axis
+    fn buttons(&self) -> Vec<InputBinding> {
+        match *self {
+            Self::Keys { positive, negative } => {
+                vec![InputBinding::Key(positive), InputBinding::Key(negative)]
+            }
+            Self::GamepadButtons { positive, negative } => vec![
+                InputBinding::GamepadButton(positive),
+                InputBinding::GamepadButton(negative),
+            ],
+            Self::GamepadAxis(_) | Self::MouseWheel => Vec::new(),
+        }
+    }
+}
+
+/// Actions and axes that are only active while the context is pushed, such as a menu's.
+/// Inputs bound in a context are consumed by it, so contexts below it don't see them.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ActionContext {
+    pub actions: BTreeMap<String, Vec<InputBinding>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+
+    /// Consumes every input, not just the bound ones, blocking all contexts below it
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+impl ActionContext {
+    pub const GAMEPLAY: &'static str = "gameplay";
+    pub const VEHICLE: &'static str = "vehicle";
+    pub const MENU: &'static str = "menu";
+    pub const CONSOLE: &'static str = "console";
+
+    pub fn bind(&mut self, action: &str, binding: InputBinding) {
+        bind(&mut self.actions, action, binding);
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: InputBinding) {
+        unbind(&mut self.actions, action, binding);
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        bind(&mut self.axes, axis, binding);
+    }
+}
+
+/// The bindings of an active context, or the bindings that are always active
+struct Layer<'a> {
+    actions: &'a BTreeMap<String, Vec<InputBinding>>,
+    axes: &'a BTreeMap<String, Vec<AxisBinding>>,
+}
+
+/// Inputs bound in the layers above the one being evaluated
+#[derive(Default)]
+struct Consumed {
+    buttons: HashSet<InputBinding>,
+    axes: Vec<AxisBinding>,
+}
+
+impl Consumed {
+    fn add(&mut self, layer: &Layer) {
+        self.buttons
+            .extend(layer.actions.values().flatten().copied());
+        for binding in layer.axes.values().flatten() {
+            self.buttons.extend(binding.buttons());
+            self.axes.push(*binding);
+        }
+    }
+
+    fn button(&self, binding: &InputBinding) -> bool {
+        self.buttons.contains(binding)
+    }
+
+    fn axis(&self, binding: &AxisBinding) -> bool {
+        self.axes.contains(binding)
+            || binding
+                .buttons()
+                .iter()
+                .any(|button| self.buttons.contains(button))
+    }
+}
+
+/// Named actions and axes bound to keyboard, mouse and gamepad inputs.
+/// Gameplay code asks for actions by name, so bindings can be changed at runtime
+/// and saved as profiles without touching that code.
+/// Profiles also hold each device's tuning, so players keep their sensitivity with their bindings.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ActionMap {
+    pub actions: BTreeMap<String, Vec<InputBinding>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+
+    /// Contexts that can be pushed on top of the bindings above
+    #[serde(default)]
+    pub contexts: BTreeMap<String, ActionContext>,
+
+    #[serde(default)]
+    pub tuning: InputTuning,
+
+    /// The action waiting for its next binding to be pressed
+    #[serde(skip)]
+    rebinding: Option<String>,
+
+    /// Pushed contexts, from the lowest priority to the highest
+    #[serde(skip)]
+    active_contexts: Vec<String>,
+}
+
+impl ActionMap {
+    /// Axes and actions that drive the fly camera
+    pub const MOVE_X: &'static str = "move_x";
+    pub const MOVE_Y: &'static str = "move_y";
+    pub const MOVE_Z: &'static str = "move_z";
+    pub const FLY_FAST: &'static str = "fly_fast";
+    pub const FLY_SLOW: &'static str = "fly_slow";
+
+    /// Binds the fly camera to WASD, E and Q, with shift to fly faster and control to fly slower.
+    /// Axes and actions that are already bound keep their bindings,
+    /// so this can be called again after loading a profile.
+    pub fn bind_fly_camera_defaults(&mut self) {
+        let keys = |positive, negative| AxisBinding::Keys { positive, negative };
+        for (axis, binding) in [
+            (Self::MOVE_X, keys(VirtualKeyCode::D, VirtualKeyCode::A)),
+            (Self::MOVE_Y, keys(VirtualKeyCode::E, VirtualKeyCode::Q)),
+            (Self::MOVE_Z, keys(VirtualKeyCode::W, VirtualKeyCode::S)),
+        ] {
+            self.axes
+                .entry(axis.to_string())
+                .or_insert_with(|| vec![binding]);
+        }
+        for (action, keycode) in [
+            (Self::FLY_FAST, VirtualKeyCode::LShift),
+            (Self::FLY_SLOW, VirtualKeyCode::LControl),
+        ] {
+            self.actions
+                .entry(action.to_string())
+                .or_insert_with(|| vec![InputBinding::Key(keycode)]);
+        }
+    }
+
+    pub fn bind(&mut self, action: &str, binding: InputBinding) {
+        bind(&mut self.actions, action, binding);
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: InputBinding) {
+        unbind(&mut self.actions, action, binding);
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        bind(&mut self.axes, axis, binding);
+    }
+
+    /// The context with this name, created empty if it doesn't exist yet
+    pub fn context_mut(&mut self, name: &str) -> &mut ActionContext {
+        self.contexts.entry(name.to_string()).or_default()
+    }
+
+    /// Makes a context the highest priority, moving it to the top if it's already active
+    pub fn push_context(&mut self, name: &str) {
+        self.remove_context(name);
+        self.active_contexts.push(name.to_string());
+    }
+
+    pub fn pop_context(&mut self) -> Option<String> {
+        self.active_contexts.pop()
+    }
+
+    pub fn remove_context(&mut self, name: &str) {
+        self.active_contexts.retain(|active| active != name);
+    }
+
+    /// Pushes the context if it isn't active, or removes it if it shouldn't be
+    pub fn set_context_active(&mut self, name: &str, active: bool) {
+        if !active {
+            self.remove_context(name);
+        } else if !self.is_context_active(name) {
+            self.active_contexts.push(name.to_string());
+        }
+    }
+
+    pub fn is_context_active(&self, name: &str) -> bool {
+        self.active_contexts.iter().any(|active| active == name)
+    }
+
+    /// Active contexts from the lowest priority to the highest
+    pub fn active_contexts(&self) -> &[String] {
+        &self.active_contexts
+    }
+
+    /// The active contexts from the highest priority down, then the bindings that are always active.
+    /// Nothing below an exclusive context is included.
+    fn layers(&self) -> Vec<Layer> {
+        let mut layers = Vec::new();
+        for name in self.active_contexts.iter().rev() {
+            let context = match self.contexts.get(name) {
+                Some(context) => context,
+                None => continue,
+            };
+            layers.push(Layer {
+                actions: &context.actions,
+                axes: &context.axes,
+            });
+            if context.exclusive {
+                return layers;
+            }
+        }
+        layers.push(Layer {
+            actions: &self.actions,
+            axes: &self.axes,
+        });
+        layers
+    }
+
+    /// Binds the action to the next key, mouse button or gamepad button pressed,
+    /// replacing its bindings from the same kind of device
+    pub fn start_rebinding(&mut self, action: &str) {
+        self.rebinding = Some(action.to_string());
+    }
+
+    pub fn cancel_rebinding(&mut self) {
+        self.rebinding = None;
+    }
+
+    /// The action waiting to be rebound
+    pub fn rebinding(&self) -> Option<&str> {
+        self.rebinding.as_deref()
+    }
+
+    /// Completes a rebind, returning true if the press was used for it
+    pub(crate) fn binding_pressed(&mut self, binding: InputBinding) -> bool {
+        let action = match self.rebinding.take() {
+            Some(action) => action,
+            None => return false,
+        };
+        // The binding replaces the one in the highest priority context that has the action
+        let context = self
+            .active_contexts
+            .iter()
+            .rev()
+            .find(|name| {
+                self.contexts
+                    .get(*name)
+                    .map_or(false, |context| context.actions.contains_key(&action))
+            })
+            .cloned();
+        let actions = match context.and_then(|name| self.contexts.get_mut(&name)) {
+            Some(context) => &mut context.actions,
+            None => &mut self.actions,
+        };
+        let bindings = actions.entry(action).or_default();
+        bindings.retain(|existing| existing.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+        true
+    }
+
+    /// Whether the action is held in any active context,
+    /// ignoring inputs consumed by higher priority contexts
+    pub fn is_pressed(&self, action: &str, input: &Input) -> bool {
+        let pressed = |binding: &InputBinding| match binding {
+            InputBinding::Key(keycode) => input.is_key_pressed(*keycode),
+            InputBinding::Mouse(button) => input.mouse.is_pressed(*button),
+            InputBinding::GamepadButton(button) => input.gamepads.is_pressed(*button),
+        };
+        let mut consumed = Consumed::default();
+        for layer in self.layers() {
+            if let Some(bindings) = layer.actions.get(action) {
+                if bindings
+                    .iter()
+                    .any(|binding| !consumed.button(binding) && pressed(binding))
+                {
+                    return true;
+                }
+            }
+            consumed.add(&layer);
+        }
+        false
+    }
+
+    /// The value of the binding pushed furthest, from -1 to 1,
+    /// ignoring inputs consumed by higher priority contexts
+    pub fn axis(&self, axis: &str, input: &Input) -> f32 {
+        let gamepad_button = |button: Button| input.gamepads.is_pressed(button);
+        let buttons = |positive: bool, negative: bool| match (positive, negative) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+        let value = |binding: &AxisBinding| match binding {
+            AxisBinding::Keys { positive, negative } => buttons(
+                input.is_key_pressed(*positive),
+                input.is_key_pressed(*negative),
+            ),
+            AxisBinding::GamepadButtons { positive, negative } => {
+                buttons(gamepad_button(*positive), gamepad_button(*negative))
+            }
+            AxisBinding::GamepadAxis(axis) => input.gamepad_axis(*axis),
+            AxisBinding::MouseWheel => input.mouse.wheel_delta.y,
+        };
+        let mut consumed = Consumed::default();
+        let mut furthest: f32 = 0.0;
+        for layer in self.layers() {
+            for binding in layer.axes.get(axis).into_iter().flatten() {
+                if consumed.axis(binding) {
+                    continue;
+                }
+                let value = value(binding);
+                if value.abs() > furthest.abs() {
+                    furthest = value;
+                }
+            }
+            consumed.add(&layer);
+        }
+        furthest.clamp(-1.0, 1.0)
+    }
+
+    /// Saves the bindings as a json profile
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+fn bind<T: PartialEq>(bindings: &mut BTreeMap<String, Vec<T>>, name: &str, binding: T) {
+    let bindings = bindings.entry(name.to_string()).or_default();
+    if !bindings.contains(&binding) {
+        bindings.push(binding);
+    }
+}
+
+fn unbind<T: PartialEq>(bindings: &mut BTreeMap<String, Vec<T>>, name: &str, binding: T) {
+    if let Some(bindings) = bindings.get_mut(name) {
+        bindings.retain(|existing| *existing != binding);
+    }
+}
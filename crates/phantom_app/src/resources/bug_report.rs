@@ -0,0 +1,208 @@
+use crate::{http_post, Recorder};
+use phantom_dependencies::{
+    anyhow::Result,
+    env_logger,
+    image::{DynamicImage, ImageOutputFormat, RgbaImage},
+    lazy_static::lazy_static,
+    log::{self, Log, Metadata, Record},
+    winit::event::VirtualKeyCode,
+    zip::{write::FileOptions, ZipWriter},
+};
+use phantom_render::Renderer;
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{Cursor, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+lazy_static! {
+    static ref LOG_HISTORY: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+}
+
+/// Every line ever logged, including those dropped from the history
+static LOG_LINES_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Forwards to env_logger while keeping the most recent lines for bug reports
+struct HistoryLogger {
+    inner: env_logger::Logger,
+}
+
+impl HistoryLogger {
+    const MAX_LINES: usize = 1000;
+}
+
+impl Log for HistoryLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        if let Ok(mut history) = LOG_HISTORY.lock() {
+            history.push_back(format!(
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+            while history.len() > Self::MAX_LINES {
+                history.pop_front();
+            }
+            LOG_LINES_WRITTEN.fetch_add(1, Ordering::SeqCst);
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets up env_logger, keeping recent log lines for bug reports
+pub fn init_logging() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter();
+    if log::set_boxed_logger(Box::new(HistoryLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+pub fn recent_log_lines() -> Vec<String> {
+    LOG_HISTORY
+        .lock()
+        .map(|history| history.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Lines logged after the first `seen` lines, along with the number of lines logged so far
+pub fn log_lines_since(seen: usize) -> (usize, Vec<String>) {
+    let history = match LOG_HISTORY.lock() {
+        Ok(history) => history,
+        Err(_) => return (seen, Vec::new()),
+    };
+    let written = LOG_LINES_WRITTEN.load(Ordering::SeqCst);
+    let unseen = written.saturating_sub(seen).min(history.len());
+    let lines = history
+        .iter()
+        .skip(history.len() - unseen)
+        .cloned()
+        .collect();
+    (written, lines)
+}
+
+/// Bundles a screenshot, recent logs, renderer details and the current scene into a zip
+/// when the hotkey is pressed, for QA to attach to their feedback
+pub struct BugReporter {
+    pub hotkey: Option<VirtualKeyCode>,
+    pub output_directory: PathBuf,
+
+    /// Reports are also posted here when set. Only plain `http://` urls are supported.
+    pub endpoint: Option<String>,
+
+    /// Set by states so reports say which scene was open
+    pub scene_path: Option<String>,
+
+    requested: bool,
+}
+
+impl Default for BugReporter {
+    fn default() -> Self {
+        Self {
+            hotkey: Some(VirtualKeyCode::F8),
+            output_directory: PathBuf::from("bug_reports"),
+            endpoint: None,
+            scene_path: None,
+            requested: false,
+        }
+    }
+}
+
+impl BugReporter {
+    /// Captures the next frame, then writes the report once it arrives
+    pub fn request(&mut self, recorder: &mut Recorder) {
+        self.requested = true;
+        recorder.request_screenshot();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+
+    /// Writes a requested report once its screenshot has been captured
+    pub fn update(&mut self, recorder: &mut Recorder, renderer: &dyn Renderer) -> Result<()> {
+        if !self.requested || recorder.screenshot_requested() {
+            return Ok(());
+        }
+        self.requested = false;
+        let screenshot = recorder.take_screenshot();
+        let path = self.write_report(screenshot.as_ref(), renderer)?;
+        log::info!("Saved bug report to {}", path.display());
+        Ok(())
+    }
+
+    fn write_report(
+        &self,
+        screenshot: Option<&RgbaImage>,
+        renderer: &dyn Renderer,
+    ) -> Result<PathBuf> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default();
+
+        if let Some(screenshot) = screenshot {
+            let mut png = Cursor::new(Vec::new());
+            DynamicImage::ImageRgba8(screenshot.clone())
+                .write_to(&mut png, ImageOutputFormat::Png)?;
+            zip.start_file("screenshot.png", options)?;
+            zip.write_all(&png.into_inner())?;
+        }
+
+        zip.start_file("log.txt", options)?;
+        for line in recent_log_lines() {
+            writeln!(zip, "{}", line)?;
+        }
+
+        zip.start_file("system.txt", options)?;
+        writeln!(zip, "Timestamp: {}", timestamp)?;
+        writeln!(
+            zip,
+            "OS: {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )?;
+        writeln!(
+            zip,
+            "Scene: {}",
+            self.scene_path.as_deref().unwrap_or("None")
+        )?;
+        for (name, value) in renderer.settings() {
+            writeln!(zip, "{}: {}", name, value)?;
+        }
+
+        let bytes = zip.finish()?.into_inner();
+        fs::create_dir_all(&self.output_directory)?;
+        let path = self
+            .output_directory
+            .join(format!("bug_report_{}.zip", timestamp));
+        File::create(&path)?.write_all(&bytes)?;
+
+        if let Some(endpoint) = self.endpoint.clone() {
+            thread::spawn(move || {
+                let timeout = Duration::from_secs(30);
+                if let Err(error) = http_post(&endpoint, "application/zip", &bytes, timeout) {
+                    log::warn!("Failed to send bug report: {}", error);
+                }
+            });
+        }
+        Ok(path)
+    }
+}
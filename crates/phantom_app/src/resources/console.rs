@@ -0,0 +1,377 @@
+use crate::{log_lines_since, CVars};
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    egui::{pos2, vec2, CtxRef, Key, Rect, ScrollArea, TextEdit, Window},
+    winit::event::VirtualKeyCode,
+};
+use std::collections::{BTreeMap, VecDeque};
+
+pub type CommandHandler = Box<dyn FnMut(&[String]) -> Result<String>>;
+
+struct Command {
+    description: String,
+
+    /// Commands without a handler are queued for the running state to handle
+    handler: Option<CommandHandler>,
+}
+
+/// A command entered in the console that the running state handles itself,
+/// for commands that need access to the world
+#[derive(Debug, Clone)]
+pub struct ConsoleCommand {
+    pub name: String,
+    pub arguments: Vec<String>,
+}
+
+/// A dropdown console toggled with the tilde key, for running commands,
+/// tweaking cvars and reading the log while the game is running
+pub struct Console {
+    pub open: bool,
+    pub hotkey: Option<VirtualKeyCode>,
+
+    /// Log lines are echoed to the console's output
+    pub show_log: bool,
+
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    commands: BTreeMap<String, Command>,
+    pending: Vec<ConsoleCommand>,
+    log_lines_seen: usize,
+    focus_input: bool,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self {
+            open: false,
+            hotkey: Some(VirtualKeyCode::Grave),
+            show_log: true,
+            input: String::new(),
+            output: VecDeque::new(),
+            history: Vec::new(),
+            history_index: None,
+            commands: BTreeMap::new(),
+            pending: Vec::new(),
+            log_lines_seen: 0,
+            focus_input: false,
+        }
+    }
+}
+
+impl Console {
+    const MAX_OUTPUT_LINES: usize = 500;
+    const MAX_HISTORY: usize = 100;
+
+    /// Commands every console understands
+    const BUILTIN_COMMANDS: [(&'static str, &'static str); 7] = [
+        ("help", "Lists every command and cvar"),
+        ("clear", "Clears the console output"),
+        ("echo", "Prints its arguments"),
+        ("get", "Prints a cvar's value"),
+        ("set", "Sets a cvar's value"),
+        ("reset", "Resets a cvar to its default value"),
+        ("vars", "Lists every cvar and its value"),
+    ];
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.focus_input = self.open;
+    }
+
+    pub fn print(&mut self, line: impl ToString) {
+        for line in line.to_string().lines() {
+            self.output.push_back(line.to_string());
+        }
+        while self.output.len() > Self::MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    /// Registers a command whose handler receives the arguments and returns text to print
+    pub fn register_command(
+        &mut self,
+        name: &str,
+        description: &str,
+        handler: impl FnMut(&[String]) -> Result<String> + 'static,
+    ) {
+        self.commands.insert(
+            name.to_string(),
+            Command {
+                description: description.to_string(),
+                handler: Some(Box::new(handler)),
+            },
+        );
+    }
+
+    /// Registers a command that is queued for the running state, read with `take_commands`
+    pub fn add_command(&mut self, name: &str, description: &str) {
+        self.commands.insert(
+            name.to_string(),
+            Command {
+                description: description.to_string(),
+                handler: None,
+            },
+        );
+    }
+
+    /// Commands without a handler entered since this was last called
+    pub fn take_commands(&mut self) -> Vec<ConsoleCommand> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Runs a line of input, printing the result or error.
+    /// Several commands can be separated by semicolons.
+    pub fn execute(&mut self, line: &str, cvars: &mut CVars) {
+        for statement in line.split(';') {
+            let tokens = tokenize(statement);
+            if tokens.is_empty() {
+                continue;
+            }
+            match self.run(&tokens[0], &tokens[1..], cvars) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => self.print(output),
+                Err(error) => self.print(format!("Error: {}", error)),
+            }
+        }
+    }
+
+    fn run(&mut self, name: &str, arguments: &[String], cvars: &mut CVars) -> Result<String> {
+        if let Some(command) = self.commands.get_mut(name) {
+            return match command.handler.as_mut() {
+                Some(handler) => handler(arguments),
+                None => {
+                    self.pending.push(ConsoleCommand {
+                        name: name.to_string(),
+                        arguments: arguments.to_vec(),
+                    });
+                    Ok(String::new())
+                }
+            };
+        }
+
+        // Typing a cvar's name prints it, and following it with a value sets it
+        if cvars.contains(name) {
+            return match arguments.first() {
+                Some(value) => {
+                    cvars.set_from_str(name, value)?;
+                    Ok(String::new())
+                }
+                None => self.run("get", &[name.to_string()], cvars),
+            };
+        }
+
+        match (name, arguments) {
+            ("help", _) => {
+                let mut lines = Self::BUILTIN_COMMANDS
+                    .iter()
+                    .map(|(name, description)| format!("{} - {}", name, description))
+                    .collect::<Vec<_>>();
+                lines.extend(
+                    self.commands
+                        .iter()
+                        .map(|(name, command)| format!("{} - {}", name, command.description)),
+                );
+                lines.extend(
+                    cvars
+                        .iter()
+                        .map(|(name, cvar)| format!("{} - {}", name, cvar.description)),
+                );
+                Ok(lines.join("\n"))
+            }
+            ("clear", _) => {
+                self.clear();
+                Ok(String::new())
+            }
+            ("echo", arguments) => Ok(arguments.join(" ")),
+            ("get", [name]) => {
+                let cvar = cvars
+                    .cvar(name)
+                    .context(format!("Unknown cvar '{}'", name))?;
+                Ok(format!(
+                    "{} = {} (default {})",
+                    name,
+                    cvar.value(),
+                    cvar.default()
+                ))
+            }
+            ("set", [name, value]) => {
+                cvars.set_from_str(name, value)?;
+                Ok(String::new())
+            }
+            ("reset", [name]) => {
+                cvars.reset(name)?;
+                Ok(String::new())
+            }
+            ("vars", _) => Ok(cvars
+                .iter()
+                .map(|(name, cvar)| format!("{} = {}", name, cvar.value()))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ("get", _) | ("reset", _) => bail!("Usage: {} <cvar>", name),
+            ("set", _) => bail!("Usage: set <cvar> <value>"),
+            _ => bail!("Unknown command '{}'", name),
+        }
+    }
+
+    /// Every command and cvar name starting with the prefix
+    pub fn completions(&self, prefix: &str, cvars: &CVars) -> Vec<String> {
+        Self::BUILTIN_COMMANDS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.keys().map(String::as_str))
+            .chain(cvars.iter().map(|(name, _)| name.as_str()))
+            .filter(|name| name.starts_with(prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Completes the command being typed, or lists the candidates when there are several
+    fn complete(&mut self, cvars: &CVars) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+        let candidates = self.completions(&self.input, cvars);
+        match candidates.as_slice() {
+            [] => {}
+            [candidate] => self.input = format!("{} ", candidate),
+            candidates => {
+                let mut prefix = candidates[0].clone();
+                for candidate in candidates.iter().skip(1) {
+                    while !candidate.starts_with(&prefix) {
+                        prefix.pop();
+                    }
+                }
+                self.input = prefix;
+                self.print(candidates.join("  "));
+            }
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        self.input = index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+
+    fn submit(&mut self, cvars: &mut CVars) {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+        if line.trim().is_empty() {
+            return;
+        }
+        self.print(format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > Self::MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.execute(&line, cvars);
+    }
+
+    pub fn ui(&mut self, ctx: &CtxRef, cvars: &mut CVars) {
+        let (seen, lines) = log_lines_since(self.log_lines_seen);
+        self.log_lines_seen = seen;
+        if self.show_log {
+            lines.into_iter().for_each(|line| self.print(line));
+        }
+        if !self.open {
+            return;
+        }
+
+        let screen = ctx.available_rect();
+        let height = screen.height() * 0.4;
+        Window::new("Console")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .fixed_rect(Rect::from_min_size(
+                pos2(screen.left(), screen.top()),
+                vec2(screen.width(), height),
+            ))
+            .show(ctx, |ui| {
+                ScrollArea::vertical()
+                    .max_height(height - 2.0 * ui.spacing().interact_size.y)
+                    .stick_to_bottom()
+                    .show(ui, |ui| {
+                        for line in self.output.iter() {
+                            ui.monospace(line);
+                        }
+                    });
+                ui.separator();
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.input)
+                        .id_source("console_input")
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
+                );
+                // The key that opened the console shouldn't be typed into it
+                self.input
+                    .retain(|character| character != '`' && character != '~');
+                if self.focus_input {
+                    response.request_focus();
+                    self.focus_input = false;
+                }
+
+                let (enter, tab, up, down) = {
+                    let input = ui.input();
+                    (
+                        input.key_pressed(Key::Enter),
+                        input.key_pressed(Key::Tab),
+                        input.key_pressed(Key::ArrowUp),
+                        input.key_pressed(Key::ArrowDown),
+                    )
+                };
+                if response.lost_focus() && enter {
+                    self.submit(cvars);
+                    response.request_focus();
+                } else if response.has_focus() {
+                    if tab {
+                        self.complete(cvars);
+                    } else if up || down {
+                        self.browse_history(up);
+                    }
+                }
+            });
+    }
+}
+
+/// Splits on whitespace, keeping text in double quotes together
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for character in line.chars() {
+        match character {
+            '"' => quoted = !quoted,
+            character if character.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            character => token.push(character),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
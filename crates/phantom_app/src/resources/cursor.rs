@@ -0,0 +1,118 @@
+use crate::read_asset;
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    egui::{self, epaint::Mesh, pos2, vec2, Color32, CtxRef, Id, LayerId, Order, Rect, Shape},
+    image,
+    winit::window::{CursorIcon, Window},
+};
+use phantom_render::Renderer;
+use std::collections::HashMap;
+
+struct CustomCursor {
+    texture: egui::TextureId,
+    size: egui::Vec2,
+
+    /// The pixel in the image that points at the cursor's position
+    hotspot: egui::Vec2,
+}
+
+/// The mouse cursor shown over the window. Widgets in the gui that want
+/// a particular cursor, such as text fields, take priority over this.
+pub struct Cursor {
+    pub icon: CursorIcon,
+    custom_cursors: HashMap<String, CustomCursor>,
+    custom: Option<String>,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            icon: CursorIcon::Default,
+            custom_cursors: HashMap::new(),
+            custom: None,
+        }
+    }
+}
+
+impl Cursor {
+    /// Loads an image from the assets to use as a cursor.
+    /// Winit can't change the hardware cursor's image, so custom cursors are drawn
+    /// over the gui with the hardware cursor hidden.
+    pub fn load_custom(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        name: &str,
+        path: &str,
+        hotspot: [f32; 2],
+    ) -> Result<()> {
+        let image = image::load_from_memory(&read_asset(path)?)?.into_rgba8();
+        let (width, height) = image.dimensions();
+        let texture = renderer
+            .create_gui_image(width, height, &image.into_raw())
+            .context(format!("Failed to upload cursor image: {}", path))?;
+        if let Some(previous) = self.custom_cursors.insert(
+            name.to_string(),
+            CustomCursor {
+                texture,
+                size: vec2(width as f32, height as f32),
+                hotspot: vec2(hotspot[0], hotspot[1]),
+            },
+        ) {
+            renderer.free_gui_image(previous.texture);
+        }
+        Ok(())
+    }
+
+    /// Shows a loaded custom cursor, or the standard icon when `None` is passed
+    pub fn set_custom(&mut self, name: Option<&str>) -> Result<()> {
+        if let Some(name) = name {
+            if !self.custom_cursors.contains_key(name) {
+                bail!("No custom cursor named '{}' has been loaded", name);
+            }
+        }
+        self.custom = name.map(str::to_string);
+        Ok(())
+    }
+
+    pub fn custom(&self) -> Option<&str> {
+        self.custom.as_deref()
+    }
+
+    /// Draws the custom cursor at the pointer, unless a widget wants its own cursor
+    pub(crate) fn paint(&self, ctx: &CtxRef) {
+        if ctx.output().cursor_icon != egui::CursorIcon::Default {
+            return;
+        }
+        let (cursor, pointer) = match (self.active_custom(), ctx.input().pointer.hover_pos()) {
+            (Some(cursor), Some(pointer)) => (cursor, pointer),
+            _ => return,
+        };
+        let rect = Rect::from_min_size(pointer - cursor.hotspot, cursor.size);
+        let mut mesh = Mesh::with_texture(cursor.texture);
+        mesh.add_rect_with_uv(
+            rect,
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        ctx.layer_painter(LayerId::new(Order::Tooltip, Id::new("custom_cursor")))
+            .add(Shape::mesh(mesh));
+    }
+
+    /// Applies the cursor to the window after the gui has applied the cursor its widgets want
+    pub(crate) fn apply(&self, window: &Window, gui_cursor: egui::CursorIcon) {
+        if gui_cursor != egui::CursorIcon::Default {
+            return;
+        }
+        if self.active_custom().is_some() {
+            window.set_cursor_visible(false);
+        } else {
+            window.set_cursor_icon(self.icon);
+        }
+    }
+
+    fn active_custom(&self) -> Option<&CustomCursor> {
+        self.custom
+            .as_ref()
+            .and_then(|name| self.custom_cursors.get(name))
+    }
+}
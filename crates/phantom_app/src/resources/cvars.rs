@@ -0,0 +1,482 @@
+use crate::System;
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    egui::{Button, DragValue, Grid, ScrollArea, Ui},
+    log,
+    rapier3d::na::Vector3,
+    serde::{Deserialize, Serialize},
+    serde_json,
+};
+use phantom_render::{RenderSettings, Renderer, Tonemapping};
+use phantom_world::World;
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", untagged)]
+pub enum CVarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl CVarValue {
+    /// Parses text as a value of the same type as this one
+    pub fn parse_as(&self, text: &str) -> Result<Self> {
+        let text = text.trim();
+        Ok(match self {
+            Self::Bool(_) => Self::Bool(match text {
+                "1" | "true" | "on" => true,
+                "0" | "false" | "off" => false,
+                _ => bail!("Expected true or false, got '{}'", text),
+            }),
+            Self::Int(_) => Self::Int(text.parse()?),
+            Self::Float(_) => Self::Float(text.parse()?),
+            Self::String(_) => Self::String(text.to_string()),
+        })
+    }
+
+    /// Converts a value to the type of this one, such as an int assigned to a float cvar
+    fn coerce(&self, value: Self) -> Result<Self> {
+        Ok(match (self, value) {
+            (Self::Bool(_), Self::Bool(value)) => Self::Bool(value),
+            (Self::Int(_), Self::Int(value)) => Self::Int(value),
+            (Self::Float(_), Self::Float(value)) => Self::Float(value),
+            (Self::Float(_), Self::Int(value)) => Self::Float(value as f64),
+            (Self::String(_), value) => Self::String(value.to_string()),
+            (_, Self::String(text)) => self.parse_as(&text)?,
+            (expected, value) => bail!("Expected {}, got '{}'", expected.type_name(), value),
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "a bool",
+            Self::Int(_) => "an int",
+            Self::Float(_) => "a float",
+            Self::String(_) => "a string",
+        }
+    }
+}
+
+impl fmt::Display for CVarValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+            Self::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<bool> for CVarValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for CVarValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for CVarValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<u32> for CVarValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<f64> for CVarValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<f32> for CVarValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value as f64)
+    }
+}
+
+impl From<&str> for CVarValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for CVarValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+pub type CVarCallback = Box<dyn FnMut(&CVarValue)>;
+
+pub struct CVar {
+    pub description: String,
+    value: CVarValue,
+    default: CVarValue,
+    callbacks: Vec<CVarCallback>,
+}
+
+impl CVar {
+    pub fn value(&self) -> &CVarValue {
+        &self.value
+    }
+
+    pub fn default(&self) -> &CVarValue {
+        &self.default
+    }
+
+    pub fn is_overridden(&self) -> bool {
+        self.value != self.default
+    }
+}
+
+/// Runtime-tunable values, named with a prefix for the subsystem they belong to,
+/// such as `r.` for rendering, `p.` for physics and `t.` for time.
+/// Values that differ from their defaults are the user's overrides, which are saved.
+#[derive(Default)]
+pub struct CVars {
+    cvars: BTreeMap<String, CVar>,
+
+    /// Overrides loaded before their cvar was registered
+    pending_overrides: BTreeMap<String, CVarValue>,
+    changes: Vec<String>,
+}
+
+impl CVars {
+    pub const DEFAULT_PATH: &'static str = "cvars.json";
+
+    pub const SHADOWS: &'static str = "r.shadows";
+    pub const SHADOW_RESOLUTION: &'static str = "r.shadow_resolution";
+    pub const SHADOW_DISTANCE: &'static str = "r.shadow_distance";
+    pub const FRUSTUM_CULLING: &'static str = "r.frustum_culling";
+    pub const MSAA: &'static str = "r.msaa";
+    pub const TONEMAPPING: &'static str = "r.tonemapping";
+    pub const EXPOSURE: &'static str = "r.exposure";
+    pub const BLOOM: &'static str = "r.bloom";
+    pub const FXAA: &'static str = "r.fxaa";
+    pub const GRAVITY: &'static str = "p.gravity";
+    pub const TIME_SCALE: &'static str = "t.timescale";
+
+    /// Registers a cvar, applying any override loaded for it.
+    /// Registering a name again keeps its current value.
+    pub fn register(&mut self, name: &str, default: impl Into<CVarValue>, description: &str) {
+        if self.cvars.contains_key(name) {
+            return;
+        }
+        let default = default.into();
+        let value = match self.pending_overrides.remove(name) {
+            Some(value) => default.coerce(value).unwrap_or_else(|error| {
+                log::warn!("Ignoring the saved value of cvar '{}': {}", name, error);
+                default.clone()
+            }),
+            None => default.clone(),
+        };
+        if value != default {
+            self.changes.push(name.to_string());
+        }
+        self.cvars.insert(
+            name.to_string(),
+            CVar {
+                description: description.to_string(),
+                value,
+                default,
+                callbacks: Vec::new(),
+            },
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.cvars.contains_key(name)
+    }
+
+    pub fn cvar(&self, name: &str) -> Option<&CVar> {
+        self.cvars.get(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CVarValue> {
+        self.cvars.get(name).map(CVar::value)
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            CVarValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            CVarValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            CVarValue::Float(value) => Some(*value),
+            CVarValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            CVarValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Sets a cvar, notifying its callbacks if the value changed
+    pub fn set(&mut self, name: &str, value: impl Into<CVarValue>) -> Result<()> {
+        let cvar = self
+            .cvars
+            .get_mut(name)
+            .context(format!("Unknown cvar '{}'", name))?;
+        let value = cvar
+            .default
+            .coerce(value.into())
+            .context(format!("Invalid value for cvar '{}'", name))?;
+        if cvar.value == value {
+            return Ok(());
+        }
+        cvar.value = value;
+        for callback in cvar.callbacks.iter_mut() {
+            callback(&cvar.value);
+        }
+        self.changes.push(name.to_string());
+        Ok(())
+    }
+
+    /// Parses text as the cvar's type and sets it
+    pub fn set_from_str(&mut self, name: &str, text: &str) -> Result<()> {
+        let value = self
+            .get(name)
+            .context(format!("Unknown cvar '{}'", name))?
+            .parse_as(text)?;
+        self.set(name, value)
+    }
+
+    pub fn reset(&mut self, name: &str) -> Result<()> {
+        let default = self
+            .cvars
+            .get(name)
+            .map(|cvar| cvar.default.clone())
+            .context(format!("Unknown cvar '{}'", name))?;
+        self.set(name, default)
+    }
+
+    /// Calls the callback with the new value whenever the cvar changes
+    pub fn on_change(
+        &mut self,
+        name: &str,
+        callback: impl FnMut(&CVarValue) + 'static,
+    ) -> Result<()> {
+        self.cvars
+            .get_mut(name)
+            .context(format!("Unknown cvar '{}'", name))?
+            .callbacks
+            .push(Box::new(callback));
+        Ok(())
+    }
+
+    /// Names of the cvars changed since this was last called,
+    /// for subsystems that poll instead of registering a callback
+    pub fn take_changes(&mut self) -> Vec<String> {
+        let mut changes = std::mem::take(&mut self.changes);
+        changes.dedup();
+        changes
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &CVar)> {
+        self.cvars.iter()
+    }
+
+    /// Saves every cvar that differs from its default
+    pub fn save_overrides(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut overrides = self.pending_overrides.clone();
+        overrides.extend(
+            self.cvars
+                .iter()
+                .filter(|(_, cvar)| cvar.is_overridden())
+                .map(|(name, cvar)| (name.clone(), cvar.value.clone())),
+        );
+        Ok(fs::write(path, serde_json::to_string_pretty(&overrides)?)?)
+    }
+
+    /// Applies saved overrides, holding on to those for cvars that aren't registered yet
+    pub fn load_overrides(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let overrides: BTreeMap<String, CVarValue> = serde_json::from_slice(&fs::read(path)?)?;
+        for (name, value) in overrides {
+            if self.contains(&name) {
+                if let Err(error) = self.set(&name, value) {
+                    log::warn!("Ignoring the saved value of cvar '{}': {}", name, error);
+                }
+            } else {
+                self.pending_overrides.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the cvars the engine applies to its own subsystems,
+    /// defaulting the `r.` cvars to the renderer's starting settings
+    pub fn register_engine_cvars(&mut self, settings: &RenderSettings) {
+        self.register(
+            Self::SHADOWS,
+            settings.shadows,
+            "Whether the first directional light casts shadows",
+        );
+        self.register(
+            Self::SHADOW_RESOLUTION,
+            settings.shadow_map_resolution,
+            "Width and height of the shadow map in texels",
+        );
+        self.register(
+            Self::SHADOW_DISTANCE,
+            settings.shadow_distance,
+            "Half the width of the area around the camera that receives shadows",
+        );
+        self.register(
+            Self::FRUSTUM_CULLING,
+            settings.frustum_culling,
+            "Skips meshes whose bounding box is outside the camera's view",
+        );
+        self.register(
+            Self::MSAA,
+            settings.sample_count,
+            "Samples per pixel for multisample antialiasing, one of 1, 2, 4 or 8",
+        );
+        self.register(
+            Self::TONEMAPPING,
+            settings.tonemapping.name(),
+            "How bright colors are mapped to the display, one of none, reinhard or aces",
+        );
+        self.register(
+            Self::EXPOSURE,
+            settings.exposure,
+            "Scales the world's colors before they are tonemapped",
+        );
+        self.register(Self::BLOOM, settings.bloom, "Makes bright areas glow");
+        self.register(
+            Self::FXAA,
+            settings.fxaa,
+            "Fast approximate antialiasing applied after tonemapping",
+        );
+        self.register(
+            Self::GRAVITY,
+            -9.81,
+            "Vertical gravity in meters per second squared",
+        );
+        self.register(
+            Self::TIME_SCALE,
+            1.0,
+            "Multiplies the delta time the simulation advances by",
+        );
+    }
+
+    /// Applies the `r.` and `t.` cvars to the renderer and system
+    pub fn apply_to_engine(&self, renderer: &mut dyn Renderer, system: &mut System) {
+        let mut settings = renderer.render_settings();
+        if let Some(shadows) = self.bool(Self::SHADOWS) {
+            settings.shadows = shadows;
+        }
+        if let Some(resolution) = self.int(Self::SHADOW_RESOLUTION) {
+            settings.shadow_map_resolution = resolution.clamp(256, 8192) as u32;
+        }
+        if let Some(distance) = self.float(Self::SHADOW_DISTANCE) {
+            settings.shadow_distance = distance as f32;
+        }
+        if let Some(frustum_culling) = self.bool(Self::FRUSTUM_CULLING) {
+            settings.frustum_culling = frustum_culling;
+        }
+        if let Some(sample_count) = self.int(Self::MSAA) {
+            settings.sample_count = sample_count.clamp(1, 8) as u32;
+        }
+        if let Some(tonemapping) = self.string(Self::TONEMAPPING) {
+            match Tonemapping::from_name(tonemapping) {
+                Some(tonemapping) => settings.tonemapping = tonemapping,
+                None => log::warn!("Unknown tonemapping operator '{}'", tonemapping),
+            }
+        }
+        if let Some(exposure) = self.float(Self::EXPOSURE) {
+            settings.exposure = exposure.max(0.0) as f32;
+        }
+        if let Some(bloom) = self.bool(Self::BLOOM) {
+            settings.bloom = bloom;
+        }
+        if let Some(fxaa) = self.bool(Self::FXAA) {
+            settings.fxaa = fxaa;
+        }
+        renderer.set_render_settings(settings);
+
+        if let Some(time_scale) = self.float(Self::TIME_SCALE) {
+            system.time_scale = time_scale.max(0.0);
+        }
+    }
+
+    /// Applies the `p.` cvars to a world's physics, keeping any horizontal gravity.
+    /// The app applies them to the worlds of the running states whenever they change.
+    pub fn apply_to_world(&self, world: &mut World) {
+        if let Some(gravity) = self.float(Self::GRAVITY) {
+            let horizontal = world.physics.gravity.xz();
+            world
+                .physics
+                .set_gravity(Vector3::new(horizontal.x, gravity as f32, horizontal.y));
+        }
+    }
+
+    /// Sets the `p.` cvars from a world's physics, such as after its scene is loaded,
+    /// so they show the scene's own settings instead of replacing them
+    pub fn read_from_world(&mut self, world: &World) {
+        if let Err(error) = self.set(Self::GRAVITY, world.physics.gravity.y) {
+            log::warn!("Failed to read gravity from the world: {}", error);
+        }
+    }
+
+    /// Lists every cvar with an editor for its value and a button to reset it
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let mut edits = Vec::new();
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("cvars").striped(true).show(ui, |ui| {
+                for (name, cvar) in self.cvars.iter() {
+                    ui.label(name).on_hover_text(&cvar.description);
+                    let mut value = cvar.value.clone();
+                    let changed = match &mut value {
+                        CVarValue::Bool(value) => ui.checkbox(value, "").changed(),
+                        CVarValue::Int(value) => ui.add(DragValue::new(value)).changed(),
+                        CVarValue::Float(value) => {
+                            ui.add(DragValue::new(value).speed(0.01)).changed()
+                        }
+                        CVarValue::String(value) => ui.text_edit_singleline(value).changed(),
+                    };
+                    if changed {
+                        edits.push((name.clone(), value));
+                    }
+                    if ui
+                        .add_enabled(cvar.is_overridden(), Button::new("Reset"))
+                        .clicked()
+                    {
+                        edits.push((name.clone(), cvar.default.clone()));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        for (name, value) in edits {
+            if let Err(error) = self.set(&name, value) {
+                log::error!("{}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use phantom_dependencies::{
+    anyhow::{anyhow, Result},
+    winit::event_loop::EventLoopProxy,
+};
+use std::any::Any;
+
+/// A typed event sent to the app from outside the event loop, such as a finished asset load
+pub struct AppEvent(Box<dyn Any + Send>);
+
+impl AppEvent {
+    pub fn new<T: Any + Send>(event: T) -> Self {
+        Self(Box::new(event))
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    /// Takes the event out if it's a `T`, or hands it back so another type can be tried
+    pub fn downcast<T: Any>(self) -> Result<T, Self> {
+        self.0.downcast().map(|event| *event).map_err(Self)
+    }
+}
+
+/// Wakes the event loop and delivers events to the running states' `on_user_event`.
+/// Clones can be moved to background threads such as loaders and network clients.
+#[derive(Clone)]
+pub struct EventProxy(EventLoopProxy<AppEvent>);
+
+impl EventProxy {
+    pub fn new(proxy: EventLoopProxy<AppEvent>) -> Self {
+        Self(proxy)
+    }
+
+    /// Fails if the event loop has already exited
+    pub fn send<T: Any + Send>(&self, event: T) -> Result<()> {
+        self.0
+            .send_event(AppEvent::new(event))
+            .map_err(|_| anyhow!("Failed to send an app event, the event loop has exited!"))
+    }
+}
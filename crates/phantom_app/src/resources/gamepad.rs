@@ -0,0 +1,128 @@
+use crate::InputTuning;
+use phantom_dependencies::gilrs::{Axis, Button, Event, EventType, GamepadId, Gilrs};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Default, Debug, Clone)]
+pub struct GamepadState {
+    pub name: String,
+    pub connected: bool,
+    pressed: HashSet<Button>,
+
+    /// How far analog buttons such as triggers are pushed, from 0 to 1
+    button_values: HashMap<Button, f32>,
+
+    /// Raw axis values, before the dead zone is applied
+    axes: HashMap<Axis, f32>,
+}
+
+impl GamepadState {
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    pub fn button_value(&self, button: Button) -> f32 {
+        self.button_values.get(&button).copied().unwrap_or_else(|| {
+            if self.is_pressed(button) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    }
+
+    pub fn raw_axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or_default()
+    }
+}
+
+/// The state of every gamepad seen since the app started, fed from gilrs events
+#[derive(Default)]
+pub struct Gamepads {
+    gamepads: HashMap<GamepadId, GamepadState>,
+}
+
+impl Gamepads {
+    /// Registers the gamepads that were connected before the first event arrived
+    pub fn add_connected(&mut self, gilrs: &Gilrs) {
+        for (id, gamepad) in gilrs.gamepads() {
+            let state = self.gamepads.entry(id).or_default();
+            state.name = gamepad.name().to_string();
+            state.connected = gamepad.is_connected();
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        let state = self.gamepads.entry(event.id).or_default();
+        match event.event {
+            EventType::Connected => state.connected = true,
+            EventType::Disconnected => {
+                state.connected = false;
+                state.pressed.clear();
+                state.button_values.clear();
+                state.axes.clear();
+            }
+            EventType::ButtonPressed(button, _) => {
+                state.pressed.insert(button);
+            }
+            EventType::ButtonReleased(button, _) => {
+                state.pressed.remove(&button);
+            }
+            EventType::ButtonChanged(button, value, _) => {
+                state.button_values.insert(button, value);
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                state.axes.insert(axis, value);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn gamepad(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.get(&id)
+    }
+
+    pub fn connected(&self) -> impl Iterator<Item = (GamepadId, &GamepadState)> {
+        self.gamepads
+            .iter()
+            .filter(|(_, state)| state.connected)
+            .map(|(id, state)| (*id, state))
+    }
+
+    /// Whether the button is held on any connected gamepad
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.connected().any(|(_, state)| state.is_pressed(button))
+    }
+
+    /// A gamepad's axis with its tuning applied, from -1 to 1
+    pub fn gamepad_axis(&self, id: GamepadId, axis: Axis, tuning: &InputTuning) -> f32 {
+        self.gamepad(id)
+            .map_or(0.0, |state| tuning.gamepad(&state.name).axis(state, axis))
+    }
+
+    /// A gamepad's analog button, such as a trigger, with the trigger dead zone removed
+    pub fn gamepad_button_value(&self, id: GamepadId, button: Button, tuning: &InputTuning) -> f32 {
+        self.gamepad(id).map_or(0.0, |state| {
+            tuning.gamepad(&state.name).button_value(state, button)
+        })
+    }
+
+    /// The axis pushed furthest across every connected gamepad
+    pub fn axis(&self, axis: Axis, tuning: &InputTuning) -> f32 {
+        self.connected()
+            .map(|(id, _)| self.gamepad_axis(id, axis, tuning))
+            .fold(0.0, |furthest, value| {
+                if value.abs() > furthest.abs() {
+                    value
+                } else {
+                    furthest
+                }
+            })
+    }
+
+    /// The analog button pushed furthest across every connected gamepad
+    pub fn button_value(&self, button: Button, tuning: &InputTuning) -> f32 {
+        self.connected()
+            .map(|(id, _)| self.gamepad_button_value(id, button, tuning))
+            .fold(0.0, f32::max)
+    }
+}
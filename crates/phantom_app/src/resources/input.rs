@@ -1,44 +1,147 @@
+use crate::{ActionMap, Gamepads, InputBinding, MouseTuning};
 use phantom_dependencies::{
+    gilrs::{self, Axis, Button, EventType},
     nalgebra_glm as glm,
     winit::{
         dpi::PhysicalPosition,
         event::{
-            ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
-            WindowEvent,
+            DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, Touch,
+            TouchPhase, VirtualKeyCode, WindowEvent,
         },
     },
 };
-use std::collections::HashMap;
+use phantom_world::{FlyCamera, OrbitCamera};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 pub type KeyMap = HashMap<VirtualKeyCode, ElementState>;
 
+/// An input event and the moment it arrived from the window system
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub event: Event<'static, ()>,
+    pub time: Instant,
+}
+
 pub struct Input {
     pub keystates: KeyMap,
     pub mouse: Mouse,
+    pub touches: Touches,
+    pub gamepads: Gamepads,
     pub allowed: bool,
+    pub actions: ActionMap,
+
+    /// Characters typed since the last frame, including those from the virtual keyboard.
+    /// Backspaces are kept as `Input::BACKSPACE`, so `edit_text` can erase with them.
+    pub text: String,
+
+    /// Keys that stay pressed after being pressed once, until they are pressed again
+    pub hold_to_toggle: HashSet<VirtualKeyCode>,
+    physically_held: HashSet<VirtualKeyCode>,
+
+    /// When the input event being handled arrived, for timing input between frames
+    /// in rhythm and fighting games. This is `None` outside of input event handlers.
+    pub event_time: Option<Instant>,
+    queued: Vec<TimedEvent>,
 }
 
 impl Default for Input {
     fn default() -> Self {
+        let mut actions = ActionMap::default();
+        actions.bind_fly_camera_defaults();
         Self {
             keystates: KeyMap::default(),
             mouse: Mouse::default(),
+            touches: Touches::default(),
+            gamepads: Gamepads::default(),
             allowed: true,
+            actions,
+            text: String::new(),
+            hold_to_toggle: HashSet::new(),
+            physically_held: HashSet::new(),
+            event_time: None,
+            queued: Vec::new(),
         }
     }
 }
 
 impl Input {
+    const FAST_FLY_MULTIPLIER: f32 = 4.0;
+    const SLOW_FLY_MULTIPLIER: f32 = 0.25;
+
+    /// The character typed by the backspace key
+    pub const BACKSPACE: char = '\u{8}';
+
+    /// Types the text entered since the last frame into a string, erasing a character for each backspace
+    pub fn edit_text(&self, field: &mut String) {
+        for character in self.text.chars() {
+            if character == Self::BACKSPACE {
+                field.pop();
+            } else {
+                field.push(character);
+            }
+        }
+    }
+
     pub fn is_key_pressed(&self, keycode: VirtualKeyCode) -> bool {
         self.keystates.contains_key(&keycode) && self.keystates[&keycode] == ElementState::Pressed
     }
 
+    /// Whether an event is input that is queued to be handled at the start of the next update
+    pub fn is_queued_event<T>(event: &Event<T>) -> bool {
+        match event {
+            Event::WindowEvent { event, .. } => matches!(
+                event,
+                WindowEvent::KeyboardInput { .. }
+                    | WindowEvent::ReceivedCharacter(_)
+                    | WindowEvent::ModifiersChanged(_)
+                    | WindowEvent::MouseInput { .. }
+                    | WindowEvent::CursorMoved { .. }
+                    | WindowEvent::CursorEntered { .. }
+                    | WindowEvent::CursorLeft { .. }
+                    | WindowEvent::MouseWheel { .. }
+                    | WindowEvent::Touch(_)
+                    | WindowEvent::AxisMotion { .. }
+            ),
+            Event::DeviceEvent { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Timestamps an input event and queues it, so the events arriving between frames
+    /// are handled in order at the start of the next update
+    pub fn queue_event(&mut self, event: Event<'static, ()>) {
+        self.queued.push(TimedEvent {
+            event,
+            time: Instant::now(),
+        });
+    }
+
+    /// The input events queued since the last update, oldest first
+    pub(crate) fn take_queued(&mut self) -> Vec<TimedEvent> {
+        std::mem::take(&mut self.queued)
+    }
+
     pub fn handle_event<T>(&mut self, event: &Event<T>, window_center: glm::Vec2) {
         if !self.allowed {
             return;
         }
 
+        if let Event::NewEvents { .. } = event {
+            self.text.clear();
+        }
+
         if let Event::WindowEvent { event, .. } = event {
+            if let WindowEvent::ReceivedCharacter(character) = *event {
+                // macOS sends delete rather than backspace for the backspace key
+                match character {
+                    Self::BACKSPACE | '\u{7f}' => self.text.push(Self::BACKSPACE),
+                    character if !character.is_control() => self.text.push(character),
+                    _ => {}
+                }
+            }
             if let WindowEvent::KeyboardInput {
                 input:
                     KeyboardInput {
@@ -49,11 +152,174 @@ impl Input {
                 ..
             } = *event
             {
-                *self.keystates.entry(keycode).or_insert(state) = state;
+                if state == ElementState::Pressed
+                    && self.actions.binding_pressed(InputBinding::Key(keycode))
+                {
+                    return;
+                }
+                if self.hold_to_toggle.contains(&keycode) {
+                    self.toggle_key(keycode, state);
+                } else {
+                    *self.keystates.entry(keycode).or_insert(state) = state;
+                }
+            }
+        }
+
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    button,
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.actions.binding_pressed(InputBinding::Mouse(*button)) {
+                return;
+            }
+        }
+
+        self.mouse
+            .handle_event(event, window_center, &self.actions.tuning.mouse);
+        self.touches.handle_event(event);
+    }
+
+    pub fn handle_gamepad_event(&mut self, event: &gilrs::Event) {
+        if let EventType::ButtonPressed(button, _) = event.event {
+            if self
+                .actions
+                .binding_pressed(InputBinding::GamepadButton(button))
+            {
+                return;
+            }
+        }
+        self.gamepads.handle_event(event);
+    }
+
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.actions.is_pressed(action, self)
+    }
+
+    /// The value of a bound axis, from -1 to 1
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        self.actions.axis(axis, self)
+    }
+
+    /// A gamepad axis with the tuning from the action map applied
+    pub fn gamepad_axis(&self, axis: Axis) -> f32 {
+        self.gamepads.axis(axis, &self.actions.tuning)
+    }
+
+    pub fn gamepad_button_value(&self, button: Button) -> f32 {
+        self.gamepads.button_value(button, &self.actions.tuning)
+    }
+
+    /// Dragging with the right mouse button rotates, the middle button pans and the wheel zooms
+    pub fn drive_orbit_camera(&self, camera: &mut OrbitCamera) {
+        if self.mouse.is_right_clicked {
+            camera.rotate(&self.mouse.position_delta);
+        }
+        if self.mouse.is_middle_clicked {
+            camera.pan(&self.mouse.position_delta);
+        }
+        if self.mouse.wheel_delta.y != 0.0 {
+            camera.zoom(self.mouse.wheel_delta.y);
+        }
+    }
+
+    /// Moves along the `move_x`, `move_y` and `move_z` axes and looks around with the mouse.
+    /// Holding `fly_fast` flies faster and holding `fly_slow` flies slower.
+    /// These default to WASD, E and Q, shift and control, see `ActionMap::bind_fly_camera_defaults`.
+    /// Grab the cursor with `Resources::set_mouse_look` so mouse movement isn't limited by the window.
+    pub fn drive_fly_camera(&self, camera: &mut FlyCamera, delta_time: f32) {
+        camera.look(&self.mouse.motion_delta);
+
+        let direction = glm::vec3(
+            self.action_axis(ActionMap::MOVE_X),
+            self.action_axis(ActionMap::MOVE_Y),
+            self.action_axis(ActionMap::MOVE_Z),
+        );
+
+        let speed_multiplier = if self.is_action_pressed(ActionMap::FLY_FAST) {
+            Self::FAST_FLY_MULTIPLIER
+        } else if self.is_action_pressed(ActionMap::FLY_SLOW) {
+            Self::SLOW_FLY_MULTIPLIER
+        } else {
+            1.0
+        };
+        camera.fly(&direction, speed_multiplier, delta_time);
+    }
+
+    fn toggle_key(&mut self, keycode: VirtualKeyCode, state: ElementState) {
+        // Held keys repeat their pressed events, so only the first press toggles
+        let newly_pressed = match state {
+            ElementState::Pressed => self.physically_held.insert(keycode),
+            ElementState::Released => {
+                self.physically_held.remove(&keycode);
+                false
             }
+        };
+        if newly_pressed {
+            let toggled = if self.is_key_pressed(keycode) {
+                ElementState::Released
+            } else {
+                ElementState::Pressed
+            };
+            self.keystates.insert(keycode, toggled);
         }
+    }
+}
 
-        self.mouse.handle_event(event, window_center);
+#[derive(Debug, Copy, Clone)]
+pub struct TouchPoint {
+    pub position: glm::Vec2,
+    pub start_position: glm::Vec2,
+    pub position_delta: glm::Vec2,
+    pub phase: TouchPhase,
+}
+
+/// Fingers currently touching the screen, keyed by their touch id
+#[derive(Default)]
+pub struct Touches {
+    pub active: HashMap<u64, TouchPoint>,
+}
+
+impl Touches {
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        match event {
+            Event::NewEvents { .. } => self.new_events(),
+            Event::WindowEvent {
+                event: WindowEvent::Touch(touch),
+                ..
+            } => self.touch(touch),
+            _ => {}
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.active.len()
+    }
+
+    fn new_events(&mut self) {
+        self.active
+            .retain(|_, touch| !matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled));
+        self.active
+            .values_mut()
+            .for_each(|touch| touch.position_delta = glm::vec2(0.0, 0.0));
+    }
+
+    fn touch(&mut self, touch: &Touch) {
+        let position = glm::vec2(touch.location.x as f32, touch.location.y as f32);
+        let point = self.active.entry(touch.id).or_insert(TouchPoint {
+            position,
+            start_position: position,
+            position_delta: glm::vec2(0.0, 0.0),
+            phase: touch.phase,
+        });
+        point.position_delta = position - point.position;
+        point.position = position;
+        point.phase = touch.phase;
     }
 }
 
@@ -61,8 +327,13 @@ impl Input {
 pub struct Mouse {
     pub is_left_clicked: bool,
     pub is_right_clicked: bool,
+    pub is_middle_clicked: bool,
     pub position: glm::Vec2,
     pub position_delta: glm::Vec2,
+
+    /// Mouse movement since the last frame with sensitivity and inversion applied,
+    /// which keeps working while the cursor is grabbed
+    pub motion_delta: glm::Vec2,
     pub offset_from_center: glm::Vec2,
     pub wheel_delta: glm::Vec2,
     pub moved: bool,
@@ -70,7 +341,12 @@ pub struct Mouse {
 }
 
 impl Mouse {
-    pub fn handle_event<T>(&mut self, event: &Event<T>, window_center: glm::Vec2) {
+    pub fn handle_event<T>(
+        &mut self,
+        event: &Event<T>,
+        window_center: glm::Vec2,
+        tuning: &MouseTuning,
+    ) {
         match event {
             Event::NewEvents { .. } => self.new_events(),
             Event::WindowEvent { event, .. } => match *event {
@@ -84,11 +360,17 @@ impl Mouse {
                 } => self.mouse_wheel(h_lines, v_lines),
                 _ => {}
             },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => self.motion_delta += tuning.apply(glm::vec2(delta.0 as f32, delta.1 as f32)),
             _ => {}
         }
     }
 
     fn new_events(&mut self) {
+        self.motion_delta = glm::vec2(0.0, 0.0);
+
         if !self.scrolled {
             self.wheel_delta = glm::vec2(0.0, 0.0);
         }
@@ -114,11 +396,21 @@ impl Mouse {
         self.scrolled = true;
     }
 
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        match button {
+            MouseButton::Left => self.is_left_clicked,
+            MouseButton::Right => self.is_right_clicked,
+            MouseButton::Middle => self.is_middle_clicked,
+            MouseButton::Other(_) => false,
+        }
+    }
+
     fn mouse_input(&mut self, button: MouseButton, state: ElementState) {
         let clicked = state == ElementState::Pressed;
         match button {
             MouseButton::Left => self.is_left_clicked = clicked,
             MouseButton::Right => self.is_right_clicked = clicked,
+            MouseButton::Middle => self.is_middle_clicked = clicked,
             _ => {}
         }
     }
@@ -0,0 +1,200 @@
+use crate::GamepadState;
+use phantom_dependencies::{
+    gilrs::{Axis, Button},
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum DeadZoneMode {
+    /// Removes the dead zone from each axis separately, which snaps to straight lines near the center
+    Axial,
+
+    /// Removes the dead zone by distance from the center, which keeps diagonals smooth
+    Radial,
+}
+
+/// Shapes how far an analog input reads as pushed, for finer control near the center
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum ResponseCurve {
+    Linear,
+    Quadratic,
+    Cubic,
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Quadratic => value.signum() * value * value,
+            Self::Cubic => value * value * value,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", default)]
+pub struct StickTuning {
+    pub dead_zone: f32,
+    pub dead_zone_mode: DeadZoneMode,
+    pub response_curve: ResponseCurve,
+    pub sensitivity: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Default for StickTuning {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            dead_zone_mode: DeadZoneMode::Radial,
+            response_curve: ResponseCurve::Linear,
+            sensitivity: 1.0,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+
+impl StickTuning {
+    /// Tunes a stick's raw position, keeping each axis from -1 to 1
+    pub fn apply(&self, stick: glm::Vec2) -> glm::Vec2 {
+        let stick = match self.dead_zone_mode {
+            DeadZoneMode::Axial => glm::vec2(
+                self.response_curve
+                    .apply(apply_dead_zone(stick.x, self.dead_zone)),
+                self.response_curve
+                    .apply(apply_dead_zone(stick.y, self.dead_zone)),
+            ),
+            DeadZoneMode::Radial => {
+                let magnitude = glm::length(&stick);
+                let tuned = self
+                    .response_curve
+                    .apply(apply_dead_zone(magnitude.min(1.0), self.dead_zone));
+                if tuned == 0.0 {
+                    glm::Vec2::zeros()
+                } else {
+                    stick * (tuned / magnitude)
+                }
+            }
+        };
+        let stick = stick * self.sensitivity;
+        glm::vec2(
+            invert(stick.x.clamp(-1.0, 1.0), self.invert_x),
+            invert(stick.y.clamp(-1.0, 1.0), self.invert_y),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", default)]
+pub struct GamepadTuning {
+    pub left_stick: StickTuning,
+    pub right_stick: StickTuning,
+
+    /// Triggers pushed less than this read as zero
+    pub trigger_dead_zone: f32,
+}
+
+impl Default for GamepadTuning {
+    fn default() -> Self {
+        Self {
+            left_stick: StickTuning::default(),
+            right_stick: StickTuning::default(),
+            trigger_dead_zone: 0.05,
+        }
+    }
+}
+
+impl GamepadTuning {
+    pub fn axis(&self, state: &GamepadState, axis: Axis) -> f32 {
+        let (tuning, x_axis, y_axis) = match axis {
+            Axis::LeftStickX | Axis::LeftStickY => {
+                (&self.left_stick, Axis::LeftStickX, Axis::LeftStickY)
+            }
+            Axis::RightStickX | Axis::RightStickY => {
+                (&self.right_stick, Axis::RightStickX, Axis::RightStickY)
+            }
+            Axis::LeftZ | Axis::RightZ => {
+                return apply_dead_zone(state.raw_axis(axis), self.trigger_dead_zone)
+            }
+            _ => return state.raw_axis(axis),
+        };
+        let stick = tuning.apply(glm::vec2(state.raw_axis(x_axis), state.raw_axis(y_axis)));
+        if axis == x_axis {
+            stick.x
+        } else {
+            stick.y
+        }
+    }
+
+    pub fn button_value(&self, state: &GamepadState, button: Button) -> f32 {
+        apply_dead_zone(state.button_value(button), self.trigger_dead_zone)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", default)]
+pub struct MouseTuning {
+    pub sensitivity: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Default for MouseTuning {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+
+impl MouseTuning {
+    pub fn apply(&self, motion: glm::Vec2) -> glm::Vec2 {
+        glm::vec2(
+            invert(motion.x * self.sensitivity, self.invert_x),
+            invert(motion.y * self.sensitivity, self.invert_y),
+        )
+    }
+}
+
+/// How raw input from each device is adjusted before actions read it
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", default)]
+pub struct InputTuning {
+    pub mouse: MouseTuning,
+
+    /// Used for every gamepad without its own tuning
+    pub gamepad: GamepadTuning,
+
+    /// Tuning for specific gamepads, by name
+    pub gamepads: BTreeMap<String, GamepadTuning>,
+}
+
+impl InputTuning {
+    pub fn gamepad(&self, name: &str) -> &GamepadTuning {
+        self.gamepads.get(name).unwrap_or(&self.gamepad)
+    }
+}
+
+/// Zeroes values inside the dead zone and rescales the rest to the full range
+fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    let dead_zone = dead_zone.clamp(0.0, 0.99);
+    if value.abs() <= dead_zone {
+        return 0.0;
+    }
+    value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone)
+}
+
+fn invert(value: f32, inverted: bool) -> f32 {
+    if inverted {
+        -value
+    } else {
+        value
+    }
+}
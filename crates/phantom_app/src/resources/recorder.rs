@@ -0,0 +1,239 @@
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    image::{
+        codecs::gif::{GifEncoder, Repeat},
+        imageops::{self, FilterType},
+        Delay, Frame, RgbaImage,
+    },
+    log,
+    winit::event::VirtualKeyCode,
+};
+use phantom_render::CapturedFrame;
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Encoded with ffmpeg if it is installed, falling back to a gif otherwise
+    Mp4,
+    Gif,
+
+    /// An animated webp, encoded with ffmpeg if it is installed, falling back to a gif otherwise
+    WebP,
+}
+
+/// Keeps the last few seconds of rendered frames in memory so they can be saved after something interesting happens
+pub struct Recorder {
+    /// While enabled, frames are read back from the GPU at the recording's frame rate
+    pub enabled: bool,
+
+    /// Seconds of footage to keep
+    pub duration: f32,
+    pub frames_per_second: f32,
+
+    /// Frames are downscaled to this width to bound memory use
+    pub max_width: u32,
+    pub format: RecordingFormat,
+    pub output_directory: PathBuf,
+
+    /// Saves the recording when pressed
+    pub hotkey: Option<VirtualKeyCode>,
+    frames: VecDeque<RgbaImage>,
+    time_since_frame: f32,
+    screenshot_requested: bool,
+    screenshot: Option<RgbaImage>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 30.0,
+            frames_per_second: 15.0,
+            max_width: 480,
+            format: RecordingFormat::Mp4,
+            output_directory: PathBuf::from("recordings"),
+            hotkey: Some(VirtualKeyCode::F9),
+            frames: VecDeque::new(),
+            time_since_frame: 0.0,
+            screenshot_requested: false,
+            screenshot: None,
+        }
+    }
+}
+
+impl Recorder {
+    /// Captures the next rendered frame at full resolution
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn screenshot_requested(&self) -> bool {
+        self.screenshot_requested
+    }
+
+    pub fn take_screenshot(&mut self) -> Option<RgbaImage> {
+        self.screenshot.take()
+    }
+
+    /// Whether the renderer needs to read back the next frame
+    pub fn capturing(&self) -> bool {
+        self.enabled || self.screenshot_requested
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.time_since_frame = 0.0;
+    }
+
+    /// Advances the recording's clock, returning whether the next rendered frame should be
+    /// read back. Frames are only copied from the GPU at the recording's frame rate.
+    pub fn wants_frame(&mut self, delta_time: f32) -> bool {
+        let mut wanted = self.screenshot_requested;
+        if self.enabled {
+            self.time_since_frame += delta_time;
+            let interval = 1.0 / self.frames_per_second.max(1.0);
+            if self.time_since_frame >= interval {
+                self.time_since_frame = 0.0;
+                wanted = true;
+            }
+        }
+        wanted
+    }
+
+    /// Adds a frame read back after `wants_frame` to the ring buffer
+    pub fn push(&mut self, frame: CapturedFrame) {
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.screenshot = RgbaImage::from_raw(frame.width, frame.height, frame.pixels.to_vec());
+        }
+        if !self.enabled {
+            return;
+        }
+
+        let image = match RgbaImage::from_raw(frame.width, frame.height, frame.pixels) {
+            Some(image) => image,
+            None => return,
+        };
+        let image = if image.width() > self.max_width {
+            let height = (image.height() as f32 * self.max_width as f32 / image.width() as f32)
+                .round()
+                .max(1.0) as u32;
+            imageops::resize(&image, self.max_width, height, FilterType::Triangle)
+        } else {
+            image
+        };
+
+        self.frames.push_back(image);
+        let capacity = (self.duration * self.frames_per_second).ceil().max(1.0) as usize;
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Encodes the buffered frames on a background thread
+    pub fn save(&self) -> Result<()> {
+        if self.frames.is_empty() {
+            log::warn!("No frames have been recorded yet");
+            return Ok(());
+        }
+        fs::create_dir_all(&self.output_directory)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = self
+            .output_directory
+            .join(format!("recording_{}", timestamp));
+        let frames = self.frames.iter().cloned().collect::<Vec<_>>();
+        let (format, frames_per_second) = (self.format, self.frames_per_second);
+        thread::spawn(move || {
+            let result = match format {
+                RecordingFormat::Mp4 | RecordingFormat::WebP => {
+                    encode_ffmpeg(&path, format, &frames, frames_per_second).or_else(|error| {
+                        log::warn!("Falling back to gif: {}", error);
+                        encode_gif(&path, frames, frames_per_second)
+                    })
+                }
+                RecordingFormat::Gif => encode_gif(&path, frames, frames_per_second),
+            };
+            match result {
+                Ok(path) => log::info!("Saved recording to {}", path.display()),
+                Err(error) => log::error!("Failed to save recording: {}", error),
+            }
+        });
+        Ok(())
+    }
+}
+
+fn encode_ffmpeg(
+    path: &Path,
+    format: RecordingFormat,
+    frames: &[RgbaImage],
+    frames_per_second: f32,
+) -> Result<PathBuf> {
+    let (extension, output_arguments) = match format {
+        RecordingFormat::WebP => ("webp", ["-c:v", "libwebp_anim", "-loop", "0"]),
+        _ => (
+            "mp4",
+            [
+                "-pix_fmt",
+                "yuv420p",
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            ],
+        ),
+    };
+    let path = path.with_extension(extension);
+    let (width, height) = frames[0].dimensions();
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(["-y", "-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+        .arg(format!("{}x{}", width, height))
+        .arg("-r")
+        .arg(frames_per_second.to_string())
+        .args(["-i", "-"])
+        .args(output_arguments)
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start ffmpeg!")?;
+    {
+        let stdin = ffmpeg
+            .stdin
+            .as_mut()
+            .context("Failed to open ffmpeg input!")?;
+        for frame in frames
+            .iter()
+            .filter(|frame| frame.dimensions() == (width, height))
+        {
+            stdin.write_all(frame.as_raw())?;
+        }
+    }
+    if !ffmpeg.wait()?.success() {
+        bail!("ffmpeg failed to encode the recording!");
+    }
+    Ok(path)
+}
+
+fn encode_gif(path: &Path, frames: Vec<RgbaImage>, frames_per_second: f32) -> Result<PathBuf> {
+    let path = path.with_extension("gif");
+    let mut encoder = GifEncoder::new(File::create(&path)?);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, frames_per_second.max(1.0) as u32);
+    encoder.encode_frames(
+        frames
+            .into_iter()
+            .map(|frame| Frame::from_parts(frame, 0, 0, delay)),
+    )?;
+    Ok(path)
+}
@@ -0,0 +1,114 @@
+use crate::Resources;
+use phantom_dependencies::{anyhow::Result, log};
+use phantom_world::{Entity, World};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+type CleanupHook = Box<dyn FnOnce(&mut Resources) -> Result<()>>;
+
+/// Values, entities and cleanup hooks owned by one state on the stack.
+/// Everything in a scope is cleaned up when its state stops, after `on_stop`,
+/// so a menu can't leak what it created into the gameplay state that replaces it.
+#[derive(Default)]
+pub struct Scope {
+    values: HashMap<TypeId, Box<dyn Any>>,
+    entities: Vec<Entity>,
+    hooks: Vec<CleanupHook>,
+}
+
+impl Scope {
+    /// Stores a value for as long as the state runs, replacing any value of the same type
+    pub fn insert<T: 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let value = self.values.remove(&TypeId::of::<T>())?;
+        value.downcast().ok().map(|value| *value)
+    }
+
+    /// Removes the entity from the state's world when the state stops.
+    /// The world comes from `State::world`, so states that don't return one can't track entities.
+    pub fn track(&mut self, entity: Entity) {
+        self.entities.push(entity);
+    }
+
+    pub fn tracked_entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Runs when the state stops. Hooks run in the reverse of the order they were added.
+    pub fn on_exit(&mut self, hook: impl FnOnce(&mut Resources) -> Result<()> + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Removes tracked entities that still exist, runs the hooks and drops the values
+    pub(crate) fn cleanup(self, resources: &mut Resources, world: Option<&mut World>) {
+        let Self {
+            values,
+            entities,
+            hooks,
+        } = self;
+        match world {
+            Some(world) => {
+                for entity in entities.into_iter().rev() {
+                    if !world.ecs.contains(entity) {
+                        continue;
+                    }
+                    if let Err(error) = world.remove_entity(entity) {
+                        log::error!("Failed to remove a scoped entity: {}", error);
+                    }
+                }
+            }
+            None if !entities.is_empty() => log::warn!(
+                "{} scoped entities weren't removed because the state has no world",
+                entities.len()
+            ),
+            None => {}
+        }
+        for hook in hooks.into_iter().rev() {
+            if let Err(error) = hook(resources) {
+                log::error!("State cleanup hook failed: {}", error);
+            }
+        }
+        drop(values);
+    }
+}
+
+/// A scope for each state on the state machine's stack, in the same order
+#[derive(Default)]
+pub struct StateScopes {
+    scopes: Vec<Scope>,
+}
+
+impl StateScopes {
+    /// The scope of the state that is currently running
+    pub fn current(&mut self) -> &mut Scope {
+        if self.scopes.is_empty() {
+            self.scopes.push(Scope::default());
+        }
+        self.scopes.last_mut().expect("A scope was just pushed!")
+    }
+
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    pub(crate) fn push(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Scope> {
+        self.scopes.pop()
+    }
+}
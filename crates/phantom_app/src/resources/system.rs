@@ -5,31 +5,114 @@ use phantom_dependencies::{
         event::{Event, WindowEvent},
     },
 };
+use phantom_world::Viewport;
 use std::{cmp, time::Instant};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SimulationStep {
+    /// Advance by the length of the last frame
+    Frame,
+
+    /// Advance by exactly one fixed update
+    FixedUpdate,
+}
+
 pub struct System {
     pub window_dimensions: [u32; 2], // TODO: Change this to a glm::Vec2
+
+    /// The part of the window the scene is rendered in, such as an editor panel.
+    /// The scene fills the whole window when this is `None`.
+    pub viewport: Option<Viewport>,
     pub delta_time: f64,
     pub last_frame: Instant,
     pub exit_requested: bool,
+
+    /// Mobile platforms suspend the app when it is sent to the background
+    pub suspended: bool,
+
+    /// Scaled time the simulation should advance this frame, which is zero while paused
+    pub simulation_delta_time: f64,
+    pub time_scale: f64,
+    pub paused: bool,
+
+    /// Length of one fixed update, from the tick rate in the app config.
+    /// States that simulate a world keep this in sync with the world's physics settings.
+    pub fixed_timestep: f64,
+
+    /// The most fixed updates run in a single frame,
+    /// so a slow frame can't cause more and more fixed updates to pile up
+    pub max_fixed_updates: u32,
+
+    /// How far rendering is between the last fixed update and the next one, from 0 to 1
+    pub interpolation_alpha: f64,
+    accumulator: f64,
+    pending_step: Option<SimulationStep>,
 }
 
 impl System {
-    pub fn new(window_dimensions: [u32; 2]) -> Self {
+    pub const MAX_FIXED_UPDATES_PER_FRAME: u32 = 8;
+
+    pub fn new(window_dimensions: [u32; 2], tick_rate: u32) -> Self {
         Self {
             last_frame: Instant::now(),
             window_dimensions,
+            viewport: None,
             delta_time: 0.01,
             exit_requested: false,
+            suspended: false,
+            simulation_delta_time: 0.01,
+            time_scale: 1.0,
+            paused: false,
+            fixed_timestep: 1.0 / tick_rate.max(1) as f64,
+            max_fixed_updates: Self::MAX_FIXED_UPDATES_PER_FRAME,
+            interpolation_alpha: 0.0,
+            accumulator: 0.0,
+            pending_step: None,
         }
     }
 
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances a paused simulation once on the next frame
+    pub fn step(&mut self, step: SimulationStep) {
+        self.paused = true;
+        self.pending_step = Some(step);
+    }
+
+    /// Accumulates this frame's simulation time and returns how many fixed updates to run
+    pub fn fixed_updates(&mut self) -> u32 {
+        self.accumulator += self.simulation_delta_time;
+        let mut updates = 0;
+        while self.accumulator >= self.fixed_timestep {
+            self.accumulator -= self.fixed_timestep;
+            updates += 1;
+        }
+        if updates > self.max_fixed_updates {
+            updates = self.max_fixed_updates;
+            self.accumulator = 0.0;
+        }
+        self.interpolation_alpha = self.accumulator / self.fixed_timestep;
+        updates
+    }
+
     pub fn aspect_ratio(&self) -> f32 {
         let width = self.window_dimensions[0];
         let height = cmp::max(self.window_dimensions[1], 0);
         width as f32 / height as f32
     }
 
+    /// The part of the window the scene is rendered in, which is the whole window unless set
+    pub fn viewport(&self) -> Viewport {
+        self.viewport.unwrap_or(Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.window_dimensions[0] as f32,
+            height: self.window_dimensions[1] as f32,
+        })
+    }
+
     pub fn window_center(&self) -> glm::Vec2 {
         glm::vec2(
             self.window_dimensions[0] as f32 / 2.0,
@@ -44,6 +127,12 @@ impl System {
                     as f64)
                     / 1_000_000_f64;
                 self.last_frame = Instant::now();
+                self.simulation_delta_time = match (self.paused, self.pending_step.take()) {
+                    (false, _) => self.delta_time * self.time_scale,
+                    (true, Some(SimulationStep::Frame)) => self.delta_time * self.time_scale,
+                    (true, Some(SimulationStep::FixedUpdate)) => self.fixed_timestep,
+                    (true, None) => 0.0,
+                };
             }
             Event::WindowEvent { event, .. } => match *event {
                 WindowEvent::CloseRequested => self.exit_requested = true,
@@ -0,0 +1,351 @@
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct TelemetryEvent {
+    pub name: String,
+    pub timestamp: SystemTime,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl TelemetryEvent {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            timestamp: SystemTime::now(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.properties.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Somewhere batches of telemetry events are sent, from the telemetry thread
+pub trait TelemetrySink: Send {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<()>;
+}
+
+/// Appends events to a file as json lines
+pub struct FileSink {
+    pub path: PathBuf,
+}
+
+impl TelemetrySink for FileSink {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<()> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for event in events {
+            writeln!(file, "{}", serde_json::to_string(event)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Posts events to an endpoint as a json array.
+/// Only plain `http://` urls are supported, so use a local relay for https collectors.
+pub struct HttpSink {
+    pub url: String,
+    pub timeout: Duration,
+}
+
+impl HttpSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl TelemetrySink for HttpSink {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<()> {
+        let body = serde_json::to_string(events)?;
+        http_post(&self.url, "application/json", body.as_bytes(), self.timeout)
+    }
+}
+
+/// Posts a body to a plain `http://` url, failing unless the response is a success
+pub(crate) fn http_post(
+    url: &str,
+    content_type: &str,
+    body: &[u8],
+    timeout: Duration,
+) -> Result<()> {
+    let address = url
+        .strip_prefix("http://")
+        .context(format!("Only http:// urls are supported: {}", url))?;
+    let (host, path) = match address.find('/') {
+        Some(index) => address.split_at(index),
+        None => (address, "/"),
+    };
+    let host_with_port = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+
+    let mut addresses = host_with_port
+        .to_socket_addrs()
+        .context(format!("Failed to resolve {}", host))?;
+    let mut stream = loop {
+        let address = addresses
+            .next()
+            .context(format!("Failed to connect to {}", host))?;
+        if let Ok(stream) = TcpStream::connect_timeout(&address, timeout) {
+            break stream;
+        }
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path,
+        host,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .unwrap_or_default();
+    if !(200..300).contains(&status) {
+        bail!("{} responded with status {}", url, status);
+    }
+    Ok(())
+}
+
+/// Frame times in milliseconds
+#[derive(Debug, Copy, Clone)]
+pub struct FrameTimePercentiles {
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+enum SinkMessage {
+    AddSink(Box<dyn TelemetrySink>),
+    Send(Vec<TelemetryEvent>),
+}
+
+/// Opt-in collection of performance data and gameplay events.
+/// Nothing is recorded until `enabled` is set, which should follow the player's consent.
+/// Events are sent to the sinks on a background thread so slow endpoints never stall a frame.
+pub struct Telemetry {
+    pub enabled: bool,
+
+    /// Events are flushed to the sinks once this many are queued, and when the app exits
+    pub batch_size: usize,
+
+    sender: Option<Sender<SinkMessage>>,
+    worker: Option<JoinHandle<()>>,
+    events: Vec<TelemetryEvent>,
+    frame_times: Vec<f32>,
+    breadcrumbs: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        let (sender, receiver) = channel::<SinkMessage>();
+        let worker = thread::Builder::new()
+            .name("telemetry".to_string())
+            .spawn(move || {
+                let mut sinks = Vec::new();
+                for message in receiver.iter() {
+                    match message {
+                        SinkMessage::AddSink(sink) => sinks.push(sink),
+                        SinkMessage::Send(events) => {
+                            for sink in sinks.iter_mut() {
+                                if let Err(error) = sink.send(&events) {
+                                    log::warn!("Failed to send telemetry: {}", error);
+                                }
+                            }
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the telemetry thread!");
+        Self {
+            enabled: false,
+            batch_size: 100,
+            sender: Some(sender),
+            worker: Some(worker),
+            events: Vec::new(),
+            frame_times: Vec::new(),
+            breadcrumbs: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl Telemetry {
+    const MAX_BREADCRUMBS: usize = 50;
+
+    /// Frame times are summarized into an event once this many are recorded,
+    /// so a long session doesn't keep every frame in memory
+    const MAX_FRAME_TIMES: usize = 10_000;
+
+    pub fn add_sink(&mut self, sink: impl TelemetrySink + 'static) {
+        self.send_to_worker(SinkMessage::AddSink(Box::new(sink)));
+    }
+
+    pub fn record(&mut self, event: TelemetryEvent) {
+        if !self.enabled {
+            return;
+        }
+        self.events.push(event);
+        if self.events.len() >= self.batch_size {
+            self.flush();
+        }
+    }
+
+    /// Records a step the player took, included in the crash report if the app panics
+    pub fn breadcrumb(&self, message: &str) {
+        if !self.enabled {
+            return;
+        }
+        if let Ok(mut breadcrumbs) = self.breadcrumbs.lock() {
+            breadcrumbs.push_back(message.to_string());
+            while breadcrumbs.len() > Self::MAX_BREADCRUMBS {
+                breadcrumbs.pop_front();
+            }
+        }
+    }
+
+    pub fn record_frame_time(&mut self, delta_time: f32) {
+        if !self.enabled {
+            return;
+        }
+        self.frame_times.push(delta_time * 1000.0);
+        if self.frame_times.len() >= Self::MAX_FRAME_TIMES {
+            self.summarize_frame_times();
+        }
+    }
+
+    /// Percentiles of the frame times recorded since they were last summarized
+    pub fn frame_time_percentiles(&self) -> Option<FrameTimePercentiles> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        let mut frame_times = self.frame_times.clone();
+        frame_times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |percent: f32| {
+            let index = ((frame_times.len() - 1) as f32 * percent).round() as usize;
+            frame_times[index]
+        };
+        Some(FrameTimePercentiles {
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: frame_times[frame_times.len() - 1],
+        })
+    }
+
+    /// Sends queued events and a summary of the frame times to every sink
+    pub fn flush(&mut self) {
+        self.summarize_frame_times();
+        if self.events.is_empty() {
+            return;
+        }
+        let events = std::mem::take(&mut self.events);
+        self.send_to_worker(SinkMessage::Send(events));
+    }
+
+    /// Flushes the queued events and waits for the sinks to finish sending them
+    pub fn shutdown(&mut self) {
+        self.flush();
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                log::warn!("The telemetry thread panicked!");
+            }
+        }
+    }
+
+    fn send_to_worker(&mut self, message: SinkMessage) {
+        let sent = self
+            .sender
+            .as_ref()
+            .map_or(false, |sender| sender.send(message).is_ok());
+        if !sent {
+            log::warn!("Telemetry was sent after the telemetry thread stopped");
+        }
+    }
+
+    /// Queues an event summarizing the frame times recorded since the last summary
+    fn summarize_frame_times(&mut self) {
+        if let Some(percentiles) = self.frame_time_percentiles() {
+            let event = TelemetryEvent::new("frame_times")
+                .with("frames", self.frame_times.len())
+                .with("p50_ms", percentiles.p50)
+                .with("p95_ms", percentiles.p95)
+                .with("p99_ms", percentiles.p99)
+                .with("max_ms", percentiles.max);
+            self.events.push(event);
+            self.frame_times.clear();
+        }
+    }
+
+    /// Writes the breadcrumbs and panic message to a file when the app panics,
+    /// so `report_crash` can send them the next time the app starts
+    pub fn install_crash_handler(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_path_buf();
+        let breadcrumbs = self.breadcrumbs.clone();
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let mut event = TelemetryEvent::new("crash").with("message", info);
+            if let Ok(breadcrumbs) = breadcrumbs.lock() {
+                for (index, breadcrumb) in breadcrumbs.iter().enumerate() {
+                    event = event.with(&format!("breadcrumb_{:02}", index), breadcrumb);
+                }
+            }
+            if let Ok(report) = serde_json::to_string(&event) {
+                let _ = fs::write(&path, report);
+            }
+            previous_hook(info);
+        }));
+    }
+
+    /// Queues the crash report left by a previous run, if there is one
+    pub fn report_crash(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(());
+        }
+        let event = serde_json::from_str(&fs::read_to_string(path)?)?;
+        fs::remove_file(path)?;
+        self.record(event);
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    bincode,
+    image::{
+        self,
+        imageops::{self, FilterType},
+        DynamicImage, ImageOutputFormat, RgbaImage,
+    },
+    log,
+    serde::{Deserialize, Serialize},
+};
+use phantom_platform::Platform;
+use phantom_world::World;
+use std::{
+    fs::{self, File},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Shown in save slot menus without loading the world
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct SaveMetadata {
+    pub name: String,
+    pub timestamp: SystemTime,
+
+    /// Total time played, including previous sessions
+    pub playtime: Duration,
+}
+
+impl SaveMetadata {
+    pub fn new(name: &str, playtime: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            timestamp: SystemTime::now(),
+            playtime,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct SaveFile {
+    metadata: SaveMetadata,
+
+    /// A png encoded screenshot
+    thumbnail: Option<Vec<u8>>,
+
+    /// The world in the scene format
+    world: Vec<u8>,
+}
+
+/// Numbered save slots in a directory.
+/// Saves are written to a temporary file and moved into place, so a crash mid-save
+/// leaves the previous save intact. The previous save is kept as a backup and is loaded
+/// instead if the latest one fails its checksum.
+pub struct SaveSlots {
+    directory: PathBuf,
+}
+
+impl SaveSlots {
+    const MAGIC: &'static [u8] = b"PHSAVE";
+    const VERSION: u32 = 1;
+    const EXTENSION: &'static str = "save";
+    const BACKUP_EXTENSION: &'static str = "save.bak";
+    const THUMBNAIL_WIDTH: u32 = 320;
+
+    /// Slots in the platform's save directory, which is separated per platform user
+    pub fn new(platform: &Platform, app_name: &str) -> Result<Self> {
+        Ok(Self::in_directory(platform.save_directory(app_name)?))
+    }
+
+    pub fn in_directory(directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn save(
+        &self,
+        slot: u32,
+        world: &World,
+        metadata: &SaveMetadata,
+        thumbnail: Option<&RgbaImage>,
+    ) -> Result<()> {
+        let thumbnail = match thumbnail {
+            Some(thumbnail) => Some(encode_thumbnail(thumbnail)?),
+            None => None,
+        };
+        let file = SaveFile {
+            metadata: metadata.clone(),
+            thumbnail,
+            world: world.as_scene_bytes()?,
+        };
+        let payload = bincode::serialize(&file)?;
+
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend_from_slice(&Self::VERSION.to_le_bytes());
+        bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+        bytes.extend(payload);
+
+        fs::create_dir_all(&self.directory)?;
+        let path = self.slot_path(slot, Self::EXTENSION);
+        let temporary_path = self.slot_path(slot, "save.tmp");
+        {
+            let mut temporary_file = File::create(&temporary_path)?;
+            temporary_file.write_all(&bytes)?;
+            temporary_file.sync_all()?;
+        }
+        if path.exists() {
+            fs::copy(&path, self.slot_path(slot, Self::BACKUP_EXTENSION))?;
+        }
+        fs::rename(&temporary_path, &path)
+            .context(format!("Failed to write save: {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn load(&self, slot: u32) -> Result<(World, SaveMetadata)> {
+        let file = self.read(slot)?;
+        Ok((World::from_scene_bytes(&file.world)?, file.metadata))
+    }
+
+    pub fn metadata(&self, slot: u32) -> Result<SaveMetadata> {
+        Ok(self.read(slot)?.metadata)
+    }
+
+    pub fn thumbnail(&self, slot: u32) -> Result<Option<RgbaImage>> {
+        match self.read(slot)?.thumbnail {
+            Some(bytes) => Ok(Some(image::load_from_memory(&bytes)?.to_rgba8())),
+            None => Ok(None),
+        }
+    }
+
+    /// Every slot with a save, in order
+    pub fn occupied_slots(&self) -> Result<Vec<u32>> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let mut slots = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            let slot = name
+                .strip_prefix("slot_")
+                .and_then(|name| name.strip_suffix(&format!(".{}", Self::EXTENSION)))
+                .and_then(|slot| slot.parse().ok());
+            if let Some(slot) = slot {
+                slots.push(slot);
+            }
+        }
+        slots.sort_unstable();
+        Ok(slots)
+    }
+
+    pub fn delete(&self, slot: u32) -> Result<()> {
+        for extension in [Self::EXTENSION, Self::BACKUP_EXTENSION] {
+            let path = self.slot_path(slot, extension);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, slot: u32) -> Result<SaveFile> {
+        let path = self.slot_path(slot, Self::EXTENSION);
+        let error = match read_save_file(&path) {
+            Ok(file) => return Ok(file),
+            Err(error) => error,
+        };
+        let backup_path = self.slot_path(slot, Self::BACKUP_EXTENSION);
+        if !backup_path.exists() {
+            return Err(error);
+        }
+        log::warn!(
+            "Failed to read save {}, loading its backup instead: {}",
+            path.display(),
+            error
+        );
+        read_save_file(&backup_path)
+    }
+
+    fn slot_path(&self, slot: u32, extension: &str) -> PathBuf {
+        self.directory.join(format!("slot_{}.{}", slot, extension))
+    }
+}
+
+fn read_save_file(path: &Path) -> Result<SaveFile> {
+    let bytes = fs::read(path).context(format!("Failed to read save: {}", path.display()))?;
+    let header_length = SaveSlots::MAGIC.len() + 4 + 8;
+    if bytes.len() < header_length || !bytes.starts_with(SaveSlots::MAGIC) {
+        bail!("{} is not a phantom save!", path.display());
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&bytes[SaveSlots::MAGIC.len()..SaveSlots::MAGIC.len() + 4]);
+    let version = u32::from_le_bytes(version);
+    if version != SaveSlots::VERSION {
+        bail!(
+            "The save is version {} but only version {} can be loaded!",
+            version,
+            SaveSlots::VERSION
+        );
+    }
+    let mut expected_checksum = [0; 8];
+    expected_checksum.copy_from_slice(&bytes[SaveSlots::MAGIC.len() + 4..header_length]);
+    let payload = &bytes[header_length..];
+    if checksum(payload) != u64::from_le_bytes(expected_checksum) {
+        bail!("{} is corrupted!", path.display());
+    }
+    Ok(bincode::deserialize(payload)?)
+}
+
+fn encode_thumbnail(image: &RgbaImage) -> Result<Vec<u8>> {
+    let width = SaveSlots::THUMBNAIL_WIDTH.min(image.width()).max(1);
+    let height = (image.height() as f32 * width as f32 / image.width().max(1) as f32)
+        .round()
+        .max(1.0) as u32;
+    let thumbnail = imageops::resize(image, width, height, FilterType::Triangle);
+    let mut bytes = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(thumbnail).write_to(&mut bytes, ImageOutputFormat::Png)?;
+    Ok(bytes.into_inner())
+}
+
+/// FNV-1a, which is enough to catch truncated or partially written saves
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
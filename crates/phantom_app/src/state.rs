@@ -1,9 +1,11 @@
-use crate::Resources;
+use crate::{AppEvent, Resources};
 use phantom_dependencies::{
     anyhow::{Context, Result},
     gilrs::Event as GilrsEvent,
-    winit::event::{ElementState, Event, KeyboardInput, MouseButton},
+    winit::event::{ElementState, Event, KeyboardInput, MouseButton, Touch},
 };
+use phantom_platform::RichPresence;
+use phantom_world::World;
 use std::path::PathBuf;
 
 pub struct EmptyState {}
@@ -30,6 +32,12 @@ pub trait State {
         Ok(Transition::None)
     }
 
+    /// Called at the tick rate from the app config, before the frame's `update`.
+    /// Deterministic logic such as physics belongs here.
+    fn fixed_update(&mut self, _resources: &mut Resources) -> Result<Transition> {
+        Ok(Transition::None)
+    }
+
     fn update_gui(&mut self, _resources: &mut Resources) -> Result<Transition> {
         Ok(Transition::None)
     }
@@ -55,6 +63,10 @@ pub trait State {
         Ok(Transition::None)
     }
 
+    fn on_touch(&mut self, _resources: &mut Resources, _touch: &Touch) -> Result<Transition> {
+        Ok(Transition::None)
+    }
+
     fn on_gamepad_event(
         &mut self,
         _resources: &mut Resources,
@@ -66,129 +78,306 @@ pub trait State {
     fn on_event(&mut self, _resources: &mut Resources, _event: &Event<()>) -> Result<Transition> {
         Ok(Transition::None)
     }
+
+    /// The world that entities tracked in this state's scope are removed from when it stops
+    fn world(&mut self) -> Option<&mut World> {
+        None
+    }
+
+    /// Whether input events stop here instead of also reaching the states beneath this overlay
+    fn blocks_input(&self) -> bool {
+        false
+    }
+
+    /// Receives events sent through the `EventProxy`, such as results from background threads
+    fn on_user_event(
+        &mut self,
+        _resources: &mut Resources,
+        _event: &AppEvent,
+    ) -> Result<Transition> {
+        Ok(Transition::None)
+    }
+
+    /// Published to platforms such as discord when this state becomes the current state
+    fn rich_presence(&self) -> Option<RichPresence> {
+        None
+    }
 }
 
 pub enum Transition {
     None,
+
+    /// Stops the state that requested it, along with any overlays above it
     Pop,
+
+    /// Pauses the running states and starts a new state above them
     Push(Box<dyn State>),
+
+    /// Starts a state that runs on top of the running states without pausing them,
+    /// such as a HUD or notifications
+    PushOverlay(Box<dyn State>),
+
+    /// Replaces the state that requested it, stopping any overlays above it
     Switch(Box<dyn State>),
+
     Quit,
 }
 
+struct StackEntry {
+    state: Box<dyn State>,
+    overlay: bool,
+}
+
+/// A stack of states where the topmost regular state runs along with any overlays above it.
+/// Updates run from the bottom of the running states up, so overlays draw over what they cover.
+/// Input events are routed from the top down until a state that blocks input receives them.
+/// Only the first transition requested during a pass is applied.
 pub struct StateMachine {
     running: bool,
-    states: Vec<Box<dyn State>>,
+    states: Vec<StackEntry>,
 }
 
 impl StateMachine {
     pub fn new(initial_state: impl State + 'static) -> Self {
         Self {
             running: false,
-            states: vec![Box::new(initial_state)],
+            states: vec![StackEntry {
+                state: Box::new(initial_state),
+                overlay: false,
+            }],
         }
     }
 
-    // TODO: Make this private, states should have passthrough methods
-    pub fn current_state(&mut self) -> Result<&mut Box<(dyn State + 'static)>> {
+    fn current_state(&mut self) -> Result<&mut Box<(dyn State + 'static)>> {
         self.states
             .last_mut()
+            .map(|entry| &mut entry.state)
             .context("Tried to access state in state machine with no states present!")
     }
 
+    /// The index of the topmost regular state, which runs along with the overlays above it
+    fn first_running(&self) -> usize {
+        self.states
+            .iter()
+            .rposition(|entry| !entry.overlay)
+            .unwrap_or_default()
+    }
+
     pub fn is_running(&self) -> bool {
         self.running
     }
 
     pub fn start(&mut self, resources: &mut Resources) -> Result<()> {
         if !self.running {
+            resources.scopes.push();
             let state = self.current_state()?;
             state.on_start(resources)?;
             self.running = true;
+            self.update_rich_presence(resources);
         }
         Ok(())
     }
 
     pub fn handle_event(&mut self, resources: &mut Resources, event: &Event<()>) -> Result<()> {
-        if self.running {
-            let transition = match self.states.last_mut() {
-                Some(state) => state.on_event(resources, &event)?,
-                None => Transition::None,
-            };
-            self.transition(transition, resources)?;
-        }
-        Ok(())
+        self.route_input(resources, |state, resources| {
+            state.on_event(resources, event)
+        })
+    }
+
+    /// The worlds of the running states, for settings that apply to every simulated world
+    pub fn running_worlds(&mut self) -> Vec<&mut World> {
+        let first_running = self.first_running();
+        self.states[first_running..]
+            .iter_mut()
+            .filter_map(|entry| entry.state.world())
+            .collect()
     }
 
     pub fn update(&mut self, resources: &mut Resources) -> Result<()> {
-        if self.running {
-            let transition = match self.states.last_mut() {
-                Some(state) => state.update(resources)?,
-                None => Transition::None,
-            };
-            self.transition(transition, resources)?;
+        self.run(resources, |state, resources| state.update(resources))
+    }
+
+    pub fn fixed_update(&mut self, resources: &mut Resources) -> Result<()> {
+        self.run(resources, |state, resources| state.fixed_update(resources))
+    }
+
+    pub fn update_gui(&mut self, resources: &mut Resources) -> Result<()> {
+        self.run(resources, |state, resources| state.update_gui(resources))
+    }
+
+    pub fn on_file_dropped(&mut self, resources: &mut Resources, path: &PathBuf) -> Result<()> {
+        self.route_input(resources, |state, resources| {
+            state.on_file_dropped(resources, path)
+        })
+    }
+
+    pub fn on_mouse(
+        &mut self,
+        resources: &mut Resources,
+        button: &MouseButton,
+        button_state: &ElementState,
+    ) -> Result<()> {
+        self.route_input(resources, |state, resources| {
+            state.on_mouse(resources, button, button_state)
+        })
+    }
+
+    pub fn on_key(&mut self, resources: &mut Resources, input: KeyboardInput) -> Result<()> {
+        self.route_input(resources, |state, resources| state.on_key(resources, input))
+    }
+
+    pub fn on_touch(&mut self, resources: &mut Resources, touch: &Touch) -> Result<()> {
+        self.route_input(resources, |state, resources| {
+            state.on_touch(resources, touch)
+        })
+    }
+
+    pub fn on_gamepad_event(&mut self, resources: &mut Resources, event: GilrsEvent) -> Result<()> {
+        self.route_input(resources, |state, resources| {
+            state.on_gamepad_event(resources, event)
+        })
+    }
+
+    /// Delivers an app event to every running state, even below overlays that block input
+    pub fn on_user_event(&mut self, resources: &mut Resources, event: &AppEvent) -> Result<()> {
+        self.run(resources, |state, resources| {
+            state.on_user_event(resources, event)
+        })
+    }
+
+    /// Runs an action on each running state from the bottom up
+    fn run(
+        &mut self,
+        resources: &mut Resources,
+        mut action: impl FnMut(&mut dyn State, &mut Resources) -> Result<Transition>,
+    ) -> Result<()> {
+        if !self.running {
+            return Ok(());
+        }
+        for index in self.first_running()..self.states.len() {
+            let transition = action(self.states[index].state.as_mut(), resources)?;
+            if !matches!(transition, Transition::None) {
+                return self.apply(index, transition, resources);
+            }
         }
         Ok(())
     }
 
-    // TODO: Generalize this so it takes an action that returns a transition
-    pub fn update_gui(&mut self, resources: &mut Resources) -> Result<()> {
-        if self.running {
-            let transition = match self.states.last_mut() {
-                Some(state) => state.update_gui(resources)?,
-                None => Transition::None,
-            };
-            self.transition(transition, resources)?;
+    /// Runs an action on each running state from the top down,
+    /// stopping at the first state that blocks input
+    fn route_input(
+        &mut self,
+        resources: &mut Resources,
+        mut action: impl FnMut(&mut dyn State, &mut Resources) -> Result<Transition>,
+    ) -> Result<()> {
+        if !self.running {
+            return Ok(());
+        }
+        for index in (self.first_running()..self.states.len()).rev() {
+            let state = self.states[index].state.as_mut();
+            let transition = action(state, resources)?;
+            let blocks_input = state.blocks_input();
+            if !matches!(transition, Transition::None) {
+                return self.apply(index, transition, resources);
+            }
+            if blocks_input {
+                break;
+            }
         }
         Ok(())
     }
 
+    /// Applies a transition as if the topmost state requested it
     pub fn transition(&mut self, request: Transition, resources: &mut Resources) -> Result<()> {
+        match self.states.len().checked_sub(1) {
+            Some(index) => self.apply(index, request, resources),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies a transition requested by the state at the index
+    fn apply(
+        &mut self,
+        index: usize,
+        request: Transition,
+        resources: &mut Resources,
+    ) -> Result<()> {
         if self.running {
+            let changed = !matches!(request, Transition::None);
             match request {
                 Transition::None => (),
-                Transition::Pop => self.pop(resources)?,
-                Transition::Push(state) => self.push(state, resources)?,
-                Transition::Switch(state) => self.switch(state, resources)?,
+                Transition::Pop => self.pop(index, resources)?,
+                Transition::Push(state) => self.push(state, false, resources)?,
+                Transition::PushOverlay(state) => self.push(state, true, resources)?,
+                Transition::Switch(state) => self.switch(index, state, resources)?,
                 Transition::Quit => self.stop(resources)?,
             }
+            if changed {
+                self.update_rich_presence(resources);
+            }
         }
         Ok(())
     }
 
-    fn switch(&mut self, state: Box<dyn State>, resources: &mut Resources) -> Result<()> {
+    fn update_rich_presence(&self, resources: &mut Resources) {
+        if let Some(presence) = self.states[self.first_running()..]
+            .iter()
+            .rev()
+            .find_map(|entry| entry.state.rich_presence())
+        {
+            resources.platform.set_presence(&presence);
+        }
+    }
+
+    fn switch(
+        &mut self,
+        index: usize,
+        state: Box<dyn State>,
+        resources: &mut Resources,
+    ) -> Result<()> {
         if self.running {
-            if let Some(mut state) = self.states.pop() {
-                state.on_stop(resources)?;
-            }
-            self.states.push(state);
+            let overlay = self.states.get(index).map_or(false, |entry| entry.overlay);
+            self.truncate(index, resources)?;
+            self.states.push(StackEntry { state, overlay });
+            resources.scopes.push();
             let new_state = self.current_state()?;
             new_state.on_start(resources)?;
         }
         Ok(())
     }
 
-    fn push(&mut self, state: Box<dyn State>, resources: &mut Resources) -> Result<()> {
+    fn push(
+        &mut self,
+        state: Box<dyn State>,
+        overlay: bool,
+        resources: &mut Resources,
+    ) -> Result<()> {
         if self.running {
-            if let Ok(state) = self.current_state() {
-                state.on_pause(resources)?;
+            if !overlay {
+                let first_running = self.first_running();
+                for entry in self.states[first_running..].iter_mut() {
+                    entry.state.on_pause(resources)?;
+                }
             }
-            self.states.push(state);
+            self.states.push(StackEntry { state, overlay });
+            resources.scopes.push();
             let new_state = self.current_state()?;
             new_state.on_start(resources)?;
         }
         Ok(())
     }
 
-    fn pop(&mut self, resources: &mut Resources) -> Result<()> {
+    fn pop(&mut self, index: usize, resources: &mut Resources) -> Result<()> {
         if self.running {
-            if let Some(mut state) = self.states.pop() {
-                state.on_stop(resources)?;
-            }
-            if let Some(state) = self.states.last_mut() {
-                state.on_resume(resources)?;
-            } else {
+            let overlay = self.states.get(index).map_or(false, |entry| entry.overlay);
+            self.truncate(index, resources)?;
+            if self.states.is_empty() {
                 self.running = false;
+            } else if !overlay {
+                let first_running = self.first_running();
+                for entry in self.states[first_running..].iter_mut() {
+                    entry.state.on_resume(resources)?;
+                }
             }
         }
         Ok(())
@@ -196,11 +385,28 @@ impl StateMachine {
 
     pub fn stop(&mut self, resources: &mut Resources) -> Result<()> {
         if self.running {
-            while let Some(mut state) = self.states.pop() {
-                state.on_stop(resources)?;
-            }
+            self.truncate(0, resources)?;
             self.running = false;
         }
         Ok(())
     }
+
+    /// Stops the state at the index and everything above it, from the top down
+    fn truncate(&mut self, index: usize, resources: &mut Resources) -> Result<()> {
+        while self.states.len() > index {
+            if let Some(entry) = self.states.pop() {
+                Self::stop_state(entry.state, resources)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops a state that has been taken off the stack and cleans up its scope
+    fn stop_state(mut state: Box<dyn State>, resources: &mut Resources) -> Result<()> {
+        let stopped = state.on_stop(resources);
+        if let Some(scope) = resources.scopes.pop() {
+            scope.cleanup(resources, state.world());
+        }
+        stopped
+    }
 }
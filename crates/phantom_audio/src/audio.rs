@@ -0,0 +1,466 @@
+use crate::{Mixer, EFFECTS_GROUP, MUSIC_GROUP};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::IntoQuery,
+    log, nalgebra_glm as glm,
+    rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source, SpatialSink},
+};
+use phantom_world::{AssetId, AudioSource, Entity, Transform, World};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, Cursor},
+    path::Path,
+    sync::Arc,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+enum Output {
+    Flat(Sink),
+    Spatial(SpatialSink),
+}
+
+impl Output {
+    fn set_volume(&self, volume: f32) {
+        match self {
+            Self::Flat(sink) => sink.set_volume(volume),
+            Self::Spatial(sink) => sink.set_volume(volume),
+        }
+    }
+
+    fn set_paused(&self, paused: bool) {
+        match (self, paused) {
+            (Self::Flat(sink), true) => sink.pause(),
+            (Self::Flat(sink), false) => sink.play(),
+            (Self::Spatial(sink), true) => sink.pause(),
+            (Self::Spatial(sink), false) => sink.play(),
+        }
+    }
+
+    fn stop(&self) {
+        match self {
+            Self::Flat(sink) => sink.stop(),
+            Self::Spatial(sink) => sink.stop(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            Self::Flat(sink) => sink.empty(),
+            Self::Spatial(sink) => sink.empty(),
+        }
+    }
+}
+
+/// Where a spatialized sound is emitted from
+#[derive(Debug, Copy, Clone)]
+struct Emitter {
+    position: glm::Vec3,
+
+    /// Distance at which the sound is fully attenuated
+    range: f32,
+}
+
+struct Sound {
+    output: Output,
+    group: String,
+    volume: f32,
+    emitter: Option<Emitter>,
+
+    /// Whether the game paused the sound, which it stays after the app is resumed
+    paused: bool,
+}
+
+/// Plays clips, emitters attached to entities and streamed music.
+/// When no output device is available, sounds are accepted but nothing is heard.
+pub struct Audio {
+    pub mixer: Mixer,
+
+    /// The mixer group that emitters attached to entities play through
+    pub emitter_group: String,
+
+    stream: Option<(OutputStream, OutputStreamHandle)>,
+    clips: HashMap<String, Arc<[u8]>>,
+
+    /// The names clips loaded for assets are played by
+    clip_assets: HashMap<AssetId, String>,
+
+    /// Assets that couldn't be decoded, so they aren't requested again every frame
+    failed_clip_assets: HashSet<AssetId>,
+    sounds: HashMap<SoundHandle, Sound>,
+    emitters: HashMap<Entity, SoundHandle>,
+    music: Option<SoundHandle>,
+    listener: Transform,
+    started_clips: Vec<String>,
+    next_handle: u64,
+
+    /// Every sound is held paused while the app is suspended
+    suspended: bool,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        let stream = match OutputStream::try_default() {
+            Ok(stream) => Some(stream),
+            Err(error) => {
+                log::warn!(
+                    "No audio output is available, sounds will not play: {}",
+                    error
+                );
+                None
+            }
+        };
+        Self {
+            mixer: Mixer::default(),
+            emitter_group: EFFECTS_GROUP.to_string(),
+            stream,
+            clips: HashMap::new(),
+            clip_assets: HashMap::new(),
+            failed_clip_assets: HashSet::new(),
+            sounds: HashMap::new(),
+            emitters: HashMap::new(),
+            music: None,
+            listener: Transform::default(),
+            started_clips: Vec::new(),
+            next_handle: 0,
+            suspended: false,
+        }
+    }
+}
+
+impl Audio {
+    /// Distance of each ear from the center of the listener
+    const EAR_OFFSET: f32 = 0.1;
+
+    pub fn is_available(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Loads a wav or ogg clip that is played by name
+    pub fn load_clip(&mut self, name: &str, bytes: Vec<u8>) -> Result<()> {
+        let data: Arc<[u8]> = bytes.into();
+        Decoder::new(Cursor::new(data.clone()))
+            .context(format!("Failed to decode audio clip '{}'", name))?;
+        self.clips.insert(name.to_string(), data);
+        Ok(())
+    }
+
+    pub fn load_clip_file(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        self.load_clip(name, std::fs::read(path)?)
+    }
+
+    /// Loads the clip for an asset, which audio sources referring to the asset then play
+    pub fn load_clip_asset(&mut self, id: AssetId, name: &str, bytes: Vec<u8>) -> Result<()> {
+        if let Err(error) = self.load_clip(name, bytes) {
+            self.failed_clip_assets.insert(id);
+            return Err(error);
+        }
+        self.clip_assets.insert(id, name.to_string());
+        Ok(())
+    }
+
+    /// The clip assets audio sources in the world refer to that haven't been loaded yet
+    pub fn missing_clip_assets(&self, world: &World) -> Vec<AssetId> {
+        let mut missing = <&AudioSource>::query()
+            .iter(&world.ecs)
+            .filter_map(|source| source.clip)
+            .filter(|id| {
+                !self.clip_assets.contains_key(id) && !self.failed_clip_assets.contains(id)
+            })
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|id| id.0);
+        missing.dedup();
+        missing
+    }
+
+    pub fn unload_clip(&mut self, name: &str) {
+        self.clips.remove(name);
+    }
+
+    pub fn has_clip(&self, name: &str) -> bool {
+        self.clips.contains_key(name)
+    }
+
+    /// Plays a loaded clip without spatialization, such as for ui sounds
+    pub fn play(&mut self, clip: &str, group: &str, looping: bool) -> Result<SoundHandle> {
+        let output = match self.stream.as_ref() {
+            Some((_, handle)) => {
+                let sink = Sink::try_new(handle)?;
+                append_clip(&self.clips, clip, looping, |source| sink.append(source))?;
+                Some(Output::Flat(sink))
+            }
+            None => None,
+        };
+        Ok(self.add_sound(clip, output, group, None))
+    }
+
+    /// Plays a loaded clip from a position in the world
+    pub fn play_at(
+        &mut self,
+        clip: &str,
+        group: &str,
+        position: glm::Vec3,
+        range: f32,
+        looping: bool,
+    ) -> Result<SoundHandle> {
+        let output = match self.stream.as_ref() {
+            Some((_, handle)) => {
+                let sink = SpatialSink::try_new(handle, [0.0; 3], [0.0; 3], [0.0; 3])?;
+                append_clip(&self.clips, clip, looping, |source| sink.append(source))?;
+                Some(Output::Spatial(sink))
+            }
+            None => None,
+        };
+        let emitter = Emitter { position, range };
+        Ok(self.add_sound(clip, output, group, Some(emitter)))
+    }
+
+    /// Streams music from a file instead of loading it into memory, replacing the current track
+    pub fn play_music(&mut self, path: impl AsRef<Path>, looping: bool) -> Result<SoundHandle> {
+        self.stop_music();
+        let path = path.as_ref();
+        let output = match self.stream.as_ref() {
+            Some((_, handle)) => {
+                let sink = Sink::try_new(handle)?;
+                let reader = BufReader::new(File::open(path)?);
+                if looping {
+                    sink.append(Decoder::new_looped(reader)?);
+                } else {
+                    sink.append(Decoder::new(reader)?);
+                }
+                Some(Output::Flat(sink))
+            }
+            None => None,
+        };
+        let name = path.display().to_string();
+        let handle = self.add_sound(&name, output, MUSIC_GROUP, None);
+        self.music = Some(handle);
+        Ok(handle)
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(handle) = self.music.take() {
+            self.stop(handle);
+        }
+    }
+
+    pub fn music(&self) -> Option<SoundHandle> {
+        self.music
+    }
+
+    pub fn stop(&mut self, handle: SoundHandle) {
+        if let Some(sound) = self.sounds.remove(&handle) {
+            sound.output.stop();
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        for (_, sound) in self.sounds.drain() {
+            sound.output.stop();
+        }
+        self.music = None;
+    }
+
+    pub fn set_paused(&mut self, handle: SoundHandle, paused: bool) {
+        let suspended = self.suspended;
+        if let Some(sound) = self.sounds.get_mut(&handle) {
+            sound.paused = paused;
+            sound.output.set_paused(paused || suspended);
+        }
+    }
+
+    pub fn is_paused(&self, handle: SoundHandle) -> bool {
+        self.sounds
+            .get(&handle)
+            .map(|sound| sound.paused)
+            .unwrap_or(false)
+    }
+
+    /// Holds every sound paused while the app is suspended.
+    /// Resuming only plays the sounds that weren't paused with `set_paused`.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+        for sound in self.sounds.values() {
+            sound.output.set_paused(sound.paused || suspended);
+        }
+    }
+
+    pub fn set_volume(&mut self, handle: SoundHandle, volume: f32) {
+        if let Some(sound) = self.sounds.get_mut(&handle) {
+            sound.volume = volume.max(0.0);
+        }
+        self.update_sound(handle);
+    }
+
+    pub fn set_position(&mut self, handle: SoundHandle, position: glm::Vec3) {
+        if let Some(emitter) = self
+            .sounds
+            .get_mut(&handle)
+            .and_then(|sound| sound.emitter.as_mut())
+        {
+            emitter.position = position;
+        }
+        self.update_sound(handle);
+    }
+
+    pub fn is_playing(&self, handle: SoundHandle) -> bool {
+        self.sounds.contains_key(&handle)
+    }
+
+    /// Clips started since this was last called, for showing their subtitles
+    pub fn take_started_clips(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.started_clips)
+    }
+
+    /// Moves the listener to the active camera, starts and moves emitters for
+    /// entities with an `AudioSource`, and applies the mixer volumes
+    pub fn update(&mut self, world: &World) -> Result<()> {
+        if let Ok(camera) = world.active_camera() {
+            self.listener = world.entity_global_transform(camera)?;
+        }
+        self.update_emitters(world)?;
+
+        let handles = self.sounds.keys().copied().collect::<Vec<_>>();
+        for handle in handles {
+            self.update_sound(handle);
+        }
+
+        let finished = self
+            .sounds
+            .iter()
+            .filter(|(_, sound)| sound.output.is_finished())
+            .map(|(handle, _)| *handle)
+            .collect::<Vec<_>>();
+        for handle in finished {
+            self.sounds.remove(&handle);
+            if self.music == Some(handle) {
+                self.music = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_emitters(&mut self, world: &World) -> Result<()> {
+        let mut query = <(Entity, &AudioSource)>::query();
+        let sources = query
+            .iter(&world.ecs)
+            .map(|(entity, source)| (*entity, source.clone()))
+            .collect::<Vec<_>>();
+
+        let removed = self
+            .emitters
+            .keys()
+            .filter(|entity| !sources.iter().any(|(source, _)| source == *entity))
+            .copied()
+            .collect::<Vec<_>>();
+        for entity in removed {
+            if let Some(handle) = self.emitters.remove(&entity) {
+                self.stop(handle);
+            }
+        }
+
+        for (entity, source) in sources {
+            let position = world.entity_global_transform(entity)?.translation;
+            let clip = source
+                .clip
+                .and_then(|id| self.clip_assets.get(&id))
+                .filter(|clip| self.has_clip(clip))
+                .cloned();
+            match (self.emitters.get(&entity).copied(), clip) {
+                // One-shot emitters stay registered after finishing so they don't replay
+                (Some(handle), _) => self.set_position(handle, position),
+                (None, Some(clip)) => {
+                    let group = self.emitter_group.clone();
+                    let handle =
+                        self.play_at(&clip, &group, position, source.range, source.looping)?;
+                    self.set_volume(handle, source.volume);
+                    self.emitters.insert(entity, handle);
+                }
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_sound(
+        &mut self,
+        clip: &str,
+        output: Option<Output>,
+        group: &str,
+        emitter: Option<Emitter>,
+    ) -> SoundHandle {
+        let handle = SoundHandle(self.next_handle);
+        self.next_handle += 1;
+        self.started_clips.push(clip.to_string());
+        if let Some(output) = output {
+            let sound = Sound {
+                output,
+                group: group.to_string(),
+                volume: 1.0,
+                emitter,
+                paused: false,
+            };
+            if self.suspended {
+                sound.output.set_paused(true);
+            }
+            self.sounds.insert(handle, sound);
+        }
+        self.update_sound(handle);
+        handle
+    }
+
+    fn update_sound(&self, handle: SoundHandle) {
+        let sound = match self.sounds.get(&handle) {
+            Some(sound) => sound,
+            None => return,
+        };
+        let mut volume = self.mixer.volume(&sound.group) * sound.volume;
+
+        if let (Output::Spatial(sink), Some(emitter)) = (&sound.output, sound.emitter) {
+            let listener = self.listener.translation;
+            let offset = emitter.position - listener;
+            let distance = glm::length(&offset);
+            if emitter.range > 0.0 {
+                volume *= (1.0 - distance / emitter.range).clamp(0.0, 1.0);
+            }
+
+            // Attenuation is applied through the volume above, so the emitter is
+            // placed a unit away from the listener and only decides the panning
+            let direction = if distance > f32::EPSILON {
+                offset / distance
+            } else {
+                self.listener.forward()
+            };
+            let ear = self.listener.right() * Self::EAR_OFFSET;
+            sink.set_emitter_position(as_array(&(listener + direction)));
+            sink.set_left_ear_position(as_array(&(listener - ear)));
+            sink.set_right_ear_position(as_array(&(listener + ear)));
+        }
+
+        sound.output.set_volume(volume);
+    }
+}
+
+fn append_clip(
+    clips: &HashMap<String, Arc<[u8]>>,
+    clip: &str,
+    looping: bool,
+    append: impl FnOnce(Box<dyn Source<Item = i16> + Send>),
+) -> Result<()> {
+    let data = clips
+        .get(clip)
+        .context(format!("Audio clip '{}' has not been loaded", clip))?;
+    let reader = Cursor::new(data.clone());
+    if looping {
+        append(Box::new(Decoder::new_looped(reader)?));
+    } else {
+        append(Box::new(Decoder::new(reader)?));
+    }
+    Ok(())
+}
+
+fn as_array(vector: &glm::Vec3) -> [f32; 3] {
+    [vector.x, vector.y, vector.z]
+}
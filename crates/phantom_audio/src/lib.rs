@@ -0,0 +1,4 @@
+mod audio;
+mod mixer;
+
+pub use self::{audio::*, mixer::*};
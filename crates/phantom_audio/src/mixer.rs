@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+pub const MUSIC_GROUP: &str = "music";
+pub const EFFECTS_GROUP: &str = "effects";
+pub const VOICE_GROUP: &str = "voice";
+
+/// Volumes applied on top of each sound's own volume, so players can
+/// turn down music separately from effects and voice
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    pub master: f32,
+    groups: BTreeMap<String, f32>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        let groups = [MUSIC_GROUP, EFFECTS_GROUP, VOICE_GROUP]
+            .iter()
+            .map(|group| (group.to_string(), 1.0))
+            .collect();
+        Self {
+            master: 1.0,
+            groups,
+        }
+    }
+}
+
+impl Mixer {
+    /// Groups that were never set play at full volume
+    pub fn group_volume(&self, group: &str) -> f32 {
+        self.groups.get(group).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_group_volume(&mut self, group: &str, volume: f32) {
+        self.groups.insert(group.to_string(), volume.max(0.0));
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.groups
+            .iter()
+            .map(|(group, volume)| (group.as_str(), *volume))
+    }
+
+    /// The master volume combined with the group's volume
+    pub fn volume(&self, group: &str) -> f32 {
+        self.master.max(0.0) * self.group_volume(group)
+    }
+}
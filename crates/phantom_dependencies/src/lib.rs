@@ -2,6 +2,9 @@ pub use anyhow;
 pub use bincode;
 pub use bmfont;
 pub use bytemuck;
+pub use csv;
+#[cfg(feature = "discord")]
+pub use discord_rich_presence;
 pub use egui;
 pub use egui_wgpu_backend;
 pub use egui_winit_platform;
@@ -15,10 +18,22 @@ pub use legion;
 pub use log;
 pub use nalgebra;
 pub use nalgebra_glm;
+#[cfg(target_os = "android")]
+pub use ndk_glue;
+pub use notify;
 pub use petgraph;
 pub use pollster;
 pub use rapier3d;
 pub use raw_window_handle;
+pub use rayon;
+pub use rodio;
+pub use ron;
 pub use serde;
+pub use serde_json;
+#[cfg(feature = "steam")]
+pub use steamworks;
+pub use tungstenite;
+pub use uuid;
 pub use wgpu;
 pub use winit;
+pub use zip;
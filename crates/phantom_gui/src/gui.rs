@@ -1,8 +1,14 @@
+use crate::{GamepadNavigation, InputMode, SubtitleManager, SubtitleStyle, VirtualKeyboard};
 use phantom_dependencies::{
-    egui::{epaint::ClippedMesh, CtxRef, FontDefinitions},
+    egui::{epaint::ClippedMesh, CtxRef, CursorIcon, FontDefinitions, Style},
     egui_winit_platform::{Platform, PlatformDescriptor},
     epi::{self, backend::FrameData},
-    winit::{dpi::PhysicalSize, event::Event, window::Window},
+    gilrs::EventType,
+    winit::{
+        dpi::PhysicalSize,
+        event::{Event, WindowEvent},
+        window::Window,
+    },
 };
 use std::{sync::Arc, time::Instant};
 
@@ -23,6 +29,16 @@ pub struct Gui {
     start_time: Instant,
     last_frame_start: Instant,
     previous_frame_time: Option<f32>,
+    pub gamepad_navigation: GamepadNavigation,
+    pub virtual_keyboard: VirtualKeyboard,
+    pub input_mode: InputMode,
+    pub subtitle_style: SubtitleStyle,
+    pub subtitles: SubtitleManager,
+    ui_scale: f32,
+
+    /// The fonts at a scale of one, which the ui scale is applied to
+    fonts: FontDefinitions,
+    cursor_icon: CursorIcon,
 }
 
 impl Gui {
@@ -41,6 +57,14 @@ impl Gui {
             start_time: Instant::now(),
             previous_frame_time: None,
             last_frame_start: Instant::now(),
+            gamepad_navigation: GamepadNavigation::default(),
+            virtual_keyboard: VirtualKeyboard::default(),
+            input_mode: InputMode::default(),
+            subtitle_style: SubtitleStyle::default(),
+            subtitles: SubtitleManager::default(),
+            ui_scale: 1.0,
+            fonts: FontDefinitions::default(),
+            cursor_icon: CursorIcon::Default,
         }
     }
 
@@ -49,9 +73,77 @@ impl Gui {
     }
 
     pub fn handle_event(&mut self, event: &Event<()>) {
+        if let Event::WindowEvent { event, .. } = event {
+            match event {
+                WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. } => {
+                    self.input_mode = InputMode::KeyboardAndMouse
+                }
+                WindowEvent::Touch(_) => self.input_mode = InputMode::Touch,
+                _ => {}
+            }
+        }
         self.platform.handle_event(&event);
     }
 
+    /// Lets gamepads move the focus between widgets, so menus work without a mouse
+    pub fn handle_gamepad_event(&mut self, event: &EventType) {
+        if let EventType::ButtonPressed(..) = event {
+            self.input_mode = InputMode::Gamepad;
+        }
+        if let Some(navigation) = self.gamepad_navigation.handle_event(event) {
+            self.input_mode = InputMode::Gamepad;
+            self.platform
+                .raw_input_mut()
+                .events
+                .push(navigation.as_key_event());
+        }
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// Replaces the gui's fonts, which are sized by the current ui scale.
+    /// Setting fonts on the context directly loses them the next time the ui scale changes.
+    pub fn set_fonts(&mut self, fonts: FontDefinitions) {
+        self.fonts = fonts;
+        self.set_ui_scale(self.ui_scale);
+    }
+
+    /// Scales text and widget spacing, for players who need a larger interface
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        let mut fonts = self.fonts.clone();
+        fonts
+            .family_and_size
+            .values_mut()
+            .for_each(|(_family, size)| *size *= scale);
+
+        let default_spacing = Style::default().spacing;
+        let context = self.context();
+        let mut style = (*context.style()).clone();
+        let spacing = &mut style.spacing;
+        spacing.item_spacing = default_spacing.item_spacing * scale;
+        spacing.window_padding = default_spacing.window_padding * scale;
+        spacing.button_padding = default_spacing.button_padding * scale;
+        spacing.indent = default_spacing.indent * scale;
+        spacing.interact_size = default_spacing.interact_size * scale;
+        spacing.slider_width = default_spacing.slider_width * scale;
+        spacing.text_edit_width = default_spacing.text_edit_width * scale;
+        spacing.icon_width = default_spacing.icon_width * scale;
+        spacing.icon_spacing = default_spacing.icon_spacing * scale;
+        spacing.tooltip_width = default_spacing.tooltip_width * scale;
+        spacing.scroll_bar_width = default_spacing.scroll_bar_width * scale;
+
+        context.set_fonts(fonts);
+        context.set_style(style);
+        self.ui_scale = scale;
+    }
+
+    /// Shows a line of dialogue this frame, styled by the subtitle accessibility settings
+    pub fn show_subtitle(&self, speaker: Option<&str>, text: &str) {
+        self.subtitle_style.show(&self.context(), speaker, text);
+    }
+
     pub fn context(&self) -> CtxRef {
         self.platform.context()
     }
@@ -60,7 +152,11 @@ impl Gui {
         self.platform
             .update_time(self.start_time.elapsed().as_secs_f64());
 
+        let delta_time = self.last_frame_start.elapsed().as_secs_f32();
+        self.subtitles.update(delta_time);
         self.last_frame_start = Instant::now();
+        let keyboard_events = self.virtual_keyboard.take_events();
+        self.platform.raw_input_mut().events.extend(keyboard_events);
         self.platform.begin_frame();
         let app_output = epi::backend::AppOutput::default();
 
@@ -77,8 +173,17 @@ impl Gui {
         }
     }
 
+    /// The cursor the gui's widgets asked for in the last frame,
+    /// which is `CursorIcon::Default` when no widget wants a particular cursor
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor_icon
+    }
+
     pub fn end_frame(&mut self, window: &Window) -> Vec<ClippedMesh> {
-        let (_output, clipped_shapes) = self.platform.end_frame(Some(window));
+        self.subtitles.show(&self.context(), &self.subtitle_style);
+        self.virtual_keyboard.ui(&self.context(), self.input_mode);
+        let (output, clipped_shapes) = self.platform.end_frame(Some(window));
+        self.cursor_icon = output.cursor_icon;
         let frame_time = (Instant::now() - self.last_frame_start).as_secs_f64() as f32;
         self.previous_frame_time = Some(frame_time);
         self.context().tessellate(clipped_shapes)
@@ -0,0 +1,230 @@
+use phantom_dependencies::egui::{
+    vec2, Align2, Area, Button, CtxRef, Event, Frame, Id, Key, Modifiers, Order, Rect, TextEdit, Ui,
+};
+
+/// The device the player last used, which decides whether the virtual keyboard is needed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputMode {
+    KeyboardAndMouse,
+    Gamepad,
+    Touch,
+}
+
+impl Default for InputMode {
+    fn default() -> Self {
+        Self::KeyboardAndMouse
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum VirtualKey {
+    Character(char),
+    Space,
+    Backspace,
+    Shift,
+    Done,
+}
+
+/// An on-screen keyboard shown while a text field has focus in gamepad or touch mode.
+/// Typed text is sent to the focused text field and collected for the app's input.
+pub struct VirtualKeyboard {
+    pub enabled: bool,
+    shifted: bool,
+
+    /// The text field being typed into
+    target: Option<Id>,
+
+    /// Gamepad focus goes back to the pressed key once the text field has received its text
+    return_focus: Option<Id>,
+
+    focus_first_key: bool,
+    key_ids: Vec<Id>,
+    rect: Option<Rect>,
+    events: Vec<Event>,
+    text: String,
+}
+
+impl Default for VirtualKeyboard {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shifted: false,
+            target: None,
+            return_focus: None,
+            focus_first_key: false,
+            key_ids: Vec::new(),
+            rect: None,
+            events: Vec::new(),
+            text: String::new(),
+        }
+    }
+}
+
+impl VirtualKeyboard {
+    const ROWS: [&'static str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl'", "zxcvbnm,.-"];
+    const KEY_SIZE: f32 = 36.0;
+
+    pub fn is_open(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Text typed since this was last called, with a `'\u{8}'` for each backspace
+    pub fn take_text(&mut self) -> String {
+        std::mem::take(&mut self.text)
+    }
+
+    /// Events for the text field, given to egui at the start of the next frame
+    pub(crate) fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Drawn after everything else, so the text field has already handled this frame's input
+    pub fn ui(&mut self, ctx: &CtxRef, mode: InputMode) {
+        if !self.enabled || mode == InputMode::KeyboardAndMouse {
+            self.close();
+            return;
+        }
+
+        let response = Area::new("virtual_keyboard")
+            .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -8.0))
+            .order(Order::Foreground)
+            .show(ctx, |ui| {
+                self.update_target(ui, mode);
+                if self.target.is_none() {
+                    return;
+                }
+                Frame::popup(ui.style()).show(ui, |ui| self.keys_ui(ui));
+            })
+            .response;
+        self.rect = self.target.map(|_| response.rect);
+    }
+
+    fn update_target(&mut self, ui: &mut Ui, mode: InputMode) {
+        let mut focused = ui.memory().focus();
+        if let Some(key) = self.return_focus.take() {
+            if focused.is_some() && focused == self.target {
+                ui.memory().request_focus(key);
+                focused = Some(key);
+            }
+        }
+
+        match focused {
+            Some(id) if TextEdit::cursor(ui, id).is_some() => {
+                if self.target != Some(id) {
+                    self.focus_first_key = mode == InputMode::Gamepad;
+                    self.shifted = false;
+                }
+                self.target = Some(id);
+            }
+            Some(id) if self.key_ids.contains(&id) => {}
+            // Tapping a key takes the focus away from the text field before the tap is handled
+            None if self.is_pressing_key(ui) => {}
+            _ => self.close(),
+        }
+    }
+
+    fn is_pressing_key(&self, ui: &Ui) -> bool {
+        let input = ui.input();
+        match (self.rect, input.pointer.interact_pos()) {
+            (Some(rect), Some(position)) => input.pointer.any_released() && rect.contains(position),
+            _ => false,
+        }
+    }
+
+    fn keys_ui(&mut self, ui: &mut Ui) {
+        let mut key_ids = Vec::new();
+        let mut pressed = None;
+        let key_size = vec2(Self::KEY_SIZE, Self::KEY_SIZE);
+        for row in Self::ROWS {
+            ui.horizontal(|ui| {
+                for character in row.chars() {
+                    let character = if self.shifted {
+                        character.to_ascii_uppercase()
+                    } else {
+                        character
+                    };
+                    let response = ui.add_sized(key_size, Button::new(character));
+                    key_ids.push(response.id);
+                    if response.clicked() {
+                        pressed = Some((VirtualKey::Character(character), response.id));
+                    }
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let keys = [
+                (VirtualKey::Shift, "⇧", 1.5),
+                (VirtualKey::Space, "Space", 5.0),
+                (VirtualKey::Backspace, "⌫", 1.5),
+                (VirtualKey::Done, "Done", 2.0),
+            ];
+            for (key, label, width) in keys {
+                let size = vec2(Self::KEY_SIZE * width, Self::KEY_SIZE);
+                let response = ui.add_sized(size, Button::new(label));
+                key_ids.push(response.id);
+                if response.clicked() {
+                    pressed = Some((key, response.id));
+                }
+            }
+        });
+
+        if std::mem::take(&mut self.focus_first_key) {
+            if let Some(first_key) = key_ids.first() {
+                ui.memory().request_focus(*first_key);
+            }
+        }
+        self.key_ids = key_ids;
+
+        if let Some((key, id)) = pressed {
+            self.press(ui, key, id);
+        }
+    }
+
+    fn press(&mut self, ui: &mut Ui, key: VirtualKey, id: Id) {
+        let target = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+        match key {
+            VirtualKey::Character(character) => {
+                self.events.push(Event::Text(character.to_string()));
+                self.text.push(character);
+                self.shifted = false;
+            }
+            VirtualKey::Space => {
+                self.events.push(Event::Text(" ".to_string()));
+                self.text.push(' ');
+            }
+            VirtualKey::Backspace => {
+                self.events.push(Self::key_event(Key::Backspace));
+                self.text.push('\u{8}');
+            }
+            VirtualKey::Shift => self.shifted = !self.shifted,
+            // Single line text fields give up focus on enter, which closes the keyboard
+            VirtualKey::Done => self.events.push(Self::key_event(Key::Enter)),
+        }
+
+        // The text field only receives text while it has focus
+        let key_had_focus = ui.memory().has_focus(id);
+        ui.memory().request_focus(target);
+        if key_had_focus && key != VirtualKey::Done {
+            self.return_focus = Some(id);
+        }
+    }
+
+    fn close(&mut self) {
+        self.target = None;
+        self.return_focus = None;
+        self.focus_first_key = false;
+        self.key_ids.clear();
+        self.rect = None;
+    }
+
+    fn key_event(key: Key) -> Event {
+        Event::Key {
+            key,
+            pressed: true,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
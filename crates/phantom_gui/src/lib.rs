@@ -1,3 +1,6 @@
 mod gui;
+mod keyboard;
+mod navigation;
+mod subtitles;
 
-pub use self::gui::*;
+pub use self::{gui::*, keyboard::*, navigation::*, subtitles::*};
@@ -0,0 +1,96 @@
+use phantom_dependencies::{
+    egui::{Event, Key, Modifiers},
+    gilrs::{Axis, Button, EventType},
+};
+
+/// How far a stick has to be pushed before it moves the focus
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// A focus movement in the gui, driven by a gamepad
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GuiNavigation {
+    Next,
+    Previous,
+    Activate,
+    Back,
+}
+
+impl GuiNavigation {
+    /// egui moves focus with tab, clicks the focused widget with enter
+    /// and clears the focus or closes popups with escape
+    pub fn as_key_event(&self) -> Event {
+        let (key, modifiers) = match self {
+            Self::Next => (Key::Tab, Modifiers::default()),
+            Self::Previous => (
+                Key::Tab,
+                Modifiers {
+                    shift: true,
+                    ..Default::default()
+                },
+            ),
+            Self::Activate => (Key::Enter, Modifiers::default()),
+            Self::Back => (Key::Escape, Modifiers::default()),
+        };
+        Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+        }
+    }
+}
+
+/// Turns gamepad events into gui navigation.
+/// The d-pad and left stick move the focus, south (A) activates and east (B) backs out.
+pub struct GamepadNavigation {
+    pub enabled: bool,
+    stick_centered: bool,
+}
+
+impl Default for GamepadNavigation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stick_centered: true,
+        }
+    }
+}
+
+impl GamepadNavigation {
+    pub fn handle_event(&mut self, event: &EventType) -> Option<GuiNavigation> {
+        if !self.enabled {
+            return None;
+        }
+        match event {
+            EventType::ButtonPressed(button, _) => match button {
+                Button::DPadDown | Button::DPadRight => Some(GuiNavigation::Next),
+                Button::DPadUp | Button::DPadLeft => Some(GuiNavigation::Previous),
+                Button::South => Some(GuiNavigation::Activate),
+                Button::East => Some(GuiNavigation::Back),
+                _ => None,
+            },
+            EventType::AxisChanged(axis @ (Axis::LeftStickX | Axis::LeftStickY), value, _) => {
+                // Only the first push past the threshold moves the focus,
+                // the stick has to return to the center before it can move again
+                if value.abs() < STICK_THRESHOLD {
+                    self.stick_centered = true;
+                    return None;
+                }
+                if !self.stick_centered {
+                    return None;
+                }
+                self.stick_centered = false;
+                // The y axis points up
+                let forward = match axis {
+                    Axis::LeftStickY => *value < 0.0,
+                    _ => *value > 0.0,
+                };
+                Some(if forward {
+                    GuiNavigation::Next
+                } else {
+                    GuiNavigation::Previous
+                })
+            }
+            _ => None,
+        }
+    }
+}
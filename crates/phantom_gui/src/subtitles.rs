@@ -0,0 +1,207 @@
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    egui::{vec2, Align2, Area, Color32, CtxRef, Frame, Order, TextStyle},
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum SubtitleSize {
+    Normal,
+    Large,
+}
+
+impl SubtitleSize {
+    fn text_style(&self) -> TextStyle {
+        match self {
+            Self::Normal => TextStyle::Body,
+            Self::Large => TextStyle::Heading,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct SubtitleStyle {
+    pub enabled: bool,
+    pub size: SubtitleSize,
+
+    /// Opacity of the box behind the text, from zero to one
+    pub background_opacity: f32,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            size: SubtitleSize::Normal,
+            background_opacity: 0.6,
+        }
+    }
+}
+
+impl SubtitleStyle {
+    const SPEAKER_COLOR: Color32 = Color32::from_rgb(255, 210, 110);
+
+    /// Draws a line of dialogue centered at the bottom of the screen,
+    /// with the speaker's name in front of it
+    pub fn show(&self, ctx: &CtxRef, speaker: Option<&str>, text: &str) {
+        if !self.enabled || text.is_empty() {
+            return;
+        }
+        let background = (self.background_opacity.clamp(0.0, 1.0) * 255.0) as u8;
+        Area::new("subtitles")
+            .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -48.0))
+            .order(Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::none()
+                    .fill(Color32::from_black_alpha(background))
+                    .margin(vec2(12.0, 6.0))
+                    .corner_radius(4.0)
+                    .show(ui, |ui| {
+                        ui.style_mut().override_text_style = Some(self.size.text_style());
+                        ui.horizontal_wrapped(|ui| {
+                            if let Some(speaker) = speaker {
+                                ui.colored_label(Self::SPEAKER_COLOR, format!("{}:", speaker));
+                            }
+                            ui.colored_label(Color32::WHITE, text);
+                        });
+                    });
+            });
+    }
+}
+
+/// A timed line of dialogue or sound description
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Caption {
+    pub speaker: Option<String>,
+
+    /// A localization key, or the text itself when there is no entry for it
+    pub text: String,
+
+    /// Seconds to wait before showing this caption, after the previous one ends
+    pub delay: f32,
+
+    /// Seconds the caption stays on screen
+    pub duration: f32,
+}
+
+impl Caption {
+    pub fn new(speaker: Option<&str>, text: &str, duration: f32) -> Self {
+        Self {
+            speaker: speaker.map(str::to_string),
+            text: text.to_string(),
+            delay: 0.0,
+            duration,
+        }
+    }
+}
+
+/// Queues captions and shows them one after another through the gui
+#[derive(Default)]
+pub struct SubtitleManager {
+    queue: VecDeque<Caption>,
+    current: Option<Caption>,
+
+    /// Seconds the current caption has been waiting or showing
+    elapsed: f32,
+
+    /// Localized text keyed by localization key
+    pub localization: HashMap<String, String>,
+
+    /// Captions queued when an audio clip starts playing, keyed by the clip name
+    pub audio_captions: HashMap<String, Vec<Caption>>,
+}
+
+impl SubtitleManager {
+    pub fn queue(&mut self, caption: Caption) {
+        self.queue.push_back(caption);
+    }
+
+    /// Queues the captions attached to an audio clip.
+    /// Call this when the clip starts playing.
+    pub fn audio_started(&mut self, clip: &str) {
+        if let Some(captions) = self.audio_captions.get(clip) {
+            self.queue.extend(captions.iter().cloned());
+        }
+    }
+
+    /// Removes the current caption and everything queued after it
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.current = None;
+        self.elapsed = 0.0;
+    }
+
+    /// Loads localized text from lines of `key = text`, replacing existing entries.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn load_localization(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, text) = line.split_once('=').context(format!(
+                "Expected 'key = text' on line {} of {}",
+                index + 1,
+                path.display()
+            ))?;
+            self.localization
+                .insert(key.trim().to_string(), text.trim().to_string());
+        }
+        Ok(())
+    }
+
+    pub fn localize<'a>(&'a self, key: &'a str) -> &'a str {
+        self.localization.get(key).map_or(key, String::as_str)
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.elapsed += delta_time;
+        loop {
+            if self.current.is_none() {
+                self.current = self.queue.pop_front();
+            }
+            let current = match self.current.as_ref() {
+                Some(current) => current,
+                None => {
+                    self.elapsed = 0.0;
+                    return;
+                }
+            };
+            let end = current.delay + current.duration;
+            if self.elapsed < end {
+                return;
+            }
+            self.elapsed -= end;
+            self.current = None;
+        }
+    }
+
+    /// The speaker and text of the caption on screen, localized
+    pub fn visible(&self) -> Option<(Option<&str>, &str)> {
+        let current = self.current.as_ref()?;
+        if self.elapsed < current.delay {
+            return None;
+        }
+        let speaker = current
+            .speaker
+            .as_deref()
+            .map(|speaker| self.localize(speaker));
+        Some((speaker, self.localize(&current.text)))
+    }
+
+    pub fn show(&self, ctx: &CtxRef, style: &SubtitleStyle) {
+        if let Some((speaker, text)) = self.visible() {
+            style.show(ctx, speaker, text);
+        }
+    }
+}
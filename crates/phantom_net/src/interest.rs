@@ -0,0 +1,290 @@
+use crate::ClientId;
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::IntoQuery,
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use phantom_world::{Entity, World};
+use std::collections::{HashMap, HashSet};
+
+/// Marks an entity as replicated to clients
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Replicated {
+    /// Sent to every client regardless of their interest policy
+    pub always_relevant: bool,
+
+    /// Higher priority entities are kept first when a client is over its entity budget
+    pub priority: f32,
+}
+
+impl Default for Replicated {
+    fn default() -> Self {
+        Self {
+            always_relevant: false,
+            priority: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum InterestPolicy {
+    /// Entities within a distance of the client's position
+    Radius(f32),
+
+    /// Only the entities in the client's visibility set
+    VisibilitySet,
+
+    /// Entities within a distance or in the client's visibility set
+    RadiusOrVisibilitySet(f32),
+
+    /// Every replicated entity
+    Everything,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientInterest {
+    pub position: glm::Vec3,
+    pub policy: InterestPolicy,
+
+    /// Entities the game has decided this client can see, such as from a potentially visible set
+    pub visibility_set: HashSet<Entity>,
+    relevant: HashSet<Entity>,
+}
+
+impl ClientInterest {
+    pub fn new(policy: InterestPolicy) -> Self {
+        Self {
+            position: glm::Vec3::zeros(),
+            policy,
+            visibility_set: HashSet::new(),
+            relevant: HashSet::new(),
+        }
+    }
+
+    pub fn relevant(&self) -> &HashSet<Entity> {
+        &self.relevant
+    }
+}
+
+/// The entities that became relevant or stopped being relevant to a client since the last update
+#[derive(Default, Debug, Clone)]
+pub struct InterestDelta {
+    pub entered: Vec<Entity>,
+    pub exited: Vec<Entity>,
+}
+
+impl InterestDelta {
+    pub fn is_empty(&self) -> bool {
+        self.entered.is_empty() && self.exited.is_empty()
+    }
+}
+
+/// Decides which replicated entities each client should receive,
+/// using a spatial grid so radius queries only visit nearby cells.
+pub struct InterestManager {
+    pub cell_size: f32,
+
+    /// The most entities replicated to a single client, keeping bandwidth bounded on large maps
+    pub max_entities_per_client: usize,
+    clients: HashMap<ClientId, ClientInterest>,
+    grid: HashMap<[i32; 3], Vec<(Entity, glm::Vec3)>>,
+}
+
+impl Default for InterestManager {
+    fn default() -> Self {
+        Self {
+            cell_size: 32.0,
+            max_entities_per_client: 512,
+            clients: HashMap::new(),
+            grid: HashMap::new(),
+        }
+    }
+}
+
+impl InterestManager {
+    pub fn add_client(&mut self, client: ClientId, policy: InterestPolicy) {
+        self.clients.insert(client, ClientInterest::new(policy));
+    }
+
+    pub fn remove_client(&mut self, client: ClientId) -> Option<ClientInterest> {
+        self.clients.remove(&client)
+    }
+
+    pub fn client(&self, client: ClientId) -> Option<&ClientInterest> {
+        self.clients.get(&client)
+    }
+
+    pub fn client_mut(&mut self, client: ClientId) -> Option<&mut ClientInterest> {
+        self.clients.get_mut(&client)
+    }
+
+    pub fn set_client_position(&mut self, client: ClientId, position: glm::Vec3) {
+        if let Some(interest) = self.clients.get_mut(&client) {
+            interest.position = position;
+        }
+    }
+
+    pub fn is_relevant(&self, client: ClientId, entity: Entity) -> bool {
+        self.clients
+            .get(&client)
+            .map_or(false, |interest| interest.relevant.contains(&entity))
+    }
+
+    pub fn relevant_entities(&self, client: ClientId) -> Vec<Entity> {
+        self.clients
+            .get(&client)
+            .map(|interest| interest.relevant.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Recomputes each client's relevant entities, returning what changed per client
+    pub fn update(&mut self, world: &World) -> Result<HashMap<ClientId, InterestDelta>> {
+        let disabled = world.disabled_entities();
+        let mut replicated = HashMap::new();
+        self.grid.clear();
+        let mut query = <(Entity, &Replicated)>::query();
+        for (entity, replication) in query.iter(&world.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            let position = (world.entity_global_transform_matrix(*entity)?
+                * glm::vec4(0.0, 0.0, 0.0, 1.0))
+            .xyz();
+            replicated.insert(*entity, (*replication, position));
+            let cell = self.cell(&position);
+            self.grid
+                .entry(cell)
+                .or_insert_with(Vec::new)
+                .push((*entity, position));
+        }
+
+        let client_ids = self.clients.keys().copied().collect::<Vec<_>>();
+        let mut deltas = HashMap::new();
+        for client in client_ids {
+            let (position, policy, visibility_set) = {
+                let interest = &self.clients[&client];
+                (
+                    interest.position,
+                    interest.policy,
+                    interest.visibility_set.clone(),
+                )
+            };
+
+            let mut candidates = HashSet::new();
+            match policy {
+                InterestPolicy::Radius(radius) => {
+                    candidates.extend(self.entities_in_radius(&position, radius));
+                }
+                InterestPolicy::VisibilitySet => {
+                    candidates.extend(visibility_set.iter().copied());
+                }
+                InterestPolicy::RadiusOrVisibilitySet(radius) => {
+                    candidates.extend(self.entities_in_radius(&position, radius));
+                    candidates.extend(visibility_set.iter().copied());
+                }
+                InterestPolicy::Everything => {
+                    candidates.extend(replicated.keys().copied());
+                }
+            }
+            candidates.extend(
+                replicated
+                    .iter()
+                    .filter(|(_, (replication, _))| replication.always_relevant)
+                    .map(|(entity, _)| *entity),
+            );
+
+            let mut candidates = candidates
+                .into_iter()
+                .filter_map(|entity| {
+                    replicated
+                        .get(&entity)
+                        .map(|(replication, entity_position)| {
+                            (
+                                entity,
+                                replication.always_relevant,
+                                replication.priority,
+                                glm::distance2(&position, entity_position),
+                            )
+                        })
+                })
+                .collect::<Vec<_>>();
+            if candidates.len() > self.max_entities_per_client {
+                candidates.sort_by(|a, b| {
+                    b.1.cmp(&a.1)
+                        .then(b.2.total_cmp(&a.2))
+                        .then(a.3.total_cmp(&b.3))
+                });
+                candidates.truncate(self.max_entities_per_client);
+            }
+            let relevant = candidates
+                .into_iter()
+                .map(|(entity, ..)| entity)
+                .collect::<HashSet<_>>();
+
+            let interest = self
+                .clients
+                .get_mut(&client)
+                .expect("Client was removed during update!");
+            let delta = InterestDelta {
+                entered: relevant.difference(&interest.relevant).copied().collect(),
+                exited: interest.relevant.difference(&relevant).copied().collect(),
+            };
+            interest.relevant = relevant;
+            deltas.insert(client, delta);
+        }
+
+        Ok(deltas)
+    }
+
+    fn entities_in_radius(&self, position: &glm::Vec3, radius: f32) -> Vec<Entity> {
+        let min = self.cell(&(position - glm::vec3(radius, radius, radius)));
+        let max = self.cell(&(position + glm::vec3(radius, radius, radius)));
+        let radius_squared = radius * radius;
+
+        // Large radii cover far more cells than are occupied, so the occupied cells are
+        // visited instead of every cell in the bounding cube
+        let cube_cells = (0..3)
+            .map(|axis| (max[axis] as i64 - min[axis] as i64 + 1) as u64)
+            .fold(1_u64, u64::saturating_mul);
+        let cells = if cube_cells > self.grid.len() as u64 {
+            self.grid
+                .iter()
+                .filter(|(cell, _)| {
+                    (0..3).all(|axis| (min[axis]..=max[axis]).contains(&cell[axis]))
+                })
+                .map(|(_, entities)| entities)
+                .collect::<Vec<_>>()
+        } else {
+            let mut cells = Vec::new();
+            for x in min[0]..=max[0] {
+                for y in min[1]..=max[1] {
+                    for z in min[2]..=max[2] {
+                        cells.extend(self.grid.get(&[x, y, z]));
+                    }
+                }
+            }
+            cells
+        };
+
+        cells
+            .into_iter()
+            .flat_map(|cell| cell.iter())
+            .filter(|(_, entity_position)| {
+                glm::distance2(position, entity_position) <= radius_squared
+            })
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    fn cell(&self, position: &glm::Vec3) -> [i32; 3] {
+        let cell_size = self.cell_size.max(f32::EPSILON);
+        [
+            (position.x / cell_size).floor() as i32,
+            (position.y / cell_size).floor() as i32,
+            (position.z / cell_size).floor() as i32,
+        ]
+    }
+}
@@ -0,0 +1,20 @@
+mod interest;
+mod lobby;
+mod reliable;
+mod rewind;
+mod transport;
+mod udp;
+mod websocket;
+
+pub use self::{interest::*, lobby::*, reliable::*, rewind::*, transport::*, udp::*, websocket::*};
+
+use phantom_dependencies::anyhow::Result;
+use phantom_world::register_component;
+
+pub type ClientId = u64;
+
+/// Registers the networking components so they are saved with the world
+pub fn register_components() -> Result<()> {
+    register_component::<Replicated>("replicated")?;
+    Ok(())
+}
@@ -0,0 +1,516 @@
+use crate::{ClientId, ReliableChannel, Transport, TransportEvent};
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    bincode, log,
+    serde::{Deserialize, Serialize},
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct PlayerInfo {
+    pub id: ClientId,
+    pub name: String,
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Lobby {
+    pub name: String,
+    pub max_players: usize,
+
+    /// Players in the order they joined
+    pub players: Vec<PlayerInfo>,
+
+    /// The player with authority over the session, such as the one who picks the map
+    pub host: Option<ClientId>,
+}
+
+impl Lobby {
+    pub fn player(&self, id: ClientId) -> Option<&PlayerInfo> {
+        self.players.iter().find(|player| player.id == id)
+    }
+
+    fn player_mut(&mut self, id: ClientId) -> Option<&mut PlayerInfo> {
+        self.players.iter_mut().find(|player| player.id == id)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= self.max_players
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+enum LobbyMessage {
+    Join {
+        name: String,
+        metadata: HashMap<String, String>,
+    },
+    Welcome {
+        id: ClientId,
+        lobby: Lobby,
+    },
+    Rejected(String),
+    PlayerJoined(PlayerInfo),
+    PlayerLeft(ClientId),
+    SetMetadata {
+        key: String,
+        value: String,
+    },
+    MetadataChanged {
+        id: ClientId,
+        key: String,
+        value: String,
+    },
+    Chat {
+        from: ClientId,
+        text: String,
+    },
+    Data {
+        from: ClientId,
+        channel: u8,
+        payload: Vec<u8>,
+    },
+    HostChanged(ClientId),
+}
+
+#[derive(Debug, Clone)]
+pub enum LobbyEvent {
+    Joined(Lobby),
+    Rejected(String),
+    PlayerJoined(PlayerInfo),
+    PlayerLeft(ClientId),
+    MetadataChanged {
+        id: ClientId,
+        key: String,
+        value: String,
+    },
+    Chat {
+        from: ClientId,
+        text: String,
+    },
+    Message {
+        from: ClientId,
+        channel: u8,
+        payload: Vec<u8>,
+    },
+    HostChanged(ClientId),
+
+    /// The connection to the lobby server was lost.
+    /// The last known lobby is included so a player can take over hosting it.
+    ServerLost(Lobby),
+}
+
+/// Picks the next host when the current one leaves
+pub type HostMigration = Box<dyn FnMut(&Lobby) -> Option<ClientId>>;
+
+/// Owns a lobby, relaying chat and messages between its players
+pub struct LobbyServer<T: Transport> {
+    transport: T,
+    lobby: Lobby,
+    channels: HashMap<ClientId, ReliableChannel>,
+    host_migration: HostMigration,
+}
+
+impl<T: Transport> LobbyServer<T> {
+    pub fn new(transport: T, name: &str, max_players: usize) -> Self {
+        Self {
+            transport,
+            lobby: Lobby {
+                name: name.to_string(),
+                max_players,
+                players: Vec::new(),
+                host: None,
+            },
+            channels: HashMap::new(),
+            host_migration: Box::new(|lobby| lobby.players.first().map(|player| player.id)),
+        }
+    }
+
+    pub fn lobby(&self) -> &Lobby {
+        &self.lobby
+    }
+
+    /// Replaces the default host migration, which promotes the longest connected player
+    pub fn set_host_migration(
+        &mut self,
+        host_migration: impl FnMut(&Lobby) -> Option<ClientId> + 'static,
+    ) {
+        self.host_migration = Box::new(host_migration);
+    }
+
+    pub fn set_host(&mut self, id: ClientId) -> Result<()> {
+        if self.lobby.player(id).is_none() {
+            bail!("Failed to find player to make host!");
+        }
+        self.lobby.host = Some(id);
+        self.broadcast(&LobbyMessage::HostChanged(id))
+    }
+
+    pub fn kick(&mut self, id: ClientId) -> Result<()> {
+        self.transport.disconnect(id)?;
+        self.channels.remove(&id);
+        self.remove_player(id)?;
+        Ok(())
+    }
+
+    /// Sends a chat message from the server to every player
+    pub fn send_chat(&mut self, text: &str) -> Result<()> {
+        self.broadcast(&LobbyMessage::Chat {
+            from: ClientId::MAX,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn update(&mut self) -> Result<Vec<LobbyEvent>> {
+        let mut events = Vec::new();
+        for event in self.transport.poll()? {
+            match event {
+                TransportEvent::Connected(id) => {
+                    let channel = ReliableChannel::new(self.transport.max_message_size());
+                    self.channels.insert(id, channel);
+                }
+                TransportEvent::Disconnected(id) => {
+                    self.channels.remove(&id);
+                    if self.remove_player(id)? {
+                        events.push(LobbyEvent::PlayerLeft(id));
+                    }
+                }
+                // Anyone can send a datagram, so a bad packet only drops itself
+                TransportEvent::Message(id, packet) => {
+                    let messages = match self.receive(id, &packet) {
+                        Ok(messages) => messages,
+                        Err(error) => {
+                            log::warn!("Dropped a lobby packet from {}: {}", id, error);
+                            continue;
+                        }
+                    };
+                    for message in messages {
+                        if let Err(error) = self.handle_message(id, message, &mut events) {
+                            log::warn!("Failed to handle a lobby message from {}: {}", id, error);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (id, channel) in self.channels.iter_mut() {
+            for packet in channel.resend()? {
+                if let Err(error) = self.transport.send(*id, &packet) {
+                    log::warn!("Failed to resend a lobby packet to {}: {}", id, error);
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    fn handle_message(
+        &mut self,
+        id: ClientId,
+        message: LobbyMessage,
+        events: &mut Vec<LobbyEvent>,
+    ) -> Result<()> {
+        let joined = self.lobby.player(id).is_some();
+        match message {
+            LobbyMessage::Join { name, metadata } if !joined => {
+                if self.lobby.is_full() {
+                    self.send(id, &LobbyMessage::Rejected("Lobby is full".to_string()))?;
+                    return Ok(());
+                }
+                let player = PlayerInfo { id, name, metadata };
+                self.lobby.players.push(player.clone());
+                if self.lobby.host.is_none() {
+                    self.lobby.host = Some(id);
+                }
+                let lobby = self.lobby.clone();
+                self.send(id, &LobbyMessage::Welcome { id, lobby })?;
+                self.broadcast_except(id, &LobbyMessage::PlayerJoined(player.clone()))?;
+                events.push(LobbyEvent::PlayerJoined(player));
+            }
+            LobbyMessage::SetMetadata { key, value } if joined => {
+                if let Some(player) = self.lobby.player_mut(id) {
+                    player.metadata.insert(key.to_string(), value.to_string());
+                }
+                self.broadcast(&LobbyMessage::MetadataChanged {
+                    id,
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })?;
+                events.push(LobbyEvent::MetadataChanged { id, key, value });
+            }
+            LobbyMessage::Chat { text, .. } if joined => {
+                self.broadcast(&LobbyMessage::Chat {
+                    from: id,
+                    text: text.to_string(),
+                })?;
+                events.push(LobbyEvent::Chat { from: id, text });
+            }
+            LobbyMessage::Data {
+                channel, payload, ..
+            } if joined => {
+                self.broadcast_except(
+                    id,
+                    &LobbyMessage::Data {
+                        from: id,
+                        channel,
+                        payload: payload.to_vec(),
+                    },
+                )?;
+                events.push(LobbyEvent::Message {
+                    from: id,
+                    channel,
+                    payload,
+                });
+            }
+            message => log::warn!("Ignored lobby message from {}: {:?}", id, message),
+        }
+        Ok(())
+    }
+
+    /// Returns true if the client had joined the lobby
+    fn remove_player(&mut self, id: ClientId) -> Result<bool> {
+        let count = self.lobby.players.len();
+        self.lobby.players.retain(|player| player.id != id);
+        if self.lobby.players.len() == count {
+            return Ok(false);
+        }
+        self.broadcast(&LobbyMessage::PlayerLeft(id))?;
+        if self.lobby.host == Some(id) {
+            self.lobby.host = (self.host_migration)(&self.lobby);
+            if let Some(host) = self.lobby.host {
+                self.broadcast(&LobbyMessage::HostChanged(host))?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn receive(&mut self, id: ClientId, packet: &[u8]) -> Result<Vec<LobbyMessage>> {
+        let max_packet_size = self.transport.max_message_size();
+        let channel = self
+            .channels
+            .entry(id)
+            .or_insert_with(|| ReliableChannel::new(max_packet_size));
+        let (outgoing, delivered) = channel.receive(packet)?;
+        for packet in outgoing {
+            if let Err(error) = self.transport.send(id, &packet) {
+                log::warn!(
+                    "Failed to acknowledge a lobby packet from {}: {}",
+                    id,
+                    error
+                );
+            }
+        }
+        Ok(delivered
+            .iter()
+            .filter_map(|message| match bincode::deserialize(message) {
+                Ok(message) => Some(message),
+                Err(error) => {
+                    log::warn!("Dropped a lobby message from {}: {}", id, error);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn send(&mut self, id: ClientId, message: &LobbyMessage) -> Result<()> {
+        let channel = self
+            .channels
+            .get_mut(&id)
+            .context("Failed to find client channel!")?;
+        let packet = channel.send(bincode::serialize(message)?)?;
+        self.transport.send(id, &packet)
+    }
+
+    fn broadcast(&mut self, message: &LobbyMessage) -> Result<()> {
+        let players = self
+            .lobby
+            .players
+            .iter()
+            .map(|player| player.id)
+            .collect::<Vec<_>>();
+        for id in players {
+            self.send(id, message)?;
+        }
+        Ok(())
+    }
+
+    fn broadcast_except(&mut self, except: ClientId, message: &LobbyMessage) -> Result<()> {
+        let players = self
+            .lobby
+            .players
+            .iter()
+            .map(|player| player.id)
+            .filter(|id| *id != except)
+            .collect::<Vec<_>>();
+        for id in players {
+            self.send(id, message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A player's connection to a lobby server
+pub struct LobbyClient<T: Transport> {
+    transport: T,
+    channel: ReliableChannel,
+    id: Option<ClientId>,
+    lobby: Option<Lobby>,
+}
+
+impl<T: Transport> LobbyClient<T> {
+    /// The server is expected to be the transport's peer with the id 0
+    pub const SERVER: ClientId = 0;
+
+    pub fn join(transport: T, name: &str, metadata: HashMap<String, String>) -> Result<Self> {
+        let channel = ReliableChannel::new(transport.max_message_size());
+        let mut client = Self {
+            transport,
+            channel,
+            id: None,
+            lobby: None,
+        };
+        client.send(&LobbyMessage::Join {
+            name: name.to_string(),
+            metadata,
+        })?;
+        Ok(client)
+    }
+
+    /// This player's id, once the server has accepted them
+    pub fn id(&self) -> Option<ClientId> {
+        self.id
+    }
+
+    pub fn lobby(&self) -> Option<&Lobby> {
+        self.lobby.as_ref()
+    }
+
+    pub fn is_host(&self) -> bool {
+        match (self.id, self.lobby.as_ref()) {
+            (Some(id), Some(lobby)) => lobby.host == Some(id),
+            _ => false,
+        }
+    }
+
+    pub fn send_chat(&mut self, text: &str) -> Result<()> {
+        self.send(&LobbyMessage::Chat {
+            from: self.id.unwrap_or_default(),
+            text: text.to_string(),
+        })
+    }
+
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Result<()> {
+        self.send(&LobbyMessage::SetMetadata {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Sends a reliable, ordered message to every other player in the lobby
+    pub fn send_message(&mut self, channel: u8, payload: &[u8]) -> Result<()> {
+        self.send(&LobbyMessage::Data {
+            from: self.id.unwrap_or_default(),
+            channel,
+            payload: payload.to_vec(),
+        })
+    }
+
+    pub fn leave(&mut self) -> Result<()> {
+        self.transport.disconnect(Self::SERVER)?;
+        self.lobby = None;
+        self.id = None;
+        Ok(())
+    }
+
+    pub fn update(&mut self) -> Result<Vec<LobbyEvent>> {
+        let mut events = Vec::new();
+        for event in self.transport.poll()? {
+            match event {
+                TransportEvent::Message(id, packet) if id == Self::SERVER => {
+                    let (outgoing, delivered) = match self.channel.receive(&packet) {
+                        Ok(received) => received,
+                        Err(error) => {
+                            log::warn!("Dropped a lobby packet from the server: {}", error);
+                            continue;
+                        }
+                    };
+                    for packet in outgoing {
+                        self.transport.send(Self::SERVER, &packet)?;
+                    }
+                    for message in delivered {
+                        match bincode::deserialize(&message) {
+                            Ok(message) => self.handle_message(message, &mut events),
+                            Err(error) => {
+                                log::warn!("Dropped a lobby message from the server: {}", error)
+                            }
+                        }
+                    }
+                }
+                TransportEvent::Disconnected(id) if id == Self::SERVER => {
+                    if let Some(lobby) = self.lobby.take() {
+                        events.push(LobbyEvent::ServerLost(lobby));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for packet in self.channel.resend()? {
+            self.transport.send(Self::SERVER, &packet)?;
+        }
+        Ok(events)
+    }
+
+    fn handle_message(&mut self, message: LobbyMessage, events: &mut Vec<LobbyEvent>) {
+        match message {
+            LobbyMessage::Welcome { id, lobby } => {
+                self.id = Some(id);
+                self.lobby = Some(lobby.clone());
+                events.push(LobbyEvent::Joined(lobby));
+            }
+            LobbyMessage::Rejected(reason) => events.push(LobbyEvent::Rejected(reason)),
+            LobbyMessage::PlayerJoined(player) => {
+                if let Some(lobby) = self.lobby.as_mut() {
+                    lobby.players.push(player.clone());
+                }
+                events.push(LobbyEvent::PlayerJoined(player));
+            }
+            LobbyMessage::PlayerLeft(id) => {
+                if let Some(lobby) = self.lobby.as_mut() {
+                    lobby.players.retain(|player| player.id != id);
+                }
+                events.push(LobbyEvent::PlayerLeft(id));
+            }
+            LobbyMessage::MetadataChanged { id, key, value } => {
+                if let Some(player) = self.lobby.as_mut().and_then(|lobby| lobby.player_mut(id)) {
+                    player.metadata.insert(key.to_string(), value.to_string());
+                }
+                events.push(LobbyEvent::MetadataChanged { id, key, value });
+            }
+            LobbyMessage::Chat { from, text } => events.push(LobbyEvent::Chat { from, text }),
+            LobbyMessage::Data {
+                from,
+                channel,
+                payload,
+            } => events.push(LobbyEvent::Message {
+                from,
+                channel,
+                payload,
+            }),
+            LobbyMessage::HostChanged(id) => {
+                if let Some(lobby) = self.lobby.as_mut() {
+                    lobby.host = Some(id);
+                }
+                events.push(LobbyEvent::HostChanged(id));
+            }
+            message => log::warn!("Ignored lobby message from server: {:?}", message),
+        }
+    }
+
+    fn send(&mut self, message: &LobbyMessage) -> Result<()> {
+        let packet = self.channel.send(bincode::serialize(message)?)?;
+        self.transport.send(Self::SERVER, &packet)
+    }
+}
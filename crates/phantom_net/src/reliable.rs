@@ -0,0 +1,114 @@
+use phantom_dependencies::{
+    anyhow::{bail, Result},
+    bincode,
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+enum Packet {
+    Reliable { sequence: u32, payload: Vec<u8> },
+    Ack(u32),
+}
+
+/// Delivers messages exactly once and in order over an unreliable transport,
+/// resending anything that has not been acknowledged.
+pub struct ReliableChannel {
+    pub resend_interval: Duration,
+
+    /// Packets larger than the transport can carry are rejected instead of being resent forever
+    pub max_packet_size: usize,
+    next_send: u32,
+    next_receive: u32,
+    unacknowledged: VecDeque<(u32, Vec<u8>, Instant)>,
+    received: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Default for ReliableChannel {
+    fn default() -> Self {
+        Self {
+            resend_interval: Duration::from_millis(200),
+            max_packet_size: usize::MAX,
+            next_send: 0,
+            next_receive: 0,
+            unacknowledged: VecDeque::new(),
+            received: BTreeMap::new(),
+        }
+    }
+}
+
+impl ReliableChannel {
+    /// A channel for a transport that carries messages up to this size
+    pub fn new(max_packet_size: usize) -> Self {
+        Self {
+            max_packet_size,
+            ..Default::default()
+        }
+    }
+
+    /// Wraps a message in a packet to hand to the transport.
+    /// Messages too large for a single packet are rejected without being queued.
+    pub fn send(&mut self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        let sequence = self.next_send;
+        let packet = bincode::serialize(&Packet::Reliable {
+            sequence,
+            payload: payload.to_vec(),
+        })?;
+        if packet.len() > self.max_packet_size {
+            bail!(
+                "The message is {} bytes once packed, which is more than the {} bytes the transport can send!",
+                packet.len(),
+                self.max_packet_size
+            );
+        }
+        self.next_send = self.next_send.wrapping_add(1);
+        self.unacknowledged
+            .push_back((sequence, payload, Instant::now()));
+        Ok(packet)
+    }
+
+    /// Handles a packet from the transport, returning the packets to send back
+    /// and any messages that are now ready in order
+    pub fn receive(&mut self, packet: &[u8]) -> Result<(Vec<Vec<u8>>, Vec<Vec<u8>>)> {
+        let mut outgoing = Vec::new();
+        let mut delivered = Vec::new();
+        match bincode::deserialize::<Packet>(packet)? {
+            Packet::Reliable { sequence, payload } => {
+                outgoing.push(bincode::serialize(&Packet::Ack(sequence))?);
+                if sequence.wrapping_sub(self.next_receive) < u32::MAX / 2 {
+                    self.received.entry(sequence).or_insert(payload);
+                }
+                while let Some(payload) = self.received.remove(&self.next_receive) {
+                    delivered.push(payload);
+                    self.next_receive = self.next_receive.wrapping_add(1);
+                }
+            }
+            Packet::Ack(sequence) => {
+                self.unacknowledged
+                    .retain(|(unacknowledged, ..)| *unacknowledged != sequence);
+            }
+        }
+        Ok((outgoing, delivered))
+    }
+
+    /// Packets that have waited too long for an acknowledgement
+    pub fn resend(&mut self) -> Result<Vec<Vec<u8>>> {
+        let now = Instant::now();
+        let mut outgoing = Vec::new();
+        for (sequence, payload, sent) in self.unacknowledged.iter_mut() {
+            if now.duration_since(*sent) < self.resend_interval {
+                continue;
+            }
+            *sent = now;
+            outgoing.push(bincode::serialize(&Packet::Reliable {
+                sequence: *sequence,
+                payload: payload.to_vec(),
+            })?);
+        }
+        Ok(outgoing)
+    }
+}
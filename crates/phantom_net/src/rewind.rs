@@ -0,0 +1,218 @@
+use phantom_dependencies::{
+    legion::IntoQuery,
+    nalgebra_glm as glm,
+    rapier3d::{
+        geometry::{ColliderHandle, InteractionGroups, Ray},
+        math::Isometry,
+        parry::query::RayCast,
+    },
+};
+use phantom_world::{Entity, RigidBody, World};
+use std::collections::VecDeque;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ColliderRecord {
+    pub entity: Entity,
+    pub collider: ColliderHandle,
+    pub position: Isometry<f32>,
+}
+
+/// Collider positions at a point in server time
+#[derive(Debug, Clone)]
+pub struct ColliderSnapshot {
+    pub time: f64,
+    pub colliders: Vec<ColliderRecord>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct RewindHit {
+    pub entity: Entity,
+    pub collider: ColliderHandle,
+    pub point: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+/// Keeps a history of collider positions on the server so hits
+/// can be checked against where targets were when the client fired.
+pub struct LagCompensation {
+    /// Seconds of history to keep, which is also the furthest a hit can be rewound
+    pub history_length: f64,
+    snapshots: VecDeque<ColliderSnapshot>,
+}
+
+impl Default for LagCompensation {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl LagCompensation {
+    pub fn new(history_length: f64) -> Self {
+        Self {
+            history_length,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    pub fn snapshots(&self) -> &VecDeque<ColliderSnapshot> {
+        &self.snapshots
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Records the current collider positions, discarding snapshots older than the history length.
+    /// Call this once per server tick after physics has stepped.
+    pub fn record(&mut self, world: &World, time: f64) {
+        let disabled = world.disabled_entities();
+        let mut colliders = Vec::new();
+        let mut query = <(Entity, &RigidBody)>::query();
+        for (entity, rigid_body) in query.iter(&world.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            let handles = match world.physics.bodies.get(rigid_body.handle) {
+                Some(body) => body.colliders(),
+                None => continue,
+            };
+            for handle in handles.iter() {
+                if let Some(collider) = world.physics.colliders.get(*handle) {
+                    colliders.push(ColliderRecord {
+                        entity: *entity,
+                        collider: *handle,
+                        position: *collider.position(),
+                    });
+                }
+            }
+        }
+
+        while self
+            .snapshots
+            .back()
+            .map_or(false, |snapshot| snapshot.time >= time)
+        {
+            self.snapshots.pop_back();
+        }
+        self.snapshots
+            .push_back(ColliderSnapshot { time, colliders });
+
+        while self
+            .snapshots
+            .front()
+            .map_or(false, |snapshot| time - snapshot.time > self.history_length)
+        {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Collider positions at the given time, interpolated between the surrounding snapshots.
+    /// Times outside the recorded history are clamped to the oldest or newest snapshot.
+    pub fn colliders_at(&self, time: f64) -> Vec<ColliderRecord> {
+        let (first, last) = match (self.snapshots.front(), self.snapshots.back()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        if time <= first.time {
+            return first.colliders.clone();
+        }
+        if time >= last.time {
+            return last.colliders.clone();
+        }
+
+        let next_index = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.time >= time)
+            .unwrap_or(self.snapshots.len() - 1);
+        let previous = &self.snapshots[next_index - 1];
+        let next = &self.snapshots[next_index];
+        let alpha = ((time - previous.time) / (next.time - previous.time)) as f32;
+
+        previous
+            .colliders
+            .iter()
+            .map(|record| {
+                let position = next
+                    .colliders
+                    .iter()
+                    .find(|next_record| next_record.collider == record.collider)
+                    .map_or(record.position, |next_record| {
+                        record.position.lerp_slerp(&next_record.position, alpha)
+                    });
+                ColliderRecord {
+                    position,
+                    ..*record
+                }
+            })
+            .collect()
+    }
+
+    /// Casts a ray against the colliders as they were at the given time
+    pub fn raycast(
+        &self,
+        world: &World,
+        time: f64,
+        origin: glm::Vec3,
+        direction: glm::Vec3,
+        max_distance: f32,
+        groups: InteractionGroups,
+    ) -> Option<RewindHit> {
+        if glm::length2(&direction) <= f32::EPSILON {
+            return None;
+        }
+        let ray = Ray::new(origin.into(), glm::normalize(&direction));
+        let mut closest: Option<RewindHit> = None;
+        for record in self.colliders_at(time) {
+            let collider = match world.physics.colliders.get(record.collider) {
+                Some(collider) => collider,
+                None => continue,
+            };
+            if collider.is_sensor() || !collider.collision_groups().test(groups) {
+                continue;
+            }
+            let max_distance = closest.map_or(max_distance, |hit| hit.distance);
+            if let Some(intersection) =
+                collider
+                    .shape()
+                    .cast_ray_and_get_normal(&record.position, &ray, max_distance, true)
+            {
+                closest = Some(RewindHit {
+                    entity: record.entity,
+                    collider: record.collider,
+                    point: ray.point_at(intersection.toi).coords,
+                    normal: intersection.normal,
+                    distance: intersection.toi,
+                });
+            }
+        }
+        closest
+    }
+
+    /// Checks a client's reported hit by rewinding to when they fired.
+    /// Returns the hit if the ray reaches the claimed target first.
+    pub fn validate_hit(
+        &self,
+        world: &World,
+        time: f64,
+        origin: glm::Vec3,
+        direction: glm::Vec3,
+        max_distance: f32,
+        target: Entity,
+    ) -> Option<RewindHit> {
+        let oldest = self.snapshots.front()?.time;
+        if time < oldest {
+            return None;
+        }
+        self.raycast(
+            world,
+            time,
+            origin,
+            direction,
+            max_distance,
+            InteractionGroups::all(),
+        )
+        .filter(|hit| hit.entity == target)
+    }
+}
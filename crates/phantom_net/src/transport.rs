@@ -0,0 +1,35 @@
+use crate::ClientId;
+use phantom_dependencies::anyhow::Result;
+
+#[derive(Debug, Clone)]
+pub enum TransportEvent {
+    Connected(ClientId),
+    Disconnected(ClientId),
+    Message(ClientId, Vec<u8>),
+}
+
+/// Moves messages between peers, so the rest of phantom_net
+/// does not depend on which protocol carries them.
+pub trait Transport {
+    /// Sends a message to a connected peer
+    fn send(&mut self, client: ClientId, message: &[u8]) -> Result<()>;
+
+    /// Returns everything that happened since the last poll without blocking
+    fn poll(&mut self) -> Result<Vec<TransportEvent>>;
+
+    fn disconnect(&mut self, client: ClientId) -> Result<()>;
+
+    fn connections(&self) -> Vec<ClientId>;
+
+    /// The largest message `send` accepts
+    fn max_message_size(&self) -> usize {
+        usize::MAX
+    }
+
+    fn broadcast(&mut self, message: &[u8]) -> Result<()> {
+        for client in self.connections() {
+            self.send(client, message)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{ClientId, Transport, TransportEvent};
+use phantom_dependencies::anyhow::{bail, Context, Result};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+struct UdpPeer {
+    address: SocketAddr,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+/// Sends each message as a single datagram.
+/// Empty datagrams are keepalives, and peers that stay silent past the timeout are disconnected.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peers: HashMap<ClientId, UdpPeer>,
+    next_client: ClientId,
+    accept_connections: bool,
+    pub timeout: Duration,
+    pub keepalive_interval: Duration,
+}
+
+impl UdpTransport {
+    pub const MAX_DATAGRAM_SIZE: usize = 1200;
+
+    /// Listens for clients on the given address
+    pub fn host(address: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::new(socket, true))
+    }
+
+    /// Connects to a server, which is reported as a peer with the id 0
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .context("Failed to resolve server address!")?;
+        let local_address = if address.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local_address)?;
+        socket.set_nonblocking(true)?;
+        socket.send_to(&[], address)?;
+
+        let mut transport = Self::new(socket, false);
+        let now = Instant::now();
+        transport.peers.insert(
+            0,
+            UdpPeer {
+                address,
+                last_received: now,
+                last_sent: now,
+            },
+        );
+        transport.next_client = 1;
+        Ok(transport)
+    }
+
+    fn new(socket: UdpSocket, accept_connections: bool) -> Self {
+        Self {
+            socket,
+            peers: HashMap::new(),
+            next_client: 0,
+            accept_connections,
+            timeout: Duration::from_secs(10),
+            keepalive_interval: Duration::from_secs(1),
+        }
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    fn client_by_address(&self, address: &SocketAddr) -> Option<ClientId> {
+        self.peers
+            .iter()
+            .find(|(_, peer)| peer.address == *address)
+            .map(|(client, _)| *client)
+    }
+}
+
+impl Transport for UdpTransport {
+    fn max_message_size(&self) -> usize {
+        Self::MAX_DATAGRAM_SIZE
+    }
+
+    fn send(&mut self, client: ClientId, message: &[u8]) -> Result<()> {
+        if message.is_empty() || message.len() > Self::MAX_DATAGRAM_SIZE {
+            bail!(
+                "UDP messages must be between 1 and {} bytes!",
+                Self::MAX_DATAGRAM_SIZE
+            );
+        }
+        let peer = self
+            .peers
+            .get_mut(&client)
+            .context("Failed to find peer!")?;
+        self.socket.send_to(message, peer.address)?;
+        peer.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<TransportEvent>> {
+        let mut events = Vec::new();
+        let mut buffer = [0; Self::MAX_DATAGRAM_SIZE];
+        loop {
+            let (length, address) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => continue,
+                Err(error) => return Err(error.into()),
+            };
+
+            let client = match self.client_by_address(&address) {
+                Some(client) => client,
+                None if self.accept_connections => {
+                    let client = self.next_client;
+                    self.next_client += 1;
+                    let now = Instant::now();
+                    self.peers.insert(
+                        client,
+                        UdpPeer {
+                            address,
+                            last_received: now,
+                            last_sent: now,
+                        },
+                    );
+                    events.push(TransportEvent::Connected(client));
+                    client
+                }
+                None => continue,
+            };
+
+            if let Some(peer) = self.peers.get_mut(&client) {
+                peer.last_received = Instant::now();
+            }
+            if length > 0 {
+                events.push(TransportEvent::Message(client, buffer[..length].to_vec()));
+            }
+        }
+
+        let now = Instant::now();
+        let timed_out = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| now.duration_since(peer.last_received) > self.timeout)
+            .map(|(client, _)| *client)
+            .collect::<Vec<_>>();
+        for client in timed_out {
+            self.peers.remove(&client);
+            events.push(TransportEvent::Disconnected(client));
+        }
+
+        for peer in self.peers.values_mut() {
+            if now.duration_since(peer.last_sent) > self.keepalive_interval {
+                self.socket.send_to(&[], peer.address)?;
+                peer.last_sent = now;
+            }
+        }
+
+        Ok(events)
+    }
+
+    fn disconnect(&mut self, client: ClientId) -> Result<()> {
+        self.peers.remove(&client);
+        Ok(())
+    }
+
+    fn connections(&self) -> Vec<ClientId> {
+        self.peers.keys().copied().collect()
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{ClientId, Transport, TransportEvent};
+use phantom_dependencies::{
+    anyhow::{anyhow, Context, Result},
+    log,
+    tungstenite::{
+        self,
+        handshake::{
+            server::{NoCallback, ServerHandshake},
+            HandshakeError, MidHandshake,
+        },
+        Message, WebSocket,
+    },
+};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+type PendingHandshake = MidHandshake<ServerHandshake<TcpStream, NoCallback>>;
+
+/// Sends messages as binary WebSocket frames over TCP,
+/// for browser clients and networks that block UDP.
+pub struct WebSocketTransport {
+    listener: Option<TcpListener>,
+
+    /// Connections whose handshakes are continued each poll, with when they were accepted
+    handshakes: Vec<(PendingHandshake, Instant)>,
+    sockets: HashMap<ClientId, WebSocket<TcpStream>>,
+    next_client: ClientId,
+}
+
+impl WebSocketTransport {
+    /// Connections that haven't finished their handshake by then are dropped
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Listens for clients on the given address
+    pub fn host(address: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            handshakes: Vec::new(),
+            sockets: HashMap::new(),
+            next_client: 0,
+        })
+    }
+
+    /// Connects to a server url such as `ws://localhost:9001`, which is reported as a peer with the id 0
+    pub fn connect(url: &str) -> Result<Self> {
+        let address = url
+            .trim_start_matches("ws://")
+            .split('/')
+            .next()
+            .context("Failed to parse server address!")?;
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let (socket, _response) = tungstenite::client(url, stream)
+            .map_err(|error| anyhow!("Failed to connect to websocket server: {}", error))?;
+        socket.get_ref().set_nonblocking(true)?;
+
+        let mut sockets = HashMap::new();
+        sockets.insert(0, socket);
+        Ok(Self {
+            listener: None,
+            handshakes: Vec::new(),
+            sockets,
+            next_client: 1,
+        })
+    }
+
+    fn accept_connections(&mut self, events: &mut Vec<TransportEvent>) -> Result<()> {
+        let listener = match self.listener.as_ref() {
+            Some(listener) => listener,
+            None => return Ok(()),
+        };
+        let mut handshakes = std::mem::take(&mut self.handshakes);
+        let mut accepted = Vec::new();
+        loop {
+            let stream = match listener.accept() {
+                Ok((stream, _address)) => stream,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    log::warn!("Failed to accept a websocket connection: {}", error);
+                    break;
+                }
+            };
+
+            // Handshakes are driven without blocking, continuing over later polls
+            // until the client has sent its whole request
+            stream.set_nonblocking(true)?;
+            stream.set_nodelay(true)?;
+            match tungstenite::accept(stream) {
+                Ok(socket) => accepted.push(socket),
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    handshakes.push((handshake, Instant::now()))
+                }
+                Err(HandshakeError::Failure(error)) => {
+                    log::warn!("Rejected websocket connection: {}", error)
+                }
+            }
+        }
+
+        for socket in accepted {
+            self.add_socket(socket, events);
+        }
+        for (handshake, started) in handshakes {
+            match handshake.handshake() {
+                Ok(socket) => self.add_socket(socket, events),
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    if started.elapsed() < Self::HANDSHAKE_TIMEOUT {
+                        self.handshakes.push((handshake, started));
+                    } else {
+                        log::warn!(
+                            "Dropped a websocket connection that didn't finish its handshake"
+                        );
+                    }
+                }
+                Err(HandshakeError::Failure(error)) => {
+                    log::warn!("Rejected websocket connection: {}", error)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_socket(&mut self, socket: WebSocket<TcpStream>, events: &mut Vec<TransportEvent>) {
+        let client = self.next_client;
+        self.next_client += 1;
+        self.sockets.insert(client, socket);
+        events.push(TransportEvent::Connected(client));
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&mut self, client: ClientId, message: &[u8]) -> Result<()> {
+        let socket = self
+            .sockets
+            .get_mut(&client)
+            .context("Failed to find peer!")?;
+        match socket.write_message(Message::Binary(message.to_vec())) {
+            Ok(()) => Ok(()),
+            Err(tungstenite::Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn poll(&mut self) -> Result<Vec<TransportEvent>> {
+        let mut events = Vec::new();
+        self.accept_connections(&mut events)?;
+
+        let mut closed = Vec::new();
+        for (client, socket) in self.sockets.iter_mut() {
+            loop {
+                match socket.read_message() {
+                    Ok(Message::Binary(data)) => {
+                        events.push(TransportEvent::Message(*client, data));
+                    }
+                    Ok(Message::Text(text)) => {
+                        events.push(TransportEvent::Message(*client, text.into_bytes()));
+                    }
+                    Ok(Message::Close(_)) => {
+                        closed.push(*client);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(tungstenite::Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => {
+                        break
+                    }
+                    Err(_) => {
+                        closed.push(*client);
+                        break;
+                    }
+                }
+            }
+
+            match socket.write_pending() {
+                Ok(()) => {}
+                Err(tungstenite::Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => closed.push(*client),
+            }
+        }
+
+        closed.dedup();
+        for client in closed {
+            self.sockets.remove(&client);
+            events.push(TransportEvent::Disconnected(client));
+        }
+
+        Ok(events)
+    }
+
+    fn disconnect(&mut self, client: ClientId) -> Result<()> {
+        if let Some(mut socket) = self.sockets.remove(&client) {
+            let _ = socket.close(None);
+            let _ = socket.write_pending();
+        }
+        Ok(())
+    }
+
+    fn connections(&self) -> Vec<ClientId> {
+        self.sockets.keys().copied().collect()
+    }
+}
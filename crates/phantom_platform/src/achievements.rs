@@ -0,0 +1,88 @@
+use phantom_dependencies::{
+    anyhow::Result,
+    serde::{Deserialize, Serialize},
+    serde_json,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+/// A place achievements and stats are recorded, such as a storefront or a local file
+pub trait AchievementBackend {
+    fn unlock_achievement(&mut self, name: &str) -> Result<()>;
+    fn is_achievement_unlocked(&self, name: &str) -> Result<bool>;
+    fn stat(&self, name: &str) -> Result<i32>;
+    fn set_stat(&mut self, name: &str, value: i32) -> Result<()>;
+
+    /// Writes changes made since the last store
+    fn store(&mut self) -> Result<()>;
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct AchievementRecord {
+    unlocked: BTreeSet<String>,
+    stats: BTreeMap<String, i32>,
+}
+
+/// Achievements and stats kept in a json file,
+/// used on every platform so progress is never lost when offline
+#[derive(Default, Debug)]
+pub struct LocalAchievements {
+    /// Where the record is stored, or `None` to keep it in memory only
+    path: Option<PathBuf>,
+    record: AchievementRecord,
+}
+
+impl LocalAchievements {
+    /// Loads the record at the path, starting an empty one if the file does not exist yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let record = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            AchievementRecord::default()
+        };
+        Ok(Self {
+            path: Some(path),
+            record,
+        })
+    }
+
+    pub fn unlocked_achievements(&self) -> impl Iterator<Item = &str> {
+        self.record.unlocked.iter().map(String::as_str)
+    }
+}
+
+impl AchievementBackend for LocalAchievements {
+    fn unlock_achievement(&mut self, name: &str) -> Result<()> {
+        self.record.unlocked.insert(name.to_string());
+        Ok(())
+    }
+
+    fn is_achievement_unlocked(&self, name: &str) -> Result<bool> {
+        Ok(self.record.unlocked.contains(name))
+    }
+
+    fn stat(&self, name: &str) -> Result<i32> {
+        Ok(self.record.stats.get(name).copied().unwrap_or_default())
+    }
+
+    fn set_stat(&mut self, name: &str, value: i32) -> Result<()> {
+        self.record.stats.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn store(&mut self) -> Result<()> {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&self.record)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use crate::RichPresence;
+use phantom_dependencies::{
+    anyhow::{anyhow, Result},
+    discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient},
+};
+use std::time::UNIX_EPOCH;
+
+/// A connection to the local Discord client for publishing rich presence
+pub struct Discord {
+    client: DiscordIpcClient,
+}
+
+impl Discord {
+    pub fn new(application_id: &str) -> Result<Self> {
+        let mut client = DiscordIpcClient::new(application_id)
+            .map_err(|error| anyhow!("Failed to create discord client: {}", error))?;
+        client
+            .connect()
+            .map_err(|error| anyhow!("Failed to connect to discord: {}", error))?;
+        Ok(Self { client })
+    }
+
+    pub fn set_presence(&mut self, presence: &RichPresence) -> Result<()> {
+        let start = presence
+            .start
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let mut activity = activity::Activity::new()
+            .state(&presence.state)
+            .timestamps(activity::Timestamps::new().start(start));
+        if let Some(details) = presence.details.as_ref() {
+            activity = activity.details(details);
+        }
+        if let Some(party_size) = presence.party_size {
+            activity = activity.party(activity::Party::new().size(party_size));
+        }
+
+        self.client
+            .set_activity(activity)
+            .map_err(|error| anyhow!("Failed to set discord activity: {}", error))
+    }
+
+    pub fn clear_presence(&mut self) -> Result<()> {
+        self.client
+            .clear_activity()
+            .map_err(|error| anyhow!("Failed to clear discord activity: {}", error))
+    }
+}
+
+impl Drop for Discord {
+    fn drop(&mut self) {
+        let _ = self.client.close();
+    }
+}
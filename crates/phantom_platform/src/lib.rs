@@ -0,0 +1,13 @@
+mod achievements;
+#[cfg(feature = "discord")]
+mod discord;
+mod paths;
+mod platform;
+#[cfg(feature = "steam")]
+mod steam;
+
+#[cfg(feature = "discord")]
+pub use self::discord::*;
+#[cfg(feature = "steam")]
+pub use self::steam::*;
+pub use self::{achievements::*, paths::*, platform::*};
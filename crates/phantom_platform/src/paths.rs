@@ -0,0 +1,37 @@
+use phantom_dependencies::anyhow::Result;
+use std::path::PathBuf;
+
+/// The per-user directory for application data,
+/// such as `%APPDATA%` on windows or `~/.local/share` on linux
+#[cfg(not(target_os = "android"))]
+pub fn user_data_directory() -> Result<PathBuf> {
+    use phantom_dependencies::anyhow::Context;
+    use std::env;
+
+    if cfg!(target_os = "windows") {
+        return Ok(PathBuf::from(
+            env::var_os("APPDATA").context("Failed to find the APPDATA directory!")?,
+        ));
+    }
+
+    let home = PathBuf::from(env::var_os("HOME").context("Failed to find the home directory!")?);
+
+    // The home directory of an iOS app is its sandboxed container
+    if cfg!(any(target_os = "macos", target_os = "ios")) {
+        return Ok(home.join("Library").join("Application Support"));
+    }
+
+    Ok(env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local").join("share")))
+}
+
+/// The app's internal storage, since android apps have no home directory
+#[cfg(target_os = "android")]
+pub fn user_data_directory() -> Result<PathBuf> {
+    use phantom_dependencies::ndk_glue;
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let path = ndk_glue::native_activity().internal_data_path();
+    Ok(PathBuf::from(OsStr::from_bytes(path.to_bytes())))
+}
@@ -0,0 +1,202 @@
+use crate::{user_data_directory, AchievementBackend, LocalAchievements};
+use phantom_dependencies::{anyhow::Result, log};
+use std::{path::PathBuf, time::SystemTime};
+
+#[cfg(feature = "discord")]
+use crate::Discord;
+#[cfg(feature = "steam")]
+use crate::Steam;
+
+#[derive(Default, Debug, Clone)]
+pub struct PlatformConfig {
+    /// Enables discord rich presence when the `discord` feature is on
+    pub discord_application_id: Option<String>,
+
+    /// The json file achievements and stats are kept in locally.
+    /// They are only kept in memory when this is `None`.
+    pub achievements_path: Option<PathBuf>,
+}
+
+/// What the player is currently doing, shown to their friends
+#[derive(Debug, Clone)]
+pub struct RichPresence {
+    /// Such as the name of the current scene
+    pub state: String,
+    pub details: Option<String>,
+
+    /// The current and maximum party size
+    pub party_size: Option<[i32; 2]>,
+
+    /// Shown as the elapsed time
+    pub start: SystemTime,
+}
+
+impl RichPresence {
+    pub fn new(state: &str) -> Self {
+        Self {
+            state: state.to_string(),
+            details: None,
+            party_size: None,
+            start: SystemTime::now(),
+        }
+    }
+}
+
+/// Storefront and platform services.
+/// Every method is safe to call when no platform backend is enabled or running.
+#[derive(Default)]
+pub struct Platform {
+    /// Every achievement and stat is recorded here as well as on the storefront
+    pub achievements: LocalAchievements,
+
+    #[cfg(feature = "steam")]
+    pub steam: Option<Steam>,
+
+    #[cfg(feature = "discord")]
+    pub discord: Option<Discord>,
+}
+
+impl Platform {
+    /// Connects to any platform backends that are enabled and running
+    pub fn new(config: &PlatformConfig) -> Self {
+        Self {
+            achievements: match config.achievements_path.as_ref() {
+                Some(path) => LocalAchievements::load(path).unwrap_or_else(|error| {
+                    log::warn!("Failed to load achievements: {}", error);
+                    LocalAchievements::default()
+                }),
+                None => LocalAchievements::default(),
+            },
+
+            #[cfg(feature = "steam")]
+            steam: match Steam::new() {
+                Ok(steam) => Some(steam),
+                Err(error) => {
+                    log::warn!("Steam is unavailable: {}", error);
+                    None
+                }
+            },
+
+            #[cfg(feature = "discord")]
+            discord: config
+                .discord_application_id
+                .as_ref()
+                .and_then(|application_id| match Discord::new(application_id) {
+                    Ok(discord) => Some(discord),
+                    Err(error) => {
+                        log::warn!("Discord is unavailable: {}", error);
+                        None
+                    }
+                }),
+        }
+    }
+
+    /// Dispatches platform callbacks, call this once per frame
+    pub fn update(&mut self) {
+        #[cfg(feature = "steam")]
+        if let Some(steam) = self.steam.as_mut() {
+            steam.update();
+        }
+    }
+
+    /// The storefront's achievement backend, if one is running
+    fn platform_achievements(&mut self) -> Option<&mut dyn AchievementBackend> {
+        #[cfg(feature = "steam")]
+        if let Some(steam) = self.steam.as_mut() {
+            return Some(steam);
+        }
+        None
+    }
+
+    pub fn unlock_achievement(&mut self, name: &str) -> Result<()> {
+        self.achievements.unlock_achievement(name)?;
+        if let Some(backend) = self.platform_achievements() {
+            backend.unlock_achievement(name)?;
+        }
+        self.store_achievements()
+    }
+
+    pub fn is_achievement_unlocked(&self, name: &str) -> bool {
+        self.achievements
+            .is_achievement_unlocked(name)
+            .unwrap_or_default()
+    }
+
+    pub fn stat(&self, name: &str) -> i32 {
+        self.achievements.stat(name).unwrap_or_default()
+    }
+
+    /// Stats are stored with the next achievement unlock or call to `store_achievements`
+    pub fn set_stat(&mut self, name: &str, value: i32) -> Result<()> {
+        self.achievements.set_stat(name, value)?;
+        if let Some(backend) = self.platform_achievements() {
+            backend.set_stat(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Records progress toward an achievement in a stat, unlocking it once the target is reached
+    pub fn progress_achievement(
+        &mut self,
+        name: &str,
+        stat: &str,
+        progress: i32,
+        target: i32,
+    ) -> Result<()> {
+        self.set_stat(stat, progress)?;
+        if progress >= target && !self.is_achievement_unlocked(name) {
+            return self.unlock_achievement(name);
+        }
+        Ok(())
+    }
+
+    pub fn store_achievements(&mut self) -> Result<()> {
+        self.achievements.store()?;
+        if let Some(backend) = self.platform_achievements() {
+            backend.store()?;
+        }
+        Ok(())
+    }
+
+    /// Sets a rich presence key shown to friends, or clears it if the value is `None`
+    pub fn set_rich_presence(&mut self, _key: &str, _value: Option<&str>) {
+        #[cfg(feature = "steam")]
+        if let Some(steam) = self.steam.as_mut() {
+            steam.set_rich_presence(_key, _value);
+        }
+    }
+
+    /// Publishes what the player is doing to every platform that shows it
+    pub fn set_presence(&mut self, presence: &RichPresence) {
+        self.set_rich_presence("status", Some(&presence.state));
+
+        #[cfg(feature = "discord")]
+        if let Some(discord) = self.discord.as_mut() {
+            if let Err(error) = discord.set_presence(presence) {
+                log::warn!("{}", error);
+            }
+        }
+    }
+
+    /// True while a platform overlay is drawn over the app.
+    /// Games should pause and keep presenting frames so the overlay stays responsive.
+    pub fn overlay_active(&self) -> bool {
+        #[cfg(feature = "steam")]
+        if let Some(steam) = self.steam.as_ref() {
+            return steam.overlay_active();
+        }
+        false
+    }
+
+    /// The directory save files should be written to.
+    /// When signed in to a platform, saves are separated per platform user
+    /// so cloud sync does not mix up saves from different accounts.
+    pub fn save_directory(&self, app_name: &str) -> Result<PathBuf> {
+        let directory = user_data_directory()?.join(app_name).join("saves");
+        #[cfg(feature = "steam")]
+        if let Some(steam) = self.steam.as_ref() {
+            return Ok(directory.join(steam.user_id().to_string()));
+        }
+        Ok(directory)
+    }
+}
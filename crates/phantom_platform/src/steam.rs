@@ -0,0 +1,200 @@
+use crate::AchievementBackend;
+use phantom_dependencies::{
+    anyhow::{anyhow, bail, Result},
+    log,
+    steamworks::{CallbackHandle, Client, GameOverlayActivated, SingleClient, UserStatsReceived},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Achievement and stat changes made before Steam sent the user's stats
+#[derive(Default)]
+struct PendingStats {
+    unlocked: BTreeSet<String>,
+    stats: BTreeMap<String, i32>,
+    store: bool,
+}
+
+/// A connection to the running Steam client.
+/// The app id is read from `steam_appid.txt` during development or from Steam when launched through it.
+pub struct Steam {
+    client: Client,
+    single: SingleClient,
+    overlay_active: Arc<AtomicBool>,
+    _overlay_callback: CallbackHandle,
+
+    /// Steam rejects achievement and stat changes until the user's stats are received,
+    /// so changes are queued in `pending` until then
+    stats_ready: Arc<AtomicBool>,
+    stats_failed: Arc<AtomicBool>,
+    _stats_callback: CallbackHandle,
+    pending: PendingStats,
+}
+
+impl Steam {
+    pub fn new() -> Result<Self> {
+        let (client, single) =
+            Client::init().map_err(|error| anyhow!("Failed to initialize steam: {}", error))?;
+
+        let overlay_active = Arc::new(AtomicBool::new(false));
+        let overlay_callback = {
+            let overlay_active = overlay_active.clone();
+            client.register_callback(move |overlay: GameOverlayActivated| {
+                overlay_active.store(overlay.active, Ordering::Relaxed);
+            })
+        };
+
+        let stats_ready = Arc::new(AtomicBool::new(false));
+        let stats_failed = Arc::new(AtomicBool::new(false));
+        let stats_callback = {
+            let stats_ready = stats_ready.clone();
+            let stats_failed = stats_failed.clone();
+            let user = client.user().steam_id();
+            client.register_callback(move |received: UserStatsReceived| {
+                if received.steam_id != user {
+                    return;
+                }
+                match received.result {
+                    Ok(()) => stats_ready.store(true, Ordering::Relaxed),
+                    Err(error) => {
+                        log::warn!("Failed to receive steam stats: {}", error);
+                        stats_failed.store(true, Ordering::Relaxed);
+                    }
+                }
+            })
+        };
+        client.user_stats().request_current_stats();
+
+        Ok(Self {
+            client,
+            single,
+            overlay_active,
+            _overlay_callback: overlay_callback,
+            stats_ready,
+            stats_failed,
+            _stats_callback: stats_callback,
+            pending: PendingStats::default(),
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Runs steam callbacks, requesting the user's stats again if they failed to arrive
+    /// and applying the changes queued while waiting for them
+    pub fn update(&mut self) {
+        self.single.run_callbacks();
+        if self.stats_failed.swap(false, Ordering::Relaxed) {
+            self.client.user_stats().request_current_stats();
+        }
+        if self.stats_ready() {
+            if let Err(error) = self.apply_pending() {
+                log::warn!("Failed to apply queued steam stats: {}", error);
+            }
+        }
+    }
+
+    /// Whether Steam has sent the user's stats, after which achievements and stats can be changed
+    pub fn stats_ready(&self) -> bool {
+        self.stats_ready.load(Ordering::Relaxed)
+    }
+
+    fn apply_pending(&mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        for name in pending.unlocked.iter() {
+            self.unlock_achievement(name)?;
+        }
+        for (name, value) in pending.stats.iter() {
+            self.set_stat(name, *value)?;
+        }
+        if pending.store {
+            self.store()?;
+        }
+        Ok(())
+    }
+
+    pub fn app_id(&self) -> u32 {
+        self.client.utils().app_id().0
+    }
+
+    pub fn user_id(&self) -> u64 {
+        self.client.user().steam_id().raw()
+    }
+
+    pub fn set_rich_presence(&mut self, key: &str, value: Option<&str>) {
+        self.client.friends().set_rich_presence(key, value);
+    }
+
+    pub fn overlay_active(&self) -> bool {
+        self.overlay_active.load(Ordering::Relaxed)
+    }
+}
+
+impl AchievementBackend for Steam {
+    fn unlock_achievement(&mut self, name: &str) -> Result<()> {
+        if !self.stats_ready() {
+            self.pending.unlocked.insert(name.to_string());
+            return Ok(());
+        }
+        self.client
+            .user_stats()
+            .achievement(name)
+            .set()
+            .map_err(|_| anyhow!("Failed to unlock achievement: {}", name))
+    }
+
+    fn is_achievement_unlocked(&self, name: &str) -> Result<bool> {
+        if !self.stats_ready() {
+            if self.pending.unlocked.contains(name) {
+                return Ok(true);
+            }
+            bail!("Steam hasn't sent the user's achievements yet!");
+        }
+        self.client
+            .user_stats()
+            .achievement(name)
+            .get()
+            .map_err(|_| anyhow!("Failed to read achievement: {}", name))
+    }
+
+    fn stat(&self, name: &str) -> Result<i32> {
+        if !self.stats_ready() {
+            if let Some(value) = self.pending.stats.get(name) {
+                return Ok(*value);
+            }
+            bail!("Steam hasn't sent the user's stats yet!");
+        }
+        self.client
+            .user_stats()
+            .get_stat_i32(name)
+            .map_err(|_| anyhow!("Failed to read stat: {}", name))
+    }
+
+    fn set_stat(&mut self, name: &str, value: i32) -> Result<()> {
+        if !self.stats_ready() {
+            self.pending.stats.insert(name.to_string(), value);
+            return Ok(());
+        }
+        self.client
+            .user_stats()
+            .set_stat_i32(name, value)
+            .map_err(|_| anyhow!("Failed to set stat: {}", name))
+    }
+
+    fn store(&mut self) -> Result<()> {
+        if !self.stats_ready() {
+            self.pending.store = true;
+            return Ok(());
+        }
+        self.client
+            .user_stats()
+            .store_stats()
+            .map_err(|_| anyhow!("Failed to store steam stats!"))
+    }
+}
@@ -0,0 +1,71 @@
+use phantom_dependencies::{
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+
+/// Shifts the colors of the rendered world so they can be told apart with color vision deficiencies
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum ColorFilter {
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Default for ColorFilter {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl ColorFilter {
+    pub const ALL: [ColorFilter; 4] = [
+        ColorFilter::None,
+        ColorFilter::Protanopia,
+        ColorFilter::Deuteranopia,
+        ColorFilter::Tritanopia,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Protanopia => "Protanopia",
+            Self::Deuteranopia => "Deuteranopia",
+            Self::Tritanopia => "Tritanopia",
+        }
+    }
+
+    /// Daltonization: the colors lost by simulating the deficiency
+    /// are moved into channels that can still be seen
+    pub fn matrix(&self) -> glm::Mat4 {
+        #[rustfmt::skip]
+        let simulation = match self {
+            Self::None => return glm::Mat4::identity(),
+            // Machado, Oliveira and Fernandes (2009), at full severity
+            Self::Protanopia => glm::mat3(
+                0.152286, 1.052583, -0.204868,
+                0.114503, 0.786281, 0.099216,
+                -0.003882, -0.048116, 1.051998,
+            ),
+            Self::Deuteranopia => glm::mat3(
+                0.367322, 0.860646, -0.227968,
+                0.280085, 0.672501, 0.047413,
+                -0.011820, 0.042940, 0.968881,
+            ),
+            Self::Tritanopia => glm::mat3(
+                1.255528, -0.076749, -0.178779,
+                -0.078411, 0.930809, 0.148602,
+                0.004733, 0.691367, 0.303900,
+            ),
+        };
+        #[rustfmt::skip]
+        let error_shift = glm::mat3(
+            0.0, 0.0, 0.0,
+            0.7, 1.0, 0.0,
+            0.7, 0.0, 1.0,
+        );
+        let correction = glm::Mat3::identity() + error_shift * (glm::Mat3::identity() - simulation);
+        glm::mat3_to_mat4(&correction)
+    }
+}
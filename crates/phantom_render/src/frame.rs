@@ -0,0 +1,185 @@
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::{EntityStore, IntoQuery},
+};
+use phantom_world::{
+    Camera, Cloth, ClothEnvironment, Entity, Light, Material, RenderExtract, Texture, Transform,
+    Vertex, World,
+};
+use std::collections::HashMap;
+
+/// Everything a renderer needs from a world to draw it. Frames own their data,
+/// so they can be built on the thread that owns the world and drawn on another.
+#[derive(Clone)]
+pub struct RenderFrame {
+    pub extract: RenderExtract,
+
+    /// The active camera and its global transform, if the world has one
+    pub camera: Option<(Camera, Transform)>,
+    pub lights: Vec<(Transform, Light)>,
+
+    /// Only carried by frames built after the world's assets changed
+    pub assets: Option<FrameAssets>,
+
+    /// Every enabled cloth, drawn after the world's meshes
+    pub cloth: Vec<ClothFrame>,
+}
+
+#[derive(Clone)]
+pub struct ClothFrame {
+    pub entity: Entity,
+    pub columns: usize,
+    pub rows: usize,
+    pub material_index: Option<usize>,
+    pub simulation: ClothSimulation,
+}
+
+#[derive(Clone)]
+pub enum ClothSimulation {
+    /// Stepped by the world, so the frame carries its particles as vertices
+    Cpu(Vec<Vertex>),
+
+    /// Stepped by the renderer, which keeps the particles on the GPU between frames
+    Gpu {
+        /// Carried when the cloth is first drawn or has been edited,
+        /// restarting its simulation from the component
+        reset: Option<Box<Cloth>>,
+        environment: ClothEnvironment,
+
+        /// The simulation time since the last frame, which is zero while paused
+        delta_time: f32,
+    },
+}
+
+#[derive(Clone)]
+pub enum FrameAssets {
+    /// Geometry or textures were added, removed or edited, so everything is uploaded again
+    All {
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        textures: Vec<Texture>,
+        materials: Vec<Material>,
+    },
+
+    /// Edited materials don't need the geometry and textures uploaded again
+    Materials(Vec<Material>),
+}
+
+/// Builds frames on the thread that owns the world,
+/// copying its assets into a frame only when they have changed since the last one
+#[derive(Default)]
+pub struct FrameBuilder {
+    /// Lengths of the world's vertices, indices, textures and materials in the last frame that carried them,
+    /// which catch a different world being built from without its revisions changing
+    uploaded: (usize, usize, usize, usize),
+
+    /// The world's geometry and textures revisions in the last frame that carried them
+    uploaded_revisions: (u64, u64),
+
+    /// The world's materials revision in the last frame that carried materials
+    uploaded_materials: u64,
+
+    /// Extracts of frames that have been drawn, reused for their allocations and cached transforms
+    extracts: Vec<RenderExtract>,
+
+    /// The world's fixed steps when the last frame was built
+    steps: u64,
+
+    /// Each cloth simulated by the renderer as it was last sent, to tell when it has been edited
+    gpu_cloth: HashMap<Entity, Cloth>,
+}
+
+impl FrameBuilder {
+    pub fn build(&mut self, world: &World) -> Result<RenderFrame> {
+        let mut extract = self.extracts.pop().unwrap_or_default();
+        world.extract_render(&mut extract)?;
+
+        let camera = match world.active_camera() {
+            Ok(entity) => {
+                let camera = world
+                    .ecs
+                    .entry_ref(entity)?
+                    .get_component::<Camera>()?
+                    .clone();
+                Some((camera, world.entity_global_transform(entity)?))
+            }
+            Err(_) => None,
+        };
+
+        let lengths = (
+            world.geometry.vertices.len(),
+            world.geometry.indices.len(),
+            world.textures.len(),
+            world.materials.len(),
+        );
+        let revisions = (world.geometry_revision, world.textures_revision);
+        let assets = if lengths != self.uploaded || revisions != self.uploaded_revisions {
+            self.uploaded = lengths;
+            self.uploaded_revisions = revisions;
+            self.uploaded_materials = world.materials_revision;
+            Some(FrameAssets::All {
+                vertices: world.geometry.vertices.clone(),
+                indices: world.geometry.indices.clone(),
+                textures: world.textures.clone(),
+                materials: world.materials.clone(),
+            })
+        } else if world.materials_revision != self.uploaded_materials {
+            self.uploaded_materials = world.materials_revision;
+            Some(FrameAssets::Materials(world.materials.clone()))
+        } else {
+            None
+        };
+
+        Ok(RenderFrame {
+            extract,
+            camera,
+            lights: world.lights()?,
+            assets,
+            cloth: self.build_cloth(world)?,
+        })
+    }
+
+    fn build_cloth(&mut self, world: &World) -> Result<Vec<ClothFrame>> {
+        let steps = world.steps.saturating_sub(self.steps);
+        self.steps = world.steps;
+        let delta_time = steps as f32 * world.physics.settings.fixed_timestep();
+
+        let disabled = world.disabled_entities();
+        let mut gpu_cloth = HashMap::new();
+        let mut frames = Vec::new();
+        let mut query = <(Entity, &Cloth)>::query();
+        for (entity, cloth) in query.iter(&world.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            let simulation = if world.is_gpu_cloth(cloth) {
+                let reset = match self.gpu_cloth.remove(entity) {
+                    Some(sent) if sent == *cloth => None,
+                    _ => Some(Box::new(cloth.clone())),
+                };
+                gpu_cloth.insert(*entity, cloth.clone());
+                ClothSimulation::Gpu {
+                    reset,
+                    environment: world.cloth_environment(cloth)?,
+                    delta_time,
+                }
+            } else {
+                ClothSimulation::Cpu(cloth.vertices())
+            };
+            frames.push(ClothFrame {
+                entity: *entity,
+                columns: cloth.columns,
+                rows: cloth.rows,
+                material_index: cloth.material_index,
+                simulation,
+            });
+        }
+        self.gpu_cloth = gpu_cloth;
+        Ok(frames)
+    }
+
+    /// Takes back a frame once it has been drawn, so the next frame can reuse its extract
+    pub fn recycle(&mut self, frame: RenderFrame) {
+        self.extracts.push(frame.extract);
+    }
+}
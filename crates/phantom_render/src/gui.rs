@@ -0,0 +1,13 @@
+use phantom_dependencies::egui::{epaint::ClippedMesh, CtxRef, TextureId};
+
+/// Composites the egui gui over a renderer's frames. Each backend provides its own,
+/// and a renderer without one attached draws no gui, such as in shipped games and on servers.
+pub trait GuiRenderer {
+    /// Hands over the gui's meshes and font texture to be drawn in the next frame
+    fn update(&mut self, context: &CtxRef, paint_jobs: Vec<ClippedMesh>);
+
+    /// Uploads an RGBA image so it can be shown in the gui
+    fn create_image(&mut self, width: u32, height: u32, pixels: &[u8]) -> Option<TextureId>;
+
+    fn free_image(&mut self, id: TextureId);
+}
@@ -0,0 +1,73 @@
+use phantom_dependencies::nalgebra_glm as glm;
+
+/// A mesh uploaded to a renderer with `upload_mesh`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MeshHandle(pub(crate) usize);
+
+/// A texture uploaded to a renderer with `upload_texture`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextureHandle(pub(crate) usize);
+
+/// A material created on a renderer with `create_material`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(pub(crate) usize);
+
+/// The textures a material samples. Missing textures are replaced with neutral ones,
+/// so the material's factors are used unchanged.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MaterialTextures {
+    pub color: Option<TextureHandle>,
+    pub metallic_roughness: Option<TextureHandle>,
+    pub normal: Option<TextureHandle>,
+    pub occlusion: Option<TextureHandle>,
+    pub emissive: Option<TextureHandle>,
+}
+
+/// A mesh drawn through handles, lit and viewed like the world's own meshes
+#[derive(Debug, Copy, Clone)]
+pub struct MeshInstance {
+    pub mesh: MeshHandle,
+
+    /// Meshes without a material are drawn with the default material
+    pub material: Option<MaterialHandle>,
+    pub model: glm::Mat4,
+}
+
+/// Hands out handles and recycles the slots of freed ones
+#[derive(Debug)]
+pub(crate) struct Slots<T> {
+    items: Vec<Option<T>>,
+}
+
+impl<T> Default for Slots<T> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<T> Slots<T> {
+    pub fn insert(&mut self, item: T) -> usize {
+        match self.items.iter().position(Option::is_none) {
+            Some(index) => {
+                self.items[index] = Some(item);
+                index
+            }
+            None => {
+                self.items.push(Some(item));
+                self.items.len() - 1
+            }
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.items.get_mut(index).and_then(Option::as_mut)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        self.items.get_mut(index).and_then(Option::take)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    handles::Slots, renderer::Renderer, Backend, MaterialHandle, MaterialTextures, MeshHandle,
+    TextureHandle,
+};
+use phantom_dependencies::anyhow::{Context, Result};
+use phantom_world::{Material, Texture, Vertex};
+
+/// Draws nothing, so apps still run where no graphics adapter is available
+#[derive(Default)]
+pub struct HeadlessRenderer {
+    dimensions: [u32; 2],
+
+    /// Handles are still handed out, so apps that upload resources run unchanged
+    meshes: Slots<()>,
+    textures: Slots<()>,
+    materials: Slots<()>,
+}
+
+impl Renderer for HeadlessRenderer {
+    fn backend(&self) -> Backend {
+        Backend::Headless
+    }
+
+    fn resize(&mut self, dimensions: [u32; 2]) {
+        self.dimensions = dimensions;
+    }
+
+    fn render(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn upload_mesh(&mut self, _vertices: &[Vertex], _indices: &[u32]) -> Result<MeshHandle> {
+        Ok(MeshHandle(self.meshes.insert(())))
+    }
+
+    fn free_mesh(&mut self, mesh: MeshHandle) {
+        self.meshes.remove(mesh.0);
+    }
+
+    fn upload_texture(&mut self, _texture: &Texture, _srgb: bool) -> Result<TextureHandle> {
+        Ok(TextureHandle(self.textures.insert(())))
+    }
+
+    fn free_texture(&mut self, texture: TextureHandle) {
+        self.textures.remove(texture.0);
+    }
+
+    fn create_material(
+        &mut self,
+        _material: &Material,
+        _textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        Ok(MaterialHandle(self.materials.insert(())))
+    }
+
+    fn update_material(&mut self, handle: MaterialHandle, _material: &Material) -> Result<()> {
+        self.materials
+            .get(handle.0)
+            .context("The material has been freed!")?;
+        Ok(())
+    }
+
+    fn free_material(&mut self, material: MaterialHandle) {
+        self.materials.remove(material.0);
+    }
+
+    fn settings(&self) -> Vec<(String, String)> {
+        vec![
+            ("Renderer".to_string(), format!("{:?}", self.backend())),
+            (
+                "Resolution".to_string(),
+                format!("{}x{}", self.dimensions[0], self.dimensions[1]),
+            ),
+        ]
+    }
+}
@@ -1,4 +1,25 @@
+mod color_filter;
+mod frame;
+mod gui;
+mod handles;
+mod headless;
 mod renderer;
+mod settings;
+mod statistics;
+mod threaded;
 mod wgpu;
 
-pub use self::renderer::{create_render_backend, Backend, Renderer};
+pub use self::{
+    color_filter::ColorFilter,
+    frame::{ClothFrame, ClothSimulation, FrameAssets, FrameBuilder, RenderFrame},
+    gui::GuiRenderer,
+    handles::{MaterialHandle, MaterialTextures, MeshHandle, MeshInstance, TextureHandle},
+    renderer::{
+        create_render_backend, create_render_backend_with_fallbacks, Backend, CapturedFrame,
+        Renderer,
+    },
+    settings::{RenderSettings, Tonemapping},
+    statistics::RenderStatistics,
+    threaded::ThreadedRenderer,
+    wgpu::{render_thumbnail, CustomPass, PassContext, Slot, WgpuGuiRenderer},
+};
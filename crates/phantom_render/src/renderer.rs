@@ -1,17 +1,219 @@
-use crate::wgpu::WgpuRenderer;
+use crate::{
+    frame::{FrameBuilder, RenderFrame},
+    gui::GuiRenderer,
+    handles::{MaterialHandle, MaterialTextures, MeshHandle, MeshInstance, TextureHandle},
+    headless::HeadlessRenderer,
+    wgpu::WgpuRenderer,
+    ColorFilter, CustomPass, RenderSettings, RenderStatistics,
+};
 use phantom_dependencies::{
-    anyhow::Result,
-    egui::{epaint::ClippedMesh, CtxRef},
-    raw_window_handle::HasRawWindowHandle,
+    anyhow::{anyhow, bail, Result},
+    egui::TextureId,
+    log,
+    raw_window_handle::{HasRawWindowHandle, RawWindowHandle},
 };
+use phantom_world::{Material, Texture, Vertex, Viewport, World};
+
+/// A rendered frame read back from the GPU as tightly packed RGBA
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Backend {
+    /// wgpu on the best graphics api available, which `WGPU_BACKEND` can override
     Wgpu,
+
+    /// wgpu on OpenGL or DirectX 11, for drivers without Vulkan, Metal or DirectX 12
+    WgpuCompatibility,
+
+    /// wgpu on a software adapter such as llvmpipe or WARP
+    WgpuSoftware,
+
+    /// Draws nothing, for machines without a GPU such as CI servers
+    Headless,
+}
+
+impl Backend {
+    /// Tried in order when the preferred backend can't be created
+    pub const FALLBACKS: [Backend; 3] = [
+        Backend::WgpuCompatibility,
+        Backend::WgpuSoftware,
+        Backend::Headless,
+    ];
 }
 
-pub trait Renderer {
+/// Renderers are sent to the render thread when rendering on one
+pub trait Renderer: Send {
+    /// The backend that was created, which may be a fallback from the one requested
+    fn backend(&self) -> Backend;
+
     fn resize(&mut self, dimensions: [u32; 2]);
-    fn render(&mut self, gui_context: &CtxRef, paint_jobs: Vec<ClippedMesh>) -> Result<()>;
+
+    /// The part of the window the world is drawn in, such as an editor panel.
+    /// The world fills the whole window when this is `None`.
+    fn set_viewport(&mut self, _viewport: Option<Viewport>) {}
+
+    /// Draws the world and then the attached gui, if any
+    fn render(&mut self) -> Result<()>;
+
+    /// Creates the backend's gui renderer so the gui is drawn over each frame
+    fn attach_gui(&mut self) -> Result<()> {
+        bail!("This renderer can't draw a gui!")
+    }
+
+    fn detach_gui(&mut self) {}
+
+    fn gui_renderer(&mut self) -> Option<&mut dyn GuiRenderer> {
+        None
+    }
+
+    /// Releases the window surface, which mobile platforms destroy when the app is suspended
+    fn suspend(&mut self) {}
+
+    /// Recreates the window surface after the app is resumed
+    fn resume(&mut self, _window_handle: RawWindowHandle, _dimensions: [u32; 2]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Prepares the surface for frames to be copied back to the CPU while enabled
+    fn set_frame_capture(&mut self, _enabled: bool) {}
+
+    /// Copies the next rendered frame back to the CPU, if frame capture is enabled.
+    /// The frame is read back asynchronously, arriving in `take_captured_frame` a few frames later.
+    fn capture_next_frame(&mut self) {}
+
+    /// The most recently captured frame, if frame capture is enabled
+    fn take_captured_frame(&mut self) -> Option<CapturedFrame> {
+        None
+    }
+
+    /// Uploads an RGBA image so it can be shown in the gui, if a gui renderer is attached
+    fn create_gui_image(&mut self, width: u32, height: u32, pixels: &[u8]) -> Option<TextureId> {
+        self.gui_renderer()?.create_image(width, height, pixels)
+    }
+
+    fn free_gui_image(&mut self, id: TextureId) {
+        if let Some(gui_renderer) = self.gui_renderer() {
+            gui_renderer.free_image(id);
+        }
+    }
+
+    /// Describes the renderer's current configuration, such as the adapter and surface format
+    fn settings(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Prepares the world to be drawn in the next frame,
+    /// uploading its geometry and textures again if they have changed
+    fn update_world(&mut self, _world: &World) -> Result<()> {
+        Ok(())
+    }
+
+    /// Prepares a frame built with a `FrameBuilder`, which may have been built on another thread
+    fn update_frame(&mut self, _frame: &RenderFrame) -> Result<()> {
+        Ok(())
+    }
+
+    /// Recolors the rendered world for players with color vision deficiencies
+    fn set_color_filter(&mut self, _color_filter: ColorFilter) {}
+
+    fn render_settings(&self) -> RenderSettings {
+        RenderSettings::default()
+    }
+
+    fn set_render_settings(&mut self, _settings: RenderSettings) {}
+
+    /// Clears each frame to transparent black instead of the background color,
+    /// so a window created with transparency shows the desktop behind it
+    fn set_transparent(&mut self, _transparent: bool) {}
+
+    /// Sample counts the adapter can multisample with
+    fn supported_sample_counts(&self) -> Vec<u32> {
+        vec![1]
+    }
+
+    /// Inserts a pass that runs after the world and before the gui,
+    /// replacing any custom pass with the same name
+    fn add_render_pass(&mut self, _pass: Box<dyn CustomPass>) {}
+
+    fn remove_render_pass(&mut self, _name: &str) {}
+
+    /// Names of the passes rendered each frame, in order
+    fn render_passes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn statistics(&self) -> RenderStatistics {
+        RenderStatistics::default()
+    }
+
+    /// Renders a world other than the one being shown from its active camera,
+    /// for previews such as asset thumbnails
+    fn render_thumbnail(
+        &mut self,
+        world: &World,
+        width: u32,
+        height: u32,
+    ) -> Result<CapturedFrame> {
+        let frame = FrameBuilder::default().build(world)?;
+        self.render_frame_thumbnail(&frame, width, height)
+    }
+
+    /// Renders a thumbnail of a frame that carries all of its world's assets,
+    /// such as one from a new `FrameBuilder`
+    fn render_frame_thumbnail(
+        &mut self,
+        _frame: &RenderFrame,
+        _width: u32,
+        _height: u32,
+    ) -> Result<CapturedFrame> {
+        bail!("This renderer can't render thumbnails!")
+    }
+
+    /// Uploads geometry that can be drawn with `set_mesh_instances`
+    fn upload_mesh(&mut self, _vertices: &[Vertex], _indices: &[u32]) -> Result<MeshHandle> {
+        bail!("This renderer can't upload meshes!")
+    }
+
+    fn free_mesh(&mut self, _mesh: MeshHandle) {}
+
+    /// Uploads a texture for materials to sample. Color textures are stored in sRGB.
+    fn upload_texture(&mut self, _texture: &Texture, _srgb: bool) -> Result<TextureHandle> {
+        bail!("This renderer can't upload textures!")
+    }
+
+    fn free_texture(&mut self, _texture: TextureHandle) {}
+
+    /// Creates a material from the factors of a world material and the textures it samples.
+    /// The world material's texture indices are ignored.
+    fn create_material(
+        &mut self,
+        _material: &Material,
+        _textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        bail!("This renderer can't create materials!")
+    }
+
+    /// Replaces a material's factors, keeping its textures
+    fn update_material(&mut self, _handle: MaterialHandle, _material: &Material) -> Result<()> {
+        bail!("This renderer can't update materials!")
+    }
+
+    fn free_material(&mut self, _material: MaterialHandle) {}
+
+    /// Replaces the meshes drawn through handles, which are drawn alongside the world
+    /// from its active camera and lit by its lights
+    fn set_mesh_instances(&mut self, _instances: Vec<MeshInstance>) {}
+
+    /// Whether cloth that prefers the GPU is simulated by the renderer from each frame,
+    /// instead of by the world on the cpu
+    fn simulates_cloth(&self) -> bool {
+        false
+    }
 }
 
 pub fn create_render_backend(
@@ -19,10 +221,42 @@ pub fn create_render_backend(
     window_handle: &impl HasRawWindowHandle,
     dimensions: &[u32; 2],
 ) -> Result<Box<dyn Renderer>> {
-    match backend {
-        Backend::Wgpu => {
-            let backend = WgpuRenderer::new(window_handle, dimensions)?;
-            Ok(Box::new(backend) as Box<dyn Renderer>)
+    let renderer: Box<dyn Renderer> = match backend {
+        Backend::Wgpu | Backend::WgpuCompatibility | Backend::WgpuSoftware => Box::new(
+            WgpuRenderer::with_backend(window_handle, dimensions, *backend)?,
+        ),
+        Backend::Headless => Box::new(HeadlessRenderer::default()),
+    };
+    Ok(renderer)
+}
+
+/// Creates the first backend in the list that works, logging why the others failed
+pub fn create_render_backend_with_fallbacks(
+    backends: &[Backend],
+    window_handle: &impl HasRawWindowHandle,
+    dimensions: &[u32; 2],
+) -> Result<Box<dyn Renderer>> {
+    let mut failures = Vec::new();
+    for backend in backends.iter() {
+        match create_render_backend(backend, window_handle, dimensions) {
+            Ok(renderer) => {
+                if !failures.is_empty() {
+                    log::warn!("Falling back to the {:?} render backend", backend);
+                }
+                return Ok(renderer);
+            }
+            Err(error) => {
+                log::warn!(
+                    "Failed to create the {:?} render backend: {}",
+                    backend,
+                    error
+                );
+                failures.push(format!("{:?}: {}", backend, error));
+            }
         }
     }
+    Err(anyhow!(
+        "Failed to create any render backend! {}",
+        failures.join(", ")
+    ))
 }
@@ -0,0 +1,98 @@
+use phantom_dependencies::serde::{Deserialize, Serialize};
+
+/// Maps the world's high dynamic range colors to the range the display can show
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum Tonemapping {
+    /// Clips colors brighter than white
+    None,
+    Reinhard,
+
+    /// A fitted approximation of the filmic curve from the Academy Color Encoding System
+    Aces,
+}
+
+impl Tonemapping {
+    pub const ALL: [Self; 3] = [Self::None, Self::Reinhard, Self::Aces];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Reinhard => "reinhard",
+            Self::Aces => "aces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|tonemapping| tonemapping.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Quality options for the renderer, which games usually expose in their graphics menu
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde", default)]
+pub struct RenderSettings {
+    /// Whether the first directional light in the world casts shadows
+    pub shadows: bool,
+
+    /// Width and height of the shadow map in texels
+    pub shadow_map_resolution: u32,
+
+    /// Depth offset applied when comparing against the shadow map, to avoid shadow acne.
+    /// Too much bias detaches shadows from the objects casting them.
+    pub shadow_bias: f32,
+
+    /// Half the width of the area around the camera that receives shadows
+    pub shadow_distance: f32,
+
+    /// Skips meshes whose bounding box is outside the camera's view
+    pub frustum_culling: bool,
+
+    /// Samples per pixel for multisample antialiasing, one of 1, 2, 4 or 8.
+    /// Counts the adapter doesn't support fall back to the nearest lower count.
+    pub sample_count: u32,
+
+    pub tonemapping: Tonemapping,
+
+    /// Scales the world's colors before they are tonemapped
+    pub exposure: f32,
+
+    /// Makes bright areas glow by blurring them over their surroundings
+    pub bloom: bool,
+
+    /// Colors brighter than this glow when bloom is enabled
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+
+    /// Fast approximate antialiasing, which smooths edges after the frame is tonemapped
+    pub fxaa: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            shadows: true,
+            shadow_map_resolution: 2048,
+            shadow_bias: 0.002,
+            shadow_distance: 20.0,
+            frustum_culling: true,
+            sample_count: 1,
+            tonemapping: Tonemapping::None,
+            exposure: 1.0,
+            bloom: false,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+            fxaa: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Whether the world is rendered into a high dynamic range target and tonemapped,
+    /// which is only needed when an effect works with colors brighter than white
+    pub fn hdr(&self) -> bool {
+        self.tonemapping != Tonemapping::None || self.bloom || self.exposure != 1.0
+    }
+}
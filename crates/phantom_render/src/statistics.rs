@@ -0,0 +1,9 @@
+/// Counts from the last frame the world was prepared for, for profiling scenes
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderStatistics {
+    /// Draw calls submitted to the main pass
+    pub visible_draw_calls: usize,
+
+    /// Draw calls skipped because their bounding box was outside the camera's frustum
+    pub culled_draw_calls: usize,
+}
@@ -0,0 +1,503 @@
+use crate::{
+    renderer::Renderer, Backend, CapturedFrame, ClothFrame, ClothSimulation, ColorFilter,
+    CustomPass, FrameAssets, FrameBuilder, GuiRenderer, MaterialHandle, MaterialTextures,
+    MeshHandle, MeshInstance, RenderFrame, RenderSettings, RenderStatistics, TextureHandle,
+};
+use phantom_dependencies::{
+    anyhow::{anyhow, Result},
+    egui::{epaint::ClippedMesh, CtxRef, TextureId},
+    log,
+    raw_window_handle::RawWindowHandle,
+};
+use phantom_world::{Material, Texture, Vertex, Viewport, World};
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+/// How many frames the render thread can still be drawing when the next one is sent.
+/// With one, frame N is drawn while frame N + 1 is simulated.
+const FRAMES_IN_FLIGHT: usize = 1;
+
+type Call = Box<dyn FnOnce(&mut dyn Renderer) + Send>;
+
+enum Command {
+    /// Runs on the render thread in order with the frames around it
+    Call(Call),
+
+    Frame {
+        frame: Option<RenderFrame>,
+        gui: Option<(CtxRef, Vec<ClippedMesh>)>,
+    },
+
+    /// Sent when the renderer is dropped, after every frame already sent
+    Shutdown,
+}
+
+/// Sent back by the render thread once a frame has been submitted
+struct CompletedFrame {
+    result: Result<()>,
+
+    /// Handed back so its extract can be reused
+    frame: Option<RenderFrame>,
+    statistics: RenderStatistics,
+}
+
+/// Encodes and submits frames on a dedicated render thread, so the next frame
+/// is simulated while the last one is drawn. Worlds are extracted into frames
+/// on the calling thread, and `render` waits for the frame before last to finish.
+pub struct ThreadedRenderer {
+    commands: Sender<Command>,
+    completed: Receiver<CompletedFrame>,
+    thread: Option<JoinHandle<()>>,
+
+    frame_builder: FrameBuilder,
+
+    /// Built by `update_world` and sent to the render thread by the next `render`
+    pending_frame: Option<RenderFrame>,
+    frames_in_flight: usize,
+
+    /// Captured frames are only asked for while capturing, so rendering isn't waited on every frame
+    frame_capture: bool,
+
+    gui: ThreadedGuiRenderer,
+
+    /// Cached so they can be read without waiting on the render thread
+    backend: Backend,
+    render_settings: RenderSettings,
+    supported_sample_counts: Vec<u32>,
+    simulates_cloth: bool,
+    statistics: RenderStatistics,
+}
+
+impl ThreadedRenderer {
+    /// Moves the renderer onto a new render thread
+    pub fn new(renderer: Box<dyn Renderer>) -> Result<Self> {
+        let backend = renderer.backend();
+        let render_settings = renderer.render_settings();
+        let supported_sample_counts = renderer.supported_sample_counts();
+        let simulates_cloth = renderer.simulates_cloth();
+        let statistics = renderer.statistics();
+
+        let (commands, command_receiver) = channel();
+        let (completed_sender, completed) = channel();
+        let thread = thread::Builder::new()
+            .name("render".to_string())
+            .spawn(move || Self::run(renderer, command_receiver, completed_sender))?;
+
+        Ok(Self {
+            gui: ThreadedGuiRenderer {
+                commands: commands.clone(),
+                attached: false,
+                pending: None,
+            },
+            commands,
+            completed,
+            thread: Some(thread),
+            frame_builder: FrameBuilder::default(),
+            pending_frame: None,
+            frames_in_flight: 0,
+            frame_capture: false,
+            backend,
+            render_settings,
+            supported_sample_counts,
+            simulates_cloth,
+            statistics,
+        })
+    }
+
+    fn run(
+        mut renderer: Box<dyn Renderer>,
+        commands: Receiver<Command>,
+        completed: Sender<CompletedFrame>,
+    ) {
+        for command in commands.iter() {
+            match command {
+                Command::Call(call) => call(renderer.as_mut()),
+                Command::Frame { frame, gui } => {
+                    let result = Self::draw(renderer.as_mut(), frame.as_ref(), gui);
+                    let completed_frame = CompletedFrame {
+                        result,
+                        frame,
+                        statistics: renderer.statistics(),
+                    };
+                    if completed.send(completed_frame).is_err() {
+                        break;
+                    }
+                }
+                Command::Shutdown => break,
+            }
+        }
+    }
+
+    fn draw(
+        renderer: &mut dyn Renderer,
+        frame: Option<&RenderFrame>,
+        gui: Option<(CtxRef, Vec<ClippedMesh>)>,
+    ) -> Result<()> {
+        if let Some(frame) = frame {
+            renderer.update_frame(frame)?;
+        }
+        if let (Some((context, paint_jobs)), Some(gui_renderer)) = (gui, renderer.gui_renderer()) {
+            gui_renderer.update(&context, paint_jobs);
+        }
+        renderer.render()
+    }
+
+    /// Replaces the frame waiting to be sent, keeping any assets it carried
+    /// that the new frame doesn't, so they are still uploaded
+    fn queue_frame(&mut self, mut frame: RenderFrame) {
+        if let Some(mut previous) = self.pending_frame.take() {
+            frame.assets = match (previous.assets.take(), frame.assets.take()) {
+                (
+                    Some(FrameAssets::All {
+                        vertices,
+                        indices,
+                        textures,
+                        ..
+                    }),
+                    Some(FrameAssets::Materials(materials)),
+                ) => Some(FrameAssets::All {
+                    vertices,
+                    indices,
+                    textures,
+                    materials,
+                }),
+                (previous, None) => previous,
+                (_, next) => next,
+            };
+            Self::merge_cloth(&mut frame, &mut previous);
+            self.frame_builder.recycle(previous);
+        }
+        self.pending_frame = Some(frame);
+    }
+
+    /// Carries the simulation time and any restarts of a replaced frame's GPU cloth into the next frame
+    fn merge_cloth(frame: &mut RenderFrame, previous: &mut RenderFrame) {
+        for cloth in frame.cloth.iter_mut() {
+            let previous = previous
+                .cloth
+                .iter_mut()
+                .find(|previous| previous.entity == cloth.entity);
+            if let (
+                ClothSimulation::Gpu {
+                    reset, delta_time, ..
+                },
+                Some(ClothFrame {
+                    simulation:
+                        ClothSimulation::Gpu {
+                            reset: previous_reset,
+                            delta_time: previous_delta_time,
+                            ..
+                        },
+                    ..
+                }),
+            ) = (&mut cloth.simulation, previous)
+            {
+                if reset.is_none() {
+                    *reset = previous_reset.take();
+                    *delta_time += *previous_delta_time;
+                }
+            }
+        }
+    }
+
+    /// The sync barrier, waiting for the oldest frame in flight to be submitted
+    fn wait_for_frame(&mut self) -> Result<()> {
+        let completed_frame = self.completed.recv().map_err(|_| render_thread_stopped())?;
+        self.frames_in_flight -= 1;
+        self.statistics = completed_frame.statistics;
+        if let Some(frame) = completed_frame.frame {
+            self.frame_builder.recycle(frame);
+        }
+        completed_frame.result
+    }
+
+    fn send(&self, call: impl FnOnce(&mut dyn Renderer) + Send + 'static) {
+        send(&self.commands, call);
+    }
+
+    fn call<T: Send + 'static>(
+        &self,
+        call: impl FnOnce(&mut dyn Renderer) -> T + Send + 'static,
+    ) -> Result<T> {
+        self::call(&self.commands, call)
+    }
+}
+
+impl Drop for ThreadedRenderer {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The render thread panicked!");
+            }
+        }
+    }
+}
+
+impl Renderer for ThreadedRenderer {
+    fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    fn resize(&mut self, dimensions: [u32; 2]) {
+        self.send(move |renderer| renderer.resize(dimensions));
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let command = Command::Frame {
+            frame: self.pending_frame.take(),
+            gui: self.gui.pending.take(),
+        };
+        self.commands
+            .send(command)
+            .map_err(|_| render_thread_stopped())?;
+        self.frames_in_flight += 1;
+        while self.frames_in_flight > FRAMES_IN_FLIGHT {
+            self.wait_for_frame()?;
+        }
+        Ok(())
+    }
+
+    fn attach_gui(&mut self) -> Result<()> {
+        self.call(|renderer| renderer.attach_gui())??;
+        self.gui.attached = true;
+        Ok(())
+    }
+
+    fn detach_gui(&mut self) {
+        self.gui.attached = false;
+        self.gui.pending = None;
+        self.send(|renderer| renderer.detach_gui());
+    }
+
+    fn gui_renderer(&mut self) -> Option<&mut dyn GuiRenderer> {
+        if self.gui.attached {
+            Some(&mut self.gui)
+        } else {
+            None
+        }
+    }
+
+    fn suspend(&mut self) {
+        self.send(|renderer| renderer.suspend());
+    }
+
+    fn resume(&mut self, window_handle: RawWindowHandle, dimensions: [u32; 2]) -> Result<()> {
+        let window_handle = SendWindowHandle(window_handle);
+        self.call(move |renderer| renderer.resume(window_handle.0, dimensions))?
+    }
+
+    fn set_frame_capture(&mut self, enabled: bool) {
+        if enabled == self.frame_capture {
+            return;
+        }
+        self.frame_capture = enabled;
+        self.send(move |renderer| renderer.set_frame_capture(enabled));
+    }
+
+    fn capture_next_frame(&mut self) {
+        if self.frame_capture {
+            self.send(|renderer| renderer.capture_next_frame());
+        }
+    }
+
+    fn take_captured_frame(&mut self) -> Option<CapturedFrame> {
+        if !self.frame_capture {
+            return None;
+        }
+        self.call(|renderer| renderer.take_captured_frame())
+            .ok()
+            .flatten()
+    }
+
+    fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = self
+            .call(|renderer| renderer.settings())
+            .unwrap_or_default();
+        settings.push(("Render Thread".to_string(), true.to_string()));
+        settings
+    }
+
+    fn update_world(&mut self, world: &World) -> Result<()> {
+        let frame = self.frame_builder.build(world)?;
+        self.queue_frame(frame);
+        Ok(())
+    }
+
+    fn update_frame(&mut self, frame: &RenderFrame) -> Result<()> {
+        self.queue_frame(frame.clone());
+        Ok(())
+    }
+
+    fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.send(move |renderer| renderer.set_viewport(viewport));
+    }
+
+    fn set_color_filter(&mut self, color_filter: ColorFilter) {
+        self.send(move |renderer| renderer.set_color_filter(color_filter));
+    }
+
+    fn render_settings(&self) -> RenderSettings {
+        self.render_settings
+    }
+
+    fn set_render_settings(&mut self, settings: RenderSettings) {
+        // The renderer may adjust the settings, such as to a supported sample count
+        self.render_settings = self
+            .call(move |renderer| {
+                renderer.set_render_settings(settings);
+                renderer.render_settings()
+            })
+            .unwrap_or(settings);
+    }
+
+    fn set_transparent(&mut self, transparent: bool) {
+        self.send(move |renderer| renderer.set_transparent(transparent));
+    }
+
+    fn supported_sample_counts(&self) -> Vec<u32> {
+        self.supported_sample_counts.clone()
+    }
+
+    fn add_render_pass(&mut self, pass: Box<dyn CustomPass>) {
+        self.send(move |renderer| renderer.add_render_pass(pass));
+    }
+
+    fn remove_render_pass(&mut self, name: &str) {
+        let name = name.to_string();
+        self.send(move |renderer| renderer.remove_render_pass(&name));
+    }
+
+    fn render_passes(&self) -> Vec<String> {
+        self.call(|renderer| renderer.render_passes())
+            .unwrap_or_default()
+    }
+
+    /// Statistics of the last frame the render thread finished
+    fn statistics(&self) -> RenderStatistics {
+        self.statistics
+    }
+
+    fn render_thumbnail(
+        &mut self,
+        world: &World,
+        width: u32,
+        height: u32,
+    ) -> Result<CapturedFrame> {
+        let frame = FrameBuilder::default().build(world)?;
+        self.call(move |renderer| renderer.render_frame_thumbnail(&frame, width, height))?
+    }
+
+    fn render_frame_thumbnail(
+        &mut self,
+        frame: &RenderFrame,
+        width: u32,
+        height: u32,
+    ) -> Result<CapturedFrame> {
+        let frame = frame.clone();
+        self.call(move |renderer| renderer.render_frame_thumbnail(&frame, width, height))?
+    }
+
+    fn upload_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<MeshHandle> {
+        let (vertices, indices) = (vertices.to_vec(), indices.to_vec());
+        self.call(move |renderer| renderer.upload_mesh(&vertices, &indices))?
+    }
+
+    fn free_mesh(&mut self, mesh: MeshHandle) {
+        self.send(move |renderer| renderer.free_mesh(mesh));
+    }
+
+    fn upload_texture(&mut self, texture: &Texture, srgb: bool) -> Result<TextureHandle> {
+        let texture = texture.clone();
+        self.call(move |renderer| renderer.upload_texture(&texture, srgb))?
+    }
+
+    fn free_texture(&mut self, texture: TextureHandle) {
+        self.send(move |renderer| renderer.free_texture(texture));
+    }
+
+    fn create_material(
+        &mut self,
+        material: &Material,
+        textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        let (material, textures) = (material.clone(), *textures);
+        self.call(move |renderer| renderer.create_material(&material, &textures))?
+    }
+
+    fn update_material(&mut self, handle: MaterialHandle, material: &Material) -> Result<()> {
+        let material = material.clone();
+        self.call(move |renderer| renderer.update_material(handle, &material))?
+    }
+
+    fn free_material(&mut self, material: MaterialHandle) {
+        self.send(move |renderer| renderer.free_material(material));
+    }
+
+    fn set_mesh_instances(&mut self, instances: Vec<MeshInstance>) {
+        self.send(move |renderer| renderer.set_mesh_instances(instances));
+    }
+
+    fn simulates_cloth(&self) -> bool {
+        self.simulates_cloth
+    }
+}
+
+/// Holds the gui until the next frame is sent, so it is drawn over the world it was built with
+struct ThreadedGuiRenderer {
+    commands: Sender<Command>,
+    attached: bool,
+    pending: Option<(CtxRef, Vec<ClippedMesh>)>,
+}
+
+impl GuiRenderer for ThreadedGuiRenderer {
+    fn update(&mut self, context: &CtxRef, paint_jobs: Vec<ClippedMesh>) {
+        self.pending = Some((context.clone(), paint_jobs));
+    }
+
+    fn create_image(&mut self, width: u32, height: u32, pixels: &[u8]) -> Option<TextureId> {
+        let pixels = pixels.to_vec();
+        call(&self.commands, move |renderer| {
+            renderer.create_gui_image(width, height, &pixels)
+        })
+        .ok()
+        .flatten()
+    }
+
+    fn free_image(&mut self, id: TextureId) {
+        send(&self.commands, move |renderer| renderer.free_gui_image(id));
+    }
+}
+
+/// Window handles aren't `Send`, but the window outlives the renderer
+/// and the render thread only uses the handle to recreate its surface
+struct SendWindowHandle(RawWindowHandle);
+
+unsafe impl Send for SendWindowHandle {}
+
+fn render_thread_stopped() -> phantom_dependencies::anyhow::Error {
+    anyhow!("The render thread has stopped!")
+}
+
+/// Runs a call on the render thread without waiting for it
+fn send(commands: &Sender<Command>, call: impl FnOnce(&mut dyn Renderer) + Send + 'static) {
+    if commands.send(Command::Call(Box::new(call))).is_err() {
+        log::error!("{}", render_thread_stopped());
+    }
+}
+
+/// Runs a call on the render thread and waits for its result
+fn call<T: Send + 'static>(
+    commands: &Sender<Command>,
+    call: impl FnOnce(&mut dyn Renderer) -> T + Send + 'static,
+) -> Result<T> {
+    let (sender, receiver) = channel();
+    commands
+        .send(Command::Call(Box::new(move |renderer| {
+            let _ = sender.send(call(renderer));
+        })))
+        .map_err(|_| render_thread_stopped())?;
+    receiver.recv().map_err(|_| render_thread_stopped())
+}
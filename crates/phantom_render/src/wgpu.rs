@@ -1,31 +1,89 @@
+mod capture;
+mod cloth;
+mod graph;
+mod gui;
+mod handles;
+mod post;
+mod shaders;
+mod shadow;
 mod texture;
+mod thumbnail;
 mod uniform;
 mod world;
 
-use crate::renderer::Renderer;
+use crate::{
+    renderer::Renderer, Backend, CapturedFrame, ColorFilter, FrameBuilder, GuiRenderer,
+    MaterialHandle, MaterialTextures, MeshHandle, MeshInstance, RenderFrame, RenderSettings,
+    RenderStatistics, TextureHandle,
+};
+use capture::FrameCapture;
+use graph::{BuiltinPass, RenderGraph};
+pub use graph::{CustomPass, PassContext, Slot};
+pub use gui::WgpuGuiRenderer;
 use phantom_dependencies::{
     anyhow::{Context, Result},
-    egui::{epaint::ClippedMesh, CtxRef},
-    egui_wgpu_backend::{RenderPass as GuiRenderPass, ScreenDescriptor},
     log, pollster,
-    raw_window_handle::HasRawWindowHandle,
+    raw_window_handle::{HasRawWindowHandle, RawWindowHandle},
     wgpu::{self, Device, Queue, Surface, SurfaceConfiguration},
 };
+use phantom_world::{Material, Vertex, Viewport, World};
+use shaders::{
+    ShaderManager, BLIT_SHADER, BLOOM_SHADER, CLOTH_SHADER, COLOR_FILTER_SHADER, FXAA_SHADER,
+    TONEMAP_SHADER, WORLD_SHADER,
+};
 use texture::Texture;
+pub use thumbnail::render_thumbnail;
 use world::WorldRender;
 
+struct WindowHandle(RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
 pub struct WgpuRenderer {
-    surface: Surface,
+    backend: Backend,
+    instance: wgpu::Instance,
+    adapter_info: wgpu::AdapterInfo,
+
+    /// The surface is released while the app is suspended on mobile platforms
+    surface: Option<Surface>,
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
     dimensions: [u32; 2],
+
+    /// Where the world is drawn within the surface, which is all of it when `None`
+    viewport: Option<Viewport>,
     depth_texture: Texture,
-    gui_renderpass: GuiRenderPass,
+
+    /// The world is rendered into this and resolved into the surface when multisampling
+    multisampled_framebuffer: Option<wgpu::TextureView>,
+    supported_sample_counts: Vec<u32>,
+    transparent: bool,
+
+    /// Draws the gui over each frame while attached
+    gui: Option<WgpuGuiRenderer>,
     world_render: WorldRender,
+
+    /// Builds frames from the worlds passed to `update_world`
+    frame_builder: FrameBuilder,
+    graph: RenderGraph,
+    shader_manager: ShaderManager,
+    frame_capture: Option<FrameCapture>,
+
+    /// Whether the next rendered frame is copied back, set by `capture_next_frame`
+    capture_requested: bool,
+    captured_frame: Option<CapturedFrame>,
 }
 
 impl Renderer for WgpuRenderer {
+    fn backend(&self) -> Backend {
+        self.backend
+    }
+
     fn resize(&mut self, dimensions: [u32; 2]) {
         if dimensions[0] == 0 || dimensions[1] == 0 {
             return;
@@ -33,17 +91,20 @@ impl Renderer for WgpuRenderer {
         self.dimensions = dimensions;
         self.config.width = dimensions[0];
         self.config.height = dimensions[1];
-        self.surface.configure(&self.device, &self.config);
-        self.depth_texture = Texture::create_depth_texture(
-            &self.device,
-            dimensions[0],
-            dimensions[1],
-            "Depth Texture",
-        );
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&self.device, &self.config);
+        }
+        self.graph.resize(dimensions);
+        self.create_render_targets();
+    }
+
+    fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.viewport = viewport;
     }
 
-    fn render(&mut self, gui_context: &CtxRef, paint_jobs: Vec<ClippedMesh>) -> Result<()> {
-        match self.render_frame(gui_context, paint_jobs) {
+    fn render(&mut self) -> Result<()> {
+        self.reload_shaders();
+        match self.render_frame() {
             Ok(_) => {}
             // Recreate the swapchain if lost
             Err(wgpu::SurfaceError::Lost) => self.resize(self.dimensions),
@@ -54,6 +115,219 @@ impl Renderer for WgpuRenderer {
         }
         Ok(())
     }
+
+    fn set_frame_capture(&mut self, enabled: bool) {
+        if enabled == self.frame_capture.is_some() {
+            return;
+        }
+        // Reading back the surface requires it to be configured as a copy source
+        self.config.usage = if enabled {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
+        self.frame_capture = enabled.then(FrameCapture::default);
+        self.captured_frame = None;
+        self.resize(self.dimensions);
+    }
+
+    fn capture_next_frame(&mut self) {
+        self.capture_requested = true;
+    }
+
+    fn take_captured_frame(&mut self) -> Option<CapturedFrame> {
+        self.captured_frame.take()
+    }
+
+    fn attach_gui(&mut self) -> Result<()> {
+        if self.gui.is_none() {
+            self.gui = Some(WgpuGuiRenderer::new(&self.device, self.config.format));
+        }
+        Ok(())
+    }
+
+    fn detach_gui(&mut self) {
+        self.gui = None;
+    }
+
+    fn gui_renderer(&mut self) -> Option<&mut dyn GuiRenderer> {
+        self.gui.as_mut().map(|gui| gui as &mut dyn GuiRenderer)
+    }
+
+    fn settings(&self) -> Vec<(String, String)> {
+        vec![
+            ("Renderer".to_string(), format!("{:?}", self.backend)),
+            ("Adapter".to_string(), self.adapter_info.name.to_string()),
+            (
+                "Backend".to_string(),
+                format!("{:?}", self.adapter_info.backend),
+            ),
+            (
+                "Device Type".to_string(),
+                format!("{:?}", self.adapter_info.device_type),
+            ),
+            (
+                "Surface Format".to_string(),
+                format!("{:?}", self.config.format),
+            ),
+            (
+                "Present Mode".to_string(),
+                format!("{:?}", self.config.present_mode),
+            ),
+            (
+                "Resolution".to_string(),
+                format!("{}x{}", self.config.width, self.config.height),
+            ),
+            (
+                "MSAA".to_string(),
+                format!("{}x", self.world_render.settings.sample_count),
+            ),
+            (
+                "Tonemapping".to_string(),
+                format!("{:?}", self.world_render.settings.tonemapping),
+            ),
+            ("Transparent".to_string(), self.transparent.to_string()),
+        ]
+    }
+
+    fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    fn resume(&mut self, window_handle: RawWindowHandle, dimensions: [u32; 2]) -> Result<()> {
+        let surface = unsafe { self.instance.create_surface(&WindowHandle(window_handle)) };
+        self.surface = Some(surface);
+        self.resize(dimensions);
+        Ok(())
+    }
+
+    fn update_world(&mut self, world: &World) -> Result<()> {
+        let frame = self.frame_builder.build(world)?;
+        let result = self.update_frame(&frame);
+        self.frame_builder.recycle(frame);
+        result
+    }
+
+    fn update_frame(&mut self, frame: &RenderFrame) -> Result<()> {
+        let aspect_ratio = self.world_viewport().aspect_ratio();
+        self.world_render
+            .update(&self.device, &self.queue, frame, aspect_ratio)
+    }
+
+    fn set_color_filter(&mut self, color_filter: ColorFilter) {
+        self.graph.set_color_filter(&self.device, color_filter);
+    }
+
+    fn render_settings(&self) -> RenderSettings {
+        self.world_render.settings
+    }
+
+    fn set_render_settings(&mut self, settings: RenderSettings) {
+        let sample_count = self.validate_sample_count(settings.sample_count);
+        let resample = sample_count != self.world_render.settings.sample_count;
+        let color_format = if settings.hdr() {
+            Texture::HDR_FORMAT
+        } else {
+            self.config.format
+        };
+        let reformat = color_format != self.world_render.color_format();
+        self.world_render.settings = RenderSettings {
+            sample_count: self.world_render.settings.sample_count,
+            ..settings
+        };
+        if resample {
+            self.world_render
+                .set_sample_count(&self.device, sample_count);
+        }
+        if reformat {
+            self.world_render
+                .set_color_format(&self.device, color_format);
+        }
+        if resample || reformat {
+            self.create_render_targets();
+        }
+    }
+
+    fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
+
+    fn supported_sample_counts(&self) -> Vec<u32> {
+        self.supported_sample_counts.clone()
+    }
+
+    fn add_render_pass(&mut self, pass: Box<dyn CustomPass>) {
+        self.graph.insert(pass);
+    }
+
+    fn remove_render_pass(&mut self, name: &str) {
+        self.graph.remove(name);
+    }
+
+    fn render_passes(&self) -> Vec<String> {
+        self.graph.pass_names()
+    }
+
+    fn statistics(&self) -> RenderStatistics {
+        self.world_render.statistics
+    }
+
+    fn render_frame_thumbnail(
+        &mut self,
+        frame: &RenderFrame,
+        width: u32,
+        height: u32,
+    ) -> Result<CapturedFrame> {
+        thumbnail::render(&self.device, &self.queue, frame, width, height)
+    }
+
+    fn upload_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<MeshHandle> {
+        self.world_render
+            .upload_mesh(&self.device, vertices, indices)
+    }
+
+    fn free_mesh(&mut self, mesh: MeshHandle) {
+        self.world_render.free_mesh(mesh);
+    }
+
+    fn upload_texture(
+        &mut self,
+        texture: &phantom_world::Texture,
+        srgb: bool,
+    ) -> Result<TextureHandle> {
+        self.world_render
+            .upload_texture(&self.device, &self.queue, texture, srgb)
+    }
+
+    fn free_texture(&mut self, texture: TextureHandle) {
+        self.world_render.free_texture(texture);
+    }
+
+    fn create_material(
+        &mut self,
+        material: &Material,
+        textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        self.world_render
+            .create_material(&self.device, material, textures)
+    }
+
+    fn update_material(&mut self, handle: MaterialHandle, material: &Material) -> Result<()> {
+        self.world_render
+            .update_material(&self.device, handle, material)
+    }
+
+    fn free_material(&mut self, material: MaterialHandle) {
+        self.world_render.free_material(material);
+    }
+
+    fn set_mesh_instances(&mut self, instances: Vec<MeshInstance>) {
+        self.world_render.set_mesh_instances(instances);
+    }
+
+    fn simulates_cloth(&self) -> bool {
+        self.world_render.simulates_cloth()
+    }
 }
 
 impl WgpuRenderer {
@@ -62,18 +336,32 @@ impl WgpuRenderer {
     }
 
     pub fn new(window_handle: &impl HasRawWindowHandle, dimensions: &[u32; 2]) -> Result<Self> {
-        pollster::block_on(WgpuRenderer::new_async(window_handle, dimensions))
+        Self::with_backend(window_handle, dimensions, Backend::Wgpu)
+    }
+
+    /// Creates the renderer on the graphics apis and adapters the wgpu backend allows
+    pub fn with_backend(
+        window_handle: &impl HasRawWindowHandle,
+        dimensions: &[u32; 2],
+        backend: Backend,
+    ) -> Result<Self> {
+        pollster::block_on(WgpuRenderer::new_async(window_handle, dimensions, backend))
     }
 
     async fn new_async(
         window_handle: &impl HasRawWindowHandle,
         dimensions: &[u32; 2],
+        backend: Backend,
     ) -> Result<Self> {
-        let instance = wgpu::Instance::new(Self::backends());
+        let backends = match backend {
+            Backend::WgpuCompatibility => wgpu::Backends::SECONDARY,
+            _ => Self::backends(),
+        };
+        let instance = wgpu::Instance::new(backends);
 
         let surface = unsafe { instance.create_surface(window_handle) };
 
-        let adapter = Self::create_adapter(&instance, &surface).await?;
+        let adapter = Self::create_adapter(&instance, &surface, backend).await?;
 
         let (device, queue) = Self::request_device(&adapter).await?;
 
@@ -91,27 +379,180 @@ impl WgpuRenderer {
 
         surface.configure(&device, &config);
 
-        let depth_texture =
-            Texture::create_depth_texture(&device, dimensions[0], dimensions[1], "Depth Texture");
-
-        let gui_renderpass = GuiRenderPass::new(&device, config.format, 1);
+        let depth_texture = Texture::create_depth_texture(
+            &device,
+            dimensions[0],
+            dimensions[1],
+            1,
+            "Depth Texture",
+        );
 
-        let world_render = WorldRender::new(&device, &config)?;
+        let downlevel_flags = adapter.get_downlevel_properties().flags;
+        let world_render = WorldRender::new(
+            &device,
+            &queue,
+            &config,
+            downlevel_flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS),
+            downlevel_flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE),
+        )?;
 
         Ok(Self {
-            surface,
+            backend,
+            instance,
+            adapter_info: adapter.get_info(),
+            surface: Some(surface),
             device,
             queue,
             config,
             dimensions: *dimensions,
+            viewport: None,
             depth_texture,
-            gui_renderpass,
+            multisampled_framebuffer: None,
+            supported_sample_counts: Self::sample_counts(
+                &adapter,
+                &[
+                    swapchain_format,
+                    world_render.color_format(),
+                    Texture::HDR_FORMAT,
+                    Texture::DEPTH_FORMAT,
+                ],
+            ),
+            transparent: false,
+            gui: None,
             world_render,
+            frame_builder: FrameBuilder::default(),
+            graph: RenderGraph::new(&device, swapchain_format, *dimensions),
+            shader_manager: ShaderManager::new(),
+            frame_capture: None,
+            capture_requested: false,
+            captured_frame: None,
         })
     }
 
+    /// Transparent windows are cleared to transparent black, which reads as empty
+    /// whether the compositor expects straight or premultiplied alpha.
+    /// wgpu chooses the surface's composite alpha mode itself, so the desktop only
+    /// shows through on platforms that composite the surface with its alpha.
+    fn clear_color(&self) -> wgpu::Color {
+        if self.transparent {
+            wgpu::Color::TRANSPARENT
+        } else {
+            wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            }
+        }
+    }
+
+    /// The viewport kept within the surface, since wgpu rejects viewports that extend past it
+    fn world_viewport(&self) -> Viewport {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let viewport = self.viewport.unwrap_or(Viewport {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        });
+        let x = viewport.x.clamp(0.0, width - 1.0);
+        let y = viewport.y.clamp(0.0, height - 1.0);
+        Viewport {
+            x,
+            y,
+            width: viewport.width.clamp(1.0, width - x),
+            height: viewport.height.clamp(1.0, height - y),
+        }
+    }
+
+    /// The sample counts every format the world renders into supports on the adapter.
+    /// WebGPU only guarantees 4x multisampling, while native backends support 2x, 4x and 8x.
+    /// wgpu reports per format whether it can be rendered to and resolved rather than
+    /// exact counts, so formats that can't fall back to 1x and limit the rest.
+    fn sample_counts(adapter: &wgpu::Adapter, formats: &[wgpu::TextureFormat]) -> Vec<u32> {
+        let backend_counts = match adapter.get_info().backend {
+            wgpu::Backend::Vulkan | wgpu::Backend::Metal | wgpu::Backend::Dx12 => {
+                vec![1, 2, 4, 8]
+            }
+            _ => vec![1, 4],
+        };
+        formats.iter().fold(backend_counts, |counts, format| {
+            let features = adapter.get_texture_format_features(*format);
+            let is_depth = matches!(
+                format.describe().sample_type,
+                wgpu::TextureSampleType::Depth
+            );
+            let multisampled = features
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+                && (is_depth || features.filterable);
+            if !multisampled {
+                log::warn!("{:?} can't be multisampled on this adapter", format);
+            }
+            counts
+                .into_iter()
+                .filter(|count| *count == 1 || multisampled)
+                .collect()
+        })
+    }
+
+    /// The highest supported sample count that doesn't exceed the one requested
+    fn validate_sample_count(&self, sample_count: u32) -> u32 {
+        let supported = self
+            .supported_sample_counts
+            .iter()
+            .copied()
+            .filter(|count| *count <= sample_count)
+            .max()
+            .unwrap_or(1);
+        if supported != sample_count {
+            log::warn!(
+                "{}x MSAA isn't supported by this adapter, using {}x",
+                sample_count,
+                supported
+            );
+        }
+        supported
+    }
+
+    /// Recreates the depth texture and multisampled framebuffer
+    /// to match the surface size, sample count and the format the world renders into
+    fn create_render_targets(&mut self) {
+        let [width, height] = self.dimensions;
+        let sample_count = self.world_render.settings.sample_count;
+        self.depth_texture = Texture::create_depth_texture(
+            &self.device,
+            width,
+            height,
+            sample_count,
+            "Depth Texture",
+        );
+        self.multisampled_framebuffer = (sample_count > 1).then(|| {
+            Texture::create_multisampled_framebuffer(
+                &self.device,
+                width,
+                height,
+                self.world_render.color_format(),
+                sample_count,
+            )
+        });
+    }
+
     fn required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
-        wgpu::Limits::default()
+        let downlevel_properties = adapter.get_downlevel_properties();
+        let limits = if downlevel_properties.is_webgpu_compliant() {
+            wgpu::Limits::default()
+        } else if downlevel_properties
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            // GLES 3.1 and older mobile GPUs
+            wgpu::Limits::downlevel_defaults()
+        } else {
+            // GLES 3.0 without compute shaders
+            wgpu::Limits::downlevel_webgl2_defaults()
+        };
+        limits
             // Use the texture resolution limits from the adapter
             // to support images the size of the surface
             .using_resolution(adapter.limits())
@@ -128,14 +569,28 @@ impl WgpuRenderer {
     async fn create_adapter(
         instance: &wgpu::Instance,
         surface: &wgpu::Surface,
+        backend: Backend,
     ) -> Result<wgpu::Adapter> {
-        wgpu::util::initialize_adapter_from_env_or_default(
-            instance,
-            Self::backends(),
-            Some(surface),
-        )
-        .await
-        .context("No suitable GPU adapters found on the system!")
+        match backend {
+            Backend::Wgpu => wgpu::util::initialize_adapter_from_env_or_default(
+                instance,
+                Self::backends(),
+                Some(surface),
+            )
+            .await
+            .context("No suitable GPU adapters found on the system!"),
+            _ => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter: backend == Backend::WgpuSoftware,
+                    compatible_surface: Some(surface),
+                })
+                .await
+                .context(format!(
+                    "No adapters for the {:?} backend were found!",
+                    backend
+                )),
+        }
     }
 
     async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
@@ -155,33 +610,55 @@ impl WgpuRenderer {
             .context("Failed to request a device!")
     }
 
-    fn render_frame(
-        &mut self,
-        gui_context: &CtxRef,
-        paint_jobs: Vec<ClippedMesh>,
-    ) -> Result<(), wgpu::SurfaceError> {
-        let surface_texture = self.surface.get_current_texture()?;
+    /// Rebuilds the pipelines of any shaders edited on disk
+    fn reload_shaders(&mut self) {
+        for name in self.shader_manager.changed() {
+            let result = if name == WORLD_SHADER.name {
+                self.world_render.reload_shader(&self.device)
+            } else if [
+                BLIT_SHADER.name,
+                BLOOM_SHADER.name,
+                TONEMAP_SHADER.name,
+                FXAA_SHADER.name,
+                COLOR_FILTER_SHADER.name,
+            ]
+            .contains(&name.as_str())
+            {
+                self.graph.reload_shader(&self.device, &name)
+            } else if name == CLOTH_SHADER.name {
+                self.world_render.reload_cloth_shader(&self.device)
+            } else {
+                continue;
+            };
+            match result {
+                Ok(()) => log::info!("Reloaded shader '{}'", name),
+                Err(error) => log::error!(
+                    "Failed to reload shader '{}', keeping the last good pipelines: {}",
+                    name,
+                    error
+                ),
+            }
+        }
+    }
+
+    fn render_frame(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let surface = match self.surface.as_ref() {
+            Some(surface) => surface,
+            None => return Ok(()),
+        };
+        let surface_texture = surface.get_current_texture()?;
 
         let view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let screen_descriptor = ScreenDescriptor {
-            physical_width: self.config.width,
-            physical_height: self.config.height,
-            scale_factor: 1.0, // TODO: Store the scale factor in the renderer and update it when winit reports that the scale factor has changed
-        };
-
-        self.gui_renderpass
-            .update_texture(&self.device, &self.queue, &gui_context.texture());
-        self.gui_renderpass
-            .update_user_textures(&self.device, &self.queue);
-        self.gui_renderpass.update_buffers(
-            &self.device,
-            &self.queue,
-            &paint_jobs,
-            &screen_descriptor,
-        );
+        if let Some(gui) = self.gui.as_mut() {
+            gui.prepare(
+                &self.device,
+                &self.queue,
+                [self.config.width, self.config.height],
+            );
+        }
 
         let mut encoder = self
             .device
@@ -189,41 +666,101 @@ impl WgpuRenderer {
                 label: Some("Render Encoder"),
             });
 
-        encoder.push_debug_group("Main Passes");
-
-        encoder.insert_debug_marker("Render Entities");
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
+        let clear_color = self.clear_color();
+        let settings = self.world_render.settings;
+        let world_render = &mut self.world_render;
+        let gui = &mut self.gui;
+        let multisampled_framebuffer = self.multisampled_framebuffer.as_ref();
+        let depth_view = &self.depth_texture.view;
+        let viewport = self.world_viewport();
+        self.graph.execute(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &view,
+            depth_view,
+            &settings,
+            |pass, encoder, color_view| {
+                let color_view = || color_view.expect("The world and gui passes write color!");
+                match pass {
+                    BuiltinPass::Shadows => world_render.render_shadows(encoder),
+                    BuiltinPass::World => {
+                        let color_view = color_view();
+                        let (attachment, resolve_target) = match multisampled_framebuffer {
+                            Some(framebuffer) => (framebuffer, Some(color_view)),
+                            None => (color_view, None),
+                        };
+                        let mut render_pass =
+                            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                label: Some("Render Pass"),
+                                color_attachments: &[wgpu::RenderPassColorAttachment {
+                                    view: attachment,
+                                    resolve_target,
+                                    ops: wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(clear_color),
+                                        store: true,
+                                    },
+                                }],
+                                depth_stencil_attachment: Some(
+                                    wgpu::RenderPassDepthStencilAttachment {
+                                        view: depth_view,
+                                        depth_ops: Some(wgpu::Operations {
+                                            load: wgpu::LoadOp::Clear(1.0),
+                                            store: true,
+                                        }),
+                                        stencil_ops: None,
+                                    },
+                                ),
+                            });
+                        render_pass.set_viewport(
+                            viewport.x,
+                            viewport.y,
+                            viewport.width,
+                            viewport.height,
+                            0.0,
+                            1.0,
+                        );
+                        world_render
+                            .render(&mut render_pass)
+                            .expect("Failed to render frame!");
+                    }
+                    BuiltinPass::Gui => {
+                        if let Some(gui) = gui.as_mut() {
+                            gui.execute(encoder, color_view())
+                                .expect("Failed to execute the gui renderpass!");
+                        }
+                    }
+                }
+            },
+        );
 
-            self.world_render
-                .render(&mut render_pass)
-                .expect("Failed to render frame!");
+        let capture_requested = std::mem::take(&mut self.capture_requested);
+        if let Some(frame_capture) = self.frame_capture.as_mut() {
+            if capture_requested {
+                frame_capture.copy(
+                    &self.device,
+                    &mut encoder,
+                    &surface_texture.texture,
+                    self.config.width,
+                    self.config.height,
+                );
+            }
         }
 
-        encoder.insert_debug_marker("Render Gui");
-        self.gui_renderpass
-            .execute(&mut encoder, &view, &paint_jobs, &screen_descriptor, None)
-            .expect("Failed to execute the gui renderpass!");
-
         self.queue.submit(std::iter::once(encoder.finish()));
         surface_texture.present();
 
+        // Frames copied earlier are read back once the GPU has finished with them,
+        // so a captured frame arrives a frame or two after it was rendered
+        if let Some(frame_capture) = self.frame_capture.as_mut() {
+            frame_capture.map_copied();
+            match frame_capture.read(&self.device, self.config.format) {
+                Ok(Some(frame)) => self.captured_frame = Some(frame),
+                Ok(None) => {}
+                Err(error) => log::error!("Failed to read back frame: {}", error),
+            }
+        }
+
         Ok(())
     }
 }
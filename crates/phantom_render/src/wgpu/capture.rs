@@ -0,0 +1,228 @@
+use crate::CapturedFrame;
+use phantom_dependencies::{
+    anyhow::Result,
+    log,
+    wgpu::{self, Device},
+};
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+};
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Copies rendered frames from the surface into a ring of buffers that are mapped
+/// asynchronously, so reading a frame back never waits for the GPU to finish it
+#[derive(Default)]
+pub(crate) struct FrameCapture {
+    ring: Vec<StagingBuffer>,
+
+    /// Numbers copies so frames are read back in the order they were rendered
+    next_sequence: u64,
+}
+
+struct StagingBuffer {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    sequence: u64,
+    state: StagingState,
+}
+
+enum StagingState {
+    Free,
+
+    /// A copy has been recorded but not yet submitted
+    Copied,
+    Mapping(MapFuture),
+    Mapped,
+}
+
+/// Frames are dropped rather than stalling the GPU while every buffer is in flight
+const RING_SIZE: usize = 3;
+
+/// Lets map futures be polled without an executor, since the device is polled each frame anyway
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+impl FrameCapture {
+    /// Records a copy of the texture into a free buffer, skipping the frame if none is free
+    pub fn copy(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        width: u32,
+        height: u32,
+    ) {
+        let index = match self.free_buffer(device, width, height) {
+            Some(index) => index,
+            None => {
+                log::debug!("Skipped a frame capture because every readback is still in flight");
+                return;
+            }
+        };
+        let staging = &mut self.ring[index];
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &staging.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(staging.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        staging.sequence = self.next_sequence;
+        staging.state = StagingState::Copied;
+        self.next_sequence += 1;
+    }
+
+    /// Starts mapping the buffers copied into, which must happen after their copies are submitted
+    pub fn map_copied(&mut self) {
+        for staging in self.ring.iter_mut() {
+            if let StagingState::Copied = staging.state {
+                let mapping = staging.buffer.slice(..).map_async(wgpu::MapMode::Read);
+                staging.state = StagingState::Mapping(Box::pin(mapping));
+            }
+        }
+    }
+
+    /// The oldest copied frame that has finished mapping, as tightly packed RGBA
+    pub fn read(
+        &mut self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<Option<CapturedFrame>> {
+        device.poll(wgpu::Maintain::Poll);
+        self.take_mapped(format)
+    }
+
+    /// Waits for every copied frame to finish mapping, then reads back the oldest
+    pub fn read_blocking(
+        &mut self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<Option<CapturedFrame>> {
+        self.map_copied();
+        device.poll(wgpu::Maintain::Wait);
+        self.take_mapped(format)
+    }
+
+    /// Finds a buffer that isn't in flight, creating or resizing it to fit the frame
+    fn free_buffer(&mut self, device: &Device, width: u32, height: u32) -> Option<usize> {
+        let index = match self
+            .ring
+            .iter()
+            .position(|staging| matches!(staging.state, StagingState::Free))
+        {
+            Some(index) => index,
+            None if self.ring.len() < RING_SIZE => {
+                self.ring.push(StagingBuffer::new(device, width, height));
+                return Some(self.ring.len() - 1);
+            }
+            None => return None,
+        };
+        let staging = &self.ring[index];
+        if staging.width != width || staging.height != height {
+            self.ring[index] = StagingBuffer::new(device, width, height);
+        }
+        Some(index)
+    }
+
+    fn take_mapped(&mut self, format: wgpu::TextureFormat) -> Result<Option<CapturedFrame>> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        for staging in self.ring.iter_mut() {
+            if let StagingState::Mapping(mapping) = &mut staging.state {
+                if let Poll::Ready(result) = mapping.as_mut().poll(&mut context) {
+                    staging.state = match result {
+                        Ok(()) => StagingState::Mapped,
+                        Err(error) => {
+                            log::error!("Failed to map a frame capture: {:?}", error);
+                            StagingState::Free
+                        }
+                    };
+                }
+            }
+        }
+
+        let staging = match self
+            .ring
+            .iter_mut()
+            .filter(|staging| matches!(staging.state, StagingState::Mapped))
+            .min_by_key(|staging| staging.sequence)
+        {
+            Some(staging) => staging,
+            None => return Ok(None),
+        };
+        let frame = staging.read(format);
+        staging.buffer.unmap();
+        staging.state = StagingState::Free;
+        Ok(Some(frame))
+    }
+}
+
+impl StagingBuffer {
+    fn new(device: &Device, width: u32, height: u32) -> Self {
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4 + alignment - 1) / alignment * alignment;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            sequence: 0,
+            state: StagingState::Free,
+        }
+    }
+
+    fn read(&self, format: wgpu::TextureFormat) -> CapturedFrame {
+        let swap_red_blue = matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                for pixel in row[..(self.width * 4) as usize].chunks(4) {
+                    if swap_red_blue {
+                        pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+                    } else {
+                        pixels.extend_from_slice(pixel);
+                    }
+                }
+            }
+        }
+        CapturedFrame {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
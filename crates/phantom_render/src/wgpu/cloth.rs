@@ -0,0 +1,592 @@
+use super::shaders::{create_shader, validate, CLOTH_SHADER};
+use crate::{ClothFrame, ClothSimulation};
+use phantom_dependencies::{
+    anyhow::Result,
+    bytemuck,
+    wgpu::{self, util::DeviceExt, Device, Queue},
+};
+use phantom_world::{Cloth, ClothCollider, ClothEnvironment, Entity, Vertex};
+use std::{collections::HashMap, mem::size_of};
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+struct GpuParticle {
+    position: [f32; 4],
+    previous: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for GpuParticle {}
+
+unsafe impl bytemuck::Pod for GpuParticle {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct GpuNeighbor {
+    index: i32,
+    rest_length: f32,
+    compliance: f32,
+    padding: f32,
+}
+
+impl Default for GpuNeighbor {
+    fn default() -> Self {
+        Self {
+            index: -1,
+            rest_length: 0.0,
+            compliance: 0.0,
+            padding: 0.0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for GpuNeighbor {}
+
+unsafe impl bytemuck::Pod for GpuNeighbor {}
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+struct GpuCollider {
+    shape: [f32; 4],
+    first: [f32; 4],
+    second: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for GpuCollider {}
+
+unsafe impl bytemuck::Pod for GpuCollider {}
+
+impl From<&ClothCollider> for GpuCollider {
+    fn from(collider: &ClothCollider) -> Self {
+        match *collider {
+            ClothCollider::Sphere { center, radius, .. } => Self {
+                shape: [0.0, radius, 0.0, 0.0],
+                first: [center.x, center.y, center.z, 0.0],
+                second: [0.0; 4],
+            },
+            ClothCollider::Capsule {
+                start, end, radius, ..
+            } => Self {
+                shape: [1.0, radius, 0.0, 0.0],
+                first: [start.x, start.y, start.z, 0.0],
+                second: [end.x, end.y, end.z, 0.0],
+            },
+            ClothCollider::Plane { normal, distance } => Self {
+                shape: [2.0, distance, 0.0, 0.0],
+                first: [normal.x, normal.y, normal.z, 0.0],
+                second: [0.0; 4],
+            },
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+struct ClothParams {
+    gravity: [f32; 4],
+    wind: [f32; 4],
+    counts: [u32; 4],
+    settings: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for ClothParams {}
+
+unsafe impl bytemuck::Pod for ClothParams {}
+
+struct ClothPipelines {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    integrate: wgpu::ComputePipeline,
+    solve: wgpu::ComputePipeline,
+    collide: wgpu::ComputePipeline,
+    write_vertices: wgpu::ComputePipeline,
+}
+
+/// The buffers a cloth is drawn from, which are filled by the cpu or by compute shaders
+pub(crate) struct ClothMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub number_of_indices: u32,
+    pub material_index: Option<usize>,
+    dimensions: (usize, usize),
+    simulation: Option<GpuCloth>,
+}
+
+impl ClothMesh {
+    /// The vertex buffer can only be written by compute shaders when the adapter supports them
+    fn new(device: &Device, columns: usize, rows: usize, compute_shaders: bool) -> Self {
+        let indices = Cloth::indices(columns, rows);
+        let mut usage = wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST;
+        if compute_shaders {
+            usage |= wgpu::BufferUsages::STORAGE;
+        }
+        Self {
+            vertex_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Cloth Vertex Buffer"),
+                size: (columns * rows * size_of::<Vertex>()) as wgpu::BufferAddress,
+                usage,
+                mapped_at_creation: false,
+            }),
+            index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cloth Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+            number_of_indices: indices.len() as u32,
+            material_index: None,
+            dimensions: (columns, rows),
+            simulation: None,
+        }
+    }
+}
+
+/// A cloth simulated with compute shaders, which keeps its particles on the GPU between frames
+struct GpuCloth {
+    /// The component the simulation was last restarted from, for its settings
+    cloth: Cloth,
+    params: wgpu::Buffer,
+    particles: [wgpu::Buffer; 2],
+    neighbors: wgpu::Buffer,
+    colliders: wgpu::Buffer,
+    collider_capacity: usize,
+    bind_groups: [wgpu::BindGroup; 2],
+
+    /// The particle buffer holding the latest positions
+    current: usize,
+}
+
+impl GpuCloth {
+    const MAX_NEIGHBORS: usize = 12;
+
+    fn new(
+        device: &Device,
+        pipelines: &ClothPipelines,
+        cloth: Cloth,
+        vertex_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let particles = cloth
+            .particles
+            .iter()
+            .map(|particle| GpuParticle {
+                position: [
+                    particle.position.x,
+                    particle.position.y,
+                    particle.position.z,
+                    particle.inverse_mass,
+                ],
+                previous: [
+                    particle.previous_position.x,
+                    particle.previous_position.y,
+                    particle.previous_position.z,
+                    0.0,
+                ],
+            })
+            .collect::<Vec<_>>();
+        let create_particle_buffer = |label| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&particles),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        let particles = [
+            create_particle_buffer("Cloth Particle Buffer A"),
+            create_particle_buffer("Cloth Particle Buffer B"),
+        ];
+
+        let neighbors = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Neighbor Buffer"),
+            contents: bytemuck::cast_slice(&Self::neighbors(&cloth)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Params Buffer"),
+            contents: bytemuck::cast_slice(&[ClothParams::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let collider_capacity = cloth.colliders.len().max(1);
+        let colliders = Self::create_collider_buffer(device, collider_capacity);
+        let bind_groups = Self::create_bind_groups(
+            device,
+            pipelines,
+            &params,
+            &particles,
+            &neighbors,
+            &colliders,
+            vertex_buffer,
+        );
+
+        Self {
+            cloth,
+            params,
+            particles,
+            neighbors,
+            colliders,
+            collider_capacity,
+            bind_groups,
+            current: 0,
+        }
+    }
+
+    fn create_collider_buffer(device: &Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cloth Collider Buffer"),
+            size: (capacity * size_of::<GpuCollider>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// The first bind group reads the first particle buffer and writes the second,
+    /// and the second bind group does the opposite
+    #[allow(clippy::too_many_arguments)]
+    fn create_bind_groups(
+        device: &Device,
+        pipelines: &ClothPipelines,
+        params: &wgpu::Buffer,
+        particles: &[wgpu::Buffer; 2],
+        neighbors: &wgpu::Buffer,
+        colliders: &wgpu::Buffer,
+        vertex_buffer: &wgpu::Buffer,
+    ) -> [wgpu::BindGroup; 2] {
+        [0, 1].map(|source| {
+            let buffers = [
+                params,
+                &particles[source],
+                &particles[1 - source],
+                neighbors,
+                colliders,
+                vertex_buffer,
+            ];
+            let entries = buffers
+                .iter()
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &pipelines.bind_group_layout,
+                entries: &entries,
+                label: Some("Cloth Bind Group"),
+            })
+        })
+    }
+
+    fn neighbors(cloth: &Cloth) -> Vec<GpuNeighbor> {
+        let mut neighbors =
+            vec![GpuNeighbor::default(); cloth.particles.len() * Self::MAX_NEIGHBORS];
+        let mut counts = vec![0; cloth.particles.len()];
+        for constraint in cloth.constraints.iter() {
+            for (particle, other) in [
+                (constraint.first, constraint.second),
+                (constraint.second, constraint.first),
+            ] {
+                if counts[particle] == Self::MAX_NEIGHBORS {
+                    continue;
+                }
+                neighbors[particle * Self::MAX_NEIGHBORS + counts[particle]] = GpuNeighbor {
+                    index: other as i32,
+                    rest_length: constraint.rest_length,
+                    compliance: constraint.compliance,
+                    padding: 0.0,
+                };
+                counts[particle] += 1;
+            }
+        }
+        neighbors
+    }
+
+    /// Writes the environment into the cloth's buffers, growing the collider buffer if it is too small
+    #[allow(clippy::too_many_arguments)]
+    fn upload_environment(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        pipelines: &ClothPipelines,
+        environment: &ClothEnvironment,
+        substep: f32,
+        vertex_buffer: &wgpu::Buffer,
+    ) {
+        if environment.colliders.len() > self.collider_capacity {
+            self.collider_capacity = environment.colliders.len().next_power_of_two();
+            self.colliders = Self::create_collider_buffer(device, self.collider_capacity);
+            self.bind_groups = Self::create_bind_groups(
+                device,
+                pipelines,
+                &self.params,
+                &self.particles,
+                &self.neighbors,
+                &self.colliders,
+                vertex_buffer,
+            );
+        }
+        let colliders = environment
+            .colliders
+            .iter()
+            .map(GpuCollider::from)
+            .collect::<Vec<_>>();
+        if !colliders.is_empty() {
+            queue.write_buffer(&self.colliders, 0, bytemuck::cast_slice(&colliders));
+        }
+
+        let cloth = &self.cloth;
+        let gravity = environment.gravity;
+        let params = ClothParams {
+            gravity: [gravity.x, gravity.y, gravity.z, substep],
+            wind: [cloth.wind.x, cloth.wind.y, cloth.wind.z, cloth.damping],
+            counts: [
+                cloth.particles.len() as u32,
+                cloth.columns as u32,
+                cloth.rows as u32,
+                colliders.len() as u32,
+            ],
+            settings: [cloth.thickness, 0.0, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&[params]));
+
+        // Pinned particles are moved to their targets rather than simulated
+        for (particle, position) in environment.pins.iter() {
+            let pinned = GpuParticle {
+                position: [position.x, position.y, position.z, 0.0],
+                previous: [position.x, position.y, position.z, 0.0],
+            };
+            queue.write_buffer(
+                &self.particles[self.current],
+                (particle * size_of::<GpuParticle>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&[pinned]),
+            );
+        }
+    }
+
+    /// Records the substeps, each integrating, solving and colliding like the CPU solver,
+    /// then writes the particles into the vertex buffer
+    fn encode(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &ClothPipelines,
+        delta_time: f32,
+    ) {
+        let workgroups = (self.cloth.particles.len() as u32 + ClothRender::WORKGROUP_SIZE - 1)
+            / ClothRender::WORKGROUP_SIZE;
+        let substeps = if delta_time > 0.0 {
+            self.cloth.substeps.max(1)
+        } else {
+            0
+        };
+
+        let mut passes = Vec::new();
+        for _ in 0..substeps {
+            passes.push(&pipelines.integrate);
+            passes.extend((0..ClothRender::SOLVER_ITERATIONS).map(|_| &pipelines.solve));
+            passes.push(&pipelines.collide);
+        }
+
+        let mut current = self.current;
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Compute Pass"),
+            });
+            for pipeline in passes {
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.set_bind_group(0, &self.bind_groups[current], &[]);
+                compute_pass.dispatch(workgroups, 1, 1);
+                current = 1 - current;
+            }
+            compute_pass.set_pipeline(&pipelines.write_vertices);
+            compute_pass.set_bind_group(0, &self.bind_groups[current], &[]);
+            compute_pass.dispatch(workgroups, 1, 1);
+        }
+        self.current = current;
+    }
+}
+
+/// Draws cloth, simulating the cloth that prefers the GPU with compute shaders
+/// so its particles never have to be read back
+pub(crate) struct ClothRender {
+    /// Only created when the adapter supports compute shaders
+    pipelines: Option<ClothPipelines>,
+    pub meshes: HashMap<Entity, ClothMesh>,
+}
+
+impl ClothRender {
+    const WORKGROUP_SIZE: u32 = 64;
+
+    /// Jacobi iterations converge slower than the CPU solver's Gauss-Seidel,
+    /// so each substep is solved more than once
+    const SOLVER_ITERATIONS: usize = 3;
+
+    pub fn new(device: &Device, compute_shaders: bool) -> Self {
+        let pipelines = compute_shaders.then(|| {
+            let shader = CLOTH_SHADER.load(device, "Cloth Shader");
+            Self::create_pipelines(device, &shader)
+        });
+        Self {
+            pipelines,
+            meshes: HashMap::new(),
+        }
+    }
+
+    pub fn simulates_cloth(&self) -> bool {
+        self.pipelines.is_some()
+    }
+
+    fn create_pipelines(device: &Device, shader: &wgpu::ShaderModule) -> ClothPipelines {
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |read_only| wgpu::BufferBindingType::Storage { read_only };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                buffer_entry(1, storage(true)),
+                buffer_entry(2, storage(false)),
+                buffer_entry(3, storage(true)),
+                buffer_entry(4, storage(true)),
+                buffer_entry(5, storage(false)),
+            ],
+            label: Some("Cloth Bind Group Layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let [integrate, solve, collide, write_vertices] =
+            Self::create_compute_pipelines(device, &pipeline_layout, shader);
+
+        ClothPipelines {
+            bind_group_layout,
+            pipeline_layout,
+            integrate,
+            solve,
+            collide,
+            write_vertices,
+        }
+    }
+
+    fn create_compute_pipelines(
+        device: &Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> [wgpu::ComputePipeline; 4] {
+        [
+            ("Cloth Integrate Pipeline", "integrate"),
+            ("Cloth Solve Pipeline", "solve"),
+            ("Cloth Collide Pipeline", "collide"),
+            ("Cloth Vertices Pipeline", "write_vertices"),
+        ]
+        .map(|(label, entry_point)| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                module: shader,
+                entry_point,
+            })
+        })
+    }
+
+    /// Recompiles the shader, keeping the current pipelines if the new ones fail to validate
+    pub fn reload_shader(&mut self, device: &Device) -> Result<()> {
+        let pipelines = match self.pipelines.as_mut() {
+            Some(pipelines) => pipelines,
+            None => return Ok(()),
+        };
+        let [integrate, solve, collide, write_vertices] = validate(device, || {
+            let shader = create_shader(device, "Cloth Shader", CLOTH_SHADER.source());
+            Self::create_compute_pipelines(device, &pipelines.pipeline_layout, &shader)
+        })?;
+        pipelines.integrate = integrate;
+        pipelines.solve = solve;
+        pipelines.collide = collide;
+        pipelines.write_vertices = write_vertices;
+        Ok(())
+    }
+
+    /// Fills the vertex buffers of the frame's cloth, uploading the particles of cloth
+    /// simulated on the cpu and dispatching the compute shaders for the rest
+    pub fn update(&mut self, device: &Device, queue: &Queue, frames: &[ClothFrame]) {
+        self.meshes
+            .retain(|entity, _| frames.iter().any(|frame| frame.entity == *entity));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Cloth Encoder"),
+        });
+        let mut dispatched = false;
+        for frame in frames.iter() {
+            // Cloth needs at least one cell to have any triangles
+            if frame.columns < 2 || frame.rows < 2 {
+                continue;
+            }
+            let dimensions = (frame.columns, frame.rows);
+            let compute_shaders = self.pipelines.is_some();
+            let mesh = self.meshes.entry(frame.entity).or_insert_with(|| {
+                ClothMesh::new(device, frame.columns, frame.rows, compute_shaders)
+            });
+            if mesh.dimensions != dimensions {
+                *mesh = ClothMesh::new(device, frame.columns, frame.rows, compute_shaders);
+            }
+            mesh.material_index = frame.material_index;
+
+            match &frame.simulation {
+                ClothSimulation::Cpu(vertices) => {
+                    mesh.simulation = None;
+                    queue.write_buffer(&mesh.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+                }
+                ClothSimulation::Gpu {
+                    reset,
+                    environment,
+                    delta_time,
+                } => {
+                    let pipelines = match self.pipelines.as_ref() {
+                        Some(pipelines) => pipelines,
+                        None => continue,
+                    };
+                    let ClothMesh {
+                        vertex_buffer,
+                        simulation,
+                        ..
+                    } = mesh;
+                    if let Some(cloth) = reset {
+                        *simulation = Some(GpuCloth::new(
+                            device,
+                            pipelines,
+                            cloth.as_ref().clone(),
+                            vertex_buffer,
+                        ));
+                    }
+                    let simulation = match simulation.as_mut() {
+                        Some(simulation) => simulation,
+                        None => continue,
+                    };
+                    let substep = *delta_time / simulation.cloth.substeps.max(1) as f32;
+                    simulation.upload_environment(
+                        device,
+                        queue,
+                        pipelines,
+                        environment,
+                        substep,
+                        vertex_buffer,
+                    );
+                    simulation.encode(&mut encoder, pipelines, *delta_time);
+                    dispatched = true;
+                }
+            }
+        }
+        if dispatched {
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+}
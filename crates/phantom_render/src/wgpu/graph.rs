@@ -0,0 +1,614 @@
+use super::{
+    post::{Bloom, ColorFilterPass, Fxaa, Tonemap, BLOOM_SCRATCH_TARGET, BLOOM_TARGET, HDR_TARGET},
+    shaders::{
+        create_shader, validate, ShaderAsset, BLIT_SHADER, BLOOM_SHADER, COLOR_FILTER_SHADER,
+        FXAA_SHADER, TONEMAP_SHADER,
+    },
+    texture::Texture,
+};
+use crate::{ColorFilter, RenderSettings};
+use phantom_dependencies::{
+    anyhow::Result,
+    log,
+    wgpu::{self, CommandEncoder, Device, Queue, TextureFormat, TextureView},
+};
+use std::collections::HashMap;
+
+/// A texture that render passes read from and write to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// The frame's color, which is shown on the surface once every pass has run
+    Color,
+
+    /// The world's depth, which is multisampled when MSAA is enabled
+    Depth,
+
+    /// An offscreen texture the size of the surface, created the first time a pass uses it
+    Target(String),
+}
+
+/// What a custom pass gets to record its commands with
+pub struct PassContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    pub encoder: &'a mut CommandEncoder,
+
+    /// The format of the color and offscreen targets
+    pub format: TextureFormat,
+    pub dimensions: [u32; 2],
+    pub settings: &'a RenderSettings,
+    inputs: Vec<(Slot, &'a TextureView)>,
+    outputs: Vec<(Slot, &'a TextureView)>,
+}
+
+impl<'a> PassContext<'a> {
+    /// A texture the pass declared as an input, to be sampled
+    pub fn input(&self, slot: &Slot) -> Option<&'a TextureView> {
+        find_view(&self.inputs, slot)
+    }
+
+    /// A texture the pass declared as an output, to be rendered into
+    pub fn output(&self, slot: &Slot) -> Option<&'a TextureView> {
+        find_view(&self.outputs, slot)
+    }
+}
+
+fn find_view<'a>(views: &[(Slot, &'a TextureView)], slot: &Slot) -> Option<&'a TextureView> {
+    views
+        .iter()
+        .find(|(view_slot, _)| view_slot == slot)
+        .map(|(_, view)| *view)
+}
+
+/// A pass that runs between the world and the gui, such as a post-processing effect.
+/// A pass that samples `Slot::Color` and writes it again gets a different texture
+/// to write into than the one it reads, so effects can be chained.
+pub trait CustomPass: Send {
+    fn name(&self) -> &str;
+
+    /// Disabled passes are skipped, and the passes around them are planned as if they weren't there
+    fn enabled(&self, _settings: &RenderSettings) -> bool {
+        true
+    }
+
+    /// Slots the pass samples
+    fn inputs(&self) -> Vec<Slot> {
+        Vec::new()
+    }
+
+    /// Slots the pass renders into
+    fn outputs(&self) -> Vec<Slot>;
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<()>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum BuiltinPass {
+    Shadows,
+    World,
+    Gui,
+}
+
+enum PassKind {
+    Builtin(BuiltinPass),
+    Custom(Box<dyn CustomPass>),
+}
+
+struct PassNode {
+    name: String,
+    inputs: Vec<Slot>,
+    outputs: Vec<Slot>,
+    kind: PassKind,
+}
+
+/// The format and size of an offscreen target
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct TargetDescription {
+    format: TextureFormat,
+
+    /// The target's width and height are the surface's divided by this
+    divisor: u32,
+}
+
+/// Where the color slot lives while a pass runs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ColorTarget {
+    Surface,
+    Offscreen(usize),
+}
+
+/// The passes rendered each frame, in order, and the textures they share.
+/// The graph decides which texture backs each slot, rendering the world straight
+/// into the surface unless a later pass needs to sample it.
+/// wgpu tracks how each texture is used, so it inserts the barriers between passes itself.
+pub(crate) struct RenderGraph {
+    passes: Vec<PassNode>,
+    format: TextureFormat,
+    dimensions: [u32; 2],
+
+    /// Sampled color is ping-ponged between these so a pass never reads what it writes
+    color_targets: Vec<Texture>,
+    targets: HashMap<String, Texture>,
+    descriptions: HashMap<String, TargetDescription>,
+    blit: Blit,
+
+    /// Kept so the color filter pass can be rebuilt when its shader is edited
+    color_filter: ColorFilter,
+}
+
+impl RenderGraph {
+    pub fn new(device: &Device, format: TextureFormat, dimensions: [u32; 2]) -> Self {
+        let builtin = |name: &str, inputs, outputs, pass| PassNode {
+            name: name.to_string(),
+            inputs,
+            outputs,
+            kind: PassKind::Builtin(pass),
+        };
+        let mut graph = Self {
+            passes: vec![
+                builtin("Shadows", Vec::new(), Vec::new(), BuiltinPass::Shadows),
+                builtin(
+                    "World",
+                    Vec::new(),
+                    vec![Slot::Color, Slot::Depth],
+                    BuiltinPass::World,
+                ),
+                builtin("Gui", Vec::new(), vec![Slot::Color], BuiltinPass::Gui),
+            ],
+            format,
+            dimensions,
+            color_targets: Vec::new(),
+            targets: HashMap::new(),
+            descriptions: HashMap::new(),
+            blit: Blit::new(device, format, BLIT_SHADER.load(device, "Blit Shader")),
+            color_filter: ColorFilter::None,
+        };
+
+        // The post-processing passes run right after the world, so custom passes
+        // see the tonemapped frame in the surface format
+        graph.describe_target(HDR_TARGET, Texture::HDR_FORMAT, 1);
+        graph.describe_target(BLOOM_TARGET, Texture::HDR_FORMAT, 2);
+        graph.describe_target(BLOOM_SCRATCH_TARGET, Texture::HDR_FORMAT, 2);
+        graph.insert(Box::new(Bloom::new(
+            device,
+            &BLOOM_SHADER.load(device, "Bloom Shader"),
+        )));
+        graph.insert(Box::new(Tonemap::new(
+            device,
+            format,
+            &TONEMAP_SHADER.load(device, "Tonemap Shader"),
+        )));
+        graph.insert(Box::new(Fxaa::new(
+            device,
+            format,
+            &FXAA_SHADER.load(device, "FXAA Shader"),
+        )));
+        graph.set_color_filter(device, ColorFilter::None);
+        graph
+    }
+
+    /// Rebuilds the color filter pass, which runs last so it filters every effect before it
+    pub fn set_color_filter(&mut self, device: &Device, color_filter: ColorFilter) {
+        self.color_filter = color_filter;
+        self.insert(Box::new(ColorFilterPass::new(
+            device,
+            self.format,
+            &COLOR_FILTER_SHADER.load(device, "Color Filter Shader"),
+            color_filter,
+        )));
+    }
+
+    /// Creates the named offscreen target with this format and a fraction of the surface's size,
+    /// instead of the surface's format and size
+    pub fn describe_target(&mut self, name: &str, format: TextureFormat, divisor: u32) {
+        self.descriptions.insert(
+            name.to_string(),
+            TargetDescription {
+                format,
+                divisor: divisor.max(1),
+            },
+        );
+        self.targets.remove(name);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.passes.iter().any(|pass| pass.name == name)
+    }
+
+    /// Inserts a pass before the gui, replacing any custom pass with the same name
+    pub fn insert(&mut self, pass: Box<dyn CustomPass>) {
+        let node = PassNode {
+            name: pass.name().to_string(),
+            inputs: pass.inputs(),
+            outputs: pass.outputs(),
+            kind: PassKind::Custom(pass),
+        };
+        let existing = self.passes.iter().position(|existing| {
+            existing.name == node.name && matches!(existing.kind, PassKind::Custom(_))
+        });
+        match existing {
+            Some(index) => self.passes[index] = node,
+            None => {
+                let gui = self
+                    .passes
+                    .iter()
+                    .position(|pass| matches!(pass.kind, PassKind::Builtin(BuiltinPass::Gui)))
+                    .unwrap_or(self.passes.len());
+                self.passes.insert(gui, node);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.passes
+            .retain(|pass| pass.name != name || matches!(pass.kind, PassKind::Builtin(_)));
+    }
+
+    pub fn pass_names(&self) -> Vec<String> {
+        self.passes.iter().map(|pass| pass.name.clone()).collect()
+    }
+
+    /// Offscreen targets are recreated at the new size the next time they're used
+    pub fn resize(&mut self, dimensions: [u32; 2]) {
+        if dimensions != self.dimensions {
+            self.dimensions = dimensions;
+            self.color_targets.clear();
+            self.targets.clear();
+        }
+    }
+
+    /// Rebuilds the blit, or a post-processing pass the graph still has, from its edited shader.
+    /// The current pipelines are kept if the new ones fail to validate.
+    pub fn reload_shader(&mut self, device: &Device, name: &str) -> Result<()> {
+        let format = self.format;
+        let compile =
+            |shader: &ShaderAsset, label: &str| create_shader(device, label, shader.source());
+        let pass: Box<dyn CustomPass> = if name == BLOOM_SHADER.name {
+            validate(device, || {
+                Box::new(Bloom::new(device, &compile(&BLOOM_SHADER, "Bloom Shader")))
+            })?
+        } else if name == TONEMAP_SHADER.name {
+            validate(device, || {
+                Box::new(Tonemap::new(
+                    device,
+                    format,
+                    &compile(&TONEMAP_SHADER, "Tonemap Shader"),
+                ))
+            })?
+        } else if name == FXAA_SHADER.name {
+            validate(device, || {
+                Box::new(Fxaa::new(
+                    device,
+                    format,
+                    &compile(&FXAA_SHADER, "FXAA Shader"),
+                ))
+            })?
+        } else if name == COLOR_FILTER_SHADER.name {
+            let color_filter = self.color_filter;
+            validate(device, || {
+                Box::new(ColorFilterPass::new(
+                    device,
+                    format,
+                    &compile(&COLOR_FILTER_SHADER, "Color Filter Shader"),
+                    color_filter,
+                ))
+            })?
+        } else {
+            return self.reload_blit(device);
+        };
+        if self.contains(pass.name()) {
+            self.insert(pass);
+        }
+        Ok(())
+    }
+
+    fn reload_blit(&mut self, device: &Device) -> Result<()> {
+        let format = self.format;
+        self.blit = validate(device, || {
+            Blit::new(
+                device,
+                format,
+                create_shader(device, "Blit Shader", BLIT_SHADER.source()),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Runs every enabled pass in order, calling `run_builtin` with the color target for the builtin passes.
+    /// The world renders into the hdr target instead of the color when the settings tonemap it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        surface_view: &TextureView,
+        depth_view: &TextureView,
+        settings: &RenderSettings,
+        mut run_builtin: impl FnMut(BuiltinPass, &mut CommandEncoder, Option<&TextureView>),
+    ) {
+        let world_color = if settings.hdr() {
+            Slot::Target(HDR_TARGET.to_string())
+        } else {
+            Slot::Color
+        };
+        if let Some(world) = self
+            .passes
+            .iter_mut()
+            .find(|pass| matches!(pass.kind, PassKind::Builtin(BuiltinPass::World)))
+        {
+            world.outputs = vec![world_color, Slot::Depth];
+        }
+
+        let enabled = self
+            .passes
+            .iter()
+            .map(|pass| match &pass.kind {
+                PassKind::Builtin(_) => true,
+                PassKind::Custom(pass) => pass.enabled(settings),
+            })
+            .collect::<Vec<_>>();
+        let (plan, presented) = self.plan(&enabled);
+        self.create_targets(device, &plan, &enabled);
+
+        let color_targets = &self.color_targets;
+        let targets = &self.targets;
+        let color_view = |target: ColorTarget| match target {
+            ColorTarget::Surface => surface_view,
+            ColorTarget::Offscreen(index) => &color_targets[index].view,
+        };
+        let slot_view = |slot: &Slot, color: Option<ColorTarget>| match slot {
+            Slot::Color => color.map(color_view),
+            Slot::Depth => Some(depth_view),
+            Slot::Target(name) => targets.get(name).map(|target| &target.view),
+        };
+
+        for ((node, (color_input, color_output)), enabled) in
+            self.passes.iter_mut().zip(plan).zip(enabled)
+        {
+            if !enabled {
+                continue;
+            }
+            encoder.push_debug_group(&node.name);
+            match &mut node.kind {
+                PassKind::Builtin(pass) => {
+                    let view = node.outputs.iter().find_map(|slot| match slot {
+                        Slot::Depth => None,
+                        _ => slot_view(slot, color_output),
+                    });
+                    run_builtin(*pass, encoder, view)
+                }
+                PassKind::Custom(pass) => {
+                    let views = |slots: &[Slot], color: Option<ColorTarget>| {
+                        slots
+                            .iter()
+                            .filter_map(|slot| Some((slot.clone(), slot_view(slot, color)?)))
+                            .collect::<Vec<_>>()
+                    };
+                    let mut context = PassContext {
+                        device,
+                        queue,
+                        encoder: &mut *encoder,
+                        format: self.format,
+                        dimensions: self.dimensions,
+                        settings,
+                        inputs: views(&node.inputs, color_input),
+                        outputs: views(&node.outputs, color_output),
+                    };
+                    if let Err(error) = pass.execute(&mut context) {
+                        log::error!("Render pass '{}' failed: {}", node.name, error);
+                    }
+                }
+            }
+            encoder.pop_debug_group();
+        }
+
+        if let Some(ColorTarget::Offscreen(index)) = presented {
+            self.blit
+                .draw(device, encoder, &self.color_targets[index], surface_view);
+        }
+    }
+
+    /// Chooses the color texture each pass reads and writes, and the one holding the final frame.
+    /// Passes that write color without sampling it draw over the current color in place.
+    fn plan(
+        &self,
+        enabled: &[bool],
+    ) -> (
+        Vec<(Option<ColorTarget>, Option<ColorTarget>)>,
+        Option<ColorTarget>,
+    ) {
+        let sampled = self
+            .passes
+            .iter()
+            .zip(enabled)
+            .any(|(pass, enabled)| *enabled && pass.inputs.contains(&Slot::Color));
+        let mut current = None;
+        let mut versions = 0;
+        let plan = self
+            .passes
+            .iter()
+            .zip(enabled)
+            .map(|(pass, enabled)| {
+                if !enabled {
+                    return (None, None);
+                }
+                let reads = pass.inputs.contains(&Slot::Color);
+                let input = if reads { current } else { None };
+                let output = if !pass.outputs.contains(&Slot::Color) {
+                    None
+                } else if !reads && current.is_some() {
+                    current
+                } else {
+                    let target = if sampled {
+                        ColorTarget::Offscreen(versions % 2)
+                    } else {
+                        ColorTarget::Surface
+                    };
+                    versions += 1;
+                    current = Some(target);
+                    current
+                };
+                (input, output)
+            })
+            .collect();
+        (plan, current)
+    }
+
+    fn create_targets(
+        &mut self,
+        device: &Device,
+        plan: &[(Option<ColorTarget>, Option<ColorTarget>)],
+        enabled: &[bool],
+    ) {
+        let [width, height] = self.dimensions;
+        let color_targets = plan
+            .iter()
+            .flat_map(|(input, output)| [*input, *output])
+            .filter_map(|target| match target {
+                Some(ColorTarget::Offscreen(index)) => Some(index + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default();
+        while self.color_targets.len() < color_targets {
+            self.color_targets.push(Texture::create_render_target(
+                device,
+                width,
+                height,
+                self.format,
+                "Color Target",
+            ));
+        }
+
+        for (pass, _) in self
+            .passes
+            .iter()
+            .zip(enabled)
+            .filter(|(_, enabled)| **enabled)
+        {
+            for slot in pass.inputs.iter().chain(pass.outputs.iter()) {
+                if let Slot::Target(name) = slot {
+                    if !self.targets.contains_key(name) {
+                        let description =
+                            self.descriptions
+                                .get(name)
+                                .copied()
+                                .unwrap_or(TargetDescription {
+                                    format: self.format,
+                                    divisor: 1,
+                                });
+                        let target = Texture::create_render_target(
+                            device,
+                            (width / description.divisor).max(1),
+                            (height / description.divisor).max(1),
+                            description.format,
+                            name,
+                        );
+                        self.targets.insert(name.clone(), target);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Copies an offscreen color target to the surface with a fullscreen triangle
+struct Blit {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Blit {
+    fn new(device: &Device, format: TextureFormat, shader: wgpu::ShaderModule) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    fn draw(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &Texture,
+        target: &TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&source.sampler),
+                },
+            ],
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
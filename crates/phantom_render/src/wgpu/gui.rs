@@ -0,0 +1,89 @@
+use crate::GuiRenderer;
+use phantom_dependencies::{
+    anyhow::{anyhow, Result},
+    egui::{self, epaint::ClippedMesh, Color32, CtxRef, TextureId},
+    egui_wgpu_backend::{epi::TextureAllocator, RenderPass, ScreenDescriptor},
+    wgpu::{self, Device, Queue},
+};
+use std::sync::Arc;
+
+/// Draws the gui with wgpu into whichever view it's given, such as the surface or a render target
+pub struct WgpuGuiRenderer {
+    renderpass: RenderPass,
+    font_texture: Option<Arc<egui::Texture>>,
+    paint_jobs: Vec<ClippedMesh>,
+    screen_descriptor: ScreenDescriptor,
+}
+
+impl WgpuGuiRenderer {
+    pub fn new(device: &Device, format: wgpu::TextureFormat) -> Self {
+        Self {
+            renderpass: RenderPass::new(device, format, 1),
+            font_texture: None,
+            paint_jobs: Vec::new(),
+            screen_descriptor: ScreenDescriptor {
+                physical_width: 1,
+                physical_height: 1,
+                scale_factor: 1.0,
+            },
+        }
+    }
+
+    /// Uploads the font texture, images and meshes for a view of the given size.
+    /// Call once per frame before `execute`.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, dimensions: [u32; 2]) {
+        self.screen_descriptor = ScreenDescriptor {
+            physical_width: dimensions[0],
+            physical_height: dimensions[1],
+            scale_factor: 1.0, // TODO: Store the scale factor in the renderer and update it when winit reports that the scale factor has changed
+        };
+        if let Some(font_texture) = self.font_texture.as_ref() {
+            self.renderpass.update_texture(device, queue, font_texture);
+        }
+        self.renderpass.update_user_textures(device, queue);
+        self.renderpass
+            .update_buffers(device, queue, &self.paint_jobs, &self.screen_descriptor);
+    }
+
+    /// Draws the prepared gui over the contents of the view
+    pub fn execute(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) -> Result<()> {
+        self.renderpass
+            .execute(
+                encoder,
+                view,
+                &self.paint_jobs,
+                &self.screen_descriptor,
+                None,
+            )
+            .map_err(|error| anyhow!("Failed to execute the gui renderpass: {:?}", error))
+    }
+}
+
+impl GuiRenderer for WgpuGuiRenderer {
+    fn update(&mut self, context: &CtxRef, paint_jobs: Vec<ClippedMesh>) {
+        self.font_texture = Some(context.texture());
+        self.paint_jobs = paint_jobs;
+    }
+
+    fn create_image(&mut self, width: u32, height: u32, pixels: &[u8]) -> Option<TextureId> {
+        if pixels.len() != (width * height * 4) as usize {
+            return None;
+        }
+        let pixels = pixels
+            .chunks(4)
+            .map(|pixel| Color32::from_rgba_unmultiplied(pixel[0], pixel[1], pixel[2], pixel[3]))
+            .collect::<Vec<_>>();
+        Some(
+            self.renderpass
+                .alloc_srgba_premultiplied((width as usize, height as usize), &pixels),
+        )
+    }
+
+    fn free_image(&mut self, id: TextureId) {
+        self.renderpass.free(id);
+    }
+}
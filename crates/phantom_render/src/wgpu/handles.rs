@@ -0,0 +1,186 @@
+use super::{
+    texture::Texture,
+    uniform::{MaterialBinding, MaterialUniform},
+};
+use crate::{
+    handles::Slots, MaterialHandle, MaterialTextures, MeshHandle, MeshInstance, TextureHandle,
+};
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    bytemuck,
+    wgpu::{self, util::DeviceExt, Device, Queue},
+};
+use phantom_world::{BoundingBox, Material, Vertex};
+
+pub(crate) struct GpuMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub number_of_indices: u32,
+    pub bounding_box: BoundingBox,
+}
+
+pub(crate) struct GpuMaterial {
+    pub bind_group: wgpu::BindGroup,
+    pub material: Material,
+    textures: MaterialTextures,
+
+    /// Kept alive for as long as the bind group uses it
+    _buffer: wgpu::Buffer,
+}
+
+/// Meshes, textures and materials created through handles rather than from the world
+#[derive(Default)]
+pub(crate) struct HandleResources {
+    pub meshes: Slots<GpuMesh>,
+    pub textures: Slots<Texture>,
+    pub materials: Slots<GpuMaterial>,
+    pub instances: Vec<MeshInstance>,
+}
+
+impl HandleResources {
+    pub fn upload_mesh(
+        &mut self,
+        device: &Device,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Result<MeshHandle> {
+        if vertices.is_empty() || indices.is_empty() {
+            bail!("A mesh needs at least one vertex and one index!");
+        }
+        if let Some(index) = indices
+            .iter()
+            .find(|index| **index as usize >= vertices.len())
+        {
+            bail!(
+                "Index {} is out of range for a mesh with {} vertices!",
+                index,
+                vertices.len()
+            );
+        }
+
+        let mut bounding_box = BoundingBox::new_invalid();
+        vertices
+            .iter()
+            .for_each(|vertex| bounding_box.fit_point(vertex.position));
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Ok(MeshHandle(self.meshes.insert(GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            number_of_indices: indices.len() as u32,
+            bounding_box,
+        })))
+    }
+
+    pub fn upload_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        texture: &phantom_world::Texture,
+        srgb: bool,
+    ) -> Result<TextureHandle> {
+        let texture = Texture::from_world_texture(device, queue, texture, srgb, "Handle Texture")?;
+        Ok(TextureHandle(self.textures.insert(texture)))
+    }
+
+    /// Materials keep sampling a freed texture until they are recreated
+    pub fn free_texture(&mut self, texture: TextureHandle) {
+        self.textures.remove(texture.0);
+    }
+
+    pub fn create_material(
+        &mut self,
+        device: &Device,
+        binding: &MaterialBinding,
+        defaults: [&Texture; 2],
+        material: &Material,
+        textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        let gpu_material = self.gpu_material(device, binding, defaults, material, textures)?;
+        Ok(MaterialHandle(self.materials.insert(gpu_material)))
+    }
+
+    pub fn update_material(
+        &mut self,
+        device: &Device,
+        binding: &MaterialBinding,
+        defaults: [&Texture; 2],
+        handle: MaterialHandle,
+        material: &Material,
+    ) -> Result<()> {
+        let textures = self
+            .materials
+            .get(handle.0)
+            .context("The material has been freed!")?
+            .textures;
+        let gpu_material = self.gpu_material(device, binding, defaults, material, &textures)?;
+        if let Some(existing) = self.materials.get_mut(handle.0) {
+            *existing = gpu_material;
+        }
+        Ok(())
+    }
+
+    /// The white and flat normal textures in `defaults` replace textures the material doesn't have
+    fn gpu_material(
+        &self,
+        device: &Device,
+        binding: &MaterialBinding,
+        defaults: [&Texture; 2],
+        material: &Material,
+        textures: &MaterialTextures,
+    ) -> Result<GpuMaterial> {
+        let [white, flat_normal] = defaults;
+        let texture = |handle: Option<TextureHandle>, default| match handle {
+            Some(handle) => self
+                .textures
+                .get(handle.0)
+                .context(format!("Texture {:?} has been freed!", handle)),
+            None => Ok(default),
+        };
+        let (bind_group, buffer) = binding.create_bind_group(
+            device,
+            &material_uniform(material, textures),
+            [
+                texture(textures.color, white)?,
+                texture(textures.metallic_roughness, white)?,
+                texture(textures.normal, flat_normal)?,
+                texture(textures.occlusion, white)?,
+                texture(textures.emissive, white)?,
+            ],
+        );
+        Ok(GpuMaterial {
+            bind_group,
+            material: material.clone(),
+            textures: *textures,
+            _buffer: buffer,
+        })
+    }
+}
+
+/// Texture sets come from the material, but whether a texture is sampled comes from the handles
+fn material_uniform(material: &Material, textures: &MaterialTextures) -> MaterialUniform {
+    let set = |texture: Option<TextureHandle>, set: i32| match texture {
+        Some(_) => set.max(0),
+        None => -1,
+    };
+    MaterialUniform {
+        color_texture_set: set(textures.color, material.color_texture_set),
+        metallic_roughness_texture_set: set(
+            textures.metallic_roughness,
+            material.metallic_roughness_texture_set,
+        ),
+        normal_texture_set: set(textures.normal, material.normal_texture_set),
+        occlusion_texture_set: set(textures.occlusion, material.occlusion_texture_set),
+        emissive_texture_set: set(textures.emissive, material.emissive_texture_set),
+        ..MaterialUniform::from(material)
+    }
+}
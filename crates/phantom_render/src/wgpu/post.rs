@@ -0,0 +1,477 @@
+use super::{
+    graph::{CustomPass, PassContext, Slot},
+    texture::Texture,
+};
+use crate::{ColorFilter, RenderSettings, Tonemapping};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    wgpu::{
+        self, util::DeviceExt, CommandEncoder, Device, Queue, ShaderModule, TextureFormat,
+        TextureView,
+    },
+};
+
+/// The world is rendered into this before it is tonemapped
+pub(crate) const HDR_TARGET: &str = "hdr";
+
+/// Bloom is extracted and blurred at half resolution, ping-ponging between these
+pub(crate) const BLOOM_TARGET: &str = "bloom";
+pub(crate) const BLOOM_SCRATCH_TARGET: &str = "bloom_scratch";
+
+fn target(name: &str) -> Slot {
+    Slot::Target(name.to_string())
+}
+
+/// Draws a fullscreen triangle that samples textures and reads a uniform of parameters
+struct FullscreenPipeline {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl FullscreenPipeline {
+    fn new(
+        device: &Device,
+        shader: &ShaderModule,
+        entry_point: &str,
+        texture_count: u32,
+        format: TextureFormat,
+        label: &str,
+    ) -> Self {
+        let mut entries = (0..texture_count)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: texture_count,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: texture_count + 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &entries,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    fn draw(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        textures: &[&TextureView],
+        parameters: &Parameters,
+    ) {
+        let mut entries = textures
+            .iter()
+            .enumerate()
+            .map(|(binding, texture)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::TextureView(texture),
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupEntry {
+            binding: textures.len() as u32,
+            resource: wgpu::BindingResource::Sampler(&self.sampler),
+        });
+        entries.push(wgpu::BindGroupEntry {
+            binding: textures.len() as u32 + 1,
+            resource: parameters.buffer.as_entire_binding(),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// The parameters of a single draw.
+/// Buffer writes all land before the frame's commands run,
+/// so draws in the same frame can't share one.
+struct Parameters {
+    buffer: wgpu::Buffer,
+}
+
+impl Parameters {
+    fn new(device: &Device, label: &str) -> Self {
+        Self::with_values(device, label, &[0.0; 4])
+    }
+
+    /// Parameters larger than four floats, which are set once when the pass is created
+    fn with_values(device: &Device, label: &str, values: &[f32]) -> Self {
+        Self {
+            buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck_values(values),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+        }
+    }
+
+    fn write(&self, queue: &Queue, values: [f32; 4]) {
+        queue.write_buffer(&self.buffer, 0, bytemuck_values(&values));
+    }
+}
+
+fn bytemuck_values(values: &[f32]) -> &[u8] {
+    phantom_dependencies::bytemuck::cast_slice(values)
+}
+
+pub(crate) struct Bloom {
+    extract: FullscreenPipeline,
+    blur: FullscreenPipeline,
+    extract_parameters: Parameters,
+    horizontal_parameters: Parameters,
+    vertical_parameters: Parameters,
+}
+
+impl Bloom {
+    pub fn new(device: &Device, shader: &ShaderModule) -> Self {
+        let format = Texture::HDR_FORMAT;
+        Self {
+            extract: FullscreenPipeline::new(
+                device,
+                shader,
+                "fs_extract",
+                1,
+                format,
+                "Bloom Extract",
+            ),
+            blur: FullscreenPipeline::new(device, shader, "fs_blur", 1, format, "Bloom Blur"),
+            extract_parameters: Parameters::new(device, "Bloom Extract Parameters"),
+            horizontal_parameters: Parameters::new(device, "Bloom Horizontal Parameters"),
+            vertical_parameters: Parameters::new(device, "Bloom Vertical Parameters"),
+        }
+    }
+}
+
+impl CustomPass for Bloom {
+    fn name(&self) -> &str {
+        "Bloom"
+    }
+
+    fn enabled(&self, settings: &RenderSettings) -> bool {
+        settings.bloom
+    }
+
+    fn inputs(&self) -> Vec<Slot> {
+        vec![target(HDR_TARGET)]
+    }
+
+    fn outputs(&self) -> Vec<Slot> {
+        vec![target(BLOOM_TARGET), target(BLOOM_SCRATCH_TARGET)]
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<()> {
+        let hdr = context
+            .input(&target(HDR_TARGET))
+            .context("Bloom needs the hdr target!")?;
+        let bloom = context
+            .output(&target(BLOOM_TARGET))
+            .context("Bloom needs its target!")?;
+        let scratch = context
+            .output(&target(BLOOM_SCRATCH_TARGET))
+            .context("Bloom needs its scratch target!")?;
+
+        // The bloom targets are half the size of the frame
+        let [width, height] = context.dimensions;
+        let texel_width = 2.0 / width.max(1) as f32;
+        let texel_height = 2.0 / height.max(1) as f32;
+        self.extract_parameters.write(
+            context.queue,
+            [context.settings.bloom_threshold, 0.0, 0.0, 0.0],
+        );
+        self.horizontal_parameters
+            .write(context.queue, [texel_width, 0.0, 0.0, 0.0]);
+        self.vertical_parameters
+            .write(context.queue, [0.0, texel_height, 0.0, 0.0]);
+
+        let device = context.device;
+        self.extract.draw(
+            device,
+            context.encoder,
+            bloom,
+            &[hdr],
+            &self.extract_parameters,
+        );
+        self.blur.draw(
+            device,
+            context.encoder,
+            scratch,
+            &[bloom],
+            &self.horizontal_parameters,
+        );
+        self.blur.draw(
+            device,
+            context.encoder,
+            bloom,
+            &[scratch],
+            &self.vertical_parameters,
+        );
+        Ok(())
+    }
+}
+
+pub(crate) struct Tonemap {
+    pipeline: FullscreenPipeline,
+    parameters: Parameters,
+}
+
+impl Tonemap {
+    pub fn new(device: &Device, format: TextureFormat, shader: &ShaderModule) -> Self {
+        Self {
+            pipeline: FullscreenPipeline::new(device, shader, "fs_main", 2, format, "Tonemap"),
+            parameters: Parameters::new(device, "Tonemap Parameters"),
+        }
+    }
+}
+
+impl CustomPass for Tonemap {
+    fn name(&self) -> &str {
+        "Tonemap"
+    }
+
+    fn enabled(&self, settings: &RenderSettings) -> bool {
+        settings.hdr()
+    }
+
+    fn inputs(&self) -> Vec<Slot> {
+        vec![target(HDR_TARGET), target(BLOOM_TARGET)]
+    }
+
+    fn outputs(&self) -> Vec<Slot> {
+        vec![Slot::Color]
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<()> {
+        let hdr = context
+            .input(&target(HDR_TARGET))
+            .context("Tonemapping needs the hdr target!")?;
+        let bloom = context
+            .input(&target(BLOOM_TARGET))
+            .context("Tonemapping needs the bloom target!")?;
+        let color = context
+            .output(&Slot::Color)
+            .context("Tonemapping needs the color target!")?;
+
+        let settings = context.settings;
+        let bloom_intensity = if settings.bloom {
+            settings.bloom_intensity
+        } else {
+            0.0
+        };
+        let operator = match settings.tonemapping {
+            Tonemapping::None => 0.0,
+            Tonemapping::Reinhard => 1.0,
+            Tonemapping::Aces => 2.0,
+        };
+        self.parameters.write(
+            context.queue,
+            [settings.exposure, bloom_intensity, operator, 0.0],
+        );
+        self.pipeline.draw(
+            context.device,
+            context.encoder,
+            color,
+            &[hdr, bloom],
+            &self.parameters,
+        );
+        Ok(())
+    }
+}
+
+pub(crate) struct Fxaa {
+    pipeline: FullscreenPipeline,
+    parameters: Parameters,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device, format: TextureFormat, shader: &ShaderModule) -> Self {
+        Self {
+            pipeline: FullscreenPipeline::new(device, shader, "fs_main", 1, format, "FXAA"),
+            parameters: Parameters::new(device, "FXAA Parameters"),
+        }
+    }
+}
+
+impl CustomPass for Fxaa {
+    fn name(&self) -> &str {
+        "FXAA"
+    }
+
+    fn enabled(&self, settings: &RenderSettings) -> bool {
+        settings.fxaa
+    }
+
+    fn inputs(&self) -> Vec<Slot> {
+        vec![Slot::Color]
+    }
+
+    fn outputs(&self) -> Vec<Slot> {
+        vec![Slot::Color]
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<()> {
+        let source = context
+            .input(&Slot::Color)
+            .context("FXAA needs the color to smooth!")?;
+        let color = context
+            .output(&Slot::Color)
+            .context("FXAA needs the color target!")?;
+        let [width, height] = context.dimensions;
+        self.parameters.write(
+            context.queue,
+            [
+                1.0 / width.max(1) as f32,
+                1.0 / height.max(1) as f32,
+                0.0,
+                0.0,
+            ],
+        );
+        self.pipeline.draw(
+            context.device,
+            context.encoder,
+            color,
+            &[source],
+            &self.parameters,
+        );
+        Ok(())
+    }
+}
+
+/// Corrects the colors of the finished frame for a color vision deficiency,
+/// so everything drawn before the gui is filtered the same way
+pub(crate) struct ColorFilterPass {
+    pipeline: FullscreenPipeline,
+    parameters: Parameters,
+    color_filter: ColorFilter,
+}
+
+impl ColorFilterPass {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        shader: &ShaderModule,
+        color_filter: ColorFilter,
+    ) -> Self {
+        Self {
+            pipeline: FullscreenPipeline::new(device, shader, "fs_main", 1, format, "Color Filter"),
+            parameters: Parameters::with_values(
+                device,
+                "Color Filter Parameters",
+                color_filter.matrix().as_slice(),
+            ),
+            color_filter,
+        }
+    }
+}
+
+impl CustomPass for ColorFilterPass {
+    fn name(&self) -> &str {
+        "Color Filter"
+    }
+
+    fn enabled(&self, _settings: &RenderSettings) -> bool {
+        self.color_filter != ColorFilter::None
+    }
+
+    fn inputs(&self) -> Vec<Slot> {
+        vec![Slot::Color]
+    }
+
+    fn outputs(&self) -> Vec<Slot> {
+        vec![Slot::Color]
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<()> {
+        let source = context
+            .input(&Slot::Color)
+            .context("The color filter needs the color to filter!")?;
+        let color = context
+            .output(&Slot::Color)
+            .context("The color filter needs the color target!")?;
+        self.pipeline.draw(
+            context.device,
+            context.encoder,
+            color,
+            &[source],
+            &self.parameters,
+        );
+        Ok(())
+    }
+}
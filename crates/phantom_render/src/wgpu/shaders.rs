@@ -0,0 +1,154 @@
+use phantom_dependencies::{
+    anyhow::{bail, Result},
+    log,
+    notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher},
+    pollster,
+    wgpu::{self, Device, ShaderModule},
+};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Duration,
+};
+
+/// A WGSL shader read from the assets directory at runtime.
+/// A copy is embedded in the binary for platforms that don't ship the assets directory.
+pub(crate) struct ShaderAsset {
+    pub name: &'static str,
+    embedded: &'static str,
+}
+
+pub(crate) const WORLD_SHADER: ShaderAsset = ShaderAsset {
+    name: "shader.wgsl",
+    embedded: include_str!("../../../../assets/shaders/shader.wgsl"),
+};
+
+pub(crate) const CLOTH_SHADER: ShaderAsset = ShaderAsset {
+    name: "cloth.wgsl",
+    embedded: include_str!("../../../../assets/shaders/cloth.wgsl"),
+};
+
+pub(crate) const BLIT_SHADER: ShaderAsset = ShaderAsset {
+    name: "blit.wgsl",
+    embedded: include_str!("../../../../assets/shaders/blit.wgsl"),
+};
+
+pub(crate) const BLOOM_SHADER: ShaderAsset = ShaderAsset {
+    name: "bloom.wgsl",
+    embedded: include_str!("../../../../assets/shaders/bloom.wgsl"),
+};
+
+pub(crate) const TONEMAP_SHADER: ShaderAsset = ShaderAsset {
+    name: "tonemap.wgsl",
+    embedded: include_str!("../../../../assets/shaders/tonemap.wgsl"),
+};
+
+pub(crate) const FXAA_SHADER: ShaderAsset = ShaderAsset {
+    name: "fxaa.wgsl",
+    embedded: include_str!("../../../../assets/shaders/fxaa.wgsl"),
+};
+
+pub(crate) const COLOR_FILTER_SHADER: ShaderAsset = ShaderAsset {
+    name: "color_filter.wgsl",
+    embedded: include_str!("../../../../assets/shaders/color_filter.wgsl"),
+};
+
+impl ShaderAsset {
+    /// The shader's source from the assets directory, or the embedded copy if it can't be read
+    pub fn source(&self) -> Cow<'static, str> {
+        match std::fs::read_to_string(Path::new(ShaderManager::DIRECTORY).join(self.name)) {
+            Ok(source) => Cow::Owned(source),
+            Err(_) => Cow::Borrowed(self.embedded),
+        }
+    }
+
+    /// Compiles the shader, falling back to the embedded copy if the one on disk is invalid
+    pub fn load(&self, device: &Device, label: &str) -> ShaderModule {
+        match validate(device, || create_shader(device, label, self.source())) {
+            Ok(shader) => shader,
+            Err(error) => {
+                log::error!(
+                    "Failed to compile shader '{}', using the embedded copy: {}",
+                    self.name,
+                    error
+                );
+                create_shader(device, label, Cow::Borrowed(self.embedded))
+            }
+        }
+    }
+}
+
+pub(crate) fn create_shader(device: &Device, label: &str, source: Cow<str>) -> ShaderModule {
+    device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source),
+    })
+}
+
+/// Runs `build` and fails if it caused any validation errors,
+/// so broken shaders can be rejected instead of panicking the device
+pub(crate) fn validate<T>(device: &Device, build: impl FnOnce() -> T) -> Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = build();
+    if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+        bail!("{}", error);
+    }
+    Ok(result)
+}
+
+/// Watches the shaders directory so pipelines can be rebuilt when a shader is edited
+pub(crate) struct ShaderManager {
+    _watcher: Option<RecommendedWatcher>,
+    receiver: Receiver<DebouncedEvent>,
+}
+
+impl ShaderManager {
+    pub const DIRECTORY: &'static str = "assets/shaders";
+
+    /// Editors often write a file in several steps, so changes are batched for this long
+    const DEBOUNCE_DELAY: Duration = Duration::from_millis(200);
+
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        let watcher = match Self::watch(sender) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                log::info!(
+                    "Shaders in '{}' won't be hot reloaded: {}",
+                    Self::DIRECTORY,
+                    error
+                );
+                None
+            }
+        };
+        Self {
+            _watcher: watcher,
+            receiver,
+        }
+    }
+
+    fn watch(sender: Sender<DebouncedEvent>) -> Result<RecommendedWatcher> {
+        let mut watcher = notify::watcher(sender, Self::DEBOUNCE_DELAY)?;
+        watcher.watch(Self::DIRECTORY, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+
+    /// Names of the shaders that changed on disk since the last call
+    pub fn changed(&self) -> HashSet<String> {
+        self.receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => Some(path),
+                _ => None,
+            })
+            .filter(|path| {
+                path.extension().and_then(|extension| extension.to_str()) == Some("wgsl")
+            })
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+            .collect()
+    }
+}
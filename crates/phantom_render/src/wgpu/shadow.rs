@@ -0,0 +1,158 @@
+use super::{texture::Texture, uniform::UniformBinding};
+use phantom_dependencies::{
+    nalgebra_glm as glm,
+    wgpu::{self, Device},
+};
+
+/// Scene depth rendered from the directional light,
+/// which the lit pipelines compare against to find shadowed fragments
+pub(crate) struct ShadowMap {
+    pub resolution: u32,
+    pub texture: Texture,
+
+    /// The scene uniform and lights alongside the shadow map and its comparison sampler.
+    /// The lit pipelines bind this in place of the uniform bind group,
+    /// which the shadow pass binds while rendering into the shadow map.
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMap {
+    pub fn new(device: &Device, uniform_binding: &UniformBinding, resolution: u32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Shadow Map Bind Group Layout"),
+        });
+        let resolution = Self::supported_resolution(device, resolution);
+        let texture =
+            Texture::create_depth_texture(device, resolution, resolution, 1, "Shadow Map");
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, uniform_binding, &texture);
+        Self {
+            resolution,
+            texture,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Recreates the shadow map if its resolution changed
+    pub fn resize(&mut self, device: &Device, uniform_binding: &UniformBinding, resolution: u32) {
+        let resolution = Self::supported_resolution(device, resolution);
+        if resolution == self.resolution {
+            return;
+        }
+        self.resolution = resolution;
+        self.texture =
+            Texture::create_depth_texture(device, resolution, resolution, 1, "Shadow Map");
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            uniform_binding,
+            &self.texture,
+        );
+    }
+
+    fn supported_resolution(device: &Device, resolution: u32) -> u32 {
+        resolution.clamp(1, device.limits().max_texture_dimension_2d)
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_binding: &UniformBinding,
+        texture: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_binding.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform_binding.light_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Shadow Map Bind Group"),
+        })
+    }
+
+    /// An orthographic projection from the light covering the area around the center.
+    /// The area is snapped to whole texels so shadow edges don't shimmer as the camera moves.
+    pub fn light_view_projection(
+        light_direction: &glm::Vec3,
+        center: &glm::Vec3,
+        distance: f32,
+        resolution: u32,
+    ) -> glm::Mat4 {
+        let up = if light_direction.normalize().y.abs() > 0.99 {
+            glm::Vec3::z()
+        } else {
+            glm::Vec3::y()
+        };
+        let view = glm::look_at(&glm::Vec3::zeros(), light_direction, &up);
+        let center = view * glm::vec4(center.x, center.y, center.z, 1.0);
+        let texel = 2.0 * distance / resolution as f32;
+        let x = (center.x / texel).floor() * texel;
+        let y = (center.y / texel).floor() * texel;
+
+        // Casters further towards the light than the area itself can still shadow it
+        let depth = -center.z;
+        let projection = glm::ortho_rh_zo(
+            x - distance,
+            x + distance,
+            y - distance,
+            y + distance,
+            depth - distance * 3.0,
+            depth + distance,
+        );
+        projection * view
+    }
+}
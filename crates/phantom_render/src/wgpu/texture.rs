@@ -1,5 +1,5 @@
 use phantom_dependencies::{anyhow::Result, wgpu};
-use phantom_world::{Filter, WrappingMode};
+use phantom_world::{Filter, Format, WrappingMode};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -9,11 +9,15 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
+    /// Color textures are stored in sRGB, while textures holding data
+    /// such as normals or roughness must be sampled linearly
     pub fn from_world_texture(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         world_texture: &phantom_world::Texture,
+        srgb: bool,
         label: &str,
     ) -> Result<Self> {
         let size = wgpu::Extent3d {
@@ -22,7 +26,20 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let format = Self::map_texture_format(world_texture.format);
+        let format = Self::map_texture_format(world_texture.format, srgb);
+
+        // wgpu has no three channel formats, so an opaque alpha channel is added
+        let (pixels, bytes_per_row) = match world_texture.format {
+            Format::R8G8B8 | Format::B8G8R8 => (
+                world_texture
+                    .pixels
+                    .chunks(3)
+                    .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                    .collect::<Vec<_>>(),
+                4 * world_texture.width,
+            ),
+            _ => (world_texture.pixels.to_vec(), world_texture.bytes_per_row()),
+        };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some(label),
@@ -41,10 +58,10 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &world_texture.pixels,
+            &pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(world_texture.bytes_per_row()),
+                bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
                 rows_per_image: std::num::NonZeroU32::new(world_texture.height),
             },
             size,
@@ -70,9 +87,14 @@ impl Texture {
         })
     }
 
-    fn map_texture_format(texture_format: phantom_world::Format) -> wgpu::TextureFormat {
-        // FIXME: Map texture formats
-        wgpu::TextureFormat::Rgba8UnormSrgb
+    fn map_texture_format(texture_format: Format, srgb: bool) -> wgpu::TextureFormat {
+        match (texture_format, srgb) {
+            (Format::B8G8R8 | Format::B8G8R8A8, true) => wgpu::TextureFormat::Bgra8UnormSrgb,
+            (Format::B8G8R8 | Format::B8G8R8A8, false) => wgpu::TextureFormat::Bgra8Unorm,
+            // FIXME: Map the remaining texture formats
+            (_, true) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            (_, false) => wgpu::TextureFormat::Rgba8Unorm,
+        }
     }
 
     fn map_sampler(sampler: &phantom_world::Sampler) -> wgpu::SamplerDescriptor<'static> {
@@ -120,6 +142,7 @@ impl Texture {
         device: &wgpu::Device,
         width: u32,
         height: u32,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -131,7 +154,7 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -158,4 +181,67 @@ impl Texture {
             sampler,
         }
     }
+
+    /// An offscreen color target that later passes can sample
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// A color target the world is rendered into with multisampling,
+    /// then resolved into the single sampled surface texture
+    pub fn create_multisampled_framebuffer(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Multisampled Framebuffer"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
 }
@@ -0,0 +1,99 @@
+use super::{capture::FrameCapture, texture::Texture, world::WorldRender, WgpuRenderer};
+use crate::{CapturedFrame, FrameBuilder, RenderFrame};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    pollster,
+    wgpu::{self, Device, Queue},
+};
+use phantom_world::World;
+
+/// Renders the world from its active camera without a window,
+/// for tools such as thumbnail generation on build servers
+pub fn render_thumbnail(world: &World, width: u32, height: u32) -> Result<CapturedFrame> {
+    pollster::block_on(render_thumbnail_async(world, width, height))
+}
+
+async fn render_thumbnail_async(world: &World, width: u32, height: u32) -> Result<CapturedFrame> {
+    let instance = wgpu::Instance::new(WgpuRenderer::backends());
+    let adapter = wgpu::util::initialize_adapter_from_env_or_default(
+        &instance,
+        WgpuRenderer::backends(),
+        None,
+    )
+    .await
+    .context("No suitable GPU adapters found on the system!")?;
+    let (device, queue) = WgpuRenderer::request_device(&adapter).await?;
+    let frame = FrameBuilder::default().build(world)?;
+    render(&device, &queue, &frame, width, height)
+}
+
+/// Renders with an existing device, such as the window's renderer.
+/// The frame must carry all of its world's assets, as a new `FrameBuilder` builds them.
+pub(crate) fn render(
+    device: &Device,
+    queue: &Queue,
+    frame: &RenderFrame,
+    width: u32,
+    height: u32,
+) -> Result<CapturedFrame> {
+    let config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width,
+        height,
+        present_mode: wgpu::PresentMode::Fifo,
+    };
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Thumbnail Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_texture = Texture::create_depth_texture(device, width, height, 1, "Thumbnail Depth");
+
+    let mut world_render = WorldRender::new(device, queue, &config, false)?;
+    world_render.update(device, queue, frame, width as f32 / height.max(1) as f32)?;
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Thumbnail Encoder"),
+    });
+    world_render.render_shadows(&mut encoder);
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Thumbnail Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        world_render.render(&mut render_pass)?;
+    }
+
+    let mut frame_capture = FrameCapture::default();
+    frame_capture.copy(device, &mut encoder, &target, width, height);
+    queue.submit(std::iter::once(encoder.finish()));
+    frame_capture
+        .read_blocking(device, config.format)?
+        .context("Failed to read back the thumbnail!")
+}
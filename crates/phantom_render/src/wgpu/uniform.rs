@@ -3,7 +3,7 @@ use phantom_dependencies::{
     bytemuck, nalgebra_glm as glm,
     wgpu::{self, util::DeviceExt, BufferAddress, Queue},
 };
-use phantom_world::Vertex;
+use phantom_world::{AlphaMode, LightKind, Vertex};
 use std::mem::size_of;
 
 pub(crate) struct Geometry {
@@ -115,6 +115,9 @@ impl Geometry {
 
 pub(crate) struct UniformBinding {
     pub buffer: wgpu::Buffer,
+
+    /// The lights in the world, bound alongside the shadow map for the lit pipelines
+    pub light_buffer: wgpu::Buffer,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
@@ -127,10 +130,17 @@ impl UniformBinding {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Buffer"),
+            size: size_of::<LightsUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -152,6 +162,7 @@ impl UniformBinding {
 
         Self {
             buffer,
+            light_buffer,
             bind_group_layout,
             bind_group,
         }
@@ -165,6 +176,10 @@ impl UniformBinding {
     ) {
         queue.write_buffer(&self.buffer, offset, bytemuck::cast_slice(data));
     }
+
+    pub fn upload_lights(&self, queue: &Queue, lights: &LightsUniform) {
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::bytes_of(lights));
+    }
 }
 
 #[repr(C)]
@@ -172,12 +187,87 @@ impl UniformBinding {
 pub(crate) struct Uniform {
     pub view: glm::Mat4,
     pub projection: glm::Mat4,
+    pub camera_position: glm::Vec4,
+    pub light_view_projection: glm::Mat4,
+
+    /// Points towards the directional light
+    pub light_direction: glm::Vec4,
+
+    /// The shadow bias, one if shadows are enabled, and the size of a shadow map texel
+    pub shadow_parameters: glm::Vec4,
 }
 
 unsafe impl bytemuck::Zeroable for Uniform {}
 
 unsafe impl bytemuck::Pod for Uniform {}
 
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+pub(crate) struct LightUniform {
+    pub position: glm::Vec4,
+
+    /// The direction directional and spot lights shine in
+    pub direction: glm::Vec4,
+
+    /// The light's color in rgb and its intensity in alpha
+    pub color: glm::Vec4,
+
+    /// Zero or less for lights without a range
+    pub range: f32,
+    pub kind: u32,
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+}
+
+unsafe impl bytemuck::Zeroable for LightUniform {}
+
+unsafe impl bytemuck::Pod for LightUniform {}
+
+impl LightUniform {
+    pub const DIRECTIONAL: u32 = 0;
+    pub const POINT: u32 = 1;
+    pub const SPOT: u32 = 2;
+
+    pub fn new(light: &phantom_world::Light, position: glm::Vec3, direction: glm::Vec3) -> Self {
+        let (kind, inner_cone_cos, outer_cone_cos) = match light.kind {
+            LightKind::Directional => (Self::DIRECTIONAL, 0.0, 0.0),
+            LightKind::Point => (Self::POINT, 0.0, 0.0),
+            LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => (Self::SPOT, inner_cone_angle.cos(), outer_cone_angle.cos()),
+        };
+        let color = light.color;
+        Self {
+            position: glm::vec4(position.x, position.y, position.z, 1.0),
+            direction: glm::vec4(direction.x, direction.y, direction.z, 0.0),
+            color: glm::vec4(color.x, color.y, color.z, light.intensity),
+            range: light.range,
+            kind,
+            inner_cone_cos,
+            outer_cone_cos,
+        }
+    }
+}
+
+/// The most lights that can shine on the world at once, the nearest to the camera are kept
+pub(crate) const MAX_LIGHTS: usize = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LightsUniform {
+    pub lights: [LightUniform; MAX_LIGHTS],
+    pub count: u32,
+
+    /// Index of the light casting shadows, or -1 if no light does
+    pub shadow_light: i32,
+    pub padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for LightsUniform {}
+
+unsafe impl bytemuck::Pod for LightsUniform {}
+
 pub(crate) struct DynamicUniformBinding {
     pub alignment: wgpu::BufferAddress,
     pub buffer: wgpu::Buffer,
@@ -189,7 +279,9 @@ impl DynamicUniformBinding {
     pub const MAX_NUMBER_OF_MESHES: usize = 10_000;
 
     pub fn new(device: &wgpu::Device) -> Self {
-        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        // Each uniform is padded to 256 bytes, the largest offset alignment a device can require,
+        // so the uniform's size is always a valid dynamic offset
+        let alignment = size_of::<DynamicUniform>() as wgpu::BufferAddress;
 
         let buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Dynamic Uniform Buffer"),
@@ -235,10 +327,7 @@ impl DynamicUniformBinding {
 
     pub fn upload_uniform_data(&self, queue: &Queue, offset: BufferAddress, data: &[impl Copy]) {
         queue.write_buffer(&self.buffer, offset, unsafe {
-            std::slice::from_raw_parts(
-                data.as_ptr() as *const u8,
-                data.len() * self.alignment as usize,
-            )
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
         });
     }
 }
@@ -247,66 +336,335 @@ impl DynamicUniformBinding {
 #[derive(Default, Copy, Clone, Debug)]
 pub(crate) struct DynamicUniform {
     pub model: glm::Mat4,
+
+    /// Index of the mesh's first joint matrix, for skinned meshes
+    pub joint_offset: u32,
 }
 
 unsafe impl bytemuck::Zeroable for DynamicUniform {}
 
-pub(crate) struct TextureBinding {
+/// Joint matrices of every skinned mesh in the world, read by the skinned vertex shader.
+/// Adapters that can't read storage buffers in vertex shaders, such as GLES 3.0,
+/// read them from a float texture instead, four texels to a matrix.
+pub(crate) struct JointBinding {
+    storage: JointStorage,
     pub bind_group_layout: wgpu::BindGroupLayout,
-    pub bind_groups: Vec<wgpu::BindGroup>,
+    pub bind_group: wgpu::BindGroup,
 }
 
-impl TextureBinding {
-    pub fn new(device: &wgpu::Device) -> Self {
+enum JointStorage {
+    Buffer(wgpu::Buffer),
+    Texture(wgpu::Texture),
+}
+
+impl JointBinding {
+    pub const MAX_NUMBER_OF_JOINTS: usize = 16_384;
+
+    /// Matrices in each row of the joint texture, which is kept within the GLES 3.0 texture size limit
+    const MATRICES_PER_ROW: usize = 256;
+
+    pub fn new(device: &wgpu::Device, vertex_storage: bool) -> Self {
+        let (storage, ty) = if vertex_storage {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Joint Buffer"),
+                size: (Self::MAX_NUMBER_OF_JOINTS * size_of::<glm::Mat4>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let ty = wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            };
+            (JointStorage::Buffer(buffer), ty)
+        } else {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Joint Texture"),
+                size: wgpu::Extent3d {
+                    width: (Self::MATRICES_PER_ROW * 4) as u32,
+                    height: (Self::MAX_NUMBER_OF_JOINTS / Self::MATRICES_PER_ROW) as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            });
+            let ty = wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            };
+            (JointStorage::Texture(texture), ty)
+        };
+
+        // The shader reads the storage buffer from binding 0 and the texture from binding 1
+        let binding = match storage {
+            JointStorage::Buffer(_) => 0,
+            JointStorage::Texture(_) => 1,
+        };
+
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty,
+                count: None,
+            }],
+            label: Some("Joint Buffer Bind Group Layout"),
+        });
+
+        let view;
+        let resource = match &storage {
+            JointStorage::Buffer(buffer) => buffer.as_entire_binding(),
+            JointStorage::Texture(texture) => {
+                view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                wgpu::BindingResource::TextureView(&view)
+            }
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding, resource }],
+            label: Some("Joint Buffer Bind Group"),
+        });
+
+        Self {
+            storage,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Whether the skinned shaders must read the joint matrices from the joint texture
+    pub fn uses_texture(&self) -> bool {
+        matches!(self.storage, JointStorage::Texture(_))
+    }
+
+    pub fn upload_joint_matrices(&self, queue: &Queue, joint_matrices: &[glm::Mat4]) {
+        match &self.storage {
+            JointStorage::Buffer(buffer) => {
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(joint_matrices));
+            }
+            JointStorage::Texture(texture) => {
+                // Only whole rows are written, so the last one is padded with identity matrices
+                let rows =
+                    (joint_matrices.len() + Self::MATRICES_PER_ROW - 1) / Self::MATRICES_PER_ROW;
+                let mut matrices = joint_matrices.to_vec();
+                matrices.resize(rows * Self::MATRICES_PER_ROW, glm::Mat4::identity());
+                let bytes_per_row = Self::MATRICES_PER_ROW * size_of::<glm::Mat4>();
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
                     },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
+                    bytemuck::cast_slice(&matrices),
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(bytes_per_row as u32),
+                        rows_per_image: std::num::NonZeroU32::new(rows as u32),
+                    },
+                    wgpu::Extent3d {
+                        width: (Self::MATRICES_PER_ROW * 4) as u32,
+                        height: rows as u32,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// One bind group per material, holding its factors and its five textures
+pub(crate) struct MaterialBinding {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_groups: Vec<wgpu::BindGroup>,
+    buffers: Vec<wgpu::Buffer>,
+}
+
+impl MaterialBinding {
+    /// Base color, metallic-roughness, normal, occlusion and emissive,
+    /// each bound as a texture followed by its sampler
+    pub const NUMBER_OF_TEXTURES: u32 = 5;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let mut entries = Vec::new();
+        for texture in 0..Self::NUMBER_OF_TEXTURES {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: texture * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
                 },
-            ],
-            label: Some("texture_bind_group_layout"),
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: texture * 2 + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: Self::NUMBER_OF_TEXTURES * 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(size_of::<MaterialUniform>() as _),
+            },
+            count: None,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("material_bind_group_layout"),
         });
 
         Self {
             bind_group_layout,
             bind_groups: Vec::new(),
+            buffers: Vec::new(),
         }
     }
 
-    pub fn clear_textures(&mut self) {
-        self.bind_groups.clear()
+    pub fn clear_materials(&mut self) {
+        self.bind_groups.clear();
+        self.buffers.clear();
     }
 
-    pub fn upload_textures(&mut self, device: &wgpu::Device, textures: &[Texture], offset: usize) {
-        textures.iter().skip(offset).for_each(|texture| {
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                    },
-                ],
-                label: Some("texture_bind_group"),
+    pub fn add_material(
+        &mut self,
+        device: &wgpu::Device,
+        material: &MaterialUniform,
+        textures: [&Texture; Self::NUMBER_OF_TEXTURES as usize],
+    ) {
+        let (bind_group, buffer) = self.create_bind_group(device, material, textures);
+        self.bind_groups.push(bind_group);
+        self.buffers.push(buffer);
+    }
+
+    /// Creates a material's bind group without adding it to the bound materials,
+    /// for materials owned elsewhere such as those created through handles
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        material: &MaterialUniform,
+        textures: [&Texture; Self::NUMBER_OF_TEXTURES as usize],
+    ) -> (wgpu::BindGroup, wgpu::Buffer) {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&[*material]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut entries = Vec::new();
+        for (index, texture) in textures.iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: index as u32 * 2,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
             });
-            self.bind_groups.push(bind_group)
-        })
+            entries.push(wgpu::BindGroupEntry {
+                binding: index as u32 * 2 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        entries.push(wgpu::BindGroupEntry {
+            binding: Self::NUMBER_OF_TEXTURES * 2,
+            resource: buffer.as_entire_binding(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &entries,
+            label: Some("material_bind_group"),
+        });
+        (bind_group, buffer)
+    }
+}
+
+/// Texture sets are -1 for textures the material doesn't have
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+pub(crate) struct MaterialUniform {
+    pub base_color_factor: glm::Vec4,
+    pub emissive_factor: glm::Vec4,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+
+    /// Fragments with less alpha are discarded, zero for materials that aren't masked
+    pub alpha_cutoff: f32,
+    pub is_unlit: i32,
+    pub color_texture_set: i32,
+    pub metallic_roughness_texture_set: i32,
+    pub normal_texture_set: i32,
+    pub occlusion_texture_set: i32,
+    pub emissive_texture_set: i32,
+    pub transmission_factor: f32,
+
+    /// The top two rows of the matrix transforming texture coordinates
+    pub texture_transform: [glm::Vec4; 2],
+}
+
+unsafe impl bytemuck::Zeroable for MaterialUniform {}
+
+unsafe impl bytemuck::Pod for MaterialUniform {}
+
+impl From<&phantom_world::Material> for MaterialUniform {
+    fn from(material: &phantom_world::Material) -> Self {
+        let emissive = material.emissive_factor * material.emissive_strength;
+        let [transform_x, transform_y] = material.texture_transform.rows();
+        Self {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: glm::vec4(emissive.x, emissive.y, emissive.z, 1.0),
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            normal_scale: material.normal_texture_scale,
+            occlusion_strength: material.occlusion_strength,
+            alpha_cutoff: match material.alpha_mode {
+                AlphaMode::Mask => material.alpha_cutoff,
+                _ => 0.0,
+            },
+            is_unlit: material.is_unlit as i32,
+            color_texture_set: texture_set(
+                material.color_texture_index,
+                material.color_texture_set,
+            ),
+            metallic_roughness_texture_set: texture_set(
+                material.metallic_roughness_texture_index,
+                material.metallic_roughness_texture_set,
+            ),
+            normal_texture_set: texture_set(
+                material.normal_texture_index,
+                material.normal_texture_set,
+            ),
+            occlusion_texture_set: texture_set(
+                material.occlusion_texture_index,
+                material.occlusion_texture_set,
+            ),
+            emissive_texture_set: texture_set(
+                material.emissive_texture_index,
+                material.emissive_texture_set,
+            ),
+            transmission_factor: material.transmission_factor,
+            texture_transform: [
+                glm::vec4(transform_x.x, transform_x.y, transform_x.z, 0.0),
+                glm::vec4(transform_y.x, transform_y.y, transform_y.z, 0.0),
+            ],
+        }
+    }
+}
+
+fn texture_set(index: i32, set: i32) -> i32 {
+    if index < 0 {
+        -1
+    } else {
+        set.max(0)
     }
 }
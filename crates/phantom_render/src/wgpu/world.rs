@@ -1,78 +1,980 @@
+use super::{
+    cloth::ClothRender,
+    handles::HandleResources,
+    shaders::{create_shader, validate, WORLD_SHADER},
+    shadow::ShadowMap,
+    texture::Texture,
+    uniform::{
+        DynamicUniform, DynamicUniformBinding, Geometry, JointBinding, LightUniform, LightsUniform,
+        MaterialBinding, MaterialUniform, Uniform, UniformBinding, MAX_LIGHTS,
+    },
+};
+use crate::{
+    FrameAssets, MaterialHandle, MaterialTextures, MeshHandle, MeshInstance, RenderFrame,
+    RenderSettings, RenderStatistics, TextureHandle,
+};
 use phantom_dependencies::{
-    anyhow::Result,
-    wgpu::{self, Device, RenderPipeline, SurfaceConfiguration},
+    anyhow::{bail, Result},
+    bytemuck, nalgebra_glm as glm,
+    wgpu::{
+        self, BufferAddress, CommandEncoder, Device, PipelineLayout, Queue, RenderPipeline,
+        ShaderModule, SurfaceConfiguration, TextureFormat,
+    },
 };
+use phantom_world::{
+    AlphaMode, Entity, Frustum, Light, LightKind, Material, Sampler, Transform, Vertex,
+};
+use std::{collections::HashSet, mem::size_of, ops::Range};
+
+struct DrawCall {
+    indices: Range<u32>,
+
+    /// The default material is bound at index zero
+    material_index: usize,
+    uniform_index: usize,
+    blended: bool,
+    skinned: bool,
+
+    /// Draws outside the camera's frustum are skipped in the main pass but still cast shadows
+    visible: bool,
+
+    /// Set for meshes drawn through handles, which have their own buffers and materials
+    mesh: Option<MeshHandle>,
+    material: Option<MaterialHandle>,
+
+    /// Set for primitives drawn from the morphed vertex buffer, offsetting their indices into it
+    base_vertex: Option<i32>,
+
+    /// Set for cloth, which is drawn in world space from its own buffers
+    cloth: Option<Entity>,
+}
+
+impl DrawCall {
+    /// Blended draws come after everything opaque, and draws sharing a pipeline are grouped
+    fn pipeline_index(&self) -> usize {
+        self.blended as usize * 2 + self.skinned as usize
+    }
+}
 
 pub struct WorldRender {
-    render_pipeline: RenderPipeline,
+    /// Opaque, opaque skinned, blended and blended skinned pipelines.
+    /// Blended materials are drawn after everything opaque, without writing depth.
+    pipelines: [RenderPipeline; 4],
+
+    /// Kept to recreate the pipelines when the sample count changes
+    shader: ShaderModule,
+    render_pipeline_layout: PipelineLayout,
+    shadow_pipeline_layout: PipelineLayout,
+    color_format: TextureFormat,
+
+    /// Depth only pipelines rendering opaque and opaque skinned meshes into the shadow map
+    shadow_pipelines: [RenderPipeline; 2],
+    shadow_map: ShadowMap,
+
+    /// Whether the shadow map is rendered this frame, which needs a directional light
+    shadows_visible: bool,
+    geometry: Geometry,
+    uniform_binding: UniformBinding,
+    dynamic_uniform_binding: DynamicUniformBinding,
+    material_binding: MaterialBinding,
+    joint_binding: JointBinding,
+
+    /// Bound in place of the textures a material doesn't have
+    white_texture: Texture,
+    flat_normal_texture: Texture,
+    textures: Vec<Texture>,
+    handles: HandleResources,
+
+    /// Holds the extract's morphed vertices, recreated larger when a frame needs more room
+    morph_vertex_buffer: Option<wgpu::Buffer>,
+    morph_vertex_capacity: usize,
+    cloth: ClothRender,
+
+    /// The world's materials as they were last uploaded, to tell which draws are blended
+    materials: Vec<Material>,
+    draw_calls: Vec<DrawCall>,
+    pub settings: RenderSettings,
+    pub statistics: RenderStatistics,
 }
 
 impl WorldRender {
-    pub fn new(device: &Device, config: &SurfaceConfiguration) -> Result<Self> {
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(
-                include_str!("../../../../assets/shaders/shader.wgsl").into(),
-            ),
-        });
+    /// Points towards the light used for shading when the world has no directional light
+    const DEFAULT_LIGHT_DIRECTION: glm::Vec3 = glm::Vec3::new(0.4, 1.0, 0.3);
+
+    /// Cloth that prefers the GPU is only simulated here when the adapter supports compute shaders,
+    /// and joint matrices are read from a texture when it can't read storage buffers in vertex shaders
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        config: &SurfaceConfiguration,
+        compute_shaders: bool,
+        vertex_storage: bool,
+    ) -> Result<Self> {
+        let shader = WORLD_SHADER.load(device, "Shader");
+
+        let geometry = Geometry::new(device);
+        let uniform_binding = UniformBinding::new(device);
+        let dynamic_uniform_binding = DynamicUniformBinding::new(device);
+        let material_binding = MaterialBinding::new(device);
+        let joint_binding = JointBinding::new(device, vertex_storage);
+        let settings = RenderSettings::default();
+        let shadow_map = ShadowMap::new(device, &uniform_binding, settings.shadow_map_resolution);
+
+        let white_texture = single_pixel_texture(device, queue, [255, 255, 255, 255], "White")?;
+        let flat_normal_texture =
+            single_pixel_texture(device, queue, [128, 128, 255, 255], "Flat Normal")?;
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
+                bind_group_layouts: &[
+                    &shadow_map.bind_group_layout,
+                    &dynamic_uniform_binding.bind_group_layout,
+                    &material_binding.bind_group_layout,
+                    &joint_binding.bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
+        let shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[
+                    &uniform_binding.bind_group_layout,
+                    &dynamic_uniform_binding.bind_group_layout,
+                    &material_binding.bind_group_layout,
+                    &joint_binding.bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let joint_texture = joint_binding.uses_texture();
+        let shadow_pipelines = [false, true].map(|skinned| {
+            create_shadow_pipeline(
+                device,
+                &shadow_pipeline_layout,
+                &shader,
+                &geometry,
+                skinned,
+                joint_texture,
+            )
         });
 
-        Ok(Self { render_pipeline })
+        let pipelines = create_pipelines(
+            device,
+            &render_pipeline_layout,
+            &shader,
+            &geometry,
+            config.format,
+            settings.sample_count,
+            joint_texture,
+        );
+
+        let mut world_render = Self {
+            pipelines,
+            shader,
+            render_pipeline_layout,
+            shadow_pipeline_layout,
+            color_format: config.format,
+            shadow_pipelines,
+            shadow_map,
+            shadows_visible: false,
+            geometry,
+            uniform_binding,
+            dynamic_uniform_binding,
+            material_binding,
+            joint_binding,
+            white_texture,
+            flat_normal_texture,
+            textures: Vec::new(),
+            handles: HandleResources::default(),
+            morph_vertex_buffer: None,
+            morph_vertex_capacity: 0,
+            cloth: ClothRender::new(device, compute_shaders),
+            materials: Vec::new(),
+            draw_calls: Vec::new(),
+            settings,
+            statistics: RenderStatistics::default(),
+        };
+        world_render.upload_materials(device, &[]);
+        Ok(world_render)
+    }
+
+    /// Rebuilds the pipelines to render into targets with a different sample count
+    pub fn set_sample_count(&mut self, device: &Device, sample_count: u32) {
+        self.settings.sample_count = sample_count;
+        self.pipelines = create_pipelines(
+            device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            &self.geometry,
+            self.color_format,
+            sample_count,
+            self.joint_binding.uses_texture(),
+        );
+    }
+
+    /// Rebuilds the pipelines to render into targets with a different format
+    pub fn set_color_format(&mut self, device: &Device, color_format: TextureFormat) {
+        self.color_format = color_format;
+        self.pipelines = create_pipelines(
+            device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            &self.geometry,
+            color_format,
+            self.settings.sample_count,
+            self.joint_binding.uses_texture(),
+        );
+    }
+
+    pub fn color_format(&self) -> TextureFormat {
+        self.color_format
+    }
+
+    pub fn simulates_cloth(&self) -> bool {
+        self.cloth.simulates_cloth()
+    }
+
+    pub fn reload_cloth_shader(&mut self, device: &Device) -> Result<()> {
+        self.cloth.reload_shader(device)
+    }
+
+    /// Recompiles the shader and rebuilds every pipeline that uses it.
+    /// The current pipelines are kept if the new ones fail to validate.
+    pub fn reload_shader(&mut self, device: &Device) -> Result<()> {
+        let joint_texture = self.joint_binding.uses_texture();
+        let (shader, shadow_pipelines, pipelines) = validate(device, || {
+            let shader = create_shader(device, "Shader", WORLD_SHADER.source());
+            let shadow_pipelines = [false, true].map(|skinned| {
+                create_shadow_pipeline(
+                    device,
+                    &self.shadow_pipeline_layout,
+                    &shader,
+                    &self.geometry,
+                    skinned,
+                    joint_texture,
+                )
+            });
+            let pipelines = create_pipelines(
+                device,
+                &self.render_pipeline_layout,
+                &shader,
+                &self.geometry,
+                self.color_format,
+                self.settings.sample_count,
+                joint_texture,
+            );
+            (shader, shadow_pipelines, pipelines)
+        })?;
+        self.shader = shader;
+        self.shadow_pipelines = shadow_pipelines;
+        self.pipelines = pipelines;
+        Ok(())
+    }
+
+    /// Uploads the frame's assets if it carries any,
+    /// then gathers the draw calls for every enabled mesh in the frame
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame: &RenderFrame,
+        aspect_ratio: f32,
+    ) -> Result<()> {
+        self.draw_calls.clear();
+        self.statistics = RenderStatistics::default();
+
+        match frame.assets.as_ref() {
+            Some(FrameAssets::All {
+                vertices,
+                indices,
+                textures,
+                materials,
+            }) => self.upload(device, queue, vertices, indices, textures, materials)?,
+            Some(FrameAssets::Materials(materials)) => self.upload_materials(device, materials),
+            None => {}
+        }
+
+        // Cloth keeps simulating even while there is no camera to draw it with
+        self.cloth.update(device, queue, &frame.cloth);
+
+        // Nothing can be drawn without a camera
+        let (projection, view) = match frame.camera.as_ref() {
+            Some((camera, transform)) => (
+                camera.projection_matrix(aspect_ratio),
+                transform.as_view_matrix(),
+            ),
+            None => return Ok(()),
+        };
+        let camera_position = glm::inverse(&view).column(3).clone_owned();
+        let frustum = Frustum::from_matrix(&(projection * view));
+
+        self.shadow_map.resize(
+            device,
+            &self.uniform_binding,
+            self.settings.shadow_map_resolution,
+        );
+        let lights = gather_lights(&frame.lights, &camera_position.xyz());
+        self.uniform_binding.upload_lights(queue, &lights);
+        let shadow_light = usize::try_from(lights.shadow_light)
+            .ok()
+            .map(|index| lights.lights[index].direction.xyz());
+        self.shadows_visible = self.settings.shadows && shadow_light.is_some();
+        let light_direction =
+            shadow_light.unwrap_or_else(|| -Self::DEFAULT_LIGHT_DIRECTION.normalize());
+        let light_view_projection = ShadowMap::light_view_projection(
+            &light_direction,
+            &camera_position.xyz(),
+            self.settings.shadow_distance,
+            self.shadow_map.resolution,
+        );
+        let toward_light = -light_direction.normalize();
+
+        self.uniform_binding.upload_uniform_data(
+            queue,
+            0,
+            &[Uniform {
+                view,
+                projection,
+                camera_position,
+                light_view_projection,
+                light_direction: glm::vec4(toward_light.x, toward_light.y, toward_light.z, 0.0),
+                shadow_parameters: glm::vec4(
+                    self.settings.shadow_bias,
+                    self.shadows_visible as i32 as f32,
+                    1.0 / self.shadow_map.resolution as f32,
+                    0.0,
+                ),
+            }],
+        );
+
+        let extract = &frame.extract;
+        self.upload_morphed_vertices(device, queue, &extract.morphed_vertices);
+        let mut mesh_uniforms = (0..extract.number_of_meshes())
+            .map(|mesh| DynamicUniform {
+                model: extract.global_matrices[mesh],
+                joint_offset: extract.joints[mesh]
+                    .as_ref()
+                    .map_or(0, |joints| joints.start as u32),
+            })
+            .collect::<Vec<_>>();
+        for primitive in extract.primitives.iter() {
+            if !extract.visible[primitive.mesh] {
+                continue;
+            }
+            let material = primitive
+                .material_index
+                .and_then(|index| self.materials.get(index));
+            let skinned = extract.is_skinned(primitive.mesh);
+
+            // Skinned meshes are never culled, since their joints can move them outside their bounds
+            let visible = !self.settings.frustum_culling
+                || skinned
+                || frustum.intersects_box(
+                    &primitive.bounding_box,
+                    &extract.global_matrices[primitive.mesh],
+                );
+            if visible {
+                self.statistics.visible_draw_calls += 1;
+            } else {
+                self.statistics.culled_draw_calls += 1;
+            }
+            self.draw_calls.push(DrawCall {
+                indices: primitive.indices.clone(),
+                material_index: primitive.material_index.map_or(0, |index| index + 1),
+                uniform_index: primitive.mesh,
+                blended: material.map_or(false, is_blended),
+                skinned,
+                visible,
+                mesh: None,
+                material: None,
+                base_vertex: primitive.morphed_base_vertex,
+                cloth: None,
+            });
+        }
+        for instance in self.handles.instances.iter() {
+            let mesh = match self.handles.meshes.get(instance.mesh.0) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let material = instance
+                .material
+                .and_then(|material| self.handles.materials.get(material.0));
+            let visible = !self.settings.frustum_culling
+                || frustum.intersects_box(&mesh.bounding_box, &instance.model);
+            if visible {
+                self.statistics.visible_draw_calls += 1;
+            } else {
+                self.statistics.culled_draw_calls += 1;
+            }
+            let uniform_index = mesh_uniforms.len();
+            mesh_uniforms.push(DynamicUniform {
+                model: instance.model,
+                joint_offset: 0,
+            });
+            self.draw_calls.push(DrawCall {
+                indices: 0..mesh.number_of_indices,
+                material_index: 0,
+                uniform_index,
+                blended: material.map_or(false, |material| is_blended(&material.material)),
+                skinned: false,
+                visible,
+                mesh: Some(instance.mesh),
+                material: instance.material,
+                base_vertex: None,
+                cloth: None,
+            });
+        }
+        // Cloth is never culled, since its bounds move with a simulation that may be on the GPU
+        for (entity, cloth) in self.cloth.meshes.iter() {
+            let material = cloth
+                .material_index
+                .and_then(|index| self.materials.get(index));
+            self.statistics.visible_draw_calls += 1;
+            let uniform_index = mesh_uniforms.len();
+            mesh_uniforms.push(DynamicUniform {
+                model: glm::Mat4::identity(),
+                joint_offset: 0,
+            });
+            self.draw_calls.push(DrawCall {
+                indices: 0..cloth.number_of_indices,
+                material_index: cloth.material_index.map_or(0, |index| index + 1),
+                uniform_index,
+                blended: material.map_or(false, is_blended),
+                skinned: false,
+                visible: true,
+                mesh: None,
+                material: None,
+                base_vertex: None,
+                cloth: Some(*entity),
+            });
+        }
+        self.draw_calls.sort_by_key(DrawCall::pipeline_index);
+
+        if mesh_uniforms.len() > DynamicUniformBinding::MAX_NUMBER_OF_MESHES {
+            self.draw_calls.clear();
+            bail!(
+                "The world has {} meshes but at most {} can be rendered!",
+                mesh_uniforms.len(),
+                DynamicUniformBinding::MAX_NUMBER_OF_MESHES
+            );
+        }
+        self.dynamic_uniform_binding
+            .upload_uniform_data(queue, 0, &mesh_uniforms);
+
+        let joint_matrices = &frame.extract.joint_matrices;
+        if joint_matrices.len() > JointBinding::MAX_NUMBER_OF_JOINTS {
+            self.draw_calls.clear();
+            bail!(
+                "The world has {} joints but at most {} can be rendered!",
+                joint_matrices.len(),
+                JointBinding::MAX_NUMBER_OF_JOINTS
+            );
+        }
+        if !joint_matrices.is_empty() {
+            self.joint_binding
+                .upload_joint_matrices(queue, joint_matrices);
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn upload(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        vertices: &[Vertex],
+        indices: &[u32],
+        textures: &[phantom_world::Texture],
+        materials: &[Material],
+    ) -> Result<()> {
+        if vertices.len() > Geometry::MAX_VERTICES as usize {
+            bail!(
+                "The world has {} vertices but at most {} can be rendered!",
+                vertices.len(),
+                Geometry::MAX_VERTICES
+            );
+        }
+        if indices.len() > Geometry::MAX_INDICES as usize {
+            bail!(
+                "The world has {} indices but at most {} can be rendered!",
+                indices.len(),
+                Geometry::MAX_INDICES
+            );
+        }
+        if !vertices.is_empty() {
+            self.geometry.upload_vertices(queue, 0, vertices);
+        }
+        if !indices.is_empty() {
+            self.geometry.upload_indices(queue, 0, indices);
+        }
+
+        // Textures holding colors are stored in sRGB, everything else is linear data
+        let srgb_textures = materials
+            .iter()
+            .flat_map(|material| {
+                [
+                    material.color_texture_index,
+                    material.emissive_texture_index,
+                ]
+            })
+            .collect::<HashSet<_>>();
+        self.textures = textures
+            .iter()
+            .enumerate()
+            .map(|(index, texture)| {
+                Texture::from_world_texture(
+                    device,
+                    queue,
+                    texture,
+                    srgb_textures.contains(&(index as i32)),
+                    &format!("World Texture {}", index),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.upload_materials(device, materials);
+        Ok(())
+    }
+
+    fn upload_materials(&mut self, device: &Device, materials: &[Material]) {
+        self.materials = materials.to_vec();
+        self.material_binding.clear_materials();
+        let default_material = Material::default();
+        for material in std::iter::once(&default_material).chain(materials.iter()) {
+            let texture = |index: i32| {
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| self.textures.get(index))
+            };
+            let textures = [
+                texture(material.color_texture_index).unwrap_or(&self.white_texture),
+                texture(material.metallic_roughness_texture_index).unwrap_or(&self.white_texture),
+                texture(material.normal_texture_index).unwrap_or(&self.flat_normal_texture),
+                texture(material.occlusion_texture_index).unwrap_or(&self.white_texture),
+                texture(material.emissive_texture_index).unwrap_or(&self.white_texture),
+            ];
+            self.material_binding
+                .add_material(device, &MaterialUniform::from(material), textures);
+        }
     }
 
     pub fn render<'a, 'b>(&'a mut self, render_pass: &'b mut wgpu::RenderPass<'a>) -> Result<()> {
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.draw(0..3, 0..1);
+        if self.draw_calls.is_empty() {
+            return Ok(());
+        }
+        render_pass.set_bind_group(0, &self.shadow_map.bind_group, &[]);
+        render_pass.set_bind_group(3, &self.joint_binding.bind_group, &[]);
+        let mut pipeline_index = None;
+        let mut bound_mesh = None;
+        for draw_call in self.draw_calls.iter().filter(|draw_call| draw_call.visible) {
+            if !self.bind_geometry(render_pass, draw_call, &mut bound_mesh) {
+                continue;
+            }
+            if pipeline_index != Some(draw_call.pipeline_index()) {
+                pipeline_index = Some(draw_call.pipeline_index());
+                render_pass.set_pipeline(&self.pipelines[draw_call.pipeline_index()]);
+            }
+            let offset =
+                draw_call.uniform_index as BufferAddress * self.dynamic_uniform_binding.alignment;
+            render_pass.set_bind_group(
+                1,
+                &self.dynamic_uniform_binding.bind_group,
+                &[offset as wgpu::DynamicOffset],
+            );
+            let material_bind_group = match draw_call.material {
+                Some(material) => self
+                    .handles
+                    .materials
+                    .get(material.0)
+                    .map(|material| &material.bind_group),
+                None => self
+                    .material_binding
+                    .bind_groups
+                    .get(draw_call.material_index),
+            }
+            .unwrap_or(&self.material_binding.bind_groups[0]);
+            render_pass.set_bind_group(2, material_bind_group, &[]);
+            render_pass.draw_indexed(
+                draw_call.indices.clone(),
+                draw_call.base_vertex.unwrap_or(0),
+                0..1,
+            );
+        }
         Ok(())
     }
+
+    /// Binds the world's geometry, its morphed vertices, a cloth, or a mesh uploaded through a handle,
+    /// if it isn't bound already. Returns false if the mesh has been freed since the draw calls were gathered.
+    fn bind_geometry<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        draw_call: &DrawCall,
+        bound_mesh: &mut Option<(Option<MeshHandle>, Option<Entity>, bool)>,
+    ) -> bool {
+        let morphed = draw_call.base_vertex.is_some();
+        let (vertex_buffer, index_buffer) = match (draw_call.mesh, draw_call.cloth) {
+            (Some(mesh), _) => match self.handles.meshes.get(mesh.0) {
+                Some(mesh) => (&mesh.vertex_buffer, &mesh.index_buffer),
+                None => return false,
+            },
+            (None, Some(cloth)) => match self.cloth.meshes.get(&cloth) {
+                Some(cloth) => (&cloth.vertex_buffer, &cloth.index_buffer),
+                None => return false,
+            },
+            (None, None) if morphed => match self.morph_vertex_buffer.as_ref() {
+                Some(buffer) => (buffer, &self.geometry.index_buffer),
+                None => return false,
+            },
+            (None, None) => (&self.geometry.vertex_buffer, &self.geometry.index_buffer),
+        };
+        let binding = (draw_call.mesh, draw_call.cloth, morphed);
+        if *bound_mesh != Some(binding) {
+            *bound_mesh = Some(binding);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        }
+        true
+    }
+
+    fn upload_morphed_vertices(&mut self, device: &Device, queue: &Queue, vertices: &[Vertex]) {
+        if vertices.is_empty() {
+            return;
+        }
+        if self.morph_vertex_buffer.is_none() || vertices.len() > self.morph_vertex_capacity {
+            self.morph_vertex_capacity = vertices.len().next_power_of_two();
+            self.morph_vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Morphed Vertex Buffer"),
+                size: (self.morph_vertex_capacity * size_of::<Vertex>()) as BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        if let Some(buffer) = self.morph_vertex_buffer.as_ref() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(vertices));
+        }
+    }
+
+    pub fn upload_mesh(
+        &mut self,
+        device: &Device,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Result<MeshHandle> {
+        self.handles.upload_mesh(device, vertices, indices)
+    }
+
+    pub fn free_mesh(&mut self, mesh: MeshHandle) {
+        self.handles.meshes.remove(mesh.0);
+    }
+
+    pub fn upload_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        texture: &phantom_world::Texture,
+        srgb: bool,
+    ) -> Result<TextureHandle> {
+        self.handles.upload_texture(device, queue, texture, srgb)
+    }
+
+    pub fn free_texture(&mut self, texture: TextureHandle) {
+        self.handles.free_texture(texture);
+    }
+
+    pub fn create_material(
+        &mut self,
+        device: &Device,
+        material: &Material,
+        textures: &MaterialTextures,
+    ) -> Result<MaterialHandle> {
+        self.handles.create_material(
+            device,
+            &self.material_binding,
+            [&self.white_texture, &self.flat_normal_texture],
+            material,
+            textures,
+        )
+    }
+
+    pub fn update_material(
+        &mut self,
+        device: &Device,
+        handle: MaterialHandle,
+        material: &Material,
+    ) -> Result<()> {
+        self.handles.update_material(
+            device,
+            &self.material_binding,
+            [&self.white_texture, &self.flat_normal_texture],
+            handle,
+            material,
+        )
+    }
+
+    pub fn free_material(&mut self, material: MaterialHandle) {
+        self.handles.materials.remove(material.0);
+    }
+
+    pub fn set_mesh_instances(&mut self, instances: Vec<MeshInstance>) {
+        self.handles.instances = instances;
+    }
+
+    /// Renders the depth of every opaque mesh from the directional light into the shadow map
+    pub fn render_shadows(&self, encoder: &mut CommandEncoder) {
+        if !self.shadows_visible || self.draw_calls.is_empty() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.shadow_map.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_bind_group(0, &self.uniform_binding.bind_group, &[]);
+        render_pass.set_bind_group(2, &self.material_binding.bind_groups[0], &[]);
+        render_pass.set_bind_group(3, &self.joint_binding.bind_group, &[]);
+        let mut skinned = None;
+        let mut bound_mesh = None;
+        for draw_call in self
+            .draw_calls
+            .iter()
+            .filter(|draw_call| !draw_call.blended)
+        {
+            if !self.bind_geometry(&mut render_pass, draw_call, &mut bound_mesh) {
+                continue;
+            }
+            if skinned != Some(draw_call.skinned) {
+                skinned = Some(draw_call.skinned);
+                render_pass.set_pipeline(&self.shadow_pipelines[draw_call.skinned as usize]);
+            }
+            let offset =
+                draw_call.uniform_index as BufferAddress * self.dynamic_uniform_binding.alignment;
+            render_pass.set_bind_group(
+                1,
+                &self.dynamic_uniform_binding.bind_group,
+                &[offset as wgpu::DynamicOffset],
+            );
+            render_pass.draw_indexed(
+                draw_call.indices.clone(),
+                draw_call.base_vertex.unwrap_or(0),
+                0..1,
+            );
+        }
+    }
+}
+
+/// Transmission is drawn as transparency, so transmissive materials are blended too
+fn is_blended(material: &Material) -> bool {
+    material.alpha_mode == AlphaMode::Blend || material.transmission_factor > 0.0
+}
+
+/// Directional lights come first, then the lights nearest to the camera up to the most that
+/// can be uploaded. The first directional light casts shadows.
+fn gather_lights(lights: &[(Transform, Light)], camera_position: &glm::Vec3) -> LightsUniform {
+    let mut lights = lights.to_vec();
+    let distance = |position: &glm::Vec3, kind: &LightKind| match kind {
+        LightKind::Directional => -1.0,
+        _ => glm::distance2(position, camera_position),
+    };
+    lights.sort_by(|(a, a_light), (b, b_light)| {
+        distance(&a.translation, &a_light.kind)
+            .partial_cmp(&distance(&b.translation, &b_light.kind))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    lights.truncate(MAX_LIGHTS);
+
+    let mut uniform: LightsUniform = bytemuck::Zeroable::zeroed();
+    uniform.count = lights.len() as u32;
+    uniform.shadow_light = -1;
+    for (index, (transform, light)) in lights.iter().enumerate() {
+        uniform.lights[index] =
+            LightUniform::new(light, transform.translation, transform.forward());
+        if matches!(light.kind, LightKind::Directional) && uniform.shadow_light < 0 {
+            uniform.shadow_light = index as i32;
+        }
+    }
+    uniform
+}
+
+fn single_pixel_texture(
+    device: &Device,
+    queue: &Queue,
+    pixel: [u8; 4],
+    label: &str,
+) -> Result<Texture> {
+    Texture::from_world_texture(
+        device,
+        queue,
+        &phantom_world::Texture {
+            pixels: pixel.to_vec(),
+            format: phantom_world::Format::R8G8B8A8,
+            width: 1,
+            height: 1,
+            sampler: Sampler::default(),
+        },
+        false,
+        label,
+    )
+}
+
+fn create_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    geometry: &Geometry,
+    format: TextureFormat,
+    sample_count: u32,
+    joint_texture: bool,
+) -> [RenderPipeline; 4] {
+    let pipeline = |blended: bool, skinned: bool| {
+        create_pipeline(
+            device,
+            layout,
+            shader,
+            geometry,
+            format,
+            sample_count,
+            blended,
+            skinned,
+            joint_texture,
+        )
+    };
+    [
+        pipeline(false, false),
+        pipeline(false, true),
+        pipeline(true, false),
+        pipeline(true, true),
+    ]
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    geometry: &Geometry,
+    format: TextureFormat,
+    sample_count: u32,
+    blended: bool,
+    skinned: bool,
+    joint_texture: bool,
+) -> RenderPipeline {
+    let blend = if blended {
+        wgpu::BlendState::ALPHA_BLENDING
+    } else {
+        wgpu::BlendState::REPLACE
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(match (blended, skinned) {
+            (false, false) => "Render Pipeline",
+            (false, true) => "Skinned Render Pipeline",
+            (true, false) => "Blend Render Pipeline",
+            (true, true) => "Skinned Blend Render Pipeline",
+        }),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: match (skinned, joint_texture) {
+                (false, _) => "vs_main",
+                (true, false) => "vs_skinned",
+                (true, true) => "vs_skinned_texture",
+            },
+            buffers: &[geometry.vertex_buffer_layout.clone()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+            // or Features::POLYGON_MODE_POINT
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: !blended,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        // If the pipeline will be used with a multiview render pass, this
+        // indicates how many array layers the attachments will have.
+        multiview: None,
+    })
+}
+
+fn create_shadow_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    geometry: &Geometry,
+    skinned: bool,
+    joint_texture: bool,
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(if skinned {
+            "Skinned Shadow Pipeline"
+        } else {
+            "Shadow Pipeline"
+        }),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: match (skinned, joint_texture) {
+                (false, _) => "vs_shadow",
+                (true, false) => "vs_shadow_skinned",
+                (true, true) => "vs_shadow_skinned_texture",
+            },
+            buffers: &[geometry.vertex_buffer_layout.clone()],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // Both faces cast shadows, so planes and open meshes shadow from either side
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
 }
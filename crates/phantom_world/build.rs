@@ -0,0 +1,28 @@
+fn main() {
+    #[cfg(feature = "draco")]
+    build_draco_decoder();
+}
+
+/// Compiles the shim the glTF importer decodes Draco compressed primitives through.
+/// Draco's headers and static library are found in `DRACO_DIR` if it's set,
+/// and on the compiler's default paths otherwise.
+#[cfg(feature = "draco")]
+fn build_draco_decoder() {
+    println!("cargo:rerun-if-changed=draco/decoder.cpp");
+    println!("cargo:rerun-if-env-changed=DRACO_DIR");
+    let mut build = cc::Build::new();
+    build
+        .cpp(true)
+        .flag_if_supported("-std=c++11")
+        .file("draco/decoder.cpp");
+    if let Some(directory) = std::env::var_os("DRACO_DIR") {
+        let directory = std::path::PathBuf::from(directory);
+        build.include(directory.join("include"));
+        println!(
+            "cargo:rustc-link-search=native={}",
+            directory.join("lib").display()
+        );
+    }
+    build.compile("phantom_draco");
+    println!("cargo:rustc-link-lib=static=draco");
+}
@@ -0,0 +1,67 @@
+// Exposes Draco's mesh decoder to the glTF importer through a C interface
+#include <cstddef>
+#include <cstdint>
+#include <memory>
+
+#include "draco/compression/decode.h"
+#include "draco/mesh/mesh.h"
+
+struct PhantomDracoMesh {
+    std::unique_ptr<draco::Mesh> mesh;
+};
+
+extern "C" {
+
+// Returns null if the data isn't a Draco compressed triangle mesh
+PhantomDracoMesh *phantom_draco_decode(const uint8_t *data, size_t length) {
+    draco::DecoderBuffer buffer;
+    buffer.Init(reinterpret_cast<const char *>(data), length);
+    draco::Decoder decoder;
+    auto decoded = decoder.DecodeMeshFromBuffer(&buffer);
+    if (!decoded.ok()) {
+        return nullptr;
+    }
+    auto mesh = new PhantomDracoMesh();
+    mesh->mesh = std::move(decoded).value();
+    return mesh;
+}
+
+void phantom_draco_free(PhantomDracoMesh *mesh) {
+    delete mesh;
+}
+
+uint32_t phantom_draco_vertex_count(const PhantomDracoMesh *mesh) {
+    return mesh->mesh->num_points();
+}
+
+uint32_t phantom_draco_index_count(const PhantomDracoMesh *mesh) {
+    return mesh->mesh->num_faces() * 3;
+}
+
+void phantom_draco_copy_indices(const PhantomDracoMesh *mesh, uint32_t *indices) {
+    for (draco::FaceIndex face(0); face < mesh->mesh->num_faces(); ++face) {
+        const auto &corners = mesh->mesh->face(face);
+        for (const auto &corner : corners) {
+            *indices++ = corner.value();
+        }
+    }
+}
+
+// Copies an attribute as floats, `components` per vertex, returning false if the mesh doesn't have it.
+// Normalized integers are mapped to floats and missing components are filled with zeros.
+bool phantom_draco_copy_attribute(const PhantomDracoMesh *mesh, uint32_t unique_id,
+                                  uint32_t components, float *values) {
+    const draco::PointAttribute *attribute = mesh->mesh->GetAttributeByUniqueId(unique_id);
+    if (attribute == nullptr) {
+        return false;
+    }
+    for (draco::PointIndex point(0); point < mesh->mesh->num_points(); ++point) {
+        if (!attribute->ConvertValue<float>(attribute->mapped_index(point),
+                                            static_cast<int8_t>(components), values)) {
+            return false;
+        }
+        values += components;
+    }
+    return true;
+}
+}
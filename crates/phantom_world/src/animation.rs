@@ -1,10 +1,12 @@
-use crate::{Ecs, Entity, Mesh, Transform};
+use crate::{ChangeTracker, Ecs, Entity, MorphWeights, Transform};
 use phantom_dependencies::{
     anyhow::Result,
     legion::EntityStore,
     log, nalgebra_glm as glm,
+    rayon::prelude::*,
     serde::{Deserialize, Serialize},
 };
+use std::mem::size_of;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
@@ -13,10 +15,32 @@ pub struct Animation {
     pub time: f32,
     pub channels: Vec<Channel>,
     pub max_animation_time: f32,
+    pub root_motion: Option<RootMotion>,
 }
 
 impl Animation {
-    pub fn animate(&mut self, ecs: &mut Ecs, step: f32) -> Result<()> {
+    pub fn animate(&mut self, ecs: &mut Ecs, changes: &mut ChangeTracker, step: f32) -> Result<()> {
+        for sample in self.step(step) {
+            sample.apply(ecs, changes)?;
+        }
+        Ok(())
+    }
+
+    /// Advances the animation and samples it, extracting root motion if it is configured
+    pub fn step(&mut self, step: f32) -> Vec<ChannelSample> {
+        let previous_time = self.time;
+        self.advance(step);
+        let mut samples = self.sample(self.time);
+        let looped = if step >= 0.0 {
+            self.time < previous_time
+        } else {
+            self.time > previous_time
+        };
+        self.extract_root_motion(&mut samples, looped, step >= 0.0);
+        samples
+    }
+
+    pub fn advance(&mut self, step: f32) {
         self.time += step;
         // TODO: Allow for specifying a specific animation by name
         if self.time > self.max_animation_time {
@@ -25,77 +49,231 @@ impl Animation {
         if self.time < 0.0 {
             self.time = self.max_animation_time;
         }
+    }
+
+    fn extract_root_motion(&mut self, samples: &mut [ChannelSample], looped: bool, forward: bool) {
+        let root = match self.root_motion.as_ref() {
+            Some(root_motion) => root_motion.root,
+            None => return,
+        };
+
+        let mut first_samples = Vec::new();
+        let mut last_samples = Vec::new();
+        for channel in self
+            .channels
+            .iter()
+            .filter(|channel| channel.target == root)
+        {
+            if let (Some(first), Some(last)) = (channel.inputs.first(), channel.inputs.last()) {
+                first_samples.extend(channel.sample(*first));
+                last_samples.extend(channel.sample(*last));
+            }
+        }
 
-        for channel in self.channels.iter_mut() {
-            let mut input_iter = channel.inputs.iter().enumerate().peekable();
-            while let Some((previous_key, previous_time)) = input_iter.next() {
-                if let Some((next_key, next_time)) = input_iter.peek() {
-                    let next_key = *next_key;
-                    let next_time = **next_time;
-                    let previous_time = *previous_time;
-                    if self.time < previous_time || self.time > next_time {
-                        continue;
+        let root_motion = match self.root_motion.as_mut() {
+            Some(root_motion) => root_motion,
+            None => return,
+        };
+        if looped {
+            let restart_samples = if forward {
+                &first_samples
+            } else {
+                &last_samples
+            };
+            root_motion.previous_translation = find_translation(restart_samples);
+            root_motion.previous_rotation = find_rotation(restart_samples);
+        }
+        root_motion.delta_translation = glm::Vec3::zeros();
+        root_motion.delta_rotation = glm::Quat::identity();
+
+        for sample in samples.iter_mut().filter(|sample| sample.target == root) {
+            match &mut sample.value {
+                SampledValue::Translation(translation) if root_motion.extract_translation => {
+                    let previous = root_motion.previous_translation.unwrap_or(*translation);
+                    root_motion.delta_translation = *translation - previous;
+                    root_motion.previous_translation = Some(*translation);
+                    if let Some(rest) = find_translation(&first_samples) {
+                        *translation = rest;
                     }
-                    let interpolation = (self.time - previous_time) / (next_time - previous_time);
-                    // TODO: Interpolate with other methods
-                    // Only Linear interpolation is used for now
-                    match &channel.transformations {
-                        TransformationSet::Translations(translations) => {
-                            let start = translations[previous_key];
-                            let end = translations[next_key];
-                            let translation_vec = glm::mix(&start, &end, interpolation);
-                            ecs.entry_mut(channel.target)?
-                                .get_component_mut::<Transform>()?
-                                .translation = translation_vec;
-                        }
-                        TransformationSet::Rotations(rotations) => {
-                            let start = rotations[previous_key];
-                            let end = rotations[next_key];
-                            let start_quat = glm::make_quat(start.as_slice());
-                            let end_quat = glm::make_quat(end.as_slice());
-                            let rotation_quat =
-                                glm::quat_slerp(&start_quat, &end_quat, interpolation);
-
-                            ecs.entry_mut(channel.target)?
-                                .get_component_mut::<Transform>()?
-                                .rotation = rotation_quat;
-                        }
-                        TransformationSet::Scales(scales) => {
-                            let start = scales[previous_key];
-                            let end = scales[next_key];
-                            let scale_vec = glm::mix(&start, &end, interpolation);
-
-                            ecs.entry_mut(channel.target)?
-                                .get_component_mut::<Transform>()?
-                                .scale = scale_vec;
-                        }
-                        TransformationSet::MorphTargetWeights(animation_weights) => {
-                            match ecs.entry_mut(channel.target)?.get_component_mut::<Mesh>() {
-                                Ok(mesh) => {
-                                    let number_of_mesh_weights = mesh.weights.len();
-                                    if animation_weights.len() % number_of_mesh_weights != 0 {
-                                        log::warn!("Animation channel's weights are not a multiple of the mesh's weights: (channel) {} % (mesh) {} != 0", number_of_mesh_weights, animation_weights.len());
-                                        continue;
-                                    }
-                                    let weights = animation_weights
-                                        .as_slice()
-                                        .chunks(number_of_mesh_weights)
-                                        .collect::<Vec<_>>();
-                                    let start = weights[previous_key];
-                                    let end = weights[next_key];
-                                    for index in 0..number_of_mesh_weights {
-                                        (*mesh).weights[index] = glm::lerp_scalar(
-                                            start[index],
-                                            end[index],
-                                            interpolation,
-                                        );
-                                    }
-                                }
-                                Err(_) => {
-                                    log::warn!("Animation channel's target node animates morph target weights, but node has no mesh!");
-                                }
-                            }
+                }
+                SampledValue::Rotation(rotation) if root_motion.extract_rotation => {
+                    let previous = root_motion.previous_rotation.unwrap_or(*rotation);
+                    root_motion.delta_rotation = *rotation * glm::quat_inverse(&previous);
+                    root_motion.previous_rotation = Some(*rotation);
+                    if let Some(rest) = find_rotation(&first_samples) {
+                        *rotation = rest;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Removes redundant keyframes and optionally quantizes rotations,
+    /// returning the number of keyframes that were removed
+    pub fn optimize(&mut self, settings: &AnimationOptimization) -> usize {
+        let removed =
+            self.channels
+                .iter_mut()
+                .map(|channel| {
+                    let tolerance = match channel.transformations {
+                        TransformationSet::Translations(_) => settings.translation_tolerance,
+                        TransformationSet::Rotations(_)
+                        | TransformationSet::QuantizedRotations(_) => settings.rotation_tolerance,
+                        TransformationSet::Scales(_) => settings.scale_tolerance,
+                        TransformationSet::MorphTargetWeights(_) => settings.weight_tolerance,
+                    };
+                    channel.remove_redundant_keys(tolerance)
+                })
+                .sum();
+        if settings.quantize_rotations {
+            self.channels
+                .iter_mut()
+                .for_each(|channel| channel.quantize_rotations());
+        }
+        removed
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.channels
+            .iter()
+            .map(|channel| channel.size_in_bytes())
+            .sum()
+    }
+
+    /// Samples every channel at the given time across the thread pool
+    pub fn sample(&self, time: f32) -> Vec<ChannelSample> {
+        self.channels
+            .par_iter()
+            .filter_map(|channel| channel.sample(time))
+            .collect()
+    }
+}
+
+fn find_translation(samples: &[ChannelSample]) -> Option<glm::Vec3> {
+    samples.iter().find_map(|sample| match sample.value {
+        SampledValue::Translation(translation) => Some(translation),
+        _ => None,
+    })
+}
+
+fn find_rotation(samples: &[ChannelSample]) -> Option<glm::Quat> {
+    samples.iter().find_map(|sample| match sample.value {
+        SampledValue::Rotation(rotation) => Some(rotation),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AnimationOptimization {
+    pub translation_tolerance: f32,
+    /// The maximum angle in radians a removed rotation keyframe may deviate by
+    pub rotation_tolerance: f32,
+    pub scale_tolerance: f32,
+    pub weight_tolerance: f32,
+    pub quantize_rotations: bool,
+}
+
+impl Default for AnimationOptimization {
+    fn default() -> Self {
+        Self {
+            translation_tolerance: 0.001,
+            rotation_tolerance: 0.1_f32.to_radians(),
+            scale_tolerance: 0.001,
+            weight_tolerance: 0.001,
+            quantize_rotations: true,
+        }
+    }
+}
+
+/// Strips the motion of a root joint out of an animation so that
+/// it can drive the movement of an entity instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct RootMotion {
+    pub root: Entity,
+    pub target: Entity,
+    pub extract_translation: bool,
+    pub extract_rotation: bool,
+    /// When disabled, the extracted motion is only recorded in the deltas
+    /// so that it can be consumed elsewhere, such as by a character controller
+    pub apply_to_target: bool,
+    pub delta_translation: glm::Vec3,
+    pub delta_rotation: glm::Quat,
+    #[serde(skip)]
+    previous_translation: Option<glm::Vec3>,
+    #[serde(skip)]
+    previous_rotation: Option<glm::Quat>,
+}
+
+impl RootMotion {
+    pub fn new(root: Entity, target: Entity) -> Self {
+        Self {
+            root,
+            target,
+            extract_translation: true,
+            extract_rotation: true,
+            apply_to_target: true,
+            delta_translation: glm::Vec3::zeros(),
+            delta_rotation: glm::Quat::identity(),
+            previous_translation: None,
+            previous_rotation: None,
+        }
+    }
+
+    pub fn apply(&self, ecs: &mut Ecs, changes: &mut ChangeTracker) -> Result<()> {
+        let mut entry = ecs.entry_mut(self.target)?;
+        let transform = entry.get_component_mut::<Transform>()?;
+        transform.translation +=
+            glm::quat_rotate_vec3(&transform.rotation, &self.delta_translation);
+        transform.rotation *= self.delta_rotation;
+        changes.mark_changed::<Transform>(self.target);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelSample {
+    pub target: Entity,
+    pub value: SampledValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum SampledValue {
+    Translation(glm::Vec3),
+    Rotation(glm::Quat),
+    Scale(glm::Vec3),
+    MorphTargetWeights(Vec<f32>),
+}
+
+impl ChannelSample {
+    pub fn apply(&self, ecs: &mut Ecs, changes: &mut ChangeTracker) -> Result<()> {
+        let mut entry = ecs.entry_mut(self.target)?;
+        match &self.value {
+            SampledValue::Translation(translation) => {
+                entry.get_component_mut::<Transform>()?.translation = *translation;
+                changes.mark_changed::<Transform>(self.target);
+            }
+            SampledValue::Rotation(rotation) => {
+                entry.get_component_mut::<Transform>()?.rotation = *rotation;
+                changes.mark_changed::<Transform>(self.target);
+            }
+            SampledValue::Scale(scale) => {
+                entry.get_component_mut::<Transform>()?.scale = *scale;
+                changes.mark_changed::<Transform>(self.target);
+            }
+            SampledValue::MorphTargetWeights(weights) => {
+                match entry.get_component_mut::<MorphWeights>() {
+                    Ok(morph_weights) => {
+                        if morph_weights.0.len() != weights.len() {
+                            log::warn!("Animation channel's weights do not match the mesh's weights: (channel) {} != (mesh) {}", weights.len(), morph_weights.0.len());
+                            return Ok(());
                         }
+                        morph_weights.0.copy_from_slice(weights);
+                        changes.mark_changed::<MorphWeights>(self.target);
+                    }
+                    Err(_) => {
+                        log::warn!("Animation channel's target node animates morph target weights, but node has no mesh!");
                     }
                 }
             }
@@ -113,6 +291,236 @@ pub struct Channel {
     pub _interpolation: Interpolation,
 }
 
+impl Channel {
+    pub fn sample(&self, time: f32) -> Option<ChannelSample> {
+        let previous_key = self
+            .inputs
+            .windows(2)
+            .position(|window| time >= window[0] && time <= window[1])?;
+        let next_key = previous_key + 1;
+        let previous_time = self.inputs[previous_key];
+        let next_time = self.inputs[next_key];
+        let interpolation = if next_time > previous_time {
+            (time - previous_time) / (next_time - previous_time)
+        } else {
+            0.0
+        };
+
+        // TODO: Interpolate with other methods
+        // Only Linear interpolation is used for now
+        let value = match &self.transformations {
+            TransformationSet::Translations(translations) => {
+                let start = translations[previous_key];
+                let end = translations[next_key];
+                SampledValue::Translation(glm::mix(&start, &end, interpolation))
+            }
+            TransformationSet::Rotations(rotations) => {
+                let start = glm::make_quat(rotations[previous_key].as_slice());
+                let end = glm::make_quat(rotations[next_key].as_slice());
+                SampledValue::Rotation(glm::quat_slerp(&start, &end, interpolation))
+            }
+            TransformationSet::QuantizedRotations(rotations) => {
+                let start = dequantize_rotation(&rotations[previous_key]);
+                let end = dequantize_rotation(&rotations[next_key]);
+                SampledValue::Rotation(glm::quat_slerp(&start, &end, interpolation))
+            }
+            TransformationSet::Scales(scales) => {
+                let start = scales[previous_key];
+                let end = scales[next_key];
+                SampledValue::Scale(glm::mix(&start, &end, interpolation))
+            }
+            TransformationSet::MorphTargetWeights(animation_weights) => {
+                let number_of_weights = animation_weights.len() / self.inputs.len();
+                if number_of_weights == 0 {
+                    return None;
+                }
+                let weights = animation_weights
+                    .chunks(number_of_weights)
+                    .collect::<Vec<_>>();
+                let start = weights[previous_key];
+                let end = weights[next_key];
+                let weights = start
+                    .iter()
+                    .zip(end.iter())
+                    .map(|(start, end)| glm::lerp_scalar(*start, *end, interpolation))
+                    .collect();
+                SampledValue::MorphTargetWeights(weights)
+            }
+        };
+
+        Some(ChannelSample {
+            target: self.target,
+            value,
+        })
+    }
+
+    /// Removes keyframes that can be reconstructed by interpolating
+    /// their neighbors within the given tolerance
+    pub fn remove_redundant_keys(&mut self, tolerance: f32) -> usize {
+        let inputs = &self.inputs;
+        let kept_keys = match &self.transformations {
+            TransformationSet::Translations(values) | TransformationSet::Scales(values) => {
+                reduce_keys(
+                    inputs,
+                    tolerance,
+                    |previous, current, next, interpolation| {
+                        let interpolated =
+                            glm::mix(&values[previous], &values[next], interpolation);
+                        glm::distance(&interpolated, &values[current])
+                    },
+                )
+            }
+            TransformationSet::Rotations(rotations) => {
+                let rotation = |index: usize| glm::make_quat(rotations[index].as_slice());
+                reduce_keys(
+                    inputs,
+                    tolerance,
+                    |previous, current, next, interpolation| {
+                        let interpolated =
+                            glm::quat_slerp(&rotation(previous), &rotation(next), interpolation);
+                        rotation_error(&interpolated, &rotation(current))
+                    },
+                )
+            }
+            TransformationSet::QuantizedRotations(rotations) => {
+                let rotation = |index: usize| dequantize_rotation(&rotations[index]);
+                reduce_keys(
+                    inputs,
+                    tolerance,
+                    |previous, current, next, interpolation| {
+                        let interpolated =
+                            glm::quat_slerp(&rotation(previous), &rotation(next), interpolation);
+                        rotation_error(&interpolated, &rotation(current))
+                    },
+                )
+            }
+            TransformationSet::MorphTargetWeights(weights) => {
+                let number_of_weights = weights.len() / inputs.len().max(1);
+                if number_of_weights == 0 {
+                    return 0;
+                }
+                reduce_keys(
+                    inputs,
+                    tolerance,
+                    |previous, current, next, interpolation| {
+                        (0..number_of_weights)
+                            .map(|index| {
+                                let start = weights[previous * number_of_weights + index];
+                                let end = weights[next * number_of_weights + index];
+                                let value = weights[current * number_of_weights + index];
+                                (glm::lerp_scalar(start, end, interpolation) - value).abs()
+                            })
+                            .fold(0.0, f32::max)
+                    },
+                )
+            }
+        };
+
+        let removed = self.inputs.len() - kept_keys.len();
+        if removed == 0 {
+            return 0;
+        }
+
+        self.inputs = select_keys(&self.inputs, &kept_keys, 1);
+        match &mut self.transformations {
+            TransformationSet::Translations(values) | TransformationSet::Scales(values) => {
+                *values = select_keys(values, &kept_keys, 1);
+            }
+            TransformationSet::Rotations(rotations) => {
+                *rotations = select_keys(rotations, &kept_keys, 1);
+            }
+            TransformationSet::QuantizedRotations(rotations) => {
+                *rotations = select_keys(rotations, &kept_keys, 1);
+            }
+            TransformationSet::MorphTargetWeights(weights) => {
+                let number_of_weights = weights.len() / (self.inputs.len() + removed);
+                *weights = select_keys(weights, &kept_keys, number_of_weights);
+            }
+        }
+        removed
+    }
+
+    pub fn quantize_rotations(&mut self) {
+        if let TransformationSet::Rotations(rotations) = &self.transformations {
+            let quantized = rotations.iter().map(quantize_rotation).collect();
+            self.transformations = TransformationSet::QuantizedRotations(quantized);
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        let outputs = match &self.transformations {
+            TransformationSet::Translations(values) | TransformationSet::Scales(values) => {
+                values.len() * size_of::<glm::Vec3>()
+            }
+            TransformationSet::Rotations(rotations) => rotations.len() * size_of::<glm::Vec4>(),
+            TransformationSet::QuantizedRotations(rotations) => {
+                rotations.len() * size_of::<[i16; 4]>()
+            }
+            TransformationSet::MorphTargetWeights(weights) => weights.len() * size_of::<f32>(),
+        };
+        self.inputs.len() * size_of::<f32>() + outputs
+    }
+}
+
+/// Greedily keeps only the keys that are needed to reconstruct every removed key within tolerance.
+/// The error function receives the previous, current, and next key indices along with
+/// the interpolation factor of the current key between the previous and next keys.
+fn reduce_keys(
+    inputs: &[f32],
+    tolerance: f32,
+    error: impl Fn(usize, usize, usize, f32) -> f32,
+) -> Vec<usize> {
+    if inputs.len() < 3 {
+        return (0..inputs.len()).collect();
+    }
+    let mut kept_keys = vec![0];
+    for current in 1..inputs.len() - 1 {
+        let previous = kept_keys[kept_keys.len() - 1];
+        let next = current + 1;
+        let span = inputs[next] - inputs[previous];
+        let exceeds_tolerance = (previous + 1..=current).any(|skipped| {
+            let interpolation = if span > 0.0 {
+                (inputs[skipped] - inputs[previous]) / span
+            } else {
+                0.0
+            };
+            error(previous, skipped, next, interpolation) > tolerance
+        });
+        if exceeds_tolerance {
+            kept_keys.push(current);
+        }
+    }
+    kept_keys.push(inputs.len() - 1);
+    kept_keys
+}
+
+fn select_keys<T: Copy>(values: &[T], kept_keys: &[usize], stride: usize) -> Vec<T> {
+    kept_keys
+        .iter()
+        .flat_map(|key| values[key * stride..(key + 1) * stride].iter().copied())
+        .collect()
+}
+
+fn rotation_error(first: &glm::Quat, second: &glm::Quat) -> f32 {
+    let dot = glm::quat_dot(first, second).abs().min(1.0);
+    2.0 * dot.acos()
+}
+
+fn quantize_rotation(rotation: &glm::Vec4) -> [i16; 4] {
+    let rotation = rotation.normalize() * i16::MAX as f32;
+    [
+        rotation.x.round() as i16,
+        rotation.y.round() as i16,
+        rotation.z.round() as i16,
+        rotation.w.round() as i16,
+    ]
+}
+
+fn dequantize_rotation(rotation: &[i16; 4]) -> glm::Quat {
+    let rotation = rotation.map(|component| component as f32 / i16::MAX as f32);
+    glm::quat_normalize(&glm::make_quat(&rotation))
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub enum Interpolation {
@@ -128,4 +536,8 @@ pub enum TransformationSet {
     Rotations(Vec<glm::Vec4>),
     Scales(Vec<glm::Vec3>),
     MorphTargetWeights(Vec<f32>),
+
+    /// Rotations stored as normalized quaternions with 16 bits per component.
+    /// Kept last so the serialized variants before it keep their indices.
+    QuantizedRotations(Vec<[i16; 4]>),
 }
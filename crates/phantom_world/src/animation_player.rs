@@ -0,0 +1,369 @@
+use crate::{Animation, ChannelSample, Entity, SampledValue, World};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum LoopMode {
+    /// Plays to the end and holds the last pose
+    Once,
+    Loop,
+    /// Plays forward, then backward, then forward again
+    PingPong,
+}
+
+/// A clip playing in an animation player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AnimationLayer {
+    /// Index of the clip in the world's animations
+    pub animation: usize,
+    pub time: f32,
+    pub weight: f32,
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+    reversed: bool,
+    fade: Option<Fade>,
+}
+
+impl AnimationLayer {
+    pub fn new(animation: usize, weight: f32, loop_mode: LoopMode) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            weight,
+            speed: 1.0,
+            loop_mode,
+            reversed: false,
+            fade: None,
+        }
+    }
+
+    /// Moves the weight to the target weight over the duration in seconds
+    pub fn fade_to(&mut self, target_weight: f32, duration: f32) {
+        if duration <= 0.0 {
+            self.weight = target_weight;
+            self.fade = None;
+            return;
+        }
+        self.fade = Some(Fade {
+            target_weight,
+            rate: (target_weight - self.weight).abs() / duration,
+        });
+    }
+
+    /// Whether a clip that plays once has reached its end
+    pub fn is_finished(&self, duration: f32) -> bool {
+        self.loop_mode == LoopMode::Once && self.time >= duration
+    }
+
+    fn advance(&mut self, delta_time: f32, duration: f32) {
+        if let Some(fade) = self.fade {
+            let step = fade.rate * delta_time;
+            if (fade.target_weight - self.weight).abs() <= step {
+                self.weight = fade.target_weight;
+                self.fade = None;
+            } else {
+                self.weight += step * (fade.target_weight - self.weight).signum();
+            }
+        }
+
+        if duration <= 0.0 {
+            self.time = 0.0;
+            return;
+        }
+        let step = if self.reversed {
+            -delta_time * self.speed
+        } else {
+            delta_time * self.speed
+        };
+        self.time += step;
+        match self.loop_mode {
+            LoopMode::Once => self.time = self.time.clamp(0.0, duration),
+            LoopMode::Loop => self.time = self.time.rem_euclid(duration),
+            LoopMode::PingPong => {
+                if self.time > duration {
+                    self.time = (2.0 * duration - self.time).max(0.0);
+                    self.reversed = !self.reversed;
+                } else if self.time < 0.0 {
+                    self.time = (-self.time).min(duration);
+                    self.reversed = !self.reversed;
+                }
+            }
+        }
+    }
+
+    /// Layers fading out are removed once they can no longer be seen
+    fn faded_out(&self) -> bool {
+        self.weight <= 0.0 && self.fade.map_or(true, |fade| fade.target_weight <= 0.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct Fade {
+    target_weight: f32,
+
+    /// Change in weight per second
+    rate: f32,
+}
+
+/// Plays clips from the world's animations on an entity's hierarchy,
+/// blending them by weight. Clips in a player are not stepped by `World::animate` on their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AnimationPlayer {
+    pub layers: Vec<AnimationLayer>,
+
+    /// Multiplies the speed of every layer
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            layers: Vec::new(),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    pub fn layer_mut(&mut self, animation: usize) -> Option<&mut AnimationLayer> {
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.animation == animation)
+    }
+}
+
+impl World {
+    pub fn animation_index(&self, name: &str) -> Result<usize> {
+        self.animations
+            .iter()
+            .position(|animation| animation.name == name)
+            .context(format!("Failed to find an animation named '{}'", name))
+    }
+
+    /// Plays a single clip, replacing everything the entity's player was playing
+    pub fn play_animation(
+        &mut self,
+        entity: Entity,
+        name: &str,
+        loop_mode: LoopMode,
+    ) -> Result<()> {
+        let animation = self.animation_index(name)?;
+        self.modify_animation_player(entity, |player| {
+            player.layers = vec![AnimationLayer::new(animation, 1.0, loop_mode)];
+        })
+    }
+
+    /// Fades the clip in while fading out everything else over the duration in seconds
+    pub fn crossfade_animation(
+        &mut self,
+        entity: Entity,
+        name: &str,
+        duration: f32,
+        loop_mode: LoopMode,
+    ) -> Result<()> {
+        let animation = self.animation_index(name)?;
+        self.modify_animation_player(entity, |player| {
+            for layer in player.layers.iter_mut() {
+                if layer.animation != animation {
+                    layer.fade_to(0.0, duration);
+                }
+            }
+            if player.layer_mut(animation).is_none() {
+                player
+                    .layers
+                    .push(AnimationLayer::new(animation, 0.0, loop_mode));
+            }
+            if let Some(layer) = player.layer_mut(animation) {
+                layer.loop_mode = loop_mode;
+                layer.fade_to(1.0, duration);
+            }
+        })
+    }
+
+    /// Plays the clip alongside the others, or changes its weight if it is already playing
+    pub fn blend_animation(
+        &mut self,
+        entity: Entity,
+        name: &str,
+        weight: f32,
+        loop_mode: LoopMode,
+    ) -> Result<()> {
+        let animation = self.animation_index(name)?;
+        self.modify_animation_player(entity, |player| match player.layer_mut(animation) {
+            Some(layer) => {
+                layer.weight = weight;
+                layer.fade = None;
+                layer.loop_mode = loop_mode;
+            }
+            None => player
+                .layers
+                .push(AnimationLayer::new(animation, weight, loop_mode)),
+        })
+    }
+
+    pub fn stop_animation(&mut self, entity: Entity, name: &str) -> Result<()> {
+        let animation = self.animation_index(name)?;
+        self.modify_animation_player(entity, |player| {
+            player.layers.retain(|layer| layer.animation != animation)
+        })
+    }
+
+    pub fn set_animation_speed(&mut self, entity: Entity, speed: f32) -> Result<()> {
+        self.modify_animation_player(entity, |player| player.speed = speed)
+    }
+
+    /// Adds a player to entities that don't have one yet
+    fn modify_animation_player(
+        &mut self,
+        entity: Entity,
+        modify: impl FnOnce(&mut AnimationPlayer),
+    ) -> Result<()> {
+        let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+        if entry.get_component::<AnimationPlayer>().is_err() {
+            entry.add_component(AnimationPlayer::default());
+        }
+        modify(entry.get_component_mut::<AnimationPlayer>()?);
+        Ok(())
+    }
+
+    /// Indices of the clips that are driven by animation players
+    pub(crate) fn player_animations(&self) -> HashSet<usize> {
+        let mut query = <&AnimationPlayer>::query();
+        query
+            .iter(&self.ecs)
+            .flat_map(|player| player.layers.iter().map(|layer| layer.animation))
+            .collect()
+    }
+
+    /// Advances every animation player and applies its blended pose
+    pub fn update_animation_players(&mut self, delta_time: f32) -> Result<()> {
+        let disabled = &self.disabled;
+        let mut samples = Vec::new();
+        let mut query = <(Entity, &mut AnimationPlayer)>::query();
+        for (entity, player) in query.iter_mut(&mut self.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            if !player.paused {
+                let delta_time = delta_time * player.speed;
+                for layer in player.layers.iter_mut() {
+                    let duration = self
+                        .animations
+                        .get(layer.animation)
+                        .map_or(0.0, |animation| animation.max_animation_time);
+                    layer.advance(delta_time, duration);
+                }
+                player.layers.retain(|layer| !layer.faded_out());
+            }
+            samples.extend(blend_layers(&self.animations, &player.layers));
+        }
+        for sample in samples {
+            if disabled.contains(&sample.target) {
+                continue;
+            }
+            sample.apply(&mut self.ecs, &mut self.changes)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    MorphTargetWeights,
+}
+
+impl Property {
+    fn of(value: &SampledValue) -> Self {
+        match value {
+            SampledValue::Translation(_) => Self::Translation,
+            SampledValue::Rotation(_) => Self::Rotation,
+            SampledValue::Scale(_) => Self::Scale,
+            SampledValue::MorphTargetWeights(_) => Self::MorphTargetWeights,
+        }
+    }
+}
+
+/// Averages the layers' samples by weight. Rotations are summed in the same
+/// hemisphere and normalized, which is close to slerp for nearby poses.
+fn blend_layers(animations: &[Animation], layers: &[AnimationLayer]) -> Vec<ChannelSample> {
+    let mut blended: HashMap<(Entity, Property), (SampledValue, f32)> = HashMap::new();
+    for layer in layers.iter().filter(|layer| layer.weight > 0.0) {
+        let animation = match animations.get(layer.animation) {
+            Some(animation) => animation,
+            None => continue,
+        };
+        let weight = layer.weight;
+        for sample in animation.sample(layer.time) {
+            let key = (sample.target, Property::of(&sample.value));
+            let (sum, total_weight) = match blended.get_mut(&key) {
+                Some(entry) => entry,
+                None => {
+                    blended.insert(key, (scale_value(&sample.value, weight), weight));
+                    continue;
+                }
+            };
+            *total_weight += weight;
+            match (sum, &sample.value) {
+                (SampledValue::Translation(sum), SampledValue::Translation(value))
+                | (SampledValue::Scale(sum), SampledValue::Scale(value)) => {
+                    *sum += value * weight;
+                }
+                (SampledValue::Rotation(sum), SampledValue::Rotation(value)) => {
+                    let weight = if glm::quat_dot(sum, value) < 0.0 {
+                        -weight
+                    } else {
+                        weight
+                    };
+                    *sum += value * weight;
+                }
+                (
+                    SampledValue::MorphTargetWeights(sum),
+                    SampledValue::MorphTargetWeights(value),
+                ) => {
+                    sum.iter_mut()
+                        .zip(value.iter())
+                        .for_each(|(sum, value)| *sum += value * weight);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    blended
+        .into_iter()
+        .map(|((target, _), (value, total_weight))| {
+            let value = match value {
+                SampledValue::Rotation(rotation) => {
+                    SampledValue::Rotation(glm::quat_normalize(&rotation))
+                }
+                value => scale_value(&value, 1.0 / total_weight),
+            };
+            ChannelSample { target, value }
+        })
+        .collect()
+}
+
+fn scale_value(value: &SampledValue, scale: f32) -> SampledValue {
+    match value {
+        SampledValue::Translation(translation) => SampledValue::Translation(translation * scale),
+        SampledValue::Rotation(rotation) => SampledValue::Rotation(rotation * scale),
+        SampledValue::Scale(value) => SampledValue::Scale(value * scale),
+        SampledValue::MorphTargetWeights(weights) => {
+            SampledValue::MorphTargetWeights(weights.iter().map(|weight| weight * scale).collect())
+        }
+    }
+}
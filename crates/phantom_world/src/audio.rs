@@ -0,0 +1,27 @@
+use crate::AssetId;
+use phantom_dependencies::serde::{Deserialize, Serialize};
+
+/// Plays a clip from the entity's position in the scene
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AudioSource {
+    /// The clip's asset, found by id so it can be moved or renamed along with its sidecar
+    pub clip: Option<AssetId>,
+
+    pub volume: f32,
+    pub looping: bool,
+
+    /// Distance at which the source is fully attenuated
+    pub range: f32,
+}
+
+impl Default for AudioSource {
+    fn default() -> Self {
+        Self {
+            clip: None,
+            volume: 1.0,
+            looping: false,
+            range: 10.0,
+        }
+    }
+}
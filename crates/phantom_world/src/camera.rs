@@ -1,9 +1,14 @@
+use crate::{BoundingBox, Entity, Transform, World};
 use phantom_dependencies::{
+    anyhow::Result,
+    legion::IntoQuery,
+    nalgebra::Point3,
     nalgebra_glm as glm,
+    rapier3d::geometry::Ray,
     serde::{Deserialize, Serialize},
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Camera {
     pub name: String,
@@ -27,14 +32,246 @@ impl Camera {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Builds a world space ray through a point on the screen,
+/// where (0, 0) is the top left corner and (1, 1) is the bottom right
+pub fn screen_ray(screen_position: &glm::Vec2, projection: &glm::Mat4, view: &glm::Mat4) -> Ray {
+    let inverse_view_projection = glm::inverse(&(projection * view));
+    let x = screen_position.x * 2.0 - 1.0;
+    let y = 1.0 - screen_position.y * 2.0;
+    let unproject = |depth: f32| {
+        let point = inverse_view_projection * glm::vec4(x, y, depth, 1.0);
+        point.xyz() / point.w
+    };
+    // The second point isn't on the far plane, which is at infinity for infinite perspectives
+    let near = unproject(0.0);
+    let direction = glm::normalize(&(unproject(0.5) - near));
+    Ray::new(Point3::from(near), direction)
+}
+
+/// Orbits its entity around a target point, for navigating a scene like an editor viewport.
+/// The entity's local transform is replaced by `World::update_orbit_cameras`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct OrbitCamera {
+    pub target: glm::Vec3,
+    pub radius: f32,
+
+    /// Radians around the y axis, where zero looks down the negative z axis
+    pub yaw: f32,
+
+    /// Radians above the target
+    pub pitch: f32,
+
+    pub min_radius: f32,
+    pub max_radius: f32,
+
+    /// Radians per pixel dragged
+    pub rotation_sensitivity: f32,
+
+    /// Fraction of the radius moved per line scrolled
+    pub zoom_sensitivity: f32,
+
+    /// Fraction of the radius moved per pixel dragged
+    pub pan_sensitivity: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: glm::Vec3::zeros(),
+            radius: 10.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_radius: 0.1,
+            max_radius: 1000.0,
+            rotation_sensitivity: 0.005,
+            zoom_sensitivity: 0.1,
+            pan_sensitivity: 0.002,
+        }
+    }
+}
+
+impl OrbitCamera {
+    /// Keeps the camera from flipping over the poles
+    const MAX_PITCH: f32 = 1.55;
+
+    /// Orbits the point the transform is looking at, from its current position
+    pub fn from_transform(transform: &Transform, radius: f32) -> Self {
+        let forward = transform.forward();
+        Self {
+            target: transform.translation + forward * radius,
+            radius,
+            yaw: (-forward.x).atan2(-forward.z),
+            pitch: (-forward.y).clamp(-1.0, 1.0).asin(),
+            ..Default::default()
+        }
+    }
+
+    /// Rotates around the target by a mouse drag in pixels
+    pub fn rotate(&mut self, delta: &glm::Vec2) {
+        self.yaw -= delta.x * self.rotation_sensitivity;
+        self.pitch = (self.pitch + delta.y * self.rotation_sensitivity)
+            .clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Moves toward the target by the lines scrolled
+    pub fn zoom(&mut self, lines: f32) {
+        let scale = (1.0 - lines * self.zoom_sensitivity).max(0.0);
+        self.radius = (self.radius * scale).clamp(self.min_radius, self.max_radius);
+    }
+
+    /// Moves the target across the view by a mouse drag in pixels,
+    /// so the scene follows the cursor
+    pub fn pan(&mut self, delta: &glm::Vec2) {
+        let transform = self.transform();
+        let offset = transform.up() * delta.y - transform.right() * delta.x;
+        self.target += offset * self.pan_sensitivity * self.radius;
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        let direction = glm::vec3(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + direction * self.radius
+    }
+
+    pub fn transform(&self) -> Transform {
+        let position = self.position();
+        let mut transform = Transform {
+            translation: position,
+            ..Default::default()
+        };
+        transform.look_at(&(self.target - position), &glm::Vec3::y());
+        transform
+    }
+}
+
+/// Flies its entity through the scene with first person mouse look.
+/// The entity's local transform is replaced by `World::update_fly_cameras`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct FlyCamera {
+    pub position: glm::Vec3,
+
+    /// Radians around the y axis, where zero looks down the negative z axis
+    pub yaw: f32,
+
+    /// Radians above the horizon
+    pub pitch: f32,
+
+    /// Units per second
+    pub speed: f32,
+
+    /// Radians per pixel of mouse movement
+    pub look_sensitivity: f32,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            position: glm::vec3(0.0, 0.0, 10.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 5.0,
+            look_sensitivity: 0.002,
+        }
+    }
+}
+
+impl FlyCamera {
+    /// Keeps the camera from flipping over when looking straight up or down
+    const MAX_PITCH: f32 = 1.55;
+
+    /// Looks the same way as the transform, from the same position
+    pub fn from_transform(transform: &Transform) -> Self {
+        let forward = transform.forward();
+        Self {
+            position: transform.translation,
+            yaw: (-forward.x).atan2(-forward.z),
+            pitch: forward.y.clamp(-1.0, 1.0).asin(),
+            ..Default::default()
+        }
+    }
+
+    /// Turns by a mouse movement in pixels
+    pub fn look(&mut self, delta: &glm::Vec2) {
+        self.yaw -= delta.x * self.look_sensitivity;
+        self.pitch =
+            (self.pitch - delta.y * self.look_sensitivity).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Moves along a direction relative to the view, where x is right,
+    /// y is up in the world and z is forward. The speed is scaled by the multiplier.
+    pub fn fly(&mut self, direction: &glm::Vec3, speed_multiplier: f32, delta_time: f32) {
+        if direction.magnitude_squared() <= f32::EPSILON {
+            return;
+        }
+        let forward = self.forward();
+        let right = glm::normalize(&glm::cross(&forward, &glm::Vec3::y()));
+        let movement = right * direction.x + glm::Vec3::y() * direction.y + forward * direction.z;
+        self.position += glm::normalize(&movement) * self.speed * speed_multiplier * delta_time;
+    }
+
+    pub fn forward(&self) -> glm::Vec3 {
+        glm::vec3(
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn transform(&self) -> Transform {
+        let mut transform = Transform {
+            translation: self.position,
+            ..Default::default()
+        };
+        transform.look_at(&self.forward(), &glm::Vec3::y());
+        transform
+    }
+}
+
+impl World {
+    /// Moves every entity with a fly camera to its position and view, keeping its scale
+    pub fn update_fly_cameras(&mut self) -> Result<()> {
+        let mut query = <(Entity, &FlyCamera)>::query();
+        let views = query
+            .iter(&self.ecs)
+            .map(|(entity, camera)| (*entity, camera.transform()))
+            .collect::<Vec<_>>();
+        self.set_camera_transforms(views)
+    }
+
+    /// Moves every entity with an orbit camera to its orbit, keeping its scale
+    pub fn update_orbit_cameras(&mut self) -> Result<()> {
+        let mut query = <(Entity, &OrbitCamera)>::query();
+        let orbits = query
+            .iter(&self.ecs)
+            .map(|(entity, orbit)| (*entity, orbit.transform()))
+            .collect::<Vec<_>>();
+        self.set_camera_transforms(orbits)
+    }
+
+    fn set_camera_transforms(&mut self, views: Vec<(Entity, Transform)>) -> Result<()> {
+        for (entity, view) in views {
+            self.modify_component::<Transform>(entity, |transform| {
+                transform.translation = view.translation;
+                transform.rotation = view.rotation;
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub enum Projection {
     Perspective(PerspectiveCamera),
     Orthographic(OrthographicCamera),
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct PerspectiveCamera {
     pub aspect_ratio: Option<f32>,
@@ -59,7 +296,7 @@ impl PerspectiveCamera {
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct OrthographicCamera {
     pub x_mag: f32,
@@ -92,3 +329,39 @@ impl OrthographicCamera {
         )
     }
 }
+
+/// The planes bounding what a camera can see, for skipping anything outside of it
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    /// Normals point inwards, with the plane's distance from the origin in `w`
+    planes: Vec<glm::Vec4>,
+}
+
+impl Frustum {
+    /// Extracts the planes from a view projection matrix with a depth range of zero to one.
+    /// The far plane of an infinite perspective is left out.
+    pub fn from_matrix(view_projection: &glm::Mat4) -> Self {
+        let row = |index: usize| view_projection.row(index).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .iter()
+            .filter_map(|plane| {
+                let length = plane.xyz().magnitude();
+                (length > f32::EPSILON).then(|| plane / length)
+            })
+            .collect();
+        Self { planes }
+    }
+
+    /// Whether a bounding box, transformed into world space by the model matrix,
+    /// is at least partly inside the frustum
+    pub fn intersects_box(&self, bounding_box: &BoundingBox, model: &glm::Mat4) -> bool {
+        let center = (model * bounding_box.center().push(1.0)).xyz();
+        let half_extents = glm::abs(&glm::mat4_to_mat3(model)) * bounding_box.half_extents();
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let radius = glm::dot(&half_extents, &glm::abs(&normal));
+            glm::dot(&normal, &center) + plane.w >= -radius
+        })
+    }
+}
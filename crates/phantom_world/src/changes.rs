@@ -0,0 +1,155 @@
+use crate::Entity;
+use phantom_dependencies::legion::storage::Component;
+use std::{any::TypeId, collections::HashMap};
+
+/// Records the tick at which each component of each entity was added and last modified
+pub struct ChangeTracker {
+    tick: u64,
+    changes: HashMap<TypeId, HashMap<Entity, u64>>,
+    additions: HashMap<TypeId, HashMap<Entity, u64>>,
+}
+
+impl Default for ChangeTracker {
+    fn default() -> Self {
+        Self {
+            tick: 1,
+            changes: HashMap::new(),
+            additions: HashMap::new(),
+        }
+    }
+}
+
+impl ChangeTracker {
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn increment_tick(&mut self) {
+        self.tick += 1;
+    }
+
+    pub fn mark_changed<T: Component>(&mut self, entity: Entity) {
+        self.changes
+            .entry(TypeId::of::<T>())
+            .or_insert_with(HashMap::new)
+            .insert(entity, self.tick);
+    }
+
+    /// Marks a component by its type id, for components only known at runtime
+    pub fn mark_changed_type(&mut self, component: TypeId, entity: Entity) {
+        self.changes
+            .entry(component)
+            .or_insert_with(HashMap::new)
+            .insert(entity, self.tick);
+    }
+
+    /// Added components also count as changed
+    pub fn mark_added<T: Component>(&mut self, entity: Entity) {
+        self.additions
+            .entry(TypeId::of::<T>())
+            .or_insert_with(HashMap::new)
+            .insert(entity, self.tick);
+        self.mark_changed::<T>(entity);
+    }
+
+    pub fn is_changed_since<T: Component>(&self, entity: Entity, tick: u64) -> bool {
+        Self::is_since(&self.changes, TypeId::of::<T>(), entity, tick)
+    }
+
+    pub fn is_added_since<T: Component>(&self, entity: Entity, tick: u64) -> bool {
+        Self::is_since(&self.additions, TypeId::of::<T>(), entity, tick)
+    }
+
+    /// Whether a change detection filter lets an entity through
+    pub fn matches(&self, filter: &ChangeFilter, entity: Entity) -> bool {
+        match *filter {
+            ChangeFilter::Added { component, since } => {
+                Self::is_since(&self.additions, component, entity, since)
+            }
+            ChangeFilter::Changed { component, since } => {
+                Self::is_since(&self.changes, component, entity, since)
+            }
+        }
+    }
+
+    fn is_since(
+        ticks: &HashMap<TypeId, HashMap<Entity, u64>>,
+        component: TypeId,
+        entity: Entity,
+        tick: u64,
+    ) -> bool {
+        ticks
+            .get(&component)
+            .and_then(|entities| entities.get(&entity))
+            .map_or(false, |changed_tick| *changed_tick > tick)
+    }
+
+    pub fn changed_since<T: Component>(&self, tick: u64) -> Vec<Entity> {
+        match self.changes.get(&TypeId::of::<T>()) {
+            Some(entities) => entities
+                .iter()
+                .filter(|(_, changed_tick)| **changed_tick > tick)
+                .map(|(entity, _)| *entity)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the entities whose `T` component changed since `last_run`,
+    /// then moves `last_run` forward so the next call only sees newer changes
+    pub fn read_changes<T: Component>(&mut self, last_run: &mut u64) -> Vec<Entity> {
+        let changed = self.changed_since::<T>(*last_run);
+        *last_run = self.tick;
+        self.increment_tick();
+        changed
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity) {
+        self.changes
+            .values_mut()
+            .chain(self.additions.values_mut())
+            .for_each(|entities| {
+                entities.remove(&entity);
+            });
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.additions.clear();
+    }
+}
+
+/// Narrows a query to the entities whose component was added or changed after a tick
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChangeFilter {
+    Added { component: TypeId, since: u64 },
+    Changed { component: TypeId, since: u64 },
+}
+
+impl ChangeFilter {
+    pub fn added<T: Component>(since: u64) -> Self {
+        Self::Added {
+            component: TypeId::of::<T>(),
+            since,
+        }
+    }
+
+    pub fn changed<T: Component>(since: u64) -> Self {
+        Self::Changed {
+            component: TypeId::of::<T>(),
+            since,
+        }
+    }
+}
+
+/// Remembers when a system last ran so it can query only the components changed since then
+#[derive(Default, Debug, Copy, Clone)]
+pub struct ChangeCursor {
+    pub last_run: u64,
+}
+
+impl ChangeCursor {
+    pub fn changed<T: Component>(&mut self, tracker: &mut ChangeTracker) -> Vec<Entity> {
+        tracker.read_changes::<T>(&mut self.last_run)
+    }
+}
@@ -0,0 +1,462 @@
+use crate::{Entity, Vertex, World};
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ClothParticle {
+    pub position: glm::Vec3,
+    pub previous_position: glm::Vec3,
+    pub inverse_mass: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ClothConstraint {
+    pub first: usize,
+    pub second: usize,
+    pub rest_length: f32,
+    pub compliance: f32,
+}
+
+/// Holds a particle at an offset from an entity, such as a skeleton joint
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct ClothPin {
+    pub particle: usize,
+    pub target: Entity,
+    pub offset: glm::Vec3,
+}
+
+/// Simple shapes the cloth is pushed out of.
+/// Shapes with a target move with that entity's global transform.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum ClothCollider {
+    Sphere {
+        target: Option<Entity>,
+        center: glm::Vec3,
+        radius: f32,
+    },
+    Capsule {
+        target: Option<Entity>,
+        start: glm::Vec3,
+        end: glm::Vec3,
+        radius: f32,
+    },
+    Plane {
+        normal: glm::Vec3,
+        distance: f32,
+    },
+}
+
+impl ClothCollider {
+    fn to_world(&self, transform: &glm::Mat4) -> Self {
+        let point =
+            |point: &glm::Vec3| (transform * glm::vec4(point.x, point.y, point.z, 1.0)).xyz();
+        match *self {
+            Self::Sphere {
+                target,
+                center,
+                radius,
+            } => Self::Sphere {
+                target,
+                center: point(&center),
+                radius,
+            },
+            Self::Capsule {
+                target,
+                start,
+                end,
+                radius,
+            } => Self::Capsule {
+                target,
+                start: point(&start),
+                end: point(&end),
+                radius,
+            },
+            plane => plane,
+        }
+    }
+
+    fn target(&self) -> Option<Entity> {
+        match self {
+            Self::Sphere { target, .. } | Self::Capsule { target, .. } => *target,
+            Self::Plane { .. } => None,
+        }
+    }
+
+    /// Moves a point to the surface of the shape if it is inside of it
+    pub fn resolve(&self, position: &mut glm::Vec3, thickness: f32) {
+        match *self {
+            Self::Sphere { center, radius, .. } => {
+                push_out_of_sphere(position, &center, radius + thickness);
+            }
+            Self::Capsule {
+                start, end, radius, ..
+            } => {
+                let segment = end - start;
+                let length_squared = glm::length2(&segment);
+                let t = if length_squared > f32::EPSILON {
+                    (glm::dot(&(*position - start), &segment) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                push_out_of_sphere(position, &(start + segment * t), radius + thickness);
+            }
+            Self::Plane { normal, distance } => {
+                let depth = glm::dot(position, &normal) - distance - thickness;
+                if depth < 0.0 {
+                    *position -= normal * depth;
+                }
+            }
+        }
+    }
+}
+
+fn push_out_of_sphere(position: &mut glm::Vec3, center: &glm::Vec3, radius: f32) {
+    let offset = *position - center;
+    let distance = glm::length(&offset);
+    if distance < radius && distance > f32::EPSILON {
+        *position = center + offset * (radius / distance);
+    }
+}
+
+/// Everything outside of the cloth that a simulation step depends on
+#[derive(Default, Debug, Clone)]
+pub struct ClothEnvironment {
+    pub gravity: glm::Vec3,
+    pub pins: Vec<(usize, glm::Vec3)>,
+    pub colliders: Vec<ClothCollider>,
+}
+
+/// A grid of particles simulated with XPBD, for capes, flags, and banners
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Cloth {
+    pub columns: usize,
+    pub rows: usize,
+    pub particles: Vec<ClothParticle>,
+    pub constraints: Vec<ClothConstraint>,
+    pub pins: Vec<ClothPin>,
+    pub colliders: Vec<ClothCollider>,
+    pub wind: glm::Vec3,
+    pub damping: f32,
+    pub substeps: u32,
+    pub thickness: f32,
+
+    /// Simulate on the GPU when the renderer supports compute shaders
+    pub prefer_gpu: bool,
+
+    /// Drawn with the default material when this is `None`
+    pub material_index: Option<usize>,
+}
+
+impl Cloth {
+    pub const STRETCH_COMPLIANCE: f32 = 0.0;
+    pub const SHEAR_COMPLIANCE: f32 = 0.0001;
+    pub const BEND_COMPLIANCE: f32 = 0.01;
+
+    /// Creates a grid hanging down from its top row, positioned by the transform
+    pub fn grid(columns: usize, rows: usize, spacing: f32, transform: &glm::Mat4) -> Self {
+        let mut particles = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let local = glm::vec4(column as f32 * spacing, -(row as f32) * spacing, 0.0, 1.0);
+                let position = (transform * local).xyz();
+                particles.push(ClothParticle {
+                    position,
+                    previous_position: position,
+                    inverse_mass: 1.0,
+                });
+            }
+        }
+
+        let mut cloth = Self {
+            columns,
+            rows,
+            particles,
+            constraints: Vec::new(),
+            pins: Vec::new(),
+            colliders: Vec::new(),
+            wind: glm::Vec3::zeros(),
+            damping: 0.01,
+            substeps: 10,
+            thickness: 0.01,
+            prefer_gpu: true,
+            material_index: None,
+        };
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let neighbors = [
+                    (1, 0, Self::STRETCH_COMPLIANCE),
+                    (0, 1, Self::STRETCH_COMPLIANCE),
+                    (1, 1, Self::SHEAR_COMPLIANCE),
+                    (-1, 1, Self::SHEAR_COMPLIANCE),
+                    (2, 0, Self::BEND_COMPLIANCE),
+                    (0, 2, Self::BEND_COMPLIANCE),
+                ];
+                for (column_offset, row_offset, compliance) in neighbors {
+                    let other_column = column as isize + column_offset;
+                    let other_row = row + row_offset;
+                    if other_column < 0 || other_column as usize >= columns || other_row >= rows {
+                        continue;
+                    }
+                    cloth.add_constraint(
+                        cloth.particle_index(column, row),
+                        cloth.particle_index(other_column as usize, other_row),
+                        compliance,
+                    );
+                }
+            }
+        }
+
+        cloth
+    }
+
+    pub fn particle_index(&self, column: usize, row: usize) -> usize {
+        row * self.columns + column
+    }
+
+    pub fn add_constraint(&mut self, first: usize, second: usize, compliance: f32) {
+        let rest_length = glm::distance(
+            &self.particles[first].position,
+            &self.particles[second].position,
+        );
+        self.constraints.push(ClothConstraint {
+            first,
+            second,
+            rest_length,
+            compliance,
+        });
+    }
+
+    /// Pins a particle to an entity, keeping its current offset from the entity
+    pub fn pin(&mut self, particle: usize, target: Entity, target_transform: &glm::Mat4) {
+        let position = self.particles[particle].position;
+        let offset = (glm::inverse(target_transform)
+            * glm::vec4(position.x, position.y, position.z, 1.0))
+        .xyz();
+        self.particles[particle].inverse_mass = 0.0;
+        self.pins.push(ClothPin {
+            particle,
+            target,
+            offset,
+        });
+    }
+
+    pub fn simulate(&mut self, delta_time: f32, environment: &ClothEnvironment) {
+        if delta_time <= 0.0 {
+            return;
+        }
+        self.apply_pins(environment);
+        let substeps = self.substeps.max(1);
+        let substep = delta_time / substeps as f32;
+        for _ in 0..substeps {
+            self.integrate(substep, &environment.gravity);
+            self.solve_constraints(substep);
+            self.collide(environment);
+        }
+    }
+
+    pub fn apply_pins(&mut self, environment: &ClothEnvironment) {
+        for (particle, position) in environment.pins.iter() {
+            let particle = &mut self.particles[*particle];
+            particle.previous_position = particle.position;
+            particle.position = *position;
+        }
+    }
+
+    /// The acceleration wind applies to a particle, strongest when it faces the wind
+    pub fn wind_acceleration(&self, index: usize) -> glm::Vec3 {
+        let normal = self.particle_normal(index);
+        normal * glm::dot(&normal, &self.wind)
+    }
+
+    pub fn particle_normal(&self, index: usize) -> glm::Vec3 {
+        let (column, row) = (index % self.columns, index / self.columns);
+        let right = self.particle_index((column + 1).min(self.columns - 1), row);
+        let left = self.particle_index(column.saturating_sub(1), row);
+        let down = self.particle_index(column, (row + 1).min(self.rows - 1));
+        let up = self.particle_index(column, row.saturating_sub(1));
+        let horizontal = self.particles[right].position - self.particles[left].position;
+        let vertical = self.particles[down].position - self.particles[up].position;
+        let normal = glm::cross(&horizontal, &vertical);
+        if glm::length2(&normal) > f32::EPSILON {
+            glm::normalize(&normal)
+        } else {
+            glm::Vec3::zeros()
+        }
+    }
+
+    fn integrate(&mut self, substep: f32, gravity: &glm::Vec3) {
+        let wind = (0..self.particles.len())
+            .map(|index| self.wind_acceleration(index))
+            .collect::<Vec<_>>();
+        let damping = 1.0 - self.damping;
+        for (particle, wind) in self.particles.iter_mut().zip(wind.iter()) {
+            if particle.inverse_mass == 0.0 {
+                continue;
+            }
+            let velocity = (particle.position - particle.previous_position) * damping;
+            particle.previous_position = particle.position;
+            particle.position += velocity + (gravity + wind) * substep * substep;
+        }
+    }
+
+    fn solve_constraints(&mut self, substep: f32) {
+        for constraint in self.constraints.iter() {
+            let first = self.particles[constraint.first];
+            let second = self.particles[constraint.second];
+            let weight = first.inverse_mass + second.inverse_mass;
+            if weight == 0.0 {
+                continue;
+            }
+            let offset = first.position - second.position;
+            let length = glm::length(&offset);
+            if length < f32::EPSILON {
+                continue;
+            }
+            let alpha = constraint.compliance / (substep * substep);
+            let lambda = -(length - constraint.rest_length) / (weight + alpha);
+            let correction = offset * (lambda / length);
+            self.particles[constraint.first].position += correction * first.inverse_mass;
+            self.particles[constraint.second].position -= correction * second.inverse_mass;
+        }
+    }
+
+    /// The particles as vertices, with texture coordinates spanning the grid
+    pub fn vertices(&self) -> Vec<Vertex> {
+        let last_column = self.columns.saturating_sub(1).max(1) as f32;
+        let last_row = self.rows.saturating_sub(1).max(1) as f32;
+        self.particles
+            .iter()
+            .enumerate()
+            .map(|(index, particle)| {
+                let (column, row) = (index % self.columns, index / self.columns);
+                Vertex {
+                    position: particle.position,
+                    normal: self.particle_normal(index),
+                    uv_0: glm::vec2(column as f32 / last_column, row as f32 / last_row),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Two triangles for each cell of the grid, listed with both windings
+    /// so the cloth is drawn from either side
+    pub fn indices(columns: usize, rows: usize) -> Vec<u32> {
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        for row in 0..rows.saturating_sub(1) {
+            for column in 0..columns.saturating_sub(1) {
+                let top_left = (row * columns + column) as u32;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + columns as u32;
+                let bottom_right = bottom_left + 1;
+                front.extend_from_slice(&[
+                    top_left,
+                    top_right,
+                    bottom_left,
+                    top_right,
+                    bottom_right,
+                    bottom_left,
+                ]);
+                back.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    top_right,
+                    top_right,
+                    bottom_left,
+                    bottom_right,
+                ]);
+            }
+        }
+        front.extend(back);
+        front
+    }
+
+    /// Pushes particles out of the environment's collision shapes
+    pub fn collide(&mut self, environment: &ClothEnvironment) {
+        if environment.colliders.is_empty() {
+            return;
+        }
+        for particle in self.particles.iter_mut() {
+            if particle.inverse_mass == 0.0 {
+                continue;
+            }
+            for collider in environment.colliders.iter() {
+                collider.resolve(&mut particle.position, self.thickness);
+            }
+        }
+    }
+}
+
+impl World {
+    /// Resolves the pins and colliders of a cloth into world space
+    pub fn cloth_environment(&self, cloth: &Cloth) -> Result<ClothEnvironment> {
+        let mut pins = Vec::new();
+        for pin in cloth.pins.iter() {
+            let transform = self.entity_global_transform_matrix(pin.target)?;
+            let position =
+                (transform * glm::vec4(pin.offset.x, pin.offset.y, pin.offset.z, 1.0)).xyz();
+            pins.push((pin.particle, position));
+        }
+
+        let mut colliders = Vec::new();
+        for collider in cloth.colliders.iter() {
+            let collider = match collider.target() {
+                Some(target) => collider.to_world(&self.entity_global_transform_matrix(target)?),
+                None => *collider,
+            };
+            colliders.push(collider);
+        }
+
+        Ok(ClothEnvironment {
+            gravity: self.physics.gravity,
+            pins,
+            colliders,
+        })
+    }
+
+    /// Whether the renderer simulates a cloth, rather than the world
+    pub fn is_gpu_cloth(&self, cloth: &Cloth) -> bool {
+        self.gpu_cloth && cloth.prefer_gpu
+    }
+
+    /// Steps every enabled cloth that the renderer doesn't simulate
+    pub fn simulate_cloth(&mut self, delta_time: f32) -> Result<()> {
+        if delta_time <= 0.0 {
+            return Ok(());
+        }
+        let disabled = &self.disabled;
+        let mut query = <(Entity, &Cloth)>::query();
+        let entities = query
+            .iter(&self.ecs)
+            .filter(|(entity, cloth)| !disabled.contains(entity) && !self.is_gpu_cloth(cloth))
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in entities {
+            let environment = {
+                let entry = self.ecs.entry_ref(entity)?;
+                let cloth = entry.get_component::<Cloth>()?;
+                self.cloth_environment(cloth)?
+            };
+            let mut entry = match self.ecs.entry(entity) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            entry
+                .get_component_mut::<Cloth>()?
+                .simulate(delta_time, &environment);
+            self.changes.mark_changed::<Cloth>(entity);
+        }
+        Ok(())
+    }
+}
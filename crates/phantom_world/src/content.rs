@@ -0,0 +1,153 @@
+use crate::{Geometry, Material, Mesh, Texture};
+use phantom_dependencies::{anyhow::Result, bincode, serde::Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::Hasher,
+};
+
+/// Hashes the serialized contents of an asset
+pub fn content_hash(value: &impl Serialize) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&bincode::serialize(value)?);
+    Ok(hasher.finish())
+}
+
+/// Content hashes of a world's textures, materials and meshes, so identical assets
+/// imported from different files are stored and uploaded once.
+/// Assets added without going through here are hashed the next time something is inserted.
+#[derive(Default)]
+pub struct ContentHashes {
+    textures: IndexedHashes,
+    materials: IndexedHashes,
+    meshes: HashMap<u64, String>,
+    hashed_meshes: HashSet<String>,
+}
+
+impl ContentHashes {
+    /// Returns the index of a texture with the same contents, pushing the texture if there is none
+    pub fn insert_texture(
+        &mut self,
+        textures: &mut Vec<Texture>,
+        texture: Texture,
+    ) -> Result<usize> {
+        self.textures.insert(textures, texture)
+    }
+
+    /// Returns the index of a material with the same factors and textures,
+    /// pushing the material if there is none
+    pub fn insert_material(
+        &mut self,
+        materials: &mut Vec<Material>,
+        material: Material,
+    ) -> Result<usize> {
+        self.materials.insert(materials, material)
+    }
+
+    /// Adds a mesh whose geometry was appended starting at `first_vertex` and `first_index`,
+    /// returning the name to render it with. If a mesh with the same geometry and materials
+    /// already exists, the appended geometry is removed again and that mesh's name is returned.
+    pub fn insert_mesh(
+        &mut self,
+        geometry: &mut Geometry,
+        mesh: Mesh,
+        first_vertex: usize,
+        first_index: usize,
+    ) -> Result<String> {
+        let unhashed = geometry
+            .meshes
+            .iter()
+            .filter(|(name, _)| !self.hashed_meshes.contains(*name))
+            .map(|(name, mesh)| -> Result<(u64, String)> {
+                Ok((mesh_hash(mesh, geometry)?, name.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (hash, name) in unhashed {
+            self.hashed_meshes.insert(name.to_string());
+            self.meshes.entry(hash).or_insert(name);
+        }
+
+        let hash = mesh_hash(&mesh, geometry)?;
+        if let Some(name) = self.meshes.get(&hash) {
+            // Meshes can be removed or replaced, so a match is only trusted while it is unchanged
+            if let Some(existing) = geometry.meshes.get(name) {
+                if mesh_hash(existing, geometry)? == hash {
+                    geometry.vertices.truncate(first_vertex);
+                    geometry.indices.truncate(first_index);
+                    return Ok(name.to_string());
+                }
+            }
+        }
+
+        let name = if geometry.meshes.contains_key(&mesh.name) {
+            // FIXME: increment a repeated name with a number
+            //        instead of just adding an underscore
+            let name = mesh.name.to_string();
+            name + "_"
+        } else {
+            mesh.name.to_string()
+        };
+        geometry.meshes.insert(name.clone(), mesh);
+        self.hashed_meshes.insert(name.clone());
+        self.meshes.insert(hash, name.clone());
+        Ok(name)
+    }
+}
+
+/// Hashes of the elements of a list that is only ever appended to, cleared or edited in place
+#[derive(Default)]
+struct IndexedHashes {
+    indices: HashMap<u64, usize>,
+    hashed: usize,
+}
+
+impl IndexedHashes {
+    fn insert<T: Serialize>(&mut self, items: &mut Vec<T>, item: T) -> Result<usize> {
+        if items.len() < self.hashed {
+            self.indices.clear();
+            self.hashed = 0;
+        }
+        for (index, existing) in items.iter().enumerate().skip(self.hashed) {
+            self.indices.entry(content_hash(existing)?).or_insert(index);
+        }
+        self.hashed = items.len();
+
+        let hash = content_hash(&item)?;
+        if let Some(&index) = self.indices.get(&hash) {
+            // Items can be edited in place, so a match is only trusted while it is unchanged
+            if content_hash(&items[index])? == hash {
+                return Ok(index);
+            }
+        }
+        items.push(item);
+        self.indices.insert(hash, items.len() - 1);
+        self.hashed = items.len();
+        Ok(items.len() - 1)
+    }
+}
+
+/// Hashes a mesh's vertices, indices relative to its vertices, materials and morph targets,
+/// leaving out its name and where its geometry is stored
+fn mesh_hash(mesh: &Mesh, geometry: &Geometry) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    for primitive in mesh.primitives.iter() {
+        let vertices = geometry
+            .vertices
+            .get(primitive.first_vertex..primitive.first_vertex + primitive.number_of_vertices)
+            .unwrap_or(&[]);
+        let indices = geometry
+            .indices
+            .get(primitive.first_index..primitive.first_index + primitive.number_of_indices)
+            .unwrap_or(&[])
+            .iter()
+            .map(|index| index.wrapping_sub(primitive.first_vertex as u32))
+            .collect::<Vec<_>>();
+        hasher.write(&bincode::serialize(&(
+            vertices,
+            indices,
+            primitive.material_index,
+            &primitive.morph_targets,
+        ))?);
+    }
+    hasher.write(&bincode::serialize(&mesh.weights)?);
+    Ok(hasher.finish())
+}
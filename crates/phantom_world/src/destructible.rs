@@ -0,0 +1,314 @@
+use crate::{
+    BoundingBox, Entity, Mesh, MeshRender, Name, Primitive, RigidBody, Transform, Vertex, World,
+};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    rapier3d::{geometry::InteractionGroups, prelude::RigidBodyType},
+    serde::{Deserialize, Serialize},
+};
+use std::collections::HashMap;
+
+/// A pre-fractured piece of a destructible mesh, positioned relative to the intact mesh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct FracturePiece {
+    pub mesh: String,
+    pub offset: Transform,
+}
+
+/// Swaps the entity's mesh for physical debris once it takes enough damage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Destructible {
+    pub pieces: Vec<FracturePiece>,
+    pub health: f32,
+
+    /// Damage below this amount is ignored
+    pub damage_threshold: f32,
+
+    /// Impulse applied to each piece, pushing it away from the point of impact
+    pub impulse: f32,
+
+    /// Seconds before the debris is despawned
+    pub debris_lifetime: f32,
+}
+
+impl Destructible {
+    pub fn new(pieces: Vec<FracturePiece>, health: f32) -> Self {
+        Self {
+            pieces,
+            health,
+            damage_threshold: 0.0,
+            impulse: 1.0,
+            debris_lifetime: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Debris {
+    pub age: f32,
+    pub lifetime: f32,
+
+    /// Debris that has come to rest no longer needs a rigid body
+    pub settled: bool,
+}
+
+impl Debris {
+    /// The most debris pieces alive at once, the oldest are removed first
+    pub const MAX_ACTIVE: usize = 256;
+}
+
+impl World {
+    /// Splits an entity's mesh into a grid of pieces, assigning each triangle to the cell containing its center
+    pub fn generate_fracture_pieces(
+        &mut self,
+        entity: Entity,
+        cells: [usize; 3],
+    ) -> Result<Vec<FracturePiece>> {
+        let mesh_name = self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<MeshRender>()?
+            .name
+            .to_string();
+        let mesh = self
+            .geometry
+            .meshes
+            .get(&mesh_name)
+            .context("Failed to find mesh to fracture!")?
+            .clone();
+        let bounding_box = mesh.bounding_box();
+        let extents = bounding_box.extents();
+        let cells = glm::vec3(
+            cells[0].max(1) as f32,
+            cells[1].max(1) as f32,
+            cells[2].max(1) as f32,
+        );
+
+        let mut cell_triangles: HashMap<[usize; 3], Vec<(usize, [Vertex; 3])>> = HashMap::new();
+        for (primitive_index, primitive) in mesh.primitives.iter().enumerate() {
+            let indices = &self.geometry.indices
+                [primitive.first_index..primitive.first_index + primitive.number_of_indices];
+            for triangle in indices.chunks(3) {
+                if triangle.len() < 3 {
+                    continue;
+                }
+                let vertices = [
+                    self.geometry.vertices[triangle[0] as usize],
+                    self.geometry.vertices[triangle[1] as usize],
+                    self.geometry.vertices[triangle[2] as usize],
+                ];
+                let center =
+                    (vertices[0].position + vertices[1].position + vertices[2].position) / 3.0;
+                let relative = (center - bounding_box.min)
+                    .component_div(&extents.map(|extent| extent.max(f32::EPSILON)));
+                let cell = [0, 1, 2].map(|axis| {
+                    ((relative[axis] * cells[axis]).floor().max(0.0) as usize)
+                        .min(cells[axis] as usize - 1)
+                });
+                cell_triangles
+                    .entry(cell)
+                    .or_insert_with(Vec::new)
+                    .push((primitive_index, vertices));
+            }
+        }
+
+        let mut pieces = Vec::new();
+        for (cell, triangles) in cell_triangles {
+            let mut piece_bounds = BoundingBox::new_invalid();
+            triangles
+                .iter()
+                .flat_map(|(_, vertices)| vertices.iter())
+                .for_each(|vertex| piece_bounds.fit_point(vertex.position));
+            let center = piece_bounds.center();
+
+            let mut primitives = Vec::new();
+            for (primitive_index, source) in mesh.primitives.iter().enumerate() {
+                let first_vertex = self.geometry.vertices.len();
+                let first_index = self.geometry.indices.len();
+                let mut primitive_bounds = BoundingBox::new_invalid();
+                for (_, vertices) in triangles
+                    .iter()
+                    .filter(|(index, _)| *index == primitive_index)
+                {
+                    for vertex in vertices.iter() {
+                        let mut vertex = *vertex;
+                        vertex.position -= center;
+                        primitive_bounds.fit_point(vertex.position);
+                        self.geometry
+                            .indices
+                            .push(self.geometry.vertices.len() as u32);
+                        self.geometry.vertices.push(vertex);
+                    }
+                }
+                let number_of_vertices = self.geometry.vertices.len() - first_vertex;
+                if number_of_vertices == 0 {
+                    continue;
+                }
+                primitives.push(Primitive {
+                    first_vertex,
+                    first_index,
+                    number_of_vertices,
+                    number_of_indices: number_of_vertices,
+                    material_index: source.material_index,
+                    morph_targets: Vec::new(),
+                    bounding_box: primitive_bounds,
+                });
+            }
+
+            let name = format!("{}_piece_{}_{}_{}", mesh_name, cell[0], cell[1], cell[2]);
+            self.geometry.meshes.insert(
+                name.to_string(),
+                Mesh {
+                    name: name.to_string(),
+                    primitives,
+                    weights: Vec::new(),
+                },
+            );
+            pieces.push(FracturePiece {
+                mesh: name,
+                offset: Transform {
+                    translation: center,
+                    ..Default::default()
+                },
+            });
+        }
+        self.mark_geometry_changed();
+
+        Ok(pieces)
+    }
+
+    /// Applies damage to a destructible entity, fracturing it when its health runs out.
+    /// Returns the spawned debris if the entity was fractured.
+    pub fn damage(
+        &mut self,
+        entity: Entity,
+        amount: f32,
+        point: glm::Vec3,
+    ) -> Result<Option<Vec<Entity>>> {
+        let destroyed = {
+            let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+            let destructible = entry.get_component_mut::<Destructible>()?;
+            if amount < destructible.damage_threshold || destructible.health <= 0.0 {
+                return Ok(None);
+            }
+            destructible.health -= amount;
+            destructible.health <= 0.0
+        };
+        self.changes.mark_changed::<Destructible>(entity);
+        if !destroyed {
+            return Ok(None);
+        }
+        self.fracture(entity, point).map(Some)
+    }
+
+    /// Replaces the intact entity with a physical debris entity for each of its pieces
+    pub fn fracture(&mut self, entity: Entity, point: glm::Vec3) -> Result<Vec<Entity>> {
+        let destructible = self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<Destructible>()?
+            .clone();
+        let global_transform = self.entity_global_transform_matrix(entity)?;
+        let name = match self.ecs.entry_ref(entity)?.get_component::<Name>() {
+            Ok(name) => name.0.to_string(),
+            Err(_) => "Destructible".to_string(),
+        };
+
+        if self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<RigidBody>()
+            .is_ok()
+        {
+            self.remove_rigid_body(entity)?;
+        }
+        self.set_enabled(entity, false)?;
+
+        let mut debris = Vec::new();
+        for (index, piece) in destructible.pieces.iter().enumerate() {
+            let transform = Transform::from(global_transform * piece.offset.matrix());
+            let piece_entity = self.ecs.push((
+                Name(format!("{} Debris {}", name, index)),
+                transform,
+                MeshRender {
+                    name: piece.mesh.to_string(),
+                },
+                Debris {
+                    age: 0.0,
+                    lifetime: destructible.debris_lifetime,
+                    settled: false,
+                },
+            ));
+            self.scene.default_scenegraph_mut()?.add_node(piece_entity);
+            self.add_rigid_body(piece_entity, RigidBodyType::Dynamic)?;
+            self.add_box_collider(piece_entity, InteractionGroups::all())?;
+
+            let direction = transform.translation - point;
+            let direction = if glm::length2(&direction) > f32::EPSILON {
+                glm::normalize(&direction)
+            } else {
+                glm::Vec3::y()
+            };
+            let handle = self
+                .ecs
+                .entry_ref(piece_entity)?
+                .get_component::<RigidBody>()?
+                .handle;
+            if let Some(body) = self.physics.bodies.get_mut(handle) {
+                body.apply_impulse(direction * destructible.impulse, true);
+            }
+            debris.push(piece_entity);
+        }
+
+        Ok(debris)
+    }
+
+    /// Ages debris, freezing pieces that have come to rest and despawning expired ones.
+    /// Settled pieces are frozen rather than merged into one mesh, since merging would
+    /// append new geometry to the world for every fracture.
+    pub fn update_debris(&mut self, delta_time: f32) -> Result<()> {
+        let mut settled = Vec::new();
+        let mut expired = Vec::new();
+        let mut alive = Vec::new();
+        let mut query = <(Entity, &mut Debris, Option<&RigidBody>)>::query();
+        for (entity, debris, rigid_body) in query.iter_mut(&mut self.ecs) {
+            debris.age += delta_time;
+            if debris.age >= debris.lifetime {
+                expired.push(*entity);
+                continue;
+            }
+            alive.push((*entity, debris.age));
+            if debris.settled {
+                continue;
+            }
+            let sleeping = rigid_body
+                .and_then(|rigid_body| self.physics.bodies.get(rigid_body.handle))
+                .map_or(false, |body| body.is_sleeping());
+            if sleeping {
+                debris.settled = true;
+                settled.push(*entity);
+            }
+        }
+
+        if alive.len() > Debris::MAX_ACTIVE {
+            alive.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let excess = alive.len() - Debris::MAX_ACTIVE;
+            expired.extend(alive.iter().take(excess).map(|(entity, _)| *entity));
+        }
+
+        for entity in settled {
+            if !expired.contains(&entity) {
+                self.remove_rigid_body(entity)?;
+            }
+        }
+        for entity in expired {
+            self.remove_entity(entity)?;
+        }
+        Ok(())
+    }
+}
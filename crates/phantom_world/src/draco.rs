@@ -0,0 +1,233 @@
+use crate::gltf::DRACO_MESH_COMPRESSION;
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    bytemuck,
+    gltf::{self, json::Root},
+    serde::Deserialize,
+    serde_json::{json, Value},
+};
+use std::ptr::NonNull;
+
+#[repr(C)]
+struct RawMesh {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    fn phantom_draco_decode(data: *const u8, length: usize) -> *mut RawMesh;
+    fn phantom_draco_free(mesh: *mut RawMesh);
+    fn phantom_draco_vertex_count(mesh: *const RawMesh) -> u32;
+    fn phantom_draco_index_count(mesh: *const RawMesh) -> u32;
+    fn phantom_draco_copy_indices(mesh: *const RawMesh, indices: *mut u32);
+    fn phantom_draco_copy_attribute(
+        mesh: *const RawMesh,
+        unique_id: u32,
+        components: u32,
+        values: *mut f32,
+    ) -> bool;
+}
+
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// A triangle mesh decoded by Draco
+struct DracoMesh(NonNull<RawMesh>);
+
+impl DracoMesh {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mesh = unsafe { phantom_draco_decode(bytes.as_ptr(), bytes.len()) };
+        NonNull::new(mesh)
+            .map(Self)
+            .context("Failed to decode a Draco compressed primitive!")
+    }
+
+    fn vertex_count(&self) -> usize {
+        unsafe { phantom_draco_vertex_count(self.0.as_ptr()) as usize }
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        let count = unsafe { phantom_draco_index_count(self.0.as_ptr()) } as usize;
+        let mut indices = vec![0; count];
+        unsafe { phantom_draco_copy_indices(self.0.as_ptr(), indices.as_mut_ptr()) };
+        indices
+    }
+
+    fn attribute(&self, unique_id: u32, components: usize) -> Result<Vec<f32>> {
+        let mut values = vec![0.0; self.vertex_count() * components];
+        let copied = unsafe {
+            phantom_draco_copy_attribute(
+                self.0.as_ptr(),
+                unique_id,
+                components as u32,
+                values.as_mut_ptr(),
+            )
+        };
+        if !copied {
+            bail!(
+                "A Draco compressed primitive doesn't have attribute {}!",
+                unique_id
+            );
+        }
+        Ok(values)
+    }
+}
+
+impl Drop for DracoMesh {
+    fn drop(&mut self) {
+        unsafe { phantom_draco_free(self.0.as_ptr()) }
+    }
+}
+
+/// Where a primitive's compressed data is, and which accessors it replaces
+struct CompressedPrimitive {
+    buffer_view: usize,
+    indices: Option<usize>,
+
+    /// The semantic, accessor and Draco attribute id of each compressed attribute
+    attributes: Vec<(String, usize, u32)>,
+}
+
+/// Decodes every primitive compressed with `KHR_draco_mesh_compression` into a new buffer,
+/// pointing the primitives' accessors at the decoded data instead of their uncompressed copies.
+/// The document is rebuilt from the edited json if anything was decoded.
+pub(crate) fn decode_primitives(
+    json: &mut Value,
+    document: gltf::Document,
+    mut buffers: Vec<gltf::buffer::Data>,
+) -> Result<(gltf::Document, Vec<gltf::buffer::Data>)> {
+    let primitives = compressed_primitives(json);
+    if primitives.is_empty() {
+        return Ok((document, buffers));
+    }
+
+    let buffer = buffers.len();
+    let first_view = json["bufferViews"].as_array().map_or(0, Vec::len);
+    let mut decoded = Vec::new();
+    let mut views = Vec::new();
+    let mut push_view = |bytes: &[u8]| {
+        // Views are kept aligned for the floats and integers read from them
+        decoded.resize((decoded.len() + 3) / 4 * 4, 0);
+        views.push(json!({
+            "buffer": buffer,
+            "byteOffset": decoded.len(),
+            "byteLength": bytes.len(),
+        }));
+        decoded.extend_from_slice(bytes);
+        first_view + views.len() - 1
+    };
+
+    for primitive in primitives {
+        let mesh = DracoMesh::decode(view_bytes(json, &buffers, primitive.buffer_view)?)?;
+        if let Some(accessor) = primitive.indices {
+            let indices = mesh.indices();
+            let view = push_view(bytemuck::cast_slice(&indices));
+            replace_accessor(json, accessor, view, UNSIGNED_INT, indices.len())?;
+        }
+        for (semantic, accessor, unique_id) in primitive.attributes {
+            let components = accessor_components(json, accessor)?;
+            let values = mesh.attribute(unique_id, components)?;
+            // Joints are read as integers, everything else as floats
+            let (view, component_type) = if semantic.starts_with("JOINTS_") {
+                let joints = values.iter().map(|joint| *joint as u16).collect::<Vec<_>>();
+                (push_view(bytemuck::cast_slice(&joints)), UNSIGNED_SHORT)
+            } else {
+                (push_view(bytemuck::cast_slice(&values)), FLOAT)
+            };
+            replace_accessor(json, accessor, view, component_type, mesh.vertex_count())?;
+        }
+    }
+
+    json_array(json, "bufferViews").extend(views);
+    json_array(json, "buffers").push(json!({ "byteLength": decoded.len() }));
+    buffers.push(gltf::buffer::Data(decoded));
+    let document = gltf::Document::from_json(Root::deserialize(&*json)?)?;
+    Ok((document, buffers))
+}
+
+fn compressed_primitives(json: &Value) -> Vec<CompressedPrimitive> {
+    let index = |value: &Value| value.as_u64().map(|index| index as usize);
+    json["meshes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|mesh| mesh["primitives"].as_array().into_iter().flatten())
+        .filter_map(|primitive| {
+            let extension = &primitive["extensions"][DRACO_MESH_COMPRESSION];
+            let attributes = extension["attributes"]
+                .as_object()?
+                .iter()
+                .filter_map(|(semantic, id)| {
+                    let accessor = index(&primitive["attributes"][semantic.as_str()])?;
+                    Some((semantic.clone(), accessor, id.as_u64()? as u32))
+                })
+                .collect();
+            Some(CompressedPrimitive {
+                buffer_view: index(&extension["bufferView"])?,
+                indices: index(&primitive["indices"]),
+                attributes,
+            })
+        })
+        .collect()
+}
+
+fn view_bytes<'a>(
+    json: &Value,
+    buffers: &'a [gltf::buffer::Data],
+    view: usize,
+) -> Result<&'a [u8]> {
+    let view = &json["bufferViews"][view];
+    let buffer = view["buffer"]
+        .as_u64()
+        .context("A Draco compressed primitive refers to a buffer view that doesn't exist!")?;
+    let start = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+    let length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+    buffers
+        .get(buffer as usize)
+        .and_then(|buffer| buffer.get(start..start + length))
+        .context("A Draco compressed primitive reads past the end of its buffer!")
+}
+
+fn accessor_components(json: &Value, accessor: usize) -> Result<usize> {
+    Ok(match json["accessors"][accessor]["type"].as_str() {
+        Some("SCALAR") => 1,
+        Some("VEC2") => 2,
+        Some("VEC3") => 3,
+        Some("VEC4") => 4,
+        _ => bail!(
+            "A Draco compressed attribute's accessor {} isn't a scalar or vector!",
+            accessor
+        ),
+    })
+}
+
+/// Points an accessor at tightly packed decoded data
+fn replace_accessor(
+    json: &mut Value,
+    accessor: usize,
+    view: usize,
+    component_type: u32,
+    count: usize,
+) -> Result<()> {
+    let accessor = json["accessors"]
+        .get_mut(accessor)
+        .and_then(Value::as_object_mut)
+        .context("A Draco compressed primitive refers to an accessor that doesn't exist!")?;
+    accessor.insert("bufferView".to_string(), json!(view));
+    accessor.insert("byteOffset".to_string(), json!(0));
+    accessor.insert("componentType".to_string(), json!(component_type));
+    accessor.insert("count".to_string(), json!(count));
+    accessor.remove("normalized");
+    accessor.remove("sparse");
+    Ok(())
+}
+
+fn json_array<'a>(json: &'a mut Value, key: &str) -> &'a mut Vec<Value> {
+    let value = &mut json[key];
+    if !value.is_array() {
+        *value = json!([]);
+    }
+    value
+        .as_array_mut()
+        .expect("The value was just made an array")
+}
@@ -0,0 +1,213 @@
+use crate::{
+    BoundingBox, Enabled, Entity, MeshRender, MorphWeights, Skin, Transform, Vertex, World,
+};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::EntityStore,
+    nalgebra_glm as glm,
+};
+use std::{collections::HashMap, mem::take, ops::Range};
+
+/// A primitive of an extracted mesh, with everything needed to issue its draw call
+#[derive(Debug, Clone)]
+pub struct ExtractedPrimitive {
+    /// Index into the per mesh arrays of the extract
+    pub mesh: usize,
+    pub indices: Range<u32>,
+    pub material_index: Option<usize>,
+    pub bounding_box: BoundingBox,
+
+    /// Set for primitives with weighted morph targets, which are drawn from `morphed_vertices`
+    /// with their indices offset by this many vertices
+    pub morphed_base_vertex: Option<i32>,
+}
+
+/// The scenegraphs flattened into arrays once per frame, so the renderer
+/// draws from these instead of walking the hierarchy itself.
+/// The per mesh arrays all have one element for each entity with a `MeshRender`.
+#[derive(Default, Clone)]
+pub struct RenderExtract {
+    pub entities: Vec<Entity>,
+
+    /// Rigid bodies use their interpolated physics pose instead of their scenegraph transform
+    pub global_matrices: Vec<glm::Mat4>,
+
+    /// False for meshes disabled directly or through an ancestor
+    pub visible: Vec<bool>,
+
+    /// The range of `joint_matrices` deforming each mesh that has a skin
+    pub joints: Vec<Option<Range<usize>>>,
+    pub joint_matrices: Vec<glm::Mat4>,
+    pub primitives: Vec<ExtractedPrimitive>,
+
+    /// The vertices of every morphed primitive with its morph targets applied
+    pub morphed_vertices: Vec<Vertex>,
+
+    /// How many nodes had their global transform recomputed by the last extraction
+    pub updated_transforms: usize,
+
+    transforms: HashMap<Entity, CachedTransform>,
+}
+
+impl RenderExtract {
+    pub fn number_of_meshes(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_skinned(&self, mesh: usize) -> bool {
+        self.joints[mesh].is_some()
+    }
+
+    fn clear(&mut self) {
+        self.entities.clear();
+        self.global_matrices.clear();
+        self.visible.clear();
+        self.joints.clear();
+        self.joint_matrices.clear();
+        self.primitives.clear();
+        self.morphed_vertices.clear();
+        self.updated_transforms = 0;
+    }
+}
+
+/// A node's global transform, reused while neither it nor anything above it has moved
+#[derive(Copy, Clone)]
+struct CachedTransform {
+    local: Transform,
+    parent: Option<Entity>,
+    global: glm::Mat4,
+}
+
+/// What the children of a node need from it while the scenegraph is walked
+#[derive(Copy, Clone)]
+struct ExtractedNode {
+    global: glm::Mat4,
+    changed: bool,
+    enabled: bool,
+}
+
+impl World {
+    /// Flattens the scenegraphs into `extract`, recomputing only the global transforms
+    /// of nodes whose local transform, parent or ancestors changed since the last extraction.
+    /// Transforms are compared by value rather than read from the change tracker,
+    /// so edits made directly through the ecs are still picked up.
+    pub fn extract_render(&self, extract: &mut RenderExtract) -> Result<()> {
+        extract.clear();
+        let previous = take(&mut extract.transforms);
+        let mut skinned = Vec::new();
+        for graph in self.scene.graphs.iter() {
+            let mut nodes: Vec<Option<ExtractedNode>> = vec![None; graph.number_of_nodes()];
+            graph.walk(|node_index| {
+                let entity = graph[node_index];
+                let entry = self.ecs.entry_ref(entity)?;
+                let local = *entry.get_component::<Transform>().context(
+                    "A transform component was requested from a component that does not have one!",
+                )?;
+
+                // Parents are always visited before their children
+                let parent_index = graph.parent_of(node_index);
+                let parent = parent_index.and_then(|index| nodes[index.index()]);
+                let parent_entity = parent_index.map(|index| graph[index]);
+                let parent_changed = parent.map_or(false, |parent| parent.changed);
+                let (global, changed) = match previous.get(&entity) {
+                    Some(cached)
+                        if !parent_changed
+                            && cached.parent == parent_entity
+                            && cached.local == local =>
+                    {
+                        (cached.global, false)
+                    }
+                    _ => {
+                        let parent_global =
+                            parent.map_or_else(glm::Mat4::identity, |parent| parent.global);
+                        (parent_global * local.matrix(), true)
+                    }
+                };
+                if changed {
+                    extract.updated_transforms += 1;
+                }
+                let enabled = parent.map_or(true, |parent| parent.enabled)
+                    && entry
+                        .get_component::<Enabled>()
+                        .map_or(true, |enabled| enabled.0);
+                nodes[node_index.index()] = Some(ExtractedNode {
+                    global,
+                    changed,
+                    enabled,
+                });
+                extract.transforms.insert(
+                    entity,
+                    CachedTransform {
+                        local,
+                        parent: parent_entity,
+                        global,
+                    },
+                );
+
+                let mesh = match entry
+                    .get_component::<MeshRender>()
+                    .ok()
+                    .and_then(|mesh_render| self.geometry.meshes.get(&mesh_render.name))
+                {
+                    Some(mesh) => mesh,
+                    None => return Ok(()),
+                };
+                let mesh_index = extract.entities.len();
+                if entry.get_component::<Skin>().is_ok() {
+                    skinned.push((mesh_index, global));
+                }
+                extract.entities.push(entity);
+                extract
+                    .global_matrices
+                    .push(self.entity_model_matrix(entity, global)?);
+                extract.visible.push(enabled);
+                extract.joints.push(None);
+                let weights = entry
+                    .get_component::<MorphWeights>()
+                    .ok()
+                    .filter(|weights| weights.0.iter().any(|weight| *weight != 0.0));
+                for primitive in mesh.primitives.iter() {
+                    let first_index = primitive.first_index as u32;
+                    let morphed_base_vertex = match weights {
+                        Some(weights) if !primitive.morph_targets.is_empty() => {
+                            let base_vertex = extract.morphed_vertices.len() as i32
+                                - primitive.first_vertex as i32;
+                            extract.morphed_vertices.extend(
+                                primitive.morphed_vertices(&self.geometry.vertices, &weights.0),
+                            );
+                            Some(base_vertex)
+                        }
+                        _ => None,
+                    };
+                    extract.primitives.push(ExtractedPrimitive {
+                        mesh: mesh_index,
+                        indices: first_index..first_index + primitive.number_of_indices as u32,
+                        material_index: primitive.material_index,
+                        bounding_box: primitive.bounding_box.clone(),
+                        morphed_base_vertex,
+                    });
+                }
+                Ok(())
+            })?;
+        }
+
+        // Joints can be anywhere in the scenegraphs, so skins are resolved once every node is
+        for (mesh_index, node_transform) in skinned {
+            let entry = self.ecs.entry_ref(extract.entities[mesh_index])?;
+            let skin = entry.get_component::<Skin>()?;
+            let inverse_node_transform = glm::inverse(&node_transform);
+            let first_joint = extract.joint_matrices.len();
+            for joint in skin.joints.iter() {
+                let joint_transform = extract
+                    .transforms
+                    .get(&joint.target)
+                    .map_or_else(glm::Mat4::identity, |cached| cached.global);
+                extract
+                    .joint_matrices
+                    .push(inverse_node_transform * joint_transform * joint.inverse_bind_matrix);
+            }
+            extract.joints[mesh_index] = Some(first_joint..extract.joint_matrices.len());
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,19 @@
 use crate::{
     AlphaMode, Animation, BoundingBox, Camera, Channel, Ecs, Entity, Filter, Format, Geometry,
-    Interpolation, Joint, Light, LightKind, Material, Mesh, MeshRender, MorphTarget, Name,
-    OrthographicCamera, PerspectiveCamera, Primitive, Projection, Sampler, Scene, SceneGraph, Skin,
-    Texture, Transform, TransformationSet, Vertex, World, WrappingMode,
+    Interpolation, Joint, Light, LightKind, Material, Mesh, MeshRender, MorphTarget, MorphWeights,
+    Name, OrthographicCamera, PerspectiveCamera, Primitive, Projection, Sampler, Scene, SceneGraph,
+    Skin, Texture, TextureTransform, Transform, TransformationSet, Vertex, World, WrappingMode,
 };
 use phantom_dependencies::{
-    anyhow::{Context, Result},
+    anyhow::{bail, Context, Result},
     gltf::{self, animation::util::ReadOutputs},
     legion::EntityStore,
     nalgebra_glm as glm,
     petgraph::prelude::*,
+    serde::Deserialize,
+    serde_json::{self, Value},
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn create_scene_graph(node: &gltf::Node, ecs: &mut Ecs, entities: &[Entity]) -> SceneGraph {
     let mut node_graph = SceneGraph::new();
@@ -48,32 +50,43 @@ fn node_transform(node: &gltf::Node) -> Transform {
 
 const DEFAULT_NAME: &str = "<Unnamed>";
 
-pub fn load_gltf(path: impl AsRef<Path>, world: &mut World) -> Result<()> {
-    let (gltf, buffers, images) = gltf::import(path)?;
+const MESH_QUANTIZATION: &str = "KHR_mesh_quantization";
+pub(crate) const DRACO_MESH_COMPRESSION: &str = "KHR_draco_mesh_compression";
 
-    let number_of_materials = world.materials.len();
+pub fn load_gltf(path: impl AsRef<Path>, world: &mut World) -> Result<()> {
+    let (gltf, buffers, images) = import(path.as_ref())?;
 
-    let number_of_textures = world.textures.len();
-    let mut materials = load_materials(&gltf)?;
-    materials.iter_mut().for_each(|material| {
-        let increment = |value: &mut i32| {
-            if *value != -1_i32 {
-                *value += number_of_textures as i32;
-            }
-        };
-        increment(&mut material.color_texture_index);
-        increment(&mut material.metallic_roughness_texture_index);
-        increment(&mut material.normal_texture_index);
-        increment(&mut material.occlusion_texture_index);
-        increment(&mut material.emissive_texture_index);
-    });
-    materials
+    // Textures, materials and meshes identical to ones already in the world are shared
+    let texture_indices = load_textures(&gltf, &images)?
         .into_iter()
-        .for_each(|material| world.materials.push(material));
+        .map(|texture| {
+            world
+                .content_hashes
+                .insert_texture(&mut world.textures, texture)
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    load_textures(&gltf, &images)?
+    let material_indices = load_materials(&gltf)?
         .into_iter()
-        .for_each(|texture| world.textures.push(texture));
+        .map(|mut material| {
+            let remap = |value: &mut i32| {
+                if *value != -1_i32 {
+                    *value = texture_indices
+                        .get(*value as usize)
+                        .map_or(-1, |index| *index as i32);
+                }
+            };
+            remap(&mut material.color_texture_index);
+            remap(&mut material.metallic_roughness_texture_index);
+            remap(&mut material.normal_texture_index);
+            remap(&mut material.occlusion_texture_index);
+            remap(&mut material.emissive_texture_index);
+            remap(&mut material.transmission_texture_index);
+            world
+                .content_hashes
+                .insert_material(&mut world.materials, material)
+        })
+        .collect::<Result<Vec<_>>>()?;
 
     let entities = world
         .ecs
@@ -84,23 +97,7 @@ pub fn load_gltf(path: impl AsRef<Path>, world: &mut World) -> Result<()> {
         .into_iter()
         .for_each(|node| world.animations.push(node));
 
-    load_nodes(
-        &gltf,
-        &buffers,
-        &mut world.ecs,
-        &mut world.geometry,
-        &entities,
-    )?;
-
-    for entity in entities.iter() {
-        if let Ok(mesh) = world.ecs.entry_mut(*entity)?.get_component_mut::<Mesh>() {
-            mesh.primitives.iter_mut().for_each(|primitive| {
-                if let Some(material_index) = primitive.material_index.as_mut() {
-                    *material_index += number_of_materials
-                }
-            })
-        }
-    }
+    load_nodes(&gltf, &buffers, world, &entities, &material_indices)?;
 
     // Only merge default scene
     let new_scenes = load_scenes(&gltf, &mut world.ecs, &entities);
@@ -110,9 +107,86 @@ pub fn load_gltf(path: impl AsRef<Path>, world: &mut World) -> Result<()> {
         });
     }
 
+    world.mark_textures_changed();
+    world.mark_materials_changed();
+    world.mark_geometry_changed();
+
     Ok(())
 }
 
+/// Reads a glTF file like `gltf::import`, but first drops the required mesh extensions
+/// the importer decodes itself, so validation doesn't reject files that use them.
+/// Draco compressed primitives are decoded with the `draco` feature. Without it, files that
+/// use Draco without requiring it are read from the uncompressed copies in their accessors.
+fn import(
+    path: &Path,
+) -> Result<(
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<gltf::image::Data>,
+)> {
+    let bytes = std::fs::read(path)?;
+    let (mut json, blob) = if bytes.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(&bytes)?;
+        (
+            serde_json::from_slice::<Value>(&glb.json)?,
+            glb.bin.map(|bin| bin.into_owned()),
+        )
+    } else {
+        (serde_json::from_slice::<Value>(&bytes)?, None)
+    };
+    if let Some(required) = json
+        .get_mut("extensionsRequired")
+        .and_then(Value::as_array_mut)
+    {
+        if !cfg!(feature = "draco")
+            && required
+                .iter()
+                .any(|extension| extension.as_str() == Some(DRACO_MESH_COMPRESSION))
+        {
+            bail!(
+                "{} requires {}, which is only decoded when phantom is built with the `draco` feature. \
+                 Enable it, or decompress the model before importing it.",
+                path.display(),
+                DRACO_MESH_COMPRESSION
+            );
+        }
+        required.retain(|extension| {
+            !matches!(
+                extension.as_str(),
+                Some(MESH_QUANTIZATION) | Some(DRACO_MESH_COMPRESSION)
+            )
+        });
+    }
+    let document = gltf::Document::from_json(gltf::json::Root::deserialize(&json)?)?;
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let buffers = gltf::import_buffers(&document, Some(base), blob)?;
+    let images = gltf::import_images(&document, Some(base), &buffers)?;
+    #[cfg(feature = "draco")]
+    let (document, buffers) = crate::draco::decode_primitives(&mut json, document, buffers)?;
+    Ok((document, buffers, images))
+}
+
+/// The external buffers and images a glTF file references, relative to its directory.
+/// Binary glTF files and embedded data uris have nothing to depend on.
+pub fn gltf_dependencies(path: impl AsRef<Path>, bytes: &[u8]) -> Result<Vec<PathBuf>> {
+    let gltf = gltf::Gltf::from_slice(bytes)?;
+    let directory = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+    let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => Some(uri),
+        gltf::buffer::Source::Bin => None,
+    });
+    let image_uris = gltf.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+    Ok(buffer_uris
+        .chain(image_uris)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| directory.join(uri.replace("%20", " ")))
+        .collect())
+}
+
 fn load_samplers(document: &gltf::Document) -> Vec<Sampler> {
     document.samplers().map(map_gltf_sampler).collect()
 }
@@ -219,9 +293,29 @@ fn load_material(primitive_material: &gltf::Material) -> Result<Material> {
     material.alpha_mode = map_gltf_alpha_mode(&primitive_material.alpha_mode());
     material.alpha_cutoff = primitive_material.alpha_cutoff().unwrap_or(0.5);
     material.is_unlit = primitive_material.unlit();
+    material.emissive_strength = primitive_material.emissive_strength().unwrap_or(1.0);
+    if let Some(transmission) = primitive_material.transmission() {
+        material.transmission_factor = transmission.transmission_factor();
+        if let Some(transmission_texture) = transmission.transmission_texture() {
+            material.transmission_texture_index = transmission_texture.texture().index() as i32;
+            material.transmission_texture_set = transmission_texture.tex_coord() as i32;
+        }
+    }
     if let Some(base_color_texture) = pbr.base_color_texture() {
         material.color_texture_index = base_color_texture.texture().index() as i32;
         material.color_texture_set = base_color_texture.tex_coord() as i32;
+
+        // The renderer transforms every texture the same way, so the base color's transform is used
+        if let Some(transform) = base_color_texture.texture_transform() {
+            material.texture_transform = TextureTransform {
+                offset: glm::Vec2::from(transform.offset()),
+                rotation: transform.rotation(),
+                scale: glm::Vec2::from(transform.scale()),
+            };
+            if let Some(tex_coord) = transform.tex_coord() {
+                material.color_texture_set = tex_coord as i32;
+            }
+        }
     }
     if let Some(metallic_roughness_texture) = pbr.metallic_roughness_texture() {
         material.metallic_roughness_texture_index =
@@ -269,10 +363,16 @@ fn load_scenes(gltf: &gltf::Document, ecs: &mut Ecs, entities: &[Entity]) -> Vec
 fn load_nodes(
     gltf: &gltf::Document,
     buffers: &[gltf::buffer::Data],
-    ecs: &mut Ecs,
-    geometry: &mut Geometry,
+    world: &mut World,
     entities: &[Entity],
+    material_indices: &[usize],
 ) -> Result<()> {
+    let World {
+        ecs,
+        geometry,
+        content_hashes,
+        ..
+    } = world;
     for (index, node) in gltf.nodes().enumerate() {
         let entity = entities[index];
 
@@ -289,16 +389,18 @@ fn load_nodes(
         }
 
         if let Some(gltf_mesh) = node.mesh() {
-            let mesh = load_mesh(&gltf_mesh, buffers, geometry)?;
-            let name = if geometry.meshes.contains_key(&mesh.name) {
-                // FIXME: increment a repeated name with a number
-                //        instead of just adding an underscore
-                let name = mesh.name.to_string();
-                name + "_"
-            } else {
-                mesh.name.to_string()
-            };
-            geometry.meshes.insert(name.clone(), mesh);
+            let (first_vertex, first_index) = (geometry.vertices.len(), geometry.indices.len());
+            let mesh = load_mesh(&gltf_mesh, buffers, geometry, material_indices)?;
+            let number_of_morph_targets = mesh.number_of_morph_targets();
+            if number_of_morph_targets > 0 {
+                // Nodes can override the default weights of their mesh
+                let mut weights = node
+                    .weights()
+                    .map_or_else(|| mesh.weights.clone(), <[f32]>::to_vec);
+                weights.resize(number_of_morph_targets, 0.0);
+                entry.add_component(MorphWeights(weights));
+            }
+            let name = content_hashes.insert_mesh(geometry, mesh, first_vertex, first_index)?;
             entry.add_component(MeshRender { name });
         }
 
@@ -344,10 +446,11 @@ fn load_mesh(
     mesh: &gltf::Mesh,
     buffers: &[gltf::buffer::Data],
     geometry: &mut Geometry,
+    material_indices: &[usize],
 ) -> Result<Mesh> {
     let primitives = mesh
         .primitives()
-        .map(|primitive| load_primitive(&primitive, buffers, geometry))
+        .map(|primitive| load_primitive(&primitive, buffers, geometry, material_indices))
         .collect::<Result<Vec<_>>>()?;
     let weights = match mesh.weights() {
         Some(weights) => weights.to_vec(),
@@ -364,6 +467,7 @@ fn load_primitive(
     primitive: &gltf::Primitive,
     buffers: &[gltf::buffer::Data],
     geometry: &mut Geometry,
+    material_indices: &[usize],
 ) -> Result<Primitive> {
     // Indices must be loaded before vertices in this case
     // because the number of vertices is used to offset indices
@@ -371,11 +475,10 @@ fn load_primitive(
     let first_vertex = geometry.vertices.len();
     let number_of_indices = load_primitive_indices(primitive, buffers, geometry)?;
     let number_of_vertices = load_primitive_vertices(primitive, buffers, geometry)?;
-    let bounding_box = primitive.bounding_box();
     let morph_targets = load_morph_targets(primitive, buffers)?;
-    let bounding_box = BoundingBox::new(
-        glm::Vec3::from(bounding_box.min),
-        glm::Vec3::from(bounding_box.max),
+    let bounding_box = primitive_bounding_box(
+        &geometry.vertices[first_vertex..first_vertex + number_of_vertices],
+        &morph_targets,
     );
     Ok(Primitive {
         first_index,
@@ -383,11 +486,32 @@ fn load_primitive(
         number_of_indices,
         number_of_vertices,
         morph_targets,
-        material_index: primitive.material().index(),
+        material_index: primitive
+            .material()
+            .index()
+            .and_then(|index| material_indices.get(index).copied()),
         bounding_box,
     })
 }
 
+/// Computed from the vertices rather than trusting the accessor bounds,
+/// and grown to contain every morph target at full weight so culling never clips them
+fn primitive_bounding_box(vertices: &[Vertex], morph_targets: &[MorphTarget]) -> BoundingBox {
+    let mut bounding_box = BoundingBox::new_invalid();
+    for (index, vertex) in vertices.iter().enumerate() {
+        bounding_box.fit_point(vertex.position);
+        for morph_target in morph_targets.iter() {
+            if let Some(displacement) = morph_target.positions.get(index) {
+                bounding_box.fit_point(vertex.position + displacement.xyz());
+            }
+        }
+    }
+    if vertices.is_empty() {
+        bounding_box = BoundingBox::new(glm::Vec3::zeros(), glm::Vec3::zeros());
+    }
+    bounding_box
+}
+
 fn load_primitive_vertices(
     primitive: &gltf::Primitive,
     buffers: &[gltf::buffer::Data],
@@ -395,30 +519,48 @@ fn load_primitive_vertices(
 ) -> Result<usize> {
     let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
-    let mut positions = Vec::new();
-
-    let read_positions = reader.read_positions().context(
+    let positions = primitive.get(&gltf::Semantic::Positions).context(
         "Failed to read vertex positions from the model. Vertex positions are required.",
     )?;
-    for position in read_positions {
-        positions.push(glm::Vec3::from(position));
+    if positions.view().is_none() && positions.sparse().is_none() {
+        // Draco files that don't require the extension keep an uncompressed copy in the accessors
+        bail!(
+            "A primitive's vertices are only stored with {}, which is only decoded \
+             when phantom is built with the `draco` feature.",
+            DRACO_MESH_COMPRESSION
+        );
     }
+    let positions = read_floats::<3>(&positions, buffers)?
+        .into_iter()
+        .map(glm::Vec3::from)
+        .collect::<Vec<_>>();
     let number_of_vertices = positions.len();
 
-    let normals = reader.read_normals().map_or(
+    let read_attribute = |semantic: gltf::Semantic| {
+        primitive
+            .get(&semantic)
+            .map(|accessor| read_floats::<4>(&accessor, buffers))
+            .transpose()
+    };
+
+    let normals = read_attribute(gltf::Semantic::Normals)?.map_or(
         vec![glm::vec3(0.0, 0.0, 0.0); number_of_vertices],
-        |normals| normals.map(glm::Vec3::from).collect::<Vec<_>>(),
+        |normals| {
+            normals
+                .iter()
+                .map(|normal| glm::make_vec3(normal))
+                .collect()
+        },
     );
 
-    let map_to_vec2 = |coords: gltf::mesh::util::ReadTexCoords<'_>| -> Vec<glm::Vec2> {
-        coords.into_f32().map(glm::Vec2::from).collect::<Vec<_>>()
-    };
-    let uv_0 = reader
-        .read_tex_coords(0)
-        .map_or(vec![glm::vec2(0.0, 0.0); number_of_vertices], map_to_vec2);
-    let uv_1 = reader
-        .read_tex_coords(1)
-        .map_or(vec![glm::vec2(0.0, 0.0); number_of_vertices], map_to_vec2);
+    let uv_0 = read_attribute(gltf::Semantic::TexCoords(0))?
+        .map_or(vec![glm::vec2(0.0, 0.0); number_of_vertices], |coords| {
+            coords.iter().map(|coord| glm::make_vec2(coord)).collect()
+        });
+    let uv_1 = read_attribute(gltf::Semantic::TexCoords(1))?
+        .map_or(vec![glm::vec2(0.0, 0.0); number_of_vertices], |coords| {
+            coords.iter().map(|coord| glm::make_vec2(coord)).collect()
+        });
 
     let convert_joints = |joints: gltf::mesh::util::ReadJoints<'_>| -> Vec<glm::Vec4> {
         joints
@@ -432,25 +574,14 @@ fn load_primitive_vertices(
         convert_joints,
     );
 
-    let convert_weights = |weights: gltf::mesh::util::ReadWeights<'_>| -> Vec<glm::Vec4> {
-        weights.into_f32().map(glm::Vec4::from).collect::<Vec<_>>()
-    };
-
-    let weights_0 = reader.read_weights(0).map_or(
+    let weights_0 = read_attribute(gltf::Semantic::Weights(0))?.map_or(
         vec![glm::vec4(1.0, 0.0, 0.0, 0.0); number_of_vertices],
-        convert_weights,
+        |weights| weights.into_iter().map(glm::Vec4::from).collect(),
     );
 
-    let convert_colors = |colors: gltf::mesh::util::ReadColors<'_>| -> Vec<glm::Vec3> {
-        colors
-            .into_rgb_f32()
-            .map(glm::Vec3::from)
-            .collect::<Vec<_>>()
-    };
-
-    let colors_0 = reader.read_colors(0).map_or(
+    let colors_0 = read_attribute(gltf::Semantic::Colors(0))?.map_or(
         vec![glm::vec3(1.0, 1.0, 1.0); number_of_vertices],
-        convert_colors,
+        |colors| colors.iter().map(|color| glm::make_vec3(color)).collect(),
     );
 
     for (index, position) in positions.into_iter().enumerate() {
@@ -492,45 +623,120 @@ fn load_morph_targets(
     primitive: &gltf::Primitive,
     buffers: &[gltf::buffer::Data],
 ) -> Result<Vec<MorphTarget>> {
-    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-
-    let mut morph_targets = Vec::new();
-    for (mut position_displacements, mut normal_displacements, mut tangent_displacements) in
-        reader.read_morph_targets()
-    {
-        let positions = match position_displacements.as_mut() {
-            Some(position_displacements) => position_displacements
-                .map(glm::Vec3::from)
-                .map(|v| glm::vec3_to_vec4(&v))
-                .collect::<Vec<_>>(),
+    let read_displacements = |accessor: Option<gltf::Accessor>| -> Result<Vec<glm::Vec4>> {
+        Ok(match accessor {
+            Some(accessor) => read_floats::<3>(&accessor, buffers)?
+                .into_iter()
+                .map(|displacement| glm::vec3_to_vec4(&glm::Vec3::from(displacement)))
+                .collect(),
             None => Vec::new(),
-        };
+        })
+    };
+    primitive
+        .morph_targets()
+        .map(|morph_target| {
+            Ok(MorphTarget {
+                positions: read_displacements(morph_target.positions())?,
+                normals: read_displacements(morph_target.normals())?,
+                tangents: read_displacements(morph_target.tangents())?,
+            })
+        })
+        .collect()
+}
 
-        let normals = match normal_displacements.as_mut() {
-            Some(normal_displacements) => normal_displacements
-                .map(glm::Vec3::from)
-                .map(|v| glm::vec3_to_vec4(&v))
-                .collect::<Vec<_>>(),
-            None => Vec::new(),
-        };
+/// Reads a float attribute whatever its component type, since `KHR_mesh_quantization`
+/// stores positions, normals, texture coordinates and displacements as normalized or plain integers.
+/// Components the accessor doesn't have are left as zero.
+fn read_floats<const N: usize>(
+    accessor: &gltf::Accessor,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<[f32; N]>> {
+    let data_type = accessor.data_type();
+    let normalized = accessor.normalized();
+    let component_size = component_size(data_type);
+    let dimensions = accessor.dimensions().multiplicity();
+    let read_element = |bytes: &[u8], element: &mut [f32; N]| {
+        for (component, value) in element.iter_mut().enumerate().take(dimensions) {
+            let offset = component * component_size;
+            *value = read_component(
+                data_type,
+                normalized,
+                &bytes[offset..offset + component_size],
+            );
+        }
+    };
+    let element_bytes = |view: &gltf::buffer::View, offset: usize, index: usize| {
+        let stride = view.stride().unwrap_or(component_size * dimensions);
+        let start = view.offset() + offset + index * stride;
+        buffers[view.buffer().index()]
+            .get(start..start + component_size * dimensions)
+            .context("An accessor reads past the end of its buffer!")
+    };
 
-        let tangents = match tangent_displacements.as_mut() {
-            Some(tangent_displacements) => tangent_displacements
-                .map(glm::Vec3::from)
-                .map(|v| glm::vec3_to_vec4(&v))
-                .collect::<Vec<_>>(),
-            None => Vec::new(),
-        };
+    let mut elements = vec![[0.0; N]; accessor.count()];
+    if let Some(view) = accessor.view() {
+        for (index, element) in elements.iter_mut().enumerate() {
+            read_element(element_bytes(&view, accessor.offset(), index)?, element);
+        }
+    }
 
-        let morph_target = MorphTarget {
-            positions,
-            normals,
-            tangents,
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_size = match indices.index_type() {
+            gltf::accessor::sparse::IndexType::U8 => 1,
+            gltf::accessor::sparse::IndexType::U16 => 2,
+            gltf::accessor::sparse::IndexType::U32 => 4,
         };
-        morph_targets.push(morph_target);
+        let indices_view = indices.view();
+        let index_bytes = &buffers[indices_view.buffer().index()];
+        let values = sparse.values();
+        for sparse_index in 0..sparse.count() {
+            let start = indices_view.offset() + indices.offset() + sparse_index * index_size;
+            let bytes = index_bytes
+                .get(start..start + index_size)
+                .context("A sparse accessor reads past the end of its buffer!")?;
+            let index = bytes
+                .iter()
+                .rev()
+                .fold(0_usize, |index, byte| (index << 8) | *byte as usize);
+            let element = elements
+                .get_mut(index)
+                .context("A sparse accessor replaces an element that doesn't exist!")?;
+            read_element(
+                element_bytes(&values.view(), values.offset(), sparse_index)?,
+                element,
+            );
+        }
     }
 
-    Ok(morph_targets)
+    Ok(elements)
+}
+
+fn component_size(data_type: gltf::accessor::DataType) -> usize {
+    match data_type {
+        gltf::accessor::DataType::I8 | gltf::accessor::DataType::U8 => 1,
+        gltf::accessor::DataType::I16 | gltf::accessor::DataType::U16 => 2,
+        gltf::accessor::DataType::U32 | gltf::accessor::DataType::F32 => 4,
+    }
+}
+
+/// Normalized integers are mapped to [0, 1] or [-1, 1] as the glTF specification defines
+fn read_component(data_type: gltf::accessor::DataType, normalized: bool, bytes: &[u8]) -> f32 {
+    use gltf::accessor::DataType;
+    match (data_type, normalized) {
+        (DataType::I8, true) => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+        (DataType::I8, false) => bytes[0] as i8 as f32,
+        (DataType::U8, true) => bytes[0] as f32 / 255.0,
+        (DataType::U8, false) => bytes[0] as f32,
+        (DataType::I16, true) => {
+            (i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32767.0).max(-1.0)
+        }
+        (DataType::I16, false) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        (DataType::U16, true) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+        (DataType::U16, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        (DataType::U32, _) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        (DataType::F32, _) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
 }
 
 fn load_animations(
@@ -598,6 +804,7 @@ fn load_animations(
             time: 0.0,
             max_animation_time,
             name,
+            root_motion: None,
         });
     }
     Ok(animations)
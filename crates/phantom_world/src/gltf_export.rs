@@ -0,0 +1,796 @@
+use crate::{
+    AlphaMode, Camera, Entity, Filter, Format, Interpolation, Light, LightKind, Material,
+    MeshRender, MorphWeights, Name, Projection, Sampler, Skin, Texture, TextureTransform,
+    Transform, TransformationSet, World, WrappingMode,
+};
+use phantom_dependencies::{
+    anyhow::{bail, Result},
+    image::{DynamicImage, ImageOutputFormat, RgbaImage},
+    legion::EntityStore,
+    log, nalgebra_glm as glm,
+    serde_json::{self, json, Value},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    path::Path,
+};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// Collects the binary chunk of a .glb along with the buffer views and accessors into it
+#[derive(Default)]
+struct GlbBuffer {
+    bytes: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GlbBuffer {
+    fn view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        // Accessors must start on a multiple of their component size
+        while self.bytes.len() % 4 != 0 {
+            self.bytes.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bytes.len(),
+            "byteLength": data.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bytes.extend_from_slice(data);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    /// Adds `components` floats per element. Bounds are required for positions and animation times.
+    fn floats(
+        &mut self,
+        data: &[f32],
+        kind: &str,
+        components: usize,
+        target: Option<u32>,
+        bounds: bool,
+    ) -> usize {
+        let bytes = data
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        let view = self.view(&bytes, target);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": data.len() / components,
+            "type": kind,
+        });
+        if bounds {
+            let mut min = vec![f32::MAX; components];
+            let mut max = vec![f32::MIN; components];
+            for element in data.chunks(components) {
+                for (component, value) in element.iter().enumerate() {
+                    min[component] = min[component].min(*value);
+                    max[component] = max[component].max(*value);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn vec3s<'a>(&mut self, data: impl Iterator<Item = &'a [f32]>, bounds: bool) -> usize {
+        let data = data
+            .flat_map(|value| value[..3].to_vec())
+            .collect::<Vec<_>>();
+        self.floats(&data, "VEC3", 3, Some(ARRAY_BUFFER), bounds)
+    }
+
+    fn indices(&mut self, indices: &[u32]) -> usize {
+        let bytes = indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect::<Vec<_>>();
+        let view = self.view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn joints(&mut self, joints: &[[u16; 4]]) -> usize {
+        let bytes = joints
+            .iter()
+            .flat_map(|joint| joint.iter().flat_map(|index| index.to_le_bytes()))
+            .collect::<Vec<_>>();
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_SHORT,
+            "count": joints.len(),
+            "type": "VEC4",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+impl World {
+    /// Writes the scene's nodes, meshes, materials, textures, skins and animations to a .glb file
+    pub fn export_gltf(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.as_glb_bytes()?)?;
+        Ok(())
+    }
+
+    /// The scene as a binary glTF file
+    pub fn as_glb_bytes(&self) -> Result<Vec<u8>> {
+        GltfExport::new(self).glb()
+    }
+}
+
+struct GltfExport<'a> {
+    world: &'a World,
+    buffer: GlbBuffer,
+    nodes: HashMap<Entity, usize>,
+
+    /// Exported glTF texture indices by world texture index, for textures that could be encoded
+    textures: Vec<Option<usize>>,
+    extensions_used: HashSet<&'static str>,
+}
+
+impl<'a> GltfExport<'a> {
+    fn new(world: &'a World) -> Self {
+        Self {
+            world,
+            buffer: GlbBuffer::default(),
+            nodes: HashMap::new(),
+            textures: Vec::new(),
+            extensions_used: HashSet::new(),
+        }
+    }
+
+    fn glb(mut self) -> Result<Vec<u8>> {
+        let world = self.world;
+        let mut root = json!({
+            "asset": { "version": "2.0", "generator": "Phantom" },
+        });
+
+        // Every entity in a scenegraph becomes a node, numbered before any are written
+        // so children, joints and animation targets can refer to them
+        let mut entities = Vec::new();
+        let mut scene_nodes = Vec::new();
+        for graph in world.scene.graphs.iter() {
+            for index in graph.root_nodes() {
+                scene_nodes.push(entities.len());
+                for node_index in graph.subtree(index) {
+                    self.nodes.insert(graph[node_index], entities.len());
+                    entities.push((graph[node_index], graph.children(node_index)));
+                }
+            }
+        }
+        let graph_of = |entity: Entity| {
+            world
+                .scene
+                .graphs
+                .iter()
+                .find(|graph| graph.find_node(entity).is_some())
+        };
+
+        let (images, samplers, textures) = self.textures();
+        let materials = world
+            .materials
+            .iter()
+            .map(|material| self.material(material))
+            .collect::<Vec<_>>();
+
+        let skinned_meshes = entities
+            .iter()
+            .filter_map(|(entity, _)| {
+                let entry = world.ecs.entry_ref(*entity).ok()?;
+                entry.get_component::<Skin>().ok()?;
+                Some(entry.get_component::<MeshRender>().ok()?.name.to_string())
+            })
+            .collect::<HashSet<_>>();
+
+        let mut nodes = Vec::new();
+        let mut meshes = Vec::new();
+        let mut mesh_indices = HashMap::new();
+        let mut cameras = Vec::new();
+        let mut lights = Vec::new();
+        let mut skins = Vec::new();
+        for (entity, children) in entities.iter() {
+            let entry = world.ecs.entry_ref(*entity)?;
+            let transform = entry
+                .get_component::<Transform>()
+                .map(|transform| *transform)
+                .unwrap_or_default();
+            let mut node_json = json!({
+                "translation": transform.translation.as_slice(),
+                "rotation": transform.rotation.coords.as_slice(),
+                "scale": transform.scale.as_slice(),
+            });
+            if let Ok(name) = entry.get_component::<Name>() {
+                node_json["name"] = json!(name.0);
+            }
+            let graph = graph_of(*entity);
+            let node_children = children
+                .iter()
+                .filter_map(|index| self.nodes.get(&graph?[*index]).copied())
+                .collect::<Vec<_>>();
+            if !node_children.is_empty() {
+                node_json["children"] = json!(node_children);
+            }
+
+            if let Ok(mesh_render) = entry.get_component::<MeshRender>() {
+                let mesh_index = match mesh_indices.get(&mesh_render.name) {
+                    Some(index) => Some(*index),
+                    None => match self.mesh(
+                        &mesh_render.name,
+                        skinned_meshes.contains(&mesh_render.name),
+                    ) {
+                        Some(mesh) => {
+                            meshes.push(mesh);
+                            mesh_indices.insert(mesh_render.name.to_string(), meshes.len() - 1);
+                            Some(meshes.len() - 1)
+                        }
+                        None => None,
+                    },
+                };
+                if let Some(mesh_index) = mesh_index {
+                    node_json["mesh"] = json!(mesh_index);
+                    if let Ok(morph_weights) = entry.get_component::<MorphWeights>() {
+                        node_json["weights"] = json!(morph_weights.0);
+                    }
+                }
+            }
+            if let Ok(camera) = entry.get_component::<Camera>() {
+                cameras.push(camera_json(camera));
+                node_json["camera"] = json!(cameras.len() - 1);
+            }
+            if let Ok(light) = entry.get_component::<Light>() {
+                lights.push(light_json(light));
+                node_json["extensions"] =
+                    json!({ "KHR_lights_punctual": { "light": lights.len() - 1 } });
+                self.extensions_used.insert("KHR_lights_punctual");
+            }
+            if let Ok(skin) = entry.get_component::<Skin>() {
+                if let Some(skin) = self.skin(skin) {
+                    skins.push(skin);
+                    node_json["skin"] = json!(skins.len() - 1);
+                }
+            }
+            nodes.push(node_json);
+        }
+
+        let animations = world
+            .animations
+            .iter()
+            .filter_map(|animation| self.animation(animation))
+            .collect::<Vec<_>>();
+
+        let mut arrays = vec![
+            ("scenes", vec![json!({ "nodes": scene_nodes })]),
+            ("nodes", nodes),
+            ("meshes", meshes),
+            ("materials", materials),
+            ("textures", textures),
+            ("images", images),
+            ("samplers", samplers),
+            ("cameras", cameras),
+            ("skins", skins),
+            ("animations", animations),
+        ];
+        if !self.buffer.bytes.is_empty() {
+            arrays.push((
+                "buffers",
+                vec![json!({ "byteLength": self.buffer.bytes.len() })],
+            ));
+            arrays.push(("bufferViews", std::mem::take(&mut self.buffer.views)));
+            arrays.push(("accessors", std::mem::take(&mut self.buffer.accessors)));
+        }
+        for (name, array) in arrays {
+            if !array.is_empty() {
+                root[name] = json!(array);
+            }
+        }
+        root["scene"] = json!(0);
+        if !lights.is_empty() {
+            root["extensions"] = json!({ "KHR_lights_punctual": { "lights": lights } });
+        }
+        if !self.extensions_used.is_empty() {
+            let mut extensions_used = self.extensions_used.iter().collect::<Vec<_>>();
+            extensions_used.sort();
+            root["extensionsUsed"] = json!(extensions_used);
+        }
+
+        Ok(pack_glb(serde_json::to_vec(&root)?, self.buffer.bytes))
+    }
+
+    fn textures(&mut self) -> (Vec<Value>, Vec<Value>, Vec<Value>) {
+        let (mut images, mut samplers, mut textures) = (Vec::new(), Vec::new(), Vec::new());
+        for (index, texture) in self.world.textures.iter().enumerate() {
+            let png = match texture_png(texture) {
+                Ok(png) => png,
+                Err(error) => {
+                    log::warn!("Texture {} won't be exported: {}", index, error);
+                    self.textures.push(None);
+                    continue;
+                }
+            };
+            let view = self.buffer.view(&png, None);
+            images.push(json!({ "bufferView": view, "mimeType": "image/png" }));
+            samplers.push(sampler_json(&texture.sampler));
+            textures.push(json!({ "source": images.len() - 1, "sampler": samplers.len() - 1 }));
+            self.textures.push(Some(textures.len() - 1));
+        }
+        (images, samplers, textures)
+    }
+
+    fn texture_info(
+        &mut self,
+        index: i32,
+        set: i32,
+        transform: &TextureTransform,
+    ) -> Option<Value> {
+        let texture = (*self.textures.get(usize::try_from(index).ok()?)?)?;
+        let mut info = json!({ "index": texture, "texCoord": set.max(0) });
+        if *transform != TextureTransform::default() {
+            info["extensions"] = json!({
+                "KHR_texture_transform": {
+                    "offset": transform.offset.as_slice(),
+                    "rotation": transform.rotation,
+                    "scale": transform.scale.as_slice(),
+                }
+            });
+            self.extensions_used.insert("KHR_texture_transform");
+        }
+        Some(info)
+    }
+
+    fn material(&mut self, material: &Material) -> Value {
+        let mut pbr = json!({
+            "baseColorFactor": material.base_color_factor.as_slice(),
+            "metallicFactor": material.metallic_factor,
+            "roughnessFactor": material.roughness_factor,
+        });
+        if let Some(info) = self.texture_info(
+            material.color_texture_index,
+            material.color_texture_set,
+            &material.texture_transform,
+        ) {
+            pbr["baseColorTexture"] = info;
+        }
+        if let Some(info) = self.texture_info(
+            material.metallic_roughness_texture_index,
+            material.metallic_roughness_texture_set,
+            &material.texture_transform,
+        ) {
+            pbr["metallicRoughnessTexture"] = info;
+        }
+
+        let mut material_json = json!({
+            "name": material.name,
+            "pbrMetallicRoughness": pbr,
+            "emissiveFactor": material.emissive_factor.as_slice(),
+            "alphaMode": match material.alpha_mode {
+                AlphaMode::Opaque => "OPAQUE",
+                AlphaMode::Mask => "MASK",
+                AlphaMode::Blend => "BLEND",
+            },
+        });
+        if material.alpha_mode == AlphaMode::Mask {
+            material_json["alphaCutoff"] = json!(material.alpha_cutoff);
+        }
+        if let Some(mut info) = self.texture_info(
+            material.normal_texture_index,
+            material.normal_texture_set,
+            &material.texture_transform,
+        ) {
+            info["scale"] = json!(material.normal_texture_scale);
+            material_json["normalTexture"] = info;
+        }
+        if let Some(mut info) = self.texture_info(
+            material.occlusion_texture_index,
+            material.occlusion_texture_set,
+            &material.texture_transform,
+        ) {
+            info["strength"] = json!(material.occlusion_strength);
+            material_json["occlusionTexture"] = info;
+        }
+        if let Some(info) = self.texture_info(
+            material.emissive_texture_index,
+            material.emissive_texture_set,
+            &material.texture_transform,
+        ) {
+            material_json["emissiveTexture"] = info;
+        }
+        let mut extensions = serde_json::Map::new();
+        if material.is_unlit {
+            extensions.insert("KHR_materials_unlit".to_string(), json!({}));
+            self.extensions_used.insert("KHR_materials_unlit");
+        }
+        if material.emissive_strength != 1.0 {
+            extensions.insert(
+                "KHR_materials_emissive_strength".to_string(),
+                json!({ "emissiveStrength": material.emissive_strength }),
+            );
+            self.extensions_used
+                .insert("KHR_materials_emissive_strength");
+        }
+        if material.transmission_factor > 0.0 {
+            let mut transmission = json!({ "transmissionFactor": material.transmission_factor });
+            if let Some(info) = self.texture_info(
+                material.transmission_texture_index,
+                material.transmission_texture_set,
+                &material.texture_transform,
+            ) {
+                transmission["transmissionTexture"] = info;
+            }
+            extensions.insert("KHR_materials_transmission".to_string(), transmission);
+            self.extensions_used.insert("KHR_materials_transmission");
+        }
+        if !extensions.is_empty() {
+            material_json["extensions"] = Value::Object(extensions);
+        }
+        material_json
+    }
+
+    fn mesh(&mut self, name: &str, skinned: bool) -> Option<Value> {
+        let geometry = &self.world.geometry;
+        let mesh = geometry.meshes.get(name)?;
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives.iter() {
+            let vertices = geometry.vertices.get(
+                primitive.first_vertex..primitive.first_vertex + primitive.number_of_vertices,
+            )?;
+            let buffer = &mut self.buffer;
+            let mut attributes = json!({
+                "POSITION": buffer.vec3s(vertices.iter().map(|vertex| vertex.position.as_slice()), true),
+                "NORMAL": buffer.vec3s(vertices.iter().map(|vertex| vertex.normal.as_slice()), false),
+            });
+            for (set, uvs) in [
+                vertices
+                    .iter()
+                    .map(|vertex| vertex.uv_0)
+                    .collect::<Vec<_>>(),
+                vertices
+                    .iter()
+                    .map(|vertex| vertex.uv_1)
+                    .collect::<Vec<_>>(),
+            ]
+            .iter()
+            .enumerate()
+            {
+                let data = uvs.iter().flat_map(|uv| [uv.x, uv.y]).collect::<Vec<_>>();
+                attributes[format!("TEXCOORD_{}", set)] =
+                    json!(buffer.floats(&data, "VEC2", 2, Some(ARRAY_BUFFER), false));
+            }
+            if skinned {
+                let joints = vertices
+                    .iter()
+                    .map(|vertex| {
+                        let joint = vertex.joint_0;
+                        [
+                            joint.x as u16,
+                            joint.y as u16,
+                            joint.z as u16,
+                            joint.w as u16,
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                attributes["JOINTS_0"] = json!(buffer.joints(&joints));
+                let weights = vertices
+                    .iter()
+                    .flat_map(|vertex| vertex.weight_0.as_slice().to_vec())
+                    .collect::<Vec<_>>();
+                attributes["WEIGHTS_0"] =
+                    json!(buffer.floats(&weights, "VEC4", 4, Some(ARRAY_BUFFER), false));
+            }
+
+            // White vertex colors are what the importer assumes when there are none
+            let white = glm::vec3(1.0, 1.0, 1.0);
+            if vertices.iter().any(|vertex| vertex.color_0 != white) {
+                attributes["COLOR_0"] = json!(buffer.vec3s(
+                    vertices.iter().map(|vertex| vertex.color_0.as_slice()),
+                    false
+                ));
+            }
+
+            let mut primitive_json = json!({ "attributes": attributes });
+            if primitive.number_of_indices > 0 {
+                let indices = geometry
+                    .indices
+                    .get(
+                        primitive.first_index..primitive.first_index + primitive.number_of_indices,
+                    )?
+                    .iter()
+                    .map(|index| index - primitive.first_vertex as u32)
+                    .collect::<Vec<_>>();
+                primitive_json["indices"] = json!(buffer.indices(&indices));
+            }
+            if let Some(material) = primitive.material_index {
+                primitive_json["material"] = json!(material);
+            }
+            if !primitive.morph_targets.is_empty() {
+                let targets = primitive
+                    .morph_targets
+                    .iter()
+                    .map(|target| {
+                        let mut target_json = json!({});
+                        if !target.positions.is_empty() {
+                            target_json["POSITION"] = json!(buffer.vec3s(
+                                target.positions.iter().map(|position| position.as_slice()),
+                                true
+                            ));
+                        }
+                        if !target.normals.is_empty() {
+                            target_json["NORMAL"] = json!(buffer.vec3s(
+                                target.normals.iter().map(|normal| normal.as_slice()),
+                                false
+                            ));
+                        }
+                        target_json
+                    })
+                    .collect::<Vec<_>>();
+                primitive_json["targets"] = json!(targets);
+            }
+            primitives.push(primitive_json);
+        }
+
+        let mut mesh_json = json!({ "name": mesh.name, "primitives": primitives });
+        if !mesh.weights.is_empty() {
+            mesh_json["weights"] = json!(mesh.weights);
+        }
+        Some(mesh_json)
+    }
+
+    /// Skins are skipped if any of their joints aren't in a scenegraph
+    fn skin(&mut self, skin: &Skin) -> Option<Value> {
+        let joints = skin
+            .joints
+            .iter()
+            .map(|joint| self.nodes.get(&joint.target).copied())
+            .collect::<Option<Vec<_>>>()?;
+        let matrices = skin
+            .joints
+            .iter()
+            .flat_map(|joint| joint.inverse_bind_matrix.as_slice().to_vec())
+            .collect::<Vec<_>>();
+        let inverse_bind_matrices = self.buffer.floats(&matrices, "MAT4", 16, None, false);
+        Some(json!({
+            "name": skin.name,
+            "joints": joints,
+            "inverseBindMatrices": inverse_bind_matrices,
+        }))
+    }
+
+    fn animation(&mut self, animation: &crate::Animation) -> Option<Value> {
+        let mut samplers = Vec::new();
+        let mut channels = Vec::new();
+        for channel in animation.channels.iter() {
+            let node = match self.nodes.get(&channel.target) {
+                Some(node) => *node,
+                None => continue,
+            };
+            let (path, outputs, kind, components) = match &channel.transformations {
+                TransformationSet::Translations(translations) => (
+                    "translation",
+                    translations
+                        .iter()
+                        .flat_map(|v| v.as_slice().to_vec())
+                        .collect(),
+                    "VEC3",
+                    3,
+                ),
+                TransformationSet::Rotations(rotations) => (
+                    "rotation",
+                    rotations
+                        .iter()
+                        .flat_map(|v| v.as_slice().to_vec())
+                        .collect(),
+                    "VEC4",
+                    4,
+                ),
+                TransformationSet::QuantizedRotations(rotations) => (
+                    "rotation",
+                    rotations
+                        .iter()
+                        .flat_map(|rotation| {
+                            let rotation =
+                                rotation.map(|component| component as f32 / i16::MAX as f32);
+                            let length = rotation
+                                .iter()
+                                .map(|component| component * component)
+                                .sum::<f32>()
+                                .sqrt()
+                                .max(f32::EPSILON);
+                            rotation.map(|component| component / length)
+                        })
+                        .collect::<Vec<_>>(),
+                    "VEC4",
+                    4,
+                ),
+                TransformationSet::Scales(scales) => (
+                    "scale",
+                    scales.iter().flat_map(|v| v.as_slice().to_vec()).collect(),
+                    "VEC3",
+                    3,
+                ),
+                TransformationSet::MorphTargetWeights(weights) => {
+                    ("weights", weights.to_vec(), "SCALAR", 1)
+                }
+            };
+            let input = self.buffer.floats(&channel.inputs, "SCALAR", 1, None, true);
+            let output = self.buffer.floats(&outputs, kind, components, None, false);
+            samplers.push(json!({
+                "input": input,
+                "output": output,
+                "interpolation": match channel._interpolation {
+                    Interpolation::Linear => "LINEAR",
+                    Interpolation::Step => "STEP",
+                    Interpolation::CubicSpline => "CUBICSPLINE",
+                },
+            }));
+            channels.push(json!({
+                "sampler": samplers.len() - 1,
+                "target": { "node": node, "path": path },
+            }));
+        }
+        if channels.is_empty() {
+            return None;
+        }
+        Some(json!({
+            "name": animation.name,
+            "samplers": samplers,
+            "channels": channels,
+        }))
+    }
+}
+
+fn camera_json(camera: &Camera) -> Value {
+    match &camera.projection {
+        Projection::Perspective(perspective) => {
+            let mut projection = json!({
+                "yfov": perspective.y_fov_rad,
+                "znear": perspective.z_near,
+            });
+            if let Some(aspect_ratio) = perspective.aspect_ratio {
+                projection["aspectRatio"] = json!(aspect_ratio);
+            }
+            if let Some(z_far) = perspective.z_far {
+                projection["zfar"] = json!(z_far);
+            }
+            json!({ "name": camera.name, "type": "perspective", "perspective": projection })
+        }
+        Projection::Orthographic(orthographic) => json!({
+            "name": camera.name,
+            "type": "orthographic",
+            "orthographic": {
+                "xmag": orthographic.x_mag,
+                "ymag": orthographic.y_mag,
+                "znear": orthographic.z_near,
+                "zfar": orthographic.z_far,
+            },
+        }),
+    }
+}
+
+fn light_json(light: &Light) -> Value {
+    let mut light_json = json!({
+        "color": light.color.as_slice(),
+        "intensity": light.intensity,
+    });
+    // Lights without a range reach infinitely far
+    if light.range > 0.0 {
+        light_json["range"] = json!(light.range);
+    }
+    match light.kind {
+        LightKind::Directional => light_json["type"] = json!("directional"),
+        LightKind::Point => light_json["type"] = json!("point"),
+        LightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => {
+            light_json["type"] = json!("spot");
+            light_json["spot"] = json!({
+                "innerConeAngle": inner_cone_angle,
+                "outerConeAngle": outer_cone_angle,
+            });
+        }
+    }
+    light_json
+}
+
+fn sampler_json(sampler: &Sampler) -> Value {
+    let filter = |filter: &Filter| match filter {
+        Filter::Nearest => 9728,
+        Filter::Linear => 9729,
+    };
+    let wrap = |wrap: &WrappingMode| match wrap {
+        WrappingMode::ClampToEdge => 33071,
+        WrappingMode::MirroredRepeat => 33648,
+        WrappingMode::Repeat => 10497,
+    };
+    json!({
+        "magFilter": filter(&sampler.mag_filter),
+        "minFilter": filter(&sampler.min_filter),
+        "wrapS": wrap(&sampler.wrap_s),
+        "wrapT": wrap(&sampler.wrap_t),
+    })
+}
+
+/// Encodes 8 and 16 bit textures as 8 bit RGBA pngs
+fn texture_png(texture: &Texture) -> Result<Vec<u8>> {
+    // 16 bit channels are stored little endian, so their high byte comes second
+    let (channels, bytes_per_channel, swizzle) = match texture.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false),
+        format => bail!("{:?} textures can't be exported!", format),
+    };
+    let rgba = texture
+        .pixels
+        .chunks_exact(channels * bytes_per_channel)
+        .flat_map(|pixel| {
+            let channel = |index: usize| pixel[index * bytes_per_channel + bytes_per_channel - 1];
+            let mut rgba = [0, 0, 0, 255];
+            for (index, value) in rgba.iter_mut().enumerate().take(channels) {
+                *value = channel(index);
+            }
+            if swizzle {
+                rgba.swap(0, 2);
+            }
+            rgba
+        })
+        .collect::<Vec<_>>();
+    let image = match RgbaImage::from_raw(texture.width, texture.height, rgba) {
+        Some(image) => image,
+        None => bail!("The texture has the wrong number of pixels!"),
+    };
+    let mut png = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Packs the json and binary chunks into a .glb container
+fn pack_glb(mut json: Vec<u8>, mut bin: Vec<u8>) -> Vec<u8> {
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+    let mut length = 12 + 8 + json.len();
+    if !bin.is_empty() {
+        length += 8 + bin.len();
+    }
+
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2_u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+    }
+    glb
+}
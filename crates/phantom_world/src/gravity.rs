@@ -0,0 +1,135 @@
+use crate::{Entity, RigidBody, World};
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::IntoQuery,
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+
+/// Changes the gravity applied to an entity's rigid body
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct GravityOverride {
+    pub scale: f32,
+
+    /// Replaces the global gravity and any gravity fields for this body
+    pub gravity: Option<glm::Vec3>,
+}
+
+impl Default for GravityOverride {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            gravity: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum GravityFieldKind {
+    /// Pulls toward the field's position, like a planet
+    Point,
+
+    /// Pulls in a fixed direction, in the field's local space
+    Directional(glm::Vec3),
+}
+
+/// Replaces the global gravity for rigid bodies within its radius.
+/// Bodies inside several fields are pulled by all of them.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct GravityField {
+    pub kind: GravityFieldKind,
+    pub strength: f32,
+    pub radius: f32,
+
+    /// Weaken the pull with the square of the distance from the field's position
+    pub falloff: bool,
+}
+
+impl GravityField {
+    pub fn acceleration(
+        &self,
+        field_transform: &glm::Mat4,
+        position: &glm::Vec3,
+    ) -> Option<glm::Vec3> {
+        let center = (field_transform * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz();
+        let offset = center - position;
+        let distance = glm::length(&offset);
+        if distance > self.radius {
+            return None;
+        }
+        let strength = if self.falloff && distance > f32::EPSILON {
+            self.strength / (distance * distance)
+        } else {
+            self.strength
+        };
+        let direction = match self.kind {
+            GravityFieldKind::Point => {
+                if distance <= f32::EPSILON {
+                    return Some(glm::Vec3::zeros());
+                }
+                offset / distance
+            }
+            GravityFieldKind::Directional(direction) => {
+                let direction =
+                    (field_transform * glm::vec4(direction.x, direction.y, direction.z, 0.0)).xyz();
+                if glm::length2(&direction) <= f32::EPSILON {
+                    return Some(glm::Vec3::zeros());
+                }
+                glm::normalize(&direction)
+            }
+        };
+        Some(direction * strength)
+    }
+}
+
+impl World {
+    /// Resolves gravity overrides and fields into per-body gravity for the next physics step
+    pub fn update_gravity(&mut self) -> Result<()> {
+        let disabled = &self.disabled;
+        let mut fields = Vec::new();
+        let mut query = <(Entity, &GravityField)>::query();
+        for (entity, field) in query.iter(&self.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            fields.push((self.entity_global_transform_matrix(*entity)?, *field));
+        }
+
+        let mut body_gravity = Vec::new();
+        let mut query = <(&RigidBody, Option<&GravityOverride>)>::query();
+        for (rigid_body, gravity_override) in query.iter(&self.ecs) {
+            let position = match self.physics.bodies.get(rigid_body.handle) {
+                Some(body) => body.position().translation.vector,
+                None => continue,
+            };
+
+            let mut field_gravity = None;
+            for (transform, field) in fields.iter() {
+                if let Some(acceleration) = field.acceleration(transform, &position) {
+                    *field_gravity.get_or_insert_with(glm::Vec3::zeros) += acceleration;
+                }
+            }
+
+            let gravity = match (gravity_override, field_gravity) {
+                (Some(gravity_override), _) => Some(
+                    gravity_override
+                        .gravity
+                        .or(field_gravity)
+                        .unwrap_or(self.physics.gravity)
+                        * gravity_override.scale,
+                ),
+                (None, Some(field_gravity)) => Some(field_gravity),
+                (None, None) => None,
+            };
+            body_gravity.push((rigid_body.handle, gravity));
+        }
+
+        for (handle, gravity) in body_gravity {
+            self.physics.set_body_gravity(handle, gravity);
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,34 @@
 mod animation;
+mod animation_player;
+mod audio;
 mod camera;
+mod changes;
+mod cloth;
+mod content;
+mod destructible;
+#[cfg(feature = "draco")]
+mod draco;
+mod extract;
 mod gltf;
+mod gltf_export;
+mod gravity;
+mod lightmap;
+mod lipsync;
+mod navmesh;
 mod physics;
+mod picking;
+mod platform;
+mod pool;
+mod procedural;
+mod projectile;
+mod query;
+mod random;
+mod reflect;
 mod registry;
 mod scenegraph;
+mod skeleton;
+mod spawn;
+mod stable_id;
 mod texture;
 mod transform;
 mod world;
@@ -11,10 +36,19 @@ mod world;
 use phantom_dependencies::serde::{Deserialize, Serialize};
 
 pub use self::{
-    animation::*, camera::*, gltf::*, physics::*, registry::*, scenegraph::*, texture::*,
-    transform::*, world::*,
+    animation::*, animation_player::*, audio::*, camera::*, changes::*, cloth::*, content::*,
+    destructible::*, extract::*, gltf::*, gravity::*, lightmap::*, lipsync::*, navmesh::*,
+    physics::*, picking::*, platform::*, pool::*, procedural::*, projectile::*, query::*,
+    random::*, reflect::*, registry::*, scenegraph::*, skeleton::*, spawn::*, stable_id::*,
+    texture::*, transform::*, world::*,
 };
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Name(pub String);
+
+/// Entities without this component are enabled.
+/// Disabling an entity also disables every node beneath it in the scenegraph.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Enabled(pub bool);
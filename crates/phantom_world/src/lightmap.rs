@@ -0,0 +1,199 @@
+use crate::{
+    Entity, Format, Light, LightKind, Name, PickPrecision, Sampler, Texture, Transform, World,
+};
+use phantom_dependencies::{
+    anyhow::Result, legion::EntityStore, log, nalgebra::Point3, nalgebra_glm as glm,
+    rapier3d::geometry::Ray,
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct LightmapSettings {
+    /// The width and height of each lightmap in texels
+    pub resolution: u32,
+
+    /// Casts a ray from each texel to each light, which is slow for large scenes
+    pub shadows: bool,
+
+    /// How far shadow rays start from the surface, so surfaces don't shadow themselves
+    pub shadow_bias: f32,
+}
+
+impl Default for LightmapSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            shadows: true,
+            shadow_bias: 0.01,
+        }
+    }
+}
+
+/// The direct light falling on an entity's mesh, laid out in its second texture coordinates
+pub struct Lightmap {
+    pub entity: Entity,
+
+    /// The entity's name if it has one, otherwise its mesh's name
+    pub name: String,
+    pub texture: Texture,
+}
+
+impl World {
+    /// Bakes a lightmap for every enabled mesh that isn't skinned and has second texture coordinates
+    pub fn bake_lightmaps(&self, settings: &LightmapSettings) -> Result<Vec<Lightmap>> {
+        let lights = self.lights()?;
+        let resolution = settings.resolution.max(1) as usize;
+        let mut lightmaps = Vec::new();
+        for (entity, mesh, model) in self.static_meshes()? {
+            let vertices = &self.geometry.vertices;
+            let triangles = self
+                .mesh_triangles(mesh)
+                .filter(|triangle| triangle.iter().all(|index| *index < vertices.len()))
+                .collect::<Vec<_>>();
+            if triangles
+                .iter()
+                .flatten()
+                .all(|index| vertices[*index].uv_1 == glm::Vec2::zeros())
+            {
+                log::warn!(
+                    "Mesh '{}' has no second texture coordinates to bake a lightmap into",
+                    mesh.name
+                );
+                continue;
+            }
+
+            let normal_matrix = glm::transpose(&glm::inverse(&glm::mat4_to_mat3(&model)));
+            let mut light = vec![glm::Vec3::zeros(); resolution * resolution];
+            for [a, b, c] in triangles {
+                let [a, b, c] = [a, b, c].map(|index| &vertices[index]);
+                let texel_space = |uv: glm::Vec2| uv * resolution as f32;
+                let (uv_a, uv_b, uv_c) = (
+                    texel_space(a.uv_1),
+                    texel_space(b.uv_1),
+                    texel_space(c.uv_1),
+                );
+                let area = edge(&uv_a, &uv_b, &uv_c);
+                if area.abs() <= f32::EPSILON {
+                    continue;
+                }
+
+                let min = uv_a.inf(&uv_b).inf(&uv_c);
+                let max = uv_a.sup(&uv_b).sup(&uv_c);
+                let clamp = |value: f32| (value.max(0.0) as usize).min(resolution - 1);
+                for y in clamp(min.y.floor())..=clamp(max.y.ceil()) {
+                    for x in clamp(min.x.floor())..=clamp(max.x.ceil()) {
+                        let center = glm::vec2(x as f32 + 0.5, y as f32 + 0.5);
+                        let weights = glm::vec3(
+                            edge(&uv_b, &uv_c, &center),
+                            edge(&uv_c, &uv_a, &center),
+                            edge(&uv_a, &uv_b, &center),
+                        ) / area;
+                        if weights.min() < 0.0 {
+                            continue;
+                        }
+                        let position = a.position * weights.x
+                            + b.position * weights.y
+                            + c.position * weights.z;
+                        let normal =
+                            a.normal * weights.x + b.normal * weights.y + c.normal * weights.z;
+                        let position = (model * position.push(1.0)).xyz();
+                        let normal = (normal_matrix * normal).normalize();
+                        light[y * resolution + x] =
+                            self.direct_light(&lights, &position, &normal, settings);
+                    }
+                }
+            }
+
+            let name = match self.ecs.entry_ref(entity)?.get_component::<Name>() {
+                Ok(name) => name.0.clone(),
+                Err(_) => mesh.name.clone(),
+            };
+            lightmaps.push(Lightmap {
+                entity,
+                name,
+                texture: Texture {
+                    pixels: light
+                        .iter()
+                        .flat_map(|texel| {
+                            let channel =
+                                |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                            [channel(texel.x), channel(texel.y), channel(texel.z), 255]
+                        })
+                        .collect(),
+                    format: Format::R8G8B8A8,
+                    width: resolution as u32,
+                    height: resolution as u32,
+                    sampler: Sampler::default(),
+                },
+            });
+        }
+        Ok(lightmaps)
+    }
+
+    /// The light arriving at a surface from every light, attenuated the same way the renderer does
+    fn direct_light(
+        &self,
+        lights: &[(Transform, Light)],
+        position: &glm::Vec3,
+        normal: &glm::Vec3,
+        settings: &LightmapSettings,
+    ) -> glm::Vec3 {
+        let mut total = glm::Vec3::zeros();
+        for (transform, light) in lights.iter() {
+            let forward = transform.forward().normalize();
+            let mut radiance = light.color * light.intensity;
+            let (direction, distance) = match light.kind {
+                LightKind::Directional => (-forward, f32::MAX),
+                _ => {
+                    let offset = transform.translation - position;
+                    let distance = glm::length(&offset);
+                    radiance *= attenuation(distance, light.range);
+                    (offset / distance.max(0.0001), distance)
+                }
+            };
+            if let LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } = light.kind
+            {
+                let cone_cos = (-direction).dot(&forward);
+                radiance *= smooth_step(outer_cone_angle.cos(), inner_cone_angle.cos(), cone_cos);
+            }
+
+            let n_dot_l = normal.dot(&direction).max(0.0);
+            if n_dot_l <= 0.0 || radiance == glm::Vec3::zeros() {
+                continue;
+            }
+            if settings.shadows {
+                let origin = position + normal * settings.shadow_bias;
+                let ray = Ray::new(Point3::from(origin), direction);
+                let occluded = self
+                    .pick_entity(&ray, PickPrecision::Triangle)
+                    .map_or(false, |hit| hit.distance < distance);
+                if occluded {
+                    continue;
+                }
+            }
+            total += radiance * n_dot_l;
+        }
+        total
+    }
+}
+
+/// Twice the signed area of the triangle, which is positive when it winds counterclockwise
+fn edge(a: &glm::Vec2, b: &glm::Vec2, point: &glm::Vec2) -> f32 {
+    (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+}
+
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let inverse_square = 1.0 / (distance * distance).max(0.0001);
+    if range <= 0.0 {
+        return inverse_square;
+    }
+    let ratio = distance / range;
+    (1.0 - ratio.powi(4)).clamp(0.0, 1.0) * inverse_square
+}
+
+fn smooth_step(edge_0: f32, edge_1: f32, value: f32) -> f32 {
+    let t = ((value - edge_0) / (edge_1 - edge_0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,205 @@
+use crate::{Entity, MorphWeights, World};
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::IntoQuery,
+    serde::{Deserialize, Serialize},
+};
+
+/// Mouth shapes that morph targets on a face mesh can be mapped to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum Viseme {
+    Silent,
+    PP,
+    FF,
+    TH,
+    DD,
+    KK,
+    CH,
+    SS,
+    NN,
+    RR,
+    AA,
+    E,
+    I,
+    O,
+    U,
+}
+
+impl Viseme {
+    /// Maps an ARPAbet phoneme (such as "AA1" or "TH") to its viseme
+    pub fn from_phoneme(phoneme: &str) -> Self {
+        let phoneme = phoneme
+            .trim_end_matches(|character: char| character.is_ascii_digit())
+            .to_ascii_uppercase();
+        match phoneme.as_str() {
+            "P" | "B" | "M" => Self::PP,
+            "F" | "V" => Self::FF,
+            "TH" | "DH" => Self::TH,
+            "T" | "D" => Self::DD,
+            "K" | "G" | "NG" | "HH" => Self::KK,
+            "CH" | "JH" | "SH" | "ZH" => Self::CH,
+            "S" | "Z" => Self::SS,
+            "N" | "L" => Self::NN,
+            "R" | "ER" => Self::RR,
+            "AA" | "AE" | "AH" | "AY" | "AW" => Self::AA,
+            "EH" | "EY" => Self::E,
+            "IH" | "IY" | "Y" => Self::I,
+            "AO" | "OW" | "OY" => Self::O,
+            "UH" | "UW" | "W" => Self::U,
+            _ => Self::Silent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct PhonemeKey {
+    pub time: f32,
+    pub viseme: Viseme,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum LipSyncTrack {
+    /// Visemes authored or generated ahead of time, sorted by time
+    Phonemes(Vec<PhonemeKey>),
+
+    /// Loudness of the audio clip sampled at a fixed interval,
+    /// used to open the mouth when no phoneme data is available
+    Amplitude { envelope: Vec<f32>, interval: f32 },
+}
+
+impl LipSyncTrack {
+    pub fn from_phonemes(phonemes: &[(f32, &str)]) -> Self {
+        let mut keys = phonemes
+            .iter()
+            .map(|(time, phoneme)| PhonemeKey {
+                time: *time,
+                viseme: Viseme::from_phoneme(phoneme),
+            })
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self::Phonemes(keys)
+    }
+
+    /// Builds a normalized RMS envelope from mono audio samples
+    pub fn from_samples(samples: &[f32], sample_rate: u32, interval: f32) -> Self {
+        let window = ((sample_rate as f32 * interval) as usize).max(1);
+        let mut envelope = samples
+            .chunks(window)
+            .map(|chunk| {
+                let sum = chunk.iter().map(|sample| sample * sample).sum::<f32>();
+                (sum / chunk.len() as f32).sqrt()
+            })
+            .collect::<Vec<_>>();
+        let peak = envelope.iter().cloned().fold(0.0, f32::max);
+        if peak > f32::EPSILON {
+            envelope.iter_mut().for_each(|value| *value /= peak);
+        }
+        Self::Amplitude { envelope, interval }
+    }
+
+    pub fn duration(&self) -> f32 {
+        match self {
+            Self::Phonemes(keys) => keys.last().map_or(0.0, |key| key.time),
+            Self::Amplitude { envelope, interval } => envelope.len() as f32 * interval,
+        }
+    }
+
+    /// The viseme and how strongly it is shown at the given time
+    pub fn sample(&self, time: f32) -> (Viseme, f32) {
+        match self {
+            Self::Phonemes(keys) => match keys.iter().rev().find(|key| key.time <= time) {
+                Some(key) => (key.viseme, 1.0),
+                None => (Viseme::Silent, 0.0),
+            },
+            Self::Amplitude { envelope, interval } => {
+                if *interval <= 0.0 || envelope.is_empty() {
+                    return (Viseme::Silent, 0.0);
+                }
+                let position = time / interval;
+                let index = position.floor() as usize;
+                if index + 1 >= envelope.len() {
+                    return (Viseme::AA, *envelope.last().unwrap_or(&0.0));
+                }
+                let fraction = position - index as f32;
+                let value = envelope[index] + (envelope[index + 1] - envelope[index]) * fraction;
+                (Viseme::AA, value)
+            }
+        }
+    }
+}
+
+/// Drives the `MorphWeights` of this entity from a lip sync track.
+/// Set `time` from the audio source's playback position to stay in sync with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct LipSync {
+    pub track: LipSyncTrack,
+    pub targets: Vec<(Viseme, usize)>,
+    pub time: f32,
+    pub playing: bool,
+
+    /// How quickly weights move toward their target values, in units per second
+    pub blend_speed: f32,
+}
+
+impl LipSync {
+    pub fn new(track: LipSyncTrack, targets: Vec<(Viseme, usize)>) -> Self {
+        Self {
+            track,
+            targets,
+            time: 0.0,
+            playing: false,
+            blend_speed: 12.0,
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    fn update_weights(&self, weights: &mut [f32], step: f32) {
+        let (viseme, strength) = if self.playing {
+            self.track.sample(self.time)
+        } else {
+            (Viseme::Silent, 0.0)
+        };
+        let blend = (self.blend_speed * step).min(1.0);
+        for (target_viseme, morph_target) in self.targets.iter() {
+            let weight = match weights.get_mut(*morph_target) {
+                Some(weight) => weight,
+                None => continue,
+            };
+            let target_weight = if *target_viseme == viseme {
+                strength
+            } else {
+                0.0
+            };
+            *weight += (target_weight - *weight) * blend;
+        }
+    }
+}
+
+impl World {
+    pub fn update_lip_sync(&mut self, step: f32) -> Result<()> {
+        let disabled = &self.disabled;
+        let mut query = <(Entity, &mut LipSync, &mut MorphWeights)>::query();
+        for (entity, lip_sync, morph_weights) in query.iter_mut(&mut self.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            if lip_sync.playing {
+                lip_sync.time += step;
+                if lip_sync.time > lip_sync.track.duration() {
+                    lip_sync.playing = false;
+                }
+            }
+            lip_sync.update_weights(&mut morph_weights.0, step);
+            self.changes.mark_changed::<MorphWeights>(*entity);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{Entity, Mesh, MeshRender, Skin, World};
+use phantom_dependencies::{
+    anyhow::Result,
+    bincode,
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    path::Path,
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct NavMeshSettings {
+    /// The steepest slope that can be walked on, in radians
+    pub max_slope: f32,
+
+    /// Vertices closer than this are merged, joining triangles that only nearly share an edge
+    pub weld_distance: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            max_slope: 45_f32.to_radians(),
+            weld_distance: 0.01,
+        }
+    }
+}
+
+/// The walkable triangles of a world's static meshes, baked ahead of time to find paths on
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct NavMesh {
+    pub vertices: Vec<glm::Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+
+    /// The triangle across each edge of a triangle, where the first edge runs from its first vertex to its second
+    pub neighbors: Vec<[Option<u32>; 3]>,
+}
+
+impl NavMesh {
+    /// Gathers the upward facing triangles of every enabled mesh that isn't skinned
+    pub fn bake(world: &World, settings: &NavMeshSettings) -> Result<Self> {
+        let min_up = settings.max_slope.cos();
+        let mut nav_mesh = Self::default();
+        let mut welded = HashMap::new();
+        for (_, mesh, model) in world.static_meshes()? {
+            for [a, b, c] in world.mesh_triangles(mesh) {
+                let vertices = &world.geometry.vertices;
+                let corners = match (vertices.get(a), vertices.get(b), vertices.get(c)) {
+                    (Some(a), Some(b), Some(c)) => {
+                        [a, b, c].map(|vertex| (model * vertex.position.push(1.0)).xyz())
+                    }
+                    _ => continue,
+                };
+                let normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+                if glm::length2(&normal) <= f32::EPSILON || normal.normalize().y < min_up {
+                    continue;
+                }
+                let triangle = corners
+                    .map(|corner| nav_mesh.weld(&mut welded, corner, settings.weld_distance));
+                if triangle[0] != triangle[1]
+                    && triangle[1] != triangle[2]
+                    && triangle[2] != triangle[0]
+                {
+                    nav_mesh.triangles.push(triangle);
+                }
+            }
+        }
+        nav_mesh.connect();
+        Ok(nav_mesh)
+    }
+
+    fn weld(
+        &mut self,
+        welded: &mut HashMap<[i64; 3], u32>,
+        position: glm::Vec3,
+        weld_distance: f32,
+    ) -> u32 {
+        let cell = [0, 1, 2].map(|axis| (position[axis] / weld_distance).round() as i64);
+        let vertices = &mut self.vertices;
+        *welded.entry(cell).or_insert_with(|| {
+            vertices.push(position);
+            vertices.len() as u32 - 1
+        })
+    }
+
+    /// Links triangles that share an edge. Edges shared by more than two triangles are left unlinked.
+    fn connect(&mut self) {
+        let mut edges = HashMap::<(u32, u32), Vec<(usize, usize)>>::new();
+        for (triangle, vertices) in self.triangles.iter().enumerate() {
+            for edge in 0..3 {
+                let (start, end) = (vertices[edge], vertices[(edge + 1) % 3]);
+                edges
+                    .entry((start.min(end), start.max(end)))
+                    .or_default()
+                    .push((triangle, edge));
+            }
+        }
+        self.neighbors = vec![[None; 3]; self.triangles.len()];
+        for sides in edges.values() {
+            if let [(first, first_edge), (second, second_edge)] = sides[..] {
+                self.neighbors[first][first_edge] = Some(second as u32);
+                self.neighbors[second][second_edge] = Some(first as u32);
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, bincode::serialize(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(bincode::deserialize(&std::fs::read(path)?)?)
+    }
+
+    fn corners(&self, triangle: usize) -> [glm::Vec3; 3] {
+        self.triangles[triangle].map(|vertex| self.vertices[vertex as usize])
+    }
+
+    fn center(&self, triangle: usize) -> glm::Vec3 {
+        let [a, b, c] = self.corners(triangle);
+        (a + b + c) / 3.0
+    }
+
+    /// The triangle beneath or above the position that is closest to it vertically,
+    /// or the triangle with the nearest center if the position is over none of them
+    pub fn nearest_triangle(&self, position: &glm::Vec3) -> Option<usize> {
+        let over = |triangle: usize| {
+            let [a, b, c] = self.corners(triangle);
+            let side = |start: glm::Vec3, end: glm::Vec3| {
+                (end.x - start.x) * (position.z - start.z)
+                    - (end.z - start.z) * (position.x - start.x)
+            };
+            let sides = [side(a, b), side(b, c), side(c, a)];
+            sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+        };
+        let vertical = |triangle: &usize| (self.center(*triangle).y - position.y).abs();
+        let distance = |triangle: &usize| glm::distance2(&self.center(*triangle), position);
+        (0..self.triangles.len())
+            .filter(|triangle| over(*triangle))
+            .min_by(|a, b| compare(vertical(a), vertical(b)))
+            .or_else(|| (0..self.triangles.len()).min_by(|a, b| compare(distance(a), distance(b))))
+    }
+
+    /// Finds a path across connected triangles with A*, passing through the middle of each edge crossed.
+    /// Returns `None` if either position is off the mesh or they aren't connected.
+    pub fn find_path(&self, start: &glm::Vec3, end: &glm::Vec3) -> Option<Vec<glm::Vec3>> {
+        let first = self.nearest_triangle(start)?;
+        let last = self.nearest_triangle(end)?;
+
+        let mut came_from = HashMap::new();
+        let mut costs = HashMap::from([(first, 0.0)]);
+        let mut open = BinaryHeap::from([Candidate {
+            triangle: first,
+            estimate: 0.0,
+        }]);
+        while let Some(Candidate { triangle, .. }) = open.pop() {
+            if triangle == last {
+                break;
+            }
+            for neighbor in self.neighbors[triangle].iter().flatten() {
+                let neighbor = *neighbor as usize;
+                let cost = costs[&triangle]
+                    + glm::distance(&self.center(triangle), &self.center(neighbor));
+                if costs
+                    .get(&neighbor)
+                    .map_or(true, |existing| cost < *existing)
+                {
+                    costs.insert(neighbor, cost);
+                    came_from.insert(neighbor, triangle);
+                    open.push(Candidate {
+                        triangle: neighbor,
+                        estimate: cost + glm::distance(&self.center(neighbor), end),
+                    });
+                }
+            }
+        }
+        if !costs.contains_key(&last) {
+            return None;
+        }
+
+        let mut path = vec![*end];
+        let mut triangle = last;
+        while let Some(previous) = came_from.get(&triangle) {
+            path.push(self.shared_edge_middle(*previous, triangle));
+            triangle = *previous;
+        }
+        path.push(*start);
+        path.reverse();
+        Some(path)
+    }
+
+    fn shared_edge_middle(&self, triangle: usize, neighbor: usize) -> glm::Vec3 {
+        let edge = self.neighbors[triangle]
+            .iter()
+            .position(|other| *other == Some(neighbor as u32))
+            .unwrap_or_default();
+        let corners = self.corners(triangle);
+        (corners[edge] + corners[(edge + 1) % 3]) / 2.0
+    }
+}
+
+/// A triangle waiting to be searched, ordered so the lowest estimated cost is searched first
+struct Candidate {
+    triangle: usize,
+    estimate: f32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(other.estimate, self.estimate)
+    }
+}
+
+fn compare(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+impl World {
+    /// Every enabled mesh that isn't skinned, with its global transform, for baking
+    pub(crate) fn static_meshes(&self) -> Result<Vec<(Entity, &Mesh, glm::Mat4)>> {
+        let mut meshes = Vec::new();
+        let mut query = <(Entity, &MeshRender)>::query();
+        for (entity, mesh_render) in query.iter(&self.ecs) {
+            if self.disabled.contains(entity)
+                || self.ecs.entry_ref(*entity)?.get_component::<Skin>().is_ok()
+            {
+                continue;
+            }
+            if let Some(mesh) = self.geometry.meshes.get(&mesh_render.name) {
+                meshes.push((*entity, mesh, self.entity_global_transform_matrix(*entity)?));
+            }
+        }
+        Ok(meshes)
+    }
+}
@@ -1,14 +1,19 @@
 use phantom_dependencies::{
     rapier3d::{
         self,
-        dynamics::{CCDSolver, IntegrationParameters, RigidBodySet},
-        geometry::{BroadPhase, ColliderSet, NarrowPhase},
+        dynamics::{
+            CCDSolver, CoefficientCombineRule, IntegrationParameters, RigidBodySet, RigidBodyType,
+        },
+        geometry::{BroadPhase, Collider, ColliderSet, InteractionGroups, NarrowPhase},
+        math::AngVector,
+        math::Isometry,
         na::Vector3,
         pipeline::{PhysicsPipeline, QueryPipeline},
         prelude::{ImpulseJointSet, IslandManager, MultibodyJointSet, RigidBodyHandle},
     },
     serde::{Deserialize, Serialize},
 };
+use std::collections::HashMap;
 
 pub type Handle = rapier3d::dynamics::RigidBodyHandle;
 pub type ColliderHandle = rapier3d::geometry::ColliderHandle;
@@ -17,21 +22,143 @@ pub type ColliderHandle = rapier3d::geometry::ColliderHandle;
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct RigidBody {
     pub handle: Handle,
-    pub colliders: Vec<ColliderHandle>,
 }
 
 impl RigidBody {
     pub fn new(handle: Handle) -> Self {
+        Self { handle }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct PhysicsMaterial {
+    pub friction: f32,
+    pub restitution: f32,
+    pub friction_combine: CoefficientCombineRule,
+    pub restitution_combine: CoefficientCombineRule,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
         Self {
-            handle,
-            colliders: Vec::new(),
+            friction: 0.5,
+            restitution: 0.0,
+            friction_combine: CoefficientCombineRule::Average,
+            restitution_combine: CoefficientCombineRule::Average,
+        }
+    }
+}
+
+impl PhysicsMaterial {
+    pub fn apply(&self, collider: &mut Collider) {
+        collider.set_friction(self.friction);
+        collider.set_restitution(self.restitution);
+        collider.set_friction_combine_rule(self.friction_combine);
+        collider.set_restitution_combine_rule(self.restitution_combine);
+    }
+}
+
+/// The collision layer an entity's colliders belong to, from 0 to 31
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct CollisionLayer(pub u32);
+
+/// Decides which collision layers interact with each other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct CollisionMatrix {
+    pub layer_names: Vec<String>,
+    rows: Vec<u32>,
+}
+
+impl Default for CollisionMatrix {
+    fn default() -> Self {
+        let mut layer_names = vec![String::new(); Self::MAX_LAYERS];
+        layer_names[0] = "Default".to_string();
+        Self {
+            layer_names,
+            rows: vec![u32::MAX; Self::MAX_LAYERS],
+        }
+    }
+}
+
+impl CollisionMatrix {
+    pub const MAX_LAYERS: usize = 32;
+
+    pub fn collides(&self, first: u32, second: u32) -> bool {
+        self.rows[first as usize] & (1 << second) != 0
+    }
+
+    pub fn set_collides(&mut self, first: u32, second: u32, collides: bool) {
+        if collides {
+            self.rows[first as usize] |= 1 << second;
+            self.rows[second as usize] |= 1 << first;
+        } else {
+            self.rows[first as usize] &= !(1 << second);
+            self.rows[second as usize] &= !(1 << first);
         }
     }
+
+    /// Layers that have been given a name, which are the ones shown in the editor
+    pub fn named_layers(&self) -> Vec<u32> {
+        self.layer_names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(layer, _)| layer as u32)
+            .collect()
+    }
+
+    pub fn interaction_groups(&self, layer: u32) -> InteractionGroups {
+        InteractionGroups::new(1 << layer, self.rows[layer as usize])
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct PhysicsSettings {
+    /// How many fixed physics steps are taken per second
+    pub fixed_hz: f32,
+
+    /// The most steps taken in a single frame, so slow frames cannot spiral
+    pub max_substeps: u32,
+
+    pub velocity_iterations: usize,
+    pub friction_iterations: usize,
+    pub stabilization_iterations: usize,
+    pub ccd_substeps: usize,
+
+    /// Blend rigid body transforms between the last two steps when rendering
+    pub interpolate: bool,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        let integration_parameters = IntegrationParameters::default();
+        Self {
+            fixed_hz: 60.0,
+            max_substeps: 4,
+            velocity_iterations: integration_parameters.max_velocity_iterations,
+            friction_iterations: integration_parameters.max_velocity_friction_iterations,
+            stabilization_iterations: integration_parameters.max_stabilization_iterations,
+            ccd_substeps: integration_parameters.max_ccd_substeps,
+            interpolate: true,
+        }
+    }
+}
+
+impl PhysicsSettings {
+    pub fn fixed_timestep(&self) -> f32 {
+        1.0 / self.fixed_hz.max(1.0)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct WorldPhysics {
+    pub settings: PhysicsSettings,
+    pub collision_matrix: CollisionMatrix,
     pub gravity: Vector3<f32>,
     pub integration_parameters: IntegrationParameters,
     pub broad_phase: BroadPhase,
@@ -46,6 +173,34 @@ pub struct WorldPhysics {
     pub ccd_solver: CCDSolver,
     #[serde(skip)]
     pub pipeline: PhysicsPipeline,
+    /// How far rendering is between the last step and the next, from 0 to 1.
+    /// The app's fixed update accumulator decides this, since it also decides when steps are taken.
+    #[serde(skip)]
+    pub interpolation_alpha: f32,
+    #[serde(skip)]
+    previous_positions: HashMap<Handle, Isometry<f32>>,
+    body_gravity: HashMap<Handle, BodyGravity>,
+    disabled_bodies: HashMap<Handle, DisabledBody>,
+}
+
+/// A body's own gravity, along with the gravity scale it had before it was given one
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct BodyGravity {
+    gravity: Vector3<f32>,
+    gravity_scale: f32,
+}
+
+/// What disabling a body changed, so enabling it restores the body as it was.
+/// Disabled bodies are made static and their colliders stop interacting with anything,
+/// which keeps them out of the simulation and out of scene queries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct DisabledBody {
+    body_type: RigidBodyType,
+    linear_velocity: Vector3<f32>,
+    angular_velocity: AngVector<f32>,
+    colliders: Vec<(ColliderHandle, InteractionGroups, InteractionGroups)>,
 }
 
 impl Default for WorldPhysics {
@@ -57,6 +212,8 @@ impl Default for WorldPhysics {
 impl WorldPhysics {
     pub fn new() -> Self {
         Self {
+            settings: PhysicsSettings::default(),
+            collision_matrix: CollisionMatrix::default(),
             gravity: Vector3::y() * -9.81,
             integration_parameters: IntegrationParameters::default(),
             broad_phase: BroadPhase::new(),
@@ -69,6 +226,10 @@ impl WorldPhysics {
             query_pipeline: QueryPipeline::default(),
             ccd_solver: CCDSolver::new(),
             pipeline: PhysicsPipeline::new(),
+            interpolation_alpha: 0.0,
+            previous_positions: HashMap::new(),
+            body_gravity: HashMap::new(),
+            disabled_bodies: HashMap::new(),
         }
     }
 
@@ -81,14 +242,146 @@ impl WorldPhysics {
             &mut self.multibody_joints,
             true,
         );
+        self.body_gravity.remove(&handle);
+        self.disabled_bodies.remove(&handle);
+    }
+
+    pub fn is_enabled(&self, handle: Handle) -> bool {
+        !self.disabled_bodies.contains_key(&handle)
+    }
+
+    /// Takes a body and its colliders out of the simulation and scene queries,
+    /// or puts them back as they were
+    pub fn set_enabled(&mut self, handle: Handle, enabled: bool) {
+        if enabled == self.is_enabled(handle) {
+            return;
+        }
+        let body = match self.bodies.get_mut(handle) {
+            Some(body) => body,
+            None => return,
+        };
+
+        if enabled {
+            let disabled = match self.disabled_bodies.remove(&handle) {
+                Some(disabled) => disabled,
+                None => return,
+            };
+            body.set_body_type(disabled.body_type);
+            body.set_linvel(disabled.linear_velocity, false);
+            body.set_angvel(disabled.angular_velocity, false);
+            body.wake_up(true);
+            for (collider_handle, collision_groups, solver_groups) in disabled.colliders {
+                if let Some(collider) = self.colliders.get_mut(collider_handle) {
+                    collider.set_collision_groups(collision_groups);
+                    collider.set_solver_groups(solver_groups);
+                }
+            }
+            return;
+        }
+
+        let mut disabled = DisabledBody {
+            body_type: body.body_type(),
+            linear_velocity: *body.linvel(),
+            angular_velocity: *body.angvel(),
+            colliders: Vec::new(),
+        };
+        body.set_body_type(RigidBodyType::Static);
+        body.set_linvel(Vector3::zeros(), false);
+        body.set_angvel(AngVector::zeros(), false);
+        for collider_handle in body.colliders().to_vec() {
+            if let Some(collider) = self.colliders.get_mut(collider_handle) {
+                disabled.colliders.push((
+                    collider_handle,
+                    collider.collision_groups(),
+                    collider.solver_groups(),
+                ));
+                collider.set_collision_groups(InteractionGroups::none());
+                collider.set_solver_groups(InteractionGroups::none());
+            }
+        }
+        self.disabled_bodies.insert(handle, disabled);
     }
 
     pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
         self.gravity = gravity;
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        self.integration_parameters.dt = delta_time;
+    /// Gives a body its own gravity in place of the global gravity,
+    /// or restores the global gravity when `None` is passed
+    pub fn set_body_gravity(&mut self, handle: Handle, gravity: Option<Vector3<f32>>) {
+        let body = match self.bodies.get_mut(handle) {
+            Some(body) => body,
+            None => return,
+        };
+        match gravity {
+            Some(gravity) => {
+                let gravity_scale = match self.body_gravity.get(&handle) {
+                    Some(body_gravity) => body_gravity.gravity_scale,
+                    None => body.gravity_scale(),
+                };
+                body.set_gravity_scale(0.0, false);
+                self.body_gravity.insert(
+                    handle,
+                    BodyGravity {
+                        gravity,
+                        gravity_scale,
+                    },
+                );
+            }
+            None => {
+                if let Some(body_gravity) = self.body_gravity.remove(&handle) {
+                    body.set_gravity_scale(body_gravity.gravity_scale, true);
+                }
+            }
+        }
+    }
+
+    pub fn body_gravity(&self, handle: Handle) -> Vector3<f32> {
+        self.body_gravity
+            .get(&handle)
+            .map_or(self.gravity, |body_gravity| body_gravity.gravity)
+    }
+
+    /// Takes a single fixed step, remembering where bodies were so rendering can blend between steps
+    pub fn fixed_step(&mut self) {
+        self.previous_positions = self
+            .bodies
+            .iter()
+            .map(|(handle, body)| (handle, *body.position()))
+            .collect();
+        self.step(self.settings.fixed_timestep());
+    }
+
+    /// The position of a body for rendering, blended between the last two steps
+    pub fn interpolated_position(&self, handle: Handle) -> Option<Isometry<f32>> {
+        let body = self.bodies.get(handle)?;
+        let current = *body.position();
+        if !self.settings.interpolate {
+            return Some(current);
+        }
+        Some(match self.previous_positions.get(&handle) {
+            Some(previous) => previous.lerp_slerp(&current, self.interpolation_alpha),
+            None => current,
+        })
+    }
+
+    pub fn step(&mut self, timestep: f32) {
+        self.integration_parameters.dt = timestep;
+        self.integration_parameters.max_velocity_iterations = self.settings.velocity_iterations;
+        self.integration_parameters.max_velocity_friction_iterations =
+            self.settings.friction_iterations;
+        self.integration_parameters.max_stabilization_iterations =
+            self.settings.stabilization_iterations;
+        self.integration_parameters.max_ccd_substeps = self.settings.ccd_substeps;
+
+        for (handle, body_gravity) in self.body_gravity.iter() {
+            if let Some(body) = self.bodies.get_mut(*handle) {
+                if body.is_dynamic() && !body.is_sleeping() {
+                    let velocity = body.linvel() + body_gravity.gravity * timestep;
+                    body.set_linvel(velocity, false);
+                }
+            }
+        }
 
         // We ignore contact events for now.
         let event_handler = ();
@@ -0,0 +1,170 @@
+use crate::{screen_ray, BoundingBox, Entity, Mesh, MeshRender, Viewport, World};
+use phantom_dependencies::{
+    anyhow::Result, legion::IntoQuery, nalgebra_glm as glm, rapier3d::geometry::Ray,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PickPrecision {
+    /// Tests against each mesh's bounding box, which is fast but loose for irregular meshes
+    BoundingBox,
+
+    /// Tests against every triangle of meshes whose bounding box is struck
+    Triangle,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PickHit {
+    pub entity: Entity,
+
+    /// Distance along the ray, in units of the ray's direction
+    pub distance: f32,
+}
+
+impl World {
+    /// A ray from the active camera through a point on the screen,
+    /// where (0, 0) is the top left corner and (1, 1) is the bottom right
+    pub fn active_camera_ray(&self, screen_position: &glm::Vec2, aspect_ratio: f32) -> Result<Ray> {
+        let (projection, view) = self.active_camera_matrices(aspect_ratio)?;
+        Ok(screen_ray(screen_position, &projection, &view))
+    }
+
+    /// A ray from the active camera through a cursor position measured from the top left
+    /// corner of the window, for a scene rendered into a viewport that may not fill the window
+    pub fn viewport_ray(&self, cursor_position: &glm::Vec2, viewport: &Viewport) -> Result<Ray> {
+        self.active_camera_ray(
+            &viewport.normalize(cursor_position),
+            viewport.aspect_ratio(),
+        )
+    }
+
+    /// Finds the closest enabled entity with a mesh that the ray strikes.
+    /// Unlike `pick_object`, this doesn't need colliders.
+    pub fn pick_entity(&self, ray: &Ray, precision: PickPrecision) -> Option<PickHit> {
+        let disabled = &self.disabled;
+        let mut closest: Option<PickHit> = None;
+        let mut query = <(Entity, &MeshRender)>::query();
+        for (entity, mesh_render) in query.iter(&self.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            let mesh = match self.geometry.meshes.get(&mesh_render.name) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let model = match self.entity_global_transform_matrix(*entity) {
+                Ok(model) => model,
+                Err(_) => continue,
+            };
+
+            // The ray is brought into the mesh's space. Its direction isn't renormalized,
+            // so distances along it are the same in both spaces.
+            let inverse_model = glm::inverse(&model);
+            let origin = (inverse_model * ray.origin.coords.push(1.0)).xyz();
+            let direction = (inverse_model * ray.dir.push(0.0)).xyz();
+
+            let mut distance = match ray_box_distance(&origin, &direction, &mesh.bounding_box()) {
+                Some(distance) => distance,
+                None => continue,
+            };
+            if precision == PickPrecision::Triangle {
+                let triangle_distance = self
+                    .mesh_triangles(mesh)
+                    .filter_map(|[a, b, c]| {
+                        let vertices = &self.geometry.vertices;
+                        ray_triangle_distance(
+                            &origin,
+                            &direction,
+                            [
+                                vertices.get(a)?.position,
+                                vertices.get(b)?.position,
+                                vertices.get(c)?.position,
+                            ],
+                        )
+                    })
+                    .reduce(f32::min);
+                distance = match triangle_distance {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+            }
+
+            if closest.map_or(true, |hit| distance < hit.distance) {
+                closest = Some(PickHit {
+                    entity: *entity,
+                    distance,
+                });
+            }
+        }
+        closest
+    }
+
+    /// The vertex indices of every triangle in the mesh
+    pub(crate) fn mesh_triangles<'a>(
+        &'a self,
+        mesh: &'a Mesh,
+    ) -> impl Iterator<Item = [usize; 3]> + 'a {
+        mesh.primitives.iter().flat_map(move |primitive| {
+            self.geometry
+                .indices
+                .get(primitive.first_index..primitive.first_index + primitive.number_of_indices)
+                .unwrap_or_default()
+                .chunks_exact(3)
+                .map(|chunk| [chunk[0] as usize, chunk[1] as usize, chunk[2] as usize])
+        })
+    }
+}
+
+/// Slab test, returning zero when the ray starts inside the box
+fn ray_box_distance(
+    origin: &glm::Vec3,
+    direction: &glm::Vec3,
+    bounding_box: &BoundingBox,
+) -> Option<f32> {
+    let mut near = 0.0_f32;
+    let mut far = f32::MAX;
+    for axis in 0..3 {
+        if direction[axis].abs() < f32::EPSILON {
+            if origin[axis] < bounding_box.min[axis] || origin[axis] > bounding_box.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let inverse = 1.0 / direction[axis];
+        let first = (bounding_box.min[axis] - origin[axis]) * inverse;
+        let second = (bounding_box.max[axis] - origin[axis]) * inverse;
+        near = near.max(first.min(second));
+        far = far.min(first.max(second));
+        if near > far {
+            return None;
+        }
+    }
+    Some(near)
+}
+
+/// Möller–Trumbore intersection, striking both sides of the triangle
+fn ray_triangle_distance(
+    origin: &glm::Vec3,
+    direction: &glm::Vec3,
+    [a, b, c]: [glm::Vec3; 3],
+) -> Option<f32> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+    let p = direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(&edge_1);
+    let v = direction.dot(&q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge_2.dot(&q) * inverse_determinant;
+    (distance >= 0.0).then(|| distance)
+}
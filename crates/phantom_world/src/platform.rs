@@ -0,0 +1,269 @@
+use crate::{Entity, Handle, RigidBody, World};
+use phantom_dependencies::{
+    anyhow::Result,
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    rapier3d::{
+        math::{Isometry, Translation},
+        na::{UnitQuaternion, Vector3},
+    },
+    serde::{Deserialize, Serialize},
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub enum PlatformPathMode {
+    Once,
+    Loop,
+    PingPong,
+}
+
+/// Moves a kinematic rigid body along a path of waypoints,
+/// carrying any bodies that stand on top of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct KinematicPlatform {
+    pub waypoints: Vec<glm::Vec3>,
+    pub speed: f32,
+    pub mode: PlatformPathMode,
+
+    /// Follow a smooth Catmull-Rom spline through the waypoints instead of straight lines
+    pub spline: bool,
+
+    pub paused: bool,
+
+    /// Position along the path, measured in segments
+    pub progress: f32,
+
+    direction: f32,
+
+    #[serde(skip)]
+    carried_velocities: HashMap<Handle, Vector3<f32>>,
+}
+
+impl KinematicPlatform {
+    pub fn new(waypoints: Vec<glm::Vec3>, speed: f32, mode: PlatformPathMode) -> Self {
+        Self {
+            waypoints,
+            speed,
+            mode,
+            spline: false,
+            paused: false,
+            progress: 0.0,
+            direction: 1.0,
+            carried_velocities: HashMap::new(),
+        }
+    }
+
+    fn number_of_segments(&self) -> usize {
+        match self.mode {
+            PlatformPathMode::Loop => self.waypoints.len(),
+            _ => self.waypoints.len().saturating_sub(1),
+        }
+    }
+
+    fn waypoint(&self, index: isize) -> glm::Vec3 {
+        let length = self.waypoints.len() as isize;
+        let index = match self.mode {
+            PlatformPathMode::Loop => index.rem_euclid(length),
+            _ => index.clamp(0, length - 1),
+        };
+        self.waypoints[index as usize]
+    }
+
+    pub fn position_at(&self, progress: f32) -> glm::Vec3 {
+        if self.waypoints.len() < 2 {
+            return self
+                .waypoints
+                .first()
+                .cloned()
+                .unwrap_or_else(glm::Vec3::zeros);
+        }
+        let segment = (progress.floor() as isize).min(self.number_of_segments() as isize - 1);
+        let t = progress - segment as f32;
+        let start = self.waypoint(segment);
+        let end = self.waypoint(segment + 1);
+        if !self.spline {
+            return glm::lerp(&start, &end, t);
+        }
+        let before = self.waypoint(segment - 1);
+        let after = self.waypoint(segment + 2);
+        let t2 = t * t;
+        let t3 = t2 * t;
+        ((start * 2.0)
+            + (end - before) * t
+            + (before * 2.0 - start * 5.0 + end * 4.0 - after) * t2
+            + (-before + start * 3.0 - end * 3.0 + after) * t3)
+            * 0.5
+    }
+
+    /// Advances along the path and returns the new position
+    pub fn advance(&mut self, delta_time: f32) -> glm::Vec3 {
+        let segments = self.number_of_segments() as f32;
+        if self.paused || segments == 0.0 {
+            return self.position_at(self.progress);
+        }
+
+        let segment = self.progress.floor() as isize;
+        let segment_length =
+            glm::distance(&self.waypoint(segment), &self.waypoint(segment + 1)).max(0.001);
+        self.progress += self.direction * self.speed * delta_time / segment_length;
+
+        match self.mode {
+            PlatformPathMode::Once => {
+                self.progress = self.progress.clamp(0.0, segments);
+            }
+            PlatformPathMode::Loop => {
+                self.progress = self.progress.rem_euclid(segments);
+            }
+            PlatformPathMode::PingPong => {
+                if self.progress >= segments {
+                    self.progress = segments - (self.progress - segments);
+                    self.direction = -1.0;
+                } else if self.progress <= 0.0 {
+                    self.progress = -self.progress;
+                    self.direction = 1.0;
+                }
+                self.progress = self.progress.clamp(0.0, segments);
+            }
+        }
+
+        self.position_at(self.progress)
+    }
+}
+
+impl World {
+    /// Moves every kinematic platform along its path
+    pub fn update_kinematic_platforms(&mut self, delta_time: f32) -> Result<()> {
+        if delta_time <= 0.0 {
+            return Ok(());
+        }
+        let disabled = &self.disabled;
+        let mut targets = Vec::new();
+        let mut query = <(Entity, &mut KinematicPlatform)>::query();
+        for (entity, platform) in query.iter_mut(&mut self.ecs) {
+            if disabled.contains(entity) {
+                continue;
+            }
+            targets.push((*entity, platform.advance(delta_time)));
+        }
+        for (entity, translation) in targets {
+            let rotation = match self.rigid_body_handle(entity) {
+                Some(handle) => match self.physics.bodies.get(handle) {
+                    Some(body) => body.position().rotation,
+                    None => continue,
+                },
+                None => continue,
+            };
+            self.move_kinematic_platform(entity, translation, rotation, delta_time)?;
+        }
+        Ok(())
+    }
+
+    /// Moves a kinematic body to a new pose before the next physics step.
+    /// Bodies resting on top of it are carried along with it.
+    pub fn move_kinematic_platform(
+        &mut self,
+        entity: Entity,
+        translation: glm::Vec3,
+        rotation: UnitQuaternion<f32>,
+        delta_time: f32,
+    ) -> Result<()> {
+        let handle = match self.rigid_body_handle(entity) {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        let current = match self.physics.bodies.get(handle) {
+            Some(body) => *body.position(),
+            None => return Ok(()),
+        };
+        let next = Isometry::from_parts(Translation::from(translation), rotation);
+        let delta = next * current.inverse();
+        let velocity = (next.translation.vector - current.translation.vector) / delta_time;
+
+        let riders = self.platform_riders(handle);
+        let mut carried_velocities = HashMap::new();
+        let previous_velocities = match self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<KinematicPlatform>()
+        {
+            Ok(platform) => platform.carried_velocities.clone(),
+            Err(_) => HashMap::new(),
+        };
+
+        for rider in riders {
+            let body = match self.physics.bodies.get_mut(rider) {
+                Some(body) => body,
+                None => continue,
+            };
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(delta * body.position());
+            } else if body.is_dynamic() {
+                let previous = previous_velocities
+                    .get(&rider)
+                    .cloned()
+                    .unwrap_or_else(Vector3::zeros);
+                let carried = Vector3::new(velocity.x, 0.0, velocity.z);
+                body.set_linvel(body.linvel() - previous + carried, true);
+                carried_velocities.insert(rider, carried);
+            }
+        }
+
+        if let Some(body) = self.physics.bodies.get_mut(handle) {
+            body.set_next_kinematic_position(next);
+        }
+        if let Some(mut entry) = self.ecs.entry(entity) {
+            if let Ok(platform) = entry.get_component_mut::<KinematicPlatform>() {
+                platform.carried_velocities = carried_velocities;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the bodies whose contacts with the platform face upward
+    pub fn platform_riders(&self, handle: Handle) -> Vec<Handle> {
+        let body = match self.physics.bodies.get(handle) {
+            Some(body) => body,
+            None => return Vec::new(),
+        };
+        let mut riders = Vec::new();
+        for collider in body.colliders() {
+            for pair in self.physics.narrow_phase.contacts_with(*collider) {
+                if !pair.has_any_active_contact {
+                    continue;
+                }
+                let (other, sign) = if pair.collider1 == *collider {
+                    (pair.collider2, 1.0)
+                } else {
+                    (pair.collider1, -1.0)
+                };
+                let standing = pair
+                    .manifolds
+                    .iter()
+                    .any(|manifold| manifold.data.normal.y * sign > 0.7);
+                if !standing {
+                    continue;
+                }
+                if let Some(rider) = self
+                    .physics
+                    .colliders
+                    .get(other)
+                    .and_then(|collider| collider.parent())
+                {
+                    if rider != handle && !riders.contains(&rider) {
+                        riders.push(rider);
+                    }
+                }
+            }
+        }
+        riders
+    }
+
+    fn rigid_body_handle(&self, entity: Entity) -> Option<Handle> {
+        let entry = self.ecs.entry_ref(entity).ok()?;
+        let rigid_body = entry.get_component::<RigidBody>().ok()?;
+        Some(rigid_body.handle)
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{Enabled, Entity, Name, Transform, World, PROJECTILE_POOL};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    lazy_static::lazy_static,
+    legion::{world::Entry, IntoQuery},
+    log,
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// Adds the components of a pooled entity, replacing any it already has.
+/// This runs when an instance is spawned and again each time it is acquired.
+pub type Archetype = Arc<dyn Fn(&mut Entry) + Send + Sync>;
+
+lazy_static! {
+    /// The archetype of every pool created, so pools can be rebuilt after a world is loaded
+    static ref POOL_ARCHETYPES: RwLock<HashMap<String, Archetype>> = {
+        let mut archetypes = HashMap::<String, Archetype>::new();
+        archetypes.insert(PROJECTILE_POOL.to_string(), Arc::new(no_components));
+        RwLock::new(archetypes)
+    };
+}
+
+/// Registers the archetype of a pool, so a loaded world's pool by that name can spawn
+/// new entities. Pools register their archetype when created, so this is only needed
+/// when a scene with pooled entities is loaded before the pool is created.
+pub fn register_pool_archetype(name: &str, archetype: impl Fn(&mut Entry) + Send + Sync + 'static) {
+    register_archetype(name, Arc::new(archetype));
+}
+
+/// Projectiles are given their components when they are fired
+pub(crate) fn no_components(_entry: &mut Entry) {}
+
+fn register_archetype(name: &str, archetype: Archetype) {
+    POOL_ARCHETYPES
+        .write()
+        .expect("Failed to access pool archetypes!")
+        .insert(name.to_string(), archetype);
+}
+
+fn registered_archetype(name: &str) -> Option<Archetype> {
+    POOL_ARCHETYPES
+        .read()
+        .expect("Failed to access pool archetypes!")
+        .get(name)
+        .cloned()
+}
+
+/// Marks an entity as belonging to a pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Pooled {
+    pub pool: String,
+    pub in_use: bool,
+}
+
+/// Pre-spawned entities that are reused instead of being spawned and despawned.
+/// Pools aren't saved with the world, and are rebuilt from the `Pooled` entities when it is loaded.
+pub struct EntityPool {
+    /// Missing for pools rebuilt from a loaded world whose archetype was never registered,
+    /// which can reuse their entities but not grow
+    archetype: Option<Archetype>,
+    available: Vec<Entity>,
+    in_use: Vec<Entity>,
+}
+
+impl EntityPool {
+    pub fn available(&self) -> usize {
+        self.available.len()
+    }
+
+    pub fn in_use(&self) -> &[Entity] {
+        &self.in_use
+    }
+}
+
+impl World {
+    pub fn create_pool(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        archetype: impl Fn(&mut Entry) + Send + Sync + 'static,
+    ) -> Result<()> {
+        let archetype: Archetype = Arc::new(archetype);
+        register_archetype(name, archetype.clone());
+        self.pools.insert(
+            name.to_string(),
+            EntityPool {
+                archetype: Some(archetype),
+                available: Vec::new(),
+                in_use: Vec::new(),
+            },
+        );
+        for _ in 0..capacity {
+            let entity = self.spawn_pooled(name)?;
+            self.pools
+                .get_mut(name)
+                .context("Failed to find pool!")?
+                .available
+                .push(entity);
+        }
+        Ok(())
+    }
+
+    /// Takes an entity from the pool, resetting its components and enabling it.
+    /// The pool grows if every entity is already in use.
+    pub fn acquire(&mut self, name: &str) -> Result<Entity> {
+        let pool = self.pools.get_mut(name).context("Failed to find pool!")?;
+        let archetype = pool.archetype.clone();
+        let entity = match pool.available.pop() {
+            Some(entity) => entity,
+            None => self.spawn_pooled(name)?,
+        };
+
+        {
+            let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+            if let Some(archetype) = archetype.as_ref() {
+                archetype(&mut entry);
+            }
+            entry.get_component_mut::<Pooled>()?.in_use = true;
+        }
+        self.set_enabled(entity, true)?;
+        self.pools
+            .get_mut(name)
+            .context("Failed to find pool!")?
+            .in_use
+            .push(entity);
+        Ok(entity)
+    }
+
+    /// Disables an entity and returns it to its pool
+    pub fn release(&mut self, entity: Entity) -> Result<()> {
+        let name = {
+            let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+            let pooled = entry.get_component_mut::<Pooled>()?;
+            if !pooled.in_use {
+                return Ok(());
+            }
+            pooled.in_use = false;
+            pooled.pool.to_string()
+        };
+        self.set_enabled(entity, false)?;
+        let pool = self.pools.get_mut(&name).context("Failed to find pool!")?;
+        pool.in_use.retain(|pooled| *pooled != entity);
+        pool.available.push(entity);
+        Ok(())
+    }
+
+    /// Despawns every entity belonging to a pool
+    pub fn destroy_pool(&mut self, name: &str) -> Result<()> {
+        let pool = match self.pools.remove(name) {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        for entity in pool.available.into_iter().chain(pool.in_use.into_iter()) {
+            self.remove_entity(entity)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the pools from the `Pooled` entities, after the world is loaded
+    /// or its entities are restored
+    pub fn rebuild_pools(&mut self) {
+        let mut pools = HashMap::<String, EntityPool>::new();
+        for (entity, pooled) in <(Entity, &Pooled)>::query().iter(&self.ecs) {
+            let pool = pools
+                .entry(pooled.pool.to_string())
+                .or_insert_with(|| EntityPool {
+                    archetype: registered_archetype(&pooled.pool),
+                    available: Vec::new(),
+                    in_use: Vec::new(),
+                });
+            if pooled.in_use {
+                pool.in_use.push(*entity);
+            } else {
+                pool.available.push(*entity);
+            }
+        }
+        for (name, pool) in pools.iter() {
+            if pool.archetype.is_none() {
+                log::warn!(
+                    "Pool '{}' has no registered archetype, so it can't grow until it is created again",
+                    name
+                );
+            }
+        }
+        self.pools = pools;
+    }
+
+    fn spawn_pooled(&mut self, name: &str) -> Result<Entity> {
+        let archetype = self
+            .pools
+            .get(name)
+            .context("Failed to find pool!")?
+            .archetype
+            .clone()
+            .context(format!(
+                "Pool '{}' can't grow without an archetype! Register one with `register_pool_archetype`.",
+                name
+            ))?;
+        let entity = self.ecs.push((
+            Name(name.to_string()),
+            Transform::default(),
+            Pooled {
+                pool: name.to_string(),
+                in_use: false,
+            },
+            Enabled(false),
+        ));
+        archetype(&mut self.ecs.entry(entity).context("Failed to find entity!")?);
+        self.scene.default_scenegraph_mut()?.add_node(entity);
+        Ok(entity)
+    }
+}
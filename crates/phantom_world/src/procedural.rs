@@ -0,0 +1,744 @@
+use crate::{BoundingBox, Mesh, Primitive, RandomStream, Vertex, World};
+use phantom_dependencies::{
+    anyhow::{bail, Result},
+    nalgebra_glm as glm,
+};
+use std::collections::VecDeque;
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Octaves of noise layered at increasing frequency and decreasing amplitude
+#[derive(Debug, Copy, Clone)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub frequency: f32,
+
+    /// How much the frequency grows with each octave
+    pub lacunarity: f32,
+
+    /// How much the amplitude shrinks with each octave
+    pub gain: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Self {
+            octaves: 5,
+            frequency: 0.02,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+/// Seeded coherent noise. Every function returns values roughly from -1 to 1.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    permutation: Vec<u8>,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self::from_stream(&mut RandomStream::new(seed))
+    }
+
+    pub fn from_stream(stream: &mut RandomStream) -> Self {
+        let mut table = (0..=255).collect::<Vec<u8>>();
+        stream.shuffle(&mut table);
+        let permutation = table.iter().chain(table.iter()).copied().collect();
+        Self { permutation }
+    }
+
+    fn hash(&self, index: i32) -> usize {
+        self.permutation[(index & 255) as usize] as usize
+    }
+
+    pub fn perlin2(&self, point: &glm::Vec2) -> f32 {
+        let (x, y) = (point.x.floor(), point.y.floor());
+        let (xi, yi) = (x as i32, y as i32);
+        let (xf, yf) = (point.x - x, point.y - y);
+        let (u, v) = (fade(xf), fade(yf));
+
+        let a = self.hash(xi) + (yi & 255) as usize;
+        let b = self.hash(xi + 1) + (yi & 255) as usize;
+        let gradient = |hash: usize, x: f32, y: f32| {
+            let gradient = GRADIENTS[hash % 8];
+            gradient[0] * x + gradient[1] * y
+        };
+        lerp(
+            v,
+            lerp(
+                u,
+                gradient(self.permutation[a] as usize, xf, yf),
+                gradient(self.permutation[b] as usize, xf - 1.0, yf),
+            ),
+            lerp(
+                u,
+                gradient(self.permutation[a + 1] as usize, xf, yf - 1.0),
+                gradient(self.permutation[b + 1] as usize, xf - 1.0, yf - 1.0),
+            ),
+        )
+    }
+
+    pub fn perlin3(&self, point: &glm::Vec3) -> f32 {
+        let floor = point.map(f32::floor);
+        let (xi, yi, zi) = (floor.x as i32, floor.y as i32, floor.z as i32);
+        let local = point - floor;
+        let (u, v, w) = (fade(local.x), fade(local.y), fade(local.z));
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let hash = self.hash(xi + dx + self.hash(yi + dy + self.hash(zi + dz) as i32) as i32);
+            let gradient = GRADIENTS[hash % 12];
+            gradient[0] * (local.x - dx as f32)
+                + gradient[1] * (local.y - dy as f32)
+                + gradient[2] * (local.z - dz as f32)
+        };
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Cheaper than perlin noise and without its axis-aligned artifacts
+    pub fn simplex2(&self, point: &glm::Vec2) -> f32 {
+        let skew = 0.5 * (3.0_f32.sqrt() - 1.0);
+        let unskew = (3.0 - 3.0_f32.sqrt()) / 6.0;
+
+        let s = (point.x + point.y) * skew;
+        let (i, j) = ((point.x + s).floor(), (point.y + s).floor());
+        let t = (i + j) * unskew;
+        let x0 = point.x - (i - t);
+        let y0 = point.y - (j - t);
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (i1, j1, x0 - i1 as f32 + unskew, y0 - j1 as f32 + unskew),
+            (1, 1, x0 - 1.0 + 2.0 * unskew, y0 - 1.0 + 2.0 * unskew),
+        ];
+
+        let (i, j) = (i as i32, j as i32);
+        let total = corners
+            .iter()
+            .map(|(di, dj, x, y)| {
+                let falloff = 0.5 - x * x - y * y;
+                if falloff < 0.0 {
+                    return 0.0;
+                }
+                let hash = self.hash(i + di + self.hash(j + dj) as i32);
+                let gradient = GRADIENTS[hash % 12];
+                falloff.powi(4) * (gradient[0] * x + gradient[1] * y)
+            })
+            .sum::<f32>();
+        70.0 * total
+    }
+
+    /// Fractal brownian motion, layering octaves of simplex noise
+    pub fn fbm2(&self, point: &glm::Vec2, fractal: &Fractal) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitudes = 0.0;
+        let mut frequency = fractal.frequency;
+        for _ in 0..fractal.octaves.max(1) {
+            total += self.simplex2(&(point * frequency)) * amplitude;
+            amplitudes += amplitude;
+            amplitude *= fractal.gain;
+            frequency *= fractal.lacunarity;
+        }
+        total / amplitudes
+    }
+
+    /// Fractal brownian motion, layering octaves of perlin noise
+    pub fn fbm3(&self, point: &glm::Vec3, fractal: &Fractal) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitudes = 0.0;
+        let mut frequency = fractal.frequency;
+        for _ in 0..fractal.octaves.max(1) {
+            total += self.perlin3(&(point * frequency)) * amplitude;
+            amplitudes += amplitude;
+            amplitude *= fractal.gain;
+            frequency *= fractal.lacunarity;
+        }
+        total / amplitudes
+    }
+
+    /// Offsets the point by noise before sampling it, bending features into
+    /// organic shapes such as winding ridges and eroded coastlines.
+    /// The strength is in the same units as the point.
+    pub fn warp2(&self, point: &glm::Vec2, fractal: &Fractal, strength: f32) -> f32 {
+        let offset = glm::vec2(
+            self.fbm2(point, fractal),
+            self.fbm2(&(point + glm::vec2(52.0, 13.0)), fractal),
+        );
+        self.fbm2(&(point + offset * strength), fractal)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Points spread over an area that are never closer than the radius to each other,
+/// which look natural without clumping the way uniformly random points do
+pub fn poisson_disk(
+    stream: &mut RandomStream,
+    size: glm::Vec2,
+    radius: f32,
+    attempts: usize,
+) -> Vec<glm::Vec2> {
+    if radius <= 0.0 || size.x <= 0.0 || size.y <= 0.0 {
+        return Vec::new();
+    }
+
+    // Each grid cell is small enough to hold at most one point
+    let cell_size = radius / 2.0_f32.sqrt();
+    let columns = (size.x / cell_size).ceil() as usize;
+    let rows = (size.y / cell_size).ceil() as usize;
+    let mut grid = vec![None; columns * rows];
+    let cell = |point: &glm::Vec2| {
+        (
+            ((point.x / cell_size) as usize).min(columns - 1),
+            ((point.y / cell_size) as usize).min(rows - 1),
+        )
+    };
+
+    let first = glm::vec2(stream.range_f32(0.0, size.x), stream.range_f32(0.0, size.y));
+    let (column, row) = cell(&first);
+    grid[row * columns + column] = Some(0);
+    let mut points = vec![first];
+    let mut active = vec![0];
+
+    while !active.is_empty() {
+        let active_index = stream.range_i32(0, active.len() as i32) as usize;
+        let origin = points[active[active_index]];
+        let mut found = false;
+        for _ in 0..attempts {
+            let angle = stream.range_f32(0.0, std::f32::consts::TAU);
+            let distance = stream.range_f32(radius, radius * 2.0);
+            let candidate = origin + glm::vec2(angle.cos(), angle.sin()) * distance;
+            if candidate.x < 0.0
+                || candidate.y < 0.0
+                || candidate.x >= size.x
+                || candidate.y >= size.y
+            {
+                continue;
+            }
+
+            let (column, row) = cell(&candidate);
+            let too_close = (row.saturating_sub(2)..(row + 3).min(rows)).any(|row| {
+                (column.saturating_sub(2)..(column + 3).min(columns)).any(|column| {
+                    grid[row * columns + column].map_or(false, |index: usize| {
+                        glm::distance(&points[index], &candidate) < radius
+                    })
+                })
+            });
+            if too_close {
+                continue;
+            }
+
+            grid[row * columns + column] = Some(points.len());
+            active.push(points.len());
+            points.push(candidate);
+            found = true;
+            break;
+        }
+        if !found {
+            active.swap_remove(active_index);
+        }
+    }
+    points
+}
+
+/// Poisson disk points thinned by a density from 0 to 1, for scattering
+/// foliage, rocks and props more thickly in some places than others
+pub fn scatter(
+    stream: &mut RandomStream,
+    size: glm::Vec2,
+    radius: f32,
+    density: impl Fn(&glm::Vec2) -> f32,
+) -> Vec<glm::Vec2> {
+    poisson_disk(stream, size, radius, 30)
+        .into_iter()
+        .filter(|point| stream.chance(density(point)))
+        .collect()
+}
+
+/// A grid of heights from 0 to 1, such as for terrain
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    pub columns: usize,
+    pub rows: usize,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            heights: vec![0.0; columns * rows],
+        }
+    }
+
+    /// Samples fractal noise at each grid point, warped when the strength is above zero
+    pub fn from_noise(
+        noise: &Noise,
+        columns: usize,
+        rows: usize,
+        fractal: &Fractal,
+        warp_strength: f32,
+    ) -> Self {
+        let mut heightmap = Self::new(columns, rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let point = glm::vec2(column as f32, row as f32);
+                let value = if warp_strength > 0.0 {
+                    noise.warp2(&point, fractal, warp_strength)
+                } else {
+                    noise.fbm2(&point, fractal)
+                };
+                heightmap.heights[row * columns + column] = (value * 0.5 + 0.5).clamp(0.0, 1.0);
+            }
+        }
+        heightmap
+    }
+
+    /// The height at a grid point, clamped to the edges of the grid
+    pub fn height(&self, column: isize, row: isize) -> f32 {
+        if self.heights.is_empty() {
+            return 0.0;
+        }
+        let column = column.clamp(0, self.columns as isize - 1) as usize;
+        let row = row.clamp(0, self.rows as isize - 1) as usize;
+        self.heights[row * self.columns + column]
+    }
+
+    pub fn set_height(&mut self, column: usize, row: usize, height: f32) {
+        if column < self.columns && row < self.rows {
+            self.heights[row * self.columns + column] = height;
+        }
+    }
+
+    /// The height between grid points, bilinearly interpolated
+    pub fn sample(&self, column: f32, row: f32) -> f32 {
+        let (x, y) = (column.floor(), row.floor());
+        let (u, v) = (column - x, row - y);
+        let (x, y) = (x as isize, y as isize);
+        lerp(
+            v,
+            lerp(u, self.height(x, y), self.height(x + 1, y)),
+            lerp(u, self.height(x, y + 1), self.height(x + 1, y + 1)),
+        )
+    }
+
+    /// Stretches the heights to span the full range from 0 to 1
+    pub fn normalize(&mut self) {
+        let min = self.heights.iter().copied().fold(f32::MAX, f32::min);
+        let max = self.heights.iter().copied().fold(f32::MIN, f32::max);
+        if max - min <= f32::EPSILON {
+            return;
+        }
+        self.heights
+            .iter_mut()
+            .for_each(|height| *height = (*height - min) / (max - min));
+    }
+}
+
+/// A tile for wave function collapse. Neighboring tiles may only be placed
+/// where the sockets on their touching edges are equal.
+#[derive(Debug, Copy, Clone)]
+pub struct WfcTile {
+    /// How often the tile is picked relative to the others
+    pub weight: f32,
+
+    /// Edge labels in the order north, east, south, west
+    pub sockets: [u32; 4],
+}
+
+/// Fills a grid with tiles so every pair of neighbors fits together,
+/// collapsing the cell with the fewest options left and propagating the choice
+#[derive(Debug, Clone)]
+pub struct WaveFunctionCollapse {
+    pub tiles: Vec<WfcTile>,
+    pub columns: usize,
+    pub rows: usize,
+
+    /// The socket that tiles on the edge of the grid must face outwards, if any
+    pub border: Option<u32>,
+
+    /// The grid is started over after a contradiction, up to this many times
+    pub max_attempts: usize,
+}
+
+impl WaveFunctionCollapse {
+    pub fn new(tiles: Vec<WfcTile>, columns: usize, rows: usize) -> Self {
+        Self {
+            tiles,
+            columns,
+            rows,
+            border: None,
+            max_attempts: 10,
+        }
+    }
+
+    /// The index of the tile placed in each cell, row by row
+    pub fn solve(&self, stream: &mut RandomStream) -> Result<Vec<usize>> {
+        if self.tiles.is_empty() {
+            bail!("Wave function collapse needs at least one tile");
+        }
+        for _ in 0..self.max_attempts.max(1) {
+            if let Some(tiles) = self.attempt(stream) {
+                return Ok(tiles);
+            }
+        }
+        bail!(
+            "Wave function collapse failed to fill a {}x{} grid after {} attempts",
+            self.columns,
+            self.rows,
+            self.max_attempts.max(1)
+        )
+    }
+
+    fn neighbor(&self, cell: usize, direction: usize) -> Option<usize> {
+        let (column, row) = (cell % self.columns, cell / self.columns);
+        match direction {
+            0 if row > 0 => Some(cell - self.columns),
+            1 if column + 1 < self.columns => Some(cell + 1),
+            2 if row + 1 < self.rows => Some(cell + self.columns),
+            3 if column > 0 => Some(cell - 1),
+            _ => None,
+        }
+    }
+
+    fn attempt(&self, stream: &mut RandomStream) -> Option<Vec<usize>> {
+        let tile_count = self.tiles.len();
+        let mut wave = vec![vec![true; tile_count]; self.columns * self.rows];
+        let mut pending = VecDeque::new();
+
+        if let Some(border) = self.border {
+            for (cell, options) in wave.iter_mut().enumerate() {
+                for direction in 0..4 {
+                    if self.neighbor(cell, direction).is_some() {
+                        continue;
+                    }
+                    for (tile, option) in options.iter_mut().enumerate() {
+                        *option &= self.tiles[tile].sockets[direction] == border;
+                    }
+                    pending.push_back(cell);
+                }
+            }
+        }
+        if !self.propagate(&mut wave, pending) {
+            return None;
+        }
+
+        loop {
+            // The undecided cell with the fewest options, ties broken randomly
+            let cell = wave
+                .iter()
+                .enumerate()
+                .filter_map(|(cell, options)| {
+                    let count = options.iter().filter(|option| **option).count();
+                    (count > 1).then(|| (cell, count as f32 + stream.next_f32() * 0.5))
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(cell, _)| cell);
+            let cell = match cell {
+                Some(cell) => cell,
+                None => break,
+            };
+
+            let weights = wave[cell]
+                .iter()
+                .zip(self.tiles.iter())
+                .map(|(option, tile)| {
+                    if *option {
+                        tile.weight.max(0.0001)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect::<Vec<_>>();
+            let chosen = stream.weighted(&weights)?;
+            wave[cell]
+                .iter_mut()
+                .enumerate()
+                .for_each(|(tile, option)| *option = tile == chosen);
+            if !self.propagate(&mut wave, VecDeque::from(vec![cell])) {
+                return None;
+            }
+        }
+
+        wave.iter()
+            .map(|options| options.iter().position(|option| *option))
+            .collect()
+    }
+
+    /// Removes options that no longer fit any remaining option of a neighbor,
+    /// returning false if a cell is left without options
+    fn propagate(&self, wave: &mut [Vec<bool>], mut pending: VecDeque<usize>) -> bool {
+        while let Some(cell) = pending.pop_front() {
+            if !wave[cell].iter().any(|option| *option) {
+                return false;
+            }
+            for direction in 0..4 {
+                let neighbor = match self.neighbor(cell, direction) {
+                    Some(neighbor) => neighbor,
+                    None => continue,
+                };
+                let opposite = (direction + 2) % 4;
+                let mut changed = false;
+                for tile in 0..self.tiles.len() {
+                    if !wave[neighbor][tile] {
+                        continue;
+                    }
+                    let socket = self.tiles[tile].sockets[opposite];
+                    let fits = wave[cell].iter().enumerate().any(|(other, option)| {
+                        *option && self.tiles[other].sockets[direction] == socket
+                    });
+                    if !fits {
+                        wave[neighbor][tile] = false;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    pending.push_back(neighbor);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// A grid of floor and wall cells, generated from corridor tiles with wave function collapse
+#[derive(Debug, Clone)]
+pub struct DungeonLayout {
+    pub columns: usize,
+    pub rows: usize,
+    floor: Vec<bool>,
+}
+
+impl DungeonLayout {
+    /// Each tile covers this many cells in each direction
+    pub const TILE_SIZE: usize = 3;
+
+    const WALL: u32 = 0;
+    const OPEN: u32 = 1;
+
+    /// Every combination of open and walled edges, weighted towards
+    /// solid rock and straight corridors over dead ends
+    pub fn tiles() -> Vec<WfcTile> {
+        (0..16_u32)
+            .map(|mask| {
+                let sockets = [0, 1, 2, 3].map(|edge| (mask >> edge) & 1);
+                let weight = match mask.count_ones() {
+                    0 => 4.0,
+                    1 => 0.3,
+                    2 if sockets[0] == sockets[2] => 2.0,
+                    2 => 1.0,
+                    3 => 0.6,
+                    _ => 0.4,
+                };
+                WfcTile { weight, sockets }
+            })
+            .collect()
+    }
+
+    /// Generates a layout of the given size in tiles, keeping only the largest
+    /// connected area of floor so every floor cell can be reached
+    pub fn generate(stream: &mut RandomStream, columns: usize, rows: usize) -> Result<Self> {
+        let tiles = Self::tiles();
+        let mut collapse = WaveFunctionCollapse::new(tiles.clone(), columns, rows);
+        collapse.border = Some(Self::WALL);
+        let placed = collapse.solve(stream)?;
+
+        let mut layout = Self {
+            columns: columns * Self::TILE_SIZE,
+            rows: rows * Self::TILE_SIZE,
+            floor: vec![false; columns * rows * Self::TILE_SIZE * Self::TILE_SIZE],
+        };
+        for (cell, tile) in placed.iter().enumerate() {
+            let sockets = tiles[*tile].sockets;
+            if !sockets.contains(&Self::OPEN) {
+                continue;
+            }
+            let column = (cell % columns) * Self::TILE_SIZE + 1;
+            let row = (cell / columns) * Self::TILE_SIZE + 1;
+            layout.set_floor(column, row);
+            let edges = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+            for (socket, (x, y)) in sockets.iter().zip(edges.iter()) {
+                if *socket == Self::OPEN {
+                    layout.set_floor((column as isize + x) as usize, (row as isize + y) as usize);
+                }
+            }
+        }
+        layout.keep_largest_region();
+        Ok(layout)
+    }
+
+    pub fn is_floor(&self, column: usize, row: usize) -> bool {
+        column < self.columns && row < self.rows && self.floor[row * self.columns + column]
+    }
+
+    /// The column and row of every floor cell
+    pub fn floor_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let columns = self.columns;
+        self.floor
+            .iter()
+            .enumerate()
+            .filter(|(_, floor)| **floor)
+            .map(move |(index, _)| (index % columns, index / columns))
+    }
+
+    fn set_floor(&mut self, column: usize, row: usize) {
+        self.floor[row * self.columns + column] = true;
+    }
+
+    fn keep_largest_region(&mut self) {
+        let mut region = vec![usize::MAX; self.floor.len()];
+        let mut sizes = Vec::new();
+        for start in 0..self.floor.len() {
+            if !self.floor[start] || region[start] != usize::MAX {
+                continue;
+            }
+            let id = sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::from(vec![start]);
+            region[start] = id;
+            while let Some(index) = queue.pop_front() {
+                size += 1;
+                let (column, row) = (index % self.columns, index / self.columns);
+                let neighbors = [
+                    (column > 0).then(|| index - 1),
+                    (column + 1 < self.columns).then(|| index + 1),
+                    (row > 0).then(|| index - self.columns),
+                    (row + 1 < self.rows).then(|| index + self.columns),
+                ];
+                for neighbor in neighbors.into_iter().flatten() {
+                    if self.floor[neighbor] && region[neighbor] == usize::MAX {
+                        region[neighbor] = id;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+
+        let largest = (0..sizes.len()).max_by_key(|id| sizes[*id]);
+        for (floor, id) in self.floor.iter_mut().zip(region.iter()) {
+            *floor = *floor && Some(*id) == largest;
+        }
+    }
+}
+
+impl World {
+    /// Adds a terrain mesh built from a heightmap, centered on the origin.
+    /// Render it by adding a `MeshRender` with the same name to an entity.
+    pub fn add_heightmap_mesh(
+        &mut self,
+        name: &str,
+        heightmap: &Heightmap,
+        cell_size: f32,
+        height_scale: f32,
+        material_index: Option<usize>,
+    ) -> Result<()> {
+        let (columns, rows) = (heightmap.columns, heightmap.rows);
+        if columns < 2 || rows < 2 {
+            bail!("Heightmaps need at least 2x2 points to build a mesh");
+        }
+
+        let first_vertex = self.geometry.vertices.len();
+        let first_index = self.geometry.indices.len();
+        let offset = glm::vec2(
+            (columns - 1) as f32 * cell_size,
+            (rows - 1) as f32 * cell_size,
+        ) * 0.5;
+        let mut bounding_box = BoundingBox::new_invalid();
+
+        for row in 0..rows as isize {
+            for column in 0..columns as isize {
+                let height = |column, row| heightmap.height(column, row) * height_scale;
+                let position = glm::vec3(
+                    column as f32 * cell_size - offset.x,
+                    height(column, row),
+                    row as f32 * cell_size - offset.y,
+                );
+                let normal = glm::normalize(&glm::vec3(
+                    height(column - 1, row) - height(column + 1, row),
+                    2.0 * cell_size,
+                    height(column, row - 1) - height(column, row + 1),
+                ));
+                let uv = glm::vec2(
+                    column as f32 / (columns - 1) as f32,
+                    row as f32 / (rows - 1) as f32,
+                );
+                bounding_box.fit_point(position);
+                self.geometry.vertices.push(Vertex {
+                    position,
+                    normal,
+                    uv_0: uv,
+                    uv_1: uv,
+                    ..Default::default()
+                });
+            }
+        }
+
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let corner = (first_vertex + row * columns + column) as u32;
+                let below = corner + columns as u32;
+                self.geometry.indices.extend_from_slice(&[
+                    corner,
+                    below,
+                    corner + 1,
+                    corner + 1,
+                    below,
+                    below + 1,
+                ]);
+            }
+        }
+
+        let mesh = Mesh {
+            name: name.to_string(),
+            primitives: vec![Primitive {
+                first_vertex,
+                first_index,
+                number_of_vertices: columns * rows,
+                number_of_indices: self.geometry.indices.len() - first_index,
+                material_index,
+                morph_targets: Vec::new(),
+                bounding_box,
+            }],
+            weights: Vec::new(),
+        };
+        self.geometry.meshes.insert(name.to_string(), mesh);
+        self.mark_geometry_changed();
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{no_components, Entity, Pooled, RigidBody, Transform, World};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{EntityStore, IntoQuery},
+    nalgebra_glm as glm,
+    rapier3d::geometry::{ColliderHandle, InteractionGroups, Ray},
+    serde::{Deserialize, Serialize},
+};
+use std::collections::HashMap;
+
+/// A fast moving object that sweeps a ray along its path each step
+/// instead of relying on rigid body collisions, so it cannot tunnel through thin geometry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Projectile {
+    pub velocity: glm::Vec3,
+    pub gravity_scale: f32,
+
+    /// Fraction of velocity lost per second
+    pub drag: f32,
+
+    /// Seconds before the projectile is returned to the pool without hitting anything
+    pub lifetime: f32,
+    pub age: f32,
+    pub damage: f32,
+    pub collision_groups: InteractionGroups,
+
+    /// The entity that fired the projectile, which it will not collide with
+    pub owner: Option<Entity>,
+}
+
+impl Projectile {
+    pub fn new(velocity: glm::Vec3) -> Self {
+        Self {
+            velocity,
+            gravity_scale: 1.0,
+            drag: 0.0,
+            lifetime: 5.0,
+            age: 0.0,
+            damage: 0.0,
+            collision_groups: InteractionGroups::all(),
+            owner: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectileImpact {
+    pub projectile: Entity,
+    pub target: Option<Entity>,
+    pub point: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub velocity: glm::Vec3,
+    pub damage: f32,
+}
+
+/// The pool projectiles are fired from
+pub const PROJECTILE_POOL: &str = "Projectile";
+
+impl World {
+    /// Fires a projectile from the projectile pool, which grows as needed
+    pub fn spawn_projectile(
+        &mut self,
+        position: glm::Vec3,
+        projectile: Projectile,
+    ) -> Result<Entity> {
+        if !self.pools.contains_key(PROJECTILE_POOL) {
+            self.create_pool(PROJECTILE_POOL, 0, no_components)?;
+        }
+        let entity = self.acquire(PROJECTILE_POOL)?;
+        {
+            let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+            *entry.get_component_mut::<Transform>()? = Transform {
+                translation: position,
+                ..Default::default()
+            };
+            entry.add_component(projectile);
+        }
+        self.changes.mark_changed::<Transform>(entity);
+        self.changes.mark_changed::<Projectile>(entity);
+        Ok(entity)
+    }
+
+    /// Returns a projectile to its pool, or removes it if it wasn't fired from one
+    pub fn deactivate_projectile(&mut self, entity: Entity) -> Result<()> {
+        let pooled = self
+            .ecs
+            .entry_ref(entity)
+            .context("Failed to find entity!")?
+            .get_component::<Pooled>()
+            .is_ok();
+        if pooled {
+            self.release(entity)
+        } else {
+            self.remove_entity(entity)
+        }
+    }
+
+    /// Moves active projectiles, recording impacts in `projectile_impacts`
+    pub fn update_projectiles(&mut self, delta_time: f32) -> Result<()> {
+        self.projectile_impacts.clear();
+
+        let mut bodies = HashMap::new();
+        let mut query = <(Entity, &RigidBody)>::query();
+        for (entity, rigid_body) in query.iter(&self.ecs) {
+            bodies.insert(*entity, rigid_body.handle);
+        }
+
+        let gravity = self.physics.gravity;
+        let mut expired = Vec::new();
+        let mut query = <(Entity, &mut Projectile, &mut Transform, Option<&Pooled>)>::query();
+        for (entity, projectile, transform, pooled) in query.iter_mut(&mut self.ecs) {
+            // Projectiles returned to the pool keep their component until they are fired again
+            if pooled.map_or(false, |pooled| !pooled.in_use) {
+                continue;
+            }
+
+            projectile.age += delta_time;
+            if projectile.age >= projectile.lifetime {
+                expired.push(*entity);
+                continue;
+            }
+
+            projectile.velocity += gravity * projectile.gravity_scale * delta_time;
+            projectile.velocity *= (1.0 - projectile.drag * delta_time).max(0.0);
+
+            let displacement = projectile.velocity * delta_time;
+            let distance = glm::length(&displacement);
+            if distance <= f32::EPSILON {
+                continue;
+            }
+
+            let owner_body = projectile
+                .owner
+                .and_then(|owner| bodies.get(&owner).cloned());
+            let colliders = &self.physics.colliders;
+            let filter = |handle: ColliderHandle| match owner_body {
+                Some(owner_body) => colliders
+                    .get(handle)
+                    .map_or(true, |collider| collider.parent() != Some(owner_body)),
+                None => true,
+            };
+
+            let ray = Ray::new(transform.translation.into(), displacement / distance);
+            let hit = self.physics.query_pipeline.cast_ray_and_get_normal(
+                colliders,
+                &ray,
+                distance,
+                true,
+                projectile.collision_groups,
+                Some(&filter),
+            );
+
+            match hit {
+                Some((handle, intersection)) => {
+                    let point = ray.point_at(intersection.toi);
+                    transform.translation = point.coords;
+                    let target = colliders
+                        .get(handle)
+                        .and_then(|collider| collider.parent())
+                        .and_then(|parent| {
+                            bodies
+                                .iter()
+                                .find(|(_, body)| **body == parent)
+                                .map(|(entity, _)| *entity)
+                        });
+                    self.projectile_impacts.push(ProjectileImpact {
+                        projectile: *entity,
+                        target,
+                        point: point.coords,
+                        normal: intersection.normal,
+                        velocity: projectile.velocity,
+                        damage: projectile.damage,
+                    });
+                    expired.push(*entity);
+                }
+                None => {
+                    transform.translation += displacement;
+                }
+            }
+            self.changes.mark_changed::<Transform>(*entity);
+        }
+
+        for entity in expired {
+            self.deactivate_projectile(entity)?;
+        }
+        Ok(())
+    }
+}
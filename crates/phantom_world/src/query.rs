@@ -0,0 +1,100 @@
+use crate::{ChangeFilter, ChangeTracker, Ecs, Entity, World};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{
+        query::{DefaultFilter, Query, View},
+        storage::Component,
+        IntoQuery,
+    },
+};
+
+/// A typed query over a world's components, such as `(&Transform, &mut RigidBody)`,
+/// optionally narrowed to entities whose components were added or changed after a tick.
+/// Every component the query borrows mutably is marked as changed for each entity it visits.
+pub struct WorldQuery<'a, Q: IntoQuery> {
+    ecs: &'a mut Ecs,
+    changes: &'a mut ChangeTracker,
+    query: Query<Q::View, <Q::View as DefaultFilter>::Filter>,
+    filters: Vec<ChangeFilter>,
+}
+
+impl<'a, Q: IntoQuery> WorldQuery<'a, Q> {
+    /// Only entities whose `T` was added after `since`
+    pub fn added<T: Component>(mut self, since: u64) -> Self {
+        self.filters.push(ChangeFilter::added::<T>(since));
+        self
+    }
+
+    /// Only entities whose `T` was added or modified after `since`
+    pub fn changed<T: Component>(mut self, since: u64) -> Self {
+        self.filters.push(ChangeFilter::changed::<T>(since));
+        self
+    }
+
+    /// Runs the action on each matching entity, then marks what it borrowed mutably as changed
+    pub fn for_each(self, action: impl FnMut(Entity, <Q::View as View<'a>>::Element)) {
+        self.visit(true, action);
+    }
+
+    /// The entities the query matches, without marking anything as changed
+    pub fn entities(self) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        self.visit(false, |entity, _| entities.push(entity));
+        entities
+    }
+
+    fn visit(
+        self,
+        mark_writes: bool,
+        mut action: impl FnMut(Entity, <Q::View as View<'a>>::Element),
+    ) {
+        let Self {
+            ecs,
+            changes,
+            mut query,
+            filters,
+        } = self;
+        let mut visited = Vec::new();
+        for chunk in query.iter_chunks_mut(ecs) {
+            for (entity, components) in chunk.into_iter_entities() {
+                if filters.iter().all(|filter| changes.matches(filter, entity)) {
+                    action(entity, components);
+                    visited.push(entity);
+                }
+            }
+        }
+        if !mark_writes {
+            return;
+        }
+
+        let writes = <Q::View as View<'a>>::writes_types();
+        for component in writes.as_ref().iter() {
+            for entity in visited.iter() {
+                changes.mark_changed_type(component.type_id(), *entity);
+            }
+        }
+    }
+}
+
+impl World {
+    /// Queries the entities with every component in `Q`, such as
+    /// `world.query::<(&Transform, &mut RigidBody)>().changed::<Transform>(last_run)`
+    pub fn query<Q: IntoQuery>(&mut self) -> WorldQuery<'_, Q> {
+        WorldQuery {
+            ecs: &mut self.ecs,
+            changes: &mut self.changes,
+            query: Q::query(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Adds a component, recording it as added for change detection
+    pub fn add_component<T: Component>(&mut self, entity: Entity, component: T) -> Result<()> {
+        self.ecs
+            .entry(entity)
+            .context("Failed to find entity!")?
+            .add_component(component);
+        self.changes.mark_added::<T>(entity);
+        Ok(())
+    }
+}
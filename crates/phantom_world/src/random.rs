@@ -0,0 +1,183 @@
+use phantom_dependencies::{
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A seeded permuted congruential generator (PCG32).
+/// Its whole state is serialized, so a restored stream continues with the same numbers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct RandomStream {
+    state: u64,
+    increment: u64,
+}
+
+impl RandomStream {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64) -> Self {
+        Self::with_sequence(seed, 0)
+    }
+
+    /// Streams with the same seed but different sequences produce unrelated numbers
+    pub fn with_sequence(seed: u64, sequence: u64) -> Self {
+        let mut stream = Self {
+            state: 0,
+            increment: (sequence << 1) | 1,
+        };
+        stream.next_u32();
+        stream.state = stream.state.wrapping_add(seed);
+        stream.next_u32();
+        stream
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let rotation = (state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// A number from 0 up to, but not including, 1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// A number from `min` up to, but not including, `max`
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// True with the given probability, from 0 to 1
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.range_i32(0, items.len() as i32) as usize)
+    }
+
+    /// An index picked with probability proportional to its weight
+    pub fn weighted(&mut self, weights: &[f32]) -> Option<usize> {
+        let total = weights.iter().map(|weight| weight.max(0.0)).sum::<f32>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut remaining = self.next_f32() * total;
+        for (index, weight) in weights.iter().enumerate() {
+            remaining -= weight.max(0.0);
+            if remaining < 0.0 {
+                return Some(index);
+            }
+        }
+        weights.iter().rposition(|weight| *weight > 0.0)
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            let other = self.range_i32(0, index as i32 + 1) as usize;
+            items.swap(index, other);
+        }
+    }
+
+    /// A direction picked uniformly over the unit sphere
+    pub fn unit_vector(&mut self) -> glm::Vec3 {
+        let z = self.range_f32(-1.0, 1.0);
+        let angle = self.range_f32(0.0, std::f32::consts::TAU);
+        let radius = (1.0 - z * z).sqrt();
+        glm::vec3(radius * angle.cos(), radius * angle.sin(), z)
+    }
+}
+
+/// Named random streams that are saved with the world, so replays and
+/// procedural generation are reproducible from the same seed.
+/// Each system draws from its own stream, so cosmetic effects
+/// using more or fewer numbers don't change gameplay outcomes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct RandomStreams {
+    seed: u64,
+    streams: BTreeMap<String, RandomStream>,
+}
+
+impl Default for RandomStreams {
+    /// Seeded from the clock, use `reseed` for deterministic runs
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        Self::new(seed)
+    }
+}
+
+impl RandomStreams {
+    pub const GAMEPLAY: &'static str = "gameplay";
+    pub const VFX: &'static str = "vfx";
+    pub const AI: &'static str = "ai";
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts every stream from a new seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// The stream with the given name, created from the seed the first time it is used
+    pub fn stream(&mut self, name: &str) -> &mut RandomStream {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| RandomStream::with_sequence(seed, hash_name(name)))
+    }
+
+    pub fn gameplay(&mut self) -> &mut RandomStream {
+        self.stream(Self::GAMEPLAY)
+    }
+
+    pub fn vfx(&mut self) -> &mut RandomStream {
+        self.stream(Self::VFX)
+    }
+
+    pub fn ai(&mut self) -> &mut RandomStream {
+        self.stream(Self::AI)
+    }
+}
+
+/// FNV-1a, which unlike the standard library's hasher is stable across runs and platforms
+fn hash_name(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,492 @@
+use crate::{
+    AlphaMode, AssetId, AudioSource, Camera, Entity, Light, LightKind, Material, Name,
+    OrthographicCamera, PerspectiveCamera, Projection, Socket, Transform, World,
+};
+use phantom_dependencies::{
+    anyhow::{bail, Context, Result},
+    lazy_static::lazy_static,
+    legion::storage::Component,
+    nalgebra_glm as glm,
+    uuid::Uuid,
+};
+use std::{any::Any, sync::RwLock};
+
+/// A mutable view of one field of a reflected value
+pub enum FieldMut<'a> {
+    Bool(&'a mut bool),
+    Float(&'a mut f32),
+
+    /// A float limited to an inclusive range
+    Ranged(&'a mut f32, f32, f32),
+
+    /// A float that's stored in radians and shown in degrees
+    Angle(&'a mut f32),
+
+    Text(&'a mut String),
+    Vec3(&'a mut glm::Vec3),
+    Rotation(&'a mut glm::Quat),
+    Color(&'a mut glm::Vec3),
+    ColorAlpha(&'a mut glm::Vec4),
+    Choice(&'a mut dyn Choice),
+
+    /// A name picked from options that are only known at runtime, such as a skeleton's joints
+    Listed(&'a mut String, &'a [String]),
+}
+
+/// An enum whose variant can be picked by name
+pub trait Choice {
+    fn options(&self) -> &'static [&'static str];
+
+    fn selected(&self) -> usize;
+
+    /// Switches to the variant at the index, keeping what data it can
+    fn select(&mut self, index: usize);
+}
+
+/// Receives the fields of a reflected value, such as the editor's inspector
+pub trait Inspector {
+    /// Returns whether the field was changed
+    fn field(&mut self, name: &str, value: FieldMut) -> bool;
+}
+
+/// Lets tools enumerate and edit a value's fields without knowing its type.
+/// Fields are passed one at a time, so fields that depend on an enum's variant can follow it.
+pub trait Reflect {
+    fn reflect(&mut self, inspector: &mut dyn Inspector);
+}
+
+/// Forwards fields to another inspector, remembering whether any of them changed
+struct ChangeRecorder<'a> {
+    inspector: &'a mut dyn Inspector,
+    changed: bool,
+}
+
+impl<'a> Inspector for ChangeRecorder<'a> {
+    fn field(&mut self, name: &str, value: FieldMut) -> bool {
+        let changed = self.inspector.field(name, value);
+        self.changed |= changed;
+        changed
+    }
+}
+
+/// Reflects a value, returning whether the inspector changed any of its fields
+pub fn reflect_value(value: &mut dyn Reflect, inspector: &mut dyn Inspector) -> bool {
+    let mut recorder = ChangeRecorder {
+        inspector,
+        changed: false,
+    };
+    value.reflect(&mut recorder);
+    recorder.changed
+}
+
+/// A copy of a reflected component, which can be written back to undo edits
+pub struct ComponentSnapshot {
+    name: String,
+    value: Box<dyn Any>,
+}
+
+impl ComponentSnapshot {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+struct ReflectedComponent {
+    name: String,
+    contains: fn(&World, Entity) -> bool,
+    reflect: fn(&mut World, Entity, &mut dyn Inspector) -> bool,
+    snapshot: fn(&World, Entity) -> Option<Box<dyn Any>>,
+    restore: fn(&mut World, Entity, &dyn Any) -> Result<()>,
+}
+
+lazy_static! {
+    static ref REFLECTED_COMPONENTS: RwLock<Vec<ReflectedComponent>> = {
+        let components = vec![
+            reflected_component::<Name>("Name"),
+            reflected_component::<Transform>("Transform"),
+            reflected_component::<Camera>("Camera"),
+            reflected_component::<Light>("Light"),
+            reflected_component::<AudioSource>("Audio Source"),
+            ReflectedComponent {
+                name: "Socket".to_string(),
+                contains: contains_component::<Socket>,
+                reflect: reflect_socket,
+                snapshot: snapshot_component::<Socket>,
+                restore: restore_component::<Socket>,
+            },
+        ];
+        RwLock::new(components)
+    };
+}
+
+fn reflected_component<T: Component + Reflect + Clone>(name: &str) -> ReflectedComponent {
+    ReflectedComponent {
+        name: name.to_string(),
+        contains: contains_component::<T>,
+        reflect: reflect_component::<T>,
+        snapshot: snapshot_component::<T>,
+        restore: restore_component::<T>,
+    }
+}
+
+fn contains_component<T: Component>(world: &World, entity: Entity) -> bool {
+    world
+        .ecs
+        .entry_ref(entity)
+        .map_or(false, |entry| entry.get_component::<T>().is_ok())
+}
+
+fn reflect_component<T: Component + Reflect>(
+    world: &mut World,
+    entity: Entity,
+    inspector: &mut dyn Inspector,
+) -> bool {
+    let changed = match world.ecs.entry(entity) {
+        Some(mut entry) => match entry.get_component_mut::<T>() {
+            Ok(component) => reflect_value(component, inspector),
+            Err(_) => false,
+        },
+        None => false,
+    };
+    if changed {
+        world.changes.mark_changed::<T>(entity);
+    }
+    changed
+}
+
+/// Sockets offer their skeleton's joints to pick from, which have to be looked up in the world
+fn reflect_socket(world: &mut World, entity: Entity, inspector: &mut dyn Inspector) -> bool {
+    let skeleton = world
+        .ecs
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| Some(entry.get_component::<Socket>().ok()?.skeleton))
+        .and_then(|skeleton| world.find_stable_id(skeleton));
+    let joint_names = skeleton
+        .and_then(|skeleton| world.joint_names(skeleton).ok())
+        .unwrap_or_default();
+    let changed = match world.ecs.entry(entity) {
+        Some(mut entry) => match entry.get_component_mut::<Socket>() {
+            Ok(socket) => reflect_value(
+                &mut SocketFields {
+                    socket,
+                    joint_names: &joint_names,
+                },
+                inspector,
+            ),
+            Err(_) => false,
+        },
+        None => false,
+    };
+    if changed {
+        world.changes.mark_changed::<Socket>(entity);
+    }
+    changed
+}
+
+fn snapshot_component<T: Component + Clone>(world: &World, entity: Entity) -> Option<Box<dyn Any>> {
+    let entry = world.ecs.entry_ref(entity).ok()?;
+    let component = entry.get_component::<T>().ok()?;
+    Some(Box::new(component.clone()))
+}
+
+fn restore_component<T: Component + Clone>(
+    world: &mut World,
+    entity: Entity,
+    value: &dyn Any,
+) -> Result<()> {
+    let value = value
+        .downcast_ref::<T>()
+        .context("The snapshot holds a different type of component!")?;
+    world.modify_component::<T>(entity, |component| *component = value.clone())
+}
+
+/// Makes a component editable in tools such as the editor's inspector.
+/// Registering a name again replaces the earlier registration.
+pub fn register_reflected_component<T: Component + Reflect + Clone>(name: &str) -> Result<()> {
+    let mut components = REFLECTED_COMPONENTS
+        .write()
+        .expect("Failed to access the reflected components!");
+    components.retain(|component| component.name != name);
+    components.push(reflected_component::<T>(name));
+    Ok(())
+}
+
+impl World {
+    /// The names of the reflected components the entity has, in registration order
+    pub fn reflected_components(&self, entity: Entity) -> Vec<String> {
+        REFLECTED_COMPONENTS
+            .read()
+            .expect("Failed to access the reflected components!")
+            .iter()
+            .filter(|component| (component.contains)(self, entity))
+            .map(|component| component.name.to_string())
+            .collect()
+    }
+
+    /// Passes the fields of the entity's component to the inspector and
+    /// marks the component as changed if any of them were edited
+    pub fn reflect_component(
+        &mut self,
+        entity: Entity,
+        name: &str,
+        inspector: &mut dyn Inspector,
+    ) -> Result<bool> {
+        let reflect = match REFLECTED_COMPONENTS
+            .read()
+            .expect("Failed to access the reflected components!")
+            .iter()
+            .find(|component| component.name == name)
+        {
+            Some(component) => component.reflect,
+            None => bail!("No reflected component is registered as '{}'!", name),
+        };
+        Ok(reflect(self, entity, inspector))
+    }
+
+    /// Copies the entity's reflected component so edits to it can be undone
+    pub fn snapshot_component(&self, entity: Entity, name: &str) -> Option<ComponentSnapshot> {
+        let snapshot = REFLECTED_COMPONENTS
+            .read()
+            .expect("Failed to access the reflected components!")
+            .iter()
+            .find(|component| component.name == name)?
+            .snapshot;
+        Some(ComponentSnapshot {
+            name: name.to_string(),
+            value: snapshot(self, entity)?,
+        })
+    }
+
+    /// Writes a snapshot back to the entity's component, marking it as changed
+    pub fn restore_component(
+        &mut self,
+        entity: Entity,
+        snapshot: &ComponentSnapshot,
+    ) -> Result<()> {
+        let restore = match REFLECTED_COMPONENTS
+            .read()
+            .expect("Failed to access the reflected components!")
+            .iter()
+            .find(|component| component.name == snapshot.name)
+        {
+            Some(component) => component.restore,
+            None => bail!(
+                "No reflected component is registered as '{}'!",
+                snapshot.name
+            ),
+        };
+        restore(self, entity, snapshot.value.as_ref())
+    }
+}
+
+impl Reflect for Name {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field("Name", FieldMut::Text(&mut self.0));
+    }
+}
+
+impl Reflect for Transform {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field("Translation", FieldMut::Vec3(&mut self.translation));
+        inspector.field("Rotation", FieldMut::Rotation(&mut self.rotation));
+        inspector.field("Scale", FieldMut::Vec3(&mut self.scale));
+    }
+}
+
+impl Reflect for Camera {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field("Name", FieldMut::Text(&mut self.name));
+        inspector.field("Enabled", FieldMut::Bool(&mut self.enabled));
+        inspector.field("Projection", FieldMut::Choice(&mut self.projection));
+        match &mut self.projection {
+            Projection::Perspective(camera) => {
+                inspector.field("Field Of View", FieldMut::Angle(&mut camera.y_fov_rad));
+                inspector.field("Near", FieldMut::Float(&mut camera.z_near));
+                if let Some(z_far) = camera.z_far.as_mut() {
+                    inspector.field("Far", FieldMut::Float(z_far));
+                }
+            }
+            Projection::Orthographic(camera) => {
+                inspector.field("Width", FieldMut::Float(&mut camera.x_mag));
+                inspector.field("Height", FieldMut::Float(&mut camera.y_mag));
+                inspector.field("Near", FieldMut::Float(&mut camera.z_near));
+                inspector.field("Far", FieldMut::Float(&mut camera.z_far));
+            }
+        }
+    }
+}
+
+impl Choice for Projection {
+    fn options(&self) -> &'static [&'static str] {
+        &["Perspective", "Orthographic"]
+    }
+
+    fn selected(&self) -> usize {
+        match self {
+            Self::Perspective(_) => 0,
+            Self::Orthographic(_) => 1,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if index == self.selected() {
+            return;
+        }
+        *self = match self {
+            Self::Perspective(camera) => Self::Orthographic(OrthographicCamera {
+                x_mag: 1.0,
+                y_mag: 1.0,
+                z_far: camera.z_far.unwrap_or(1000.0),
+                z_near: camera.z_near,
+            }),
+            Self::Orthographic(camera) => Self::Perspective(PerspectiveCamera {
+                aspect_ratio: None,
+                y_fov_rad: 70_f32.to_radians(),
+                z_far: Some(camera.z_far),
+                z_near: camera.z_near,
+            }),
+        };
+    }
+}
+
+impl Reflect for Light {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field("Color", FieldMut::Color(&mut self.color));
+        inspector.field("Intensity", FieldMut::Float(&mut self.intensity));
+        inspector.field("Range", FieldMut::Float(&mut self.range));
+        inspector.field("Kind", FieldMut::Choice(&mut self.kind));
+        if let LightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } = &mut self.kind
+        {
+            inspector.field("Inner Cone", FieldMut::Angle(inner_cone_angle));
+            inspector.field("Outer Cone", FieldMut::Angle(outer_cone_angle));
+        }
+    }
+}
+
+impl Choice for LightKind {
+    fn options(&self) -> &'static [&'static str] {
+        &["Directional", "Point", "Spot"]
+    }
+
+    fn selected(&self) -> usize {
+        match self {
+            Self::Directional => 0,
+            Self::Point => 1,
+            Self::Spot { .. } => 2,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if index == self.selected() {
+            return;
+        }
+        *self = match index {
+            0 => Self::Directional,
+            1 => Self::Point,
+            _ => Self::Spot {
+                inner_cone_angle: 0.0,
+                outer_cone_angle: std::f32::consts::FRAC_PI_4,
+            },
+        };
+    }
+}
+
+impl Reflect for AudioSource {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        let mut clip = self.clip.map(|id| id.0.to_string()).unwrap_or_default();
+        if inspector.field("Clip", FieldMut::Text(&mut clip)) {
+            self.clip = Uuid::parse_str(clip.trim()).ok().map(AssetId);
+        }
+        inspector.field("Volume", FieldMut::Ranged(&mut self.volume, 0.0, 1.0));
+        inspector.field("Looping", FieldMut::Bool(&mut self.looping));
+        inspector.field("Range", FieldMut::Float(&mut self.range));
+    }
+}
+
+/// A socket along with the joints of its skeleton
+struct SocketFields<'a> {
+    socket: &'a mut Socket,
+    joint_names: &'a [String],
+}
+
+impl<'a> Reflect for SocketFields<'a> {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field(
+            "Joint",
+            FieldMut::Listed(&mut self.socket.joint, self.joint_names),
+        );
+        let offset = &mut self.socket.offset;
+        inspector.field("Offset", FieldMut::Vec3(&mut offset.translation));
+        inspector.field("Offset Rotation", FieldMut::Rotation(&mut offset.rotation));
+        inspector.field("Offset Scale", FieldMut::Vec3(&mut offset.scale));
+    }
+}
+
+impl Reflect for Material {
+    fn reflect(&mut self, inspector: &mut dyn Inspector) {
+        inspector.field("Name", FieldMut::Text(&mut self.name));
+        inspector.field(
+            "Base Color",
+            FieldMut::ColorAlpha(&mut self.base_color_factor),
+        );
+        inspector.field("Emissive", FieldMut::Color(&mut self.emissive_factor));
+        inspector.field(
+            "Metallic",
+            FieldMut::Ranged(&mut self.metallic_factor, 0.0, 1.0),
+        );
+        inspector.field(
+            "Roughness",
+            FieldMut::Ranged(&mut self.roughness_factor, 0.0, 1.0),
+        );
+        inspector.field(
+            "Normal Scale",
+            FieldMut::Float(&mut self.normal_texture_scale),
+        );
+        inspector.field(
+            "Occlusion Strength",
+            FieldMut::Ranged(&mut self.occlusion_strength, 0.0, 1.0),
+        );
+        inspector.field("Alpha Mode", FieldMut::Choice(&mut self.alpha_mode));
+        if self.alpha_mode == AlphaMode::Mask {
+            inspector.field(
+                "Alpha Cutoff",
+                FieldMut::Ranged(&mut self.alpha_cutoff, 0.0, 1.0),
+            );
+        }
+        inspector.field("Unlit", FieldMut::Bool(&mut self.is_unlit));
+        inspector.field(
+            "Emissive Strength",
+            FieldMut::Float(&mut self.emissive_strength),
+        );
+        inspector.field(
+            "Transmission",
+            FieldMut::Ranged(&mut self.transmission_factor, 0.0, 1.0),
+        );
+    }
+}
+
+impl Choice for AlphaMode {
+    fn options(&self) -> &'static [&'static str] {
+        &["Opaque", "Mask", "Blend"]
+    }
+
+    fn selected(&self) -> usize {
+        match self {
+            Self::Opaque => 0,
+            Self::Mask => 1,
+            Self::Blend => 2,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        *self = match index {
+            0 => Self::Opaque,
+            1 => Self::Mask,
+            _ => Self::Blend,
+        };
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{Camera, Ecs, Light, MeshRender, Name, RigidBody, Skin, Transform, World};
+use crate::{
+    AnimationPlayer, AssetId, AudioSource, Camera, Cloth, CollisionLayer, Debris, Destructible,
+    Ecs, Enabled, FlyCamera, GravityField, GravityOverride, KinematicPlatform, Light, LipSync,
+    MeshRender, MorphWeights, Name, OrbitCamera, PhysicsMaterial, Pooled, Projectile, RigidBody,
+    Scene, Skin, Socket, StableId, Transform, World, WorldPhysics,
+};
 use phantom_dependencies::{
     anyhow::Result,
     bincode,
@@ -20,9 +25,29 @@ lazy_static! {
         registry.register::<Transform>("transform".to_string());
         registry.register::<Camera>("camera".to_string());
         registry.register::<MeshRender>("mesh".to_string());
+        registry.register::<MorphWeights>("morph_weights".to_string());
         registry.register::<Skin>("skin".to_string());
         registry.register::<Light>("light".to_string());
         registry.register::<RigidBody>("rigid_body".to_string());
+        registry.register::<Enabled>("enabled".to_string());
+        registry.register::<Socket>("socket".to_string());
+        registry.register::<StableId>("stable_id".to_string());
+        registry.register::<AssetId>("asset_id".to_string());
+        registry.register::<LipSync>("lip_sync".to_string());
+        registry.register::<KinematicPlatform>("kinematic_platform".to_string());
+        registry.register::<PhysicsMaterial>("physics_material".to_string());
+        registry.register::<CollisionLayer>("collision_layer".to_string());
+        registry.register::<Cloth>("cloth".to_string());
+        registry.register::<Destructible>("destructible".to_string());
+        registry.register::<Debris>("debris".to_string());
+        registry.register::<Projectile>("projectile".to_string());
+        registry.register::<Pooled>("pooled".to_string());
+        registry.register::<GravityOverride>("gravity_override".to_string());
+        registry.register::<GravityField>("gravity_field".to_string());
+        registry.register::<AudioSource>("audio_source".to_string());
+        registry.register::<AnimationPlayer>("animation_player".to_string());
+        registry.register::<OrbitCamera>("orbit_camera".to_string());
+        registry.register::<FlyCamera>("fly_camera".to_string());
         Arc::new(RwLock::new(registry))
     };
     pub static ref ENTITY_SERIALIZER: Canon = Canon::default();
@@ -71,3 +96,54 @@ pub fn world_from_bytes(bytes: &[u8]) -> Result<World> {
         bincode::deserialize(bytes)
     })?)
 }
+
+fn serialize_ecs_ref<S>(ecs: &&Ecs, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_ecs(ecs, serializer)
+}
+
+#[derive(Serialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct EntitySnapshotRef<'a> {
+    #[serde(serialize_with = "serialize_ecs_ref")]
+    ecs: &'a Ecs,
+    physics: &'a WorldPhysics,
+    scene: &'a Scene,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+struct EntitySnapshot {
+    #[serde(deserialize_with = "deserialize_ecs")]
+    ecs: Ecs,
+    physics: WorldPhysics,
+    scene: Scene,
+}
+
+/// Serializes the world's entities, physics and scenegraphs without its assets,
+/// which is far smaller than the whole world when only entities are about to change
+pub fn entities_as_bytes(world: &World) -> Result<Vec<u8>> {
+    let snapshot = EntitySnapshotRef {
+        ecs: &world.ecs,
+        physics: &world.physics,
+        scene: &world.scene,
+    };
+    Ok(set_entity_serializer(&*ENTITY_SERIALIZER, || {
+        bincode::serialize(&snapshot)
+    })?)
+}
+
+/// Replaces the world's entities, physics and scenegraphs with ones from `entities_as_bytes`
+pub fn restore_entities_from_bytes(world: &mut World, bytes: &[u8]) -> Result<()> {
+    let snapshot: EntitySnapshot =
+        set_entity_serializer(&*ENTITY_SERIALIZER, || bincode::deserialize(bytes))?;
+    world.ecs = snapshot.ecs;
+    world.physics = snapshot.physics;
+    world.scene = snapshot.scene;
+    world.changes.clear();
+    world.rebuild_pools();
+    world.refresh_disabled_entities();
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use phantom_dependencies::{
     anyhow::Result,
     legion,
-    petgraph::{graph::WalkNeighbors, prelude::*},
+    petgraph::{prelude::*, stable_graph::WalkNeighbors},
     serde::{Deserialize, Serialize},
 };
 use std::ops::{Index, IndexMut};
@@ -9,9 +9,10 @@ use std::ops::{Index, IndexMut};
 pub type Ecs = legion::World;
 pub type Entity = legion::Entity;
 
+/// A stable graph, so removing a node leaves the indices of every other node unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
-pub struct SceneGraph(pub Graph<Entity, ()>);
+pub struct SceneGraph(pub StableGraph<Entity, ()>);
 
 impl Default for SceneGraph {
     fn default() -> Self {
@@ -21,11 +22,11 @@ impl Default for SceneGraph {
 
 impl SceneGraph {
     pub fn new() -> Self {
-        Self(Graph::<Entity, ()>::new())
+        Self(StableGraph::<Entity, ()>::new())
     }
 
     pub fn number_of_nodes(&self) -> usize {
-        self.0.raw_nodes().len()
+        self.0.node_count()
     }
 
     pub fn add_node(&mut self, node: Entity) -> NodeIndex {
@@ -36,6 +37,31 @@ impl SceneGraph {
         let _edge_index = self.0.add_edge(parent_node, node, ());
     }
 
+    /// Removes a node, leaving any children it had as root nodes
+    pub fn remove_node(&mut self, index: NodeIndex) -> Option<Entity> {
+        self.0.remove_node(index)
+    }
+
+    /// Disconnects a node from its parent, making it a root node
+    pub fn detach(&mut self, index: NodeIndex) {
+        if let Some(edge) = self
+            .parent_of(index)
+            .and_then(|parent| self.0.find_edge(parent, index))
+        {
+            self.0.remove_edge(edge);
+        }
+    }
+
+    /// The node and every node beneath it, with each node's parent listed before it
+    pub fn subtree(&self, index: NodeIndex) -> Vec<NodeIndex> {
+        let mut dfs = Dfs::new(&self.0, index);
+        let mut nodes = Vec::new();
+        while let Some(node_index) = dfs.next(&self.0) {
+            nodes.push(node_index);
+        }
+        nodes
+    }
+
     pub fn collect_nodes(&self) -> Result<Vec<SceneGraphNode>> {
         let mut nodes = Vec::new();
         let mut linear_offset = 0;
@@ -52,6 +78,23 @@ impl SceneGraph {
         incoming_walker.next_node(&self.0)
     }
 
+    pub fn root_nodes(&self) -> Vec<NodeIndex> {
+        self.0
+            .node_indices()
+            .filter(|node_index| !self.has_parents(*node_index))
+            .collect()
+    }
+
+    pub fn children(&self, index: NodeIndex) -> Vec<NodeIndex> {
+        let mut children = self
+            .0
+            .neighbors_directed(index, Outgoing)
+            .collect::<Vec<_>>();
+        // Neighbors are visited in reverse order of insertion
+        children.reverse();
+        children
+    }
+
     pub fn walk(&self, mut action: impl FnMut(NodeIndex) -> Result<()>) -> Result<()> {
         for node_index in self.0.node_indices() {
             if self.has_parents(node_index) {
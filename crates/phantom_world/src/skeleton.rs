@@ -0,0 +1,186 @@
+use crate::{
+    Animation, Channel, Entity, Name, SampledValue, Skin, Transform, TransformationSet, World,
+};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    bincode,
+    legion::EntityStore,
+    nalgebra_glm as glm,
+    serde::{Deserialize, Serialize},
+};
+use std::{collections::HashMap, path::Path};
+
+/// Maps source bone names to target bone names
+pub type BoneMap = HashMap<String, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Skeleton {
+    pub name: String,
+    pub bones: Vec<Bone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub rest_transform: Transform,
+}
+
+impl Skeleton {
+    pub fn bone_index(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    /// The distance from a bone to its parent in the rest pose
+    pub fn bone_length(&self, index: usize) -> f32 {
+        glm::length(&self.bones[index].rest_transform.translation)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, bincode::serialize(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(bincode::deserialize(&std::fs::read(path)?)?)
+    }
+}
+
+impl World {
+    /// Builds a skeleton from the joints of a skinned entity, using their current transforms as the rest pose
+    pub fn skeleton_from_skin(&self, skin_entity: Entity) -> Result<Skeleton> {
+        let entry = self.ecs.entry_ref(skin_entity)?;
+        let skin = entry.get_component::<Skin>()?;
+        let joint_entities = skin
+            .joints
+            .iter()
+            .map(|joint| joint.target)
+            .collect::<Vec<_>>();
+        let joint_names = self.joint_names(skin_entity)?;
+
+        let mut bones = Vec::new();
+        for (joint_entity, name) in joint_entities.iter().zip(joint_names.into_iter()) {
+            let rest_transform = *self
+                .ecs
+                .entry_ref(*joint_entity)?
+                .get_component::<Transform>()?;
+            let parent = self.scene.graphs.iter().find_map(|graph| {
+                let parent_index = graph.parent_of(graph.find_node(*joint_entity)?)?;
+                joint_entities
+                    .iter()
+                    .position(|entity| *entity == graph[parent_index])
+            });
+            bones.push(Bone {
+                name,
+                parent,
+                rest_transform,
+            });
+        }
+
+        let name = match self.ecs.entry_ref(skin_entity)?.get_component::<Name>() {
+            Ok(name) => name.0.to_string(),
+            Err(_) => skin.name.to_string(),
+        };
+        Ok(Skeleton { name, bones })
+    }
+
+    /// Creates a copy of an animation authored on one skinned entity that plays on another.
+    /// Bones missing from the bone map are matched by name. Translations are scaled by
+    /// the ratio of the bone lengths, and rotations are applied relative to each rig's rest pose.
+    pub fn retarget_animation(
+        &self,
+        animation: &Animation,
+        source_skin: Entity,
+        target_skin: Entity,
+        bone_map: &BoneMap,
+    ) -> Result<Animation> {
+        let source_skeleton = self.skeleton_from_skin(source_skin)?;
+        let target_skeleton = self.skeleton_from_skin(target_skin)?;
+        let source_joints = self.skin_joint_entities(source_skin)?;
+        let target_joints = self.skin_joint_entities(target_skin)?;
+
+        let mut channels = Vec::new();
+        for channel in animation.channels.iter() {
+            let source_index = match source_joints
+                .iter()
+                .position(|entity| *entity == channel.target)
+            {
+                Some(source_index) => source_index,
+                None => continue,
+            };
+            let source_bone = &source_skeleton.bones[source_index];
+            let target_name = bone_map.get(&source_bone.name).unwrap_or(&source_bone.name);
+            let target_index = match target_skeleton.bone_index(target_name) {
+                Some(target_index) => target_index,
+                None => continue,
+            };
+            let target_bone = &target_skeleton.bones[target_index];
+
+            let transformations = match &channel.transformations {
+                TransformationSet::Translations(translations) => {
+                    let source_length = source_skeleton.bone_length(source_index);
+                    let ratio = if source_length > f32::EPSILON {
+                        target_skeleton.bone_length(target_index) / source_length
+                    } else {
+                        1.0
+                    };
+                    TransformationSet::Translations(
+                        translations
+                            .iter()
+                            .map(|translation| translation * ratio)
+                            .collect(),
+                    )
+                }
+                TransformationSet::Rotations(_) | TransformationSet::QuantizedRotations(_) => {
+                    let source_rest = source_bone.rest_transform.rotation;
+                    let target_rest = target_bone.rest_transform.rotation;
+                    let rest_offset = target_rest * glm::quat_inverse(&source_rest);
+                    let rotations = channel_rotations(channel)
+                        .into_iter()
+                        .map(|rotation| (rest_offset * rotation).coords)
+                        .collect();
+                    TransformationSet::Rotations(rotations)
+                }
+                TransformationSet::Scales(scales) => TransformationSet::Scales(scales.clone()),
+                TransformationSet::MorphTargetWeights(_) => continue,
+            };
+
+            channels.push(Channel {
+                target: target_joints[target_index],
+                inputs: channel.inputs.clone(),
+                transformations,
+                _interpolation: channel._interpolation,
+            });
+        }
+
+        Ok(Animation {
+            name: format!("{} ({})", animation.name, target_skeleton.name),
+            time: 0.0,
+            channels,
+            max_animation_time: animation.max_animation_time,
+            root_motion: None,
+        })
+    }
+
+    fn skin_joint_entities(&self, skin_entity: Entity) -> Result<Vec<Entity>> {
+        let entry = self.ecs.entry_ref(skin_entity)?;
+        let skin = entry
+            .get_component::<Skin>()
+            .ok()
+            .context("Entity does not have a skin!")?;
+        Ok(skin.joints.iter().map(|joint| joint.target).collect())
+    }
+}
+
+fn channel_rotations(channel: &Channel) -> Vec<glm::Quat> {
+    let mut rotations = Vec::new();
+    for input in channel.inputs.iter() {
+        if let Some(sample) = channel.sample(*input) {
+            if let SampledValue::Rotation(rotation) = sample.value {
+                rotations.push(rotation);
+            }
+        }
+    }
+    rotations
+}
@@ -0,0 +1,117 @@
+use crate::{ChangeTracker, Entity, Name, StableId, Transform, World};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{storage::Component, world::Entry},
+};
+
+type AddComponent = Box<dyn FnOnce(&mut Entry)>;
+
+/// Describes an entity to add with `World::spawn`.
+/// Nothing is added to the world until `build` is called.
+#[must_use = "The entity is only spawned when the builder is built"]
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    transform: Transform,
+    parent: Option<Entity>,
+    components: Vec<AddComponent>,
+
+    /// Records each component as added for change detection
+    additions: Vec<fn(&mut ChangeTracker, Entity)>,
+}
+
+impl<'a> EntityBuilder<'a> {
+    pub fn with<T: Component>(mut self, component: T) -> Self {
+        self.components.push(Box::new(move |entry: &mut Entry| {
+            entry.add_component(component)
+        }));
+        self.additions.push(ChangeTracker::mark_added::<T>);
+        self
+    }
+
+    pub fn name(self, name: impl Into<String>) -> Self {
+        self.with(Name(name.into()))
+    }
+
+    /// Relative to the parent, if the entity has one
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Places the entity beneath another in the scenegraph instead of at the root of the default one
+    pub fn parent(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Adds the entity and its components to the world and the scenegraph
+    pub fn build(self) -> Result<Entity> {
+        let Self {
+            world,
+            transform,
+            parent,
+            components,
+            additions,
+        } = self;
+
+        // The scenegraph is resolved before the entity is pushed, so a failure doesn't leave it behind
+        let (graph_index, parent_index) = match parent {
+            Some(parent) => {
+                let (graph_index, parent_index) = world
+                    .scene
+                    .graphs
+                    .iter()
+                    .enumerate()
+                    .find_map(|(graph_index, graph)| Some((graph_index, graph.find_node(parent)?)))
+                    .context("The parent isn't in the scenegraph!")?;
+                (graph_index, Some(parent_index))
+            }
+            None => {
+                // The default scenegraph is the first one
+                world.scene.default_scenegraph_mut()?;
+                (0, None)
+            }
+        };
+
+        let entity = world.ecs.push((transform, StableId::generate()));
+        let mut entry = world.ecs.entry(entity).context("Failed to find entity!")?;
+        for add_component in components {
+            add_component(&mut entry);
+        }
+        world.changes.mark_added::<Transform>(entity);
+        world.changes.mark_added::<StableId>(entity);
+        for mark_added in additions {
+            mark_added(&mut world.changes, entity);
+        }
+
+        let graph = &mut world.scene.graphs[graph_index];
+        let index = graph.add_node(entity);
+        if let Some(parent_index) = parent_index {
+            graph.add_edge(parent_index, index);
+        }
+        Ok(entity)
+    }
+}
+
+impl World {
+    /// Starts describing a new entity, which always has a transform, a stable id and a scenegraph node
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        EntityBuilder {
+            world: self,
+            transform: Transform::default(),
+            parent: None,
+            components: Vec::new(),
+            additions: Vec::new(),
+        }
+    }
+
+    /// Removes an entity and everything beneath it in the scenegraph,
+    /// along with their rigid bodies and colliders
+    pub fn despawn(&mut self, entity: Entity) -> Result<()> {
+        // Children are removed before their parents
+        for descendant in self.subtree(entity).into_iter().rev() {
+            self.remove_entity(descendant)?;
+        }
+        Ok(())
+    }
+}
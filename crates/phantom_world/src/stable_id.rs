@@ -0,0 +1,88 @@
+use crate::{Entity, World};
+use phantom_dependencies::{
+    anyhow::{Context, Result},
+    legion::{EntityStore, IntoQuery},
+    serde::{Deserialize, Serialize},
+    serde_json,
+    uuid::Uuid,
+};
+use std::path::{Path, PathBuf};
+
+/// Identifies an entity across saves and scene reorganization.
+/// Components that refer to other entities store this instead of an `Entity`,
+/// and resolve it with `World::find_stable_id`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct StableId(pub Uuid);
+
+impl StableId {
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// The asset an imported entity was created from,
+/// found by its id rather than its path so the asset can be renamed or moved
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AssetId(pub Uuid);
+
+/// The contents of an asset's `.meta` sidecar file, kept next to the asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct AssetMeta {
+    pub uuid: Uuid,
+}
+
+/// Where an asset's sidecar is stored, such as `models/helmet.glb.meta`
+pub fn asset_meta_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut meta_path = path.as_ref().as_os_str().to_os_string();
+    meta_path.push(".meta");
+    PathBuf::from(meta_path)
+}
+
+/// Reads an asset's id from its sidecar, writing a new sidecar if the asset doesn't have one.
+/// The sidecar has to be moved along with the asset for the id to follow it.
+pub fn asset_id(path: impl AsRef<Path>) -> Result<AssetId> {
+    let meta_path = asset_meta_path(&path);
+    if meta_path.exists() {
+        let meta = serde_json::from_slice::<AssetMeta>(&std::fs::read(&meta_path)?)
+            .context(format!("Failed to read {}!", meta_path.display()))?;
+        return Ok(AssetId(meta.uuid));
+    }
+    let meta = AssetMeta {
+        uuid: Uuid::new_v4(),
+    };
+    std::fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)
+        .context(format!("Failed to write {}!", meta_path.display()))?;
+    Ok(AssetId(meta.uuid))
+}
+
+impl World {
+    /// The entity's stable id, assigning one if it doesn't have one yet
+    pub fn stable_id(&mut self, entity: Entity) -> Result<StableId> {
+        if let Ok(id) = self.ecs.entry_ref(entity)?.get_component::<StableId>() {
+            return Ok(*id);
+        }
+        let id = StableId::generate();
+        self.add_component(entity, id)?;
+        Ok(id)
+    }
+
+    /// The entity with a stable id, if it is still in the world
+    pub fn find_stable_id(&self, id: StableId) -> Option<Entity> {
+        <(Entity, &StableId)>::query()
+            .iter(&self.ecs)
+            .find(|(_, entity_id)| **entity_id == id)
+            .map(|(entity, _)| *entity)
+    }
+
+    /// The entities imported from an asset
+    pub fn find_asset_instances(&self, id: AssetId) -> Vec<Entity> {
+        <(Entity, &AssetId)>::query()
+            .iter(&self.ecs)
+            .filter(|(_, asset_id)| **asset_id == id)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+}
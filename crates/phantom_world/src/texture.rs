@@ -178,6 +178,17 @@ pub struct Material {
     pub occlusion_strength: f32,
     pub emissive_texture_index: i32,
     pub emissive_texture_set: i32,
+
+    /// Scales the emissive factor past one, from KHR_materials_emissive_strength
+    pub emissive_strength: f32,
+
+    /// How much light passes through the surface, from KHR_materials_transmission
+    pub transmission_factor: f32,
+    pub transmission_texture_index: i32,
+    pub transmission_texture_set: i32, // R channel - transmission values
+
+    /// Applied to the texture coordinates of every texture, from KHR_texture_transform
+    pub texture_transform: TextureTransform,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
     pub alpha_mode: AlphaMode,
@@ -190,7 +201,7 @@ impl Default for Material {
         Self {
             name: "<Unnamed>".to_string(),
             base_color_factor: glm::vec4(1.0, 1.0, 1.0, 1.0),
-            emissive_factor: glm::Vec3::identity(),
+            emissive_factor: glm::Vec3::zeros(),
             color_texture_index: -1,
             color_texture_set: -1,
             metallic_roughness_texture_index: -1,
@@ -203,6 +214,11 @@ impl Default for Material {
             occlusion_strength: 1.0,
             emissive_texture_index: -1,
             emissive_texture_set: -1,
+            emissive_strength: 1.0,
+            transmission_factor: 0.0,
+            transmission_texture_index: -1,
+            transmission_texture_set: -1,
+            texture_transform: TextureTransform::default(),
             metallic_factor: 1.0,
             roughness_factor: 1.0,
             alpha_mode: AlphaMode::Opaque,
@@ -212,6 +228,38 @@ impl Default for Material {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct TextureTransform {
+    pub offset: glm::Vec2,
+
+    /// Counterclockwise, in radians
+    pub rotation: f32,
+    pub scale: glm::Vec2,
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: glm::Vec2::zeros(),
+            rotation: 0.0,
+            scale: glm::vec2(1.0, 1.0),
+        }
+    }
+}
+
+impl TextureTransform {
+    /// The top two rows of the 3x3 matrix that transforms texture coordinates,
+    /// which is the translation, then the rotation, then the scale
+    pub fn rows(&self) -> [glm::Vec3; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            glm::vec3(cos * self.scale.x, sin * self.scale.y, self.offset.x),
+            glm::vec3(-sin * self.scale.x, cos * self.scale.y, self.offset.y),
+        ]
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub enum AlphaMode {
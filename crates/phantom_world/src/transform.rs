@@ -4,7 +4,7 @@ use phantom_dependencies::{
     serde::{Deserialize, Serialize},
 };
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Transform {
     pub translation: glm::Vec3,
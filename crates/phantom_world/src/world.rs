@@ -1,23 +1,33 @@
 use crate::{
-    deserialize_ecs, serialize_ecs, world_as_bytes, world_from_bytes, Animation, Camera, Ecs,
-    Entity, Material, PerspectiveCamera, Projection, RigidBody, SceneGraph, SceneGraphNode,
-    Texture, Transform, WorldPhysics,
+    asset_id, deserialize_ecs, gltf_dependencies, load_gltf, serialize_ecs, world_as_bytes,
+    world_from_bytes, Animation, AnimationOptimization, Camera, ChangeTracker, CollisionLayer,
+    CollisionMatrix, ContentHashes, Ecs, Enabled, Entity, EntityPool, Material, Name,
+    PerspectiveCamera, PhysicsMaterial, ProjectileImpact, Projection, RandomStreams, RigidBody,
+    SceneGraph, SceneGraphNode, StableId, Texture, Transform, WorldPhysics,
 };
 use phantom_dependencies::{
     anyhow::{bail, Context, Result},
     bmfont::{BMFont, OrdinateOrientation},
-    legion::{EntityStore, IntoQuery},
-    nalgebra::{Point, Point3},
+    bytemuck,
+    legion::{storage::Component, EntityStore, IntoQuery},
+    log,
+    nalgebra::{Isometry, Point, Point3},
     nalgebra_glm as glm,
     petgraph::prelude::*,
     rapier3d::{
         dynamics::RigidBodyBuilder,
         geometry::{ColliderBuilder, InteractionGroups, Ray},
+        parry::shape::FeatureId,
         prelude::RigidBodyType,
     },
+    rayon::prelude::*,
     serde::{Deserialize, Serialize},
 };
-use std::{collections::HashMap, mem::replace, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::replace,
+    path::Path,
+};
 
 #[derive(Default, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
@@ -32,11 +42,67 @@ pub struct World {
     pub hdr_textures: Vec<Texture>,
     pub geometry: Geometry,
     pub fonts: HashMap<String, SdfFont>,
+    pub random: RandomStreams,
+    #[serde(skip)]
+    pub changes: ChangeTracker,
+    #[serde(skip)]
+    pub projectile_impacts: Vec<ProjectileImpact>,
+    /// Rebuilt from the `Pooled` entities when the world is loaded
+    #[serde(skip)]
+    pub pools: HashMap<String, EntityPool>,
+
+    /// Lets imports reuse identical textures, materials and meshes already in the world
+    #[serde(skip)]
+    pub content_hashes: ContentHashes,
+
+    /// Increased whenever materials are edited in place, so renderers know to upload them again
+    #[serde(skip)]
+    pub materials_revision: u64,
+
+    /// Increased whenever vertices or indices are added or edited, so renderers upload them again
+    #[serde(skip)]
+    pub geometry_revision: u64,
+
+    /// Increased whenever textures are added or edited, so renderers upload them again
+    #[serde(skip)]
+    pub textures_revision: u64,
+
+    /// Whether the renderer simulates cloth that prefers the GPU, which the app sets each frame.
+    /// Any other cloth is stepped on the cpu by `tick`.
+    #[serde(skip)]
+    pub gpu_cloth: bool,
+
+    /// Fixed steps taken since the world was created or loaded,
+    /// so renderers know how far to advance the cloth they simulate
+    #[serde(skip)]
+    pub steps: u64,
+
+    /// Entities disabled directly or through an ancestor, refreshed each tick and whenever
+    /// entities are enabled, disabled, reparented or removed
+    #[serde(skip)]
+    pub(crate) disabled: HashSet<Entity>,
 }
 
 impl World {
     pub const MAIN_CAMERA_NAME: &'static str = &"Main Camera";
 
+    /// The extension of scenes saved with `World::save`
+    pub const SCENE_EXTENSION: &'static str = "phantom";
+
+    /// Scene files start with this, followed by the format version
+    const SCENE_MAGIC: &'static [u8] = b"PHANTOM";
+
+    /// Increased whenever anything serialized with the world changes shape.
+    /// Version 3 added emissive strength, transmission and texture transforms to materials.
+    /// Version 4 added materials to cloth.
+    /// Version 5 refers to socket skeletons by stable id and audio clips by asset id.
+    /// Version 6 pools projectiles instead of marking them inactive.
+    /// Version 7 stores scenegraphs as stable graphs.
+    /// Version 8 stores what disabling changed on rigid bodies and their colliders.
+    /// Version 9 stores per-body gravity overrides along with the gravity scales they replaced.
+    /// Version 10 drops the collider list from rigid bodies, whose colliders are read from physics.
+    const SCENE_VERSION: u32 = 10;
+
     pub fn new() -> Result<World> {
         let mut world = World::default();
         world.initialize()?;
@@ -87,7 +153,8 @@ impl World {
         let light_entity = self.ecs.push((
             transform,
             Light {
-                color: glm::vec3(200.0, 200.0, 200.0),
+                color: glm::vec3(1.0, 1.0, 1.0),
+                intensity: 200.0,
                 kind: LightKind::Point,
                 ..Default::default()
             },
@@ -106,6 +173,43 @@ impl World {
         bail!("The world must have at least one entity with an enabled camera component to render with!")
     }
 
+    /// Moves the active camera so every mesh in the world is in view
+    pub fn frame_camera(&mut self) -> Result<()> {
+        let mut bounds = BoundingBox::new_invalid();
+        let mut query = <(Entity, &MeshRender)>::query();
+        for (entity, mesh_render) in query.iter(&self.ecs) {
+            let mesh = match self.geometry.meshes.get(&mesh_render.name) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let transform = self.entity_global_transform_matrix(*entity)?;
+            let bounding_box = mesh.bounding_box();
+            let (min, max) = (bounding_box.min, bounding_box.max);
+            for corner in 0..8 {
+                let point = glm::vec4(
+                    if corner & 1 == 0 { min.x } else { max.x },
+                    if corner & 2 == 0 { min.y } else { max.y },
+                    if corner & 4 == 0 { min.z } else { max.z },
+                    1.0,
+                );
+                bounds.fit_point((transform * point).xyz());
+            }
+        }
+        if bounds.min.x > bounds.max.x {
+            return Ok(());
+        }
+
+        // Far enough back that a sphere around the bounds fits a 70 degree field of view
+        let radius = glm::length(&bounds.half_extents()).max(0.01);
+        let distance = radius / 35_f32.to_radians().sin();
+        let direction = glm::normalize(&glm::vec3(1.0, 0.75, 1.0));
+        let camera = self.active_camera()?;
+        self.modify_component::<Transform>(camera, |transform| {
+            transform.translation = bounds.center() + direction * distance;
+            transform.look_at(&(-direction), &glm::Vec3::y());
+        })
+    }
+
     pub fn global_transform(&self, graph: &SceneGraph, index: NodeIndex) -> Result<glm::Mat4> {
         let entity = graph[index];
         let transform = match self.ecs.entry_ref(entity)?.get_component::<Transform>() {
@@ -180,6 +284,13 @@ impl World {
         self.animations.clear();
         self.materials.clear();
         self.geometry.clear();
+        self.mark_textures_changed();
+        self.mark_materials_changed();
+        self.mark_geometry_changed();
+        self.changes.clear();
+        self.projectile_impacts.clear();
+        self.pools.clear();
+        self.disabled.clear();
         self.initialize()?;
         Ok(())
     }
@@ -189,11 +300,144 @@ impl World {
         self.materials.get(index).context(error_message)
     }
 
+    /// Call after editing materials in place so renderers upload them again
+    pub fn mark_materials_changed(&mut self) {
+        self.materials_revision += 1;
+    }
+
+    /// Call after adding or editing vertices or indices so renderers upload them again
+    pub fn mark_geometry_changed(&mut self) {
+        self.geometry_revision += 1;
+    }
+
+    /// Call after adding or editing textures so renderers upload them again
+    pub fn mark_textures_changed(&mut self) {
+        self.textures_revision += 1;
+    }
+
+    /// Mutates a component through a closure, recording the change for change detection
+    pub fn modify_component<T: Component>(
+        &mut self,
+        entity: Entity,
+        action: impl FnOnce(&mut T),
+    ) -> Result<()> {
+        let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+        action(entry.get_component_mut::<T>()?);
+        self.changes.mark_changed::<T>(entity);
+        Ok(())
+    }
+
+    /// Runs an action across the thread pool for every entity with a `T` component.
+    /// The world is borrowed immutably, so results are returned to be applied afterwards.
+    pub fn par_map_components<T, R>(
+        &self,
+        action: impl Fn(Entity, &T) -> R + Send + Sync,
+    ) -> Vec<(Entity, R)>
+    where
+        T: Component,
+        R: Send,
+    {
+        let mut query = <(Entity, &T)>::query();
+        let entities = query
+            .iter(&self.ecs)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        entities
+            .par_iter()
+            .filter_map(|entity| {
+                let entry = self.ecs.entry_ref(*entity).ok()?;
+                let component = entry.get_component::<T>().ok()?;
+                Some((*entity, action(*entity, component)))
+            })
+            .collect()
+    }
+
+    pub fn is_enabled(&self, entity: Entity) -> bool {
+        match self.ecs.entry_ref(entity) {
+            Ok(entry) => entry
+                .get_component::<Enabled>()
+                .map_or(true, |enabled| enabled.0),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks the entity's own flag as well as the flags of all of its ancestors
+    pub fn is_enabled_in_hierarchy(&self, entity: Entity) -> bool {
+        for graph in self.scene.graphs.iter() {
+            let mut node_index = match graph.find_node(entity) {
+                Some(node_index) => node_index,
+                None => continue,
+            };
+            while let Some(parent_index) = graph.parent_of(node_index) {
+                if !self.is_enabled(graph[parent_index]) {
+                    return false;
+                }
+                node_index = parent_index;
+            }
+        }
+        self.is_enabled(entity)
+    }
+
+    pub fn set_enabled(&mut self, entity: Entity, enabled: bool) -> Result<()> {
+        self.ecs
+            .entry(entity)
+            .context("Failed to find entity!")?
+            .add_component(Enabled(enabled));
+        self.changes.mark_changed::<Enabled>(entity);
+        self.refresh_disabled_entities();
+        Ok(())
+    }
+
+    /// Every entity that is disabled, either directly or through a disabled ancestor,
+    /// as of the last time the set was refreshed
+    pub fn disabled_entities(&self) -> &HashSet<Entity> {
+        &self.disabled
+    }
+
+    /// Finds the disabled entities again and takes the rigid bodies among them out of the simulation,
+    /// putting back the bodies of entities that are enabled again
+    pub fn refresh_disabled_entities(&mut self) {
+        let mut disabled = HashSet::new();
+        let mut query = <(Entity, &Enabled)>::query();
+        for (entity, enabled) in query.iter(&self.ecs) {
+            if !enabled.0 && !disabled.contains(entity) {
+                disabled.extend(self.subtree(*entity));
+            }
+        }
+
+        let mut query = <(Entity, &RigidBody)>::query();
+        for (entity, rigid_body) in query.iter(&self.ecs) {
+            self.physics
+                .set_enabled(rigid_body.handle, !disabled.contains(entity));
+        }
+        self.disabled = disabled;
+    }
+
+    /// Returns the entity along with all of its descendants in the scenegraph
+    pub fn subtree(&self, entity: Entity) -> Vec<Entity> {
+        let mut entities = vec![entity];
+        for graph in self.scene.graphs.iter() {
+            if let Some(node_index) = graph.find_node(entity) {
+                let mut dfs = Dfs::new(&graph.0, node_index);
+                while let Some(descendant_index) = dfs.next(&graph.0) {
+                    if descendant_index != node_index {
+                        entities.push(graph[descendant_index]);
+                    }
+                }
+            }
+        }
+        entities
+    }
+
     pub fn lights(&self) -> Result<Vec<(Transform, Light)>> {
+        let disabled = &self.disabled;
         let mut lights = Vec::new();
         for graph in self.scene.graphs.iter() {
             graph.walk(|node_index| {
                 let entity = graph[node_index];
+                if disabled.contains(&entity) {
+                    return Ok(());
+                }
                 let node_transform = self.global_transform(graph, node_index)?;
                 if let Ok(light) = self.ecs.entry_ref(entity)?.get_component::<Light>() {
                     lights.push((Transform::from(node_transform), *light));
@@ -205,43 +449,115 @@ impl World {
     }
 
     pub fn joint_matrices(&self) -> Result<Vec<glm::Mat4>> {
-        let mut offset = 0;
-        let mut number_of_joints = 0;
+        let mut joint_matrices = Vec::new();
         for graph in self.scene.graphs.iter() {
             graph.walk(|node_index| {
                 let entity = graph[node_index];
-                if let Ok(skin) = self.ecs.entry_ref(entity)?.get_component::<Skin>() {
-                    number_of_joints += skin.joints.len();
+                if self.ecs.entry_ref(entity)?.get_component::<Skin>().is_ok() {
+                    let node_transform = self.global_transform(graph, node_index)?;
+                    joint_matrices.extend(self.skin_joint_matrices(entity, &node_transform)?);
                 }
                 Ok(())
             })?;
         }
-        let mut joint_matrices = vec![glm::Mat4::identity(); number_of_joints];
-        for graph in self.scene.graphs.iter() {
-            graph.walk(|node_index| {
-                let entity = graph[node_index];
-                let node_transform = self.global_transform(graph, node_index)?;
-                if let Ok(skin) = self.ecs.entry_ref(entity)?.get_component::<Skin>() {
-                    for joint in skin.joints.iter() {
-                        let joint_transform = {
-                            let mut transform = glm::Mat4::identity();
-                            for graph in self.scene.graphs.iter() {
-                                if let Some(index) = graph.find_node(joint.target) {
-                                    transform = self.global_transform(graph, index)?;
-                                }
-                            }
-                            transform
-                        };
-                        joint_matrices[offset] = glm::inverse(&node_transform)
-                            * joint_transform
-                            * joint.inverse_bind_matrix;
-                        offset += 1;
+        Ok(joint_matrices)
+    }
+
+    /// The matrices that deform a skinned mesh, relative to the global transform of its node
+    pub fn skin_joint_matrices(
+        &self,
+        entity: Entity,
+        node_transform: &glm::Mat4,
+    ) -> Result<Vec<glm::Mat4>> {
+        let entry = self.ecs.entry_ref(entity)?;
+        let skin = entry.get_component::<Skin>()?;
+        let inverse_node_transform = glm::inverse(node_transform);
+        skin.joints
+            .iter()
+            .map(|joint| {
+                let mut joint_transform = glm::Mat4::identity();
+                for graph in self.scene.graphs.iter() {
+                    if let Some(index) = graph.find_node(joint.target) {
+                        joint_transform = self.global_transform(graph, index)?;
                     }
                 }
-                Ok(())
-            })?;
+                Ok(inverse_node_transform * joint_transform * joint.inverse_bind_matrix)
+            })
+            .collect()
+    }
+
+    pub fn joint_names(&self, skeleton: Entity) -> Result<Vec<String>> {
+        let entry = self.ecs.entry_ref(skeleton)?;
+        let skin = entry.get_component::<Skin>()?;
+        skin.joints
+            .iter()
+            .map(|joint| -> Result<String> {
+                let name = self
+                    .ecs
+                    .entry_ref(joint.target)?
+                    .get_component::<Name>()
+                    .map_or_else(|_| String::new(), |name| name.0.to_string());
+                Ok(name)
+            })
+            .collect()
+    }
+
+    pub fn find_joint(&self, skeleton: Entity, joint_name: &str) -> Result<Option<Entity>> {
+        let entry = self.ecs.entry_ref(skeleton)?;
+        let skin = entry.get_component::<Skin>()?;
+        let joint_names = self.joint_names(skeleton)?;
+        Ok(skin
+            .joints
+            .iter()
+            .zip(joint_names.iter())
+            .find(|(_, name)| name.as_str() == joint_name)
+            .map(|(joint, _)| joint.target))
+    }
+
+    pub fn parent_global_transform(&self, entity: Entity) -> Result<glm::Mat4> {
+        for graph in self.scene.graphs.iter() {
+            if let Some(parent_index) = graph
+                .find_node(entity)
+                .and_then(|node_index| graph.parent_of(node_index))
+            {
+                return self.global_transform(graph, parent_index);
+            }
         }
-        Ok(joint_matrices)
+        Ok(glm::Mat4::identity())
+    }
+
+    /// Moves every socketed entity to the current global transform of its joint.
+    /// This should run after animations are sampled.
+    pub fn update_sockets(&mut self) -> Result<()> {
+        let mut query = <(Entity, &Socket)>::query();
+        let sockets = query
+            .iter(&self.ecs)
+            .map(|(entity, socket)| (*entity, socket.clone()))
+            .collect::<Vec<_>>();
+        for (entity, socket) in sockets {
+            let skeleton = match self.find_stable_id(socket.skeleton) {
+                Some(skeleton) => skeleton,
+                None => {
+                    log::warn!("Socket skeleton was not found!");
+                    continue;
+                }
+            };
+            let joint = match self.find_joint(skeleton, &socket.joint)? {
+                Some(joint) => joint,
+                None => {
+                    log::warn!("Socket joint '{}' was not found in skeleton!", socket.joint);
+                    continue;
+                }
+            };
+            let joint_transform = self.entity_global_transform_matrix(joint)?;
+            let parent_transform = self.parent_global_transform(entity)?;
+            let local_transform =
+                glm::inverse(&parent_transform) * joint_transform * socket.offset.matrix();
+            let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+            *entry.get_component_mut::<Transform>()? = Transform::from(local_transform);
+            self.changes.mark_changed::<Transform>(entity);
+        }
+        Ok(())
     }
 
     pub fn add_cylinder_collider(
@@ -394,6 +710,7 @@ impl World {
             .entry(entity)
             .context("")?
             .add_component(RigidBody::new(handle));
+        self.changes.mark_changed::<RigidBody>(entity);
         Ok(())
     }
 
@@ -405,6 +722,166 @@ impl World {
         Ok(())
     }
 
+    /// Moves an entity and everything beneath it under a new parent, or to the root of its
+    /// scenegraph, keeping its global transform. The entities move to the parent's scenegraph
+    /// if it's a different one.
+    pub fn reparent(&mut self, entity: Entity, parent: Option<Entity>) -> Result<()> {
+        let find = |entity: Entity| {
+            self.scene
+                .graphs
+                .iter()
+                .enumerate()
+                .find_map(|(graph_index, graph)| Some((graph_index, graph.find_node(entity)?)))
+        };
+        let (source, node_index) = find(entity).context("The entity isn't in the scenegraph!")?;
+        let target = match parent {
+            Some(parent) => {
+                let (target, parent_index) =
+                    find(parent).context("The parent isn't in the scenegraph!")?;
+                if target == source
+                    && self.scene.graphs[source]
+                        .subtree(node_index)
+                        .contains(&parent_index)
+                {
+                    bail!("An entity can't be parented to itself or one of its descendants!");
+                }
+                target
+            }
+            None => source,
+        };
+
+        let global_transform = self.entity_global_transform_matrix(entity)?;
+        let parent_transform = match parent {
+            Some(parent) => self.entity_global_transform_matrix(parent)?,
+            None => glm::Mat4::identity(),
+        };
+
+        if target == source {
+            let graph = &mut self.scene.graphs[source];
+            graph.detach(node_index);
+            if let Some(parent_index) = parent.and_then(|parent| graph.find_node(parent)) {
+                graph.add_edge(parent_index, node_index);
+            }
+        } else {
+            // Node indices differ between the two scenegraphs, so nodes are found by entity
+            let source_graph = &self.scene.graphs[source];
+            let subtree = source_graph
+                .subtree(node_index)
+                .into_iter()
+                .map(|index| {
+                    let parent = source_graph
+                        .parent_of(index)
+                        .map(|parent_index| source_graph[parent_index]);
+                    (source_graph[index], parent)
+                })
+                .collect::<Vec<_>>();
+            for (node, _) in subtree.iter() {
+                let graph = &mut self.scene.graphs[source];
+                if let Some(index) = graph.find_node(*node) {
+                    graph.remove_node(index);
+                }
+            }
+            let graph = &mut self.scene.graphs[target];
+            for (node, node_parent) in subtree {
+                let index = graph.add_node(node);
+                let node_parent = if node == entity { parent } else { node_parent };
+                if let Some(parent_index) = node_parent.and_then(|parent| graph.find_node(parent)) {
+                    graph.add_edge(parent_index, index);
+                }
+            }
+        }
+
+        self.refresh_disabled_entities();
+        let local_transform = Transform::from(glm::inverse(&parent_transform) * global_transform);
+        self.modify_component::<Transform>(entity, |transform| *transform = local_transform)
+    }
+
+    /// Merges a glTF file into the world beneath a new root node named after the file,
+    /// so the import can be moved or removed as one. Returns the root node's entity.
+    pub fn import_gltf(&mut self, path: impl AsRef<Path>) -> Result<Entity> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Imported".to_string());
+        // The import stops at the first missing file, so every missing one is reported up front
+        if let Ok(bytes) = std::fs::read(path) {
+            for dependency in gltf_dependencies(path, &bytes).unwrap_or_default() {
+                if !dependency.exists() {
+                    log::warn!(
+                        "{} depends on {}, which is missing",
+                        path.display(),
+                        dependency.display()
+                    );
+                }
+            }
+        }
+        let root = self
+            .ecs
+            .push((Name(name), Transform::default(), StableId::generate()));
+        self.scene.default_scenegraph_mut()?.add_node(root);
+        // Read-only asset directories can't be given sidecars, which only costs the id
+        match asset_id(path) {
+            Ok(id) => self.add_component(root, id)?,
+            Err(error) => log::warn!("{} has no asset id: {}", path.display(), error),
+        }
+
+        let first_graph = self.scene.graphs.len();
+        if let Err(error) = load_gltf(path, self) {
+            self.remove_entity(root)?;
+            return Err(error.context(format!("Failed to import {}!", path.display())));
+        }
+
+        let imported_roots = self.scene.graphs[first_graph..]
+            .iter()
+            .flat_map(|graph| {
+                graph
+                    .root_nodes()
+                    .into_iter()
+                    .map(move |index| graph[index])
+            })
+            .collect::<Vec<_>>();
+        for entity in imported_roots {
+            self.reparent(entity, Some(root))?;
+        }
+
+        // Reparenting moved every imported node into the default scenegraph
+        self.scene.graphs.truncate(first_graph);
+        Ok(root)
+    }
+
+    /// Removes an entity along with its rigid body and scenegraph node
+    pub fn remove_entity(&mut self, entity: Entity) -> Result<()> {
+        if self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<RigidBody>()
+            .is_ok()
+        {
+            self.remove_rigid_body(entity)?;
+        }
+        for graph in self.scene.graphs.iter_mut() {
+            if let Some(node_index) = graph.find_node(entity) {
+                graph.remove_node(node_index);
+            }
+        }
+        // Animations keep playing after losing a target, only without the parts that drove it
+        for animation in self.animations.iter_mut() {
+            animation
+                .channels
+                .retain(|channel| channel.target != entity);
+            if animation.root_motion.as_ref().map_or(false, |root_motion| {
+                root_motion.root == entity || root_motion.target == entity
+            }) {
+                animation.root_motion = None;
+            }
+        }
+        self.changes.remove_entity(entity);
+        self.ecs.remove(entity);
+        self.disabled.remove(&entity);
+        Ok(())
+    }
+
     pub fn flatten_scenegraphs(&self) -> Vec<SceneGraphNode> {
         let mut offset = 0;
         self.scene
@@ -482,26 +959,147 @@ impl World {
         Ok(picked_entity)
     }
 
-    pub fn tick(&mut self, delta_time: f32) -> Result<()> {
-        self.physics.update(delta_time);
-        self.sync_all_rigid_bodies();
+    /// Finds the closest surface struck by a ray,
+    /// including the struck triangle when the surface is a triangle mesh collider
+    pub fn surface_hit(&self, ray: &Ray, max_distance: f32) -> Option<SurfaceHit> {
+        let (handle, intersection) = self.physics.query_pipeline.cast_ray_and_get_normal(
+            &self.physics.colliders,
+            ray,
+            max_distance,
+            true,
+            InteractionGroups::all(),
+            None,
+        )?;
+        let collider = &self.physics.colliders[handle];
+        let triangle = match (collider.shape().as_trimesh(), intersection.feature) {
+            (Some(trimesh), FeatureId::Face(index))
+                if (index as usize) < trimesh.num_triangles() =>
+            {
+                let triangle = trimesh.triangle(index);
+                let position = collider.position();
+                Some([triangle.a, triangle.b, triangle.c].map(|vertex| (position * vertex).coords))
+            }
+            _ => None,
+        };
+        Some(SurfaceHit {
+            point: ray.point_at(intersection.toi).coords,
+            normal: intersection.normal,
+            triangle,
+        })
+    }
+
+    /// Advances the world by a single fixed step, and is meant to be called from a fixed update
+    pub fn tick(&mut self) -> Result<()> {
+        let timestep = self.physics.settings.fixed_timestep();
+        self.changes.increment_tick();
+        self.steps += 1;
+        self.refresh_disabled_entities();
+        self.update_kinematic_platforms(timestep)?;
+        self.update_gravity()?;
+        self.physics.fixed_step();
+        self.simulate_cloth(timestep)?;
+        self.update_projectiles(timestep)?;
+        self.update_debris(timestep)?;
         Ok(())
     }
 
+    /// Moves rigid body transforms to where their bodies are drawn,
+    /// blended by how far the frame is between the last two fixed steps
+    pub fn interpolate_rigid_bodies(&mut self, alpha: f32) {
+        self.physics.interpolation_alpha = alpha;
+        self.sync_all_rigid_bodies();
+    }
+
+    pub fn animate(&mut self, step: f32) -> Result<()> {
+        let disabled = &self.disabled;
+        let player_animations = self.player_animations();
+        for (index, animation) in self.animations.iter_mut().enumerate() {
+            if player_animations.contains(&index) {
+                continue;
+            }
+            for sample in animation.step(step) {
+                if disabled.contains(&sample.target) {
+                    continue;
+                }
+                sample.apply(&mut self.ecs, &mut self.changes)?;
+            }
+            if let Some(root_motion) = animation.root_motion.as_ref() {
+                if root_motion.apply_to_target && !disabled.contains(&root_motion.target) {
+                    root_motion.apply(&mut self.ecs, &mut self.changes)?;
+                }
+            }
+        }
+        self.update_animation_players(step)?;
+        self.update_sockets()
+    }
+
+    pub fn optimize_animations(&mut self, settings: &AnimationOptimization) -> usize {
+        self.animations
+            .iter_mut()
+            .map(|animation| animation.optimize(settings))
+            .sum()
+    }
+
     pub fn as_bytes(&self) -> Result<Vec<u8>> {
         world_as_bytes(&self)
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<World> {
-        world_from_bytes(bytes)
+        let mut world = world_from_bytes(bytes)?;
+        world.rebuild_pools();
+        world.refresh_disabled_entities();
+        Ok(world)
+    }
+
+    /// The world in the native scene format, a versioned header followed by the world's bytes
+    pub fn as_scene_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Self::SCENE_MAGIC.to_vec();
+        bytes.extend_from_slice(&Self::SCENE_VERSION.to_le_bytes());
+        bytes.extend(self.as_bytes()?);
+        Ok(bytes)
+    }
+
+    pub fn from_scene_bytes(bytes: &[u8]) -> Result<World> {
+        let header_length = Self::SCENE_MAGIC.len() + std::mem::size_of::<u32>();
+        if bytes.len() < header_length || !bytes.starts_with(Self::SCENE_MAGIC) {
+            bail!("The data is not a phantom scene!");
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&bytes[Self::SCENE_MAGIC.len()..header_length]);
+        let version = u32::from_le_bytes(version);
+        // Older scenes are rejected rather than decoded into the current layout,
+        // which would fail partway through or load garbage
+        if version < Self::SCENE_VERSION {
+            bail!(
+                "The scene is version {}, which is older than version {} and can't be migrated! Import its source assets again.",
+                version,
+                Self::SCENE_VERSION
+            );
+        }
+        if version > Self::SCENE_VERSION {
+            bail!(
+                "The scene is version {}, which is newer than version {} and needs a newer engine!",
+                version,
+                Self::SCENE_VERSION
+            );
+        }
+        Self::from_bytes(&bytes[header_length..])
     }
 
+    /// Saves the ecs, scenegraphs, physics, materials, textures and geometry to a scene file
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
-        Ok(std::fs::write(path, &self.as_bytes()?)?)
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        Ok(std::fs::write(path, &self.as_scene_bytes()?)?)
     }
 
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
-        Self::from_bytes(&std::fs::read(path)?)
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).context(format!("Failed to read scene: {}", path.display()))?;
+        Self::from_scene_bytes(&bytes)
     }
 
     pub fn reload(&mut self, path: impl AsRef<Path>) -> Result<()> {
@@ -514,15 +1112,91 @@ impl World {
         Ok(())
     }
 
+    pub fn set_physics_material(
+        &mut self,
+        entity: Entity,
+        material: PhysicsMaterial,
+    ) -> Result<()> {
+        self.ecs
+            .entry(entity)
+            .context("Failed to find entity!")?
+            .add_component(material);
+        self.apply_collider_settings(entity)
+    }
+
+    pub fn set_collision_layer(&mut self, entity: Entity, layer: u32) -> Result<()> {
+        if layer as usize >= CollisionMatrix::MAX_LAYERS {
+            bail!("Collision layer {} is out of range!", layer);
+        }
+        self.ecs
+            .entry(entity)
+            .context("Failed to find entity!")?
+            .add_component(CollisionLayer(layer));
+        self.apply_collider_settings(entity)
+    }
+
+    /// Applies the entity's physics material and collision layer to all of its colliders
+    pub fn apply_collider_settings(&mut self, entity: Entity) -> Result<()> {
+        let entry = self.ecs.entry_ref(entity)?;
+        let rigid_body_handle = entry.get_component::<RigidBody>()?.handle;
+        let material = entry.get_component::<PhysicsMaterial>().ok().cloned();
+        let groups = entry
+            .get_component::<CollisionLayer>()
+            .ok()
+            .map(|layer| self.physics.collision_matrix.interaction_groups(layer.0));
+        let collider_handles = match self.physics.bodies.get(rigid_body_handle) {
+            Some(body) => body.colliders().to_vec(),
+            None => return Ok(()),
+        };
+        for handle in collider_handles {
+            if let Some(collider) = self.physics.colliders.get_mut(handle) {
+                if let Some(material) = material.as_ref() {
+                    material.apply(collider);
+                }
+                if let Some(groups) = groups {
+                    collider.set_collision_groups(groups);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reapplies the collision matrix to every collider, such as after it has been edited
+    pub fn apply_collision_matrix(&mut self) -> Result<()> {
+        let mut query = <(Entity, &CollisionLayer)>::query();
+        let entities = query
+            .iter(&self.ecs)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in entities {
+            self.apply_collider_settings(entity)?;
+        }
+        Ok(())
+    }
+
+    /// Enables continuous collision detection for fast moving bodies
+    pub fn set_ccd_enabled(&mut self, entity: Entity, enabled: bool) -> Result<()> {
+        let rigid_body_handle = self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<RigidBody>()?
+            .handle;
+        if let Some(body) = self.physics.bodies.get_mut(rigid_body_handle) {
+            body.enable_ccd(enabled);
+        }
+        Ok(())
+    }
+
     /// Sync the entity's physics rigid body with its transform
     pub fn sync_rigid_body_to_transform(&mut self, entity: Entity) -> Result<()> {
-        let entry = self.ecs.entry_ref(entity)?;
-        let rigid_body = entry.get_component::<RigidBody>()?;
-        let transform = entry.get_component::<Transform>()?;
-        if let Some(body) = self.physics.bodies.get_mut(rigid_body.handle) {
-            let mut position = body.position().clone();
-            position.translation.vector = transform.translation;
-            body.set_position(position, true);
+        let rigid_body_handle = self
+            .ecs
+            .entry_ref(entity)?
+            .get_component::<RigidBody>()?
+            .handle;
+        let isometry = Transform::from(self.entity_global_transform_matrix(entity)?).as_isometry();
+        if let Some(body) = self.physics.bodies.get_mut(rigid_body_handle) {
+            body.set_position(isometry, true);
         }
         Ok(())
     }
@@ -534,13 +1208,11 @@ impl World {
             .entry_ref(entity)?
             .get_component::<RigidBody>()?
             .handle;
-        let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
-        let transform = entry.get_component_mut::<Transform>()?;
-        if let Some(body) = self.physics.bodies.get(rigid_body_handle) {
-            let position = body.position();
-            transform.translation = position.translation.vector;
-            transform.rotation = *position.rotation.quaternion();
-        }
+        let position = match self.physics.bodies.get(rigid_body_handle) {
+            Some(body) => *body.position(),
+            None => return Ok(()),
+        };
+        self.set_transform_from_isometry(entity, &position)?;
         if let Some(body) = self.physics.bodies.get_mut(rigid_body_handle) {
             body.wake_up(true);
         }
@@ -549,16 +1221,43 @@ impl World {
 
     /// Sync the render transforms with the physics rigid bodies
     pub fn sync_all_rigid_bodies(&mut self) {
-        let mut query = <(&RigidBody, &mut Transform)>::query();
-        for (rigid_body, transform) in query.iter_mut(&mut self.ecs) {
-            if let Some(body) = self.physics.bodies.get(rigid_body.handle) {
-                let position = body.position();
-                transform.translation = position.translation.vector;
-                transform.rotation = *position.rotation.quaternion();
+        let disabled = &self.disabled;
+        let mut query = <(Entity, &RigidBody)>::query();
+        let positions = query
+            .iter(&self.ecs)
+            .filter_map(|(entity, rigid_body)| {
+                let body = self.physics.bodies.get(rigid_body.handle)?;
+                if body.is_sleeping() || disabled.contains(entity) {
+                    return None;
+                }
+                let position = self.physics.interpolated_position(rigid_body.handle)?;
+                Some((*entity, position))
+            })
+            .collect::<Vec<_>>();
+        for (entity, position) in positions {
+            if let Err(error) = self.set_transform_from_isometry(entity, &position) {
+                log::warn!("Failed to sync rigid body transform: {}", error);
             }
         }
     }
 
+    /// Rigid bodies are simulated in world space, while transforms are relative to their parent,
+    /// so the body's position is brought into the parent's space. The entity's scale is kept.
+    fn set_transform_from_isometry(
+        &mut self,
+        entity: Entity,
+        isometry: &Isometry<f32>,
+    ) -> Result<()> {
+        let parent = self.parent_global_transform(entity)?;
+        let local = Transform::from(glm::inverse(&parent) * isometry.to_homogeneous());
+        let mut entry = self.ecs.entry(entity).context("Failed to find entity!")?;
+        let transform = entry.get_component_mut::<Transform>()?;
+        transform.translation = local.translation;
+        transform.rotation = local.rotation;
+        self.changes.mark_changed::<Transform>(entity);
+        Ok(())
+    }
+
     pub fn entity_model_matrix(
         &self,
         entity: Entity,
@@ -567,12 +1266,10 @@ impl World {
         let entry = self.ecs.entry_ref(entity)?;
         let model = match entry.get_component::<RigidBody>() {
             Ok(rigid_body) => {
-                let body = self
+                let position = self
                     .physics
-                    .bodies
-                    .get(rigid_body.handle)
+                    .interpolated_position(rigid_body.handle)
                     .context("Failed to acquire physics body to render!")?;
-                let position = body.position();
                 let translation = position.translation.vector;
                 let rotation = *position.rotation.quaternion();
                 let scale = Transform::from(global_transform).scale;
@@ -601,6 +1298,23 @@ impl Viewport {
     pub fn as_glm_vec(&self) -> glm::Vec4 {
         glm::vec4(self.x, self.y, self.width, self.height)
     }
+
+    /// Whether a position, measured from the top left corner of the window, is inside the viewport
+    pub fn contains(&self, position: &glm::Vec2) -> bool {
+        position.x >= self.x
+            && position.y >= self.y
+            && position.x < self.x + self.width
+            && position.y < self.y + self.height
+    }
+
+    /// Converts a position measured from the top left corner of the window to one where
+    /// (0, 0) is the top left corner of the viewport and (1, 1) is the bottom right
+    pub fn normalize(&self, position: &glm::Vec2) -> glm::Vec2 {
+        glm::vec2(
+            (position.x - self.x) / self.width.max(1.0),
+            (position.y - self.y) / self.height.max(1.0),
+        )
+    }
 }
 
 pub struct MouseRayConfiguration {
@@ -610,6 +1324,47 @@ pub struct MouseRayConfiguration {
     pub mouse_position: glm::Vec2,
 }
 
+pub struct SurfaceHit {
+    pub point: glm::Vec3,
+    pub normal: glm::Vec3,
+
+    /// The struck triangle in world space, for triangle mesh colliders
+    pub triangle: Option<[glm::Vec3; 3]>,
+}
+
+impl SurfaceHit {
+    /// The corner of the struck triangle closest to the hit point
+    pub fn nearest_vertex(&self) -> Option<glm::Vec3> {
+        self.triangle?.iter().copied().min_by(|a, b| {
+            glm::distance2(a, &self.point)
+                .partial_cmp(&glm::distance2(b, &self.point))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// The point on the struck triangle's edges closest to the hit point
+    pub fn nearest_edge_point(&self) -> Option<glm::Vec3> {
+        let [a, b, c] = self.triangle?;
+        [(a, b), (b, c), (c, a)]
+            .iter()
+            .map(|(start, end)| {
+                let edge = end - start;
+                let length = glm::length2(&edge);
+                let t = if length > 0.0 {
+                    (glm::dot(&(self.point - start), &edge) / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                start + edge * t
+            })
+            .min_by(|a, b| {
+                glm::distance2(a, &self.point)
+                    .partial_cmp(&glm::distance2(b, &self.point))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Scene {
@@ -678,12 +1433,28 @@ pub struct Joint {
     pub inverse_bind_matrix: glm::Mat4,
 }
 
+/// Attaches an entity to a named joint of a skinned entity,
+/// such as a weapon held in a hand or a hat placed on a head
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct Socket {
+    /// Assigned with `World::stable_id`, so the socket survives the skeleton being moved
+    pub skeleton: StableId,
+    pub joint: String,
+    pub offset: Transform,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct MeshRender {
     pub name: String,
 }
 
+/// The weight of each morph target of the entity's mesh, set by animations and lip sync
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "phantom_dependencies::serde")]
+pub struct MorphWeights(pub Vec<f32>);
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Mesh {
@@ -701,6 +1472,14 @@ impl Mesh {
             .for_each(|primitive_bounding_box| bounding_box.fit_box(primitive_bounding_box));
         bounding_box
     }
+
+    pub fn number_of_morph_targets(&self) -> usize {
+        self.primitives
+            .iter()
+            .map(|primitive| primitive.morph_targets.len())
+            .max()
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -770,6 +1549,27 @@ pub struct MorphTarget {
     pub tangents: Vec<glm::Vec4>,
 }
 
+impl Primitive {
+    /// The primitive's vertices displaced by each of its morph targets, scaled by that target's weight
+    pub fn morphed_vertices(&self, vertices: &[Vertex], weights: &[f32]) -> Vec<Vertex> {
+        let mut morphed = vertices
+            .get(self.first_vertex..self.first_vertex + self.number_of_vertices)
+            .map_or_else(Vec::new, <[Vertex]>::to_vec);
+        for (morph_target, weight) in self.morph_targets.iter().zip(weights.iter()) {
+            if *weight == 0.0 {
+                continue;
+            }
+            for (vertex, displacement) in morphed.iter_mut().zip(morph_target.positions.iter()) {
+                vertex.position += displacement.xyz() * *weight;
+            }
+            for (vertex, displacement) in morphed.iter_mut().zip(morph_target.normals.iter()) {
+                vertex.normal += displacement.xyz() * *weight;
+            }
+        }
+        morphed
+    }
+}
+
 impl MorphTarget {
     pub fn total_length(&self) -> usize {
         self.positions.len() + self.normals.len() + self.tangents.len()
@@ -791,6 +1591,7 @@ impl Geometry {
     }
 }
 
+#[repr(C)]
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct Vertex {
@@ -817,6 +1618,10 @@ impl Default for Vertex {
     }
 }
 
+unsafe impl bytemuck::Zeroable for Vertex {}
+
+unsafe impl bytemuck::Pod for Vertex {}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "phantom_dependencies::serde")]
 pub struct SdfFont {
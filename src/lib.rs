@@ -2,6 +2,10 @@ pub mod app {
     pub use phantom_app::*;
 }
 
+pub mod audio {
+    pub use phantom_audio::*;
+}
+
 pub mod dependencies {
     pub use phantom_dependencies::*;
 }
@@ -10,6 +14,14 @@ pub mod gui {
     pub use phantom_gui::*;
 }
 
+pub mod net {
+    pub use phantom_net::*;
+}
+
+pub mod platform {
+    pub use phantom_platform::*;
+}
+
 pub mod render {
     pub use phantom_render::*;
 }